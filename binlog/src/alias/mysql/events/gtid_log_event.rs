@@ -128,6 +128,10 @@ impl GtidLogEvent {
     pub fn get_gtid_str(&self) -> String {
         format!("{}", self.gtid.to_string())
     }
+
+    pub fn get_header(&self) -> &Header {
+        &self.header
+    }
 }
 
 impl LogEvent for GtidLogEvent {
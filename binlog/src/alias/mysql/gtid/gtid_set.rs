@@ -148,6 +148,14 @@ impl GtidSet {
     pub fn get(&self, sid: &str) -> Option<&UuidSet> {
         self.uuid_sets.get(sid)
     }
+
+    /// gtid 是否包含在集合中。 按 source_id 的字节比较, 不区分 uuid 字符串的大小写
+    pub fn contains_gtid(&self, gtid: &Gtid) -> bool {
+        self.uuid_sets.values()
+            .filter(|s| s.get_source_id().data == gtid.source_id.data)
+            .flat_map(|s| s.intervals().iter())
+            .any(|i| i.get_start() <= gtid.transaction_id && gtid.transaction_id <= i.get_end())
+    }
 }
 
 #[cfg(test)]
@@ -189,6 +197,16 @@ mod test {
         assert_eq!(gtid.to_string(), "726757ad-4455-11e8-ae04-0242ac110002:1-3,726757ad-4455-11e8-ae04-0242ac110003:4,726757ad-4455-11e8-ae04-0242ac110886:1-3:7-9");
     }
 
+    #[test]
+    fn test_contains_gtid() {
+        let gtid_set = GtidSet::parse(format!("{}:1-3:7", SERVER_UUID1.to_uppercase())).unwrap();
+
+        assert!(gtid_set.contains_gtid(&Gtid::new(create_uuid1(), 2)));
+        assert!(gtid_set.contains_gtid(&Gtid::new(create_uuid1(), 7)));
+        assert!(!gtid_set.contains_gtid(&Gtid::new(create_uuid1(), 5)));
+        assert!(!gtid_set.contains_gtid(&Gtid::new(create_uuid2(), 1)));
+    }
+
     #[test]
     fn parse_empty_string_returns_empty_gtid_set() {
         let empty = String::from("");
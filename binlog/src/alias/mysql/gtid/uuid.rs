@@ -1,6 +1,7 @@
 use std::fmt;
 use serde::Serialize;
 use common::err::CResult;
+use common::uuid::{format_server_uuid, parse_server_uuid};
 
 /// Represents Uuid with little-endian bytes order unlike big-endian Guid.
 #[derive(Debug, Serialize, PartialEq, Eq, Clone)]
@@ -11,23 +12,12 @@ pub struct Uuid {
 
 impl Uuid {
     pub fn new(data: [u8; 16]) -> Self {
-        let mut uuid = hex::encode(data);
-        uuid.insert(20, '-');
-        uuid.insert(16, '-');
-        uuid.insert(12, '-');
-        uuid.insert(8, '-');
-        Self { data, uuid }
+        Self { data, uuid: format_server_uuid(&data) }
     }
 
     /// Parses Uuid from string representation.
     pub fn parse(uuid: String) -> CResult<Self> {
-        let hex = uuid.replace("-", "");
-        let vec = hex::decode(hex)?;
-
-        let mut data = [0u8; 16];
-        (0..16).for_each(|i| data[i] = vec[i]);
-
-        Ok(Self { data, uuid })
+        Ok(Self::new(parse_server_uuid(&uuid)?))
     }
 }
 
@@ -0,0 +1,56 @@
+use common::binlog::{EVENT_HEADER_SIZE, FIRST_EVENT_POSITION};
+
+use crate::events::checksum_type::{BINLOG_CHECKSUM_ALG_CRC32, ST_COMMON_PAYLOAD_CHECKSUM_LEN};
+use crate::events::protocol::v4::start_v3_event::{ST_SERVER_VER_LEN, ST_SERVER_VER_OFFSET};
+
+/// binlog 文件开头的 magic: 0xfe 'b' 'i' 'n'
+pub const BINLOG_MAGIC: [u8; FIRST_EVENT_POSITION] = [0xfe, 0x62, 0x69, 0x6e];
+
+/// FormatDescriptionEvent 中 server_version 的偏移(相对 payload)与长度
+pub const SERVER_VERSION_OFFSET: usize = ST_SERVER_VER_OFFSET as usize;
+pub const SERVER_VERSION_LEN: usize = ST_SERVER_VER_LEN as usize;
+
+/// checksum 长度
+pub const CHECKSUM_LEN: usize = ST_COMMON_PAYLOAD_CHECKSUM_LEN as usize;
+
+/// 5.6.1 起 FormatDescriptionEvent 带有 checksum_alg
+const CHECKSUM_VERSION: (u32, u32, u32) = (5, 6, 1);
+
+/// FormatDescriptionEvent(完整的事件字节)的 checksum_alg 是否为 CRC32
+pub fn checksum_enabled(format_description: &[u8]) -> bool {
+    has_checksum_alg(format_description)
+        && format_description[format_description.len() - CHECKSUM_LEN - 1] == BINLOG_CHECKSUM_ALG_CRC32
+}
+
+/// server_version >= 5.6.1 时 FormatDescriptionEvent 末尾有 checksum_alg(1) + checksum(4)
+pub fn has_checksum_alg(format_description: &[u8]) -> bool {
+    let start = EVENT_HEADER_SIZE + SERVER_VERSION_OFFSET;
+    let end = start + SERVER_VERSION_LEN;
+    if format_description.len() < end + CHECKSUM_LEN + 1 {
+        return false;
+    }
+
+    let version = String::from_utf8_lossy(&format_description[start..end]);
+    parse_version(version.trim_end_matches('\0')) >= CHECKSUM_VERSION
+}
+
+/// `8.0.32-log` -> (8, 0, 32), 无法解析的部分为 0
+pub fn parse_version(version: &str) -> (u32, u32, u32) {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit())
+        .take(3)
+        .map(|p| p.parse::<u32>().unwrap_or(0));
+
+    (parts.next().unwrap_or(0), parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binlog_file::parse_version;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("8.0.32-log"), (8, 0, 32));
+        assert_eq!(parse_version("5.5"), (5, 5, 0));
+        assert!(parse_version("5.6.1") >= (5, 6, 1));
+    }
+}
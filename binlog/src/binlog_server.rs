@@ -3,7 +3,7 @@ use std::sync::{Arc, Mutex};
 use lazy_static::lazy_static;
 use common::binlog::column::column_type::SrcColumnType;
 use common::err::decode_error::ReError;
-use common::server::Server;
+use common::server::{ComponentHealth, HealthStatus, Server};
 use crate::ast::query_parser::TableInfoBuilder;
 use crate::events::protocol::table_map_event::TableMapEvent;
 
@@ -27,7 +27,7 @@ lazy_static! {
 
 #[derive(Debug)]
 pub struct BinlogServer {
-
+    status: HealthStatus,
 }
 
 unsafe impl Send for BinlogServer {}
@@ -36,21 +36,43 @@ unsafe impl Send for BinlogServer {}
 impl Server for BinlogServer {
     async fn start(&mut self) -> Result<(), ReError> {
         println!("BinlogServer start");
+        self.status = HealthStatus::Running;
 
         Ok(())
     }
 
     async fn shutdown(&mut self, graceful: bool) -> Result<(), ReError> {
         println!("BinlogServer shutdown");
+        self.status = HealthStatus::Stopped;
+
+        Ok(())
+    }
+
+    async fn pause(&mut self) -> Result<(), ReError> {
+        if self.status == HealthStatus::Running {
+            self.status = HealthStatus::Paused;
+        }
+
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> Result<(), ReError> {
+        if self.status == HealthStatus::Paused {
+            self.status = HealthStatus::Running;
+        }
 
         Ok(())
     }
+
+    fn health(&self) -> ComponentHealth {
+        ComponentHealth::new("BinlogServer", self.status)
+    }
 }
 
 impl BinlogServer {
     pub fn new() -> Self {
         BinlogServer {
-
+            status: HealthStatus::Starting,
         }
     }
 
@@ -8,7 +8,7 @@ use crate::decoder::binlog_decoder::{BinlogReader};
 use crate::decoder::event_decoder::{LogEventDecoder};
 use crate::events::binlog_event::BinlogEvent;
 use crate::events::event_raw::EventRaw;
-use crate::events::event_header::Header;
+use crate::events::event_header::{Header, HEADER_LEN};
 use crate::events::log_context::{ILogContext, LogContext, LogContextRef};
 use crate::events::log_position::LogFilePosition;
 
@@ -102,6 +102,38 @@ impl BytesBinlogReader {
     pub fn get_source_bytes(&self) -> Vec<u8> {
         self.source_bytes.clone()
     }
+
+    /// 流式读取: chunk 接在上次剩余的字节之后解析, 不完整的事件留到下一次。 返回本次解析出的完整事件及其 header
+    pub fn read_chunk(&mut self, chunk: &[u8]) -> Result<Vec<(Header, BinlogEvent)>, ReError> {
+        let mut bytes = std::mem::take(&mut self.source_bytes);
+        bytes.extend_from_slice(chunk);
+
+        if !self.skip_magic_buffer {
+            if bytes.len() < HEADER_LEN as usize {
+                self.source_bytes = bytes;
+                return Ok(vec![]);
+            }
+            let (i, _) = Header::check_start(&bytes)
+                .map_err(|_| ReError::String(String::from("invalid binlog magic number")))?;
+            bytes = i.to_vec();
+            self.skip_magic_buffer = true;
+        }
+
+        let (remaining_bytes, event_raws) = EventRaw::steam_to_event_raw(&bytes, self.context.clone())?;
+        self.source_bytes = remaining_bytes;
+
+        let mut events = Vec::with_capacity(event_raws.len());
+        for event_raw in event_raws {
+            let header = event_raw.get_header();
+            let event = self.decoder.event_parse(event_raw.get_payload(), header.clone(), self.context.clone())?;
+            self.context.borrow_mut().add_log_stat(event.len() as usize);
+
+            let header = header.borrow().clone();
+            events.push((header, event));
+        }
+
+        Ok(events)
+    }
 }
 
 
@@ -11,6 +11,7 @@ use common::err::decode_error::ReError;
 use crate::alias::mysql::events::gtid_log_event::GtidLogEvent;
 use crate::alias::mysql::gtid::gtid_set::GtidSet;
 use crate::b_type::LogEventType::{FORMAT_DESCRIPTION_EVENT, ROTATE_EVENT};
+use crate::binlog_file::BINLOG_MAGIC;
 use crate::events::event_header_flag::EventFlag;
 use crate::events::event_raw::HeaderRef;
 use crate::events::log_context::{ILogContext, LogContextRef};
@@ -196,7 +197,7 @@ impl Header {
 
     /// binlog文件以一个值为0Xfe62696e的魔数开头，这个魔数对应0xfe 'b''i''n'。
     pub fn check_start(i: &[u8]) -> IResult<&[u8], &[u8]> {
-        tag([254, 98, 105, 110])(i)
+        tag(BINLOG_MAGIC)(i)
     }
 
     /// 解析 header
@@ -34,6 +34,9 @@ pub trait ILogContext {
     fn get_position_offset(&self) -> u64;
     /// 更新  LogFilePosition 的 pos
     fn update_position_offset(&mut self, pos: u64);
+    /// 最近一个事件 header 中的时间戳(秒)
+    fn get_event_timestamp(&self) -> u32;
+    fn set_event_timestamp(&mut self, when: u32);
 
     /// 记录已经读取一个 event。
     fn add_log_stat(&mut self, len: usize);
@@ -89,6 +92,9 @@ pub struct LogContext {
 
     /// save current gtid log event
     gtid_log_event: Option<GtidLogEvent>,
+
+    /// 最近一个事件的时间戳
+    event_timestamp: u32,
 }
 
 impl Default for LogContext {
@@ -101,6 +107,7 @@ impl Default for LogContext {
             map_of_table: Arc::new(DashMap::<u64, TableMapEvent>::new()),
             gtid_set: None,
             gtid_log_event: None,
+            event_timestamp: 0,
         }
     }
 }
@@ -127,6 +134,7 @@ impl ILogContext for LogContext {
             map_of_table: Arc::new(DashMap::<u64, TableMapEvent>::new()),
             gtid_set,
             gtid_log_event: None,
+            event_timestamp: 0,
         }
     }
 
@@ -158,6 +166,14 @@ impl ILogContext for LogContext {
         self.log_position.set_position(pos);
     }
 
+    fn get_event_timestamp(&self) -> u32 {
+        self.event_timestamp
+    }
+
+    fn set_event_timestamp(&mut self, when: u32) {
+        self.event_timestamp = when;
+    }
+
 
     /////////////////////////////////////////////
     //                  log_stat               //
@@ -547,6 +547,14 @@ impl QueryEvent {
     pub fn has_table_info(&self) -> bool {
         self.table_info.is_some()
     }
+
+    /// 执行语句时会话的 time_zone, 与全局 time_zone 相同时 MySQL 不记录
+    pub fn get_time_zone(&self) -> Option<&str> {
+        self.status_vars.iter().find_map(|v| match v {
+            QueryStatusVar::Q_TIME_ZONE_CODE(tz) => Some(tz.as_str()),
+            _ => None,
+        })
+    }
 }
 
 impl LogEvent for QueryEvent {
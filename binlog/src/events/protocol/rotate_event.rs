@@ -37,6 +37,10 @@ impl RotateEvent {
         self.binlog_position.clone()
     }
 
+    pub fn get_header(&self) -> &Header {
+        &self.header
+    }
+
     pub fn new(header: Header, binlog_filename: String, binlog_position: u64) -> Self {
         RotateEvent {
             header,
@@ -17,6 +17,7 @@ use crate::decoder::table_cache_manager::TableCacheManager;
 use crate::events::BuildType;
 use crate::events::declare::log_event::LogEvent;
 use crate::events::event_raw::HeaderRef;
+use crate::row::actual_string_type::get_actual_string_type;
 use crate::row::decimal::get_meta;
 use crate::utils::{read_bitmap_little_endian_bits, read_len_enc_num, read_string};
 
@@ -431,6 +432,24 @@ impl ColumnInfo {
         self.name = name;
     }
 
+    /// 实际的列类型: enum/set 列在 TABLE_MAP 中的类型为 String, 实际类型记录在 meta 中
+    pub fn get_real_c_type(&self) -> Option<SrcColumnType> {
+        match (self.c_type, self.b_type) {
+            (Some(SrcColumnType::String), Some(b_type)) => {
+                let mut column_type = b_type;
+                let mut meta = self.meta;
+                get_actual_string_type(&mut column_type, &mut meta);
+                SrcColumnType::try_from(column_type).ok()
+            }
+            (c_type, _) => c_type,
+        }
+    }
+
+    /// enum/set 的取值名称, binlog_row_metadata=FULL 时才有
+    pub fn get_set_enum_values(&self) -> &[String] {
+        &self.set_enum_values
+    }
+
     pub fn set_enum_values(&mut self, set_enum_values: Vec<String>) {
         self.set_enum_values = set_enum_values;
     }
@@ -439,6 +458,14 @@ impl ColumnInfo {
         self.unsigned = unsigned;
     }
 
+    pub fn is_unsigned(&self) -> bool {
+        self.unsigned
+    }
+
+    pub fn is_pk(&self) -> bool {
+        self.pk
+    }
+
     pub fn set_pk(&mut self, pk: bool) {
         self.pk = pk;
     }
@@ -459,6 +486,10 @@ impl ColumnInfo {
         self.meta = meta;
     }
 
+    pub fn is_nullable(&self) -> bool {
+        self.nullable > 0
+    }
+
     pub fn set_nullable(&mut self, nullable: u8) {
         self.nullable = nullable;
     }
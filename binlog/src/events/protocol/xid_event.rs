@@ -28,6 +28,10 @@ impl XidLogEvent {
     pub fn get_xid(&self) -> u64 {
         self.xid
     }
+
+    pub fn get_header(&self) -> &Header {
+        &self.header
+    }
 }
 
 impl LogEvent for XidLogEvent {
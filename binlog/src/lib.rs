@@ -9,6 +9,8 @@ pub mod events;
 // pub mod connection;
 // pub mod cli;
 pub mod decoder;
+pub mod writer;
+pub mod binlog_file;
 pub mod metadata;
 pub mod column;
 pub mod row;
@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, HashMap};
+
+use sha2::{Digest, Sha256};
+
+use common::binlog::column::column_value::SrcColumnValue;
+use common::err::decode_error::ReError;
+
+use crate::events::binlog_event::BinlogEvent;
+use crate::row::row_data::RowData;
+
+/// redact 替换后的值
+const REDACTED: &str = "***";
+
+/// 列的脱敏方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskStrategy {
+    /// 置为 NULL
+    Null,
+    /// 替换为 `***`
+    Redact,
+    /// sha256 的十六进制值, 相同的原值得到相同的结果, 可用于关联
+    Hash,
+    /// 保留前 prefix 个与后 suffix 个字符, 其余替换为 `*`, 如 `partial(3,4)`
+    Partial { prefix: usize, suffix: usize },
+}
+
+impl TryFrom<&str> for MaskStrategy {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = value.trim().to_ascii_lowercase();
+        match value.as_str() {
+            "null" => return Ok(MaskStrategy::Null),
+            "redact" => return Ok(MaskStrategy::Redact),
+            "hash" => return Ok(MaskStrategy::Hash),
+            _ => {},
+        }
+
+        let args = value.strip_prefix("partial(").and_then(|v| v.strip_suffix(')'))
+            .and_then(|v| v.split_once(','))
+            .and_then(|(p, s)| Some((p.trim().parse().ok()?, s.trim().parse().ok()?)));
+        match args {
+            Some((prefix, suffix)) => Ok(MaskStrategy::Partial { prefix, suffix }),
+            None => Err(ReError::String(format!("Unsupported masking strategy: {}", value))),
+        }
+    }
+}
+
+impl MaskStrategy {
+    pub fn mask(&self, value: &Option<SrcColumnValue>) -> Option<SrcColumnValue> {
+        let value = match value {
+            None => return None,
+            Some(v) => v,
+        };
+
+        match self {
+            MaskStrategy::Null => None,
+            MaskStrategy::Redact => Some(SrcColumnValue::String(REDACTED.to_string())),
+            MaskStrategy::Hash => {
+                let digest = match value {
+                    SrcColumnValue::Blob(b) => Sha256::digest(b),
+                    v => Sha256::digest(value_text(v).as_bytes()),
+                };
+                Some(SrcColumnValue::String(hex::encode(digest)))
+            },
+            MaskStrategy::Partial { prefix, suffix } => {
+                let chars: Vec<char> = value_text(value).chars().collect();
+                let masked: String = chars.iter().enumerate()
+                    .map(|(idx, c)| if idx < *prefix || idx + suffix >= chars.len() { *c } else { '*' })
+                    .collect();
+                Some(SrcColumnValue::String(masked))
+            },
+        }
+    }
+}
+
+/// 值的文本形式, 用于 hash 与 partial
+fn value_text(value: &SrcColumnValue) -> String {
+    match value {
+        SrcColumnValue::TinyInt(v) => v.to_string(),
+        SrcColumnValue::SmallInt(v) => v.to_string(),
+        SrcColumnValue::MediumInt(v) => v.to_string(),
+        SrcColumnValue::Int(v) => v.to_string(),
+        SrcColumnValue::BigInt(v) => v.to_string(),
+        SrcColumnValue::Float(v) => v.to_string(),
+        SrcColumnValue::Double(v) => v.to_string(),
+        SrcColumnValue::Decimal(v) => v.clone(),
+        SrcColumnValue::String(v) => v.clone(),
+        SrcColumnValue::Blob(v) => String::from_utf8_lossy(v).to_string(),
+        SrcColumnValue::Bit(v) => v.iter().map(|b| if *b { '1' } else { '0' }).collect(),
+        SrcColumnValue::Enum(v) => v.to_string(),
+        SrcColumnValue::Set(v) => v.to_string(),
+        SrcColumnValue::Year(v) => v.to_string(),
+        SrcColumnValue::Date(d) => format!("{:04}-{:02}-{:02}", d.year, d.month, d.day),
+        SrcColumnValue::Time(t) => format!("{:02}:{:02}:{:02}", t.hour, t.minute, t.second),
+        SrcColumnValue::DateTime(d) => format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                                               d.year, d.month, d.day, d.hour, d.minute, d.second),
+        SrcColumnValue::Timestamp(v) => v.to_string(),
+    }
+}
+
+/// 按 `db.table.column` 或 `table.column`(任意库) 对行事件中的列脱敏。
+///
+/// 列名来自 TableMapEvent 的元数据(binlog_row_metadata=FULL), 没有列名时使用 `@1`、`@2` 按位置匹配
+#[derive(Debug, Default)]
+pub struct MaskingEngine {
+    /// (db, table, column) -> strategy, db 为 None 表示任意库
+    rules: Vec<(Option<String>, String, String, MaskStrategy)>,
+
+    /// table_id -> (列序号, strategy)
+    tables: HashMap<u64, Vec<(usize, MaskStrategy)>>,
+}
+
+impl MaskingEngine {
+    /// rules: 列 -> 脱敏方式, 对应配置中的 [masking]
+    pub fn new(rules: &BTreeMap<String, String>) -> Result<Self, ReError> {
+        let mut engine = MaskingEngine::default();
+        for (column, strategy) in rules {
+            let strategy = MaskStrategy::try_from(strategy.as_str())?;
+            let parts: Vec<&str> = column.split('.').collect();
+            let rule = match parts.as_slice() {
+                [table, column] => (None, table.to_string(), column.to_string(), strategy),
+                [db, table, column] => (Some(db.to_string()), table.to_string(), column.to_string(), strategy),
+                _ => return Err(ReError::String(format!("Invalid masking column {}, expect [db.]table.column", column))),
+            };
+            engine.rules.push(rule);
+        }
+
+        Ok(engine)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 遇到 TableMapEvent 时记录需要脱敏的列, 遇到行事件时替换对应的值
+    pub fn apply(&mut self, event: &mut BinlogEvent) {
+        match event {
+            BinlogEvent::TableMap(e) => {
+                let infos = e.get_column_infos();
+                let columns_number = (e.get_columns_number() as usize).max(infos.len());
+                let columns: Vec<String> = (0..columns_number)
+                    .map(|idx| infos.get(idx).map(|i| i.get_name()).filter(|n| !n.is_empty())
+                        .unwrap_or_else(|| format!("@{}", idx + 1)))
+                    .collect();
+                self.bind(e.table_id, &e.get_database_name(), &e.get_table_name(), &columns);
+            },
+            BinlogEvent::WriteRows(e) => {
+                for row in e.rows.iter_mut() {
+                    self.mask_row(e.table_id, row);
+                }
+            },
+            BinlogEvent::UpdateRows(e) => {
+                for row in e.rows.iter_mut() {
+                    self.mask_row(e.table_id, &mut row.before_update);
+                    self.mask_row(e.table_id, &mut row.after_update);
+                }
+            },
+            BinlogEvent::DeleteRows(e) => {
+                for row in e.rows.iter_mut() {
+                    self.mask_row(e.table_id, row);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn bind(&mut self, table_id: u64, db: &str, table: &str, columns: &[String]) {
+        let masked: Vec<(usize, MaskStrategy)> = columns.iter().enumerate()
+            .filter_map(|(idx, column)| {
+                self.rules.iter()
+                    .find(|(d, t, c, _)| d.as_ref().map_or(true, |d| d == db) && t == table && c == column)
+                    .map(|(_, _, _, s)| (idx, s.clone()))
+            })
+            .collect();
+
+        if masked.is_empty() {
+            self.tables.remove(&table_id);
+        } else {
+            self.tables.insert(table_id, masked);
+        }
+    }
+
+    fn mask_row(&self, table_id: u64, row: &mut RowData) {
+        if let Some(masked) = self.tables.get(&table_id) {
+            for (idx, strategy) in masked {
+                if let Some(cell) = row.cells.get_mut(*idx) {
+                    *cell = strategy.mask(cell);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use common::binlog::column::column_value::SrcColumnValue;
+
+    use crate::row::masking::{MaskStrategy, MaskingEngine};
+    use crate::row::row_data::RowData;
+
+    #[test]
+    fn test_strategy() {
+        assert_eq!(MaskStrategy::try_from("Partial(3, 4)").unwrap(), MaskStrategy::Partial { prefix: 3, suffix: 4 });
+        assert!(MaskStrategy::try_from("shuffle").is_err());
+
+        let phone = Some(SrcColumnValue::String(String::from("13812345678")));
+        assert_eq!(MaskStrategy::Partial { prefix: 3, suffix: 4 }.mask(&phone),
+                   Some(SrcColumnValue::String(String::from("138****5678"))));
+        assert_eq!(MaskStrategy::Null.mask(&phone), None);
+        assert_eq!(MaskStrategy::Hash.mask(&None), None);
+        assert_eq!(MaskStrategy::Hash.mask(&phone), MaskStrategy::Hash.mask(&phone));
+    }
+
+    #[test]
+    fn test_engine() {
+        let mut rules = BTreeMap::new();
+        rules.insert(String::from("db1.users.email"), String::from("redact"));
+        rules.insert(String::from("users.@3"), String::from("null"));
+        let mut engine = MaskingEngine::new(&rules).unwrap();
+
+        let columns = vec![String::from("id"), String::from("email"), String::from("@3")];
+        engine.bind(1, "db1", "users", &columns);
+        engine.bind(2, "db2", "users", &columns);
+
+        let cells = vec![
+            Some(SrcColumnValue::Int(1)),
+            Some(SrcColumnValue::String(String::from("a@b.com"))),
+            Some(SrcColumnValue::Int(18)),
+        ];
+        let mut row = RowData::new_with_cells(cells.clone());
+        engine.mask_row(1, &mut row);
+        assert_eq!(row.cells, vec![Some(SrcColumnValue::Int(1)), Some(SrcColumnValue::String(String::from("***"))), None]);
+
+        // db2 只匹配不带库名的规则
+        let mut row = RowData::new_with_cells(cells);
+        engine.mask_row(2, &mut row);
+        assert_eq!(row.cells[1], Some(SrcColumnValue::String(String::from("a@b.com"))));
+        assert_eq!(row.cells[2], None);
+    }
+}
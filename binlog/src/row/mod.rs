@@ -3,3 +3,4 @@ pub mod row_parser;
 pub mod row_data;
 pub mod actual_string_type;
 pub mod decimal;
+pub mod masking;
@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use common::binlog::{EVENT_HEADER_SIZE, FIRST_EVENT_POSITION};
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::b_type::LogEventType;
+use crate::binlog_file::{checksum_enabled, BINLOG_MAGIC, CHECKSUM_LEN};
+
+/// header 中 log_pos 与 flags 的偏移
+const LOG_POS_OFFSET: usize = 13;
+const FLAGS_OFFSET: usize = 17;
+
+/// LOG_EVENT_BINLOG_IN_USE_F, 文件未正常关闭
+const BINLOG_IN_USE_FLAG: u16 = 0x1;
+
+/// 以原始字节写出 binlog 文件: magic + FormatDescriptionEvent + 事件。
+/// 写入时按新文件中的位置重写 header 中的 log_pos, 开启 checksum 时重新计算 CRC32
+#[derive(Debug)]
+pub struct FileBinlogWriter {
+    path: PathBuf,
+
+    writer: BufWriter<File>,
+
+    /// 下一个事件的起始位置
+    position: u64,
+
+    /// FormatDescriptionEvent 的 checksum_alg 为 CRC32
+    checksum: bool,
+
+    server_id: u32,
+}
+
+impl FileBinlogWriter {
+    /// 创建文件并写入 magic 与 format_description(源文件中 FormatDescriptionEvent 的完整字节)
+    pub fn create(path: &Path, format_description: &[u8]) -> CResult<Self> {
+        if format_description.len() < EVENT_HEADER_SIZE
+            || format_description[4] != LogEventType::FORMAT_DESCRIPTION_EVENT as u8 {
+            return Err(ReError::String(String::from("invalid FORMAT_DESCRIPTION_EVENT")));
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&BINLOG_MAGIC)?;
+
+        let server_id = u32::from_le_bytes([format_description[5], format_description[6],
+                                            format_description[7], format_description[8]]);
+        let mut binlog_writer = FileBinlogWriter {
+            path: path.to_path_buf(),
+            writer,
+            position: FIRST_EVENT_POSITION as u64,
+            checksum: false,
+            server_id,
+        };
+
+        let mut event = format_description.to_vec();
+        // 新文件写完后正常关闭
+        let flags = u16::from_le_bytes([event[FLAGS_OFFSET], event[FLAGS_OFFSET + 1]]) & !BINLOG_IN_USE_FLAG;
+        event[FLAGS_OFFSET..FLAGS_OFFSET + 2].copy_from_slice(&flags.to_le_bytes());
+        // checksum 关闭时 FormatDescriptionEvent 的 checksum 字段为 0, 保持不变
+        let crc_enabled = Self::checksum_of(format_description);
+        binlog_writer.write(event, crc_enabled)?;
+        binlog_writer.checksum = crc_enabled;
+
+        Ok(binlog_writer)
+    }
+
+    /// FormatDescriptionEvent 的 checksum_alg 是否为 CRC32
+    pub fn checksum_of(format_description: &[u8]) -> bool {
+        checksum_enabled(format_description)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 文件当前大小, 即下一个事件的起始位置
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn is_checksum(&self) -> bool {
+        self.checksum
+    }
+
+    /// 写入一个完整的事件(header + payload + checksum)
+    pub fn write_event(&mut self, event: &[u8]) -> CResult<()> {
+        let min_size = EVENT_HEADER_SIZE + if self.checksum { CHECKSUM_LEN } else { 0 };
+        if event.len() < min_size {
+            return Err(ReError::String(format!("invalid event size {}", event.len())));
+        }
+
+        self.write(event.to_vec(), self.checksum)
+    }
+
+    /// 写入指向 next_file 的 RotateEvent, 之后不应再写入事件
+    pub fn write_rotate(&mut self, next_file: &str) -> CResult<()> {
+        let size = EVENT_HEADER_SIZE + 8 + next_file.len() + if self.checksum { CHECKSUM_LEN } else { 0 };
+
+        let mut event = Vec::with_capacity(size);
+        event.extend_from_slice(&0u32.to_le_bytes());
+        event.push(LogEventType::ROTATE_EVENT as u8);
+        event.extend_from_slice(&self.server_id.to_le_bytes());
+        event.extend_from_slice(&(size as u32).to_le_bytes());
+        // log_pos 与 checksum 由 write 填写
+        event.extend_from_slice(&0u32.to_le_bytes());
+        event.extend_from_slice(&0u16.to_le_bytes());
+        event.extend_from_slice(&(FIRST_EVENT_POSITION as u64).to_le_bytes());
+        event.extend_from_slice(next_file.as_bytes());
+        if self.checksum {
+            event.extend_from_slice(&[0u8; CHECKSUM_LEN]);
+        }
+
+        self.write(event, self.checksum)
+    }
+
+    pub fn flush(&mut self) -> CResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write(&mut self, mut event: Vec<u8>, checksum: bool) -> CResult<()> {
+        let next_position = self.position + event.len() as u64;
+        if next_position > u32::MAX as u64 {
+            return Err(ReError::String(format!("binlog file {:?} exceeds 4GB", self.path)));
+        }
+        event[LOG_POS_OFFSET..LOG_POS_OFFSET + 4].copy_from_slice(&(next_position as u32).to_le_bytes());
+
+        if checksum {
+            let data_len = event.len() - CHECKSUM_LEN;
+            let crc = crc32fast::hash(&event[..data_len]);
+            event[data_len..].copy_from_slice(&crc.to_le_bytes());
+        }
+
+        self.writer.write_all(&event)?;
+        self.position = next_position;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binlog_file::BINLOG_MAGIC;
+    use crate::writer::file_binlog_writer::FileBinlogWriter;
+
+    /// 8.0.32, 开启 CRC32 的 FormatDescriptionEvent
+    fn format_description() -> Vec<u8> {
+        let mut payload = vec![0u8; 2 + 50 + 4 + 1];
+        payload[0] = 4;
+        payload[2..8].copy_from_slice(b"8.0.32");
+        payload[56] = 19;
+        payload.push(1);
+
+        let size = 19 + payload.len() + 4;
+        let mut event = vec![0u8; 4];
+        event.push(15);
+        event.extend_from_slice(&1u32.to_le_bytes());
+        event.extend_from_slice(&(size as u32).to_le_bytes());
+        event.extend_from_slice(&0u32.to_le_bytes());
+        event.extend_from_slice(&1u16.to_le_bytes());
+        event.extend_from_slice(&payload);
+        event.extend_from_slice(&[0u8; 4]);
+        event
+    }
+
+    #[test]
+    fn test_write_rotate() {
+        let path = std::env::temp_dir().join(format!("binlog_writer_test_{}.000001", std::process::id()));
+        let fde = format_description();
+
+        let mut writer = FileBinlogWriter::create(&path, &fde).unwrap();
+        assert!(writer.is_checksum());
+        assert_eq!(writer.position(), (4 + fde.len()) as u64);
+        writer.write_rotate("binlog.000002").unwrap();
+        writer.flush().unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(&data[..4], &BINLOG_MAGIC);
+        assert_eq!(data.len() as u64, writer.position());
+
+        // log_pos 与 CRC32 已重写
+        let rotate = &data[4 + fde.len()..];
+        assert_eq!(u32::from_le_bytes([rotate[13], rotate[14], rotate[15], rotate[16]]) as usize, data.len());
+        let (body, crc) = rotate.split_at(rotate.len() - 4);
+        assert_eq!(crc32fast::hash(body).to_le_bytes(), crc);
+        // BINLOG_IN_USE 标记已清除
+        assert_eq!(data[4 + 17], 0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use binlog::events::binlog_event::BinlogEvent;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use common::time_util::now;
+
+const DEFAULT_CHECKPOINT_FILE: &str = "binlog_cli.checkpoint";
+
+/// 两次写入 checkpoint 文件的最小间隔
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 最后一个已输出事务的结束位置
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub file: String,
+
+    pub position: u64,
+
+    /// 已执行的 GTID 集合, 以 GTID 方式订阅时存在
+    pub gtid_set: Option<String>,
+
+    /// 写入时间, unix 秒
+    pub timestamp: u64,
+}
+
+/// checkpoint 文件, json 格式。 先写临时文件再 rename, 避免中途退出时文件损坏
+#[derive(Debug)]
+pub struct CheckpointStore {
+    path: PathBuf,
+
+    last_save: Option<Instant>,
+
+    /// 尚未写入文件的 checkpoint
+    pending: Option<Checkpoint>,
+}
+
+impl CheckpointStore {
+    /// path 为 None 时使用当前目录下的 binlog_cli.checkpoint
+    pub fn new(path: Option<PathBuf>) -> Self {
+        CheckpointStore {
+            path: path.unwrap_or_else(|| PathBuf::from(DEFAULT_CHECKPOINT_FILE)),
+            last_save: None,
+            pending: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 读取已保存的 checkpoint, 文件不存在时返回 None
+    pub fn load(&self) -> CResult<Option<Checkpoint>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&self.path)?;
+        let checkpoint = serde_json::from_str(&content)
+            .map_err(|e| ReError::String(format!("Invalid checkpoint file {:?}: {}", self.path, e)))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// 记录新的 checkpoint, 由 flush 写入文件
+    pub fn update(&mut self, mut checkpoint: Checkpoint) {
+        checkpoint.timestamp = now();
+        self.pending = Some(checkpoint);
+    }
+
+    /// 距上次写入超过 CHECKPOINT_INTERVAL
+    pub fn is_due(&self) -> bool {
+        self.pending.is_some() && self.last_save.map_or(true, |t| t.elapsed() >= CHECKPOINT_INTERVAL)
+    }
+
+    pub fn flush(&mut self) -> CResult<()> {
+        let checkpoint = match self.pending.take() {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+
+        let content = serde_json::to_string_pretty(&checkpoint)
+            .map_err(|e| ReError::String(format!("checkpoint encode error: {}", e)))?;
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, &self.path)?;
+
+        self.last_save = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// 事务结束的事件: XID、除 BEGIN 以外的 Query(COMMIT 或 DDL)。 只在事务边界记录 checkpoint,
+/// 恢复时不会从事务中间开始, 从而不会丢失 TableMapEvent
+pub fn is_transaction_boundary(event: &BinlogEvent) -> bool {
+    match event {
+        BinlogEvent::XID(_) => true,
+        BinlogEvent::Query(e) => !e.query.trim().eq_ignore_ascii_case("BEGIN"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::checkpoint::{Checkpoint, CheckpointStore};
+
+    #[test]
+    fn test_checkpoint_store() {
+        let path = std::env::temp_dir().join(format!("binlog_cli_test_{}.checkpoint", std::process::id()));
+        let mut store = CheckpointStore::new(Some(path.clone()));
+        assert_eq!(store.load().unwrap(), None);
+
+        let checkpoint = Checkpoint {
+            file: String::from("binlog.000003"),
+            position: 1024,
+            gtid_set: None,
+            timestamp: 0,
+        };
+        store.update(checkpoint.clone());
+        assert!(store.is_due());
+        store.flush().unwrap();
+        assert!(!store.is_due());
+
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded.file, checkpoint.file);
+        assert_eq!(loaded.position, checkpoint.position);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
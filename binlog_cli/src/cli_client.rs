@@ -1,10 +1,30 @@
 use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{error, info};
 use binlog::binlog_server::BinlogServer;
+use binlog::row::masking::MaskingEngine;
 use common::config::BinlogConfig;
+use common::config::config_watcher::ConfigUpdate;
 use common::err::decode_error::ReError;
-use common::server::{Server};
+use common::pretty_util::{parse_bytes_len, to_bytes_len_pretty, to_duration_pretty};
+use common::server::{ComponentHealth, HealthStatus, PauseSwitch, Server};
 use connection::binlog::binlog_subscribe::BinlogSubscribe;
+use connection::binlog::lifecycle::lifecycle::BinlogLifecycle;
+use crate::checkpoint::{is_transaction_boundary, Checkpoint, CheckpointStore};
 use crate::cli_options::CliOptions;
+use crate::metrics::CliMetrics;
+use common::throttle::RateLimiter;
+use crate::output::{EventFormatter, EventMeta};
+use crate::range::{EventRange, RangeDecision};
+
+/// follow 模式下重新订阅前的等待时间
+const FOLLOW_RETRY_INTERVAL: Duration = Duration::from_secs(3);
+
+/// 暂停期间检查退出信号的间隔
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(Debug)]
 pub struct CliClient {
@@ -13,10 +33,50 @@ pub struct CliClient {
     binlog_server: BinlogServer,
 
     binlog_subscribe: BinlogSubscribe,
+
+    /// 事件输出
+    formatter: Box<dyn EventFormatter>,
+
+    /// 时间范围与结束位置。 起始位置由 binlog_config 的 file / position 决定
+    range: EventRange,
+
+    /// --follow: 连接异常或读取结束后重新订阅
+    follow: bool,
+
+    /// 收到退出信号后置位, 在事件之间检查
+    shutdown: Arc<AtomicBool>,
+
+    /// SIGUSR1 / SIGUSR2 暂停与恢复, 暂停期间保留连接, 不再读取事件
+    pause: PauseSwitch,
+
+    /// follow 模式下最近一次重试的错误, 读取恢复后清除
+    last_error: Option<String>,
+
+    /// 在事务边界记录已输出的位置
+    checkpoint: Option<CheckpointStore>,
+
+    /// --metrics-port
+    metrics: Option<Arc<CliMetrics>>,
+
+    /// 多数据源时的数据源名称, 标记在输出中
+    source: Option<String>,
+
+    /// --max-events-per-sec / --max-bytes-per-sec
+    rate_limiter: Option<RateLimiter>,
+
+    /// [masking], 输出前对列脱敏
+    masking: Option<MaskingEngine>,
+
+    /// 配置文件热加载, 应用 [rate_limit] 与 [masking] 的变化
+    config_updates: Option<watch::Receiver<Arc<ConfigUpdate>>>,
+
+    /// 命令行指定了限速时不随 [rate_limit] 变化
+    rate_limit_pinned: bool,
 }
 
 impl CliClient {
-    pub fn new(cli_options: CliOptions, binlog_config: BinlogConfig) -> Self {
+    pub fn new(cli_options: CliOptions, binlog_config: BinlogConfig, formatter: Box<dyn EventFormatter>) -> Self {
+        let follow = cli_options.is_follow();
         let binlog_server = BinlogServer::new();
         let binlog_subscribe= BinlogSubscribe::new(
             cli_options.is_debug(),
@@ -28,7 +88,206 @@ impl CliClient {
             binlog_config,
             binlog_server,
             binlog_subscribe,
+            formatter,
+            range: EventRange::default(),
+            follow,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            pause: PauseSwitch::new(),
+            last_error: None,
+            checkpoint: None,
+            metrics: None,
+            source: None,
+            rate_limiter: None,
+            masking: None,
+            config_updates: None,
+            rate_limit_pinned: false,
+        }
+    }
+
+    pub fn with_checkpoint(mut self, checkpoint: Option<CheckpointStore>) -> Self {
+        self.checkpoint = checkpoint;
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Option<Arc<CliMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn with_rate_limit(mut self, rate_limiter: Option<RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    pub fn with_masking(mut self, masking: Option<MaskingEngine>) -> Self {
+        self.masking = masking.filter(|m| !m.is_empty());
+        self
+    }
+
+    /// rate_limit_pinned: 命令行指定了 --max-events-per-sec / --max-bytes-per-sec
+    pub fn with_config_updates(mut self, updates: Option<watch::Receiver<Arc<ConfigUpdate>>>, rate_limit_pinned: bool) -> Self {
+        self.config_updates = updates;
+        self.rate_limit_pinned = rate_limit_pinned;
+        self
+    }
+
+    pub fn with_source(mut self, source: Option<String>) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_shutdown(mut self, shutdown: Arc<AtomicBool>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// 使用外部的暂停开关, 多数据源时共享
+    pub fn with_pause(mut self, pause: PauseSwitch) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// 暂停时先刷新输出与 checkpoint, 再阻塞至恢复或收到退出信号。
+    /// 读取循环持有非 Send 的迭代器, 因此同步等待而不是 await
+    fn wait_resumed(&mut self) -> Result<(), ReError> {
+        if !self.pause.is_paused() {
+            return Ok(());
         }
+        self.formatter.flush()?;
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.flush()?;
+        }
+        info!("binlog reading paused");
+        while !self.is_shutdown() && self.pause.wait_resumed(PAUSE_POLL_INTERVAL) {}
+        info!("binlog reading resumed");
+
+        Ok(())
+    }
+
+    pub fn with_range(mut self, range: EventRange) -> Self {
+        self.range = range.without_start_position();
+        self
+    }
+
+    /// 应用配置文件的变化, 无效的规则保留当前配置
+    fn apply_config_update(&mut self) {
+        let update = match self.config_updates.as_mut() {
+            Some(updates) if updates.has_changed().unwrap_or(false) => updates.borrow_and_update().clone(),
+            _ => return,
+        };
+
+        if update.diff.contains("rate_limit") && !self.rate_limit_pinned {
+            let rate_limit = update.config.rate_limit.clone().unwrap_or_default();
+            match rate_limit.max_bytes_per_sec.as_deref().map(parse_bytes_len).transpose() {
+                Ok(max_bytes) => {
+                    self.rate_limiter = RateLimiter::new(rate_limit.max_events_per_sec, max_bytes);
+                    info!("apply [rate_limit] {:?}", rate_limit);
+                },
+                Err(e) => error!("apply [rate_limit] error: {:?}", e),
+            }
+        }
+        if update.diff.contains("masking") {
+            match update.config.masking.as_ref().map(MaskingEngine::new).transpose() {
+                Ok(masking) => {
+                    self.masking = masking.filter(|m| !m.is_empty());
+                    info!("apply [masking]");
+                },
+                Err(e) => error!("apply [masking] error: {:?}", e),
+            }
+        }
+    }
+}
+
+impl CliClient {
+    /// 订阅并输出 binlog, 返回 true 表示越过了结束边界
+    async fn read_binlogs(&mut self) -> Result<bool, ReError> {
+        let binlogs = self.binlog_subscribe.binlogs().await?;
+
+        // 读取binlog 数据, 交给 formatter 输出
+        let mut stopped = false;
+        'read: for x in binlogs.get_iter() {
+            let list = match x {
+                Ok(list) => list,
+                // follow 模式下由外层重新订阅
+                Err(e) if self.follow => return Err(e),
+                Err(e) => {
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.record_error();
+                    }
+                    error!("read binlog event error: {:?}", e);
+                    continue;
+                }
+            };
+
+            self.last_error = None;
+            self.wait_resumed()?;
+            if self.is_shutdown() {
+                stopped = true;
+                break;
+            }
+            self.apply_config_update();
+
+            for mut e in list {
+                let log_pos = self.binlog_subscribe.get_log_position();
+                let when = self.binlog_subscribe.get_event_timestamp();
+                match self.range.check(&log_pos.get_file_name(), log_pos.get_position(), when) {
+                    RangeDecision::Skip => continue,
+                    // 越过结束边界, 停止读取并刷新输出
+                    RangeDecision::Stop => {
+                        stopped = true;
+                        break 'read;
+                    },
+                    RangeDecision::Accept => {},
+                }
+
+                if let Some(limiter) = self.rate_limiter.as_mut() {
+                    limiter.acquire(e.len().max(0) as u64);
+                }
+                if let Some(masking) = self.masking.as_mut() {
+                    masking.apply(&mut e);
+                }
+                let meta = EventMeta {
+                    file_name: log_pos.get_file_name(),
+                    log_pos: log_pos.get_position(),
+                    seq: self.binlog_subscribe.load_read_ptr(),
+                    source: self.source.clone(),
+                };
+                self.formatter.write_event(&meta, &e)?;
+                if let Some(metrics) = self.metrics.as_ref() {
+                    metrics.record_event(when);
+                    metrics.set_sink_backlog(self.formatter.backlog());
+                }
+
+                if is_transaction_boundary(&e) {
+                    if let Some(checkpoint) = self.checkpoint.as_mut() {
+                        checkpoint.update(Checkpoint {
+                            file: meta.file_name,
+                            position: meta.log_pos,
+                            gtid_set: self.binlog_subscribe.get_gtid_set(),
+                            timestamp: 0,
+                        });
+                        // 先刷新输出再写入 checkpoint, 重启后不会丢失已缓冲的事件
+                        if checkpoint.is_due() {
+                            self.formatter.flush()?;
+                            checkpoint.flush()?;
+                        }
+                    }
+                }
+            }
+        }
+
+        // 输出耗时信息
+        if binlogs.get_during_time().is_some() {
+            eprintln!("binlog 读取完成，耗时：{}， 收包总大小 {}.",
+                     to_duration_pretty(&binlogs.get_during_time().unwrap()),
+                     to_bytes_len_pretty(binlogs.get_receives_bytes()));
+        }
+
+        Ok(stopped)
     }
 }
 
@@ -37,10 +296,50 @@ unsafe impl Send for CliClient {}
 #[async_trait::async_trait]
 impl Server for CliClient {
     async fn start(&mut self) -> Result<(), ReError> {
-        println!("CliClient start");
+        match self.source.as_ref() {
+            Some(source) => println!("CliClient start, source {}", source),
+            None => println!("CliClient start"),
+        }
 
         self.binlog_server.start().await.unwrap();
-        self.binlog_subscribe.start().await.unwrap();
+
+        let mut c = self.binlog_config.clone();
+        loop {
+            self.binlog_subscribe.setup(&c)?;
+            let stopped = match self.read_binlogs().await {
+                Ok(stopped) => stopped,
+                // 配置、认证等错误重试无法恢复, 直接退出
+                Err(e) if self.follow && !e.is_fatal() => {
+                    if let Some(metrics) = self.metrics.as_ref() {
+                        metrics.record_error();
+                    }
+                    error!("read binlog error, retry later: {:?}", e);
+                    self.last_error = Some(e.to_string());
+                    false
+                },
+                Err(e) => return Err(e),
+            };
+            if stopped || !self.follow || self.is_shutdown() {
+                break;
+            }
+
+            // follow 模式: 从最后读取的位置重新订阅
+            let log_pos = self.binlog_subscribe.get_log_position();
+            if !log_pos.get_file_name().is_empty() {
+                c.file = Some(log_pos.get_file_name());
+                c.position = Some(log_pos.get_position() as i32);
+            }
+            self.formatter.flush()?;
+            if let Some(checkpoint) = self.checkpoint.as_mut() {
+                checkpoint.flush()?;
+            }
+            tokio::time::sleep(FOLLOW_RETRY_INTERVAL).await;
+        }
+        // 先刷新输出, 再写入 checkpoint
+        self.formatter.finish()?;
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.flush()?;
+        }
 
         let log_pos = self.binlog_subscribe.get_log_position();
         println!("load_read_ptr: [{}], pos {} in {}",
@@ -57,4 +356,32 @@ impl Server for CliClient {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    async fn pause(&mut self) -> Result<(), ReError> {
+        self.pause.pause();
+        self.binlog_server.pause().await
+    }
+
+    async fn resume(&mut self) -> Result<(), ReError> {
+        self.pause.resume();
+        self.binlog_server.resume().await
+    }
+
+    fn health(&self) -> ComponentHealth {
+        let name = match self.source.as_ref() {
+            Some(source) => format!("CliClient<{}>", source),
+            None => String::from("CliClient"),
+        };
+        let status = if self.is_shutdown() {
+            HealthStatus::Stopped
+        } else if self.pause.is_paused() {
+            HealthStatus::Paused
+        } else if self.last_error.is_some() {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Running
+        };
+
+        ComponentHealth::new(&name, status).with_message(self.last_error.clone())
+    }
+}
@@ -12,6 +12,8 @@ pub struct CliOptions {
 
     format: Format,
 
+    /// -f/--follow
+    follow: bool,
 }
 
 impl CliOptions {
@@ -20,6 +22,7 @@ impl CliOptions {
             debug,
             print_logs: false,
             format,
+            follow: false,
         }
     }
 
@@ -28,6 +31,7 @@ impl CliOptions {
             debug,
             print_logs: true,
             format,
+            follow: false,
         }
     }
 
@@ -43,8 +47,18 @@ impl CliOptions {
         self.format.clone()
     }
 
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow;
+    }
+
+    pub fn is_follow(&self) -> bool {
+        self.follow
+    }
+
     pub fn to_subscribe_options(&self) -> SubscribeOptions {
-        SubscribeOptions::new(self.debug, self.print_logs, self.format.clone())
+        let mut options = SubscribeOptions::new(self.debug, self.print_logs, self.format.clone());
+        options.set_follow(self.follow);
+        options
     }
 }
 
@@ -0,0 +1,257 @@
+use std::fmt::{Display, Formatter};
+
+use common::config::BinlogConfig;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use connection::conn::connection::{Connection, IConnection};
+use connection::conn::connection_options::ConnectionOptions;
+
+/// binlog 保留时间低于该值时给出警告
+const MIN_RETENTION_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// 可以运行, 但部分功能受限
+    Warn,
+    Fail,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// 单项检查的结果, message 为失败时的处理建议
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, status: CheckStatus, message: String) -> Self {
+        CheckResult { name, status, message }
+    }
+}
+
+/// `binlog_cli check`: 连接 MySQL, 检查复制权限、binlog_format、binlog_row_image、gtid_mode、
+/// server_id 冲突与 binlog 保留时间, 在正式运行前给出可执行的修改建议
+#[derive(Debug)]
+pub struct CheckCommand {
+    binlog_config: BinlogConfig,
+}
+
+impl CheckCommand {
+    pub fn new(binlog_config: BinlogConfig) -> Self {
+        CheckCommand { binlog_config }
+    }
+
+    /// 存在 FAIL 项时返回错误
+    pub fn run(&self) -> CResult<()> {
+        let results = self.check_all();
+        for r in &results {
+            println!("[{}] {:<18} {}", r.status, r.name, r.message);
+        }
+
+        let failed = results.iter().filter(|r| r.status == CheckStatus::Fail).count();
+        let warned = results.iter().filter(|r| r.status == CheckStatus::Warn).count();
+        if failed > 0 {
+            return Err(ReError::String(format!("{} checks failed, {} warnings", failed, warned)));
+        }
+
+        println!("all checks passed, {} warnings.", warned);
+        Ok(())
+    }
+
+    fn check_all(&self) -> Vec<CheckResult> {
+        let config = &self.binlog_config;
+        let opts = ConnectionOptions::new(
+            config.get_host().to_string(),
+            config.get_port(),
+            config.username.clone(),
+            config.password.clone(),
+        );
+
+        let mut conn = Connection::new(opts);
+        if let Err(e) = conn.try_connect() {
+            return vec![CheckResult::new("connect", CheckStatus::Fail,
+                                         format!("can not connect to {}:{}, {}", config.get_host(), config.get_port(), e))];
+        }
+        let mut results = vec![CheckResult::new("connect", CheckStatus::Pass,
+                                                format!("{}:{}", config.get_host(), config.get_port()))];
+
+        let grants = query_column(&mut conn, "SHOW GRANTS FOR CURRENT_USER()", 0);
+        results.push(match grants {
+            Ok(grants) => check_grants(&grants),
+            Err(e) => CheckResult::new("privileges", CheckStatus::Fail, format!("SHOW GRANTS error: {}", e)),
+        });
+
+        let log_bin = variable(&mut conn, "log_bin");
+        results.push(check_log_bin(log_bin.as_deref()));
+        results.push(check_binlog_format(variable(&mut conn, "binlog_format").as_deref()));
+        results.push(check_binlog_row_image(variable(&mut conn, "binlog_row_image").as_deref()));
+        results.push(check_gtid_mode(variable(&mut conn, "gtid_mode").as_deref()));
+
+        let master_id = variable(&mut conn, "server_id").and_then(|v| v.parse::<u32>().ok());
+        // SHOW SLAVE HOSTS 的第一列为 Server_id
+        let replica_ids: Vec<u32> = query_column(&mut conn, "SHOW SLAVE HOSTS", 0).unwrap_or_default()
+            .iter()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        results.push(check_server_id(config.get_server_id(), master_id, &replica_ids));
+
+        let expire_secs = variable(&mut conn, "binlog_expire_logs_seconds").and_then(|v| v.parse::<u64>().ok());
+        let expire_days = variable(&mut conn, "expire_logs_days").and_then(|v| v.parse::<u64>().ok());
+        results.push(check_retention(expire_secs, expire_days));
+
+        if let Some(file) = config.file.as_ref().filter(|f| !f.is_empty()) {
+            let binlogs = query_column(&mut conn, "SHOW BINARY LOGS", 0).unwrap_or_default();
+            results.push(check_start_file(file, &binlogs));
+        }
+
+        results
+    }
+}
+
+/// 查询结果中第 idx 列的全部值
+fn query_column(conn: &mut Connection, sql: &str, idx: usize) -> CResult<Vec<String>> {
+    let rows = conn.query(sql.to_string())?;
+    Ok(rows.iter().filter_map(|r| r.as_slice().get(idx).cloned().flatten()).collect())
+}
+
+/// 系统变量的值, 不存在时(低版本或 MariaDB)返回 None
+fn variable(conn: &mut Connection, name: &str) -> Option<String> {
+    query_column(conn, &format!("SHOW GLOBAL VARIABLES LIKE '{}'", name), 1).ok()
+        .and_then(|values| values.into_iter().next())
+}
+
+fn check_grants(grants: &[String]) -> CheckResult {
+    let grants: Vec<String> = grants.iter().map(|g| g.to_ascii_uppercase()).collect();
+    let has = |privilege: &str| grants.iter()
+        .any(|g| g.contains(" ON *.* ") && (g.contains("ALL PRIVILEGES") || g.contains(privilege)));
+
+    let missing: Vec<&str> = ["REPLICATION SLAVE", "REPLICATION CLIENT"].into_iter()
+        .filter(|p| !has(p))
+        .collect();
+    if missing.is_empty() {
+        CheckResult::new("privileges", CheckStatus::Pass, String::from("REPLICATION SLAVE, REPLICATION CLIENT"))
+    } else {
+        CheckResult::new("privileges", CheckStatus::Fail,
+                         format!("missing {}, run: GRANT REPLICATION SLAVE, REPLICATION CLIENT ON *.* TO <user>", missing.join(", ")))
+    }
+}
+
+fn check_log_bin(log_bin: Option<&str>) -> CheckResult {
+    match log_bin {
+        Some(v) if v.eq_ignore_ascii_case("ON") || v == "1" => CheckResult::new("log_bin", CheckStatus::Pass, String::from("ON")),
+        v => CheckResult::new("log_bin", CheckStatus::Fail,
+                              format!("log_bin is {}, enable it with --log-bin and restart mysqld", v.unwrap_or("unknown"))),
+    }
+}
+
+fn check_binlog_format(format: Option<&str>) -> CheckResult {
+    match format {
+        Some(v) if v.eq_ignore_ascii_case("ROW") => CheckResult::new("binlog_format", CheckStatus::Pass, String::from("ROW")),
+        v => CheckResult::new("binlog_format", CheckStatus::Fail,
+                              format!("binlog_format is {}, row events require: SET GLOBAL binlog_format = 'ROW'", v.unwrap_or("unknown"))),
+    }
+}
+
+fn check_binlog_row_image(image: Option<&str>) -> CheckResult {
+    match image {
+        Some(v) if v.eq_ignore_ascii_case("FULL") => CheckResult::new("binlog_row_image", CheckStatus::Pass, String::from("FULL")),
+        // 5.6 以前没有该变量, 相当于 FULL
+        None => CheckResult::new("binlog_row_image", CheckStatus::Pass, String::from("not supported, FULL")),
+        Some(v) => CheckResult::new("binlog_row_image", CheckStatus::Warn,
+                                    format!("binlog_row_image is {}, rows miss unchanged columns, SET GLOBAL binlog_row_image = 'FULL'", v)),
+    }
+}
+
+fn check_gtid_mode(mode: Option<&str>) -> CheckResult {
+    match mode {
+        Some(v) if v.eq_ignore_ascii_case("ON") => CheckResult::new("gtid_mode", CheckStatus::Pass, String::from("ON")),
+        v => CheckResult::new("gtid_mode", CheckStatus::Warn,
+                              format!("gtid_mode is {}, --include-gtids / --exclude-gtids and GTID checkpoints are unavailable",
+                                      v.unwrap_or("not supported"))),
+    }
+}
+
+fn check_server_id(client_id: u32, master_id: Option<u32>, replica_ids: &[u32]) -> CheckResult {
+    if master_id == Some(client_id) {
+        return CheckResult::new("server_id", CheckStatus::Fail,
+                                format!("server_id {} equals the master's, set [binlog] server_id to another value", client_id));
+    }
+    if replica_ids.contains(&client_id) {
+        return CheckResult::new("server_id", CheckStatus::Fail,
+                                format!("server_id {} is used by a registered replica, set [binlog] server_id to another value", client_id));
+    }
+
+    CheckResult::new("server_id", CheckStatus::Pass, format!("{} is unique", client_id))
+}
+
+/// binlog_expire_logs_seconds(8.0) 优先, 否则使用 expire_logs_days。 0 表示不自动清理
+fn check_retention(expire_secs: Option<u64>, expire_days: Option<u64>) -> CheckResult {
+    let secs = match (expire_secs, expire_days) {
+        (Some(s), _) if s > 0 => s,
+        (_, Some(d)) => d * 24 * 60 * 60,
+        _ => 0,
+    };
+
+    if secs == 0 {
+        CheckResult::new("binlog_retention", CheckStatus::Pass, String::from("binlogs are never purged automatically"))
+    } else if secs < MIN_RETENTION_SECS {
+        CheckResult::new("binlog_retention", CheckStatus::Warn,
+                         format!("binlogs are purged after {}s, a stopped replayer may not resume, keep at least {}s", secs, MIN_RETENTION_SECS))
+    } else {
+        CheckResult::new("binlog_retention", CheckStatus::Pass, format!("{}s", secs))
+    }
+}
+
+fn check_start_file(file: &str, binlogs: &[String]) -> CheckResult {
+    if binlogs.iter().any(|b| b == file) {
+        CheckResult::new("start_file", CheckStatus::Pass, format!("{} exists", file))
+    } else {
+        CheckResult::new("start_file", CheckStatus::Fail,
+                         format!("{} not in SHOW BINARY LOGS, it may be purged, set [binlog] file to an existing binlog", file))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cmd::check::{check_binlog_format, check_grants, check_retention, check_server_id, CheckStatus};
+
+    #[test]
+    fn test_check_grants() {
+        let grants = vec![String::from("GRANT REPLICATION SLAVE, REPLICATION CLIENT ON *.* TO `cdc`@`%`")];
+        assert_eq!(check_grants(&grants).status, CheckStatus::Pass);
+
+        let grants = vec![String::from("GRANT ALL PRIVILEGES ON *.* TO `root`@`localhost`")];
+        assert_eq!(check_grants(&grants).status, CheckStatus::Pass);
+
+        let grants = vec![String::from("GRANT SELECT, REPLICATION SLAVE ON *.* TO `cdc`@`%`")];
+        let rs = check_grants(&grants);
+        assert_eq!(rs.status, CheckStatus::Fail);
+        assert!(rs.message.contains("REPLICATION CLIENT"));
+    }
+
+    #[test]
+    fn test_check_variables() {
+        assert_eq!(check_binlog_format(Some("row")).status, CheckStatus::Pass);
+        assert_eq!(check_binlog_format(Some("MIXED")).status, CheckStatus::Fail);
+
+        assert_eq!(check_server_id(65535, Some(1), &[2, 3]).status, CheckStatus::Pass);
+        assert_eq!(check_server_id(65535, Some(65535), &[]).status, CheckStatus::Fail);
+        assert_eq!(check_server_id(2, Some(1), &[2]).status, CheckStatus::Fail);
+
+        assert_eq!(check_retention(Some(0), Some(0)).status, CheckStatus::Pass);
+        assert_eq!(check_retention(Some(3600), None).status, CheckStatus::Warn);
+        assert_eq!(check_retention(Some(0), Some(7)).status, CheckStatus::Pass);
+    }
+}
@@ -0,0 +1,39 @@
+use std::io::BufRead;
+
+use common::config::secret::{encrypt_secret, master_key};
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+/// `binlog_cli encrypt-password [password]`: 使用主密钥加密密码, 输出可写入配置的 `enc:` 值
+#[derive(Debug)]
+pub struct EncryptPasswordCommand {
+    /// 未指定时从标准输入读取一行, 避免明文留在 shell 历史中
+    password: Option<String>,
+}
+
+impl EncryptPasswordCommand {
+    pub fn new(password: Option<String>) -> Self {
+        EncryptPasswordCommand {
+            password,
+        }
+    }
+
+    pub fn run(&self) -> CResult<()> {
+        let master_key = master_key()?;
+        let password = match self.password.clone() {
+            Some(password) => password,
+            None => {
+                eprintln!("enter the password:");
+                let mut line = String::new();
+                std::io::stdin().lock().read_line(&mut line)?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            }
+        };
+        if password.is_empty() {
+            return Err(ReError::String(String::from("password is empty")));
+        }
+
+        println!("{}", encrypt_secret(&password, &master_key)?);
+        Ok(())
+    }
+}
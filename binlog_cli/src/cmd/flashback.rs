@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::events::event_header::Header;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::checkpoint::is_transaction_boundary;
+use crate::cmd::parse::{binlog_files, for_each_file_event};
+use crate::output::sql::{delete_sql, insert_sql, update_sql};
+use crate::output::{TableDef, TableDefs};
+use crate::range::{EventRange, RangeDecision};
+
+/// `binlog_cli flashback --table db.t <file-or-dir>`: 为范围内匹配的行变更生成反向 SQL。
+/// INSERT 转为 DELETE, DELETE 转为 INSERT, UPDATE 交换前后镜像。 按事务输出, 最新的事务在前,
+/// 事务内的语句同样倒序
+#[derive(Debug)]
+pub struct FlashbackCommand {
+    /// (db, table)
+    tables: Vec<(String, String)>,
+
+    range: EventRange,
+
+    table_defs: TableDefs,
+
+    /// 已结束的事务, 事务内的语句为正序
+    transactions: Vec<Vec<String>>,
+    current: Vec<String>,
+}
+
+impl FlashbackCommand {
+    /// tables: 逗号分隔的 `db.table`
+    pub fn new(tables: &str, range: EventRange) -> CResult<Self> {
+        Ok(FlashbackCommand {
+            tables: parse_tables(tables)?,
+            range,
+            table_defs: TableDefs::default(),
+            transactions: Vec::new(),
+            current: Vec::new(),
+        })
+    }
+
+    pub fn run(&mut self, path: &Path) -> CResult<()> {
+        // 同 parse: start_position 只作用于第一个文件, stop_position 只作用于最后一个文件
+        let files = binlog_files(path)?;
+        let name_of = |f: Option<&PathBuf>| f.and_then(|f| f.file_name()).map(|n| n.to_string_lossy().to_string());
+        self.range = self.range.clone().with_position_files(name_of(files.first()), name_of(files.last()));
+
+        for_each_file_event(path, |file_name, header, event| self.on_event(file_name, header, event))?;
+        self.end_transaction();
+
+        print!("{}", render(&self.transactions));
+        let statements: usize = self.transactions.iter().map(|t| t.len()).sum();
+        eprintln!("generated {} flashback statements in {} transactions.", statements, self.transactions.len());
+        Ok(())
+    }
+
+    fn on_event(&mut self, file_name: &str, header: &Header, event: &BinlogEvent) -> CResult<bool> {
+        match self.range.check(file_name, header.get_log_pos(), header.when) {
+            RangeDecision::Stop => return Ok(false),
+            RangeDecision::Skip => return Ok(true),
+            RangeDecision::Accept => {},
+        }
+
+        self.table_defs.update(event);
+        match event {
+            BinlogEvent::WriteRows(e) => {
+                if let Some(table) = self.matched(e.table_id) {
+                    let sqls: Vec<String> = e.get_rows().iter().map(|row| delete_sql(table, row)).collect();
+                    self.current.extend(sqls);
+                }
+            },
+            BinlogEvent::UpdateRows(e) => {
+                if let Some(table) = self.matched(e.table_id) {
+                    let sqls: Vec<String> = e.get_rows().iter()
+                        .map(|row| update_sql(table, &row.after_update, &row.before_update))
+                        .collect();
+                    self.current.extend(sqls);
+                }
+            },
+            BinlogEvent::DeleteRows(e) => {
+                if let Some(table) = self.matched(e.table_id) {
+                    let sqls: Vec<String> = e.get_rows().iter().map(|row| insert_sql(table, row)).collect();
+                    self.current.extend(sqls);
+                }
+            },
+            e if is_transaction_boundary(e) => self.end_transaction(),
+            _ => {},
+        }
+
+        Ok(true)
+    }
+
+    fn matched(&self, table_id: u64) -> Option<&TableDef> {
+        self.table_defs.get(table_id)
+            .filter(|t| self.tables.iter().any(|(db, table)| *db == t.database && *table == t.table))
+    }
+
+    fn end_transaction(&mut self) {
+        if !self.current.is_empty() {
+            self.transactions.push(std::mem::take(&mut self.current));
+        }
+    }
+}
+
+/// `db.t1,db.t2` -> [(db, t1), (db, t2)]
+fn parse_tables(tables: &str) -> CResult<Vec<(String, String)>> {
+    let mut rs = Vec::new();
+    for item in tables.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        match item.split_once('.') {
+            Some((db, table)) if !db.is_empty() && !table.is_empty() => rs.push((db.to_string(), table.to_string())),
+            _ => return Err(ReError::String(format!("Invalid table {}, expect db.table", item))),
+        }
+    }
+
+    if rs.is_empty() {
+        return Err(ReError::String(String::from("flashback requires --table db.table")));
+    }
+    Ok(rs)
+}
+
+/// 最新的事务在前, 事务内的语句倒序
+fn render(transactions: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    for trx in transactions.iter().rev() {
+        out.push_str("BEGIN;\n");
+        for sql in trx.iter().rev() {
+            out.push_str(sql);
+            out.push('\n');
+        }
+        out.push_str("COMMIT;\n");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cmd::flashback::{parse_tables, render};
+
+    #[test]
+    fn test_parse_tables() {
+        assert_eq!(parse_tables("db.t1, db2.t2").unwrap(),
+                   vec![(String::from("db"), String::from("t1")), (String::from("db2"), String::from("t2"))]);
+        assert!(parse_tables("t1").is_err());
+        assert!(parse_tables("").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let transactions = vec![
+            vec![String::from("a1;"), String::from("a2;")],
+            vec![String::from("b1;")],
+        ];
+        assert_eq!(render(&transactions), "BEGIN;\nb1;\nCOMMIT;\nBEGIN;\na2;\na1;\nCOMMIT;\n");
+    }
+}
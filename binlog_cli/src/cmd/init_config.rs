@@ -0,0 +1,253 @@
+use std::path::{Path, PathBuf};
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+/// 默认生成到 ./conf/replayer.toml, 与未指定 --config 时的加载路径一致
+const DEFAULT_CONFIG_PATH: &str = "conf/replayer.toml";
+
+/// 带注释的完整配置, 包含全部支持的配置项与默认值
+const CONFIG_TEMPLATE: &str = r#"# 应用名称
+app_name = "replayer"
+
+# 先加载的公共配置(相对于本文件所在目录), 本文件中的配置项覆盖公共配置
+#include = ["common.toml"]
+
+
+[base]
+# 最大使用内存, 如 256MB、1GiB。 接近时中继日志缓存停止增长, 回放中的事务等待其他组件释放内存, 修改后无需重启
+max_memory = "256MB"
+# 日志输出路径
+log_dir = "/tmp/replayer"
+# 日志级别: trace | debug | info | warn | error, 修改后无需重启。 -d 时为 debug
+#log_level = "info"
+# 日志格式: text | json
+#log_format = "text"
+# 日志文件按时间滚动: never | minutely | hourly | daily, 默认 daily
+#log_rotation = "daily"
+# 单个日志文件的最大大小, 超过后滚动
+#log_max_size = "100MB"
+# 保留的日志文件数量, 不配置时不清理
+#log_max_files = 30
+# 按模块覆盖日志级别, 修改后无需重启
+#log_modules = { binlog = "debug", connection = "warn" }
+
+
+# 读取和解析 binlog 时的数据源配置
+[binlog]
+# 数据源的 mysql 地址, 默认 127.0.0.1
+host = "127.0.0.1"
+# 数据源的 mysql 端口, 默认 3306
+port = 3306
+# 需要 REPLICATION SLAVE, REPLICATION CLIENT 权限
+username = "root"
+# 密码可以引用外部的密钥, 加载时解析: env:VAR、file:path、exec:cmd,
+# 或 binlog_cli encrypt-password 生成的 enc: 加密值(需要环境变量 REPLAYER_MASTER_KEY)。 以这些前缀开头的明文使用 plain: 前缀
+password = "123456"
+# 读取 binlog 的缓冲区大小, 默认 32KB
+payload_buffer_size = 32768
+# 起始的 binlog 文件与位置, 不指定时从最早的 binlog 开始
+#file = "mysql-bin.000005"
+#position = 4
+# 本地 binlog 文件所在的目录
+#binlog_path = "/var/lib/mysql"
+# --follow 时以 slave 身份注册使用的 server_id, 不能与其他 slave 重复, 默认 65535
+#server_id = 65535
+# 源库的 time_zone: SYSTEM | UTC | +08:00 | Asia/Shanghai, 转换 TIMESTAMP 列时使用, 语句中记录了会话时区时以会话时区为准
+#time_zone = "SYSTEM"
+
+# 复制过滤规则, 与 MySQL replicate-* 参数语义一致。 不配置时不过滤
+#[binlog.replicate]
+#replicate_do_db = ["db1"]
+#replicate_ignore_db = ["mysql"]
+#replicate_do_table = ["db1.t1"]
+#replicate_ignore_table = ["db1.t2"]
+# 支持 % 与 _ 通配符
+#replicate_wild_do_table = ["db1.order_%"]
+#replicate_wild_ignore_table = ["db1.tmp%"]
+# 匹配 db.table 的正则表达式
+#replicate_regex_do_table = ["db1\\.t\\d+"]
+#replicate_regex_ignore_table = ["db1\\.bak_.*"]
+# 按事务过滤的 GTID 集合
+#replicate_do_gtids = "3e11fa47-71ca-11e1-9e33-c80aa9429562:1-100"
+#replicate_ignore_gtids = "3e11fa47-71ca-11e1-9e33-c80aa9429562:5"
+
+# 多数据源, 每个数据源独立运行一条 pipeline, 输出中带有数据源名称。 未配置的项使用 [binlog] 中的值,
+# checkpoint 文件与 file sink 的文件名追加数据源名称
+#[[binlog.sources]]
+#name = "db1"
+#host = "10.0.0.1"
+#file = "mysql-bin.000001"
+#position = 4
+#server_id = 65501
+#[[binlog.sources]]
+#name = "db2"
+#host = "10.0.0.2"
+
+
+# 事件输出目标, 命令行的 --sink 等参数优先。 不配置时输出到标准输出
+#[sink]
+# stdout | file | kafka
+#type = "file"
+# file: 输出文件, 按大小或时间滚动, 滚动后的文件名为 <path>.<yyyyMMddHHmmss>
+#path = "/tmp/replayer/events.log"
+#rotate_size = "100MB"
+#rotate_interval = "1h"
+# kafka: 需要以 --features kafka 编译 binlog_cli
+#kafka_brokers = "127.0.0.1:9092"
+# topic 模板, 支持 {db}、{table} 占位符
+#kafka_topic = "binlog.{db}"
+
+
+# 列脱敏规则, 在输出前生效。 key 为 [db.]table.column, 没有列名元数据时可使用 @1、@2 按位置指定
+# 脱敏方式: null | redact | hash | partial(保留前 N 个, 保留后 M 个)
+#[masking]
+#"db1.users.email" = "hash"
+#"db1.users.phone" = "partial(3,4)"
+#"users.id_card" = "redact"
+
+# 读取限速, 命令行的 --max-events-per-sec / --max-bytes-per-sec 优先。 [masking] 与 [rate_limit] 修改后无需重启
+#[rate_limit]
+#max_events_per_sec = 5000
+#max_bytes_per_sec = "10MB"
+
+# 表与列的映射规则, 按顺序作用于源表的列。 table 格式为 [db.]table, * 匹配任意库或表
+# type: rename_table | rename_column | drop_column | cast_column | computed_column
+#[[mapping]]
+#type = "rename_table"
+#table = "db1.t1"
+#to = "db2.t1_copy"
+#[[mapping]]
+#type = "rename_column"
+#table = "db1.t1"
+#column = "name"
+#to = "user_name"
+#[[mapping]]
+#type = "drop_column"
+#table = "db1.t1"
+#column = "password"
+#[[mapping]]
+#type = "cast_column"
+#table = "db1.t1"
+#column = "amount"
+#to = "string"
+# expr 中的 {column} 替换为该列的值, 结果为 string, 指定 data_type 时再转换
+#[[mapping]]
+#type = "computed_column"
+#table = "db1.t1"
+#column = "full_name"
+#expr = "{first_name} {last_name}"
+
+
+# RC mysql configuration
+[rc_mysql]
+# rc multi address
+addr = ["127.0.0.1:3001"]
+username = "root"
+password = ""
+# raft shard stats fresh, default 10s, min: 5s, max: 60s
+raft_stats_fresh_interval_ms = 10000
+
+
+# RC metadata configuration
+[rc_metadata]
+addr = "127.0.0.1:3306"
+username = "root"
+password = ""
+# 定时从 information_schema 加载表结构的库, 多个库以逗号分隔
+database = "hdb_meta"
+# automatically fresh table schema, default 10s, min 5s, max 60s
+metadata_stats_fresh_interval_ms = 10000
+
+
+# 各环境的差异配置, 通过 --profile 或环境变量 REPLAYER_PROFILE 选择, 覆盖以上的配置项
+#[profile.dev.binlog]
+#host = "127.0.0.1"
+#[profile.prod.binlog]
+#host = "10.0.0.1"
+#password = "env:MYSQL_PASSWORD"
+#[profile.prod.base]
+#log_level = "warn"
+"#;
+
+/// `binlog_cli init-config [path]`: 生成带注释的 replayer.toml
+#[derive(Debug)]
+pub struct InitConfigCommand {
+    path: PathBuf,
+
+    /// 覆盖已存在的文件
+    force: bool,
+}
+
+impl InitConfigCommand {
+    pub fn new(path: Option<PathBuf>, force: bool) -> Self {
+        InitConfigCommand {
+            path: path.unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH)),
+            force,
+        }
+    }
+
+    pub fn run(&self) -> CResult<()> {
+        write_config(&self.path, self.force)?;
+        eprintln!("config written to {:?}", self.path);
+        Ok(())
+    }
+}
+
+fn write_config(path: &Path, force: bool) -> CResult<()> {
+    if path.exists() && !force {
+        return Err(ReError::String(format!("{:?} already exists, use --force to overwrite", path)));
+    }
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    std::fs::write(path, CONFIG_TEMPLATE)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use common::config::read_config;
+
+    use crate::cmd::init_config::{write_config, CONFIG_TEMPLATE};
+
+    #[test]
+    fn test_write_config() {
+        let path = std::env::temp_dir().join(format!("binlog_cli_test_{}", std::process::id())).join("replayer.toml");
+
+        write_config(&path, false).unwrap();
+        // 生成的配置可以被正常加载
+        let config = read_config(&path).unwrap();
+        assert_eq!(config.binlog.get_port(), 3306);
+        assert!(config.sink.is_none());
+
+        assert!(write_config(&path, false).is_err());
+        write_config(&path, true).unwrap();
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    /// 去掉注释后的 [[mapping]] 示例可以被正常加载
+    #[test]
+    fn test_mapping_example() {
+        let start = CONFIG_TEMPLATE.find("#[[mapping]]").unwrap();
+        let end = start + CONFIG_TEMPLATE[start..].find("\n\n").unwrap();
+        let mut template = CONFIG_TEMPLATE[..start].to_string();
+        for line in CONFIG_TEMPLATE[start..end].lines() {
+            template.push_str(line.strip_prefix('#').filter(|l| !l.starts_with(' ')).unwrap_or(line));
+            template.push('\n');
+        }
+        template.push_str(&CONFIG_TEMPLATE[end..]);
+
+        let path = std::env::temp_dir().join(format!("binlog_cli_mapping_test_{}", std::process::id())).join("replayer.toml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, template).unwrap();
+        let config = read_config(&path).unwrap();
+        assert_eq!(config.mapping.unwrap().len(), 5);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,8 @@
+pub mod check;
+pub mod encrypt_password;
+pub mod flashback;
+pub mod init_config;
+pub mod parse;
+pub mod stats;
+pub mod split;
+pub mod verify;
@@ -0,0 +1,339 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+use binlog::decoder::binlog_decoder::BinlogReader;
+use binlog::decoder::bytes_binlog_reader::BytesBinlogReader;
+use binlog::decoder::file_binlog_reader::FileBinlogReader;
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::row::masking::MaskingEngine;
+use binlog::events::event_header::Header;
+use binlog::events::log_context::{ILogContext, LogContext};
+use binlog::events::log_position::LogFilePosition;
+use common::binlog::FIRST_EVENT_POSITION;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use connection::binlog::replication_filter::ReplicationFilter;
+
+use crate::output::{EventFormatter, EventMeta};
+use crate::range::{EventRange, RangeDecision};
+use common::throttle::RateLimiter;
+
+/// follow 模式下检查文件变化的间隔
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `parse -` 从标准输入读取
+const STDIN_PATH: &str = "-";
+
+/// 每次从标准输入读取的字节数
+const STDIN_CHUNK_SIZE: usize = 64 * 1024;
+
+/// `binlog_cli parse <file-or-dir>`: 不连接 MySQL, 直接解析本地 binlog 文件
+#[derive(Debug)]
+pub struct ParseCommand {
+    formatter: Box<dyn EventFormatter>,
+
+    /// 读到末尾后继续等待追加的内容与新文件
+    follow: bool,
+
+    filter: Option<ReplicationFilter>,
+
+    range: EventRange,
+
+    /// 已输出的事件数量
+    events: u64,
+
+    rate_limiter: Option<RateLimiter>,
+
+    /// [masking], 输出前对列脱敏
+    masking: Option<MaskingEngine>,
+}
+
+impl ParseCommand {
+    pub fn new(formatter: Box<dyn EventFormatter>, filter: Option<ReplicationFilter>) -> Self {
+        ParseCommand {
+            formatter,
+            follow: false,
+            filter: filter.filter(|f| !f.is_empty()),
+            range: EventRange::default(),
+            events: 0,
+            rate_limiter: None,
+            masking: None,
+        }
+    }
+
+    /// 设置位置与时间范围
+    pub fn with_range(mut self, range: EventRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// --max-events-per-sec / --max-bytes-per-sec
+    pub fn with_rate_limit(mut self, rate_limiter: Option<RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    pub fn with_masking(mut self, masking: Option<MaskingEngine>) -> Self {
+        self.masking = masking.filter(|m| !m.is_empty());
+        self
+    }
+
+    pub fn with_follow(mut self, follow: bool) -> Self {
+        self.follow = follow;
+        self
+    }
+
+    /// 解析单个 binlog 文件, 或目录下的全部 binlog 文件(按文件名顺序)。 path 为 `-` 时从标准输入读取
+    pub fn run(&mut self, path: &Path) -> CResult<()> {
+        if path == Path::new(STDIN_PATH) {
+            self.range = self.range.clone().with_position_files(Some(STDIN_PATH.to_string()), Some(STDIN_PATH.to_string()));
+            for_each_stdin_event(|file_name, header, event| self.on_event(file_name, header, event))?;
+            self.formatter.finish()?;
+
+            eprintln!("parsed {} events from stdin.", self.events);
+            return Ok(());
+        }
+
+        // start_position 只作用于第一个文件, stop_position 只作用于最后一个文件。 follow 模式下没有最后一个文件
+        let files = binlog_files(path)?;
+        let name_of = |f: Option<&PathBuf>| f.and_then(|f| f.file_name()).map(|n| n.to_string_lossy().to_string());
+        let stop_file = if self.follow { None } else { name_of(files.last()) };
+        self.range = self.range.clone().with_position_files(name_of(files.first()), stop_file);
+
+        let files = if self.follow {
+            // 两个回调都需要修改 self
+            let this = RefCell::new(&mut *self);
+            follow_file_events(path, |file_name, header, event| {
+                this.borrow_mut().on_event(file_name, header, event)
+            }, || this.borrow_mut().formatter.flush())?
+        } else {
+            for_each_file_event(path, |file_name, header, event| {
+                self.on_event(file_name, header, event)
+            })?
+        };
+        self.formatter.finish()?;
+
+        eprintln!("parsed {} events from {} files.", self.events, files);
+        Ok(())
+    }
+
+    /// 返回 false 表示越过了结束边界
+    fn on_event(&mut self, file_name: &str, header: &Header, event: &BinlogEvent) -> CResult<bool> {
+        match self.range.check(file_name, header.get_log_pos(), header.when) {
+            RangeDecision::Skip => Ok(true),
+            RangeDecision::Stop => Ok(false),
+            RangeDecision::Accept => {
+                if let Some(limiter) = self.rate_limiter.as_mut() {
+                    limiter.acquire(header.get_event_length() as u64);
+                }
+                self.write_event(file_name, header, event)?;
+                Ok(true)
+            },
+        }
+    }
+
+    fn write_event(&mut self, file_name: &str, header: &Header, event: &BinlogEvent) -> CResult<()> {
+        if let Some(filter) = self.filter.as_mut() {
+            if !filter.accept_event(event) {
+                return Ok(());
+            }
+        }
+        self.events += 1;
+
+        let meta = EventMeta {
+            file_name: file_name.to_string(),
+            log_pos: header.get_log_pos(),
+            seq: self.events,
+            source: None,
+        };
+        match self.masking.as_mut() {
+            Some(masking) => {
+                let mut event = event.clone();
+                masking.apply(&mut event);
+                self.formatter.write_event(&meta, &event)
+            },
+            None => self.formatter.write_event(&meta, event),
+        }
+    }
+}
+
+/// 依次解析 path(文件或目录)下的 binlog 文件, 对每个事件调用 f, f 返回 false 时结束解析。 返回解析的文件数量
+pub fn for_each_file_event<F>(path: &Path, mut f: F) -> CResult<usize>
+    where F: FnMut(&str, &Header, &BinlogEvent) -> CResult<bool> {
+    let files = binlog_files(path)?;
+    if files.is_empty() {
+        return Err(ReError::String(format!("No binlog file found in {:?}", path)));
+    }
+
+    for (idx, file) in files.iter().enumerate() {
+        let file_name = file.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let context = Rc::new(RefCell::new(LogContext::new(LogFilePosition::new(&file_name))));
+
+        let mut reader = FileBinlogReader::new(context, false)?;
+        for rs in reader.read_events(File::open(file)?) {
+            let (header, event) = rs?;
+            if !f(&file_name, &header, &event)? {
+                return Ok(idx + 1);
+            }
+        }
+    }
+
+    Ok(files.len())
+}
+
+/// 从标准输入读取原始 binlog 字节(含 magic), 如 `ssh host 'cat binlog.000001' | binlog_cli parse -`。
+/// 对每个事件调用 f, file_name 为 `-`, f 返回 false 时结束
+pub fn for_each_stdin_event<F>(mut f: F) -> CResult<()>
+    where F: FnMut(&str, &Header, &BinlogEvent) -> CResult<bool> {
+    let context = Rc::new(RefCell::new(LogContext::new(LogFilePosition::new(STDIN_PATH))));
+    let mut reader = BytesBinlogReader::new(context, false)?;
+
+    let mut stdin = std::io::stdin().lock();
+    let mut buf = vec![0u8; STDIN_CHUNK_SIZE];
+    loop {
+        let n = match stdin.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(ReError::from(e)),
+        };
+
+        for (header, event) in reader.read_chunk(&buf[..n])? {
+            if !f(STDIN_PATH, &header, &event)? {
+                return Ok(());
+            }
+        }
+    }
+
+    let remaining = reader.get_source_bytes().len();
+    if remaining > 0 {
+        return Err(ReError::String(format!("stdin ended with a truncated event, {} bytes left", remaining)));
+    }
+    Ok(())
+}
+
+/// -f/--follow: 类似 tail -f, 读到文件末尾后等待追加的内容; 目录中出现更新的 binlog 文件时切换到该文件。
+/// f 返回 false 时结束, 每次等待前调用 on_idle。 返回解析的文件数量
+pub fn follow_file_events<F, I>(path: &Path, mut f: F, mut on_idle: I) -> CResult<usize>
+    where F: FnMut(&str, &Header, &BinlogEvent) -> CResult<bool>, I: FnMut() -> CResult<()> {
+    let dir = if path.is_dir() {
+        path.to_path_buf()
+    } else {
+        path.parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."))
+    };
+
+    let mut current = if path.is_dir() {
+        loop {
+            if let Some(file) = binlog_files(path)?.into_iter().next() {
+                break file;
+            }
+            on_idle()?;
+            thread::sleep(FOLLOW_POLL_INTERVAL);
+        }
+    } else {
+        path.to_path_buf()
+    };
+
+    let mut files = 0;
+    loop {
+        files += 1;
+        let file_name = current.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let context = Rc::new(RefCell::new(LogContext::new(LogFilePosition::new(&file_name))));
+        let mut reader = FileBinlogReader::new(context, false)?;
+
+        // 最后一个完整事件的结束位置。 文件末尾不完整的事件在下一次读取时重新解析
+        let mut offset = 0u64;
+        loop {
+            let len = std::fs::metadata(&current)?.len();
+            if len >= FIRST_EVENT_POSITION as u64 && len > offset {
+                let mut source = File::open(&current)?;
+                if offset > 0 {
+                    source.seek(SeekFrom::Start(offset))?;
+                } else {
+                    offset = FIRST_EVENT_POSITION as u64;
+                }
+
+                for rs in reader.read_events(source) {
+                    let (header, event) = rs?;
+                    offset += header.get_event_length() as u64;
+                    if !f(&file_name, &header, &event)? {
+                        return Ok(files);
+                    }
+                }
+            }
+
+            // 已有更新的文件, 且当前文件已读完时切换
+            if let Some(next) = next_binlog_file(&dir, &current)? {
+                if std::fs::metadata(&current)?.len() <= offset {
+                    current = next;
+                    break;
+                }
+                continue;
+            }
+
+            on_idle()?;
+            thread::sleep(FOLLOW_POLL_INTERVAL);
+        }
+    }
+}
+
+/// dir 中同名前缀、序号大于 current 的第一个 binlog 文件
+fn next_binlog_file(dir: &Path, current: &Path) -> CResult<Option<PathBuf>> {
+    let stem = current.file_stem();
+    let name = current.file_name();
+
+    let next = binlog_files(dir)?.into_iter()
+        .find(|f| f.file_stem() == stem && f.file_name() > name);
+    Ok(next)
+}
+
+/// 列出待解析的文件。 path 为目录时, 只取形如 `binlog.000001` 的文件(扩展名全为数字), 忽略 index 等文件
+pub fn binlog_files(path: &Path) -> CResult<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let file = entry?.path();
+        if file.is_file() && is_binlog_file_name(&file) {
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+fn is_binlog_file_name(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => !ext.is_empty() && ext.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use crate::cmd::parse::is_binlog_file_name;
+
+    #[test]
+    fn test_is_binlog_file_name() {
+        assert!(is_binlog_file_name(Path::new("/var/lib/mysql/binlog.000018")));
+        assert!(is_binlog_file_name(Path::new("mysql-bin.000001")));
+        assert!(!is_binlog_file_name(Path::new("binlog.index")));
+        assert!(!is_binlog_file_name(Path::new("binlog")));
+    }
+}
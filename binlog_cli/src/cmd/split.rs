@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use binlog::b_type::LogEventType;
+use binlog::binlog_file::BINLOG_MAGIC;
+use binlog::writer::file_binlog_writer::FileBinlogWriter;
+use common::binlog::{EVENT_HEADER_SIZE, FIRST_EVENT_POSITION};
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use common::pretty_util::parse_bytes_len;
+
+use crate::cmd::parse::binlog_files;
+use crate::cmd::verify::read_partial;
+
+/// 输出文件的名称前缀
+const OUTPUT_BASENAME: &str = "binlog";
+
+/// QueryEvent 的 post header: thread_id(4) exec_time(4) db_len(1) error_code(2) status_vars_len(2)
+const QUERY_POST_HEADER_LEN: usize = 13;
+
+const QUERY_EVENT: u8 = LogEventType::QUERY_EVENT as u8;
+const STOP_EVENT: u8 = LogEventType::STOP_EVENT as u8;
+const ROTATE_EVENT: u8 = LogEventType::ROTATE_EVENT as u8;
+const FORMAT_DESCRIPTION_EVENT: u8 = LogEventType::FORMAT_DESCRIPTION_EVENT as u8;
+const XID_EVENT: u8 = LogEventType::XID_EVENT as u8;
+const TABLE_MAP_EVENT: u8 = LogEventType::TABLE_MAP_EVENT as u8;
+const WRITE_ROWS_EVENT_V1: u8 = LogEventType::WRITE_ROWS_EVENT_V1 as u8;
+const UPDATE_ROWS_EVENT_V1: u8 = LogEventType::UPDATE_ROWS_EVENT_V1 as u8;
+const DELETE_ROWS_EVENT_V1: u8 = LogEventType::DELETE_ROWS_EVENT_V1 as u8;
+const HEARTBEAT_LOG_EVENT: u8 = LogEventType::HEARTBEAT_LOG_EVENT as u8;
+const WRITE_ROWS_EVENT: u8 = LogEventType::WRITE_ROWS_EVENT as u8;
+const UPDATE_ROWS_EVENT: u8 = LogEventType::UPDATE_ROWS_EVENT as u8;
+const DELETE_ROWS_EVENT: u8 = LogEventType::DELETE_ROWS_EVENT as u8;
+const GTID_LOG_EVENT: u8 = LogEventType::GTID_LOG_EVENT as u8;
+const ANONYMOUS_GTID_LOG_EVENT: u8 = LogEventType::ANONYMOUS_GTID_LOG_EVENT as u8;
+const PREVIOUS_GTIDS_LOG_EVENT: u8 = LogEventType::PREVIOUS_GTIDS_LOG_EVENT as u8;
+const PARTIAL_UPDATE_ROWS_EVENT: u8 = LogEventType::PARTIAL_UPDATE_ROWS_EVENT as u8;
+const HEARTBEAT_LOG_EVENT_V2: u8 = LogEventType::HEARTBEAT_LOG_EVENT_V2 as u8;
+
+/// 拆分方式, 对应 `--by`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    /// 每张表一组文件, 没有表信息的语句(DDL、statement 格式的 DML)归入所在库
+    Table,
+    Db,
+    /// 超过大小后切换到下一个文件, 只在事务边界切换
+    Size(u64),
+}
+
+impl SplitBy {
+    pub fn new(by: &str, size: Option<&str>) -> CResult<Self> {
+        match by.to_ascii_lowercase().as_str() {
+            "table" => Ok(SplitBy::Table),
+            "db" => Ok(SplitBy::Db),
+            "size" => match size {
+                Some(s) => Ok(SplitBy::Size(parse_bytes_len(s)?)),
+                None => Err(ReError::String(String::from("--by size requires --size"))),
+            },
+            _ => Err(ReError::String(format!("Unsupported split: {}, expect table | db | size", by))),
+        }
+    }
+}
+
+/// 事务中的一个事件, key 为其所属的分区, None 表示 BEGIN / XID 等事务框架事件
+#[derive(Debug)]
+struct TrxEvent {
+    key: Option<String>,
+    event_type: u8,
+    data: Vec<u8>,
+}
+
+/// `binlog_cli split <file-or-dir> --by table|db|size`: 将 binlog 按表、库或大小拆分为多个合法的 binlog 文件,
+/// 用于选择性恢复。 按表/库拆分时, 同一事务涉及多个分区时会拆为多个事务, 并去掉 GTID 事件
+#[derive(Debug)]
+pub struct SplitCommand {
+    by: SplitBy,
+
+    output_dir: PathBuf,
+
+    /// 源文件的 FormatDescriptionEvent, 新文件以它开头
+    format_description: Option<Vec<u8>>,
+    checksum: bool,
+
+    /// table_id -> (db, table)
+    tables: HashMap<u64, (String, String)>,
+
+    /// 当前事务的事件
+    trx: Vec<TrxEvent>,
+    /// 是否在 BEGIN 开启的事务中
+    in_trx: bool,
+
+    /// 分区 -> 当前写入的文件
+    writers: HashMap<String, FileBinlogWriter>,
+    /// 分区 -> 已创建的文件数量
+    sequences: HashMap<String, u32>,
+
+    transactions: u64,
+}
+
+impl SplitCommand {
+    /// output_dir 为 None 时使用当前目录
+    pub fn new(by: SplitBy, output_dir: Option<PathBuf>) -> Self {
+        SplitCommand {
+            by,
+            output_dir: output_dir.unwrap_or_else(|| PathBuf::from(".")),
+            format_description: None,
+            checksum: false,
+            tables: HashMap::new(),
+            trx: Vec::new(),
+            in_trx: false,
+            writers: HashMap::new(),
+            sequences: HashMap::new(),
+            transactions: 0,
+        }
+    }
+
+    pub fn run(&mut self, path: &Path) -> CResult<()> {
+        let files = binlog_files(path)?;
+        if files.is_empty() {
+            return Err(ReError::String(format!("No binlog file found in {:?}", path)));
+        }
+
+        for file in &files {
+            self.split_file(file)?;
+        }
+        if !self.trx.is_empty() {
+            eprintln!("WARN drop {} events of the incomplete transaction at the end", self.trx.len());
+            self.trx.clear();
+        }
+
+        for writer in self.writers.values_mut() {
+            writer.flush()?;
+        }
+        let files: u32 = self.sequences.values().sum();
+        eprintln!("split {} transactions into {} files in {:?}.", self.transactions, files, self.output_dir);
+        Ok(())
+    }
+
+    fn split_file(&mut self, file: &Path) -> CResult<()> {
+        let mut reader = BufReader::new(File::open(file)?);
+
+        let mut magic = [0u8; FIRST_EVENT_POSITION];
+        if read_partial(&mut reader, &mut magic)? < FIRST_EVENT_POSITION || magic != BINLOG_MAGIC {
+            return Err(ReError::String(format!("{:?} is not a binlog file", file)));
+        }
+
+        let mut offset = FIRST_EVENT_POSITION as u64;
+        while let Some(event) = next_event(&mut reader, offset)? {
+            offset += event.len() as u64;
+            self.on_event(event)?;
+        }
+
+        Ok(())
+    }
+
+    fn on_event(&mut self, data: Vec<u8>) -> CResult<()> {
+        let event_type = data[4];
+        // 是否结束当前事务: XID、COMMIT / ROLLBACK, 或 BEGIN 之外的 DDL
+        let mut end = false;
+        let key = match event_type {
+            FORMAT_DESCRIPTION_EVENT => {
+                self.checksum = FileBinlogWriter::checksum_of(&data);
+                self.format_description = Some(data);
+                return Ok(());
+            },
+            // 新文件由 writer 生成 RotateEvent, 不需要 PreviousGtids 等文件级事件
+            ROTATE_EVENT | STOP_EVENT | PREVIOUS_GTIDS_LOG_EVENT | HEARTBEAT_LOG_EVENT | HEARTBEAT_LOG_EVENT_V2 => {
+                return Ok(());
+            },
+            TABLE_MAP_EVENT => {
+                let (table_id, db, table) = self.parse_table_map(&data)?;
+                let key = self.partition_key(&db, Some(&table));
+                self.tables.insert(table_id, (db, table));
+                Some(key)
+            },
+            WRITE_ROWS_EVENT_V1 | UPDATE_ROWS_EVENT_V1 | DELETE_ROWS_EVENT_V1
+            | WRITE_ROWS_EVENT | UPDATE_ROWS_EVENT | DELETE_ROWS_EVENT | PARTIAL_UPDATE_ROWS_EVENT => {
+                let table_id = read_table_id(&data)?;
+                match self.tables.get(&table_id) {
+                    Some((db, table)) => Some(self.partition_key(db, Some(table))),
+                    None => return Err(ReError::String(format!("no TABLE_MAP_EVENT for table_id {}", table_id))),
+                }
+            },
+            QUERY_EVENT => {
+                let (db, query) = self.parse_query(&data)?;
+                match query.trim().to_ascii_uppercase().as_str() {
+                    "BEGIN" => {
+                        self.in_trx = true;
+                        None
+                    },
+                    "COMMIT" | "ROLLBACK" => {
+                        end = true;
+                        None
+                    },
+                    _ => {
+                        end = !self.in_trx;
+                        Some(self.partition_key(&db, None))
+                    },
+                }
+            },
+            XID_EVENT => {
+                end = true;
+                None
+            },
+            _ => None,
+        };
+        self.trx.push(TrxEvent { key, event_type, data });
+
+        if end {
+            self.in_trx = false;
+            self.flush_trx()?;
+        }
+        Ok(())
+    }
+
+    /// 分区名。 Size 模式下只有一个分区
+    fn partition_key(&self, db: &str, table: Option<&str>) -> String {
+        let db = if db.is_empty() { "_" } else { db };
+        match (self.by, table) {
+            (SplitBy::Table, Some(table)) => format!("{}.{}", db, table),
+            (SplitBy::Table, None) | (SplitBy::Db, _) => db.to_string(),
+            (SplitBy::Size(_), _) => String::new(),
+        }
+    }
+
+    fn flush_trx(&mut self) -> CResult<()> {
+        let trx = std::mem::take(&mut self.trx);
+        self.transactions += 1;
+
+        let mut keys: Vec<&String> = Vec::new();
+        for key in trx.iter().filter_map(|e| e.key.as_ref()) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        if let SplitBy::Size(size) = self.by {
+            let bytes: u64 = trx.iter().map(|e| e.data.len() as u64).sum();
+            let writer = self.writer(String::new(), bytes, size)?;
+            for event in &trx {
+                writer.write_event(&event.data)?;
+            }
+            return Ok(());
+        }
+
+        // 拆为多个事务时, 相同的 GTID 不能出现在多个文件中
+        let single = keys.len() == 1;
+        for key in keys {
+            let writer = self.writer(key.clone(), 0, 0)?;
+            for event in &trx {
+                let framing = event.key.is_none();
+                let is_gtid = event.event_type == GTID_LOG_EVENT || event.event_type == ANONYMOUS_GTID_LOG_EVENT;
+                if event.key.as_ref() == Some(key) || (framing && (single || !is_gtid)) {
+                    writer.write_event(&event.data)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 分区当前的文件。 size > 0 且写入 bytes 后超过 size 时切换到下一个文件
+    fn writer(&mut self, key: String, bytes: u64, size: u64) -> CResult<&mut FileBinlogWriter> {
+        let rotate = match self.writers.get(&key) {
+            None => true,
+            Some(w) => size > 0 && w.position() > FIRST_EVENT_POSITION as u64 + EVENT_HEADER_SIZE as u64
+                && w.position() + bytes > size,
+        };
+
+        if rotate {
+            let format_description = match self.format_description.as_ref() {
+                Some(f) => f,
+                None => return Err(ReError::String(String::from("no FORMAT_DESCRIPTION_EVENT before events"))),
+            };
+
+            let seq = self.sequences.entry(key.clone()).or_insert(0);
+            *seq += 1;
+            let file_name = format!("{}.{:06}", OUTPUT_BASENAME, seq);
+            let dir = if key.is_empty() { self.output_dir.clone() } else { self.output_dir.join(sanitize(&key)) };
+            std::fs::create_dir_all(&dir)?;
+
+            if let Some(mut prev) = self.writers.remove(&key) {
+                prev.write_rotate(&file_name)?;
+                prev.flush()?;
+            }
+            let writer = FileBinlogWriter::create(&dir.join(&file_name), format_description)?;
+            self.writers.insert(key.clone(), writer);
+        }
+
+        Ok(self.writers.get_mut(&key).unwrap())
+    }
+
+    /// 返回 (table_id, db, table)
+    fn parse_table_map(&self, data: &[u8]) -> CResult<(u64, String, String)> {
+        let body = self.body(data);
+        let table_id = read_table_id(data)?;
+
+        // table_id(6) flags(2) db_len(1) db \0 table_len(1) table \0
+        let mut pos = 8;
+        let db = read_len_str(body, &mut pos)?;
+        let table = read_len_str(body, &mut pos)?;
+        Ok((table_id, db, table))
+    }
+
+    /// 返回 (db, query)
+    fn parse_query(&self, data: &[u8]) -> CResult<(String, String)> {
+        let body = self.body(data);
+        if body.len() < QUERY_POST_HEADER_LEN {
+            return Err(ReError::String(String::from("invalid QUERY_EVENT")));
+        }
+
+        let db_len = body[8] as usize;
+        let status_len = u16::from_le_bytes([body[11], body[12]]) as usize;
+        let db_start = QUERY_POST_HEADER_LEN + status_len;
+        // db 以 \0 结尾
+        if body.len() < db_start + db_len + 1 {
+            return Err(ReError::String(String::from("invalid QUERY_EVENT")));
+        }
+
+        let db = String::from_utf8_lossy(&body[db_start..db_start + db_len]).to_string();
+        let query = String::from_utf8_lossy(&body[db_start + db_len + 1..]).to_string();
+        Ok((db, query))
+    }
+
+    /// 去掉 header 与 checksum 的 payload
+    fn body<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        let end = data.len() - if self.checksum { 4 } else { 0 };
+        &data[EVENT_HEADER_SIZE..end.max(EVENT_HEADER_SIZE)]
+    }
+}
+
+/// 读取下一个完整事件, 文件结束时返回 None
+fn next_event<R: Read>(reader: &mut R, offset: u64) -> CResult<Option<Vec<u8>>> {
+    let mut header = [0u8; EVENT_HEADER_SIZE];
+    match read_partial(reader, &mut header)? {
+        0 => return Ok(None),
+        n if n < EVENT_HEADER_SIZE => return Err(ReError::String(format!("truncated event at {}", offset))),
+        _ => {},
+    }
+
+    let event_size = u32::from_le_bytes([header[9], header[10], header[11], header[12]]) as usize;
+    if event_size < EVENT_HEADER_SIZE {
+        return Err(ReError::String(format!("invalid event size {} at {}", event_size, offset)));
+    }
+
+    let mut event = vec![0u8; event_size];
+    event[..EVENT_HEADER_SIZE].copy_from_slice(&header);
+    if read_partial(reader, &mut event[EVENT_HEADER_SIZE..])? < event_size - EVENT_HEADER_SIZE {
+        return Err(ReError::String(format!("truncated event at {}", offset)));
+    }
+
+    Ok(Some(event))
+}
+
+/// 行事件与 TableMapEvent 的 payload 以 6 字节的 table_id 开头
+fn read_table_id(data: &[u8]) -> CResult<u64> {
+    if data.len() < EVENT_HEADER_SIZE + 6 {
+        return Err(ReError::String(String::from("invalid rows event")));
+    }
+
+    let mut buf = [0u8; 8];
+    buf[..6].copy_from_slice(&data[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + 6]);
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// len(1) + str + \0
+fn read_len_str(body: &[u8], pos: &mut usize) -> CResult<String> {
+    let len = *body.get(*pos).ok_or_else(|| ReError::String(String::from("invalid TABLE_MAP_EVENT")))? as usize;
+    let start = *pos + 1;
+    if body.len() < start + len + 1 {
+        return Err(ReError::String(String::from("invalid TABLE_MAP_EVENT")));
+    }
+
+    *pos = start + len + 1;
+    Ok(String::from_utf8_lossy(&body[start..start + len]).to_string())
+}
+
+/// 分区名作为目录名
+fn sanitize(key: &str) -> String {
+    key.chars().map(|c| if c.is_alphanumeric() || c == '.' || c == '_' || c == '-' { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    use binlog::binlog_file::BINLOG_MAGIC;
+
+    use crate::cmd::parse::binlog_files;
+    use crate::cmd::split::{sanitize, SplitBy, SplitCommand};
+    use crate::cmd::verify::{verify_events, VerifyCommand};
+
+    /// 构造开启 CRC32 的事件
+    fn event(event_type: u8, payload: &[u8], offset: u64) -> Vec<u8> {
+        let size = 19 + payload.len() + 4;
+        let mut buf = Vec::with_capacity(size);
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.push(event_type);
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&(size as u32).to_le_bytes());
+        buf.extend_from_slice(&((offset + size as u64) as u32).to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.extend_from_slice(payload);
+        let crc = crc32fast::hash(&buf);
+        buf.extend_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    fn query(db: &str, sql: &str) -> Vec<u8> {
+        let mut payload = vec![0u8; 13];
+        payload[8] = db.len() as u8;
+        payload.extend_from_slice(db.as_bytes());
+        payload.push(0);
+        payload.extend_from_slice(sql.as_bytes());
+        payload
+    }
+
+    fn table_map(table_id: u8, db: &str, table: &str) -> Vec<u8> {
+        let mut payload = vec![table_id, 0, 0, 0, 0, 0, 1, 0];
+        for name in [db, table] {
+            payload.push(name.len() as u8);
+            payload.extend_from_slice(name.as_bytes());
+            payload.push(0);
+        }
+        // 1 列, LONG
+        payload.extend_from_slice(&[1, 3, 0, 0]);
+        payload
+    }
+
+    /// 8.0.32 的 binlog, 依次写入 db1.t1、db1.t2、db1.t1 三个事务
+    fn binlog(path: &Path) {
+        let mut fde = vec![0u8; 2 + 50 + 4 + 1];
+        fde[0] = 4;
+        fde[2..8].copy_from_slice(b"8.0.32");
+        fde[56] = 19;
+        fde.push(1);
+
+        let mut payloads = vec![(15, fde)];
+        for (table_id, table) in [(1, "t1"), (2, "t2"), (1, "t1")] {
+            payloads.push((2, query("db1", "BEGIN")));
+            payloads.push((19, table_map(table_id, "db1", table)));
+            payloads.push((30, vec![table_id, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0, 1, 0, 0, 0]));
+            payloads.push((16, 7u64.to_le_bytes().to_vec()));
+        }
+
+        let mut data = BINLOG_MAGIC.to_vec();
+        for (event_type, payload) in payloads {
+            let e = event(event_type, &payload, data.len() as u64);
+            data.extend_from_slice(&e);
+        }
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn events_of(file: &Path) -> u64 {
+        let f = File::open(file).unwrap();
+        let length = f.metadata().unwrap().len();
+        let report = verify_events("binlog", BufReader::new(f), length).unwrap();
+        assert_eq!(report.corruption, None);
+        assert!(report.checksum);
+        report.events
+    }
+
+    /// 拆分后的文件能通过 verify
+    #[test]
+    fn test_split_verify() {
+        let dir = std::env::temp_dir().join(format!("binlog_cli_split_{}", std::process::id()));
+        let source = dir.join("source").join("binlog.000001");
+        binlog(&source);
+        VerifyCommand::new().run(&source).unwrap();
+
+        let output = dir.join("table");
+        SplitCommand::new(SplitBy::Table, Some(output.clone())).run(&source).unwrap();
+        VerifyCommand::new().run(&output.join("db1.t1")).unwrap();
+        VerifyCommand::new().run(&output.join("db1.t2")).unwrap();
+        // FormatDescriptionEvent + 每个事务 4 个事件
+        assert_eq!(events_of(&output.join("db1.t1").join("binlog.000001")), 1 + 2 * 4);
+        assert_eq!(events_of(&output.join("db1.t2").join("binlog.000001")), 1 + 4);
+
+        // 每个事务一个文件, 以 RotateEvent 相连
+        let output = dir.join("size");
+        SplitCommand::new(SplitBy::Size(200), Some(output.clone())).run(&source).unwrap();
+        assert_eq!(binlog_files(&output).unwrap().len(), 3);
+        VerifyCommand::new().run(&output).unwrap();
+        assert_eq!(events_of(&output.join("binlog.000001")), 1 + 4 + 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_split_by() {
+        assert_eq!(SplitBy::new("table", None).unwrap(), SplitBy::Table);
+        assert_eq!(SplitBy::new("size", Some("1KB")).unwrap(), SplitBy::Size(1000));
+        assert!(SplitBy::new("size", None).is_err());
+        assert!(SplitBy::new("gtid", None).is_err());
+    }
+
+    #[test]
+    fn test_partition_key() {
+        let split = SplitCommand::new(SplitBy::Table, None);
+        assert_eq!(split.partition_key("db", Some("t1")), "db.t1");
+        assert_eq!(split.partition_key("db", None), "db");
+        assert_eq!(split.partition_key("", None), "_");
+
+        let split = SplitCommand::new(SplitBy::Db, None);
+        assert_eq!(split.partition_key("db", Some("t1")), "db");
+
+        assert_eq!(sanitize("db/x.t 1"), "db_x.t_1");
+    }
+}
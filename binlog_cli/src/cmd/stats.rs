@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::Serialize;
+
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::events::event_header::Header;
+use binlog::events::log_context::ILogContext;
+use common::config::BinlogConfig;
+use common::err::CResult;
+use common::pretty_util::to_string_pretty;
+use connection::conn::binlog_connection::{BinlogConnection, IBinlogConnection};
+use connection::conn::connection::IConnection;
+use connection::conn::connection_options::ConnectionOptions;
+
+use crate::cli_options::CliOptions;
+use crate::cmd::parse::for_each_file_event;
+
+/// 输出的最大事务数量
+const LARGEST_TRANSACTIONS_LIMIT: usize = 10;
+
+/// 事件大小分布的桶上界(bytes), 最后一个桶为无上界
+const SIZE_BUCKETS: [u64; 6] = [256, 1024, 4 * 1024, 16 * 1024, 64 * 1024, 1024 * 1024];
+
+/// `binlog_cli stats [file-or-dir]` 的统计结果
+#[derive(Debug, Default, Serialize)]
+pub struct BinlogStats {
+    pub events: u64,
+    pub bytes: u64,
+
+    pub events_by_type: BTreeMap<String, u64>,
+    /// key 为 `db.table`
+    pub rows_by_table: BTreeMap<String, TableRows>,
+
+    pub transactions: u64,
+    pub size_histogram: Vec<SizeBucket>,
+
+    /// 事件时间范围, unix 秒。 读取在线 binlog 时事件 header 不可见, 为 None
+    pub start_time: Option<u32>,
+    pub end_time: Option<u32>,
+
+    /// 按大小倒序
+    pub largest_transactions: Vec<TransactionSize>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TableRows {
+    pub insert: u64,
+    pub update: u64,
+    pub delete: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeBucket {
+    /// 桶上界(不含), None 表示无上界
+    pub less_than: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TransactionSize {
+    pub file: String,
+    /// 事务起始位置, 仅在事件 header 可见时存在
+    pub start_pos: Option<u64>,
+    pub bytes: u64,
+    pub events: u64,
+    pub rows: u64,
+}
+
+/// 统计收集器, 按事件顺序调用 add
+#[derive(Debug, Default)]
+pub struct StatsCollector {
+    stats: BinlogStats,
+
+    /// table_id -> db.table
+    tables: HashMap<u64, String>,
+
+    current: Option<TransactionSize>,
+    /// 当前事务是否由 BEGIN 显式开启
+    explicit: bool,
+}
+
+impl StatsCollector {
+    pub fn new() -> Self {
+        let mut collector = StatsCollector::default();
+        collector.stats.size_histogram = SIZE_BUCKETS.iter()
+            .map(|b| Some(*b))
+            .chain(std::iter::once(None))
+            .map(|less_than| SizeBucket { less_than, count: 0 })
+            .collect();
+
+        collector
+    }
+
+    pub fn add(&mut self, file_name: &str, header: Option<&Header>, event: &BinlogEvent) {
+        let len = event.len().max(0) as u64;
+
+        self.stats.events += 1;
+        self.stats.bytes += len;
+        *self.stats.events_by_type.entry(BinlogEvent::get_type_name(event)).or_insert(0) += 1;
+        if let Some(bucket) = self.stats.size_histogram.iter_mut()
+            .find(|b| b.less_than.map_or(true, |l| len < l)) {
+            bucket.count += 1;
+        }
+
+        if let Some(header) = header {
+            let when = header.when;
+            self.stats.start_time = Some(self.stats.start_time.map_or(when, |t| t.min(when)));
+            self.stats.end_time = Some(self.stats.end_time.map_or(when, |t| t.max(when)));
+        }
+
+        let start_pos = header.map(|h| h.get_log_pos().saturating_sub(h.get_event_length() as u64));
+        let rows = match event {
+            BinlogEvent::TableMap(e) => {
+                self.tables.insert(e.table_id, format!("{}.{}", e.get_database_name(), e.get_table_name()));
+                0
+            },
+            BinlogEvent::WriteRows(e) => self.add_rows(e.table_id, e.get_rows().len() as u64, |r, n| r.insert += n),
+            BinlogEvent::UpdateRows(e) => self.add_rows(e.table_id, e.get_rows().len() as u64, |r, n| r.update += n),
+            BinlogEvent::DeleteRows(e) => self.add_rows(e.table_id, e.get_rows().len() as u64, |r, n| r.delete += n),
+            _ => 0,
+        };
+
+        match event {
+            BinlogEvent::GtidLog(_) | BinlogEvent::AnonymousGtidLog(_) => {
+                self.finish();
+                self.begin(file_name, start_pos, false);
+            },
+            BinlogEvent::Query(e) if e.query.eq_ignore_ascii_case("BEGIN") => {
+                if self.current.is_none() {
+                    self.begin(file_name, start_pos, true);
+                }
+                self.explicit = true;
+            },
+            _ => {},
+        }
+
+        if let Some(current) = self.current.as_mut() {
+            current.bytes += len;
+            current.events += 1;
+            current.rows += rows;
+        }
+
+        match event {
+            BinlogEvent::XID(_) => self.finish(),
+            BinlogEvent::Query(e) => {
+                let query = e.query.trim();
+                let commit = query.eq_ignore_ascii_case("COMMIT") || query.eq_ignore_ascii_case("ROLLBACK");
+                // 未显式 BEGIN 的 Query(DDL) 自成一个事务
+                if commit || (!self.explicit && !query.eq_ignore_ascii_case("BEGIN")) {
+                    self.finish();
+                }
+            },
+            _ => {},
+        }
+    }
+
+    pub fn finish_stats(mut self) -> BinlogStats {
+        self.finish();
+        self.stats
+    }
+
+    fn add_rows<F: Fn(&mut TableRows, u64)>(&mut self, table_id: u64, rows: u64, f: F) -> u64 {
+        let table = self.tables.get(&table_id).cloned()
+            .unwrap_or_else(|| format!("table_id:{}", table_id));
+        f(self.stats.rows_by_table.entry(table).or_default(), rows);
+
+        rows
+    }
+
+    fn begin(&mut self, file_name: &str, start_pos: Option<u64>, explicit: bool) {
+        self.current = Some(TransactionSize {
+            file: file_name.to_string(),
+            start_pos,
+            ..TransactionSize::default()
+        });
+        self.explicit = explicit;
+    }
+
+    fn finish(&mut self) {
+        self.explicit = false;
+        let current = match self.current.take() {
+            Some(c) => c,
+            None => return,
+        };
+        self.stats.transactions += 1;
+
+        let largest = &mut self.stats.largest_transactions;
+        let idx = largest.partition_point(|t| t.bytes >= current.bytes);
+        if idx < LARGEST_TRANSACTIONS_LIMIT {
+            largest.insert(idx, current);
+            largest.truncate(LARGEST_TRANSACTIONS_LIMIT);
+        }
+    }
+}
+
+/// `binlog_cli stats`: 统计本地 binlog 文件, 或在未指定文件时读取配置中的 MySQL 直到当前末尾
+#[derive(Debug)]
+pub struct StatsCommand {
+    cli_options: CliOptions,
+}
+
+impl StatsCommand {
+    pub fn new(cli_options: CliOptions) -> Self {
+        StatsCommand {
+            cli_options,
+        }
+    }
+
+    pub fn run_file(&self, path: &Path) -> CResult<()> {
+        let mut collector = StatsCollector::new();
+        for_each_file_event(path, |file_name, header, event| {
+            collector.add(file_name, Some(header), event);
+            Ok(true)
+        })?;
+
+        self.print(collector.finish_stats());
+        Ok(())
+    }
+
+    pub fn run_connection(&self, binlog_config: &BinlogConfig) -> CResult<()> {
+        // 非 blocking 模式, 读取到 EOF 后结束
+        let opts = ConnectionOptions::new(
+            binlog_config.get_host().to_string(),
+            binlog_config.get_port(),
+            binlog_config.username.clone(),
+            binlog_config.password.clone(),
+        );
+        let mut conn = BinlogConnection::new(&opts);
+        conn.try_connect()?;
+
+        let mut collector = StatsCollector::new();
+        let binlogs = conn.binlog(binlog_config.payload_buffer_size)?;
+        for rs in binlogs.get_iter() {
+            let file_name = conn.get_log_context().borrow().get_log_position().get_file_name();
+            for event in rs? {
+                collector.add(&file_name, None, &event);
+            }
+        }
+
+        self.print(collector.finish_stats());
+        Ok(())
+    }
+
+    fn print(&self, stats: BinlogStats) {
+        println!("{}", to_string_pretty(&self.cli_options.get_format(), &stats));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cmd::stats::{StatsCollector, TransactionSize, LARGEST_TRANSACTIONS_LIMIT};
+
+    #[test]
+    fn test_largest_transactions() {
+        let mut collector = StatsCollector::new();
+        for bytes in 0..(LARGEST_TRANSACTIONS_LIMIT as u64 + 5) {
+            collector.current = Some(TransactionSize { bytes, ..TransactionSize::default() });
+            collector.finish();
+        }
+
+        let stats = collector.finish_stats();
+        assert_eq!(stats.transactions, LARGEST_TRANSACTIONS_LIMIT as u64 + 5);
+        assert_eq!(stats.largest_transactions.len(), LARGEST_TRANSACTIONS_LIMIT);
+        assert_eq!(stats.largest_transactions[0].bytes, LARGEST_TRANSACTIONS_LIMIT as u64 + 4);
+        assert_eq!(stats.size_histogram.len(), 7);
+    }
+}
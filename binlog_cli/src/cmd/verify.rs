@@ -0,0 +1,340 @@
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+
+use serde::Serialize;
+
+use binlog::b_type::LogEventType;
+use binlog::binlog_file::{checksum_enabled, has_checksum_alg, BINLOG_MAGIC, CHECKSUM_LEN};
+use common::binlog::{EVENT_HEADER_SIZE, FIRST_EVENT_POSITION};
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::cmd::parse::binlog_files;
+
+/// 单个文件中发现的第一个损坏
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Corruption {
+    /// 损坏事件的起始位置
+    pub offset: u64,
+    pub reason: String,
+}
+
+/// 单个文件的校验结果
+#[derive(Debug, Default, Serialize)]
+pub struct FileReport {
+    pub file_name: String,
+    pub events: u64,
+    /// 已校验通过的字节数
+    pub bytes: u64,
+    pub checksum: bool,
+
+    /// RotateEvent 指向的下一个文件与该事件的起始位置
+    pub next_file: Option<String>,
+    pub rotate_offset: Option<u64>,
+
+    pub corruption: Option<Corruption>,
+}
+
+/// `binlog_cli verify <file-or-dir>`: 遍历全部事件, 校验 CRC32、header 中的 next position、
+/// 文件之间 RotateEvent 的衔接, 并报告第一个损坏的位置
+#[derive(Debug, Default)]
+pub struct VerifyCommand {}
+
+impl VerifyCommand {
+    pub fn new() -> Self {
+        VerifyCommand {}
+    }
+
+    /// 存在损坏时返回错误
+    pub fn run(&self, path: &Path) -> CResult<()> {
+        let files = binlog_files(path)?;
+        if files.is_empty() {
+            return Err(ReError::String(format!("No binlog file found in {:?}", path)));
+        }
+
+        let mut reports: Vec<FileReport> = Vec::with_capacity(files.len());
+        for file in &files {
+            let file_name = file.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let f = File::open(file)?;
+            let length = f.metadata()?.len();
+            let mut report = verify_events(&file_name, BufReader::new(f), length)?;
+
+            // 上一个文件的 RotateEvent 应指向当前文件
+            if let Some(prev) = reports.last_mut() {
+                check_rotate_chain(prev, &file_name);
+            }
+
+            if report.corruption.is_none() && report.events == 0 {
+                report.corruption = Some(Corruption {
+                    offset: FIRST_EVENT_POSITION as u64,
+                    reason: String::from("no event in file"),
+                });
+            }
+            reports.push(report);
+        }
+
+        let mut first_corrupt = None;
+        for report in &reports {
+            match &report.corruption {
+                None => eprintln!("{}: OK, {} events, {} bytes, checksum {}",
+                                  report.file_name, report.events, report.bytes,
+                                  if report.checksum { "CRC32" } else { "NONE" }),
+                Some(c) => {
+                    eprintln!("{}: CORRUPT at offset {}, {} ({} events verified)",
+                              report.file_name, c.offset, c.reason, report.events);
+                    if first_corrupt.is_none() {
+                        first_corrupt = Some((report.file_name.clone(), c.offset));
+                    }
+                },
+            }
+        }
+
+        match first_corrupt {
+            None => {
+                eprintln!("verified {} files, no corruption found.", reports.len());
+                Ok(())
+            },
+            Some((file, offset)) => Err(ReError::ChecksumError(format!("binlog corrupted, first corrupt offset {}:{}", file, offset))),
+        }
+    }
+}
+
+/// 文件末尾缺少 RotateEvent 时 MySQL 可能异常退出过, 只有指向其他文件时才视为损坏
+fn check_rotate_chain(prev: &mut FileReport, file_name: &str) {
+    if prev.corruption.is_some() {
+        return;
+    }
+
+    match (&prev.next_file, prev.rotate_offset) {
+        (Some(next), Some(offset)) if next != file_name => {
+            prev.corruption = Some(Corruption {
+                offset,
+                reason: format!("rotate to {}, but next file is {}", next, file_name),
+            });
+        },
+        (None, _) => eprintln!("{}: WARN no rotate event at the end of file", prev.file_name),
+        _ => {},
+    }
+}
+
+/// 逐个读取事件并校验, 遇到第一个损坏时停止。 只有读取失败(非数据问题)时返回错误。
+/// length 为文件长度, 事件头中的大小超过剩余长度时视为损坏, 不按其分配内存
+pub fn verify_events<R: Read>(file_name: &str, mut reader: R, length: u64) -> CResult<FileReport> {
+    let mut report = FileReport {
+        file_name: file_name.to_string(),
+        ..FileReport::default()
+    };
+
+    let mut magic = [0u8; FIRST_EVENT_POSITION];
+    if !read_full(&mut reader, &mut magic)? || magic != BINLOG_MAGIC {
+        report.corruption = Some(Corruption { offset: 0, reason: String::from("invalid binlog magic header") });
+        return Ok(report);
+    }
+
+    let mut offset = FIRST_EVENT_POSITION as u64;
+    let mut header = [0u8; EVENT_HEADER_SIZE];
+    loop {
+        report.bytes = offset;
+        let corrupt = |reason: String| Some(Corruption { offset, reason });
+
+        match read_partial(&mut reader, &mut header)? {
+            0 => break,
+            n if n < EVENT_HEADER_SIZE => {
+                report.corruption = corrupt(format!("truncated event header, {} of {} bytes", n, EVENT_HEADER_SIZE));
+                break;
+            },
+            _ => {},
+        }
+
+        let event_type = header[4];
+        let event_size = u32::from_le_bytes([header[9], header[10], header[11], header[12]]) as usize;
+        let log_pos = u32::from_le_bytes([header[13], header[14], header[15], header[16]]) as u64;
+
+        let min_size = EVENT_HEADER_SIZE + if report.checksum { CHECKSUM_LEN } else { 0 };
+        if event_size < min_size {
+            report.corruption = corrupt(format!("invalid event size {}", event_size));
+            break;
+        }
+        if event_size as u64 > length.saturating_sub(offset) {
+            report.corruption = corrupt(format!("truncated event, size {} exceeds the remaining {} bytes", event_size, length.saturating_sub(offset)));
+            break;
+        }
+        let mut event = vec![0u8; event_size];
+        event[..EVENT_HEADER_SIZE].copy_from_slice(&header);
+        let n = read_partial(&mut reader, &mut event[EVENT_HEADER_SIZE..])?;
+        if n < event_size - EVENT_HEADER_SIZE {
+            report.corruption = corrupt(format!("truncated event, {} of {} bytes", EVENT_HEADER_SIZE + n, event_size));
+            break;
+        }
+
+        let next_position = offset + event_size as u64;
+        // 部分人工生成的事件 log_pos 为 0
+        if log_pos != 0 && log_pos != next_position {
+            report.corruption = corrupt(format!("next position {} in header, expected {}", log_pos, next_position));
+            break;
+        }
+
+        if report.events == 0 {
+            if event_type != LogEventType::FORMAT_DESCRIPTION_EVENT as u8 {
+                report.corruption = corrupt(format!("first event type {} is not FORMAT_DESCRIPTION_EVENT", event_type));
+                break;
+            }
+            report.checksum = checksum_enabled(&event);
+        } else if report.next_file.is_some() {
+            report.corruption = corrupt(String::from("event after ROTATE_EVENT"));
+            break;
+        }
+
+        if report.checksum || (event_type == LogEventType::FORMAT_DESCRIPTION_EVENT as u8 && has_checksum_alg(&event)) {
+            if let Err(reason) = verify_checksum(&event, report.checksum) {
+                report.corruption = corrupt(reason);
+                break;
+            }
+        }
+
+        if event_type == LogEventType::ROTATE_EVENT as u8 {
+            let end = event_size - if report.checksum { CHECKSUM_LEN } else { 0 };
+            // payload: position(8) + next file name
+            let name_start = EVENT_HEADER_SIZE + 8;
+            if end < name_start {
+                report.corruption = corrupt(String::from("invalid ROTATE_EVENT"));
+                break;
+            }
+            report.next_file = Some(String::from_utf8_lossy(&event[name_start..end]).to_string());
+            report.rotate_offset = Some(offset);
+        }
+
+        report.events += 1;
+        offset = next_position;
+    }
+
+    Ok(report)
+}
+
+/// checksum 为 false 时只在 FormatDescriptionEvent 的 checksum 字段非 0 时校验。
+/// 关闭 checksum 的 5.6 以上版本仍会在 FormatDescriptionEvent 中保留 4 字节的 checksum 字段
+fn verify_checksum(event: &[u8], checksum: bool) -> Result<(), String> {
+    let (data, tail) = event.split_at(event.len() - CHECKSUM_LEN);
+    let expected = u32::from_le_bytes([tail[0], tail[1], tail[2], tail[3]]);
+    if !checksum && expected == 0 {
+        return Ok(());
+    }
+
+    let actual = crc32fast::hash(data);
+    if actual != expected {
+        return Err(format!("CRC32 mismatch, expected {:#010x}, actual {:#010x}", expected, actual));
+    }
+    Ok(())
+}
+
+/// 读满 buf, 返回实际读取的字节数。 少于 buf 长度表示到达文件末尾
+pub(crate) fn read_partial<R: Read>(reader: &mut R, buf: &mut [u8]) -> CResult<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(ReError::from(e)),
+        }
+    }
+
+    Ok(read)
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> CResult<bool> {
+    Ok(read_partial(reader, buf)? == buf.len())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use binlog::binlog_file::BINLOG_MAGIC;
+
+    use crate::cmd::verify::verify_events;
+
+    /// 构造事件, checksum 为 true 时追加 CRC32
+    fn event(event_type: u8, payload: &[u8], offset: u64, checksum: bool) -> Vec<u8> {
+        let size = 19 + payload.len() + if checksum { 4 } else { 0 };
+        let mut buf = Vec::with_capacity(size);
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.push(event_type);
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&(size as u32).to_le_bytes());
+        buf.extend_from_slice(&((offset + size as u64) as u32).to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf.extend_from_slice(payload);
+        if checksum {
+            let crc = crc32fast::hash(&buf);
+            buf.extend_from_slice(&crc.to_le_bytes());
+        }
+        buf
+    }
+
+    /// 开启 CRC32 的 binlog: FormatDescriptionEvent + RotateEvent
+    fn binlog() -> Vec<u8> {
+        let mut fde = vec![0u8; 2 + 50 + 4 + 1];
+        fde[0] = 4;
+        fde[2..8].copy_from_slice(b"8.0.32");
+        fde[56] = 19;
+        fde.push(1);
+
+        let mut rotate = 4u64.to_le_bytes().to_vec();
+        rotate.extend_from_slice(b"binlog.000002");
+
+        let mut data = BINLOG_MAGIC.to_vec();
+        let fde = event(15, &fde, 4, true);
+        let rotate = event(4, &rotate, 4 + fde.len() as u64, true);
+        data.extend_from_slice(&fde);
+        data.extend_from_slice(&rotate);
+        data
+    }
+
+    #[test]
+    fn test_verify_events() {
+        let data = binlog();
+        let report = verify_events("binlog.000001", Cursor::new(&data), data.len() as u64).unwrap();
+        assert_eq!(report.corruption, None);
+        assert!(report.checksum);
+        assert_eq!(report.events, 2);
+        assert_eq!(report.bytes, data.len() as u64);
+        assert_eq!(report.next_file.as_deref(), Some("binlog.000002"));
+    }
+
+    #[test]
+    fn test_verify_corruption() {
+        let data = binlog();
+        let fde_len = data.len() - (19 + 8 + 13 + 4) - 4;
+        let rotate_offset = (4 + fde_len) as u64;
+
+        // 篡改 RotateEvent 的 payload
+        let mut damaged = data.clone();
+        let last = damaged.len() - 5;
+        damaged[last] ^= 0xff;
+        let report = verify_events("binlog.000001", Cursor::new(&damaged), damaged.len() as u64).unwrap();
+        let corruption = report.corruption.unwrap();
+        assert_eq!(corruption.offset, rotate_offset);
+        assert!(corruption.reason.contains("CRC32"));
+        assert_eq!(report.events, 1);
+
+        // 截断
+        let report = verify_events("binlog.000001", Cursor::new(&data[..data.len() - 3]), data.len() as u64 - 3).unwrap();
+        assert_eq!(report.corruption.unwrap().offset, rotate_offset);
+
+        let report = verify_events("binlog.000001", Cursor::new(&data[1..]), data.len() as u64 - 1).unwrap();
+        assert_eq!(report.corruption.unwrap().offset, 0);
+
+        // 事件头中的大小远超文件长度
+        let mut damaged = data.clone();
+        let size_at = rotate_offset as usize + 9;
+        damaged[size_at..size_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let report = verify_events("binlog.000001", Cursor::new(&damaged), damaged.len() as u64).unwrap();
+        let corruption = report.corruption.unwrap();
+        assert_eq!(corruption.offset, rotate_offset);
+        assert!(corruption.reason.contains("exceeds"));
+    }
+}
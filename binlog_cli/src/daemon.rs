@@ -0,0 +1,301 @@
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use common::server::PauseSwitch;
+use relay_log::apply::replay_progress::ReplayStatus;
+
+/// 标记当前进程为 --daemon 重新启动的后台进程
+const DAEMON_ENV: &str = "BINLOG_CLI_DAEMON";
+
+const DEFAULT_PID_FILE: &str = "binlog_cli.pid";
+
+/// --stop 等待进程退出的最长时间
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 收到退出信号后等待正常退出的时间, 超时后强制退出
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// pid 文件路径, 默认为临时目录下的 binlog_cli.pid
+pub fn pid_file_path(pid_file: Option<&PathBuf>) -> PathBuf {
+    pid_file.cloned().unwrap_or_else(|| std::env::temp_dir().join(DEFAULT_PID_FILE))
+}
+
+/// 当前进程是否为 --daemon 启动的后台进程
+pub fn is_daemon_process() -> bool {
+    std::env::var_os(DAEMON_ENV).is_some()
+}
+
+/// 以相同的参数(去掉 --daemon)在后台重新启动自身, 标准输出与错误输出写入 `<pid_file>.out`。 返回后台进程的 pid
+pub fn spawn_daemon(pid_file: &Path) -> CResult<u32> {
+    if let Some(pid) = running_pid(pid_file)? {
+        return Err(ReError::String(format!("binlog_cli is already running, pid {}", pid)));
+    }
+
+    let out = OpenOptions::new().create(true).append(true).open(pid_file.with_extension("out"))?;
+    let args: Vec<String> = std::env::args().skip(1).filter(|a| a != "--daemon").collect();
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command.args(args)
+        .env(DAEMON_ENV, "1")
+        .stdin(Stdio::null())
+        .stdout(out.try_clone()?)
+        .stderr(out);
+    detach(&mut command);
+
+    let child = command.spawn()?;
+    Ok(child.id())
+}
+
+/// 脱离当前终端的会话, 终端关闭或 Ctrl+C 不影响后台进程
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    // setsid 是 async-signal-safe 的, 可以在 fork 与 exec 之间调用
+    unsafe {
+        command.pre_exec(|| nix::unistd::setsid().map(|_| ()).map_err(std::io::Error::from));
+    }
+}
+
+#[cfg(not(unix))]
+fn detach(_command: &mut Command) {}
+
+/// pid 文件, drop 时删除
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// 写入当前进程的 pid。 已有运行中的实例时返回错误, 残留的 pid 文件会被覆盖
+    pub fn create(path: PathBuf) -> CResult<Self> {
+        if let Some(pid) = running_pid(&path)? {
+            if pid != std::process::id() {
+                return Err(ReError::String(format!("binlog_cli is already running, pid {}", pid)));
+            }
+        }
+
+        std::fs::write(&path, std::process::id().to_string())?;
+        Ok(PidFile { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// 读取 pid 文件中仍在运行的进程。 文件不存在或进程已退出时返回 None
+pub fn running_pid(pid_file: &Path) -> CResult<Option<u32>> {
+    if !pid_file.exists() {
+        return Ok(None);
+    }
+
+    let pid: u32 = std::fs::read_to_string(pid_file)?.trim().parse()?;
+    Ok(if is_alive(pid) { Some(pid) } else { None })
+}
+
+/// `binlog_cli status`
+pub fn status(pid_file: &Path) -> CResult<bool> {
+    match running_pid(pid_file)? {
+        Some(pid) => {
+            println!("binlog_cli is running, pid {} ({:?})", pid, pid_file);
+            Ok(true)
+        },
+        None => {
+            println!("binlog_cli is not running ({:?})", pid_file);
+            Ok(false)
+        },
+    }
+}
+
+/// `binlog_cli status --replay-status-dir DIR`: 打印回放任务写入的进度
+pub fn replay_status(status_dir: &Path) -> CResult<()> {
+    let statuses = ReplayStatus::load_dir(&status_dir.to_string_lossy())?;
+    if statuses.is_empty() {
+        println!("no relay log apply status in {:?}", status_dir);
+    }
+    for status in statuses {
+        println!("{}", status);
+    }
+    Ok(())
+}
+
+/// `binlog_cli --stop`: 向运行中的实例发送 SIGTERM, 并等待其退出
+pub fn stop(pid_file: &Path) -> CResult<()> {
+    let pid = match running_pid(pid_file)? {
+        Some(pid) => pid,
+        None => return Err(ReError::String(format!("binlog_cli is not running ({:?})", pid_file))),
+    };
+
+    terminate(pid)?;
+    eprintln!("stopping binlog_cli, pid {}", pid);
+
+    let begin = Instant::now();
+    while is_alive(pid) {
+        if begin.elapsed() > STOP_TIMEOUT {
+            return Err(ReError::String(format!("binlog_cli pid {} did not stop in {:?}", pid, STOP_TIMEOUT)));
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    eprintln!("binlog_cli stopped");
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
+
+    // 信号 0 只检查进程是否存在
+    kill(Pid::from_raw(pid as i32), None).is_ok()
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> CResult<()> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    kill(Pid::from_raw(pid as i32), Signal::SIGTERM)
+        .map_err(|e| ReError::String(format!("send SIGTERM to {} error: {}", pid, e)))
+}
+
+/// `binlog_cli --pause` / `--resume`: 向运行中的实例发送 SIGUSR1 / SIGUSR2
+pub fn pause(pid_file: &Path, pause: bool) -> CResult<()> {
+    let pid = match running_pid(pid_file)? {
+        Some(pid) => pid,
+        None => return Err(ReError::String(format!("binlog_cli is not running ({:?})", pid_file))),
+    };
+
+    notify_pause(pid, pause)?;
+    eprintln!("{} binlog_cli, pid {}", if pause { "pausing" } else { "resuming" }, pid);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn notify_pause(pid: u32, pause: bool) -> CResult<()> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    let signal = if pause { Signal::SIGUSR1 } else { Signal::SIGUSR2 };
+    kill(Pid::from_raw(pid as i32), signal)
+        .map_err(|e| ReError::String(format!("send {} to {} error: {}", signal, pid, e)))
+}
+
+#[cfg(not(unix))]
+fn notify_pause(pid: u32, _pause: bool) -> CResult<()> {
+    Err(ReError::String(format!("--pause / --resume is not supported on this platform, pid {}", pid)))
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn terminate(pid: u32) -> CResult<()> {
+    Err(ReError::String(format!("--stop is not supported on this platform, pid {}", pid)))
+}
+
+/// 收到 SIGTERM / SIGINT 后置位 shutdown, 由 CliClient 在事件之间检查并正常退出。
+/// 阻塞在网络读取上无法及时退出时, 超过 SHUTDOWN_GRACE 后删除 pid 文件并强制退出
+pub fn install_shutdown_handler(shutdown: Arc<AtomicBool>, pid_file: Option<PathBuf>) {
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        eprintln!("received shutdown signal, stopping...");
+        shutdown.store(true, Ordering::SeqCst);
+
+        tokio::time::sleep(SHUTDOWN_GRACE).await;
+        warn!("graceful shutdown timeout after {:?}, exit", SHUTDOWN_GRACE);
+        if let Some(path) = pid_file {
+            let _ = std::fs::remove_file(path);
+        }
+        std::process::exit(1);
+    });
+}
+
+/// 收到 SIGUSR1 时暂停读取, SIGUSR2 时恢复, 用于下游维护期间暂停消费而不断开连接
+#[cfg(unix)]
+pub fn install_pause_handler(pause: PauseSwitch) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (mut usr1, mut usr2) = match (signal(SignalKind::user_defined1()), signal(SignalKind::user_defined2())) {
+        (Ok(usr1), Ok(usr2)) => (usr1, usr2),
+        _ => {
+            warn!("install SIGUSR1 / SIGUSR2 handler failed, pause is disabled");
+            return;
+        },
+    };
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = usr1.recv() => {
+                    eprintln!("received SIGUSR1, pausing...");
+                    pause.pause();
+                },
+                _ = usr2.recv() => {
+                    eprintln!("received SIGUSR2, resuming...");
+                    pause.resume();
+                },
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn install_pause_handler(_pause: PauseSwitch) {
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            tokio::select! {
+                _ = term.recv() => {},
+                _ = tokio::signal::ctrl_c() => {},
+            }
+        },
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        },
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::daemon::{running_pid, PidFile};
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join(format!("binlog_cli_test_{}.pid", std::process::id()));
+
+        let pid_file = PidFile::create(path.clone()).unwrap();
+        assert_eq!(running_pid(&path).unwrap(), Some(std::process::id()));
+
+        drop(pid_file);
+        assert!(!path.exists());
+        assert_eq!(running_pid(&path).unwrap(), None);
+    }
+}
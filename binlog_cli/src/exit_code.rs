@@ -0,0 +1,90 @@
+use std::process::ExitCode;
+
+use serde::Serialize;
+
+use common::err::decode_error::{ErrorCategory, ReError};
+
+/// 进程退出码, 供编排脚本按失败原因分支。 2 为 clap 的参数错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CliExitCode {
+    Error = 1,
+    Config = 3,
+    Auth = 4,
+    /// 连接失败或连接中断
+    Connection = 5,
+    Checksum = 6,
+    Sink = 7,
+}
+
+impl CliExitCode {
+    pub fn of(error: &ReError) -> Self {
+        match error.category() {
+            ErrorCategory::Config => CliExitCode::Config,
+            ErrorCategory::Auth => CliExitCode::Auth,
+            ErrorCategory::Connection => CliExitCode::Connection,
+            ErrorCategory::Checksum => CliExitCode::Checksum,
+            ErrorCategory::Sink => CliExitCode::Sink,
+            _ => CliExitCode::Error,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CliExitCode::Error => "error",
+            CliExitCode::Config => "config",
+            CliExitCode::Auth => "auth",
+            CliExitCode::Connection => "connection",
+            CliExitCode::Checksum => "checksum",
+            CliExitCode::Sink => "sink",
+        }
+    }
+}
+
+/// `--error-format json` 的输出。 code 为退出码, error_code 为 ReError::code
+#[derive(Debug, Serialize)]
+struct ErrorReport<'a> {
+    code: u8,
+    kind: &'a str,
+    error_code: u16,
+    retryable: bool,
+    message: String,
+}
+
+/// 按 --error-format 将最终的错误输出到标准错误, 返回对应的退出码
+pub fn report(error: &ReError, error_format: &str) -> ExitCode {
+    let code = CliExitCode::of(error);
+    if error_format == "json" {
+        let report = ErrorReport {
+            code: code as u8,
+            kind: code.kind(),
+            error_code: error.code(),
+            retryable: error.is_retryable(),
+            message: error.to_string(),
+        };
+        eprintln!("{}", serde_json::to_string(&report).unwrap_or_default());
+    } else {
+        eprintln!("Error({} {}): {}", code.kind(), error.code(), error);
+    }
+
+    ExitCode::from(code as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use common::err::decode_error::ReError;
+
+    use crate::exit_code::CliExitCode;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(CliExitCode::of(&ReError::ConfigFileParseErr(String::new())), CliExitCode::Config);
+        assert_eq!(CliExitCode::of(&ReError::AuthError(String::new())), CliExitCode::Auth);
+        assert_eq!(CliExitCode::of(&ReError::IoError(io::Error::from(io::ErrorKind::UnexpectedEof))), CliExitCode::Connection);
+        assert_eq!(CliExitCode::of(&ReError::IoError(io::Error::from(io::ErrorKind::NotFound))), CliExitCode::Error);
+        assert_eq!(CliExitCode::of(&ReError::SinkError(String::new())) as u8, 7);
+        // 按添加上下文之前的错误分类
+        assert_eq!(CliExitCode::of(&ReError::AuthError(String::new()).context("connect 127.0.0.1:3306")), CliExitCode::Auth);
+    }
+}
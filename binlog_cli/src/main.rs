@@ -1,20 +1,61 @@
+mod checkpoint;
 mod cli_client;
 mod cli_options;
-
+mod cmd;
+mod daemon;
+mod exit_code;
+mod metrics;
+mod output;
+mod range;
+mod sink;
+
+use std::collections::BTreeMap;
 use std::env::current_dir;
 use std::fmt::{Debug};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use clap::{Args, Parser, Subcommand};
 use serde::Serialize;
+use tokio::sync::watch;
+use tracing::{error, warn};
+use binlog::row::masking::MaskingEngine;
 use connection::binlog::lifecycle::lifecycle::BinlogLifecycle;
-use common::config::{BinlogConfig, FConfig, read_config};
+use common::config::{BinlogConfig, FConfig, RcMetadata, read_config_with_profile, RepConfig, ReplicateConfig, SinkConfig};
+use common::config::config_watcher::{ConfigUpdate, ConfigWatcher};
+use common::config::secret::resolve_secret;
 use common::config::load_style::Format;
 use common::err::CResult;
+use common::err::decode_error::ReError;
 use common::log::tracing_factory::{OutputType, TracingFactory, TracingFactoryOptions};
-use common::pretty_util::to_string_pretty;
-use common::server::{Server};
+use common::memory_governor::MemoryGovernor;
+use common::schema::mysql_metadata::spawn_metadata_refresh;
+use common::pretty_util::{parse_bytes_len, parse_duration, to_string_pretty};
+use common::server::{PauseSwitch, Server, ShutdownHandle};
+use common::time_util::{TimestampOutput, TimeZoneSpec};
+use crate::checkpoint::CheckpointStore;
 use crate::cli_client::{CliClient};
+use connection::binlog::replication_filter::ReplicationFilter;
+use connection::conn::async_connection::AsyncConnection;
+use connection::conn::connection_options::ConnectionOptions;
 use crate::cli_options::CliOptions;
+use crate::cmd::check::CheckCommand;
+use crate::cmd::encrypt_password::EncryptPasswordCommand;
+use crate::cmd::flashback::FlashbackCommand;
+use crate::cmd::init_config::InitConfigCommand;
+use crate::cmd::parse::ParseCommand;
+use crate::cmd::split::{SplitBy, SplitCommand};
+use crate::cmd::stats::StatsCommand;
+use crate::cmd::verify::VerifyCommand;
+use crate::daemon::PidFile;
+use crate::metrics::CliMetrics;
+use crate::output::{create_formatter, OutputFormat, TimestampZone};
+use crate::output::sql::{DdlFilter, SqlFormatter};
+use crate::range::EventRange;
+use crate::sink::{create_sink, EventSink, SinkOptions, SinkType};
+use crate::sink::stdout::StdoutSink;
+use common::throttle::RateLimiter;
 
 #[derive(Parser, Serialize, Debug, Clone)]
 #[command(name = "cdc-cli")]
@@ -30,11 +71,45 @@ pub(crate) struct CliArgs {
     #[arg(short, long, help = "Path to loaded configuration file", value_name = "FILE")]
     pub config: Option<PathBuf>,
 
+    /// 配置文件中的 [profile.<name>], 未指定时使用环境变量 REPLAYER_PROFILE
+    #[arg(long, help = "config profile to apply, e.g. dev | prod. default: $REPLAYER_PROFILE", value_name = "PROFILE")]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 
-    #[arg(long, help = "shut down binlog cli")]
-    pub stop: Option<bool>,
+    /// 向 pid 文件中的实例发送 SIGTERM, 等待其正常退出
+    #[arg(long, help = "shut down the running binlog cli", default_value_t = false)]
+    pub stop: bool,
+
+    /// 向 pid 文件中的实例发送 SIGUSR1, 暂停读取但保留连接, 由 --resume 恢复
+    #[arg(long, help = "pause the running binlog cli without closing connections", default_value_t = false, conflicts_with = "resume")]
+    pub pause: bool,
+
+    #[arg(long, help = "resume the paused binlog cli", default_value_t = false)]
+    pub resume: bool,
+
+    /// 以相同参数在后台重新启动, 输出写入 `<pid_file>.out`
+    #[arg(long, help = "run in background", default_value_t = false)]
+    pub daemon: bool,
+
+    /// 失败时的退出码: 3 配置错误, 4 认证失败, 5 连接失败或中断, 6 binlog 校验失败, 7 sink 写入失败
+    #[arg(long = "error-format", help = "format of the final error on stderr: [text | json]", value_parser = ["text", "json"], default_value = "text")]
+    pub error_format: String,
+
+    /// 记录最后输出的事务位置, 重启时从该位置继续
+    #[arg(long = "checkpoint-file", help = "checkpoint file of the last delivered position, default ./binlog_cli.checkpoint", value_name = "FILE")]
+    pub checkpoint_file: Option<PathBuf>,
+
+    #[arg(long = "no-resume", help = "do not resume from the checkpoint file on startup", default_value_t = false)]
+    pub no_resume: bool,
+
+    /// 在 0.0.0.0:PORT/metrics 暴露 Prometheus 指标
+    #[arg(long = "metrics-port", help = "expose Prometheus metrics on http://0.0.0.0:PORT/metrics", value_name = "PORT")]
+    pub metrics_port: Option<u16>,
+
+    #[arg(long = "pid-file", help = "pid file for --daemon / --stop / --pause / --resume / status, default $TMPDIR/binlog_cli.pid", value_name = "FILE")]
+    pub pid_file: Option<PathBuf>,
 
     ///////////////////////////////////////////////////
     // Cli Options //
@@ -43,9 +118,51 @@ pub(crate) struct CliArgs {
     #[arg(short, long, help = "enable debug mode", default_value_t = false)]
     pub debug: bool,
 
-    #[arg(short, long, help = "output format: [yaml | json], default Yaml", default_value = "yaml")]
+    #[arg(short, long, help = "output format: [yaml | json | ndjson | csv | sql | avro | table | markdown], default Yaml", default_value = "yaml")]
     pub format: String,
 
+    /// 类似 tail -f: 文件读到末尾后等待新内容与新文件; 读取 MySQL 时断线重连并继续。 -f 已被 --format 占用
+    #[arg(long, help = "keep reading after EOF (files) or reconnect on errors (MySQL), like tail -f", default_value_t = false)]
+    pub follow: bool,
+
+    /// csv / avro 等按表输出文件的格式使用的目录
+    #[arg(long = "output-dir", help = "output directory for csv/avro files, default current dir", value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    /// csv / avro / table / markdown 中 TIMESTAMP 列的输出时区, source 为源库会话的 time_zone(QueryEvent 或 [binlog] time_zone)
+    #[arg(long = "timestamp-zone", help = "zone of TIMESTAMP columns in csv/avro/table/markdown: [epoch | utc | source | SYSTEM | +HH:MM | Area/City]", default_value = "epoch", value_name = "ZONE")]
+    pub timestamp_zone: String,
+
+    /// 令牌桶限速, 回放历史 binlog 时避免压垮下游
+    #[arg(long = "max-events-per-sec", help = "throttle reading to at most N events per second", value_name = "N")]
+    pub max_events_per_sec: Option<u64>,
+
+    #[arg(long = "max-bytes-per-sec", help = "throttle reading to at most SIZE of events per second, e.g. 10MB", value_name = "SIZE")]
+    pub max_bytes_per_sec: Option<String>,
+
+    ///////////////////////////////////////////////////
+    // Sink Options //
+    ///////////////////////////////////////////////////
+    /// yaml/json/ndjson/sql 的输出目标
+    #[arg(long, help = "where to deliver events: [stdout | file | kafka], default [sink] in config or stdout")]
+    pub sink: Option<String>,
+
+    #[arg(long = "sink-path", help = "file sink: output file", value_name = "FILE")]
+    pub sink_path: Option<PathBuf>,
+
+    #[arg(long = "sink-rotate-size", help = "file sink: rotate when the file exceeds the size, e.g. 100MB", value_name = "SIZE")]
+    pub sink_rotate_size: Option<String>,
+
+    #[arg(long = "sink-rotate-interval", help = "file sink: rotate after the interval, e.g. 30m, 1h, 1d", value_name = "DURATION")]
+    pub sink_rotate_interval: Option<String>,
+
+    #[arg(long = "kafka-brokers", help = "kafka sink: bootstrap servers, e.g. host1:9092,host2:9092", value_name = "BROKERS")]
+    pub kafka_brokers: Option<String>,
+
+    /// 支持 `{db}`、`{table}` 占位符
+    #[arg(long = "kafka-topic", help = "kafka sink: topic template, default binlog.{db}", value_name = "TOPIC")]
+    pub kafka_topic: Option<String>,
+
     ///////////////////////////////////////////////////
     // Binlog Options //
     ///////////////////////////////////////////////////
@@ -58,9 +175,49 @@ pub(crate) struct CliArgs {
     #[arg(short, long = "username", help = "mysql username", value_name = "username")]
     pub username: Option<String>,
 
-    #[arg(short, long = "password", help = "mysql password", value_name = "password")]
+    #[arg(short, long = "password", help = "mysql password, or env:VAR | file:path | exec:cmd | enc:<encrypted>", value_name = "password")]
     pub password: Option<String>,
 
+    ///////////////////////////////////////////////////
+    // Filter Options //
+    ///////////////////////////////////////////////////
+    /// 逗号分隔的 `db.table`, 支持 `*`、`?` 通配符, `re:` 前缀表示正则
+    #[arg(long = "include-tables", help = "comma separated db.table to include, glob (db.t_*) or regex (re:db\\.t\\d+)", value_name = "TABLES")]
+    pub include_tables: Option<String>,
+
+    #[arg(long = "exclude-tables", help = "comma separated db.table to exclude, glob (db.t_*) or regex (re:db\\.t\\d+)", value_name = "TABLES")]
+    pub exclude_tables: Option<String>,
+
+    #[arg(long = "include-dbs", help = "comma separated databases to include", value_name = "DBS")]
+    pub include_dbs: Option<String>,
+
+    #[arg(long = "exclude-dbs", help = "comma separated databases to exclude", value_name = "DBS")]
+    pub exclude_dbs: Option<String>,
+
+    /// GTID 集合, 如 `uuid:1-100,uuid2:5`
+    #[arg(long = "include-gtids", help = "only output transactions in the GTID set, e.g. uuid:1-100", value_name = "GTID_SET")]
+    pub include_gtids: Option<String>,
+
+    #[arg(long = "exclude-gtids", help = "skip transactions in the GTID set, e.g. uuid:1-100", value_name = "GTID_SET")]
+    pub exclude_gtids: Option<String>,
+
+    ///////////////////////////////////////////////////
+    // Range Options //
+    ///////////////////////////////////////////////////
+    /// 同 mysqlbinlog。 读取 MySQL 时需要在配置中指定 binlog file
+    #[arg(long = "start-position", help = "start reading at the first event having a position >= N", value_name = "N")]
+    pub start_position: Option<u64>,
+
+    #[arg(long = "stop-position", help = "stop reading at the first event having a position >= N", value_name = "N")]
+    pub stop_position: Option<u64>,
+
+    /// 本地时间
+    #[arg(long = "start-datetime", help = "start reading at the first event having a timestamp >= 'YYYY-MM-DD HH:MM:SS'", value_name = "DATETIME")]
+    pub start_datetime: Option<String>,
+
+    #[arg(long = "stop-datetime", help = "stop reading at the first event having a timestamp >= 'YYYY-MM-DD HH:MM:SS'", value_name = "DATETIME")]
+    pub stop_datetime: Option<String>,
+
 
     ///////////////////////////////////////////////////
     // Just for test //
@@ -73,34 +230,222 @@ enum Commands {
     // Usage: binlog_cli timestamp <TIMESTAMP>
     Timestamp {
         timestamp: String
-    }
+    },
+
+    // Usage: binlog_cli init-config [PATH] [--force]
+    /// 生成带注释的配置文件, 默认 ./conf/replayer.toml
+    InitConfig {
+        path: Option<PathBuf>,
+
+        #[arg(long, help = "overwrite the existing file", default_value_t = false)]
+        force: bool,
+    },
+
+    // Usage: REPLAYER_MASTER_KEY=<KEY> binlog_cli encrypt-password [PASSWORD]
+    /// 使用主密钥(REPLAYER_MASTER_KEY) 加密密码, 输出可写入配置的 enc: 值。 未指定时从标准输入读取
+    EncryptPassword {
+        password: Option<String>,
+    },
+
+    // Usage: binlog_cli check
+    /// 检查 MySQL 是否满足运行条件: 复制权限、binlog_format、gtid_mode、server_id 与 binlog 保留时间
+    Check,
+
+    // Usage: binlog_cli status [--replay-status-dir DIR]
+    /// 查看后台实例是否在运行, 以及各回放任务的进度、吞吐量与预计追平时间
+    Status {
+        #[arg(long = "replay-status-dir", help = "status dir written by relay log apply tasks", value_name = "DIR")]
+        replay_status_dir: Option<PathBuf>,
+    },
+
+    // Usage: binlog_cli parse <FILE_OR_DIR | ->
+    /// 不连接 MySQL, 解析本地的 binlog 文件或目录。 `-` 从标准输入读取, 如 ssh host 'cat binlog.000001' | binlog_cli parse -
+    Parse {
+        path: PathBuf
+    },
+
+    // Usage: binlog_cli stats [FILE_OR_DIR]
+    /// 统计事件类型、各表行数、事务数量与大小分布等。 未指定文件时读取配置中的 MySQL
+    Stats {
+        path: Option<PathBuf>
+    },
+
+    // Usage: binlog_cli verify <FILE_OR_DIR>
+    /// 校验 binlog 文件的完整性: CRC32、事件位置与 RotateEvent 衔接, 报告第一个损坏的位置
+    Verify {
+        path: PathBuf
+    },
+
+    // Usage: binlog_cli --output-dir <DIR> split <FILE_OR_DIR> --by table|db|size [--size 100MB]
+    /// 按表、库或大小将 binlog 拆分为多个合法的 binlog 文件
+    Split {
+        path: PathBuf,
+
+        #[arg(long, help = "split by: [table | db | size]", default_value = "table")]
+        by: String,
+
+        #[arg(long, help = "max size of each file for --by size, e.g. 100MB", value_name = "SIZE")]
+        size: Option<String>,
+    },
+
+    // Usage: binlog_cli --start-position X --stop-position Y flashback <FILE_OR_DIR> --table db.t
+    /// 为指定表的行变更生成反向 SQL, 最新的变更在前
+    Flashback {
+        path: PathBuf,
+
+        #[arg(long, help = "comma separated db.table to flashback", value_name = "TABLES")]
+        table: String,
+    },
+
+    // Usage: binlog_cli to-sql <FILE_OR_DIR> [--no-trx] [--no-ddl | --only-ddl]
+    /// 将行事件还原为可重放的 SQL
+    ToSql {
+        path: PathBuf,
+
+        #[arg(long = "no-trx", help = "do not output BEGIN / COMMIT", default_value_t = false)]
+        no_trx: bool,
+
+        #[arg(long = "no-ddl", help = "skip DDL statements", default_value_t = false, conflicts_with = "only_ddl")]
+        no_ddl: bool,
+
+        #[arg(long = "only-ddl", help = "output DDL statements only", default_value_t = false)]
+        only_ddl: bool,
+    },
 }
 
 #[tokio::main]
-async fn main() -> CResult<()> {
+async fn main() -> ExitCode {
     let args = CliArgs::parse();
+    let error_format = args.error_format.clone();
+
+    match run(args).await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => exit_code::report(&e, &error_format),
+    }
+}
+
+async fn run(mut args: CliArgs) -> CResult<()> {
+    let pid_file = daemon::pid_file_path(args.pid_file.as_ref());
+    if args.stop {
+        return daemon::stop(&pid_file);
+    }
+    if args.pause || args.resume {
+        return daemon::pause(&pid_file, args.pause);
+    }
+    if let Some(Commands::InitConfig { path, force }) = &args.command {
+        return InitConfigCommand::new(path.clone(), *force).run();
+    }
+    if let Some(Commands::EncryptPassword { password }) = &args.command {
+        return EncryptPasswordCommand::new(password.clone()).run();
+    }
+    if let Some(Commands::Status { replay_status_dir }) = &args.command {
+        daemon::status(&pid_file)?;
+        if let Some(dir) = replay_status_dir {
+            daemon::replay_status(dir)?;
+        }
+        return Ok(());
+    }
+    if let Some(Commands::Verify { path }) = &args.command {
+        return VerifyCommand::new().run(path);
+    }
+    if let Some(Commands::Split { path, by, size }) = &args.command {
+        let by = SplitBy::new(by, size.as_deref())?;
+        return SplitCommand::new(by, args.output_dir.clone()).run(path);
+    }
+    if args.daemon && !daemon::is_daemon_process() {
+        let pid = daemon::spawn_daemon(&pid_file)?;
+        eprintln!("binlog_cli started in background, pid {}, pid file {:?}", pid, pid_file);
+        return Ok(());
+    }
+
     let format = Format::format(&args.format);
+    let output_format = OutputFormat::try_from(args.format.as_str())?;
     eprintln!("args: \n{} ", to_string_pretty(&format, &args));
 
-    let config = load_config(&args);
-    let rep_config = config.get_config();
+    let config = load_config(&args)?;
+    let mut rep_config = config.get_config();
     eprintln!("load config: \n{}", to_string_pretty(&format, &rep_config));;
+    // 输出配置之后再解析密码, 避免明文出现在输出中
+    rep_config.resolve_secrets()?;
 
-    let log_opt = TracingFactoryOptions::new(args.debug, OutputType::LOG, rep_config.base.get_log_dir());
+    let log_opt = TracingFactoryOptions::new(args.debug, OutputType::LOG, rep_config.base.get_log_dir())
+        .with_base_config(&rep_config.base)?;
     let log_factory = TracingFactory::init_log_with_options(log_opt);
     // TracingFactory::init_log(args.debug);
     eprintln!("log_dir: {:?}", log_factory.get_log_dir());
+    MemoryGovernor::configure(&rep_config.base)?;
 
     let mut binlog_config = rep_config.binlog;
+    let sink_config = rep_config.sink;
+    let masking_rules = rep_config.masking;
+
+    // [rate_limit] 作为 --max-events-per-sec / --max-bytes-per-sec 的默认值; 命令行指定时不随配置文件热加载
+    let rate_limit_pinned = args.max_events_per_sec.is_some() || args.max_bytes_per_sec.is_some();
+    if let Some(rate_limit) = rep_config.rate_limit {
+        args.max_events_per_sec = args.max_events_per_sec.or(rate_limit.max_events_per_sec);
+        args.max_bytes_per_sec = args.max_bytes_per_sec.or(rate_limit.max_bytes_per_sec);
+    }
 
     if args.debug {
-        eprintln!("load binlog config: \n{}", to_string_pretty(&format, &binlog_config));
+        eprintln!("load binlog config: \n{}", to_string_pretty(&format, &redact_password(&binlog_config)));
     }
 
     // merge binlog settings
-    merge(&mut binlog_config, &args).expect("merge default binlog_config and args error!");
+    merge(&mut binlog_config, &args)?;
+
+    eprintln!("final binlog config: {}", to_string_pretty(&format, &redact_password(&binlog_config)));
+
+    let range = EventRange::new(args.start_position, args.stop_position,
+                                args.start_datetime.as_deref(), args.stop_datetime.as_deref())?;
 
-    eprintln!("final binlog config: {}", to_string_pretty(&format, &binlog_config));
+    if let Some(Commands::Parse { path }) = &args.command {
+        let filter = match binlog_config.replicate.as_ref() {
+            Some(replicate) => Some(ReplicationFilter::new(replicate)?),
+            None => None,
+        };
+
+        let formatter = create_formatter(output_format, true, args.output_dir.clone(), event_sink(&args, sink_config.as_ref(), None)?,
+                                         timestamp_zone(&args, &binlog_config)?)?;
+        let mut parse = ParseCommand::new(formatter, filter)
+            .with_range(range)
+            .with_rate_limit(rate_limiter(&args)?)
+            .with_masking(masking(masking_rules.as_ref())?)
+            .with_follow(args.follow);
+        return parse.run(path);
+    }
+    if let Some(Commands::ToSql { path, no_trx, no_ddl, only_ddl }) = &args.command {
+        let filter = match binlog_config.replicate.as_ref() {
+            Some(replicate) => Some(ReplicationFilter::new(replicate)?),
+            None => None,
+        };
+        let ddl = match (no_ddl, only_ddl) {
+            (true, _) => DdlFilter::NoDdl,
+            (_, true) => DdlFilter::OnlyDdl,
+            _ => DdlFilter::All,
+        };
+
+        let sink = event_sink(&args, sink_config.as_ref(), None)?.unwrap_or_else(|| Box::new(StdoutSink::new()));
+        let formatter = SqlFormatter::new(sink).with_transactions(!no_trx).with_ddl(ddl);
+        let mut parse = ParseCommand::new(Box::new(formatter), filter)
+            .with_range(range)
+            .with_rate_limit(rate_limiter(&args)?)
+            .with_masking(masking(masking_rules.as_ref())?)
+            .with_follow(args.follow);
+        return parse.run(path);
+    }
+    if let Some(Commands::Flashback { path, table }) = &args.command {
+        return FlashbackCommand::new(table, range)?.run(path);
+    }
+    if let Some(Commands::Check) = &args.command {
+        return CheckCommand::new(binlog_config).run();
+    }
+    if let Some(Commands::Stats { path }) = &args.command {
+        let stats = StatsCommand::new(CliOptions::new(args.debug, format.clone()));
+        return match path {
+            Some(path) => stats.run_file(path),
+            None => stats.run_connection(&binlog_config),
+        };
+    }
 
     let cli_f_d_ = match args.debug {true => {"-d"},false => {""}};
     let cli_f_ = format!("{}", cli_f_d_);
@@ -113,29 +458,235 @@ async fn main() -> CResult<()> {
     eprintln!(" ╩ ╩ ╩ ╚═╝ ╩ ╩═╝ Rust us Binlog CLI {}", cli_output);
     eprintln!();
 
-    let mut client = CliClient::new(CliOptions::new_with_log(args.debug, format), binlog_config);
-    client.start().await?;
+    // 后台运行或指定了 pid 文件时记录 pid, 供 --stop / status 使用。 后台进程的参数中已去掉 --daemon, 按环境变量判断
+    let pid_guard = if args.daemon || daemon::is_daemon_process() || args.pid_file.is_some() {
+        Some(PidFile::create(pid_file)?)
+    } else {
+        None
+    };
+    let shutdown = Arc::new(AtomicBool::new(false));
+    daemon::install_shutdown_handler(shutdown.clone(), pid_guard.as_ref().map(|p| p.path().to_path_buf()));
+    let pause = PauseSwitch::new();
+    daemon::install_pause_handler(pause.clone());
+
+    let metrics = match args.metrics_port {
+        Some(port) => {
+            let metrics = Arc::new(CliMetrics::new());
+            metrics::serve(port, metrics.clone())?;
+            Some(metrics)
+        },
+        None => None,
+    };
 
-    // let mut shundown = ShutdownHandle::create();
-    // shundown.add_service(Box::new(client));
+    let config_updates = watch_config(&args)?;
+    start_metadata_refresh(&rep_config.rc_metadata).await;
+
+    let mut shutdown_handle = ShutdownHandle::create();
+    let sources = binlog_config.source_configs()?;
+    if sources.is_empty() {
+        let mut client = new_client(&args, None, binlog_config, sink_config.as_ref(), range)?
+            .with_masking(masking(masking_rules.as_ref())?)
+            .with_config_updates(config_updates, rate_limit_pinned)
+            .with_shutdown(shutdown)
+            .with_pause(pause)
+            .with_metrics(metrics);
+        client.start().await?;
+        shutdown_handle.add_service(Box::new(client));
+        shutdown_handle.shutdown_services(true).await?;
+        return Ok(());
+    }
 
+    // 多数据源: 每个数据源一条 pipeline, 共享退出信号与指标。 读取 binlog 为阻塞 IO, 各自运行在独立的线程上
+    let mut tasks = Vec::with_capacity(sources.len());
+    for (name, config) in sources {
+        let mut client = new_client(&args, Some(&name), config, sink_config.as_ref(), range.clone())?
+            .with_masking(masking(masking_rules.as_ref())?)
+            .with_config_updates(config_updates.clone(), rate_limit_pinned)
+            .with_shutdown(shutdown.clone())
+            .with_pause(pause.clone())
+            .with_metrics(metrics.clone());
+        let runtime = tokio::runtime::Handle::current();
+        tasks.push(tokio::task::spawn_blocking(move || {
+            let rs = runtime.block_on(client.start());
+            (name, client, rs)
+        }));
+    }
+
+    // 单个数据源失败不影响其他数据源, 全部结束后汇总错误
+    let mut failed = Vec::new();
+    for task in tasks {
+        let (name, client, rs) = task.await
+            .map_err(|e| ReError::String(format!("source pipeline panicked: {}", e)))?;
+        if let Err(e) = rs {
+            eprintln!("source {} failed: {:?}", name, e);
+            failed.push(name);
+        }
+        shutdown_handle.add_service(Box::new(client));
+    }
+    shutdown_handle.shutdown_services(true).await?;
+
+    if !failed.is_empty() {
+        return Err(ReError::String(format!("sources failed: {}", failed.join(", "))));
+    }
     Ok(())
 }
 
-// 加载配置文件， 读取配置
-fn load_config(args: &CliArgs) -> FConfig {
-    let default_conf = get_config_path(&args);
+/// 创建一条 pipeline。 多数据源时 source 为数据源名称, checkpoint 文件、file sink 与 --output-dir 按数据源区分
+fn new_client(args: &CliArgs, source: Option<&str>, mut binlog_config: BinlogConfig,
+              sink_config: Option<&SinkConfig>, range: EventRange) -> CResult<CliClient> {
+    // 从 checkpoint 继续。 显式指定了 --start-position 时以参数为准
+    let checkpoint_file = args.checkpoint_file.clone().unwrap_or_else(|| CheckpointStore::new(None).path().to_path_buf());
+    let checkpoint = CheckpointStore::new(Some(source_path(&checkpoint_file, source)));
+    if !args.no_resume && args.start_position.is_none() {
+        if let Some(c) = checkpoint.load()? {
+            eprintln!("resume from checkpoint {:?}: {}:{}", checkpoint.path(), c.file, c.position);
+            binlog_config.file = Some(c.file);
+            binlog_config.position = Some(c.position as i32);
+        }
+    }
 
-    let _config =
-        if default_conf.is_some() {
-            let rep_conf_rs = read_config(default_conf.unwrap());
+    let output_format = OutputFormat::try_from(args.format.as_str())?;
+    let output_dir = match source {
+        Some(source) => Some(args.output_dir.clone().unwrap_or_default().join(source)),
+        None => args.output_dir.clone(),
+    };
+    let formatter = create_formatter(output_format, args.debug, output_dir, event_sink(args, sink_config, source)?,
+                                     timestamp_zone(args, &binlog_config)?)?;
 
-            FConfig::new(rep_conf_rs.unwrap())
-        } else {
-            FConfig::default()
-        };
+    let mut cli_options = CliOptions::new_with_log(args.debug, Format::format(&args.format));
+    cli_options.set_follow(args.follow);
 
-    _config
+    Ok(CliClient::new(cli_options, binlog_config, formatter)
+        .with_range(range)
+        .with_checkpoint(Some(checkpoint))
+        .with_rate_limit(rate_limiter(args)?)
+        .with_source(source.map(String::from)))
+}
+
+fn masking(rules: Option<&BTreeMap<String, String>>) -> CResult<Option<MaskingEngine>> {
+    rules.map(MaskingEngine::new).transpose()
+        .map_err(|e| ReError::ConfigFileParseErr(format!("[masking] {}", e)))
+}
+
+/// --timestamp-zone 与源库的 time_zone
+fn timestamp_zone(args: &CliArgs, binlog_config: &BinlogConfig) -> CResult<TimestampZone> {
+    let output = TimestampOutput::try_from(args.timestamp_zone.as_str())?;
+    let source = TimeZoneSpec::try_from(binlog_config.get_time_zone().unwrap_or("SYSTEM"))?;
+    Ok(TimestampZone::new(output, source))
+}
+
+/// 可以热加载的配置项, 其他配置项修改后需要重启
+const HOT_RELOAD_CONFIGS: [&str; 5] = ["base.log_level", "base.log_modules", "base.max_memory", "masking", "rate_limit"];
+
+/// 监听配置文件, 返回配置变化的订阅。 [rate_limit] 与 [masking] 由各条 pipeline 应用, 日志级别与内存预算在这里应用
+fn watch_config(args: &CliArgs) -> CResult<Option<watch::Receiver<Arc<ConfigUpdate>>>> {
+    let path = match config_file(args) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let watcher = ConfigWatcher::with_options(&path, args.profile.clone(), Some(Box::new(validate_reload)))?;
+    let updates = watcher.subscribe();
+    let mut log_updates = watcher.subscribe();
+    watcher.spawn();
+
+    let debug = args.debug;
+    tokio::spawn(async move {
+        while log_updates.changed().await.is_ok() {
+            let update = log_updates.borrow_and_update().clone();
+            if update.diff.contains("base.log_level") && !debug {
+                let level = update.config.base.get_log_level().unwrap_or("info");
+                if let Err(e) = TracingFactory::set_log_level(level) {
+                    error!("apply base.log_level error: {:?}", e);
+                }
+            }
+            if update.diff.contains("base.log_modules") {
+                let modules = update.config.base.get_log_modules().cloned().unwrap_or_default();
+                if let Err(e) = TracingFactory::set_module_levels(&modules) {
+                    error!("apply base.log_modules error: {:?}", e);
+                }
+            }
+            if update.diff.contains("base.max_memory") {
+                if let Err(e) = MemoryGovernor::configure(&update.config.base) {
+                    error!("apply base.max_memory error: {:?}", e);
+                }
+            }
+            let restart: Vec<&str> = update.diff.changed()
+                .filter(|c| !HOT_RELOAD_CONFIGS.iter().any(|h| c == h || c.starts_with(&format!("{}.", h))))
+                .collect();
+            if !restart.is_empty() {
+                warn!("config {} changed, take effect after restart", restart.join(", "));
+            }
+        }
+    });
+    Ok(Some(updates))
+}
+
+/// [rc_metadata] 配置了地址与库时, 在后台按刷新间隔从 information_schema 加载 database 中各库的表结构,
+/// 多个库以逗号分隔。 连接失败时只告警, 不影响订阅
+async fn start_metadata_refresh(rc_metadata: &RcMetadata) {
+    let databases: Vec<String> = rc_metadata.database.split(',')
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect();
+    if rc_metadata.addr.is_empty() || databases.is_empty() {
+        return;
+    }
+
+    let opts = match rc_metadata.addr.rsplit_once(':').and_then(|(host, port)| port.parse::<i16>().ok().map(|port| (host, port))) {
+        Some((host, port)) => ConnectionOptions::new_str(host, port, &rc_metadata.username, &rc_metadata.password),
+        None => {
+            warn!("invalid rc_metadata.addr {}, skip metadata refresh", rc_metadata.addr);
+            return;
+        },
+    };
+    let mut conn = AsyncConnection::new(opts);
+    if let Err(e) = conn.connect().await {
+        warn!("connect rc_metadata {} error: {:?}, skip metadata refresh", rc_metadata.addr, e);
+        return;
+    }
+    spawn_metadata_refresh(conn, databases, rc_metadata);
+}
+
+/// 重新加载时校验脱敏与过滤规则, 无效时保留当前配置
+fn validate_reload(config: &RepConfig) -> CResult<()> {
+    masking(config.masking.as_ref())?;
+    if let Some(replicate) = config.binlog.replicate.as_ref() {
+        ReplicationFilter::new(replicate)?;
+    }
+    Ok(())
+}
+
+/// 多数据源时每条 pipeline 各自限速
+fn rate_limiter(args: &CliArgs) -> CResult<Option<RateLimiter>> {
+    let max_bytes = args.max_bytes_per_sec.as_deref().map(parse_bytes_len).transpose()?;
+    Ok(RateLimiter::new(args.max_events_per_sec, max_bytes))
+}
+
+/// 多数据源时在文件名后追加数据源名称, 如 binlog_cli.checkpoint.db1
+fn source_path(path: &Path, source: Option<&str>) -> PathBuf {
+    match (source, path.file_name()) {
+        (Some(source), Some(file_name)) => path.with_file_name(format!("{}.{}", file_name.to_string_lossy(), source)),
+        _ => path.to_path_buf(),
+    }
+}
+
+// 加载配置文件， 读取配置。 未指定 --config 且默认路径不存在时使用默认配置
+fn load_config(args: &CliArgs) -> CResult<FConfig> {
+    let path = match config_file(args) {
+        Some(path) => path,
+        None => return Ok(FConfig::default()),
+    };
+
+    match read_config_with_profile(&path, args.profile.as_deref()).and_then(|c| c.validate().map(|_| c)) {
+        Ok(c) => Ok(FConfig::new(c)),
+        Err(ReError::ConfigFileParseErr(e)) => Err(ReError::ConfigFileParseErr(format!("{:?}: {}", path, e))),
+        Err(e) => Err(ReError::ConfigFileParseErr(format!("read config {:?} error: {}", path, e))),
+    }
+}
+
+/// 使用的配置文件: --config 指定的文件, 或存在的默认文件
+fn config_file(args: &CliArgs) -> Option<PathBuf> {
+    get_config_path(args).filter(|path| args.config.is_some() || path.exists())
 }
 
 fn get_config_path(args: &CliArgs) -> Option<PathBuf> {
@@ -175,9 +726,164 @@ fn merge(binlog_config: &mut BinlogConfig, args: &CliArgs) -> CResult<bool> {
         binlog_config.username = args.username.as_ref().unwrap().clone();
     }
 
-    if args.password.is_some() {
-        binlog_config.password = args.password.as_ref().unwrap().clone();
+    if let Some(password) = args.password.as_deref() {
+        binlog_config.password = resolve_secret(password)?;
     }
 
+    if let Some(position) = args.start_position {
+        // 读取 MySQL 时由 COM_BINLOG_DUMP 指定起始位置
+        if args.command.is_none() && binlog_config.file.as_deref().map_or(true, str::is_empty) {
+            return Err(ReError::String(String::from("--start-position requires binlog file in config")));
+        }
+        binlog_config.position = Some(position as i32);
+    }
+
+    merge_replicate(binlog_config, args);
+
     Ok(true)
 }
+
+/// 输出配置时隐藏密码
+fn redact_password(binlog_config: &BinlogConfig) -> BinlogConfig {
+    let mut c = binlog_config.clone();
+    c.password = String::from("******");
+    for source in c.sources.iter_mut().flatten() {
+        if source.password.is_some() {
+            source.password = Some(String::from("******"));
+        }
+    }
+    c
+}
+
+/// 根据 --sink 创建输出目标, 未指定的参数使用配置中的 [sink]。 stdout 时返回 None 使用默认输出。
+/// 多数据源时 file sink 的文件名追加数据源名称
+fn event_sink(args: &CliArgs, config: Option<&SinkConfig>, source: Option<&str>) -> CResult<Option<Box<dyn EventSink>>> {
+    let config = config.cloned().unwrap_or_default();
+    let sink = args.sink.clone().or(config.sink_type).unwrap_or_else(|| String::from("stdout"));
+    let sink_type = SinkType::try_from(sink.as_str())?;
+    if sink_type == SinkType::Stdout {
+        return Ok(None);
+    }
+
+    let rotate_size = args.sink_rotate_size.clone().or(config.rotate_size);
+    let rotate_interval = args.sink_rotate_interval.clone().or(config.rotate_interval);
+    let options = SinkOptions {
+        path: args.sink_path.clone().or(config.path.map(PathBuf::from)).map(|p| source_path(&p, source)),
+        rotate_size: rotate_size.as_deref().map(parse_bytes_len).transpose()?,
+        rotate_interval: rotate_interval.as_deref().map(parse_duration).transpose()?,
+        brokers: args.kafka_brokers.clone().or(config.kafka_brokers),
+        topic: args.kafka_topic.clone().or(config.kafka_topic),
+    };
+
+    Ok(Some(create_sink(sink_type, &options)?))
+}
+
+/// 过滤参数合并到 [binlog.replicate], 与配置文件中的规则叠加
+fn merge_replicate(binlog_config: &mut BinlogConfig, args: &CliArgs) {
+    let has_filter = args.include_tables.is_some() || args.exclude_tables.is_some()
+        || args.include_dbs.is_some() || args.exclude_dbs.is_some()
+        || args.include_gtids.is_some() || args.exclude_gtids.is_some();
+    if !has_filter {
+        return;
+    }
+
+    let replicate = binlog_config.replicate.get_or_insert_with(ReplicateConfig::default);
+
+    if let Some(tables) = &args.include_tables {
+        let (wild, regex) = split_table_rules(tables);
+        extend_rules(&mut replicate.replicate_wild_do_table, wild);
+        extend_rules(&mut replicate.replicate_regex_do_table, regex);
+    }
+    // 排除规则优先于包含规则与配置文件中的 do 规则
+    if let Some(tables) = &args.exclude_tables {
+        let (wild, regex) = split_table_rules(tables);
+        extend_rules(&mut replicate.exclude_wild_table, wild);
+        extend_rules(&mut replicate.exclude_regex_table, regex);
+    }
+    if let Some(dbs) = &args.include_dbs {
+        extend_rules(&mut replicate.replicate_do_db, split_list(dbs));
+    }
+    if let Some(dbs) = &args.exclude_dbs {
+        extend_rules(&mut replicate.replicate_ignore_db, split_list(dbs));
+    }
+    if args.include_gtids.is_some() {
+        replicate.replicate_do_gtids = args.include_gtids.clone();
+    }
+    if args.exclude_gtids.is_some() {
+        replicate.replicate_ignore_gtids = args.exclude_gtids.clone();
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).map(|s| s.to_string()).collect()
+}
+
+/// 拆分为 (wild 规则, 正则规则)。 glob 的 `*`、`?` 转换为 `%`、`_`, 原有的 `%`、`_` 转义后按字面匹配
+fn split_table_rules(value: &str) -> (Vec<String>, Vec<String>) {
+    let mut wild = Vec::new();
+    let mut regex = Vec::new();
+
+    for rule in split_list(value) {
+        match rule.strip_prefix("re:") {
+            Some(r) => regex.push(r.to_string()),
+            None => {
+                let pattern: String = rule.chars().map(|c| match c {
+                    '*' => String::from("%"),
+                    '?' => String::from("_"),
+                    '%' | '_' | '\\' => format!("\\{}", c),
+                    c => c.to_string(),
+                }).collect();
+                wild.push(pattern);
+            },
+        }
+    }
+
+    (wild, regex)
+}
+
+fn extend_rules(target: &mut Option<Vec<String>>, rules: Vec<String>) {
+    if !rules.is_empty() {
+        target.get_or_insert_with(Vec::new).extend(rules);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use clap::Parser;
+
+    use common::config::BinlogConfig;
+    use connection::binlog::replication_filter::ReplicationFilter;
+
+    use crate::{merge_replicate, source_path, split_table_rules, CliArgs};
+
+    #[test]
+    fn test_split_table_rules() {
+        let (wild, regex) = split_table_rules("db1.*, db2.order_?,re:db3\\.t\\d+");
+
+        assert_eq!(wild, vec!["db1.%", "db2.order\\__"]);
+        assert_eq!(regex, vec!["db3\\.t\\d+"]);
+    }
+
+    /// --exclude-tables 优先于 --include-tables, 正则排除同样优先于 glob 包含
+    #[test]
+    fn test_include_exclude_tables() {
+        let args = CliArgs::parse_from(["binlog_cli", "--include-tables", "db1.*",
+            "--exclude-tables", "db1.tmp,re:db1\\.bak\\d+"]);
+        let mut binlog_config = BinlogConfig::default();
+        merge_replicate(&mut binlog_config, &args);
+        let filter = ReplicationFilter::new(binlog_config.replicate.as_ref().unwrap()).unwrap();
+
+        assert!(filter.table_ok("db1", "t1"));
+        assert!(!filter.table_ok("db1", "tmp"));
+        assert!(!filter.table_ok("db1", "bak1"));
+        assert!(!filter.table_ok("db2", "t1"));
+    }
+
+    #[test]
+    fn test_source_path() {
+        assert_eq!(source_path(Path::new("/tmp/binlog_cli.checkpoint"), Some("db1")), PathBuf::from("/tmp/binlog_cli.checkpoint.db1"));
+        assert_eq!(source_path(Path::new("events.log"), None), PathBuf::from("events.log"));
+    }
+}
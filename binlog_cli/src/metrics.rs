@@ -0,0 +1,170 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::{error, warn};
+
+use common::err::CResult;
+
+/// events/sec 的统计窗口
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// 运行中的 CLI 指标, 由 `--metrics-port` 以 Prometheus 文本格式暴露在 /metrics
+#[derive(Debug)]
+pub struct CliMetrics {
+    events: AtomicU64,
+
+    errors: AtomicU64,
+
+    /// 最后一个事件的 header timestamp 与处理时刻的差值, 秒
+    lag_seconds: AtomicU64,
+
+    /// sink 中已发送但未确认投递的记录数
+    sink_backlog: AtomicU64,
+
+    rate: Mutex<RateWindow>,
+}
+
+#[derive(Debug)]
+struct RateWindow {
+    start: Instant,
+    count: u64,
+    /// 上一个完整窗口的 events/sec
+    per_second: f64,
+}
+
+impl CliMetrics {
+    pub fn new() -> Self {
+        CliMetrics {
+            events: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            lag_seconds: AtomicU64::new(0),
+            sink_backlog: AtomicU64::new(0),
+            rate: Mutex::new(RateWindow { start: Instant::now(), count: 0, per_second: 0.0 }),
+        }
+    }
+
+    /// when: 事件 header 中的时间戳, 为 0(如 heartbeat)时不更新延迟
+    pub fn record_event(&self, when: u32) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+        if when > 0 {
+            self.lag_seconds.store(unix_now().saturating_sub(when as u64), Ordering::Relaxed);
+        }
+
+        let mut rate = self.rate.lock().unwrap();
+        rate.count += 1;
+        let elapsed = rate.start.elapsed();
+        if elapsed >= RATE_WINDOW {
+            rate.per_second = rate.count as f64 / elapsed.as_secs_f64();
+            rate.start = Instant::now();
+            rate.count = 0;
+        }
+    }
+
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_sink_backlog(&self, backlog: usize) {
+        self.sink_backlog.store(backlog as u64, Ordering::Relaxed);
+    }
+
+    /// Prometheus 文本格式
+    pub fn render(&self) -> String {
+        let per_second = {
+            let rate = self.rate.lock().unwrap();
+            // 超过一个窗口没有新事件时速率为 0
+            if rate.start.elapsed() >= RATE_WINDOW * 2 { 0.0 } else { rate.per_second }
+        };
+
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = write!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value);
+        };
+        metric("binlog_cli_events_total", "counter", "Binlog events delivered to the output.",
+               self.events.load(Ordering::Relaxed).to_string());
+        metric("binlog_cli_events_per_second", "gauge", "Binlog events delivered per second.",
+               format!("{:.2}", per_second));
+        metric("binlog_cli_lag_seconds", "gauge", "Seconds between the last event timestamp and its delivery.",
+               self.lag_seconds.load(Ordering::Relaxed).to_string());
+        metric("binlog_cli_errors_total", "counter", "Errors while reading binlog events.",
+               self.errors.load(Ordering::Relaxed).to_string());
+        metric("binlog_cli_sink_backlog", "gauge", "Records sent to the sink but not yet acknowledged.",
+               self.sink_backlog.load(Ordering::Relaxed).to_string());
+
+        out
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// 在 0.0.0.0:port 上启动 /metrics 服务, 在后台线程中处理请求
+pub fn serve(port: u16, metrics: Arc<CliMetrics>) -> CResult<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    eprintln!("metrics listening on http://0.0.0.0:{}/metrics", port);
+
+    thread::Builder::new().name(String::from("metrics")).spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle(stream, &metrics) {
+                        warn!("metrics request error: {:?}", e);
+                    }
+                },
+                Err(e) => error!("metrics accept error: {:?}", e),
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+fn handle(stream: TcpStream, metrics: &CliMetrics) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream);
+
+    // 只关心请求行, 如 `GET /metrics HTTP/1.1`
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", String::from("not found\n")),
+    };
+
+    let mut stream = reader.into_inner();
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           status, body.len(), body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::CliMetrics;
+
+    #[test]
+    fn test_render() {
+        let metrics = CliMetrics::new();
+        metrics.record_event(0);
+        metrics.record_event(0);
+        metrics.record_error();
+        metrics.set_sink_backlog(7);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE binlog_cli_events_total counter\nbinlog_cli_events_total 2\n"));
+        assert!(text.contains("\nbinlog_cli_errors_total 1\n"));
+        assert!(text.contains("\nbinlog_cli_sink_backlog 7\n"));
+        assert!(text.contains("\nbinlog_cli_lag_seconds 0\n"));
+    }
+}
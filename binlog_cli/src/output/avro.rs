@@ -0,0 +1,279 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::row::row_data::RowData;
+use common::err::CResult;
+use common::uuid::uuid_v7;
+
+use crate::output::value::to_text;
+use crate::output::{EventFormatter, EventMeta, GtidTracker, TableDef, TableDefs, TimestampZone};
+
+/// Avro container 文件头
+const AVRO_MAGIC: &[u8] = b"Obj\x01";
+
+/// 每个数据块包含的记录数
+const AVRO_BLOCK_RECORDS: usize = 1000;
+
+/// 每张表输出一个 `<db>.<table>.avro` container 文件(codec null)。
+///
+/// schema 为 record: `_id`(UUIDv7)、`_op`(I/U/D)、`_file`、`_pos`、`_gtid` 以及各列, 列值统一编码为 ["null", "string"]
+#[derive(Debug)]
+pub struct AvroFormatter {
+    output_dir: PathBuf,
+
+    tables: TableDefs,
+
+    gtids: GtidTracker,
+
+    timestamps: TimestampZone,
+
+    /// `db.table` -> writer
+    writers: HashMap<String, AvroFileWriter>,
+}
+
+impl AvroFormatter {
+    pub fn new(output_dir: PathBuf, timestamps: TimestampZone) -> CResult<Self> {
+        std::fs::create_dir_all(&output_dir)?;
+
+        Ok(AvroFormatter {
+            output_dir,
+            tables: TableDefs::default(),
+            gtids: GtidTracker::default(),
+            timestamps,
+            writers: HashMap::new(),
+        })
+    }
+
+    fn write_rows<'a, I>(&mut self, table_id: u64, op: &str, meta: &EventMeta, rows: I) -> CResult<()>
+        where I: Iterator<Item = &'a RowData> {
+        let table = match self.tables.get(table_id) {
+            Some(t) => t.clone(),
+            None => return Ok(()),
+        };
+
+        let key = format!("{}.{}", table.database, table.table);
+        if !self.writers.contains_key(&key) {
+            let path = self.output_dir.join(format!("{}.avro", key));
+            self.writers.insert(key.clone(), AvroFileWriter::create(path, &table)?);
+        }
+        let writer = self.writers.get_mut(&key).unwrap();
+        let gtid = self.gtids.gtid();
+        let zone = self.timestamps.zone();
+
+        for row in rows {
+            let mut datum = Vec::new();
+            write_string(&mut datum, &uuid_v7());
+            write_string(&mut datum, op);
+            write_string(&mut datum, &meta.file_name);
+            write_long(&mut datum, meta.log_pos as i64);
+            write_nullable_string(&mut datum, gtid.as_deref());
+            for (idx, cell) in row.get_cells().iter().enumerate().take(table.columns.len()) {
+                write_nullable_string(&mut datum, to_text(cell, table.is_unsigned(idx), zone).as_deref());
+            }
+            // 行中缺少的列按 null 处理
+            for _ in row.get_cells().len()..table.columns.len() {
+                write_long(&mut datum, 0);
+            }
+
+            writer.append(datum)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl EventFormatter for AvroFormatter {
+    fn write_event(&mut self, meta: &EventMeta, event: &BinlogEvent) -> CResult<()> {
+        self.tables.update(event);
+        self.gtids.update(event);
+        self.timestamps.update(event);
+
+        match event {
+            BinlogEvent::WriteRows(e) => self.write_rows(e.table_id, "I", meta, e.get_rows().iter()),
+            BinlogEvent::UpdateRows(e) => {
+                self.write_rows(e.table_id, "U", meta, e.get_rows().iter().map(|r| &r.after_update))
+            },
+            BinlogEvent::DeleteRows(e) => self.write_rows(e.table_id, "D", meta, e.get_rows().iter()),
+            _ => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        for writer in self.writers.values_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct AvroFileWriter {
+    writer: BufWriter<File>,
+
+    sync_marker: [u8; 16],
+
+    /// 尚未写入的记录
+    block: Vec<Vec<u8>>,
+}
+
+impl AvroFileWriter {
+    fn create(path: PathBuf, table: &TableDef) -> CResult<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        let sync_marker = sync_marker(&path);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(AVRO_MAGIC);
+        // metadata map: 1 个 block, 2 个 entry
+        write_long(&mut header, 2);
+        write_string(&mut header, "avro.schema");
+        write_bytes(&mut header, schema(table).to_string().as_bytes());
+        write_string(&mut header, "avro.codec");
+        write_bytes(&mut header, b"null");
+        write_long(&mut header, 0);
+        header.extend_from_slice(&sync_marker);
+        writer.write_all(&header)?;
+
+        Ok(AvroFileWriter {
+            writer,
+            sync_marker,
+            block: Vec::new(),
+        })
+    }
+
+    fn append(&mut self, datum: Vec<u8>) -> CResult<()> {
+        self.block.push(datum);
+        if self.block.len() >= AVRO_BLOCK_RECORDS {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> CResult<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        write_long(&mut buf, self.block.len() as i64);
+        write_long(&mut buf, self.block.iter().map(|d| d.len()).sum::<usize>() as i64);
+        for datum in self.block.drain(..) {
+            buf.extend_from_slice(&datum);
+        }
+        buf.extend_from_slice(&self.sync_marker);
+        self.writer.write_all(&buf)?;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        self.write_block()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn schema(table: &TableDef) -> serde_json::Value {
+    let mut fields = vec![
+        json!({"name": "_id", "type": "string"}),
+        json!({"name": "_op", "type": "string"}),
+        json!({"name": "_file", "type": "string"}),
+        json!({"name": "_pos", "type": "long"}),
+        json!({"name": "_gtid", "type": ["null", "string"], "default": null}),
+    ];
+    for column in &table.columns {
+        fields.push(json!({"name": avro_name(column), "type": ["null", "string"], "default": null}));
+    }
+
+    json!({
+        "type": "record",
+        "name": avro_name(&table.table),
+        "namespace": avro_name(&table.database),
+        "fields": fields,
+    })
+}
+
+/// avro 名称只允许 [A-Za-z_][A-Za-z0-9_]*
+fn avro_name(name: &str) -> String {
+    let mut s: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if s.is_empty() || s.chars().next().unwrap().is_ascii_digit() {
+        s.insert(0, '_');
+    }
+    s
+}
+
+fn sync_marker(path: &PathBuf) -> [u8; 16] {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+
+    let mut marker = [0u8; 16];
+    for (i, chunk) in marker.chunks_mut(8).enumerate() {
+        let mut hasher = DefaultHasher::new();
+        (path, nanos, i).hash(&mut hasher);
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    marker
+}
+
+/// zig-zag 变长编码的 long
+fn write_long(buf: &mut Vec<u8>, n: i64) {
+    let mut z = ((n << 1) ^ (n >> 63)) as u64;
+    while z & !0x7F != 0 {
+        buf.push(((z & 0x7F) | 0x80) as u8);
+        z >>= 7;
+    }
+    buf.push(z as u8);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(buf, bytes.len() as i64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    write_bytes(buf, s.as_bytes());
+}
+
+/// ["null", "string"] 联合类型: 分支序号 + 值
+fn write_nullable_string(buf: &mut Vec<u8>, s: Option<&str>) {
+    match s {
+        None => write_long(buf, 0),
+        Some(s) => {
+            write_long(buf, 1);
+            write_string(buf, s);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::avro::{avro_name, write_long};
+
+    #[test]
+    fn test_write_long() {
+        let encode = |n| {
+            let mut buf = Vec::new();
+            write_long(&mut buf, n);
+            buf
+        };
+
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(-1), vec![0x01]);
+        assert_eq!(encode(1), vec![0x02]);
+        assert_eq!(encode(64), vec![0x80, 0x01]);
+    }
+
+    #[test]
+    fn test_avro_name() {
+        assert_eq!(avro_name("order-items"), "order_items");
+        assert_eq!(avro_name("1st"), "_1st");
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::row::row_data::RowData;
+use common::err::CResult;
+
+use crate::output::value::to_text;
+use crate::output::{EventFormatter, EventMeta, TableDef, TableDefs, TimestampZone};
+
+/// NULL 的 csv 表示, 同 LOAD DATA INFILE
+const CSV_NULL: &str = "\\N";
+
+/// 每张表输出一个 `<db>.<table>.csv`, 首行为列名。 前三列为 op(I/U/D)、file、pos, update 只输出更新后的行
+#[derive(Debug)]
+pub struct CsvFormatter {
+    output_dir: PathBuf,
+
+    tables: TableDefs,
+
+    timestamps: TimestampZone,
+
+    /// `db.table` -> writer
+    writers: HashMap<String, BufWriter<File>>,
+}
+
+impl CsvFormatter {
+    pub fn new(output_dir: PathBuf, timestamps: TimestampZone) -> CResult<Self> {
+        std::fs::create_dir_all(&output_dir)?;
+
+        Ok(CsvFormatter {
+            output_dir,
+            tables: TableDefs::default(),
+            timestamps,
+            writers: HashMap::new(),
+        })
+    }
+
+    fn write_rows<'a, I>(&mut self, table_id: u64, op: &str, meta: &EventMeta, rows: I) -> CResult<()>
+        where I: Iterator<Item = &'a RowData> {
+        let table = match self.tables.get(table_id) {
+            Some(t) => t.clone(),
+            None => return Ok(()),
+        };
+        let zone = self.timestamps.zone();
+        let writer = self.writer(&table)?;
+
+        for row in rows {
+            let mut fields = vec![op.to_string(), meta.file_name.clone(), meta.log_pos.to_string()];
+            for (idx, cell) in row.get_cells().iter().enumerate() {
+                let field = match to_text(cell, table.is_unsigned(idx), zone) {
+                    Some(text) => escape(&text),
+                    None => CSV_NULL.to_string(),
+                };
+                fields.push(field);
+            }
+            writeln!(writer, "{}", fields.join(","))?;
+        }
+
+        Ok(())
+    }
+
+    fn writer(&mut self, table: &TableDef) -> CResult<&mut BufWriter<File>> {
+        let key = format!("{}.{}", table.database, table.table);
+
+        if !self.writers.contains_key(&key) {
+            let path = self.output_dir.join(format!("{}.csv", key));
+            let mut writer = BufWriter::new(File::create(path)?);
+
+            let mut header = vec![String::from("op"), String::from("file"), String::from("pos")];
+            header.extend(table.columns.iter().map(|c| escape(c)));
+            writeln!(writer, "{}", header.join(","))?;
+
+            self.writers.insert(key.clone(), writer);
+        }
+
+        Ok(self.writers.get_mut(&key).unwrap())
+    }
+}
+
+impl EventFormatter for CsvFormatter {
+    fn write_event(&mut self, meta: &EventMeta, event: &BinlogEvent) -> CResult<()> {
+        self.tables.update(event);
+        self.timestamps.update(event);
+
+        match event {
+            BinlogEvent::WriteRows(e) => self.write_rows(e.table_id, "I", meta, e.get_rows().iter()),
+            BinlogEvent::UpdateRows(e) => {
+                self.write_rows(e.table_id, "U", meta, e.get_rows().iter().map(|r| &r.after_update))
+            },
+            BinlogEvent::DeleteRows(e) => self.write_rows(e.table_id, "D", meta, e.get_rows().iter()),
+            _ => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        for writer in self.writers.values_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// RFC 4180: 包含 , " 换行时使用双引号包裹, 内部的 " 替换为 ""
+fn escape(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::csv::escape;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("abc"), "abc");
+        assert_eq!(escape("a,b"), "\"a,b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape("line\nbreak"), "\"line\nbreak\"");
+    }
+}
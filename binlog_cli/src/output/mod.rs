@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::events::protocol::table_map_event::TableMapEvent;
+use common::config::load_style::Format;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use common::pretty_util::TableStyle;
+use common::time_util::{SessionTimeZone, TimestampOutput, TimeZoneSpec};
+use common::uuid::SidMap;
+use tracing::warn;
+
+use crate::output::avro::AvroFormatter;
+use crate::output::csv::CsvFormatter;
+use crate::output::ndjson::NdjsonFormatter;
+use crate::output::pretty::PrettyFormatter;
+use crate::output::sql::SqlFormatter;
+use crate::output::table::TableFormatter;
+use crate::sink::stdout::StdoutSink;
+use crate::sink::EventSink;
+
+pub mod value;
+pub mod pretty;
+pub mod ndjson;
+pub mod csv;
+pub mod sql;
+pub mod avro;
+pub mod table;
+
+/// 事件输出格式, 对应 `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Yaml,
+    Json,
+    /// 每行一个 json 事件
+    Ndjson,
+    /// 每张表一个 csv 文件
+    Csv,
+    /// 还原的 SQL 语句
+    Sql,
+    /// 每张表一个 Avro container 文件
+    Avro,
+    /// 行变更输出为 ASCII 表格
+    Table,
+    /// 行变更输出为 markdown 表格
+    Markdown,
+}
+
+impl TryFrom<&str> for OutputFormat {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_ascii_lowercase().as_str() {
+            "yaml" => Ok(OutputFormat::Yaml),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "sql" => Ok(OutputFormat::Sql),
+            "avro" => Ok(OutputFormat::Avro),
+            "table" => Ok(OutputFormat::Table),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(ReError::String(format!("Unsupported output format: {}", value))),
+        }
+    }
+}
+
+/// 事件的位置信息
+#[derive(Debug, Clone, Default)]
+pub struct EventMeta {
+    pub file_name: String,
+    /// 事件结束位置, 即下一个事件的起始位置
+    pub log_pos: u64,
+    /// 已输出事件的序号
+    pub seq: u64,
+    /// 多数据源时事件所属的数据源名称
+    pub source: Option<String>,
+}
+
+/// 事件编码器。 新增输出格式时实现该 trait 并在 create_formatter 中注册
+pub trait EventFormatter: Debug {
+    fn write_event(&mut self, meta: &EventMeta, event: &BinlogEvent) -> CResult<()>;
+
+    /// 刷新缓冲, follow 模式下等待新事件前调用
+    fn flush(&mut self) -> CResult<()> {
+        Ok(())
+    }
+
+    /// 输出结束, 刷新缓冲并关闭文件
+    fn finish(&mut self) -> CResult<()> {
+        self.flush()
+    }
+
+    /// 输出目标中等待投递的记录数
+    fn backlog(&self) -> usize {
+        0
+    }
+}
+
+/// 创建编码器
+///
+/// # Arguments
+///
+/// * `detail`: yaml/json 格式下是否输出事件详情, 否则只输出事件类型与位置
+/// * `output_dir`: csv/avro 文件的输出目录, 默认当前目录
+/// * `sink`: yaml/json/ndjson/sql/table/markdown 的输出目标, None 时输出到标准输出。 csv/avro 只能写入 output_dir
+/// * `timestamps`: csv/avro/table/markdown 中 TIMESTAMP 列的输出时区
+pub fn create_formatter(format: OutputFormat, detail: bool, output_dir: Option<PathBuf>,
+                        sink: Option<Box<dyn EventSink>>, timestamps: TimestampZone) -> CResult<Box<dyn EventFormatter>> {
+    let output_dir = output_dir.unwrap_or_else(|| PathBuf::from("."));
+    if sink.is_some() && (format == OutputFormat::Csv || format == OutputFormat::Avro) {
+        return Err(ReError::String(format!("--sink is not supported by format {:?}, use --output-dir", format)));
+    }
+    let sink = sink.unwrap_or_else(|| Box::new(StdoutSink::new()));
+
+    let formatter: Box<dyn EventFormatter> = match format {
+        OutputFormat::Yaml => Box::new(PrettyFormatter::new(Format::Yaml, detail, sink)),
+        OutputFormat::Json => Box::new(PrettyFormatter::new(Format::Json, detail, sink)),
+        OutputFormat::Ndjson => Box::new(NdjsonFormatter::new(sink)),
+        OutputFormat::Csv => Box::new(CsvFormatter::new(output_dir, timestamps)?),
+        OutputFormat::Sql => Box::new(SqlFormatter::new(sink)),
+        OutputFormat::Avro => Box::new(AvroFormatter::new(output_dir, timestamps)?),
+        OutputFormat::Table => Box::new(TableFormatter::new(TableStyle::Ascii, timestamps, sink)),
+        OutputFormat::Markdown => Box::new(TableFormatter::new(TableStyle::Markdown, timestamps, sink)),
+    };
+
+    Ok(formatter)
+}
+
+/// 由 TableMapEvent 得到的表结构
+#[derive(Debug, Clone)]
+pub struct TableDef {
+    pub database: String,
+    pub table: String,
+    /// 列名。 binlog_row_metadata=MINIMAL 时没有列名, 使用 `@1`、`@2` 代替
+    pub columns: Vec<String>,
+    pub unsigned: Vec<bool>,
+    /// 主键列的序号
+    pub pk: Vec<usize>,
+}
+
+impl TableDef {
+    pub fn new(table_map: &TableMapEvent) -> Self {
+        let infos = table_map.get_column_infos();
+        let columns_number = (table_map.get_columns_number() as usize).max(infos.len());
+
+        let mut columns = Vec::with_capacity(columns_number);
+        let mut unsigned = Vec::with_capacity(columns_number);
+        let mut pk = Vec::new();
+        for idx in 0..columns_number {
+            let info = infos.get(idx);
+            let name = info.map(|i| i.get_name()).filter(|n| !n.is_empty());
+            columns.push(name.unwrap_or_else(|| format!("@{}", idx + 1)));
+            unsigned.push(info.map_or(false, |i| i.is_unsigned()));
+            if info.map_or(false, |i| i.is_pk()) {
+                pk.push(idx);
+            }
+        }
+
+        TableDef {
+            database: table_map.get_database_name(),
+            table: table_map.get_table_name(),
+            columns,
+            unsigned,
+            pk,
+        }
+    }
+
+    /// `db`.`table`
+    pub fn full_name(&self) -> String {
+        format!("`{}`.`{}`", self.database.replace('`', "``"), self.table.replace('`', "``"))
+    }
+
+    pub fn is_unsigned(&self, idx: usize) -> bool {
+        self.unsigned.get(idx).cloned().unwrap_or(false)
+    }
+}
+
+/// table_id -> TableDef, 随 TableMapEvent 更新
+#[derive(Debug, Default)]
+pub struct TableDefs {
+    tables: HashMap<u64, TableDef>,
+}
+
+impl TableDefs {
+    /// 遇到 TableMapEvent 时更新表结构
+    pub fn update(&mut self, event: &BinlogEvent) {
+        if let BinlogEvent::TableMap(e) = event {
+            self.tables.insert(e.table_id, TableDef::new(e));
+        }
+    }
+
+    pub fn get(&self, table_id: u64) -> Option<&TableDef> {
+        self.tables.get(&table_id)
+    }
+
+    /// 事件所属的库与表, 用于 sink 路由。 未知时为空
+    pub fn route(&self, event: &BinlogEvent) -> (String, String) {
+        let table_id = match event {
+            BinlogEvent::TableMap(e) => return (e.get_database_name(), e.get_table_name()),
+            BinlogEvent::Query(e) => return (e.schema.clone(), String::new()),
+            BinlogEvent::WriteRows(e) => e.table_id,
+            BinlogEvent::UpdateRows(e) => e.table_id,
+            BinlogEvent::DeleteRows(e) => e.table_id,
+            _ => return (String::new(), String::new()),
+        };
+
+        match self.get(table_id) {
+            Some(t) => (t.database.clone(), t.table.clone()),
+            None => (String::new(), String::new()),
+        }
+    }
+}
+
+/// 当前事务的 GTID, 随 GtidLogEvent 更新。 以 (sid, gno) 保存, 输出时还原为 `uuid:gno`
+#[derive(Debug, Default)]
+pub struct GtidTracker {
+    sids: SidMap,
+
+    current: Option<(u32, u64)>,
+}
+
+impl GtidTracker {
+    pub fn update(&mut self, event: &BinlogEvent) {
+        match event {
+            BinlogEvent::GtidLog(e) => {
+                let sid = self.sids.add(&e.gtid.source_id.data);
+                self.current = Some((sid, e.gtid.transaction_id));
+            }
+            BinlogEvent::AnonymousGtidLog(_) => self.current = None,
+            _ => {}
+        }
+    }
+
+    /// 未开启 GTID 时为 None
+    pub fn gtid(&self) -> Option<String> {
+        self.current.and_then(|(sid, gno)| self.sids.decode_gtid(sid, gno))
+    }
+}
+
+/// TIMESTAMP 列的输出时区, 随 QueryEvent 中记录的会话 time_zone 更新
+#[derive(Debug, Clone, Copy)]
+pub struct TimestampZone {
+    output: TimestampOutput,
+
+    session: SessionTimeZone,
+
+    /// 无法识别的会话时区只提示一次
+    warned: bool,
+}
+
+impl TimestampZone {
+    /// source 为源库的全局 time_zone
+    pub fn new(output: TimestampOutput, source: TimeZoneSpec) -> Self {
+        TimestampZone {
+            output,
+            session: SessionTimeZone::new(source),
+            warned: false,
+        }
+    }
+
+    pub fn update(&mut self, event: &BinlogEvent) {
+        if let BinlogEvent::Query(e) = event {
+            if let Err(err) = self.session.update(e.get_time_zone()) {
+                if !self.warned {
+                    self.warned = true;
+                    warn!("{}, use the default time zone", err);
+                }
+            }
+        }
+    }
+
+    /// None 时输出时间戳
+    pub fn zone(&self) -> Option<TimeZoneSpec> {
+        self.output.zone(&self.session)
+    }
+}
+
+impl Default for TimestampZone {
+    fn default() -> Self {
+        TimestampZone::new(TimestampOutput::Epoch, TimeZoneSpec::Local)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::output::OutputFormat;
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!(OutputFormat::try_from("NDJSON").unwrap(), OutputFormat::Ndjson);
+        assert_eq!(OutputFormat::try_from("sql").unwrap(), OutputFormat::Sql);
+        assert_eq!(OutputFormat::try_from("markdown").unwrap(), OutputFormat::Markdown);
+        assert!(OutputFormat::try_from("xml").is_err());
+    }
+}
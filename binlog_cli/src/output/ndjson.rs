@@ -0,0 +1,79 @@
+use serde::Serialize;
+
+use binlog::events::binlog_event::BinlogEvent;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use common::uuid::uuid_v7;
+
+use crate::output::{EventFormatter, EventMeta, GtidTracker, TableDefs};
+use crate::sink::{EventSink, SinkRecord};
+
+/// 每行一个 json 对象: {"id":..,"file":..,"pos":..,"type":..,"event":{..}}, 多数据源时带有 "source", 开启 GTID 时带有 "gtid"。
+///
+/// id 为 UUIDv7, 按时间递增
+#[derive(Debug)]
+pub struct NdjsonFormatter {
+    tables: TableDefs,
+
+    gtids: GtidTracker,
+
+    sink: Box<dyn EventSink>,
+}
+
+#[derive(Serialize)]
+struct NdjsonLine<'a> {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    file: &'a str,
+    pos: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gtid: Option<String>,
+    #[serde(rename = "type")]
+    event_type: String,
+    event: &'a BinlogEvent,
+}
+
+impl NdjsonFormatter {
+    pub fn new(sink: Box<dyn EventSink>) -> Self {
+        NdjsonFormatter {
+            tables: TableDefs::default(),
+            gtids: GtidTracker::default(),
+            sink,
+        }
+    }
+}
+
+impl EventFormatter for NdjsonFormatter {
+    fn write_event(&mut self, meta: &EventMeta, event: &BinlogEvent) -> CResult<()> {
+        self.tables.update(event);
+        self.gtids.update(event);
+
+        let line = NdjsonLine {
+            id: uuid_v7(),
+            source: meta.source.as_deref(),
+            file: &meta.file_name,
+            pos: meta.log_pos,
+            gtid: self.gtids.gtid(),
+            event_type: BinlogEvent::get_type_name(event),
+            event,
+        };
+
+        let mut json = match serde_json::to_string(&line) {
+            Ok(json) => json,
+            Err(e) => return Err(ReError::String(format!("ndjson encode error: {}", e))),
+        };
+        json.push('\n');
+
+        let (database, table) = self.tables.route(event);
+        self.sink.send(&SinkRecord { database: &database, table: &table, payload: json.as_bytes() })
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        self.sink.flush()
+    }
+
+    fn backlog(&self) -> usize {
+        self.sink.backlog()
+    }
+}
@@ -0,0 +1,57 @@
+use binlog::events::binlog_event::BinlogEvent;
+use common::config::load_style::Format;
+use common::err::CResult;
+use common::pretty_util::to_string_pretty;
+
+use crate::output::{EventFormatter, EventMeta, TableDefs};
+use crate::sink::{EventSink, SinkRecord};
+
+/// yaml / json 格式化输出, 与 BinlogSubscribe 的输出保持一致
+#[derive(Debug)]
+pub struct PrettyFormatter {
+    format: Format,
+
+    /// 是否输出事件详情, 否则只输出事件类型与位置
+    detail: bool,
+
+    tables: TableDefs,
+
+    sink: Box<dyn EventSink>,
+}
+
+impl PrettyFormatter {
+    pub fn new(format: Format, detail: bool, sink: Box<dyn EventSink>) -> Self {
+        PrettyFormatter {
+            format,
+            detail,
+            tables: TableDefs::default(),
+            sink,
+        }
+    }
+}
+
+impl EventFormatter for PrettyFormatter {
+    fn write_event(&mut self, meta: &EventMeta, event: &BinlogEvent) -> CResult<()> {
+        self.tables.update(event);
+        let event_type = BinlogEvent::get_type_name(event);
+        let source = meta.source.as_ref().map(|s| format!("<{}> ", s)).unwrap_or_default();
+
+        let text = if self.detail {
+            format!("{}[{} {}], pos {} in {}\n{}\n\n",
+                    source, event_type, meta.seq, meta.log_pos, meta.file_name, to_string_pretty(&self.format, event))
+        } else {
+            format!("{}[{} {}], pos {} in {}\n\n", source, event_type, meta.seq, meta.log_pos, meta.file_name)
+        };
+
+        let (database, table) = self.tables.route(event);
+        self.sink.send(&SinkRecord { database: &database, table: &table, payload: text.as_bytes() })
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        self.sink.flush()
+    }
+
+    fn backlog(&self) -> usize {
+        self.sink.backlog()
+    }
+}
@@ -0,0 +1,247 @@
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::row::row_data::RowData;
+use common::err::CResult;
+
+use crate::output::value::to_sql_literal;
+use crate::output::{EventFormatter, EventMeta, TableDef, TableDefs};
+use crate::sink::{EventSink, SinkRecord};
+
+/// DDL 的输出方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DdlFilter {
+    All,
+    /// --no-ddl: 只输出数据变更
+    NoDdl,
+    /// --only-ddl: 只输出 DDL
+    OnlyDdl,
+}
+
+/// 将事件还原为 SQL: 行事件转为 INSERT / UPDATE / DELETE, Query 事件原样输出
+#[derive(Debug)]
+pub struct SqlFormatter {
+    tables: TableDefs,
+
+    /// 最近一次 USE 的库
+    current_db: String,
+
+    /// 是否保留 BEGIN / COMMIT
+    transactions: bool,
+
+    ddl: DdlFilter,
+
+    sink: Box<dyn EventSink>,
+}
+
+impl SqlFormatter {
+    pub fn new(sink: Box<dyn EventSink>) -> Self {
+        SqlFormatter {
+            tables: TableDefs::default(),
+            current_db: String::new(),
+            transactions: true,
+            ddl: DdlFilter::All,
+            sink,
+        }
+    }
+
+    pub fn with_transactions(mut self, transactions: bool) -> Self {
+        self.transactions = transactions;
+        self
+    }
+
+    pub fn with_ddl(mut self, ddl: DdlFilter) -> Self {
+        self.ddl = ddl;
+        self
+    }
+
+    /// BEGIN / COMMIT 是否输出
+    fn output_trx_control(&self) -> bool {
+        self.transactions && self.ddl != DdlFilter::OnlyDdl
+    }
+
+    /// 非事务控制的 Query 是否输出
+    fn output_query(&self, query: &str) -> bool {
+        match self.ddl {
+            DdlFilter::All => true,
+            DdlFilter::NoDdl => !is_ddl(query),
+            DdlFilter::OnlyDdl => is_ddl(query),
+        }
+    }
+}
+
+impl EventFormatter for SqlFormatter {
+    fn write_event(&mut self, meta: &EventMeta, event: &BinlogEvent) -> CResult<()> {
+        self.tables.update(event);
+
+        // 一个事件的所有语句作为一条记录发送
+        let mut out = String::new();
+        match event {
+            BinlogEvent::Query(e) => {
+                let query = e.query.trim();
+                let control = is_transaction_control(query);
+                if (control && !self.output_trx_control()) || (!control && !self.output_query(query)) {
+                    return Ok(());
+                }
+
+                if !e.schema.is_empty() && e.schema != self.current_db && !control {
+                    self.current_db = e.schema.clone();
+                    out.push_str(&format!("USE `{}`;\n", e.schema.replace('`', "``")));
+                }
+                out.push_str(&format!("{};\n", query.trim_end_matches(';')));
+            },
+            BinlogEvent::XID(_) => {
+                if self.output_trx_control() {
+                    match meta.source.as_ref() {
+                        Some(source) => out.push_str(&format!("COMMIT; /* {} {} {} */\n", source, meta.file_name, meta.log_pos)),
+                        None => out.push_str(&format!("COMMIT; /* {} {} */\n", meta.file_name, meta.log_pos)),
+                    }
+                }
+            },
+            BinlogEvent::WriteRows(_) | BinlogEvent::UpdateRows(_) | BinlogEvent::DeleteRows(_)
+                if self.ddl == DdlFilter::OnlyDdl => {},
+            BinlogEvent::WriteRows(e) => {
+                if let Some(table) = self.tables.get(e.table_id) {
+                    for row in e.get_rows() {
+                        out.push_str(&insert_sql(table, row));
+                        out.push('\n');
+                    }
+                }
+            },
+            BinlogEvent::UpdateRows(e) => {
+                if let Some(table) = self.tables.get(e.table_id) {
+                    for row in e.get_rows() {
+                        out.push_str(&update_sql(table, &row.before_update, &row.after_update));
+                        out.push('\n');
+                    }
+                }
+            },
+            BinlogEvent::DeleteRows(e) => {
+                if let Some(table) = self.tables.get(e.table_id) {
+                    for row in e.get_rows() {
+                        out.push_str(&delete_sql(table, row));
+                        out.push('\n');
+                    }
+                }
+            },
+            _ => {},
+        }
+
+        if out.is_empty() {
+            return Ok(());
+        }
+        let (database, table) = self.tables.route(event);
+        self.sink.send(&SinkRecord { database: &database, table: &table, payload: out.as_bytes() })
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        self.sink.flush()
+    }
+
+    fn backlog(&self) -> usize {
+        self.sink.backlog()
+    }
+}
+
+/// BEGIN / COMMIT / ROLLBACK / XA ...
+pub fn is_transaction_control(query: &str) -> bool {
+    let upper = query.trim().to_ascii_uppercase();
+    upper == "BEGIN" || upper == "COMMIT" || upper == "ROLLBACK" || upper.starts_with("XA ")
+}
+
+/// CREATE / ALTER / DROP / TRUNCATE / RENAME ...
+pub fn is_ddl(query: &str) -> bool {
+    let keyword = query.split_whitespace().next().unwrap_or("").to_ascii_uppercase();
+    matches!(keyword.as_str(), "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "RENAME")
+}
+
+/// INSERT INTO `db`.`t`(`a`, `b`) VALUES (1, 'x');
+pub fn insert_sql(table: &TableDef, row: &RowData) -> String {
+    let columns: Vec<String> = (0..row.get_cells().len()).map(|i| column_name(table, i)).collect();
+    let values: Vec<String> = row.get_cells().iter().enumerate()
+        .map(|(i, cell)| to_sql_literal(cell, table.is_unsigned(i)))
+        .collect();
+
+    format!("INSERT INTO {}({}) VALUES ({});", table.full_name(), columns.join(", "), values.join(", "))
+}
+
+/// UPDATE `db`.`t` SET `a`=2 WHERE `a`=1 LIMIT 1;
+pub fn update_sql(table: &TableDef, before: &RowData, after: &RowData) -> String {
+    let sets: Vec<String> = after.get_cells().iter().enumerate()
+        .map(|(i, cell)| format!("{}={}", column_name(table, i), to_sql_literal(cell, table.is_unsigned(i))))
+        .collect();
+
+    format!("UPDATE {} SET {} WHERE {} LIMIT 1;", table.full_name(), sets.join(", "), where_clause(table, before))
+}
+
+/// DELETE FROM `db`.`t` WHERE `a`=1 LIMIT 1;
+pub fn delete_sql(table: &TableDef, row: &RowData) -> String {
+    format!("DELETE FROM {} WHERE {} LIMIT 1;", table.full_name(), where_clause(table, row))
+}
+
+/// 有主键时只使用主键列定位行, 否则使用全部列
+fn where_clause(table: &TableDef, row: &RowData) -> String {
+    let cells = row.get_cells();
+    let indexes: Vec<usize> = if !table.pk.is_empty() && table.pk.iter().all(|i| *i < cells.len()) {
+        table.pk.clone()
+    } else {
+        (0..cells.len()).collect()
+    };
+
+    let conditions: Vec<String> = indexes.iter()
+        .map(|i| match &cells[*i] {
+            None => format!("{} IS NULL", column_name(table, *i)),
+            cell => format!("{}={}", column_name(table, *i), to_sql_literal(cell, table.is_unsigned(*i))),
+        })
+        .collect();
+
+    conditions.join(" AND ")
+}
+
+fn column_name(table: &TableDef, idx: usize) -> String {
+    match table.columns.get(idx) {
+        Some(name) => format!("`{}`", name.replace('`', "``")),
+        None => format!("`@{}`", idx + 1),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use binlog::row::row_data::RowData;
+    use common::binlog::column::column_value::SrcColumnValue;
+    use crate::output::sql::{delete_sql, insert_sql, is_ddl, update_sql};
+    use crate::output::TableDef;
+
+    fn table(pk: Vec<usize>) -> TableDef {
+        TableDef {
+            database: String::from("db"),
+            table: String::from("t"),
+            columns: vec![String::from("id"), String::from("name")],
+            unsigned: vec![true, false],
+            pk,
+        }
+    }
+
+    fn row(id: u32, name: Option<&str>) -> RowData {
+        RowData::new_with_cells(vec![
+            Some(SrcColumnValue::Int(id)),
+            name.map(|n| SrcColumnValue::String(n.to_string())),
+        ])
+    }
+
+    #[test]
+    fn test_sql() {
+        assert_eq!(insert_sql(&table(vec![0]), &row(1, Some("a"))),
+                   "INSERT INTO `db`.`t`(`id`, `name`) VALUES (1, 'a');");
+        assert_eq!(update_sql(&table(vec![0]), &row(1, Some("a")), &row(1, Some("b"))),
+                   "UPDATE `db`.`t` SET `id`=1, `name`='b' WHERE `id`=1 LIMIT 1;");
+        assert_eq!(delete_sql(&table(vec![]), &row(1, None)),
+                   "DELETE FROM `db`.`t` WHERE `id`=1 AND `name` IS NULL LIMIT 1;");
+    }
+
+    #[test]
+    fn test_is_ddl() {
+        assert!(is_ddl("create table t (id int)"));
+        assert!(is_ddl("  ALTER TABLE t ADD c int"));
+        assert!(!is_ddl("INSERT INTO t VALUES (1)"));
+        assert!(!is_ddl("BEGIN"));
+    }
+}
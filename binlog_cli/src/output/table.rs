@@ -0,0 +1,87 @@
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::row::row_data::RowData;
+use common::err::CResult;
+use common::pretty_util::{RowTable, TableStyle};
+
+use crate::output::value::to_text;
+use crate::output::{EventFormatter, EventMeta, TableDef, TableDefs, TimestampZone};
+use crate::sink::{EventSink, SinkRecord};
+
+/// 行变更输出为表格, 便于人工查看。 其他事件只输出事件类型与位置
+#[derive(Debug)]
+pub struct TableFormatter {
+    style: TableStyle,
+
+    tables: TableDefs,
+
+    timestamps: TimestampZone,
+
+    sink: Box<dyn EventSink>,
+}
+
+impl TableFormatter {
+    pub fn new(style: TableStyle, timestamps: TimestampZone, sink: Box<dyn EventSink>) -> Self {
+        TableFormatter {
+            style,
+            tables: TableDefs::default(),
+            timestamps,
+            sink,
+        }
+    }
+
+    fn texts(&self, table: &TableDef, row: &RowData) -> Vec<Option<String>> {
+        let zone = self.timestamps.zone();
+        row.get_cells().iter().enumerate()
+            .map(|(idx, cell)| to_text(cell, table.is_unsigned(idx), zone))
+            .collect()
+    }
+
+    /// 行变更事件的表格, 其他事件或表结构未知时为 None
+    fn render(&self, event: &BinlogEvent) -> Option<String> {
+        let table_id = match event {
+            BinlogEvent::WriteRows(e) => e.table_id,
+            BinlogEvent::UpdateRows(e) => e.table_id,
+            BinlogEvent::DeleteRows(e) => e.table_id,
+            _ => return None,
+        };
+        let table = self.tables.get(table_id)?;
+
+        let mut rows = RowTable::new(self.style, table.columns.clone());
+        match event {
+            BinlogEvent::WriteRows(e) => e.get_rows().iter().for_each(|r| rows.add_row("I", &self.texts(table, r))),
+            BinlogEvent::DeleteRows(e) => e.get_rows().iter().for_each(|r| rows.add_row("D", &self.texts(table, r))),
+            BinlogEvent::UpdateRows(e) => e.get_rows().iter().for_each(|r| {
+                rows.add_update(&self.texts(table, &r.before_update), &self.texts(table, &r.after_update))
+            }),
+            _ => {}
+        }
+
+        Some(format!("{}\n{}", table.full_name(), rows.render()))
+    }
+}
+
+impl EventFormatter for TableFormatter {
+    fn write_event(&mut self, meta: &EventMeta, event: &BinlogEvent) -> CResult<()> {
+        self.tables.update(event);
+        self.timestamps.update(event);
+        let source = meta.source.as_ref().map(|s| format!("<{}> ", s)).unwrap_or_default();
+
+        let mut text = format!("{}[{} {}], pos {} in {}\n",
+                               source, BinlogEvent::get_type_name(event), meta.seq, meta.log_pos, meta.file_name);
+        if let Some(rows) = self.render(event) {
+            text.push_str(&rows);
+        }
+        text.push('\n');
+
+        let (database, table) = self.tables.route(event);
+        self.sink.send(&SinkRecord { database: &database, table: &table, payload: text.as_bytes() })
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        self.sink.flush()
+    }
+
+    fn backlog(&self) -> usize {
+        self.sink.backlog()
+    }
+}
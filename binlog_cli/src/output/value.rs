@@ -0,0 +1,133 @@
+use common::binlog::column::column_value::SrcColumnValue;
+use common::time_util::TimeZoneSpec;
+
+/// 列值的文本表示, NULL 返回 None。 有符号整数按补码还原, zone 为 None 时 TIMESTAMP 输出为秒级时间戳
+pub fn to_text(value: &Option<SrcColumnValue>, unsigned: bool, zone: Option<TimeZoneSpec>) -> Option<String> {
+    let value = value.as_ref()?;
+
+    let text = match value {
+        SrcColumnValue::TinyInt(v) => if unsigned { v.to_string() } else { (*v as i8).to_string() },
+        SrcColumnValue::SmallInt(v) => if unsigned { v.to_string() } else { (*v as i16).to_string() },
+        SrcColumnValue::MediumInt(v) => {
+            if unsigned {
+                v.to_string()
+            } else {
+                // 24 位补码
+                (((*v << 8) as i32) >> 8).to_string()
+            }
+        },
+        SrcColumnValue::Int(v) => if unsigned { v.to_string() } else { (*v as i32).to_string() },
+        SrcColumnValue::BigInt(v) => if unsigned { v.to_string() } else { (*v as i64).to_string() },
+        SrcColumnValue::Float(v) => v.to_string(),
+        SrcColumnValue::Double(v) => v.to_string(),
+        SrcColumnValue::Decimal(v) => v.clone(),
+        SrcColumnValue::String(v) => v.clone(),
+        SrcColumnValue::Bit(bits) => bits.iter().map(|b| if *b { '1' } else { '0' }).collect(),
+        SrcColumnValue::Enum(v) => v.to_string(),
+        SrcColumnValue::Set(v) => v.to_string(),
+        SrcColumnValue::Blob(v) => to_hex(v),
+        SrcColumnValue::Year(v) => v.to_string(),
+        SrcColumnValue::Date(d) => format!("{:04}-{:02}-{:02}", d.year, d.month, d.day),
+        SrcColumnValue::Time(t) => {
+            let sign = if t.hour < 0 { "-" } else { "" };
+            format!("{}{:02}:{:02}:{:02}{}", sign, t.hour.abs(), t.minute, t.second, fraction(t.millis))
+        },
+        SrcColumnValue::DateTime(d) => {
+            format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+                    d.year, d.month, d.day, d.hour, d.minute, d.second, fraction(d.millis))
+        },
+        SrcColumnValue::Timestamp(millis) => match zone {
+            Some(zone) => zone.format_timestamp(*millis as i64),
+            None => format!("{}.{:03}", millis / 1000, millis % 1000),
+        },
+    };
+
+    Some(text)
+}
+
+/// 列值的 SQL 字面量
+pub fn to_sql_literal(value: &Option<SrcColumnValue>, unsigned: bool) -> String {
+    let v = match value {
+        Some(v) => v,
+        None => return String::from("NULL"),
+    };
+    let text = to_text(value, unsigned, None).unwrap_or_default();
+
+    match v {
+        SrcColumnValue::TinyInt(_) | SrcColumnValue::SmallInt(_) | SrcColumnValue::MediumInt(_) |
+        SrcColumnValue::Int(_) | SrcColumnValue::BigInt(_) | SrcColumnValue::Float(_) |
+        SrcColumnValue::Double(_) | SrcColumnValue::Decimal(_) | SrcColumnValue::Enum(_) |
+        SrcColumnValue::Set(_) | SrcColumnValue::Year(_) => text,
+        SrcColumnValue::Bit(_) => format!("b'{}'", text),
+        SrcColumnValue::Blob(_) => format!("X'{}'", text),
+        SrcColumnValue::Timestamp(_) => format!("FROM_UNIXTIME({})", text),
+        SrcColumnValue::String(_) | SrcColumnValue::Date(_) |
+        SrcColumnValue::Time(_) | SrcColumnValue::DateTime(_) => quote(&text),
+    }
+}
+
+/// 转义为单引号字符串
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            '\x1a' => quoted.push_str("\\Z"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+
+    quoted
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// 毫秒部分, 为 0 时省略
+fn fraction(millis: u32) -> String {
+    if millis == 0 {
+        String::new()
+    } else {
+        format!(".{:03}", millis)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::binlog::column::column_value::{DateTime, SrcColumnValue};
+    use common::time_util::TimeZoneSpec;
+    use crate::output::value::{to_sql_literal, to_text};
+
+    #[test]
+    fn test_to_text() {
+        assert_eq!(to_text(&Some(SrcColumnValue::TinyInt(255)), false, None), Some(String::from("-1")));
+        assert_eq!(to_text(&Some(SrcColumnValue::TinyInt(255)), true, None), Some(String::from("255")));
+        assert_eq!(to_text(&Some(SrcColumnValue::MediumInt(0xFF_FFFF)), false, None), Some(String::from("-1")));
+        assert_eq!(to_text(&None, false, None), None);
+    }
+
+    #[test]
+    fn test_timestamp_text() {
+        let ts = Some(SrcColumnValue::Timestamp(1704164645123));
+        assert_eq!(to_text(&ts, false, None), Some(String::from("1704164645.123")));
+        assert_eq!(to_text(&ts, false, Some(TimeZoneSpec::Utc)), Some(String::from("2024-01-02 03:04:05.123+00:00")));
+        assert_eq!(to_text(&ts, false, TimeZoneSpec::try_from("+08:00").ok()), Some(String::from("2024-01-02 11:04:05.123+08:00")));
+    }
+
+    #[test]
+    fn test_to_sql_literal() {
+        assert_eq!(to_sql_literal(&None, false), "NULL");
+        assert_eq!(to_sql_literal(&Some(SrcColumnValue::String(String::from("it's"))), false), "'it\\'s'");
+        assert_eq!(to_sql_literal(&Some(SrcColumnValue::Blob(vec![0xAB, 0x01])), false), "X'AB01'");
+        assert_eq!(to_sql_literal(&Some(SrcColumnValue::DateTime(DateTime {
+            year: 2024, month: 1, day: 2, hour: 3, minute: 4, second: 5, millis: 0,
+        })), false), "'2024-01-02 03:04:05'");
+    }
+}
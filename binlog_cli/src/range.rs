@@ -0,0 +1,150 @@
+use common::binlog::FIRST_EVENT_POSITION;
+use common::err::CResult;
+use common::time_util::parse_datetime;
+
+/// 事件是否在输出范围内
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeDecision {
+    /// 尚未到达起始边界, 跳过该事件
+    Skip,
+    Accept,
+    /// 已越过结束边界, 结束读取
+    Stop,
+}
+
+/// --start-position / --stop-position / --start-datetime / --stop-datetime, 语义同 mysqlbinlog:
+///
+///   * start-position: 从第一个起始位置 >= N 的事件开始
+///   * stop-position: 在第一个起始位置 >= N 的事件处停止
+///   * start-datetime: 跳过时间戳 < datetime 的事件
+///   * stop-datetime: 在第一个时间戳 >= datetime 的事件处停止
+///
+/// 解析多个文件时, start-position 只作用于第一个文件, stop-position 只作用于最后一个文件
+#[derive(Debug, Clone, Default)]
+pub struct EventRange {
+    start_position: Option<u64>,
+    stop_position: Option<u64>,
+    start_datetime: Option<u64>,
+    stop_datetime: Option<u64>,
+
+    /// start_position 作用的文件, None 表示任意文件
+    start_file: Option<String>,
+    /// stop_position 作用的文件, None 表示任意文件
+    stop_file: Option<String>,
+
+    current_file: String,
+    /// 上一个事件的结束位置, 即当前事件的起始位置
+    next_position: u64,
+}
+
+impl EventRange {
+    pub fn new(start_position: Option<u64>, stop_position: Option<u64>,
+               start_datetime: Option<&str>, stop_datetime: Option<&str>) -> CResult<Self> {
+        Ok(EventRange {
+            start_position,
+            stop_position,
+            start_datetime: start_datetime.map(parse_datetime).transpose()?,
+            stop_datetime: stop_datetime.map(parse_datetime).transpose()?,
+            ..EventRange::default()
+        })
+    }
+
+    /// 限定 start_position / stop_position 作用的文件
+    pub fn with_position_files(mut self, start_file: Option<String>, stop_file: Option<String>) -> Self {
+        self.start_file = start_file;
+        self.stop_file = stop_file;
+        self
+    }
+
+    /// 起始位置由服务端处理(COM_BINLOG_DUMP), 客户端不再按 start_position 过滤
+    pub fn without_start_position(mut self) -> Self {
+        self.start_position = None;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start_position.is_none() && self.stop_position.is_none()
+            && self.start_datetime.is_none() && self.stop_datetime.is_none()
+    }
+
+    /// 按顺序对每个事件调用
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name`: 事件所在的 binlog 文件
+    /// * `log_pos`: 事件的结束位置, 为 0 时(伪造的 RotateEvent 等)不更新位置
+    /// * `when`: 事件的时间戳, 为 0 时不参与时间范围的判断
+    pub fn check(&mut self, file_name: &str, log_pos: u64, when: u32) -> RangeDecision {
+        if self.current_file != file_name {
+            self.current_file = file_name.to_string();
+            self.next_position = FIRST_EVENT_POSITION as u64;
+        }
+        let position = self.next_position;
+        if log_pos > 0 {
+            self.next_position = log_pos;
+        }
+        let when = when as u64;
+
+        if let Some(stop) = self.stop_position {
+            if Self::match_file(&self.stop_file, file_name) && position >= stop {
+                return RangeDecision::Stop;
+            }
+        }
+        if let Some(stop) = self.stop_datetime {
+            if when > 0 && when >= stop {
+                return RangeDecision::Stop;
+            }
+        }
+        if let Some(start) = self.start_position {
+            if Self::match_file(&self.start_file, file_name) && position < start {
+                return RangeDecision::Skip;
+            }
+        }
+        if let Some(start) = self.start_datetime {
+            if when > 0 && when < start {
+                return RangeDecision::Skip;
+            }
+        }
+
+        RangeDecision::Accept
+    }
+
+    fn match_file(file: &Option<String>, file_name: &str) -> bool {
+        file.as_ref().map_or(true, |f| f == file_name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::range::{EventRange, RangeDecision};
+
+    #[test]
+    fn test_position_range() {
+        let mut range = EventRange::new(Some(200), Some(400), None, None).unwrap()
+            .with_position_files(Some(String::from("binlog.000001")), Some(String::from("binlog.000002")));
+
+        // 事件起始位置: 4, 120, 200, 300
+        assert_eq!(range.check("binlog.000001", 120, 1), RangeDecision::Skip);
+        assert_eq!(range.check("binlog.000001", 200, 1), RangeDecision::Skip);
+        assert_eq!(range.check("binlog.000001", 300, 1), RangeDecision::Accept);
+        assert_eq!(range.check("binlog.000001", 500, 1), RangeDecision::Accept);
+
+        // stop_position 只作用于最后一个文件
+        assert_eq!(range.check("binlog.000002", 300, 1), RangeDecision::Accept);
+        assert_eq!(range.check("binlog.000002", 400, 1), RangeDecision::Accept);
+        assert_eq!(range.check("binlog.000002", 500, 1), RangeDecision::Stop);
+    }
+
+    #[test]
+    fn test_datetime_range() {
+        let mut range = EventRange::new(None, None, None, None).unwrap();
+        assert!(range.is_empty());
+
+        range.start_datetime = Some(100);
+        range.stop_datetime = Some(200);
+        assert_eq!(range.check("binlog.000001", 120, 99), RangeDecision::Skip);
+        assert_eq!(range.check("binlog.000001", 0, 0), RangeDecision::Accept);
+        assert_eq!(range.check("binlog.000001", 200, 100), RangeDecision::Accept);
+        assert_eq!(range.check("binlog.000001", 300, 200), RangeDecision::Stop);
+    }
+}
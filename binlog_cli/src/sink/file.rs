@@ -0,0 +1,146 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::sink::{EventSink, SinkRecord};
+
+/// 写入本地文件。 超过 rotate_size 或 rotate_interval 后, 当前文件重命名为 `<path>.<yyyyMMddHHmmss>`,
+/// 并重新创建 path 继续写入
+#[derive(Debug)]
+pub struct FileSink {
+    path: PathBuf,
+
+    writer: BufWriter<File>,
+
+    /// 当前文件已写入的字节数
+    written: u64,
+
+    opened_at: Instant,
+
+    rotate_size: Option<u64>,
+    rotate_interval: Option<Duration>,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf, rotate_size: Option<u64>, rotate_interval: Option<Duration>) -> CResult<Self> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let (writer, written) = Self::open(&path)?;
+
+        Ok(FileSink {
+            path,
+            writer,
+            written,
+            opened_at: Instant::now(),
+            rotate_size,
+            rotate_interval,
+        })
+    }
+
+    /// 追加方式打开, 返回文件的当前大小
+    fn open(path: &Path) -> CResult<(BufWriter<File>, u64)> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = file.metadata()?.len();
+        Ok((BufWriter::new(file), len))
+    }
+
+    fn should_rotate(&self, len: usize) -> bool {
+        // 空文件不滚动, 避免单条记录大于 rotate_size 时产生大量空文件
+        if self.written == 0 {
+            return false;
+        }
+
+        self.rotate_size.map_or(false, |size| self.written + len as u64 > size)
+            || self.rotate_interval.map_or(false, |interval| self.opened_at.elapsed() >= interval)
+    }
+
+    fn rotate(&mut self) -> CResult<()> {
+        self.writer.flush()?;
+
+        let suffix = Local::now().format("%Y%m%d%H%M%S").to_string();
+        let mut target = Self::rotated_path(&self.path, &suffix, 0);
+        let mut seq = 0;
+        while target.exists() {
+            seq += 1;
+            target = Self::rotated_path(&self.path, &suffix, seq);
+        }
+        std::fs::rename(&self.path, &target)?;
+
+        let (writer, written) = Self::open(&self.path)?;
+        self.writer = writer;
+        self.written = written;
+        self.opened_at = Instant::now();
+
+        Ok(())
+    }
+
+    /// 同一秒内多次滚动时追加序号
+    fn rotated_path(path: &Path, suffix: &str, seq: usize) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(".");
+        name.push(suffix);
+        if seq > 0 {
+            name.push(format!(".{}", seq));
+        }
+        PathBuf::from(name)
+    }
+}
+
+impl FileSink {
+    fn write(&mut self, payload: &[u8]) -> CResult<()> {
+        if self.should_rotate(payload.len()) {
+            self.rotate()?;
+        }
+
+        self.writer.write_all(payload)?;
+        self.written += payload.len() as u64;
+        Ok(())
+    }
+}
+
+impl EventSink for FileSink {
+    fn send(&mut self, record: &SinkRecord) -> CResult<()> {
+        self.write(record.payload)
+            .map_err(|e| ReError::SinkError(format!("write {:?} error: {}", self.path, e)))
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        self.writer.flush()
+            .map_err(|e| ReError::SinkError(format!("flush {:?} error: {}", self.path, e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sink::file::FileSink;
+    use crate::sink::{EventSink, SinkRecord};
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = std::env::temp_dir().join(format!("binlog_cli_sink_{}", std::process::id()));
+        let path = dir.join("events.log");
+
+        let mut sink = FileSink::new(path.clone(), Some(10), None).unwrap();
+        for _ in 0..3 {
+            let record = SinkRecord { database: "db", table: "t", payload: b"123456\n" };
+            sink.send(&record).unwrap();
+        }
+        sink.flush().unwrap();
+
+        // 每个文件只能容纳一条记录
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(files, 3);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "123456\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
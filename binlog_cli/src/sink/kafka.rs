@@ -0,0 +1,146 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::Message;
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+use rdkafka::ClientContext;
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::sink::{render_topic, EventSink, SinkRecord};
+
+/// 本地发送队列满时, 等待投递的间隔
+const QUEUE_FULL_POLL: Duration = Duration::from_millis(100);
+
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 消息投递的超时时间, 超时后投递回调返回失败
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 记录投递回调中的第一个失败
+#[derive(Default)]
+struct DeliveryContext {
+    failed: Mutex<Option<String>>,
+}
+
+impl ClientContext for DeliveryContext {}
+
+impl ProducerContext for DeliveryContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, delivery_result: &DeliveryResult<'_>, _delivery_opaque: Self::DeliveryOpaque) {
+        if let Err((e, message)) = delivery_result {
+            let mut failed = self.failed.lock().unwrap();
+            if failed.is_none() {
+                *failed = Some(format!("deliver to kafka topic {} error: {}", message.topic(), e));
+            }
+        }
+    }
+}
+
+impl DeliveryContext {
+    /// 有投递失败时返回错误。 失败的消息已丢失, 之后的发送与刷新都返回该错误
+    fn check(&self) -> CResult<()> {
+        match self.failed.lock().unwrap().as_ref() {
+            Some(e) => Err(ReError::SinkError(e.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 发送到 kafka。 topic 由模板按库表生成, 消息 key 为 `db.table`, 保证同一张表的事件有序
+pub struct KafkaSink {
+    producer: BaseProducer<DeliveryContext>,
+
+    topic_template: String,
+}
+
+impl KafkaSink {
+    pub fn new(brokers: &str, topic_template: String) -> CResult<Self> {
+        Self::create(brokers, topic_template, MESSAGE_TIMEOUT)
+    }
+
+    fn create(brokers: &str, topic_template: String, message_timeout: Duration) -> CResult<Self> {
+        let producer: BaseProducer<DeliveryContext> = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", message_timeout.as_millis().to_string())
+            .create_with_context(DeliveryContext::default())
+            .map_err(|e| ReError::SinkError(format!("create kafka producer error: {}", e)))?;
+
+        Ok(KafkaSink {
+            producer,
+            topic_template,
+        })
+    }
+}
+
+impl Debug for KafkaSink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KafkaSink")
+            .field("topic_template", &self.topic_template)
+            .finish()
+    }
+}
+
+impl EventSink for KafkaSink {
+    fn send(&mut self, record: &SinkRecord) -> CResult<()> {
+        self.producer.context().check()?;
+
+        let topic = render_topic(&self.topic_template, record.database, record.table);
+        let key = format!("{}.{}", record.database, record.table);
+        // 每条记录即一条消息, 不需要行尾的换行符
+        let payload = record.payload.strip_suffix(b"\n").unwrap_or(record.payload);
+
+        let mut message = BaseRecord::to(&topic).key(&key).payload(payload);
+        loop {
+            match self.producer.send(message) {
+                Ok(_) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), m)) => {
+                    self.producer.poll(QUEUE_FULL_POLL);
+                    message = m;
+                },
+                Err((e, _)) => {
+                    return Err(ReError::SinkError(format!("send to kafka topic {} error: {}", topic, e)));
+                },
+            }
+        }
+
+        // 处理投递回调
+        self.producer.poll(Duration::ZERO);
+        self.producer.context().check()
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        self.producer.flush(FLUSH_TIMEOUT)
+            .map_err(|e| ReError::SinkError(format!("flush kafka producer error: {}", e)))?;
+        self.producer.context().check()
+    }
+
+    fn backlog(&self) -> usize {
+        self.producer.in_flight_count().max(0) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::sink::kafka::KafkaSink;
+    use crate::sink::{EventSink, SinkRecord};
+
+    #[test]
+    fn test_delivery_error() {
+        // 无法连接的 broker, 消息投递超时
+        let mut sink = KafkaSink::create("127.0.0.1:1", String::from("binlog"), Duration::from_millis(200)).unwrap();
+        let record = SinkRecord { database: "db1", table: "t1", payload: b"{}\n" };
+        sink.send(&record).unwrap();
+
+        assert!(sink.flush().is_err());
+        // 之后的发送也返回错误
+        assert!(sink.send(&record).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::sink::file::FileSink;
+use crate::sink::stdout::StdoutSink;
+
+pub mod stdout;
+pub mod file;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+
+/// 默认的 kafka topic
+pub const DEFAULT_TOPIC_TEMPLATE: &str = "binlog.{db}";
+
+/// 事件投递的目标, 对应 `--sink`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkType {
+    Stdout,
+    /// 写入本地文件, 支持按大小与时间滚动
+    File,
+    Kafka,
+}
+
+impl TryFrom<&str> for SinkType {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_ascii_lowercase().as_str() {
+            "stdout" => Ok(SinkType::Stdout),
+            "file" => Ok(SinkType::File),
+            "kafka" => Ok(SinkType::Kafka),
+            _ => Err(ReError::String(format!("Unsupported sink: {}", value))),
+        }
+    }
+}
+
+/// sink 相关参数
+#[derive(Debug, Clone, Default)]
+pub struct SinkOptions {
+    /// file: 输出文件
+    pub path: Option<PathBuf>,
+    /// file: 超过该大小后滚动
+    pub rotate_size: Option<u64>,
+    /// file: 超过该时长后滚动
+    pub rotate_interval: Option<Duration>,
+
+    /// kafka: bootstrap.servers
+    pub brokers: Option<String>,
+    /// kafka: topic 模板, 支持 `{db}`、`{table}` 占位符
+    pub topic: Option<String>,
+}
+
+/// 一条待投递的记录
+#[derive(Debug)]
+pub struct SinkRecord<'a> {
+    /// 事件所属的库与表, 未知时为空, 用于 topic 路由
+    pub database: &'a str,
+    pub table: &'a str,
+
+    /// 编码后的事件, 包含结尾的换行符
+    pub payload: &'a [u8],
+}
+
+pub trait EventSink: Debug {
+    fn send(&mut self, record: &SinkRecord) -> CResult<()>;
+
+    /// 刷新缓冲, 等待已发送的记录投递完成
+    fn flush(&mut self) -> CResult<()> {
+        Ok(())
+    }
+
+    /// 已发送但尚未确认投递的记录数
+    fn backlog(&self) -> usize {
+        0
+    }
+}
+
+pub fn create_sink(sink_type: SinkType, options: &SinkOptions) -> CResult<Box<dyn EventSink>> {
+    let sink: Box<dyn EventSink> = match sink_type {
+        SinkType::Stdout => Box::new(StdoutSink::new()),
+        SinkType::File => {
+            let path = match options.path.as_ref() {
+                Some(p) => p.clone(),
+                None => return Err(ReError::String(String::from("--sink file requires --sink-path"))),
+            };
+            Box::new(FileSink::new(path, options.rotate_size, options.rotate_interval)?)
+        },
+        SinkType::Kafka => create_kafka_sink(options)?,
+    };
+
+    Ok(sink)
+}
+
+#[cfg(feature = "kafka")]
+fn create_kafka_sink(options: &SinkOptions) -> CResult<Box<dyn EventSink>> {
+    let brokers = match options.brokers.as_ref() {
+        Some(b) => b.clone(),
+        None => return Err(ReError::String(String::from("--sink kafka requires --kafka-brokers"))),
+    };
+    let topic = options.topic.clone().unwrap_or_else(|| DEFAULT_TOPIC_TEMPLATE.to_string());
+
+    Ok(Box::new(kafka::KafkaSink::new(&brokers, topic)?))
+}
+
+#[cfg(not(feature = "kafka"))]
+fn create_kafka_sink(_options: &SinkOptions) -> CResult<Box<dyn EventSink>> {
+    Err(ReError::String(String::from("kafka sink is not enabled, rebuild binlog_cli with `--features kafka`")))
+}
+
+/// 替换 topic 模板中的 `{db}`、`{table}`。 未知的库或表使用 `_`
+pub fn render_topic(template: &str, database: &str, table: &str) -> String {
+    let or_default = |s: &str| if s.is_empty() { String::from("_") } else { s.to_string() };
+
+    template.replace("{db}", &or_default(database)).replace("{table}", &or_default(table))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sink::{render_topic, SinkType};
+
+    #[test]
+    fn test_sink_type() {
+        assert_eq!(SinkType::try_from("Kafka").unwrap(), SinkType::Kafka);
+        assert!(SinkType::try_from("s3").is_err());
+    }
+
+    #[test]
+    fn test_render_topic() {
+        assert_eq!(render_topic("cdc.{db}.{table}", "db1", "t1"), "cdc.db1.t1");
+        assert_eq!(render_topic("cdc.{db}.{table}", "db1", ""), "cdc.db1._");
+        assert_eq!(render_topic("binlog", "db1", "t1"), "binlog");
+    }
+}
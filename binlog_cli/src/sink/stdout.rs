@@ -0,0 +1,28 @@
+use std::io::{stdout, Write};
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::sink::{EventSink, SinkRecord};
+
+/// 输出到标准输出, 默认的 sink
+#[derive(Debug, Default)]
+pub struct StdoutSink {}
+
+impl StdoutSink {
+    pub fn new() -> Self {
+        StdoutSink {}
+    }
+}
+
+impl EventSink for StdoutSink {
+    fn send(&mut self, record: &SinkRecord) -> CResult<()> {
+        stdout().lock().write_all(record.payload)
+            .map_err(|e| ReError::SinkError(format!("write stdout error: {}", e)))
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        stdout().flush()
+            .map_err(|e| ReError::SinkError(format!("flush stdout error: {}", e)))
+    }
+}
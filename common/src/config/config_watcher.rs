@@ -0,0 +1,310 @@
+use std::collections::BTreeSet;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::{config_from_table, RepConfig};
+use crate::config::loader::load_table;
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+
+/// 检查配置文件变化的间隔
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 订阅方对配置的额外校验, 如脱敏与过滤规则
+pub type ConfigValidator = Box<dyn Fn(&RepConfig) -> CResult<()> + Send + Sync>;
+
+/// 两次配置的差异: 发生变化的配置项, 如 `rate_limit`、`binlog.replicate`、`base.log_level`。
+///
+/// 顶层的表比较到第二层, 数组(如 [[mapping]])整体比较
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    changed: BTreeSet<String>,
+}
+
+/// 发布给订阅方的配置
+#[derive(Debug)]
+pub struct ConfigUpdate {
+    /// 初始配置为 0, 每次重新加载加一
+    pub version: u64,
+
+    pub config: RepConfig,
+
+    /// 与上一个版本的差异, 初始配置为空
+    pub diff: ConfigDiff,
+}
+
+/// 监听配置文件(replayer.toml) 及其 include 的文件, 内容变化后重新解析与校验, 通过 tokio watch 通道发布新配置与差异。
+///
+/// 校验失败时保留当前配置; 过滤规则、限速、日志级别等由订阅方各自按差异应用, 无需重启
+pub struct ConfigWatcher {
+    path: PathBuf,
+
+    interval: Duration,
+
+    profile: Option<String>,
+
+    // 上次加载的配置表, 已展开 include 并应用 profile
+    table: toml::Table,
+
+    validator: Option<ConfigValidator>,
+
+    sender: watch::Sender<Arc<ConfigUpdate>>,
+}
+
+impl ConfigDiff {
+    pub fn between(old: &RepConfig, new: &RepConfig) -> CResult<Self> {
+        let to_table = |c: &RepConfig| match toml::Value::try_from(c) {
+            Ok(toml::Value::Table(t)) => Ok(t),
+            Ok(_) => Err(ReError::ConfigFileParseErr(String::from("config is not a table"))),
+            Err(e) => Err(ReError::ConfigFileParseErr(e.to_string())),
+        };
+        let mut changed = BTreeSet::new();
+        diff_table("", &to_table(old)?, &to_table(new)?, 2, &mut changed);
+        Ok(Self { changed })
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+    }
+
+    /// path 或其下的配置项是否变化, 如 `binlog` 包含 `binlog.replicate`
+    pub fn contains(&self, path: &str) -> bool {
+        self.changed.iter().any(|c| {
+            c == path || (c.starts_with(path) && c.as_bytes().get(path.len()) == Some(&b'.'))
+        })
+    }
+
+    /// 发生变化的配置项, 按名称排序
+    pub fn changed(&self) -> impl Iterator<Item = &str> {
+        self.changed.iter().map(|c| c.as_str())
+    }
+}
+
+fn diff_table(prefix: &str, old: &toml::Table, new: &toml::Table, depth: usize, changed: &mut BTreeSet<String>) {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (old.get(key), new.get(key)) {
+            (Some(toml::Value::Table(o)), Some(toml::Value::Table(n))) if depth > 1 => {
+                diff_table(&path, o, n, depth - 1, changed);
+            }
+            (o, n) if o != n => {
+                changed.insert(path);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Display for ConfigDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let changed: Vec<&str> = self.changed().collect();
+        write!(f, "{}", changed.join(", "))
+    }
+}
+
+impl ConfigWatcher {
+    /// 加载并校验配置文件
+    pub fn new<P: AsRef<Path>>(path: P) -> CResult<Self> {
+        Self::with_validator(path, None)
+    }
+
+    /// 加载配置文件, validator 在内置的校验之后执行, 初始配置与每次重新加载都会校验
+    pub fn with_validator<P: AsRef<Path>>(path: P, validator: Option<ConfigValidator>) -> CResult<Self> {
+        Self::with_options(path, None, validator)
+    }
+
+    /// profile 为 None 时使用环境变量 REPLAYER_PROFILE, 见 loader::load_table
+    pub fn with_options<P: AsRef<Path>>(path: P, profile: Option<String>, validator: Option<ConfigValidator>) -> CResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let table = load_table(&path, profile.as_deref())?;
+        let config = parse(table.clone(), validator.as_ref())
+            .map_err(|e| ReError::ConfigFileParseErr(format!("{:?}: {}", path, e)))?;
+        let (sender, _) = watch::channel(Arc::new(ConfigUpdate {
+            version: 0,
+            config,
+            diff: ConfigDiff::default(),
+        }));
+
+        Ok(Self {
+            path,
+            interval: DEFAULT_WATCH_INTERVAL,
+            profile,
+            table,
+            validator,
+            sender,
+        })
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 订阅配置变化, 接收方的当前值为最新的配置
+    pub fn subscribe(&self) -> watch::Receiver<Arc<ConfigUpdate>> {
+        self.sender.subscribe()
+    }
+
+    /// 最新的配置
+    pub fn current(&self) -> Arc<ConfigUpdate> {
+        self.sender.borrow().clone()
+    }
+
+    /// 检查一次文件。 内容变化且校验通过时发布新配置并返回差异;
+    /// 内容未变化或配置项没有差异(如只修改了注释)时返回 None; 校验失败时返回错误, 保留当前配置
+    pub fn check(&mut self) -> CResult<Option<ConfigDiff>> {
+        let table = load_table(&self.path, self.profile.as_deref())?;
+        if table == self.table {
+            return Ok(None);
+        }
+        let config = parse(table.clone(), self.validator.as_ref())?;
+        self.table = table;
+
+        let current = self.current();
+        let diff = ConfigDiff::between(&current.config, &config)?;
+        if diff.is_empty() {
+            return Ok(None);
+        }
+        self.sender.send_replace(Arc::new(ConfigUpdate {
+            version: current.version + 1,
+            config,
+            diff: diff.clone(),
+        }));
+        Ok(Some(diff))
+    }
+
+    /// 在后台按间隔检查文件, 所有订阅方都已关闭时退出
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(self.interval).await;
+                if self.sender.is_closed() {
+                    break;
+                }
+                match self.check() {
+                    Ok(Some(diff)) => info!("reload config {:?}, changed: {}", self.path, diff),
+                    Ok(None) => {}
+                    Err(e) => warn!("reload config {:?} err, keep the current config: {:?}", self.path, e),
+                }
+            }
+        })
+    }
+}
+
+impl Debug for ConfigWatcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigWatcher")
+            .field("path", &self.path)
+            .field("interval", &self.interval)
+            .field("profile", &self.profile)
+            .field("version", &self.sender.borrow().version)
+            .finish()
+    }
+}
+
+/// 解析并校验配置
+fn parse(table: toml::Table, validator: Option<&ConfigValidator>) -> CResult<RepConfig> {
+    let mut config = config_from_table(table)?;
+    config.validate()?;
+    config.resolve_secrets()?;
+    if let Some(validator) = validator {
+        validator(&config)?;
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::config::config_watcher::ConfigWatcher;
+    use crate::err::CResult;
+
+    const BASE: &str = r#"
+        app_name = "replayer"
+
+        [base]
+        log_level = "info"
+
+        [binlog]
+        username = "root"
+        password = "123456"
+        payload_buffer_size = 32768
+
+        [rc_mysql]
+        addr = []
+        username = ""
+        password = ""
+
+        [rc_metadata]
+        addr = ""
+        username = ""
+        password = ""
+        database = ""
+    "#;
+
+    fn config_file(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("config_watcher_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("replayer.toml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_reload() -> CResult<()> {
+        let path = config_file("reload", BASE);
+        let mut watcher = ConfigWatcher::new(&path)?;
+        let mut rx = watcher.subscribe();
+        assert_eq!(None, watcher.check()?);
+
+        // 只修改注释没有差异
+        std::fs::write(&path, format!("# comment\n{}", BASE))?;
+        assert_eq!(None, watcher.check()?);
+        assert!(!rx.has_changed().unwrap());
+
+        let content = BASE.replace("log_level = \"info\"", "log_level = \"debug\"")
+            + "\n[rate_limit]\nmax_events_per_sec = 100\n[binlog.replicate]\nreplicate_do_db = [\"db1\"]\n";
+        std::fs::write(&path, content)?;
+        let diff = watcher.check()?.unwrap();
+        assert_eq!(vec!["base.log_level", "binlog.replicate", "rate_limit"], diff.changed().collect::<Vec<_>>());
+        assert!(diff.contains("binlog") && !diff.contains("bin") && !diff.contains("binlog.host"));
+
+        assert!(rx.has_changed().unwrap());
+        let update = rx.borrow_and_update().clone();
+        assert_eq!(1, update.version);
+        assert_eq!(Some(100), update.config.rate_limit.as_ref().unwrap().max_events_per_sec);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_keeps_current() -> CResult<()> {
+        let path = config_file("invalid", BASE);
+        let mut watcher = ConfigWatcher::new(&path)?;
+
+        std::fs::write(&path, format!("{}\n[rate_limit]\nmax_bytes_per_sec = \"ten\"\n", BASE))?;
+        assert!(watcher.check().is_err());
+        std::fs::write(&path, "app_name = ")?;
+        assert!(watcher.check().is_err());
+        assert_eq!(0, watcher.current().version);
+        assert!(watcher.current().config.rate_limit.is_none());
+
+        // 修正后重新加载
+        std::fs::write(&path, format!("{}\n[rate_limit]\nmax_bytes_per_sec = \"10MB\"\n", BASE))?;
+        assert!(watcher.check()?.unwrap().contains("rate_limit"));
+        assert_eq!(1, watcher.current().version);
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+
+/// 未指定 profile 时从该环境变量读取, 如 REPLAYER_PROFILE=prod
+pub const PROFILE_ENV: &str = "REPLAYER_PROFILE";
+
+const INCLUDE_KEY: &str = "include";
+const PROFILE_KEY: &str = "profile";
+
+/// 加载配置文件, 展开 include 并应用 profile, 返回最终的配置表。
+///
+/// - `include = ["common.toml"]`: 先按顺序加载被包含的文件(相对于当前文件所在目录, 可以嵌套), 当前文件的配置覆盖被包含的文件
+/// - `[profile.<name>]`: 选中的 profile 覆盖其他配置。 profile 为 None 时使用环境变量 REPLAYER_PROFILE, 都未指定时忽略全部 profile
+///
+/// 表按配置项合并, 数组(如 [[binlog.sources]])与其他值整体替换
+pub fn load_table<P: AsRef<Path>>(path: P, profile: Option<&str>) -> CResult<toml::Table> {
+    let mut table = load_with_includes(path.as_ref(), &mut vec![])?;
+
+    let profiles = match table.remove(PROFILE_KEY) {
+        None => toml::Table::new(),
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => return Err(ReError::ConfigFileParseErr(String::from("profile must be a table, e.g. [profile.prod]"))),
+    };
+    let profile = match profile {
+        Some(profile) => Some(profile.to_string()),
+        None => std::env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty()),
+    };
+    if let Some(profile) = profile {
+        match profiles.get(&profile) {
+            Some(toml::Value::Table(overrides)) => merge_table(&mut table, overrides.clone()),
+            Some(_) => return Err(ReError::ConfigFileParseErr(format!("profile.{} must be a table", profile))),
+            None => {
+                let names: Vec<&str> = profiles.keys().map(|k| k.as_str()).collect();
+                return Err(ReError::ConfigFileParseErr(format!("profile {} not found, available: [{}]", profile, names.join(", "))));
+            }
+        }
+    }
+
+    Ok(table)
+}
+
+/// stack 为正在加载的文件, 用于检查循环包含
+fn load_with_includes(path: &Path, stack: &mut Vec<PathBuf>) -> CResult<toml::Table> {
+    let canonical = path.canonicalize()
+        .map_err(|e| ReError::ConfigFileParseErr(format!("{:?}: {}", path, e)))?;
+    if stack.contains(&canonical) {
+        return Err(ReError::ConfigFileParseErr(format!("include cycle at {:?}", path)));
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    let mut table: toml::Table = toml::from_str(&content)
+        .map_err(|e| ReError::ConfigFileParseErr(format!("{:?}: {}", path, e)))?;
+
+    let includes = match table.remove(INCLUDE_KEY) {
+        None => vec![],
+        Some(toml::Value::Array(includes)) => includes,
+        Some(_) => return Err(ReError::ConfigFileParseErr(format!("{:?}: include must be an array of paths", path))),
+    };
+    if includes.is_empty() {
+        return Ok(table);
+    }
+
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = toml::Table::new();
+    for include in includes {
+        let include = match include {
+            toml::Value::String(include) => dir.join(include),
+            other => return Err(ReError::ConfigFileParseErr(format!("{:?}: invalid include {}", path, other))),
+        };
+        merge_table(&mut merged, load_with_includes(&include, stack)?);
+    }
+    stack.pop();
+
+    merge_table(&mut merged, table);
+    Ok(merged)
+}
+
+/// 将 overrides 合并到 base, 两边都是表时逐项合并, 否则替换
+pub fn merge_table(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => merge_table(b, o),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::config::loader::load_table;
+    use crate::config::read_config_with_profile;
+    use crate::err::CResult;
+
+    fn config_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("config_loader_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, content) in files {
+            std::fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    const COMMON: &str = r#"
+        app_name = "replayer"
+
+        [base]
+        log_dir = "/tmp/replayer"
+
+        [binlog]
+        host = "127.0.0.1"
+        port = 3306
+        username = "root"
+        password = "123456"
+        payload_buffer_size = 32768
+
+        [rc_mysql]
+        addr = []
+        username = ""
+        password = ""
+
+        [rc_metadata]
+        addr = ""
+        username = ""
+        password = ""
+        database = ""
+
+        [profile.prod.binlog]
+        host = "10.0.0.1"
+    "#;
+
+    #[test]
+    fn test_include_and_profile() -> CResult<()> {
+        let dir = config_dir("profile", &[
+            ("common.toml", COMMON),
+            ("replayer.toml", r#"
+                include = ["common.toml"]
+
+                [binlog]
+                port = 13306
+
+                [profile.dev.binlog]
+                password = "dev"
+
+                [profile.prod.base]
+                log_level = "warn"
+            "#),
+        ]);
+        let path = dir.join("replayer.toml");
+
+        let c = read_config_with_profile(&path, Some("dev"))?;
+        assert_eq!(c.binlog.get_host(), "127.0.0.1");
+        assert_eq!(c.binlog.get_port(), 13306);
+        assert_eq!(c.binlog.password, "dev");
+
+        // 被包含文件与当前文件中的同名 profile 合并
+        let c = read_config_with_profile(&path, Some("prod"))?;
+        assert_eq!(c.binlog.get_host(), "10.0.0.1");
+        assert_eq!(c.binlog.password, "123456");
+        assert_eq!(c.base.get_log_level(), Some("warn"));
+
+        assert!(load_table(&path, Some("test")).unwrap_err().to_string().contains("available: [dev, prod]"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = config_dir("cycle", &[
+            ("a.toml", "include = [\"b.toml\"]\n"),
+            ("b.toml", "include = [\"a.toml\"]\n"),
+        ]);
+        assert!(load_table(dir.join("a.toml"), None).unwrap_err().to_string().contains("include cycle"));
+    }
+}
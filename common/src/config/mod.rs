@@ -1,15 +1,22 @@
 pub mod load_style;
+pub mod config_watcher;
+pub mod loader;
+pub mod secret;
+pub mod validation;
 
-use std::fs::File;
-use std::io::Read;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 use crate::binlog::PAYLOAD_BUFFER_SIZE;
 use crate::config::load_style::LoadStyle;
+use crate::schema::column_mapping::MappingRule;
 
 use crate::err::decode_error::ReError;
 
+/// 默认的 slave server_id, 与 mysqlbinlog --stop-never-slave-server-id 一致
+pub const DEFAULT_SERVER_ID: u32 = 65535;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FConfig {
     config: RepConfig,
@@ -26,14 +33,45 @@ pub struct RepConfig {
     pub rc_mysql: RcMySQL,
     pub rc_metadata: RcMetadata,
     pub base: BaseConfig,
+
+    /// 事件输出目标, 对应 [sink]
+    pub sink: Option<SinkConfig>,
+
+    /// 列脱敏规则, 对应 [masking]: `"[db.]table.column" = "null | redact | hash | partial(3,4)"`
+    pub masking: Option<BTreeMap<String, String>>,
+
+    /// 读取限速, 对应 [rate_limit]
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// 表与列的映射规则, 对应 [[mapping]]
+    pub mapping: Option<Vec<MappingRule>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BaseConfig {
+    /// 内存预算, 如 256MB。 接近时缓存停止增长、读取等待, 见 MemoryGovernor
     max_memory: Option<String>,
 
     /// 日志输出路径
     log_dir: Option<String>,
+
+    /// 日志级别: trace | debug | info | warn | error, 修改后热加载
+    log_level: Option<String>,
+
+    /// 日志格式: text | json, 默认 text
+    log_format: Option<String>,
+
+    /// 日志文件按时间滚动: never | minutely | hourly | daily, 默认 daily
+    log_rotation: Option<String>,
+
+    /// 单个日志文件的最大大小, 如 100MB, 超过后滚动
+    log_max_size: Option<String>,
+
+    /// 保留的日志文件数量, 不配置时不清理
+    log_max_files: Option<usize>,
+
+    /// 按模块覆盖日志级别, 如 `{ binlog = "debug", connection = "warn" }`, 修改后热加载
+    log_modules: Option<BTreeMap<String, String>>,
 }
 
 /// Binlog 配置
@@ -42,6 +80,7 @@ pub struct BinlogConfig {
     host: Option<String>,
     port: Option<i16>,
     pub username: String,
+    /// 支持 env:VAR、file:path、exec:cmd 与 enc: 加密的密码, 加载时解析, 见 secret::resolve_secret
     pub password: String,
 
     /// 读取binlog 的缓冲区大小
@@ -55,6 +94,87 @@ pub struct BinlogConfig {
 
     /// binlog 文件的绝对路径
     pub binlog_path: Option<String>,
+
+    /// 以 slave 身份复制时使用的 server_id, 不能与其他 slave 重复。 默认 DEFAULT_SERVER_ID
+    pub server_id: Option<u32>,
+
+    /// 源库的全局 time_zone, 如 SYSTEM、UTC、+08:00、Asia/Shanghai。 用于转换未记录会话时区的 TIMESTAMP 值, 默认 SYSTEM
+    pub time_zone: Option<String>,
+
+    /// 复制过滤规则, 对应 [binlog.replicate]
+    pub replicate: Option<ReplicateConfig>,
+
+    /// 多数据源, 对应 [[binlog.sources]]。 配置后每个数据源独立运行一条 pipeline
+    pub sources: Option<Vec<BinlogSourceConfig>>,
+}
+
+/// 一个数据源, 未配置的项使用 [binlog] 中的值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BinlogSourceConfig {
+    /// 数据源名称, 用于标记输出与区分 checkpoint 文件
+    pub name: String,
+
+    pub host: Option<String>,
+    pub port: Option<i16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    pub file: Option<String>,
+    pub position: Option<i32>,
+    pub server_id: Option<u32>,
+}
+
+/// 复制过滤规则配置。 参数名与 MySQL 的 replicate-* 选项保持一致, 语义亦相同。
+///
+/// do_table / ignore_table 的格式为 `db_name.tbl_name`,
+/// wild_do_table / wild_ignore_table 支持 `%` 与 `_` 通配符, 如 `db%.tbl_%`,
+/// regex_do_table / regex_ignore_table 为匹配 `db_name.tbl_name` 的正则表达式。
+///
+/// do_gtids / ignore_gtids 为 GTID 集合, 如 `uuid:1-100`, 按事务过滤
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplicateConfig {
+    pub replicate_do_db: Option<Vec<String>>,
+    pub replicate_ignore_db: Option<Vec<String>>,
+    pub replicate_do_table: Option<Vec<String>>,
+    pub replicate_ignore_table: Option<Vec<String>>,
+    pub replicate_wild_do_table: Option<Vec<String>>,
+    pub replicate_wild_ignore_table: Option<Vec<String>>,
+    pub replicate_regex_do_table: Option<Vec<String>>,
+    pub replicate_regex_ignore_table: Option<Vec<String>>,
+    pub replicate_do_gtids: Option<String>,
+    pub replicate_ignore_gtids: Option<String>,
+
+    /// 命令行 --exclude-tables 的规则(wild 与正则), 在其他表级别规则之前评估, 命中即忽略
+    pub exclude_wild_table: Option<Vec<String>>,
+    pub exclude_regex_table: Option<Vec<String>>,
+}
+
+/// 事件输出目标配置, 命令行的 --sink 等参数优先
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SinkConfig {
+    /// stdout | file | kafka
+    #[serde(rename = "type")]
+    pub sink_type: Option<String>,
+
+    /// file: 输出文件
+    pub path: Option<String>,
+    /// file: 按大小滚动, 如 100MB
+    pub rotate_size: Option<String>,
+    /// file: 按时间滚动, 如 1h
+    pub rotate_interval: Option<String>,
+
+    /// kafka: bootstrap.servers
+    pub kafka_brokers: Option<String>,
+    /// kafka: topic 模板, 支持 `{db}`、`{table}`
+    pub kafka_topic: Option<String>,
+}
+
+/// 读取限速, 命令行的 --max-events-per-sec 等参数优先
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    pub max_events_per_sec: Option<u64>,
+    /// 如 10MB
+    pub max_bytes_per_sec: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -91,6 +211,10 @@ impl Default for RepConfig {
             binlog: BinlogConfig::default(),
             rc_mysql: RcMySQL::default(),
             rc_metadata: RcMetadata::default(),
+            sink: None,
+            masking: None,
+            rate_limit: None,
+            mapping: None,
         }
     }
 }
@@ -129,6 +253,10 @@ impl Default for BinlogConfig {
             file: Some("".to_string()),
             position: Some(4),
             binlog_path: Some("".to_string()),
+            server_id: None,
+            time_zone: None,
+            replicate: None,
+            sources: None,
         }
     }
 }
@@ -138,6 +266,12 @@ impl Default for BaseConfig {
         BaseConfig {
             max_memory: None,
             log_dir: Some(String::from("/tmp/replayer")),
+            log_level: None,
+            log_format: None,
+            log_rotation: None,
+            log_max_size: None,
+            log_max_files: None,
+            log_modules: None,
         }
     }
 }
@@ -160,9 +294,37 @@ impl FConfig {
 }
 
 impl BaseConfig {
+    pub fn get_max_memory(&self) -> Option<&str> {
+        self.max_memory.as_deref()
+    }
+
     pub fn get_log_dir(&self) -> Option<String> {
         self.log_dir.clone()
     }
+
+    pub fn get_log_level(&self) -> Option<&str> {
+        self.log_level.as_deref()
+    }
+
+    pub fn get_log_format(&self) -> Option<&str> {
+        self.log_format.as_deref()
+    }
+
+    pub fn get_log_rotation(&self) -> Option<&str> {
+        self.log_rotation.as_deref()
+    }
+
+    pub fn get_log_max_size(&self) -> Option<&str> {
+        self.log_max_size.as_deref()
+    }
+
+    pub fn get_log_max_files(&self) -> Option<usize> {
+        self.log_max_files
+    }
+
+    pub fn get_log_modules(&self) -> Option<&BTreeMap<String, String>> {
+        self.log_modules.as_ref()
+    }
 }
 
 impl BinlogConfig {
@@ -203,21 +365,77 @@ impl BinlogConfig {
     pub fn have_port(&self) -> bool {
         self.port.is_none()
     }
+
+    pub fn get_server_id(&self) -> u32 {
+        self.server_id.unwrap_or(DEFAULT_SERVER_ID)
+    }
+
+    pub fn get_time_zone(&self) -> Option<&str> {
+        self.time_zone.as_deref()
+    }
+
+    /// 将 [[binlog.sources]] 展开为各数据源完整的配置, 未配置多数据源时返回空
+    pub fn source_configs(&self) -> Result<Vec<(String, BinlogConfig)>, ReError> {
+        let sources = match self.sources.as_ref() {
+            None => return Ok(vec![]),
+            Some(sources) => sources,
+        };
+
+        let mut rs: Vec<(String, BinlogConfig)> = Vec::with_capacity(sources.len());
+        for source in sources {
+            if source.name.is_empty() {
+                return Err(ReError::ConfigFileParseErr(String::from("binlog.sources: name is required")));
+            }
+            if rs.iter().any(|(name, _)| *name == source.name) {
+                return Err(ReError::ConfigFileParseErr(format!("binlog.sources: duplicate name {}", source.name)));
+            }
+
+            let mut c = self.clone();
+            c.sources = None;
+            if source.host.is_some() {
+                c.host = source.host.clone();
+            }
+            if source.port.is_some() {
+                c.port = source.port;
+            }
+            if let Some(username) = source.username.as_ref() {
+                c.username = username.clone();
+            }
+            if let Some(password) = source.password.as_ref() {
+                c.password = password.clone();
+            }
+            // 起始位置属于具体的数据源, 不继承
+            c.file = source.file.clone();
+            c.position = source.position;
+            if source.server_id.is_some() {
+                c.server_id = source.server_id;
+            }
+
+            rs.push((source.name.clone(), c));
+        }
+
+        Ok(rs)
+    }
 }
 
-/// 读取指定路径下的配制文件信息
+/// 读取指定路径下的配制文件信息, profile 由环境变量 REPLAYER_PROFILE 指定
 pub fn read_config<P: AsRef<Path>>(path: P) -> Result<RepConfig, ReError> {
-    let mut file = File::open(path.as_ref())?;
-    let mut s = String::new();
+    read_config_with_profile(path, None)
+}
+
+/// 读取配置文件, 展开 include 并应用指定的 profile, 见 loader::load_table
+pub fn read_config_with_profile<P: AsRef<Path>>(path: P, profile: Option<&str>) -> Result<RepConfig, ReError> {
+    config_from_table(loader::load_table(path, profile)?)
+}
 
-    let _ = file.read_to_string(&mut s);
-    toml::from_str(s.as_str())
-        .map_err(|e| ReError::ConfigFileParseErr(e.to_string()))
+pub fn config_from_table(table: toml::Table) -> Result<RepConfig, ReError> {
+    toml::Value::Table(table).try_into()
+        .map_err(|e: toml::de::Error| ReError::ConfigFileParseErr(e.to_string()))
 }
 
 #[cfg(test)]
 mod test {
-    use crate::config::read_config;
+    use crate::config::{read_config, RepConfig};
     use crate::err::CResult;
 
     #[test]
@@ -228,4 +446,49 @@ mod test {
         assert!(rs);
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_source_configs() {
+        let c: RepConfig = toml::from_str(r#"
+            app_name = "replayer"
+
+            [base]
+
+            [binlog]
+            host = "127.0.0.1"
+            port = 3306
+            username = "root"
+            password = "123456"
+            payload_buffer_size = 32768
+            file = "mysql-bin.000001"
+
+            [[binlog.sources]]
+            name = "a"
+
+            [[binlog.sources]]
+            name = "b"
+            host = "10.0.0.2"
+            server_id = 100
+
+            [rc_mysql]
+            addr = []
+            username = ""
+            password = ""
+
+            [rc_metadata]
+            addr = ""
+            username = ""
+            password = ""
+            database = ""
+        "#).unwrap();
+
+        let sources = c.binlog.source_configs().unwrap();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].0, "a");
+        assert_eq!(sources[0].1.get_host(), "127.0.0.1");
+        assert!(sources[0].1.file.is_none());
+        assert_eq!(sources[1].1.get_host(), "10.0.0.2");
+        assert_eq!(sources[1].1.get_server_id(), 100);
+        assert_eq!(sources[1].1.username, "root");
+    }
+}
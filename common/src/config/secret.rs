@@ -0,0 +1,179 @@
+use std::process::Command;
+
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use crate::config::RepConfig;
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+
+/// 主密钥的环境变量, 解密 `enc:` 密码时使用
+pub const MASTER_KEY_ENV: &str = "REPLAYER_MASTER_KEY";
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// 解析密码字段:
+///
+/// - `env:VAR`: 环境变量
+/// - `file:path`: 文件内容, 忽略末尾的换行
+/// - `exec:cmd`: 通过 `sh -c` 执行命令, 取标准输出并忽略末尾的换行
+/// - `enc:hex`: 使用主密钥(REPLAYER_MASTER_KEY) 以 AES-256-GCM 加密的密码, 由 `binlog_cli encrypt-password` 生成
+/// - `plain:value`: 原样使用 value, 用于以上述前缀开头的明文密码
+///
+/// 没有前缀时为明文
+pub fn resolve_secret(value: &str) -> CResult<String> {
+    let (scheme, rest) = match value.split_once(':') {
+        Some(split) => split,
+        None => return Ok(value.to_string()),
+    };
+
+    match scheme {
+        "env" => std::env::var(rest).map_err(|e| ReError::ConfigFileParseErr(format!("env {}: {}", rest, e))),
+        "file" => std::fs::read_to_string(rest)
+            .map(trim_newline)
+            .map_err(|e| ReError::ConfigFileParseErr(format!("file {}: {}", rest, e))),
+        "exec" => exec(rest),
+        "enc" => decrypt_secret(rest, &master_key()?),
+        "plain" => Ok(rest.to_string()),
+        _ => Ok(value.to_string()),
+    }
+}
+
+/// 主密钥, 未设置时返回错误
+pub fn master_key() -> CResult<String> {
+    match std::env::var(MASTER_KEY_ENV) {
+        Ok(key) if !key.is_empty() => Ok(key),
+        _ => Err(ReError::ConfigFileParseErr(format!("{} is not set", MASTER_KEY_ENV))),
+    }
+}
+
+/// 加密密码, 返回可直接写入配置的 `enc:` 值
+pub fn encrypt_secret(plaintext: &str, master_key: &str) -> CResult<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand_bytes(&mut nonce).map_err(|e| ReError::Error(e.to_string()))?;
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &sha256(master_key.as_bytes()), Some(&nonce), &[],
+                                  plaintext.as_bytes(), &mut tag)
+        .map_err(|e| ReError::Error(e.to_string()))?;
+
+    // nonce | ciphertext | tag
+    let mut buf = Vec::with_capacity(NONCE_LEN + ciphertext.len() + TAG_LEN);
+    buf.extend_from_slice(&nonce);
+    buf.extend_from_slice(&ciphertext);
+    buf.extend_from_slice(&tag);
+    Ok(format!("enc:{}", hex::encode(buf)))
+}
+
+/// 解密 `enc:` 之后的部分
+pub fn decrypt_secret(hex_value: &str, master_key: &str) -> CResult<String> {
+    let buf = hex::decode(hex_value.trim())
+        .map_err(|e| ReError::ConfigFileParseErr(format!("invalid encrypted value: {}", e)))?;
+    if buf.len() < NONCE_LEN + TAG_LEN {
+        return Err(ReError::ConfigFileParseErr(String::from("invalid encrypted value: too short")));
+    }
+    let (nonce, rest) = buf.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    let plaintext = decrypt_aead(Cipher::aes_256_gcm(), &sha256(master_key.as_bytes()), Some(nonce), &[], ciphertext, tag)
+        .map_err(|_| ReError::ConfigFileParseErr(String::from("decrypt failed, wrong master key or corrupted value")))?;
+
+    String::from_utf8(plaintext).map_err(|e| ReError::ConfigFileParseErr(format!("decrypted value is not utf8: {}", e)))
+}
+
+fn exec(cmd: &str) -> CResult<String> {
+    let output = Command::new("sh").arg("-c").arg(cmd).output()
+        .map_err(|e| ReError::ConfigFileParseErr(format!("exec {}: {}", cmd, e)))?;
+    if !output.status.success() {
+        return Err(ReError::ConfigFileParseErr(format!("exec {}: {}", cmd, output.status)));
+    }
+    String::from_utf8(output.stdout)
+        .map(trim_newline)
+        .map_err(|e| ReError::ConfigFileParseErr(format!("exec {}: output is not utf8, {}", cmd, e)))
+}
+
+fn trim_newline(mut s: String) -> String {
+    while s.ends_with('\n') || s.ends_with('\r') {
+        s.pop();
+    }
+    s
+}
+
+impl RepConfig {
+    /// 将各密码字段解析为明文, 一次返回所有无法解析的字段
+    pub fn resolve_secrets(&mut self) -> CResult<()> {
+        let mut passwords = vec![
+            (String::from("binlog.password"), &mut self.binlog.password),
+            (String::from("rc_mysql.password"), &mut self.rc_mysql.password),
+            (String::from("rc_metadata.password"), &mut self.rc_metadata.password),
+        ];
+        for (i, source) in self.binlog.sources.iter_mut().flatten().enumerate() {
+            if let Some(password) = source.password.as_mut() {
+                passwords.push((format!("binlog.sources[{}].password", i), password));
+            }
+        }
+
+        let mut errors = vec![];
+        for (path, password) in passwords {
+            match resolve_secret(password) {
+                Ok(resolved) => *password = resolved,
+                Err(e) => errors.push(format!("  {}: {}", path, e)),
+            }
+        }
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(ReError::ConfigFileParseErr(format!("{} secret(s) can not be resolved:\n{}", errors.len(), errors.join("\n"))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::RepConfig;
+    use crate::config::secret::{decrypt_secret, encrypt_secret, resolve_secret};
+    use crate::err::CResult;
+
+    #[test]
+    fn test_resolve_secret() -> CResult<()> {
+        assert_eq!("123456", resolve_secret("123456")?);
+        assert_eq!("env:x", resolve_secret("plain:env:x")?);
+        assert_eq!("a:b", resolve_secret("a:b")?);
+
+        std::env::set_var("SECRET_TEST_PASSWORD", "from_env");
+        assert_eq!("from_env", resolve_secret("env:SECRET_TEST_PASSWORD")?);
+        assert!(resolve_secret("env:SECRET_TEST_NOT_SET").is_err());
+
+        let path = std::env::temp_dir().join(format!("secret_test_{}", std::process::id()));
+        std::fs::write(&path, "from_file\n")?;
+        assert_eq!("from_file", resolve_secret(&format!("file:{}", path.display()))?);
+        std::fs::remove_file(&path)?;
+
+        assert_eq!("from_exec", resolve_secret("exec:echo from_exec")?);
+        assert!(resolve_secret("exec:exit 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypt() -> CResult<()> {
+        let value = encrypt_secret("p@ss:word", "master")?;
+        assert!(value.starts_with("enc:"));
+        assert_eq!("p@ss:word", decrypt_secret(&value[4..], "master")?);
+        assert!(decrypt_secret(&value[4..], "other").is_err());
+        // 每次加密使用不同的 nonce
+        assert_ne!(value, encrypt_secret("p@ss:word", "master")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_secrets() {
+        let mut c = RepConfig::default();
+        c.binlog.password = String::from("plain:secret");
+        c.rc_mysql.password = String::from("env:SECRET_TEST_NOT_SET");
+        c.rc_metadata.password = String::from("file:/not/exists");
+
+        let e = c.resolve_secrets().unwrap_err().to_string();
+        assert!(e.starts_with("2 secret(s) can not be resolved"));
+        assert!(e.contains("rc_mysql.password") && e.contains("rc_metadata.password"));
+        assert_eq!("secret", c.binlog.password);
+    }
+}
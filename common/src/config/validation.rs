@@ -0,0 +1,328 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use tracing::Level;
+
+use crate::config::{BaseConfig, BinlogConfig, RateLimitConfig, RcMetadata, RcMySQL, RepConfig, SinkConfig};
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+use crate::log::rolling::RotationPeriod;
+use crate::log::tracing_factory::{parse_module_levels, LogFormat};
+use crate::pretty_util::{parse_bytes_len, parse_duration};
+use crate::schema::column_mapping::ColumnMapping;
+use crate::time_util::TimeZoneSpec;
+
+/// payload_buffer_size 的范围: 1KB ~ 1GB(max_allowed_packet 的上限)
+pub const MIN_PAYLOAD_BUFFER_SIZE: usize = 1024;
+pub const MAX_PAYLOAD_BUFFER_SIZE: usize = 1024 * 1024 * 1024;
+
+/// binlog 文件头之后第一个事件的位置
+const MIN_BINLOG_POSITION: i32 = 4;
+
+/// 配置中的一个问题, path 为配置项的路径, 如 `binlog.sources[1].port`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for ConfigProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Problems(Vec<ConfigProblem>);
+
+impl Problems {
+    fn push<P: Into<String>, M: Into<String>>(&mut self, path: P, message: M) {
+        self.0.push(ConfigProblem { path: path.into(), message: message.into() });
+    }
+}
+
+impl RepConfig {
+    /// 校验全部配置项, 一次返回所有问题; 没有问题时返回空
+    pub fn problems(&self) -> Vec<ConfigProblem> {
+        let mut problems = Problems::default();
+        check_base(&self.base, &mut problems);
+        check_binlog(&self.binlog, &mut problems);
+        check_rc_mysql(&self.rc_mysql, &mut problems);
+        check_rc_metadata(&self.rc_metadata, &mut problems);
+        if let Some(sink) = self.sink.as_ref() {
+            check_sink(sink, &mut problems);
+        }
+        if let Some(rate_limit) = self.rate_limit.as_ref() {
+            check_rate_limit(rate_limit, &mut problems);
+        }
+        if let Some(rules) = self.mapping.as_ref() {
+            if let Err(e) = ColumnMapping::new(rules.clone()) {
+                problems.push("mapping", e.to_string());
+            }
+        }
+        problems.0
+    }
+
+    /// 校验全部配置项, 有问题时返回包含所有问题的 ConfigFileParseErr, 每行一个问题
+    pub fn validate(&self) -> CResult<()> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<String> = problems.iter().map(|p| format!("  {}", p)).collect();
+        Err(ReError::ConfigFileParseErr(format!("{} problem(s) in config:\n{}", problems.len(), lines.join("\n"))))
+    }
+}
+
+fn check_base(base: &BaseConfig, problems: &mut Problems) {
+    if let Some(max_memory) = base.max_memory.as_deref() {
+        if let Err(e) = parse_bytes_len(max_memory) {
+            problems.push("base.max_memory", e.to_string());
+        }
+    }
+    if let Some(log_dir) = base.log_dir.as_deref() {
+        check_writable_dir("base.log_dir", Path::new(log_dir), problems);
+    }
+    if let Some(level) = base.log_level.as_deref() {
+        if Level::from_str(level).is_err() {
+            problems.push("base.log_level", format!("invalid level {}, expect trace | debug | info | warn | error", level));
+        }
+    }
+    if let Some(Err(e)) = base.log_format.as_deref().map(LogFormat::try_from) {
+        problems.push("base.log_format", e.to_string());
+    }
+    if let Some(Err(e)) = base.log_rotation.as_deref().map(RotationPeriod::try_from) {
+        problems.push("base.log_rotation", e.to_string());
+    }
+    if let Some(Err(e)) = base.log_max_size.as_deref().map(parse_bytes_len) {
+        problems.push("base.log_max_size", e.to_string());
+    }
+    if base.log_max_files == Some(0) {
+        problems.push("base.log_max_files", "must be > 0");
+    }
+    if let Some(Err(e)) = base.log_modules.as_ref().map(parse_module_levels) {
+        problems.push("base.log_modules", e.to_string());
+    }
+}
+
+fn check_binlog(binlog: &BinlogConfig, problems: &mut Problems) {
+    // 读取 MySQL(host) 与读取本地文件(binlog_path) 只能选择一种
+    let host = binlog.host.as_deref().filter(|h| !h.is_empty());
+    let binlog_path = binlog.binlog_path.as_deref().filter(|p| !p.is_empty());
+    match (host, binlog_path) {
+        (Some(_), Some(_)) => problems.push("binlog", "host and binlog_path are mutually exclusive"),
+        (_, Some(path)) => {
+            if !Path::new(path).is_dir() {
+                problems.push("binlog.binlog_path", format!("{} is not a directory", path));
+            }
+        },
+        (Some(_), None) => {
+            if binlog.username.is_empty() {
+                problems.push("binlog.username", "required when reading from host");
+            }
+        },
+        (None, None) => {},
+    }
+
+    check_port("binlog.port", binlog.port, problems);
+    if !(MIN_PAYLOAD_BUFFER_SIZE..=MAX_PAYLOAD_BUFFER_SIZE).contains(&binlog.payload_buffer_size) {
+        problems.push("binlog.payload_buffer_size", format!("{} out of range [{}, {}]",
+            binlog.payload_buffer_size, MIN_PAYLOAD_BUFFER_SIZE, MAX_PAYLOAD_BUFFER_SIZE));
+    }
+    check_position("binlog.position", binlog.position, problems);
+    check_server_id("binlog.server_id", binlog.server_id, problems);
+    if let Some(time_zone) = binlog.time_zone.as_deref() {
+        if let Err(e) = TimeZoneSpec::try_from(time_zone) {
+            problems.push("binlog.time_zone", e.to_string());
+        }
+    }
+
+    let mut names = HashSet::new();
+    for (i, source) in binlog.sources.iter().flatten().enumerate() {
+        let path = format!("binlog.sources[{}]", i);
+        if source.name.is_empty() {
+            problems.push(format!("{}.name", path), "required");
+        } else if !names.insert(source.name.as_str()) {
+            problems.push(format!("{}.name", path), format!("duplicate name {}", source.name));
+        }
+        if source.host.as_deref().map_or(false, |h| !h.is_empty()) && binlog_path.is_some() {
+            problems.push(format!("{}.host", path), "host and binlog.binlog_path are mutually exclusive");
+        }
+        if source.username.as_deref().map_or(false, str::is_empty) {
+            problems.push(format!("{}.username", path), "must not be empty");
+        }
+        check_port(&format!("{}.port", path), source.port, problems);
+        check_position(&format!("{}.position", path), source.position, problems);
+        check_server_id(&format!("{}.server_id", path), source.server_id, problems);
+    }
+}
+
+fn check_rc_mysql(rc_mysql: &RcMySQL, problems: &mut Problems) {
+    for (i, addr) in rc_mysql.addr.iter().enumerate() {
+        check_addr(&format!("rc_mysql.addr[{}]", i), addr, problems);
+    }
+    check_interval("rc_mysql.raft_stats_fresh_interval_ms", rc_mysql.raft_stats_fresh_interval_ms, problems);
+}
+
+fn check_rc_metadata(rc_metadata: &RcMetadata, problems: &mut Problems) {
+    if !rc_metadata.addr.is_empty() {
+        check_addr("rc_metadata.addr", &rc_metadata.addr, problems);
+    }
+    check_interval("rc_metadata.metadata_stats_fresh_interval_ms", rc_metadata.metadata_stats_fresh_interval_ms, problems);
+}
+
+fn check_sink(sink: &SinkConfig, problems: &mut Problems) {
+    if let Some(sink_type) = sink.sink_type.as_deref() {
+        if !["stdout", "file", "kafka"].contains(&sink_type) {
+            problems.push("sink.type", format!("unsupported type {}, expect stdout | file | kafka", sink_type));
+        }
+    }
+    if let Some(path) = sink.path.as_deref() {
+        match Path::new(path).parent().filter(|p| !p.as_os_str().is_empty()) {
+            Some(dir) => check_writable_dir("sink.path", dir, problems),
+            None => check_writable_dir("sink.path", Path::new("."), problems),
+        }
+    }
+    if let Some(rotate_size) = sink.rotate_size.as_deref() {
+        if let Err(e) = parse_bytes_len(rotate_size) {
+            problems.push("sink.rotate_size", e.to_string());
+        }
+    }
+    if let Some(rotate_interval) = sink.rotate_interval.as_deref() {
+        if let Err(e) = parse_duration(rotate_interval) {
+            problems.push("sink.rotate_interval", e.to_string());
+        }
+    }
+}
+
+fn check_rate_limit(rate_limit: &RateLimitConfig, problems: &mut Problems) {
+    if let Some(max_bytes) = rate_limit.max_bytes_per_sec.as_deref() {
+        if let Err(e) = parse_bytes_len(max_bytes) {
+            problems.push("rate_limit.max_bytes_per_sec", e.to_string());
+        }
+    }
+}
+
+fn check_port(path: &str, port: Option<i16>, problems: &mut Problems) {
+    if let Some(port) = port.filter(|p| *p <= 0) {
+        problems.push(path, format!("invalid port {}", port));
+    }
+}
+
+fn check_position(path: &str, position: Option<i32>, problems: &mut Problems) {
+    if let Some(position) = position.filter(|p| *p < MIN_BINLOG_POSITION) {
+        problems.push(path, format!("{} is before the first event, must be >= {}", position, MIN_BINLOG_POSITION));
+    }
+}
+
+fn check_server_id(path: &str, server_id: Option<u32>, problems: &mut Problems) {
+    if server_id == Some(0) {
+        problems.push(path, "0 is not allowed for a replica");
+    }
+}
+
+fn check_interval(path: &str, interval: Option<u64>, problems: &mut Problems) {
+    if interval == Some(0) {
+        problems.push(path, "must be > 0");
+    }
+}
+
+/// host:port
+fn check_addr(path: &str, addr: &str, problems: &mut Problems) {
+    let valid = match addr.rsplit_once(':') {
+        Some((host, port)) => !host.is_empty() && port.parse::<u16>().map_or(false, |p| p > 0),
+        None => false,
+    };
+    if !valid {
+        problems.push(path, format!("invalid address {}, expect host:port", addr));
+    }
+}
+
+/// 目录不存在时检查最近的已存在的上级目录, 启动时会创建
+fn check_writable_dir(path: &str, dir: &Path, problems: &mut Problems) {
+    let existing = match dir.ancestors().find(|p| p.exists()) {
+        Some(existing) => existing,
+        None => return,
+    };
+    match existing.metadata() {
+        Ok(m) if !m.is_dir() => problems.push(path, format!("{:?} is not a directory", existing)),
+        Ok(m) if m.permissions().readonly() => problems.push(path, format!("{:?} is not writable", existing)),
+        Ok(_) => {},
+        Err(e) => problems.push(path, format!("{:?}: {}", existing, e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{read_config, RepConfig};
+    use crate::err::CResult;
+
+    #[test]
+    fn test_default_config() -> CResult<()> {
+        read_config("../conf/replayer.toml")?.validate()?;
+        RepConfig::default().validate()
+    }
+
+    #[test]
+    fn test_problems() {
+        let c: RepConfig = toml::from_str(r#"
+            app_name = "replayer"
+
+            [base]
+            log_level = "verbose"
+
+            [binlog]
+            host = "127.0.0.1"
+            port = -1
+            username = ""
+            password = ""
+            payload_buffer_size = 16
+            binlog_path = "/tmp"
+            position = 0
+
+            [[binlog.sources]]
+            name = "a"
+            server_id = 0
+
+            [[binlog.sources]]
+            name = "a"
+
+            [rc_mysql]
+            addr = ["127.0.0.1:3001", "127.0.0.1"]
+            username = ""
+            password = ""
+
+            [rc_metadata]
+            addr = ""
+            username = ""
+            password = ""
+            database = ""
+
+            [sink]
+            type = "redis"
+
+            [rate_limit]
+            max_bytes_per_sec = "ten"
+        "#).unwrap();
+
+        let paths: Vec<String> = c.problems().into_iter().map(|p| p.path).collect();
+        assert_eq!(vec![
+            "base.log_level",
+            "binlog",
+            "binlog.port",
+            "binlog.payload_buffer_size",
+            "binlog.position",
+            "binlog.sources[0].server_id",
+            "binlog.sources[1].name",
+            "rc_mysql.addr[1]",
+            "sink.type",
+            "rate_limit.max_bytes_per_sec",
+        ], paths);
+
+        let e = c.validate().unwrap_err().to_string();
+        assert!(e.starts_with("10 problem(s) in config:"));
+        assert!(e.contains("  binlog.port: invalid port -1"));
+    }
+}
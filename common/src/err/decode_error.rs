@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::fmt::Display;
 use std::{fmt, io};
 use std::num::ParseIntError;
@@ -37,6 +38,12 @@ pub enum ReError {
     FromHexError(FromHexError),
     ParseIntError(ParseIntError),
     ConnectionError(String),
+    /// 认证失败或缺少权限
+    AuthError(String),
+    /// binlog 校验失败: CRC32 不匹配或事件结构损坏
+    ChecksumError(String),
+    /// 事件输出目标写入失败
+    SinkError(String),
     String(String),
 
     /// The parser had an unrecoverable error: we got to the right
@@ -58,13 +65,39 @@ pub enum ReError {
     OpSchemaNotExistErr(String),
     OpMetadataErr(String),
     MetadataMockErr(String),
+
+    /// 带有上下文的错误, 分类与错误码同 source
+    Context {
+        context: String,
+        source: Box<ReError>,
+    },
+}
+
+/// 错误分类, 重连、sink 与 CLI 的退出码按分类处理, 不依赖错误信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// BUG 与未分类的错误
+    Internal,
+    Config,
+    Auth,
+    /// 连接失败或连接中断
+    Connection,
+    Io,
+    /// binlog、SQL 或数据的解析错误
+    Decode,
+    Checksum,
+    Sink,
+    /// 执行 SQL 失败
+    Query,
+    Metadata,
 }
 
 impl Display for ReError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> fmt::Result {
         match self {
             ReError::BUG(s) | ReError::Error(s) | ReError::ASTParserError(s)
-            | ReError::ConnectionError(s) | ReError::String(s) | ReError::Failure(s)
+            | ReError::ConnectionError(s) | ReError::AuthError(s) | ReError::ChecksumError(s) | ReError::SinkError(s)
+            | ReError::String(s) | ReError::Failure(s)
             | ReError::ConfigFileParseErr(s) | ReError::TableSchemaIntoErr(s) | ReError::RcMysqlUrlErr(s)
             | ReError::RcMysqlQueryErr(s) | ReError::OpRaftErr(s) | ReError::MysqlQueryErr(s)
             | ReError::OpTableNotExistErr(s) | ReError::OpSchemaNotExistErr(s) | ReError::OpMetadataErr(s)
@@ -89,6 +122,23 @@ impl Display for ReError {
             ReError::ParseIntError(err) => {
                 write!(f, "{}", err.to_string())
             }
+            ReError::Context { context, source } => {
+                write!(f, "{}: {}", context, source)
+            }
+        }
+    }
+}
+
+impl Error for ReError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReError::IoError(err) => Some(err),
+            ReError::Utf8Error(err) => Some(err),
+            ReError::FromUtf8Error(err) => Some(err),
+            ReError::FromHexError(err) => Some(err),
+            ReError::ParseIntError(err) => Some(err),
+            ReError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
         }
     }
 }
@@ -129,6 +179,11 @@ impl From<ParseIntError> for ReError {
     }
 }
 
+fn is_connection_error(kind: io::ErrorKind) -> bool {
+    matches!(kind, io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected | io::ErrorKind::BrokenPipe | io::ErrorKind::UnexpectedEof | io::ErrorKind::TimedOut)
+}
+
 /// Contains information on needed data if a parser returned `Incomplete`
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Needed {
@@ -167,7 +222,108 @@ impl Display for Needed {
     }
 }
 
+impl ErrorCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCategory::Internal => "internal",
+            ErrorCategory::Config => "config",
+            ErrorCategory::Auth => "auth",
+            ErrorCategory::Connection => "connection",
+            ErrorCategory::Io => "io",
+            ErrorCategory::Decode => "decode",
+            ErrorCategory::Checksum => "checksum",
+            ErrorCategory::Sink => "sink",
+            ErrorCategory::Query => "query",
+            ErrorCategory::Metadata => "metadata",
+        }
+    }
+}
+
 impl ReError {
+    /// 为错误添加上下文, 如 `ReError::from(e).context("read segment 5")`
+    pub fn context<C: Display>(self, context: C) -> ReError {
+        ReError::Context {
+            context: context.to_string(),
+            source: Box::new(self),
+        }
+    }
+
+    /// 去掉上下文之后的错误
+    pub fn root(&self) -> &ReError {
+        match self {
+            ReError::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self.root() {
+            ReError::BUG(_) | ReError::Error(_) | ReError::String(_) | ReError::Failure(_) => ErrorCategory::Internal,
+            ReError::ConfigFileParseErr(_) | ReError::RcMysqlUrlErr(_) => ErrorCategory::Config,
+            ReError::AuthError(_) => ErrorCategory::Auth,
+            ReError::ConnectionError(_) => ErrorCategory::Connection,
+            ReError::IoError(e) if is_connection_error(e.kind()) => ErrorCategory::Connection,
+            ReError::IoError(_) => ErrorCategory::Io,
+            ReError::Incomplete(_) | ReError::Utf8Error(_) | ReError::FromUtf8Error(_) | ReError::FromHexError(_)
+            | ReError::ParseIntError(_) | ReError::ASTParserError(_) | ReError::TableSchemaIntoErr(_) => ErrorCategory::Decode,
+            ReError::ChecksumError(_) => ErrorCategory::Checksum,
+            ReError::SinkError(_) => ErrorCategory::Sink,
+            ReError::MysqlQueryErr(_) | ReError::RcMysqlQueryErr(_) | ReError::OpRaftErr(_) => ErrorCategory::Query,
+            ReError::OpTableNotExistErr(_) | ReError::OpSchemaNotExistErr(_) | ReError::OpMetadataErr(_)
+            | ReError::MetadataMockErr(_) => ErrorCategory::Metadata,
+            ReError::Context { .. } => ErrorCategory::Internal,
+        }
+    }
+
+    /// 稳定的错误码, 千位为分类: 1 internal, 2 config, 3 auth, 4 connection, 5 io, 6 decode, 7 checksum, 8 sink, 9 query, 10 metadata。
+    /// 已发布的错误码不再修改, 新增的错误在分类内顺延
+    pub fn code(&self) -> u16 {
+        match self.root() {
+            ReError::BUG(_) => 1000,
+            ReError::Error(_) => 1001,
+            ReError::String(_) => 1002,
+            ReError::Failure(_) => 1003,
+            ReError::ConfigFileParseErr(_) => 2000,
+            ReError::RcMysqlUrlErr(_) => 2001,
+            ReError::AuthError(_) => 3000,
+            ReError::ConnectionError(_) => 4000,
+            ReError::IoError(e) if is_connection_error(e.kind()) => 4001,
+            ReError::IoError(_) => 5000,
+            ReError::Incomplete(_) => 6000,
+            ReError::Utf8Error(_) => 6001,
+            ReError::FromUtf8Error(_) => 6002,
+            ReError::FromHexError(_) => 6003,
+            ReError::ParseIntError(_) => 6004,
+            ReError::ASTParserError(_) => 6005,
+            ReError::TableSchemaIntoErr(_) => 6006,
+            ReError::ChecksumError(_) => 7000,
+            ReError::SinkError(_) => 8000,
+            ReError::MysqlQueryErr(_) => 9000,
+            ReError::RcMysqlQueryErr(_) => 9001,
+            ReError::OpRaftErr(_) => 9002,
+            ReError::OpTableNotExistErr(_) => 10000,
+            ReError::OpSchemaNotExistErr(_) => 10001,
+            ReError::OpMetadataErr(_) => 10002,
+            ReError::MetadataMockErr(_) => 10003,
+            ReError::Context { .. } => 1000,
+        }
+    }
+
+    /// 暂时性的错误, 等待后重试(重连)可能恢复: 连接中断、超时等
+    pub fn is_retryable(&self) -> bool {
+        match self.root() {
+            ReError::IoError(e) => is_connection_error(e.kind())
+                || matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock),
+            e => e.category() == ErrorCategory::Connection,
+        }
+    }
+
+    /// 重试无法恢复, 需要修改配置或人工处理的错误: BUG、配置错误、认证失败与 binlog 校验失败
+    pub fn is_fatal(&self) -> bool {
+        matches!(self.category(), ErrorCategory::Config | ErrorCategory::Auth | ErrorCategory::Checksum)
+            || matches!(self.root(), ReError::BUG(_))
+    }
+
     pub fn is_error(&self) -> bool {
         print!("a");
 
@@ -177,8 +333,49 @@ impl ReError {
 
 #[cfg(test)]
 mod test {
+    use std::error::Error;
+    use std::io;
+
+    use crate::err::decode_error::{ErrorCategory, ReError};
+
     #[test]
     fn test() {
         assert_eq!(1, 1);
     }
+
+    #[test]
+    fn test_category() {
+        let eof = ReError::IoError(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert_eq!(ErrorCategory::Connection, eof.category());
+        assert_eq!(4001, eof.code());
+        assert!(eof.is_retryable());
+        assert!(!eof.is_fatal());
+
+        let not_found = ReError::IoError(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(ErrorCategory::Io, not_found.category());
+        assert!(!not_found.is_retryable());
+
+        let auth = ReError::AuthError(String::from("Access denied"));
+        assert_eq!(3000, auth.code());
+        assert!(auth.is_fatal());
+        assert!(!auth.is_retryable());
+        assert!(ReError::BUG(String::new()).is_fatal());
+        assert!(!ReError::SinkError(String::new()).is_fatal());
+    }
+
+    #[test]
+    fn test_context() {
+        let e = ReError::IoError(io::Error::from(io::ErrorKind::ConnectionReset))
+            .context("read packet")
+            .context("binlog dump");
+        assert_eq!("binlog dump: read packet: connection reset", e.to_string());
+        assert_eq!(ErrorCategory::Connection, e.category());
+        assert!(e.is_retryable());
+
+        // source 链: binlog dump -> read packet -> io::Error
+        let read_packet = e.source().unwrap();
+        assert_eq!("read packet: connection reset", read_packet.to_string());
+        let io = read_packet.source().unwrap().source().unwrap();
+        assert!(io.downcast_ref::<io::Error>().is_some());
+    }
 }
\ No newline at end of file
@@ -15,4 +15,6 @@ pub mod binlog;
 pub mod file_util;
 pub mod pretty_util;
 pub mod uuid;
-pub mod time_util;
\ No newline at end of file
+pub mod time_util;
+pub mod throttle;
+pub mod memory_governor;
\ No newline at end of file
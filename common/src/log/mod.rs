@@ -1,3 +1,4 @@
+pub mod rolling;
 pub mod tracing_factory;
 
 
@@ -0,0 +1,232 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+
+/// 按时间滚动的周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPeriod {
+    Never,
+    Minutely,
+    Hourly,
+    Daily,
+}
+
+/// 日志文件的滚动策略: 按时间周期, 以及单个文件的最大大小
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRotation {
+    pub period: RotationPeriod,
+
+    /// 单个文件的最大字节数, 超过后在同一周期内滚动为 `.1`、`.2` ...
+    pub max_size: Option<u64>,
+
+    /// 保留的文件数量, 超过时删除最旧的文件。 None 时不清理
+    pub max_files: Option<usize>,
+}
+
+/// 按时间与大小滚动的日志文件。
+///
+/// 文件名为 `prefix.<周期>[.<序号>]`, 如 `file.log.2024-01-02`、`file.log.2024-01-02.1`; 不按时间滚动时为 `file.log[.<序号>]`
+#[derive(Debug)]
+pub struct RollingFile {
+    dir: PathBuf,
+
+    prefix: String,
+
+    rotation: LogRotation,
+
+    // 当前周期
+    stamp: String,
+
+    // 周期内按大小滚动的序号
+    index: usize,
+
+    file: Option<File>,
+
+    size: u64,
+}
+
+impl TryFrom<&str> for RotationPeriod {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "never" => Ok(RotationPeriod::Never),
+            "minutely" => Ok(RotationPeriod::Minutely),
+            "hourly" => Ok(RotationPeriod::Hourly),
+            "daily" => Ok(RotationPeriod::Daily),
+            _ => Err(ReError::ConfigFileParseErr(format!("invalid log rotation {}, expect never | minutely | hourly | daily", value))),
+        }
+    }
+}
+
+impl RotationPeriod {
+    fn stamp(&self, now: DateTime<Utc>) -> String {
+        match self {
+            RotationPeriod::Never => String::new(),
+            RotationPeriod::Minutely => now.format("%Y-%m-%d-%H-%M").to_string(),
+            RotationPeriod::Hourly => now.format("%Y-%m-%d-%H").to_string(),
+            RotationPeriod::Daily => now.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        LogRotation {
+            period: RotationPeriod::Daily,
+            max_size: None,
+            max_files: None,
+        }
+    }
+}
+
+impl RollingFile {
+    /// max_size 为 0 时不按大小滚动
+    pub fn new<P: Into<PathBuf>>(dir: P, prefix: &str, mut rotation: LogRotation) -> CResult<Self> {
+        let dir = dir.into();
+        rotation.max_size = rotation.max_size.filter(|max| *max > 0);
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(RollingFile {
+            dir,
+            prefix: prefix.to_string(),
+            rotation,
+            stamp: String::new(),
+            index: 0,
+            file: None,
+            size: 0,
+        })
+    }
+
+    fn file_name(&self) -> String {
+        let mut name = self.prefix.clone();
+        if !self.stamp.is_empty() {
+            name.push('.');
+            name.push_str(&self.stamp);
+        }
+        if self.index > 0 {
+            name.push_str(&format!(".{}", self.index));
+        }
+        name
+    }
+
+    fn write_at(&mut self, buf: &[u8], now: DateTime<Utc>) -> io::Result<usize> {
+        let stamp = self.rotation.period.stamp(now);
+        if self.file.is_none() || stamp != self.stamp {
+            self.stamp = stamp;
+            self.index = 0;
+            self.open()?;
+        } else if self.rotation.max_size.map_or(false, |max| self.size > 0 && self.size + buf.len() as u64 > max) {
+            self.index += 1;
+            self.open()?;
+        }
+
+        let n = match self.file.as_mut() {
+            Some(file) => file.write(buf)?,
+            None => 0,
+        };
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    /// 打开当前周期的文件, 重启后接着写未满的文件
+    fn open(&mut self) -> io::Result<()> {
+        loop {
+            let path = self.dir.join(self.file_name());
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            if self.rotation.max_size.map_or(false, |max| size >= max) {
+                self.index += 1;
+                continue;
+            }
+
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+            self.size = size;
+            break;
+        }
+        self.prune();
+        Ok(())
+    }
+
+    /// 按修改时间删除超出 max_files 的旧文件
+    fn prune(&self) {
+        let max_files = match self.rotation.max_files {
+            Some(max_files) => max_files,
+            None => return,
+        };
+        let current = self.file_name();
+        let mut files: Vec<(std::time::SystemTime, PathBuf)> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries.flatten()
+                .filter(|e| e.file_name().to_string_lossy().starts_with(&self.prefix) && e.file_name().to_string_lossy() != current)
+                .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+                .collect(),
+            Err(_) => return,
+        };
+        // 当前文件占用一个名额
+        if files.len() < max_files {
+            return;
+        }
+        files.sort();
+        for (_, path) in files.iter().take(files.len() + 1 - max_files.max(1)) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_at(buf, Utc::now())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use crate::log::rolling::{LogRotation, RollingFile, RotationPeriod};
+
+    fn files(dir: &std::path::Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rotate_by_time_and_size() {
+        let dir = std::env::temp_dir().join(format!("rolling_file_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let rotation = LogRotation { period: RotationPeriod::Daily, max_size: Some(10), max_files: None };
+        let mut file = RollingFile::new(&dir, "file.log", rotation).unwrap();
+
+        let day1 = Utc.with_ymd_and_hms(2024, 1, 2, 10, 0, 0).unwrap();
+        let day2 = Utc.with_ymd_and_hms(2024, 1, 3, 0, 0, 1).unwrap();
+        file.write_at(b"12345678", day1).unwrap();
+        file.write_at(b"12345678", day1).unwrap();
+        // 单条超过大小的日志不拆分
+        file.write_at(b"123456789012", day1).unwrap();
+        file.write_at(b"1234567890", day2).unwrap();
+        assert_eq!(vec!["file.log.2024-01-02", "file.log.2024-01-02.1", "file.log.2024-01-02.2", "file.log.2024-01-03"], files(&dir));
+
+        // 重启后跳过已满的文件
+        let rotation = LogRotation { period: RotationPeriod::Daily, max_size: Some(10), max_files: Some(2) };
+        let mut file = RollingFile::new(&dir, "file.log", rotation).unwrap();
+        file.write_at(b"123456789", day2).unwrap();
+        assert_eq!(vec!["file.log.2024-01-03", "file.log.2024-01-03.1"], files(&dir));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,35 @@
+use std::collections::BTreeMap;
 use std::io;
+use std::str::FromStr;
+use std::sync::Mutex;
+use once_cell::sync::{Lazy, OnceCell};
 use tracing::instrument::WithSubscriber;
 use tracing::Level;
-use tracing_appender::rolling;
 use tracing_subscriber::{
     fmt, layer::SubscriberExt, util::SubscriberInitExt, Registry,
 };
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::reload;
+use crate::config::BaseConfig;
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+use crate::log::rolling::{LogRotation, RollingFile, RotationPeriod};
+use crate::pretty_util::parse_bytes_len;
 
 /// TracingFactory 是否全局初始化完成
 static mut is_init: bool = false;
 
+/// 替换全局的日志过滤器, 初始化时设置
+static FILTER_RELOAD: OnceCell<Box<dyn Fn(EnvFilter) -> CResult<()> + Send + Sync>> = OnceCell::new();
+
+/// 当前的日志级别与按模块覆盖的级别, 修改其中一项时据此重建过滤器
+static LOG_FILTER: Lazy<Mutex<LogFilter>> = Lazy::new(|| Mutex::new(LogFilter {
+    level: Level::INFO,
+    modules: vec![],
+}));
+
 #[derive(Debug, Clone, Default)]
 pub struct TracingFactory {
     options: TracingFactoryOptions
@@ -24,6 +44,14 @@ pub struct TracingFactoryOptions {
     level: Option<Level>,
 
     log_dir: Option<String>,
+
+    format: LogFormat,
+
+    /// OutputType::LOG 时日志文件的滚动策略
+    rotation: LogRotation,
+
+    /// 按模块覆盖的日志级别, 如 binlog=debug
+    module_levels: Vec<(String, Level)>,
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +61,58 @@ pub enum OutputType {
     LOG,
 }
 
+/// 日志格式: text 为便于阅读的文本, json 为每行一个 JSON 对象, 便于日志系统采集
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+
+    Json,
+}
+
+#[derive(Debug, Clone)]
+struct LogFilter {
+    level: Level,
+
+    modules: Vec<(String, Level)>,
+}
+
+impl TryFrom<&str> for LogFormat {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(ReError::ConfigFileParseErr(format!("invalid log format {}, expect text | json", value))),
+        }
+    }
+}
+
+impl LogFilter {
+    fn env_filter(&self) -> CResult<EnvFilter> {
+        let mut directives = vec![self.level.to_string().to_lowercase()];
+        for (module, level) in &self.modules {
+            directives.push(format!("{}={}", module, level.to_string().to_lowercase()));
+        }
+        EnvFilter::try_new(directives.join(","))
+            .map_err(|e| ReError::ConfigFileParseErr(format!("invalid log filter: {}", e)))
+    }
+}
+
+/// 解析按模块覆盖的日志级别, 如 `{ binlog = "debug", connection = "warn" }`; 模块为 crate 或模块路径, 如 binlog::events
+pub fn parse_module_levels(modules: &BTreeMap<String, String>) -> CResult<Vec<(String, Level)>> {
+    let mut rs = Vec::with_capacity(modules.len());
+    for (module, level) in modules {
+        if module.is_empty() || !module.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':') {
+            return Err(ReError::ConfigFileParseErr(format!("invalid log module {}", module)));
+        }
+        let level = Level::from_str(level)
+            .map_err(|_| ReError::ConfigFileParseErr(format!("invalid log level {} for module {}", level, module)))?;
+        rs.push((module.clone(), level));
+    }
+    Ok(rs)
+}
+
 impl TracingFactory {
     pub fn init_log(debug: bool) -> Self {
         TracingFactory::init_log_with_options(TracingFactoryOptions::new_with_debug(debug))
@@ -62,46 +142,33 @@ impl TracingFactory {
 
         unsafe {
             if !is_init {
-                // Configure a custom event formatter
-                let format = fmt::format()
-                    .pretty()
-                    // display source code file paths
-                    .with_file(true)
-                    // display source code line numbers
-                    .with_line_number(false)
-                    // .with_level(false) // don't include levels in formatted output
-                    .with_target(false) // don't include targets, disable targets
-                    // enable thread id to be emitted
-                    .with_thread_ids(true) // include the thread ID of the current thread
-                    // enabled thread name to be emitted
-                    .with_thread_names(true) // include the name of the current thread
-                    .compact(); // use the `Compact` formatting style.
+                let filter = LogFilter {
+                    level,
+                    modules: opts.module_levels.clone(),
+                };
+                let env_filter = filter.env_filter().unwrap_or_else(|_| EnvFilter::new(level.to_string()));
+                if let Ok(mut current) = LOG_FILTER.lock() {
+                    *current = filter;
+                }
 
                 match opts.output_type {
                     OutputType::STDOUT => {
                         // let (non_blocking, _guard) = tracing_appender::non_blocking(io::stdout);
 
-                        tracing_subscriber::fmt()
-                            .with_max_level(level)
-                            .event_format(format)
-                            .pretty()
-                            // .with_writer(non_blocking)
-                            // sets this to be the default, global collector for this application.
-                            .init();
+                        init_subscriber(io::stdout, opts.format, env_filter);
                     },
                     OutputType::LOG => {
                         // debug 模式下，std 与 log 同时输出。 否则只输出 file
-                        let file_appender = rolling::daily(format!("{}/binlog", dir.as_str()), "file.log");
-
-                        let merge = file_appender.and(io::stdout);
-
-                        tracing_subscriber::fmt()
-                            .with_max_level(level)
-                            .event_format(format)
-                            .pretty()
-                            .with_writer(merge)
-                            // sets this to be the default, global collector for this application.
-                            .init();
+                        match RollingFile::new(format!("{}/binlog", dir.as_str()), "file.log", opts.rotation.clone()) {
+                            Ok(file_appender) => {
+                                let merge = Mutex::new(file_appender).and(io::stdout);
+                                init_subscriber(merge, opts.format, env_filter);
+                            },
+                            Err(e) => {
+                                eprintln!("open log dir {} failed, log to stdout only: {:?}", dir, e);
+                                init_subscriber(io::stdout, opts.format, env_filter);
+                            }
+                        }
                     }
                 };
 
@@ -117,6 +184,86 @@ impl TracingFactory {
     pub fn get_log_dir(&self) -> &str {
         self.options.get_log_dir()
     }
+
+    /// 修改全局日志级别, 如配置热加载时, 按模块覆盖的级别保持不变。 level 为 trace | debug | info | warn | error
+    pub fn set_log_level(level: &str) -> CResult<()> {
+        let level = Level::from_str(level)
+            .map_err(|_| ReError::ConfigFileParseErr(format!("invalid log level {}", level)))?;
+        update_filter(|filter| filter.level = level)
+    }
+
+    /// 替换按模块覆盖的日志级别, 见 parse_module_levels
+    pub fn set_module_levels(modules: &BTreeMap<String, String>) -> CResult<()> {
+        let modules = parse_module_levels(modules)?;
+        update_filter(|filter| filter.modules = modules)
+    }
+}
+
+fn update_filter<F: FnOnce(&mut LogFilter)>(f: F) -> CResult<()> {
+    let reload = FILTER_RELOAD.get()
+        .ok_or_else(|| ReError::String(String::from("log is not initialized by TracingFactory")))?;
+    let mut filter = LOG_FILTER.lock()
+        .map_err(|e| ReError::String(format!("require lock failed, {}", e)))?;
+    let mut updated = filter.clone();
+    f(&mut updated);
+    reload(updated.env_filter()?)?;
+    *filter = updated;
+    Ok(())
+}
+
+/// 设置全局的 subscriber, 过滤器可以重新加载
+fn init_subscriber<W>(writer: W, format: LogFormat, filter: EnvFilter)
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Text => {
+            // Configure a custom event formatter
+            let format = fmt::format()
+                .pretty()
+                // display source code file paths
+                .with_file(true)
+                // display source code line numbers
+                .with_line_number(false)
+                // .with_level(false) // don't include levels in formatted output
+                .with_target(false) // don't include targets, disable targets
+                // enable thread id to be emitted
+                .with_thread_ids(true) // include the thread ID of the current thread
+                // enabled thread name to be emitted
+                .with_thread_names(true) // include the name of the current thread
+                .compact(); // use the `Compact` formatting style.
+
+            let builder = tracing_subscriber::fmt()
+                .with_env_filter(filter)
+                .event_format(format)
+                .pretty()
+                .with_writer(writer)
+                .with_filter_reloading();
+            set_filter_reload(builder.reload_handle());
+            // sets this to be the default, global collector for this application.
+            builder.init();
+        },
+        LogFormat::Json => {
+            let builder = tracing_subscriber::fmt()
+                .json()
+                // 事件的字段与 level、target 等在同一层
+                .flatten_event(true)
+                .with_file(true)
+                .with_thread_ids(true)
+                .with_thread_names(true)
+                .with_env_filter(filter)
+                .with_writer(writer)
+                .with_filter_reloading();
+            set_filter_reload(builder.reload_handle());
+            builder.init();
+        }
+    }
+}
+
+fn set_filter_reload<S: 'static>(handle: reload::Handle<EnvFilter, S>) {
+    let _ = FILTER_RELOAD.set(Box::new(move |filter| {
+        handle.reload(filter).map_err(|e| ReError::String(e.to_string()))
+    }));
 }
 
 impl Default for TracingFactoryOptions {
@@ -146,7 +293,49 @@ impl TracingFactoryOptions {
             output_type,
             level: Some(level),
             log_dir,
+            format: LogFormat::Text,
+            rotation: LogRotation::default(),
+            module_levels: vec![],
+        }
+    }
+
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: LogRotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_module_levels(mut self, module_levels: Vec<(String, Level)>) -> Self {
+        self.module_levels = module_levels;
+        self
+    }
+
+    /// 应用 [base] 中的日志配置。 debug 时忽略 log_level, 使用 debug 级别
+    pub fn with_base_config(mut self, base: &BaseConfig) -> CResult<Self> {
+        if !self.debug {
+            if let Some(level) = base.get_log_level() {
+                self.level = Some(Level::from_str(level)
+                    .map_err(|_| ReError::ConfigFileParseErr(format!("invalid log level {}", level)))?);
+            }
+        }
+        if let Some(format) = base.get_log_format() {
+            self.format = LogFormat::try_from(format)?;
+        }
+        if let Some(period) = base.get_log_rotation() {
+            self.rotation.period = RotationPeriod::try_from(period)?;
+        }
+        if let Some(max_size) = base.get_log_max_size() {
+            self.rotation.max_size = Some(parse_bytes_len(max_size)?);
         }
+        self.rotation.max_files = base.get_log_max_files();
+        if let Some(modules) = base.get_log_modules() {
+            self.module_levels = parse_module_levels(modules)?;
+        }
+        Ok(self)
     }
 
     pub fn get_log_dir(&self) -> &str {
@@ -159,8 +348,9 @@ impl TracingFactoryOptions {
 
 #[cfg(test)]
 mod test {
-    use tracing::{debug, error, info, warn};
-    use crate::log::tracing_factory::{is_init, TracingFactory};
+    use std::collections::BTreeMap;
+    use tracing::{debug, error, info, warn, Level};
+    use crate::log::tracing_factory::{is_init, parse_module_levels, LogFilter, TracingFactory};
 
     #[test]
     fn test() {
@@ -176,4 +366,23 @@ mod test {
         error!("TracingFactory test: {:?}", "test");
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_module_levels() {
+        let mut modules = BTreeMap::new();
+        modules.insert(String::from("binlog"), String::from("debug"));
+        modules.insert(String::from("connection::conn"), String::from("WARN"));
+        let modules = parse_module_levels(&modules).unwrap();
+        assert_eq!(vec![(String::from("binlog"), Level::DEBUG), (String::from("connection::conn"), Level::WARN)], modules);
+
+        let filter = LogFilter { level: Level::INFO, modules };
+        assert!(filter.env_filter().is_ok());
+
+        let mut invalid = BTreeMap::new();
+        invalid.insert(String::from("binlog=debug"), String::from("info"));
+        assert!(parse_module_levels(&invalid).is_err());
+        invalid.clear();
+        invalid.insert(String::from("binlog"), String::from("verbose"));
+        assert!(parse_module_levels(&invalid).is_err());
+    }
+
+}
@@ -71,7 +71,23 @@ impl WriteValue for Buffer {
                 self.write_bytes(&v.to_le_bytes())?;
                 size += 8;
             }
-            Value::String(v) | Value::JSON(v) => {
+            Value::UnsignedByte(v) => {
+                self.write_byte(*v as i8)?;
+                size += 1;
+            }
+            Value::UnsignedShort(v) => {
+                self.write_bytes(&v.to_le_bytes())?;
+                size += 2;
+            }
+            Value::UnsignedInt(v) => {
+                self.write_bytes(&v.to_le_bytes())?;
+                size += 4;
+            }
+            Value::UnsignedLong(v) | Value::Bitmap(v) => {
+                self.write_bytes(&v.to_le_bytes())?;
+                size += 8;
+            }
+            Value::String(v) | Value::JSON(v) | Value::Enum(v) | Value::Set(v) => {
                 let bytes = v.as_bytes();
                 //length: 4 bytes
                 self.write_bytes(&(v.len() as i32).to_le_bytes())?;
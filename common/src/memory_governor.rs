@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::config::BaseConfig;
+use crate::err::CResult;
+use crate::pretty_util::{parse_bytes_len, to_bytes_len_pretty};
+
+/// 已用内存达到预算的该比例时为 MemoryPressure::High, 可以释放缓存的组件应开始释放
+pub const HIGH_WATERMARK: f64 = 0.8;
+
+/// reserve 等待释放的最长时间, 超时后透支并告警, 避免所有组件互相等待
+pub const DEFAULT_BACKPRESSURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static GLOBAL: Lazy<Arc<MemoryGovernor>> = Lazy::new(|| MemoryGovernor::new(None));
+
+/// 内存压力
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemoryPressure {
+    Normal,
+
+    /// 超过 HIGH_WATERMARK, 缓存应停止增长并释放
+    High,
+
+    /// 达到预算, 新的申请需要等待或被拒绝
+    Exceeded,
+}
+
+/// 全局内存预算, 对应 [base] max_memory。
+///
+/// 缓冲区、中继日志缓存、未提交的事务等组件通过 [MemoryGovernor::register] 登记各自占用的内存:
+/// 可以丢弃的缓存使用 try_reserve, 失败时释放(spill); 生产者使用 reserve, 超出预算时等待其他组件释放(背压)。
+/// 未配置 max_memory 时只统计, 不限制
+pub struct MemoryGovernor {
+    // 0 为不限制
+    limit: AtomicU64,
+
+    used: AtomicU64,
+
+    consumers: Mutex<BTreeMap<u64, (String, Arc<AtomicU64>)>>,
+
+    next_id: AtomicU64,
+
+    // 释放内存时唤醒等待的 reserve
+    lock: Mutex<()>,
+    released: Condvar,
+    released_async: Notify,
+}
+
+/// 一个组件登记的内存, drop 时释放全部
+pub struct MemoryConsumer {
+    governor: Arc<MemoryGovernor>,
+
+    id: u64,
+
+    name: String,
+
+    used: Arc<AtomicU64>,
+}
+
+/// 内存使用情况
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// None 为不限制
+    pub limit: Option<u64>,
+
+    pub used: u64,
+
+    /// 组件名称 -> 占用的字节数
+    pub consumers: Vec<(String, u64)>,
+}
+
+impl MemoryGovernor {
+    pub fn new(limit: Option<u64>) -> Arc<Self> {
+        Arc::new(MemoryGovernor {
+            limit: AtomicU64::new(limit.unwrap_or(0)),
+            used: AtomicU64::new(0),
+            consumers: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+            lock: Mutex::new(()),
+            released: Condvar::new(),
+            released_async: Notify::new(),
+        })
+    }
+
+    /// 进程内共享的预算, 默认不限制, 启动时由 configure 设置
+    pub fn global() -> Arc<MemoryGovernor> {
+        GLOBAL.clone()
+    }
+
+    /// 按 [base] max_memory 设置全局预算, 配置热加载时可以再次调用
+    pub fn configure(base: &BaseConfig) -> CResult<()> {
+        let limit = base.get_max_memory().map(parse_bytes_len).transpose()?;
+        Self::global().set_limit(limit);
+        Ok(())
+    }
+
+    pub fn set_limit(&self, limit: Option<u64>) {
+        self.limit.store(limit.unwrap_or(0), Ordering::SeqCst);
+        self.notify_released();
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        Some(self.limit.load(Ordering::SeqCst)).filter(|l| *l > 0)
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub fn pressure(&self) -> MemoryPressure {
+        let limit = match self.limit() {
+            Some(limit) => limit,
+            None => return MemoryPressure::Normal,
+        };
+        let used = self.used();
+        if used >= limit {
+            MemoryPressure::Exceeded
+        } else if used as f64 >= limit as f64 * HIGH_WATERMARK {
+            MemoryPressure::High
+        } else {
+            MemoryPressure::Normal
+        }
+    }
+
+    /// 登记一个组件, name 用于统计, 如 `relay_cache:db1#t1`
+    pub fn register(self: &Arc<Self>, name: &str) -> MemoryConsumer {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let used = Arc::new(AtomicU64::new(0));
+        if let Ok(mut consumers) = self.consumers.lock() {
+            consumers.insert(id, (name.to_string(), used.clone()));
+        }
+        MemoryConsumer {
+            governor: self.clone(),
+            id,
+            name: name.to_string(),
+            used,
+        }
+    }
+
+    pub fn usage(&self) -> MemoryUsage {
+        let consumers = match self.consumers.lock() {
+            Ok(consumers) => consumers.values()
+                .map(|(name, used)| (name.clone(), used.load(Ordering::SeqCst)))
+                .collect(),
+            Err(_) => vec![],
+        };
+        MemoryUsage {
+            limit: self.limit(),
+            used: self.used(),
+            consumers,
+        }
+    }
+
+    /// 预算内时占用 bytes。 没有任何占用时允许透支, 单个超过预算的申请不会被永久拒绝
+    fn try_acquire(&self, bytes: u64) -> bool {
+        let limit = self.limit.load(Ordering::SeqCst);
+        self.used.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+            if limit == 0 || used == 0 || used + bytes <= limit {
+                Some(used + bytes)
+            } else {
+                None
+            }
+        }).is_ok()
+    }
+
+    fn force_acquire(&self, bytes: u64) {
+        self.used.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn release(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let _ = self.used.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| Some(used.saturating_sub(bytes)));
+        self.notify_released();
+    }
+
+    fn notify_released(&self) {
+        // 持有锁再通知, 避免与检查之后、等待之前的 reserve 错过唤醒
+        let _guard = self.lock.lock();
+        self.released.notify_all();
+        self.released_async.notify_waiters();
+    }
+}
+
+impl Debug for MemoryGovernor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryGovernor")
+            .field("limit", &self.limit())
+            .field("used", &self.used())
+            .finish()
+    }
+}
+
+impl MemoryConsumer {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 该组件占用的字节数
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub fn pressure(&self) -> MemoryPressure {
+        self.governor.pressure()
+    }
+
+    /// 预算内时占用 bytes 并返回 true; 否则返回 false, 由调用方释放缓存或落盘
+    pub fn try_reserve(&self, bytes: u64) -> bool {
+        if !self.governor.try_acquire(bytes) {
+            return false;
+        }
+        self.used.fetch_add(bytes, Ordering::SeqCst);
+        true
+    }
+
+    /// 占用 bytes, 超出预算时阻塞等待其他组件释放。 等待超过 timeout 后透支并告警
+    pub fn reserve_blocking(&self, bytes: u64, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut guard = self.governor.lock.lock().ok();
+        while !self.try_reserve(bytes) {
+            let now = Instant::now();
+            if now >= deadline {
+                drop(guard);
+                self.overdraw(bytes, timeout);
+                return;
+            }
+            guard = match guard {
+                Some(g) => self.governor.released.wait_timeout(g, deadline - now).ok().map(|(g, _)| g),
+                None => None,
+            };
+        }
+    }
+
+    /// reserve_blocking 的异步版本。 预算内占用时返回 true, 等待超时透支时返回 false
+    pub async fn reserve(&self, bytes: u64, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let released = self.governor.released_async.notified();
+            if self.try_reserve(bytes) {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                self.overdraw(bytes, timeout);
+                return false;
+            }
+        }
+    }
+
+    /// 不等待直接占用, 可能透支。 用于同一批次中已等待超时并透支之后的占用, 不再逐条等待
+    pub fn force_reserve(&self, bytes: u64) {
+        self.governor.force_acquire(bytes);
+        self.used.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    fn overdraw(&self, bytes: u64, timeout: Duration) {
+        self.governor.force_acquire(bytes);
+        self.used.fetch_add(bytes, Ordering::SeqCst);
+        warn!("memory budget {} exceeded, {} overdraws {} after waiting {:?}, used {}",
+            to_bytes_len_pretty(self.governor.limit().unwrap_or(0) as usize), self.name,
+            to_bytes_len_pretty(bytes as usize), timeout, to_bytes_len_pretty(self.governor.used() as usize));
+    }
+
+    /// 释放 bytes, 超过已占用的部分忽略
+    pub fn release(&self, bytes: u64) {
+        let released = match self.used.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| Some(used.saturating_sub(bytes))) {
+            Ok(used) => used.min(bytes),
+            Err(_) => 0,
+        };
+        self.governor.release(released);
+    }
+
+    /// 释放该组件占用的全部内存
+    pub fn release_all(&self) {
+        let used = self.used.swap(0, Ordering::SeqCst);
+        self.governor.release(used);
+    }
+}
+
+impl Drop for MemoryConsumer {
+    fn drop(&mut self) {
+        self.release_all();
+        if let Ok(mut consumers) = self.governor.consumers.lock() {
+            consumers.remove(&self.id);
+        }
+    }
+}
+
+impl Debug for MemoryConsumer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryConsumer")
+            .field("name", &self.name)
+            .field("used", &self.used())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::memory_governor::{MemoryGovernor, MemoryPressure};
+
+    #[test]
+    fn test_try_reserve() {
+        let governor = MemoryGovernor::new(Some(100));
+        let cache = governor.register("cache");
+        let rows = governor.register("rows");
+
+        assert!(cache.try_reserve(70));
+        assert_eq!(MemoryPressure::Normal, governor.pressure());
+        assert!(rows.try_reserve(10));
+        assert_eq!(MemoryPressure::High, governor.pressure());
+        assert!(!rows.try_reserve(30));
+
+        cache.release(50);
+        assert!(rows.try_reserve(30));
+        assert_eq!(vec![(String::from("cache"), 20), (String::from("rows"), 40)], governor.usage().consumers);
+
+        drop(cache);
+        assert_eq!(40, governor.used());
+        assert_eq!(1, governor.usage().consumers.len());
+
+        // 没有占用时允许单个超过预算的申请
+        rows.release_all();
+        assert!(rows.try_reserve(500));
+        assert_eq!(MemoryPressure::Exceeded, governor.pressure());
+    }
+
+    #[test]
+    fn test_backpressure() {
+        let governor = MemoryGovernor::new(Some(100));
+        let cache = Arc::new(governor.register("cache"));
+        let rows = governor.register("rows");
+        assert!(cache.try_reserve(90));
+
+        let c = cache.clone();
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            c.release(50);
+        });
+        let start = Instant::now();
+        rows.reserve_blocking(40, Duration::from_secs(10));
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(80, governor.used());
+        release.join().unwrap();
+
+        // 超时后透支
+        rows.reserve_blocking(40, Duration::from_millis(10));
+        assert_eq!(120, governor.used());
+        assert_eq!(MemoryPressure::Exceeded, governor.pressure());
+
+        // 透支后同一批次直接占用
+        rows.force_reserve(30);
+        assert_eq!(150, governor.used());
+        assert_eq!(110, rows.used());
+
+        // 不限制
+        governor.set_limit(None);
+        assert!(rows.try_reserve(1 << 30));
+        assert_eq!(MemoryPressure::Normal, rows.pressure());
+    }
+}
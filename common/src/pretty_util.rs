@@ -5,6 +5,8 @@ use mysql_common::serde_json;
 use pretty_duration::{pretty_duration, PrettyDurationOptions, PrettyDurationOutputFormat};
 use serde::Serialize;
 use crate::config::load_style::Format;
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
 
 /// Duration 的格式化输出
 pub fn to_duration_pretty(duration: &Duration) -> String {
@@ -60,4 +62,247 @@ pub fn to_string_pretty<T: Sized + Serialize + Debug>(f: &Format, val: &T) -> St
             format!("{:?}", val)
         }
     }
-}
\ No newline at end of file
+}
+
+/// 解析字节大小, 如 `100MB`、`1GiB`、`4096`
+pub fn parse_bytes_len(s: &str) -> CResult<u64> {
+    match Byte::parse_str(s.trim(), true) {
+        Ok(b) => Ok(b.as_u64()),
+        Err(e) => Err(ReError::String(format!("Invalid bytes size {}: {}", s, e))),
+    }
+}
+
+/// 解析时长, 如 `30s`、`10m`、`1h`、`1d`, 没有单位时为秒
+pub fn parse_duration(s: &str) -> CResult<Duration> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "s"),
+    };
+
+    let num: u64 = num.parse().map_err(|_| ReError::String(format!("Invalid duration {}", s)))?;
+    let secs = match unit.trim() {
+        "s" => num,
+        "m" => num * 60,
+        "h" => num * 60 * 60,
+        "d" => num * 24 * 60 * 60,
+        _ => return Err(ReError::String(format!("Invalid duration unit {}, expect s/m/h/d", s))),
+    };
+
+    Ok(Duration::from_secs(secs))
+}
+
+/// 表格中单元格的默认最大宽度(字符数), 超出部分截断为 `...`
+pub const DEFAULT_CELL_WIDTH: usize = 64;
+
+/// 表格样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableStyle {
+    /// `+---+` 边框
+    Ascii,
+
+    Markdown,
+}
+
+impl TryFrom<&str> for TableStyle {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_ascii_lowercase().as_str() {
+            "ascii" => Ok(TableStyle::Ascii),
+            "markdown" | "md" => Ok(TableStyle::Markdown),
+            _ => Err(ReError::String(format!("Invalid table style {}, expect ascii | markdown", value))),
+        }
+    }
+}
+
+/// 行变更的表格。 首列为操作: I / D, 更新为更新前(`U-`)与更新后(`U+`)两行,
+/// 更新后的行中变化的值标记为 `*value`(markdown 中为粗体)。 NULL 显示为 `NULL`
+#[derive(Debug, Clone)]
+pub struct RowTable {
+    style: TableStyle,
+
+    columns: Vec<String>,
+
+    rows: Vec<Vec<String>>,
+
+    /// 0 为不限制
+    max_width: usize,
+}
+
+impl RowTable {
+    pub fn new(style: TableStyle, columns: Vec<String>) -> Self {
+        RowTable {
+            style,
+            columns,
+            rows: vec![],
+            max_width: DEFAULT_CELL_WIDTH,
+        }
+    }
+
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// 插入或删除的一行
+    pub fn add_row(&mut self, op: &str, cells: &[Option<String>]) {
+        let mut row = vec![op.to_string()];
+        row.extend(cells.iter().map(|c| self.cell_text(c, false)));
+        self.rows.push(row);
+    }
+
+    /// 更新的一行, 输出更新前后两行
+    pub fn add_update(&mut self, before: &[Option<String>], after: &[Option<String>]) {
+        self.add_row("U-", before);
+
+        let mut row = vec![String::from("U+")];
+        row.extend(after.iter().enumerate().map(|(i, c)| self.cell_text(c, before.get(i) != Some(c))));
+        self.rows.push(row);
+    }
+
+    /// 行数, 更新计为两行
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn cell_text(&self, cell: &Option<String>, changed: bool) -> String {
+        let text = match cell {
+            None => String::from("NULL"),
+            Some(text) => {
+                let mut escaped = String::with_capacity(text.len());
+                for c in text.chars() {
+                    match c {
+                        '\n' => escaped.push_str("\\n"),
+                        '\r' => escaped.push_str("\\r"),
+                        '\t' => escaped.push_str("\\t"),
+                        '|' if self.style == TableStyle::Markdown => escaped.push_str("\\|"),
+                        _ => escaped.push(c),
+                    }
+                }
+                escaped
+            }
+        };
+        let text = if self.max_width > 0 && text.chars().count() > self.max_width {
+            let mut truncated: String = text.chars().take(self.max_width.saturating_sub(3)).collect();
+            truncated.push_str("...");
+            truncated
+        } else {
+            text
+        };
+
+        match (changed, self.style) {
+            (false, _) => text,
+            (true, TableStyle::Ascii) => format!("*{}", text),
+            (true, TableStyle::Markdown) => format!("**{}**", text),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        // 行中多出的列使用 `@n` 作为列名
+        let width = self.rows.iter().map(|r| r.len()).max().unwrap_or(0).max(self.columns.len() + 1);
+        let mut header = vec![String::from("op")];
+        header.extend((1..width).map(|i| self.columns.get(i - 1).cloned().unwrap_or_else(|| format!("@{}", i))));
+
+        let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+
+        let line = |cells: &[String]| {
+            let padded: Vec<String> = widths.iter().enumerate()
+                .map(|(i, w)| {
+                    let cell = cells.get(i).map(String::as_str).unwrap_or("");
+                    format!("{}{}", cell, " ".repeat(w - cell.chars().count()))
+                })
+                .collect();
+            format!("| {} |\n", padded.join(" | "))
+        };
+        let separator = match self.style {
+            TableStyle::Ascii => format!("+{}+\n", widths.iter().map(|w| "-".repeat(w + 2)).collect::<Vec<_>>().join("+")),
+            TableStyle::Markdown => format!("|{}|\n", widths.iter().map(|w| "-".repeat(w + 2)).collect::<Vec<_>>().join("|")),
+        };
+
+        let mut out = String::new();
+        if self.style == TableStyle::Ascii {
+            out.push_str(&separator);
+        }
+        out.push_str(&line(&header));
+        out.push_str(&separator);
+        for row in &self.rows {
+            out.push_str(&line(row));
+        }
+        if self.style == TableStyle::Ascii {
+            out.push_str(&separator);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use crate::pretty_util::{parse_bytes_len, parse_duration, RowTable, TableStyle};
+
+    #[test]
+    fn test_parse_bytes_len() {
+        assert_eq!(parse_bytes_len("4096").unwrap(), 4096);
+        assert_eq!(parse_bytes_len("100MB").unwrap(), 100_000_000);
+        assert_eq!(parse_bytes_len("1GiB").unwrap(), 1 << 30);
+        assert!(parse_bytes_len("abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_duration("1w").is_err());
+    }
+
+    fn cells(values: &[Option<&str>]) -> Vec<Option<String>> {
+        values.iter().map(|v| v.map(String::from)).collect()
+    }
+
+    #[test]
+    fn test_ascii_table() {
+        let mut table = RowTable::new(TableStyle::Ascii, vec![String::from("id"), String::from("name")]);
+        table.add_row("I", &cells(&[Some("1"), Some("alice")]));
+        table.add_update(&cells(&[Some("2"), None]), &cells(&[Some("2"), Some("bob")]));
+        assert_eq!(3, table.len());
+
+        let expect = "\
++----+----+-------+
+| op | id | name  |
++----+----+-------+
+| I  | 1  | alice |
+| U- | 2  | NULL  |
+| U+ | 2  | *bob  |
++----+----+-------+
+";
+        assert_eq!(expect, table.render());
+    }
+
+    #[test]
+    fn test_markdown_table() {
+        let mut table = RowTable::new(TableStyle::Markdown, vec![String::from("id")]).with_max_width(8);
+        table.add_row("D", &cells(&[Some("1"), Some("a|b\nc")]));
+        table.add_update(&cells(&[Some("2")]), &cells(&[Some("0123456789")]));
+
+        let expect = "\
+| op | id           | @2      |
+|----|--------------|---------|
+| D  | 1            | a\\|b\\nc |
+| U- | 2            |         |
+| U+ | **01234...** |         |
+";
+        assert_eq!(expect, table.render());
+        assert!(TableStyle::try_from("html").is_err());
+    }
+}
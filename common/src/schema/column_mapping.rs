@@ -0,0 +1,447 @@
+use serde::{Deserialize, Serialize};
+
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+use crate::schema::data_type::{DstColumnType, Value};
+
+/// 声明式的表与列映射规则, 对应配置中的 [[mapping]]:
+///
+/// ```toml
+/// [[mapping]]
+/// type = "rename_column"
+/// table = "db1.t1"
+/// column = "name"
+/// to = "user_name"
+///
+/// [[mapping]]
+/// type = "cast_column"
+/// table = "db1.t1"
+/// column = "amount"
+/// to = "string"
+///
+/// [[mapping]]
+/// type = "computed_column"
+/// table = "db1.t1"
+/// column = "full_name"
+/// expr = "{first_name} {last_name}"
+/// ```
+///
+/// 规则按顺序作用于源表的列, 后面的规则看到的是前面规则映射后的列名; 修改、删除或转换不存在的列时跳过该规则。
+/// table 按源库表匹配, 格式为 `[db.]table`, 不指定库时匹配任意库, `*` 匹配任意库或表
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MappingRule {
+    /// 修改表名, to 为 `[db.]table`, 不指定库时保留原库名
+    RenameTable { table: String, to: String },
+    /// 修改列名
+    RenameColumn { table: String, column: String, to: String },
+    /// 删除列
+    DropColumn { table: String, column: String },
+    /// 转换列的类型, to 为 [DstColumnType] 的名称, 如 string、long、decimal
+    CastColumn { table: String, column: String, to: String },
+    /// 新增或替换为计算列: expr 中的 `{column}` 替换为该列的文本值, 任一列为 NULL 时结果为 NULL。
+    /// 结果为 string, 指定 data_type 时再转换为该类型
+    ComputedColumn {
+        table: String,
+        column: String,
+        expr: String,
+        #[serde(default)]
+        data_type: Option<String>,
+    },
+}
+
+/// 按顺序执行的映射规则
+#[derive(Debug, Clone, Default)]
+pub struct ColumnMapping {
+    rules: Vec<MappingRule>,
+}
+
+/// 一张表的映射结果: 目标库表、目标列与每个目标列的取值方式
+#[derive(Debug, Clone)]
+pub struct TableMapping {
+    pub database: String,
+    pub table: String,
+    pub columns: Vec<(String, DstColumnType)>,
+    sources: Vec<ColumnSource>,
+    // 列与取值都没有变化
+    identity: bool,
+}
+
+/// 目标列的取值方式
+#[derive(Debug, Clone)]
+enum ColumnSource {
+    /// 源行中的第 i 列
+    Column(usize),
+    Cast(Box<ColumnSource>, DstColumnType),
+    Computed(Vec<ExprPart>),
+}
+
+#[derive(Debug, Clone)]
+enum ExprPart {
+    Text(String),
+    Column(ColumnSource),
+}
+
+impl MappingRule {
+    fn table(&self) -> &str {
+        match self {
+            MappingRule::RenameTable { table, .. } |
+            MappingRule::RenameColumn { table, .. } |
+            MappingRule::DropColumn { table, .. } |
+            MappingRule::CastColumn { table, .. } |
+            MappingRule::ComputedColumn { table, .. } => table,
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// 校验规则中的类型名与表达式
+    pub fn new(rules: Vec<MappingRule>) -> CResult<Self> {
+        for rule in &rules {
+            match rule {
+                MappingRule::CastColumn { to, .. } => {
+                    DstColumnType::try_from(to.clone())?;
+                }
+                MappingRule::ComputedColumn { expr, data_type, .. } => {
+                    parse_expr(expr)?;
+                    if let Some(data_type) = data_type {
+                        DstColumnType::try_from(data_type.clone())?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 目标库表名
+    pub fn map_table_name(&self, database: &str, table: &str) -> (String, String) {
+        let mut target = (database.to_string(), table.to_string());
+        for rule in &self.rules {
+            if let MappingRule::RenameTable { table: pattern, to } = rule {
+                if table_matches(pattern, database, table) {
+                    target = match to.split_once('.') {
+                        Some((db, tbl)) => (db.to_string(), tbl.to_string()),
+                        None => (target.0, to.clone()),
+                    };
+                }
+            }
+        }
+        target
+    }
+
+    /// 按规则映射一张表, columns 为源表的列名与类型。 计算列引用不存在的列时返回错误
+    pub fn map_table(&self, database: &str, table: &str, columns: &[(String, DstColumnType)]) -> CResult<TableMapping> {
+        let (target_database, target_table) = self.map_table_name(database, table);
+        let mut mapping = TableMapping {
+            database: target_database,
+            table: target_table,
+            columns: columns.to_vec(),
+            sources: (0..columns.len()).map(ColumnSource::Column).collect(),
+            identity: true,
+        };
+
+        for rule in &self.rules {
+            if !table_matches(rule.table(), database, table) {
+                continue;
+            }
+            match rule {
+                MappingRule::RenameTable { .. } => {}
+                MappingRule::RenameColumn { column, to, .. } => {
+                    if let Some(i) = mapping.position(column) {
+                        mapping.columns[i].0 = to.clone();
+                    }
+                }
+                MappingRule::DropColumn { column, .. } => {
+                    if let Some(i) = mapping.position(column) {
+                        mapping.columns.remove(i);
+                        mapping.sources.remove(i);
+                        mapping.identity = false;
+                    }
+                }
+                MappingRule::CastColumn { column, to, .. } => {
+                    let i = match mapping.position(column) {
+                        Some(i) => i,
+                        None => continue,
+                    };
+                    let to = DstColumnType::try_from(to.clone())?;
+                    let source = mapping.sources[i].clone();
+                    mapping.sources[i] = ColumnSource::Cast(Box::new(source), to);
+                    mapping.columns[i].1 = to;
+                    mapping.identity = false;
+                }
+                MappingRule::ComputedColumn { column, expr, data_type, .. } => {
+                    let mut parts = vec![];
+                    for part in parse_expr(expr)? {
+                        parts.push(match part {
+                            Ok(text) => ExprPart::Text(text),
+                            Err(name) => ExprPart::Column(mapping.sources[mapping.column_index(&name)?].clone()),
+                        });
+                    }
+                    let mut source = ColumnSource::Computed(parts);
+                    let mut column_type = DstColumnType::String;
+                    if let Some(data_type) = data_type {
+                        column_type = DstColumnType::try_from(data_type.clone())?;
+                        source = ColumnSource::Cast(Box::new(source), column_type);
+                    }
+                    match mapping.position(column) {
+                        Some(i) => {
+                            mapping.columns[i].1 = column_type;
+                            mapping.sources[i] = source;
+                        }
+                        None => {
+                            mapping.columns.push((column.clone(), column_type));
+                            mapping.sources.push(source);
+                        }
+                    }
+                    mapping.identity = false;
+                }
+            }
+        }
+        Ok(mapping)
+    }
+}
+
+impl TableMapping {
+    fn position(&self, column: &str) -> Option<usize> {
+        self.columns.iter().position(|(name, _)| name == column)
+    }
+
+    fn column_index(&self, column: &str) -> CResult<usize> {
+        self.position(column).ok_or_else(|| {
+            ReError::String(format!("mapping column {} not found in {}.{}", column, self.database, self.table))
+        })
+    }
+
+    /// 行的值不需要转换
+    pub fn is_identity(&self) -> bool {
+        self.identity
+    }
+
+    /// 把源表的一行转换为目标列的值
+    pub fn map_row(&self, values: &[Value]) -> CResult<Vec<Value>> {
+        if self.identity {
+            return Ok(values.to_vec());
+        }
+        self.sources.iter().map(|s| s.value(values)).collect()
+    }
+}
+
+impl ColumnSource {
+    fn value(&self, values: &[Value]) -> CResult<Value> {
+        match self {
+            ColumnSource::Column(i) => Ok(values.get(*i).cloned().unwrap_or(Value::Null)),
+            ColumnSource::Cast(source, to) => cast_value(source.value(values)?, *to),
+            ColumnSource::Computed(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        ExprPart::Text(t) => text.push_str(t),
+                        ExprPart::Column(source) => match source.value(values)?.text() {
+                            Some(t) => text.push_str(&t),
+                            None => return Ok(Value::Null),
+                        },
+                    }
+                }
+                Ok(Value::String(text))
+            }
+        }
+    }
+}
+
+/// `[db.]table` 是否匹配
+pub fn table_matches(pattern: &str, database: &str, table: &str) -> bool {
+    let (db, tbl) = match pattern.split_once('.') {
+        Some((db, tbl)) => (Some(db), tbl),
+        None => (None, pattern),
+    };
+    db.map_or(true, |db| db == "*" || db == database) && (tbl == "*" || tbl == table)
+}
+
+/// 解析计算列的表达式: Ok 为文本, Err 为列名; `{{` 与 `}}` 为花括号本身
+fn parse_expr(expr: &str) -> CResult<Vec<Result<String, String>>> {
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if !closed || name.trim().is_empty() {
+                    return Err(ReError::String(format!("Invalid mapping expr {}, expect {{column}}", expr)));
+                }
+                if !text.is_empty() {
+                    parts.push(Ok(std::mem::take(&mut text)));
+                }
+                parts.push(Err(name.trim().to_string()));
+            }
+            '}' => return Err(ReError::String(format!("Invalid mapping expr {}, unmatched }}", expr))),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Ok(text));
+    }
+    Ok(parts)
+}
+
+/// 把值转换为 to 类型, NULL 保持为 NULL; 无法转换时返回错误
+pub fn cast_value(value: Value, to: DstColumnType) -> CResult<Value> {
+    if matches!(value, Value::Null) || value.get_data_type() as i32 == to as i32 {
+        return Ok(value);
+    }
+    let text = value.text().unwrap_or_default();
+    let invalid = || ReError::String(format!("can not cast {:?} to {:?}", value, to));
+    let cast = match to {
+        DstColumnType::String => Value::String(text),
+        DstColumnType::JSON => Value::JSON(text),
+        DstColumnType::Decimal => {
+            text.parse::<f64>().map_err(|_| invalid())?;
+            Value::Decimal(text)
+        }
+        DstColumnType::Boolean => match text.to_ascii_lowercase().as_str() {
+            "1" | "true" => Value::Boolean(true),
+            "0" | "false" => Value::Boolean(false),
+            _ => return Err(invalid()),
+        },
+        DstColumnType::Byte => Value::Byte(text.parse().map_err(|_| invalid())?),
+        DstColumnType::Short => Value::Short(text.parse().map_err(|_| invalid())?),
+        DstColumnType::Int => Value::Int(text.parse().map_err(|_| invalid())?),
+        DstColumnType::Long => Value::Long(text.parse().map_err(|_| invalid())?),
+        DstColumnType::UnsignedByte => Value::UnsignedByte(text.parse().map_err(|_| invalid())?),
+        DstColumnType::UnsignedShort => Value::UnsignedShort(text.parse().map_err(|_| invalid())?),
+        DstColumnType::UnsignedInt => Value::UnsignedInt(text.parse().map_err(|_| invalid())?),
+        DstColumnType::UnsignedLong => Value::UnsignedLong(text.parse().map_err(|_| invalid())?),
+        DstColumnType::Bitmap => Value::Bitmap(text.parse().map_err(|_| invalid())?),
+        DstColumnType::Enum => Value::Enum(text),
+        DstColumnType::Set => Value::Set(text),
+        DstColumnType::Float => Value::Float(text.parse().map_err(|_| invalid())?),
+        DstColumnType::Double => Value::Double(text.parse().map_err(|_| invalid())?),
+        DstColumnType::Bytes => Value::Bytes(text.into_bytes()),
+        DstColumnType::Blob => Value::Blob(text.into_bytes()),
+        _ => return Err(invalid()),
+    };
+    Ok(cast)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::err::CResult;
+    use crate::schema::column_mapping::{ColumnMapping, MappingRule};
+    use crate::schema::data_type::{DstColumnType, Value};
+
+    fn columns() -> Vec<(String, DstColumnType)> {
+        vec![
+            ("id".to_string(), DstColumnType::Long),
+            ("first_name".to_string(), DstColumnType::String),
+            ("last_name".to_string(), DstColumnType::String),
+            ("amount".to_string(), DstColumnType::Decimal),
+            ("secret".to_string(), DstColumnType::String),
+        ]
+    }
+
+    fn text(values: &[Value]) -> Vec<Option<String>> {
+        values.iter().map(|v| v.text()).collect()
+    }
+
+    #[test]
+    fn test_map_table() -> CResult<()> {
+        let config = r#"
+            [[mapping]]
+            type = "rename_table"
+            table = "db1.t1"
+            to = "db2.users"
+
+            [[mapping]]
+            type = "drop_column"
+            table = "t1"
+            column = "secret"
+
+            [[mapping]]
+            type = "rename_column"
+            table = "db1.*"
+            column = "first_name"
+            to = "given_name"
+
+            [[mapping]]
+            type = "cast_column"
+            table = "db1.t1"
+            column = "amount"
+            to = "double"
+
+            [[mapping]]
+            type = "computed_column"
+            table = "db1.t1"
+            column = "full_name"
+            expr = "{given_name} {last_name} {{{id}}}"
+        "#;
+        #[derive(serde::Deserialize)]
+        struct Config {
+            mapping: Vec<MappingRule>,
+        }
+        let config: Config = toml::from_str(config).unwrap();
+        let mapping = ColumnMapping::new(config.mapping)?.map_table("db1", "t1", &columns())?;
+
+        assert_eq!(("db2", "users"), (mapping.database.as_str(), mapping.table.as_str()));
+        let names: Vec<&str> = mapping.columns.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(vec!["id", "given_name", "last_name", "amount", "full_name"], names);
+        assert!(matches!(mapping.columns[3].1, DstColumnType::Double));
+
+        let row = mapping.map_row(&[Value::Long(7), Value::from("Ada"), Value::from("Lovelace"),
+            Value::Decimal("12.5".to_string()), Value::from("pwd")])?;
+        assert_eq!(vec![Some("7".to_string()), Some("Ada".to_string()), Some("Lovelace".to_string()),
+                        Some("12.5".to_string()), Some("Ada Lovelace {7}".to_string())], text(&row));
+        assert!(matches!(row[3], Value::Double(_)));
+
+        // 引用的列为 NULL 时计算列为 NULL
+        let row = mapping.map_row(&[Value::Long(8), Value::Null, Value::from("X"), Value::Null, Value::Null])?;
+        assert!(matches!(row[4], Value::Null));
+        assert!(matches!(row[3], Value::Null));
+        Ok(())
+    }
+
+    #[test]
+    fn test_identity_and_errors() -> CResult<()> {
+        let rules = vec![MappingRule::RenameColumn { table: "t2".to_string(), column: "id".to_string(), to: "uid".to_string() }];
+        let mapping = ColumnMapping::new(rules)?;
+        let t1 = mapping.map_table("db1", "t1", &columns())?;
+        assert!(t1.is_identity());
+        // 只修改列名时值不变
+        assert!(mapping.map_table("db1", "t2", &columns())?.is_identity());
+
+        // 源表已删除的列跳过, 计算列引用不存在的列时报错
+        let rules = vec![MappingRule::DropColumn { table: "t1".to_string(), column: "missing".to_string() }];
+        assert!(ColumnMapping::new(rules)?.map_table("db1", "t1", &columns())?.is_identity());
+        let rules = vec![MappingRule::ComputedColumn { table: "t1".to_string(), column: "c".to_string(),
+                                                       expr: "{missing}".to_string(), data_type: None }];
+        assert!(ColumnMapping::new(rules)?.map_table("db1", "t1", &columns()).is_err());
+
+        let rules = vec![MappingRule::CastColumn { table: "t1".to_string(), column: "id".to_string(), to: "uuid".to_string() }];
+        assert!(ColumnMapping::new(rules).is_err());
+
+        let rules = vec![MappingRule::CastColumn { table: "t1".to_string(), column: "first_name".to_string(), to: "long".to_string() }];
+        let t1 = ColumnMapping::new(rules)?.map_table("db1", "t1", &columns())?;
+        assert!(t1.map_row(&[Value::Long(1), Value::from("abc"), Value::Null, Value::Null, Value::Null]).is_err());
+        Ok(())
+    }
+}
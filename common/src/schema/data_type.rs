@@ -48,6 +48,14 @@ pub enum DstColumnType {
     Bitmap = 43,
     // Deprecated, not implement
     Map = 50,
+
+    // 中继日志以 bincode 按变体的位置编码, 新增的变体只能追加在末尾
+    Enum = 44,
+    Set = 45,
+    UnsignedByte = 46,
+    UnsignedShort = 47,
+    UnsignedInt = 48,
+    UnsignedLong = 49,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -78,6 +86,19 @@ pub enum Value {
     Bytes(Vec<u8>),
     Blob(Vec<u8>),
 
+    // 中继日志以 bincode 按变体的位置编码, 新增的变体只能追加在末尾
+    UnsignedByte(u8),
+    UnsignedShort(u16),
+    UnsignedInt(u32),
+    UnsignedLong(u64),
+
+    // enum 的取值名称
+    Enum(String),
+    // set 的取值名称, 逗号分隔
+    Set(String),
+    // BIT(M), M <= 64
+    Bitmap(u64),
+
     // ByteArray(Vec<i8>),
     // ShortArray(Vec<i16>),
     // IntArray(Vec<i32>),
@@ -96,8 +117,15 @@ impl Value {
             Value::Short(_) => { DstColumnType::Short}
             Value::Int(_) => { DstColumnType::Int}
             Value::Long(_) => { DstColumnType::Long}
+            Value::UnsignedByte(_) => { DstColumnType::UnsignedByte}
+            Value::UnsignedShort(_) => { DstColumnType::UnsignedShort}
+            Value::UnsignedInt(_) => { DstColumnType::UnsignedInt}
+            Value::UnsignedLong(_) => { DstColumnType::UnsignedLong}
             Value::String(_) => { DstColumnType::String}
             Value::JSON(_) => { DstColumnType::JSON}
+            Value::Enum(_) => { DstColumnType::Enum}
+            Value::Set(_) => { DstColumnType::Set}
+            Value::Bitmap(_) => { DstColumnType::Bitmap}
             Value::Float(_) => { DstColumnType::Float}
             Value::Double(_) => { DstColumnType::Double}
             Value::Decimal(_) => { DstColumnType::Decimal}
@@ -115,10 +143,32 @@ impl Value {
         let data_type = self.get_data_type();
         data_type.into()
     }
+
+    /// 值的文本形式, NULL 为 None; 布尔值为 1/0, 时间类型为毫秒数
+    pub fn text(&self) -> Option<String> {
+        let text = match self {
+            Value::Null => return None,
+            Value::Boolean(v) => if *v { "1".to_string() } else { "0".to_string() },
+            Value::Byte(v) => v.to_string(),
+            Value::Short(v) => v.to_string(),
+            Value::Int(v) => v.to_string(),
+            Value::Long(v) => v.to_string(),
+            Value::UnsignedByte(v) => v.to_string(),
+            Value::UnsignedShort(v) => v.to_string(),
+            Value::UnsignedInt(v) => v.to_string(),
+            Value::UnsignedLong(v) | Value::Bitmap(v) => v.to_string(),
+            Value::String(v) | Value::JSON(v) | Value::Decimal(v) | Value::Enum(v) | Value::Set(v) => v.clone(),
+            Value::Float(v) => v.to_string(),
+            Value::Double(v) => v.to_string(),
+            Value::Date(v) | Value::Time(v) | Value::DateTime(v) | Value::Timestamp(v) => v.to_string(),
+            Value::Binary(v) | Value::Bytes(v) | Value::Blob(v) => String::from_utf8_lossy(v).to_string(),
+        };
+        Some(text)
+    }
 }
 
 /// Table Schema
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
 pub struct TableSchema {
     pub catalog: String,
     pub database: String,
@@ -205,7 +255,16 @@ define_parse_data_type_from_str!(
     DstColumnType::MultiValue, "multiValue";
     DstColumnType::Geo2D, "Geo2D";
     DstColumnType::Blob, "Blob";
-    DstColumnType::Binary, "Binary"
+    DstColumnType::Binary, "Binary";
+    DstColumnType::JSON, "json";
+    DstColumnType::Geometry, "geometry";
+    DstColumnType::Bitmap, "bitmap", "bit";
+    DstColumnType::Enum, "enum";
+    DstColumnType::Set, "set";
+    DstColumnType::UnsignedByte, "unsignedByte";
+    DstColumnType::UnsignedShort, "unsignedShort";
+    DstColumnType::UnsignedInt, "unsignedInt";
+    DstColumnType::UnsignedLong, "unsignedLong"
 );
 
 impl TryFrom<String> for DstColumnType {
@@ -216,6 +275,41 @@ impl TryFrom<String> for DstColumnType {
     }
 }
 
+impl DstColumnType {
+    /// information_schema.COLUMNS.DATA_TYPE 对应的类型, 与 binlog 行事件转换得到的列类型保持一致
+    pub fn from_mysql_data_type(data_type: &str) -> Self {
+        Self::from_mysql_column(data_type, false)
+    }
+
+    /// 同 [DstColumnType::from_mysql_data_type], unsigned 为 COLUMN_TYPE 是否包含 unsigned
+    pub fn from_mysql_column(data_type: &str, unsigned: bool) -> Self {
+        match data_type.to_ascii_lowercase().as_str() {
+            "tinyint" if unsigned => DstColumnType::UnsignedByte,
+            "smallint" if unsigned => DstColumnType::UnsignedShort,
+            "mediumint" | "int" | "integer" if unsigned => DstColumnType::UnsignedInt,
+            "bigint" if unsigned => DstColumnType::UnsignedLong,
+            "decimal" | "numeric" => DstColumnType::Decimal,
+            "tinyint" | "mediumint" | "year" => DstColumnType::Int,
+            "smallint" => DstColumnType::Short,
+            "int" | "integer" | "bigint" => DstColumnType::Long,
+            "float" => DstColumnType::Float,
+            "double" | "real" => DstColumnType::Double,
+            "timestamp" => DstColumnType::Timestamp,
+            "date" => DstColumnType::Date,
+            "time" => DstColumnType::Time,
+            "datetime" => DstColumnType::DateTime,
+            "bit" => DstColumnType::Bitmap,
+            "json" => DstColumnType::JSON,
+            "enum" => DstColumnType::Enum,
+            "set" => DstColumnType::Set,
+            "geometry" | "point" | "linestring" | "polygon" | "multipoint" | "multilinestring" |
+            "multipolygon" | "geometrycollection" => DstColumnType::Geometry,
+            "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob" => DstColumnType::Blob,
+            _ => DstColumnType::String,
+        }
+    }
+}
+
 impl TableSchema {
 
     #[inline]
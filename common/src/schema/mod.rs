@@ -3,4 +3,8 @@ pub mod data_type;
 
 #[cfg(feature = "mock_api")]
 mod mock;
-pub mod rc_task;
\ No newline at end of file
+pub mod rc_task;
+pub mod mysql_metadata;
+pub mod schema_history;
+pub mod column_mapping;
+pub mod type_conversion;
@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::binlog::row::row_string::RowString;
+use crate::config::RcMetadata;
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+use crate::schema::data_type::DstColumnType;
+use crate::schema::schema::{Column, ColumnRef, DistributeType, get_metadata, get_metadata_mut, Metadata, op_default_catalog, Table};
+
+/// metadata_stats_fresh_interval_ms 的默认值与取值范围
+const DEFAULT_FRESH_INTERVAL_MS: u64 = 10_000;
+const MIN_FRESH_INTERVAL_MS: u64 = 5_000;
+const MAX_FRESH_INTERVAL_MS: u64 = 60_000;
+
+/// 读取 information_schema 的查询接口, 由 connection 中的连接实现
+#[async_trait::async_trait]
+pub trait MetadataQuery: Send {
+
+    async fn query(&mut self, sql: String) -> CResult<Vec<RowString>>;
+
+}
+
+impl RcMetadata {
+    /// 元数据刷新间隔, 默认 10s, 最小 5s, 最大 60s
+    pub fn fresh_interval(&self) -> Duration {
+        let ms = self.metadata_stats_fresh_interval_ms.unwrap_or(DEFAULT_FRESH_INTERVAL_MS);
+        Duration::from_millis(ms.clamp(MIN_FRESH_INTERVAL_MS, MAX_FRESH_INTERVAL_MS))
+    }
+}
+
+impl Metadata {
+    /// 从 information_schema 读取 databases 中全部基本表的表结构, 包括主键、自增列、精度与小数位数。
+    ///
+    /// information_schema 中没有表的 id, table_id 按 (库, 表) 排序从 1 开始编号, 以主键作为分布键
+    pub async fn from_mysql<C: MetadataQuery + ?Sized>(conn: &mut C, databases: &[String]) -> CResult<Metadata> {
+        Metadata::load_from_mysql(conn, databases, &HashMap::new()).await
+    }
+
+    /// 重新读取 databases 的表结构并整体替换, 已删除的表随之移除, 已有的表沿用原来的 table_id
+    pub async fn refresh_from_mysql<C: MetadataQuery + ?Sized>(&mut self, conn: &mut C, databases: &[String]) -> CResult<()> {
+        let loaded = Metadata::load_from_mysql(conn, databases, &self.table_ids()?).await?;
+        self.replace(loaded)
+    }
+
+    /// table_ids 中已有的表沿用原来的 id, 新表按 (库, 表) 排序从其中最大的 id 之后编号
+    async fn load_from_mysql<C: MetadataQuery + ?Sized>(conn: &mut C, databases: &[String],
+                                                        table_ids: &HashMap<(String, String), i32>) -> CResult<Metadata> {
+        let mut metadata = Metadata::default();
+        if databases.is_empty() {
+            return Ok(metadata);
+        }
+
+        let rows = conn.query(columns_sql(databases)).await?;
+        // (库, 表) -> 列
+        let mut tables: BTreeMap<(String, String), Vec<Column>> = BTreeMap::new();
+        for row in rows {
+            let (schema_name, table_name, column) = parse_column(&row)?;
+            tables.entry((schema_name, table_name)).or_default().push(column);
+        }
+
+        let mut last_table_id = table_ids.values().max().copied().unwrap_or(0);
+        for (key, mut columns) in tables {
+            let table_id = match table_ids.get(&key) {
+                Some(table_id) => *table_id,
+                None => {
+                    last_table_id += 1;
+                    last_table_id
+                }
+            };
+            let (schema_name, table_name) = key;
+            columns.sort_by_key(|c| c.ordinal_position);
+            let pk_column_idx: Vec<usize> = columns.iter()
+                .filter(|c| c.primary_key)
+                .map(|c| c.column_id as usize)
+                .collect();
+            let table = Table {
+                table_id,
+                physical_name: format!("{}##{}##{}", &schema_name, &table_name, table_id),
+                name: table_name,
+                schema_id: "0".to_string(),
+                columns: columns.into_iter().map(Arc::new).collect::<Vec<ColumnRef>>(),
+                distribution_key_column_idx: pk_column_idx.clone(),
+                pk_column_idx,
+                distribute_type: DistributeType::HASH,
+                version: 0,
+                is_materialized: false,
+                catalog_name: op_default_catalog().clone(),
+                schema_name,
+            };
+            debug!("mysql metadata table: {:?}", table);
+            metadata.insert_table(table)?;
+        }
+        Ok(metadata)
+    }
+
+    /// 已有表的 (库, 表) -> table_id
+    fn table_ids(&self) -> CResult<HashMap<(String, String), i32>> {
+        let mut table_ids = HashMap::new();
+        for t in self.existing_table_names()? {
+            if let Some(table) = self.get_table(&t.catalog, &t.database, &t.table)? {
+                let table = table.read().map_err(|_| ReError::OpMetadataErr("get_table err".into()))?;
+                table_ids.insert((t.database, t.table), table.table_id);
+            }
+        }
+        Ok(table_ids)
+    }
+}
+
+/// 后台任务: 按 rc_metadata 的刷新间隔从 MySQL 重新加载全局元数据, 加载失败时保留上一次的结果
+pub fn spawn_metadata_refresh<C: MetadataQuery + 'static>(mut conn: C, databases: Vec<String>, config: &RcMetadata) -> JoinHandle<()> {
+    let interval = config.fresh_interval();
+    // 初始化全局元数据
+    let _ = get_metadata();
+    info!("refresh metadata of {:?} every {:?}", databases, interval);
+
+    tokio::spawn(async move {
+        loop {
+            // 加载期间不持有全局元数据的可变引用, 加载完成后再替换
+            let loaded = match get_metadata().table_ids() {
+                Ok(table_ids) => Metadata::load_from_mysql(&mut conn, &databases, &table_ids).await,
+                Err(e) => Err(e),
+            };
+            match loaded {
+                Ok(loaded) => {
+                    if let Err(e) = get_metadata_mut().replace(loaded) {
+                        warn!("replace metadata err: {:?}", e);
+                    }
+                }
+                Err(e) => warn!("refresh metadata from mysql err: {:?}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
+
+fn columns_sql(databases: &[String]) -> String {
+    let databases: Vec<String> = databases.iter().map(|d| quote(d)).collect();
+    format!("SELECT c.TABLE_SCHEMA, c.TABLE_NAME, c.COLUMN_NAME, c.ORDINAL_POSITION, c.COLUMN_DEFAULT, c.IS_NULLABLE, \
+        c.DATA_TYPE, c.NUMERIC_PRECISION, c.NUMERIC_SCALE, c.DATETIME_PRECISION, c.COLUMN_KEY, c.EXTRA, c.COLUMN_TYPE \
+        FROM information_schema.COLUMNS c JOIN information_schema.TABLES t \
+        ON t.TABLE_SCHEMA = c.TABLE_SCHEMA AND t.TABLE_NAME = c.TABLE_NAME \
+        WHERE t.TABLE_TYPE = 'BASE TABLE' AND c.TABLE_SCHEMA IN ({}) \
+        ORDER BY c.TABLE_SCHEMA, c.TABLE_NAME, c.ORDINAL_POSITION", databases.join(", "))
+}
+
+/// SQL 字符串字面量
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "''"))
+}
+
+/// 解析 [columns_sql] 的一行为 (库, 表, 列), column_id 为从 0 开始的列序号
+fn parse_column(row: &RowString) -> CResult<(String, String, Column)> {
+    if row.len() != 13 {
+        return Err(ReError::OpMetadataErr(format!("Parse Table: COLUMNS err, fields len {} not equals 13", row.len())));
+    }
+    let values = row.as_slice();
+    let text = |i: usize, name: &str| -> CResult<String> {
+        values[i].clone().ok_or_else(|| {
+            ReError::OpMetadataErr(format!("Parse Table: COLUMNS err, field {} can not be null", name))
+        })
+    };
+    let number = |i: usize, name: &str| -> CResult<Option<i32>> {
+        values[i].as_ref().map(|v| v.parse::<i32>().map_err(|_| {
+            ReError::OpMetadataErr(format!("Parse Table: COLUMNS err, field {}: {} not illegal", name, v))
+        })).transpose()
+    };
+
+    let schema_name = text(0, "TABLE_SCHEMA")?;
+    let table_name = text(1, "TABLE_NAME")?;
+    let name = text(2, "COLUMN_NAME")?;
+    let ordinal_position = number(3, "ORDINAL_POSITION")?.ok_or_else(|| {
+        ReError::OpMetadataErr("Parse Table: COLUMNS err, field ORDINAL_POSITION can not be null".into())
+    })?;
+    let default = values[4].clone();
+    let nullable = text(5, "IS_NULLABLE")?.eq_ignore_ascii_case("YES");
+    let unsigned = text(12, "COLUMN_TYPE")?.to_ascii_lowercase().contains("unsigned");
+    let data_type = DstColumnType::from_mysql_column(&text(6, "DATA_TYPE")?, unsigned);
+    // 时间类型的精度为秒的小数位数
+    let precision = number(7, "NUMERIC_PRECISION")?.or(number(9, "DATETIME_PRECISION")?).unwrap_or(0);
+    let scale = number(8, "NUMERIC_SCALE")?.unwrap_or(0);
+    let primary_key = text(10, "COLUMN_KEY")?.eq_ignore_ascii_case("PRI");
+    let extra = text(11, "EXTRA")?.to_ascii_uppercase();
+
+    let default_upper = default.as_deref().unwrap_or_default().to_ascii_uppercase();
+    let column_id = ordinal_position - 1;
+    let column = Column {
+        column_id,
+        physical_name: format!("{}##{}", &name, column_id),
+        name,
+        data_type,
+        ordinal_position,
+        nullable,
+        primary_key,
+        auto_increment: extra.contains("AUTO_INCREMENT"),
+        default,
+        default_value_is_current_timestamp: default_upper.starts_with("CURRENT_TIMESTAMP"),
+        default_value_is_current_date: default_upper.contains("CURDATE") || default_upper.contains("CURRENT_DATE"),
+        // DEFAULT_GENERATED 是表达式默认值, 不是生成列
+        is_generated_column: extra.contains("VIRTUAL GENERATED") || extra.contains("STORED GENERATED"),
+        precision,
+        scale,
+    };
+    Ok((schema_name, table_name, column))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binlog::row::row_string::RowString;
+    use crate::config::RcMetadata;
+    use crate::err::CResult;
+    use crate::schema::data_type::DstColumnType;
+    use crate::schema::mysql_metadata::MetadataQuery;
+    use crate::schema::schema::{Metadata, op_default_catalog};
+
+    struct MockQuery {
+        rows: Vec<Vec<Option<&'static str>>>,
+        sqls: Vec<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl MetadataQuery for MockQuery {
+        async fn query(&mut self, sql: String) -> CResult<Vec<RowString>> {
+            self.sqls.push(sql);
+            Ok(self.rows.iter()
+                .map(|r| RowString::new_row(r.iter().map(|v| v.map(String::from)).collect()))
+                .collect())
+        }
+    }
+
+    fn column(table: &'static str, name: &'static str, position: &'static str, data_type: &'static str,
+              precision: Option<&'static str>, scale: Option<&'static str>, key: &'static str, extra: &'static str,
+              default: Option<&'static str>) -> Vec<Option<&'static str>> {
+        // data_type 为 COLUMN_TYPE, 如 int unsigned
+        let column_type = data_type;
+        let data_type = column_type.split(' ').next().unwrap_or_default();
+        vec![Some("db1"), Some(table), Some(name), Some(position), default, Some("NO"), Some(data_type),
+             precision, scale, None, Some(key), Some(extra), Some(column_type)]
+    }
+
+    #[test]
+    fn test_from_mysql() -> CResult<()> {
+        let mut conn = MockQuery {
+            rows: vec![
+                column("t2", "id", "1", "int unsigned", Some("10"), Some("0"), "PRI", "", None),
+                column("t1", "amount", "2", "decimal", Some("12"), Some("2"), "", "", Some("0.00")),
+                column("t1", "id", "1", "bigint", Some("19"), Some("0"), "PRI", "auto_increment", None),
+                column("t1", "created_at", "3", "timestamp", None, None, "", "DEFAULT_GENERATED", Some("CURRENT_TIMESTAMP")),
+            ],
+            sqls: vec![],
+        };
+        let databases = vec!["db1".to_string(), "it's".to_string()];
+        let metadata = futures_executor::block_on(Metadata::from_mysql(&mut conn, &databases))?;
+        assert!(conn.sqls[0].contains("IN ('db1', 'it''s')"));
+
+        let catalog = op_default_catalog();
+        let t1 = metadata.get_table(catalog, &"db1".to_string(), &"t1".to_string())?.unwrap();
+        let t1 = t1.read().unwrap();
+        assert_eq!(1, t1.table_id);
+        assert_eq!("db1##t1##1", t1.physical_name);
+        assert_eq!(vec!["id", "amount", "created_at"], t1.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(vec![0], t1.pk_column_idx);
+
+        let id = t1.get_column_by_name("id").unwrap();
+        assert!(id.primary_key && id.auto_increment);
+        assert!(matches!(id.data_type, DstColumnType::Long));
+        let amount = t1.get_column_by_name("amount").unwrap();
+        assert_eq!((12, 2), (amount.precision, amount.scale));
+        assert_eq!(Some("0.00".to_string()), amount.default);
+        let created_at = t1.get_column_by_name("created_at").unwrap();
+        assert!(created_at.default_value_is_current_timestamp);
+        assert!(!created_at.is_generated_column);
+
+        let t2 = metadata.get_table(catalog, &"db1".to_string(), &"t2".to_string())?.unwrap();
+        assert_eq!(2, t2.read().unwrap().table_id);
+        assert!(matches!(t2.read().unwrap().get_column_by_name("id").unwrap().data_type, DstColumnType::UnsignedInt));
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_removes_dropped_table() -> CResult<()> {
+        let mut conn = MockQuery {
+            rows: vec![column("t1", "id", "1", "int", Some("10"), Some("0"), "PRI", "", None)],
+            sqls: vec![],
+        };
+        let databases = vec!["db1".to_string()];
+        let mut metadata = futures_executor::block_on(Metadata::from_mysql(&mut conn, &databases))?;
+        assert_eq!(1, metadata.existing_table_names()?.len());
+
+        conn.rows.clear();
+        futures_executor::block_on(metadata.refresh_from_mysql(&mut conn, &databases))?;
+        assert!(metadata.existing_table_names()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_keeps_table_id() -> CResult<()> {
+        let mut conn = MockQuery {
+            rows: vec![
+                column("t1", "id", "1", "int", Some("10"), Some("0"), "PRI", "", None),
+                column("t2", "id", "1", "int", Some("10"), Some("0"), "PRI", "", None),
+            ],
+            sqls: vec![],
+        };
+        let databases = vec!["db1".to_string()];
+        let mut metadata = futures_executor::block_on(Metadata::from_mysql(&mut conn, &databases))?;
+
+        // 新增排在前面的 t0, 删除 t1
+        conn.rows = vec![
+            column("t0", "id", "1", "int", Some("10"), Some("0"), "PRI", "", None),
+            column("t2", "id", "1", "int", Some("10"), Some("0"), "PRI", "", None),
+        ];
+        futures_executor::block_on(metadata.refresh_from_mysql(&mut conn, &databases))?;
+
+        let catalog = op_default_catalog();
+        let table_id = |table: &str| metadata.get_table(catalog, &"db1".to_string(), &table.to_string())
+            .unwrap().map(|t| t.read().unwrap().table_id);
+        assert_eq!(Some(2), table_id("t2"));
+        assert_eq!(Some(3), table_id("t0"));
+        assert_eq!(None, table_id("t1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fresh_interval() {
+        let mut config = RcMetadata::default();
+        assert_eq!(10_000, config.fresh_interval().as_millis());
+        config.metadata_stats_fresh_interval_ms = Some(1000);
+        assert_eq!(5_000, config.fresh_interval().as_millis());
+        config.metadata_stats_fresh_interval_ms = Some(120_000);
+        assert_eq!(60_000, config.fresh_interval().as_millis());
+    }
+}
@@ -11,6 +11,7 @@ use serde::Deserialize;
 use crate::err::CResult;
 use crate::err::decode_error::ReError;
 use crate::schema::data_type::{DstColumnType, TableSchema};
+use crate::schema::schema_history::{SchemaHistory, SchemaPosition};
 
 pub type CatalogRef = Arc<RwLock<Catalog>>;
 pub type SchemaRef = Arc<RwLock<Schema>>;
@@ -27,6 +28,8 @@ pub static OP_DEFAULT_CATALOG_NAME: OnceCell<String> = OnceCell::new();
 pub struct Metadata {
     write_lock: Mutex<()>,
     catalog: HashMap<String, CatalogRef>,
+    // DDL 产生的表结构版本
+    history: SchemaHistory,
 }
 
 #[derive(Debug)]
@@ -150,6 +153,11 @@ impl Metadata {
     }
 
     pub fn insert_table(&mut self, table: Table) -> CResult<()> {
+        self.insert_table_ref(Arc::new(RwLock::new(table)))
+    }
+
+    fn insert_table_ref(&mut self, table_ref: TableRef) -> CResult<()> {
+        let table = mle!(table_ref.read())?;
         let mut c = self.catalog.entry(table.catalog_name.clone()).or_insert_with(|| {
             Arc::new(RwLock::new(Catalog {
                 name: table.catalog_name.clone(),
@@ -167,10 +175,57 @@ impl Metadata {
             }))
         });
         let mut schema = mle!(s.write())?;
-        schema.tables.insert(table.name.clone(), Arc::new(RwLock::new(table)));
+        schema.tables.insert(table.name.clone(), table_ref.clone());
         Ok(())
     }
 
+    /// DDL 创建或修改了表: 以 position 记录新的表版本并替换当前的表结构。
+    ///
+    /// 表已存在但还没有历史版本时(如启动时从 information_schema 加载), 先把当前的表结构记录为最早的版本
+    pub fn apply_ddl(&mut self, position: SchemaPosition, table: Table) -> CResult<TableRef> {
+        let table_schema = table.get_table_schema();
+        if !self.history.contains(&table_schema) {
+            if let Some(current) = self.get_table(&table_schema.catalog, &table_schema.database, &table_schema.table)? {
+                self.history.record_ref(SchemaPosition::default(), current)?;
+            }
+        }
+        let table_ref = self.history.record(position, table)?;
+        self.insert_table_ref(table_ref.clone())?;
+        Ok(table_ref)
+    }
+
+    /// DDL 删除了表, position 之后查询不到该表
+    pub fn apply_drop(&mut self, position: SchemaPosition, catalog: &String, schema: &String, table: &String) -> CResult<bool> {
+        let table_schema = TableSchema::create(catalog, schema, table);
+        if !self.history.contains(&table_schema) {
+            if let Some(current) = self.get_table(catalog, schema, table)? {
+                self.history.record_ref(SchemaPosition::default(), current)?;
+            }
+        }
+        self.history.record_drop(position, table_schema)?;
+        self.remove_table(catalog, schema, table)
+    }
+
+    /// binlog 中 position 处生效的表结构, 用于解码旧的事件。 没有历史版本的表返回当前的表结构
+    pub fn get_table_at(&self, catalog: &String, schema: &String, table: &String, position: &SchemaPosition) -> CResult<Option<TableRef>> {
+        let table_schema = TableSchema::create(catalog, schema, table);
+        if self.history.contains(&table_schema) {
+            return Ok(self.history.get_table_at(&table_schema, position));
+        }
+        self.get_table(catalog, schema, table)
+    }
+
+    /// 表结构的历史版本
+    #[inline]
+    pub fn history(&self) -> &SchemaHistory {
+        &self.history
+    }
+
+    #[inline]
+    pub fn history_mut(&mut self) -> &mut SchemaHistory {
+        &mut self.history
+    }
+
 
     /// remove table from metadata
     pub fn remove_table(&mut self, catalog: &String, schema: &String, table: &String) -> CResult<bool> {
@@ -197,6 +252,13 @@ impl Metadata {
         }
         Ok(false)
     }
+
+    /// 以 other 的全部表替换当前的表
+    pub(crate) fn replace(&mut self, other: Metadata) -> CResult<()> {
+        let _guard = mle!(self.write_lock.lock())?;
+        self.catalog = other.catalog;
+        Ok(())
+    }
 }
 
 impl Catalog {
@@ -420,6 +482,7 @@ impl Default for Metadata {
         Metadata {
             write_lock: Mutex::new(()),
             catalog: HashMap::new(),
+            history: SchemaHistory::default(),
         }
     }
 }
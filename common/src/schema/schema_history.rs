@@ -0,0 +1,274 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, RwLock};
+
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+use crate::schema::data_type::TableSchema;
+use crate::schema::schema::{Table, TableRef};
+
+/// DDL 在源库 binlog 中的位置。
+///
+/// 按 binlog 文件序号与 log_pos 排序, gtid 只用于展示, 不参与比较; 默认值早于任何位置
+#[derive(Debug, Clone, Default)]
+pub struct SchemaPosition {
+    pub binlog_file: String,
+    // 事件结束的位置, 与事件头中的 log_pos 一致
+    pub log_pos: u64,
+    pub gtid: String,
+}
+
+/// 表结构的一个版本, table 为 None 表示该位置删除了表
+#[derive(Debug, Clone)]
+pub struct TableVersion {
+    pub version: i64,
+    pub position: SchemaPosition,
+    pub table: Option<TableRef>,
+}
+
+/// 表结构的历史版本: 每个DDL以其 binlog 位置产生一个新版本, 解码旧的事件时按事件位置查找当时的表结构
+#[derive(Debug, Default)]
+pub struct SchemaHistory {
+    // 表 -> 按位置升序的版本
+    versions: HashMap<TableSchema, Vec<TableVersion>>,
+}
+
+impl SchemaPosition {
+    pub fn new(binlog_file: &str, log_pos: u64) -> Self {
+        Self {
+            binlog_file: binlog_file.to_string(),
+            log_pos,
+            gtid: String::new(),
+        }
+    }
+
+    pub fn with_gtid(mut self, gtid: &str) -> Self {
+        self.gtid = gtid.to_string();
+        self
+    }
+
+    /// binlog 文件的序号, 如 mysql-bin.000012 为 12; 序号超过 6 位后按字符串比较会出错
+    fn file_seq(&self) -> Option<u64> {
+        self.binlog_file.rsplit_once('.').and_then(|(_, seq)| seq.parse::<u64>().ok())
+    }
+}
+
+impl PartialEq for SchemaPosition {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SchemaPosition {}
+
+impl PartialOrd for SchemaPosition {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SchemaPosition {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.file_seq().cmp(&other.file_seq())
+            .then_with(|| self.binlog_file.cmp(&other.binlog_file))
+            .then_with(|| self.log_pos.cmp(&other.log_pos))
+    }
+}
+
+impl Display for SchemaPosition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.gtid.is_empty() {
+            write!(f, "{}:{}", self.binlog_file, self.log_pos)
+        } else {
+            write!(f, "{}:{} ({})", self.binlog_file, self.log_pos, self.gtid)
+        }
+    }
+}
+
+impl SchemaHistory {
+    /// 记录DDL产生的表结构, 版本号为上一个版本加一。 相同位置重复记录时替换该版本, 早于最后一个版本时返回错误
+    pub fn record(&mut self, position: SchemaPosition, table: Table) -> CResult<TableRef> {
+        let table_ref = Arc::new(RwLock::new(table));
+        self.record_ref(position, table_ref.clone())?;
+        Ok(table_ref)
+    }
+
+    /// 记录表已存在的版本, 如启动时加载的表结构
+    pub(crate) fn record_ref(&mut self, position: SchemaPosition, table_ref: TableRef) -> CResult<()> {
+        let table_schema = table_ref.read()
+            .map_err(|e| ReError::OpMetadataErr(format!("require lock failed, {}", e)))?
+            .get_table_schema();
+        let version = self.push(table_schema, position, Some(table_ref.clone()))?;
+        table_ref.write()
+            .map_err(|e| ReError::OpMetadataErr(format!("require lock failed, {}", e)))?
+            .version = version;
+        Ok(())
+    }
+
+    /// 记录 DROP TABLE
+    pub fn record_drop(&mut self, position: SchemaPosition, table_schema: TableSchema) -> CResult<()> {
+        self.push(table_schema, position, None).map(|_| ())
+    }
+
+    fn push(&mut self, table_schema: TableSchema, position: SchemaPosition, table: Option<TableRef>) -> CResult<i64> {
+        let versions = self.versions.entry(table_schema).or_default();
+        let version = match versions.last() {
+            Some(last) if position < last.position => {
+                return Err(ReError::OpMetadataErr(format!(
+                    "schema history out of order, ddl at {} is before the last version at {}", position, last.position
+                )));
+            }
+            Some(last) if position == last.position => {
+                let version = last.version;
+                versions.pop();
+                version
+            }
+            Some(last) => last.version + 1,
+            None => 0,
+        };
+        versions.push(TableVersion { version, position, table });
+        Ok(version)
+    }
+
+    /// 是否记录过该表的版本
+    #[inline]
+    pub fn contains(&self, table_schema: &TableSchema) -> bool {
+        self.versions.contains_key(table_schema)
+    }
+
+    /// position 处生效的表结构: 位置不晚于 position 的最后一个版本。 早于第一个版本或表已删除时返回 None
+    pub fn get_table_at(&self, table_schema: &TableSchema, position: &SchemaPosition) -> Option<TableRef> {
+        let versions = self.versions.get(table_schema)?;
+        let n = versions.partition_point(|v| v.position <= *position);
+        if n == 0 {
+            return None;
+        }
+        versions[n - 1].table.clone()
+    }
+
+    /// 表的全部版本, 按位置升序
+    pub fn versions(&self, table_schema: &TableSchema) -> &[TableVersion] {
+        self.versions.get(table_schema).map_or(&[], |v| v.as_slice())
+    }
+
+    /// 清理 position 之前已被替换的版本, 保留 position 处生效的版本; 返回清理的版本数
+    pub fn purge_before(&mut self, position: &SchemaPosition) -> usize {
+        let mut purged = 0;
+        self.versions.retain(|_, versions| {
+            let n = versions.partition_point(|v| v.position <= *position);
+            if n > 1 {
+                versions.drain(..n - 1);
+                purged += n - 1;
+            }
+            // 只剩已删除的表
+            !(versions.len() == 1 && versions[0].table.is_none() && versions[0].position <= *position)
+        });
+        purged
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::err::CResult;
+    use crate::schema::data_type::{DstColumnType, TableSchema};
+    use crate::schema::schema::{Column, DistributeType, Metadata, Table};
+    use crate::schema::schema_history::{SchemaHistory, SchemaPosition};
+
+    fn table(columns: &[&str]) -> Table {
+        Table {
+            table_id: 1,
+            name: "t1".to_string(),
+            physical_name: "db1##t1##1".to_string(),
+            schema_id: "0".to_string(),
+            columns: columns.iter().enumerate().map(|(i, c)| Arc::new(Column {
+                column_id: i as i32,
+                name: c.to_string(),
+                physical_name: format!("{}##{}", c, i),
+                data_type: DstColumnType::Long,
+                ordinal_position: i as i32 + 1,
+                nullable: true,
+                primary_key: i == 0,
+                auto_increment: false,
+                default: None,
+                default_value_is_current_timestamp: false,
+                default_value_is_current_date: false,
+                is_generated_column: false,
+                precision: 0,
+                scale: 0,
+            })).collect(),
+            pk_column_idx: vec![0],
+            distribute_type: DistributeType::HASH,
+            distribution_key_column_idx: vec![0],
+            version: 0,
+            is_materialized: false,
+            catalog_name: "def".to_string(),
+            schema_name: "db1".to_string(),
+        }
+    }
+
+    fn column_count(metadata: &Metadata, position: &SchemaPosition) -> CResult<Option<usize>> {
+        let names = ("def".to_string(), "db1".to_string(), "t1".to_string());
+        Ok(metadata.get_table_at(&names.0, &names.1, &names.2, position)?
+            .map(|t| t.read().unwrap().columns.len()))
+    }
+
+    #[test]
+    fn test_position_order() {
+        assert!(SchemaPosition::new("mysql-bin.999999", 900) < SchemaPosition::new("mysql-bin.1000000", 4));
+        assert!(SchemaPosition::new("mysql-bin.000001", 900) < SchemaPosition::new("mysql-bin.000002", 4));
+        assert!(SchemaPosition::default() < SchemaPosition::new("mysql-bin.000001", 4));
+        assert_eq!(SchemaPosition::new("mysql-bin.000001", 4).with_gtid("uuid:1"), SchemaPosition::new("mysql-bin.000001", 4));
+    }
+
+    #[test]
+    fn test_get_table_at() -> CResult<()> {
+        let mut metadata = Metadata::default();
+        // 启动时加载的表结构
+        metadata.insert_table(table(&["id"]))?;
+        metadata.apply_ddl(SchemaPosition::new("mysql-bin.000001", 500), table(&["id", "a"]))?;
+        metadata.apply_ddl(SchemaPosition::new("mysql-bin.000002", 300), table(&["id", "a", "b"]))?;
+
+        assert_eq!(Some(1), column_count(&metadata, &SchemaPosition::new("mysql-bin.000001", 400))?);
+        assert_eq!(Some(2), column_count(&metadata, &SchemaPosition::new("mysql-bin.000001", 500))?);
+        assert_eq!(Some(2), column_count(&metadata, &SchemaPosition::new("mysql-bin.000002", 200))?);
+        assert_eq!(Some(3), column_count(&metadata, &SchemaPosition::new("mysql-bin.000003", 4))?);
+
+        let versions: Vec<i64> = metadata.history().versions(&TableSchema::try_from("def.db1.t1")?)
+            .iter().map(|v| v.version).collect();
+        assert_eq!(vec![0, 1, 2], versions);
+
+        let current = metadata.get_table(&"def".to_string(), &"db1".to_string(), &"t1".to_string())?.unwrap();
+        assert_eq!(2, current.read().unwrap().version);
+
+        // DROP 之后查询不到, 之前的事件仍可解码
+        metadata.apply_drop(SchemaPosition::new("mysql-bin.000003", 100),
+                            &"def".to_string(), &"db1".to_string(), &"t1".to_string())?;
+        assert_eq!(None, column_count(&metadata, &SchemaPosition::new("mysql-bin.000003", 200))?);
+        assert_eq!(Some(3), column_count(&metadata, &SchemaPosition::new("mysql-bin.000003", 4))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_order_and_purge() -> CResult<()> {
+        let mut history = SchemaHistory::default();
+        history.record(SchemaPosition::new("mysql-bin.000002", 100), table(&["id"]))?;
+        assert!(history.record(SchemaPosition::new("mysql-bin.000001", 100), table(&["id", "a"])).is_err());
+        // 相同位置重复记录时替换
+        history.record(SchemaPosition::new("mysql-bin.000002", 100), table(&["id", "a"]))?;
+        history.record(SchemaPosition::new("mysql-bin.000003", 100), table(&["id", "a", "b"]))?;
+
+        let t1 = TableSchema::try_from("def.db1.t1")?;
+        assert_eq!(2, history.versions(&t1).len());
+        assert_eq!(0, history.purge_before(&SchemaPosition::new("mysql-bin.000002", 200)));
+        assert_eq!(1, history.purge_before(&SchemaPosition::new("mysql-bin.000003", 200)));
+        assert_eq!(1, history.versions(&t1)[0].version);
+
+        history.record_drop(SchemaPosition::new("mysql-bin.000004", 100), t1.clone())?;
+        history.purge_before(&SchemaPosition::new("mysql-bin.000005", 4));
+        assert!(!history.contains(&t1));
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+use crate::binlog::column::column_type::SrcColumnType;
+use crate::binlog::column::column_value::{Date, DateTime, SrcColumnValue, Time};
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+use crate::schema::data_type::{DstColumnType, Value};
+
+/// 整数可转换的目标类型
+const INTEGER_TARGETS: &[DstColumnType] = &[
+    DstColumnType::Boolean, DstColumnType::Byte, DstColumnType::Short, DstColumnType::Int, DstColumnType::Long,
+    DstColumnType::UnsignedByte, DstColumnType::UnsignedShort, DstColumnType::UnsignedInt, DstColumnType::UnsignedLong,
+    DstColumnType::Float, DstColumnType::Double, DstColumnType::Decimal, DstColumnType::String,
+];
+const BIT_TARGETS: &[DstColumnType] = &[
+    DstColumnType::Bitmap, DstColumnType::Boolean, DstColumnType::Long, DstColumnType::UnsignedLong, DstColumnType::String,
+];
+const REAL_TARGETS: &[DstColumnType] = &[
+    DstColumnType::Float, DstColumnType::Double, DstColumnType::Decimal, DstColumnType::String,
+];
+const DECIMAL_TARGETS: &[DstColumnType] = &[DstColumnType::Decimal, DstColumnType::String];
+const TEXT_TARGETS: &[DstColumnType] = &[
+    DstColumnType::String, DstColumnType::JSON, DstColumnType::Enum, DstColumnType::Set,
+    DstColumnType::Blob, DstColumnType::Binary, DstColumnType::Bytes,
+];
+// enum 取序号时转换为整数
+const ENUM_TARGETS: &[DstColumnType] = &[
+    DstColumnType::Enum, DstColumnType::String, DstColumnType::Short, DstColumnType::Int, DstColumnType::Long,
+    DstColumnType::UnsignedShort, DstColumnType::UnsignedInt, DstColumnType::UnsignedLong,
+];
+// set 取位图时转换为整数
+const SET_TARGETS: &[DstColumnType] = &[
+    DstColumnType::Set, DstColumnType::String, DstColumnType::Long, DstColumnType::UnsignedLong,
+];
+const BLOB_TARGETS: &[DstColumnType] = &[
+    DstColumnType::Blob, DstColumnType::Binary, DstColumnType::Bytes, DstColumnType::String,
+];
+const JSON_TARGETS: &[DstColumnType] = &[
+    DstColumnType::JSON, DstColumnType::Blob, DstColumnType::Binary, DstColumnType::Bytes,
+];
+const GEOMETRY_TARGETS: &[DstColumnType] = &[
+    DstColumnType::Geometry, DstColumnType::Blob, DstColumnType::Binary, DstColumnType::Bytes,
+];
+const DATE_TARGETS: &[DstColumnType] = &[DstColumnType::Date, DstColumnType::DateTime, DstColumnType::String];
+const TIME_TARGETS: &[DstColumnType] = &[DstColumnType::Time, DstColumnType::String];
+const DATETIME_TARGETS: &[DstColumnType] = &[DstColumnType::DateTime, DstColumnType::String];
+const TIMESTAMP_TARGETS: &[DstColumnType] = &[DstColumnType::Timestamp, DstColumnType::Long];
+const NULL_TARGETS: &[DstColumnType] = &[DstColumnType::Null];
+
+/// 源列类型默认转换成的目标类型。
+///
+/// 无符号整数转换为对应的 Unsigned 类型; 有符号的 tinyint/mediumint/year 转换为 Int, 与之前的中继日志保持一致
+pub fn dst_column_type(src: SrcColumnType, unsigned: bool) -> CResult<DstColumnType> {
+    let dst = match src {
+        SrcColumnType::Null => DstColumnType::Null,
+        SrcColumnType::Decimal | SrcColumnType::NewDecimal => DstColumnType::Decimal,
+        SrcColumnType::Tiny if unsigned => DstColumnType::UnsignedByte,
+        SrcColumnType::Short if unsigned => DstColumnType::UnsignedShort,
+        SrcColumnType::Int24 | SrcColumnType::Long if unsigned => DstColumnType::UnsignedInt,
+        SrcColumnType::LongLong if unsigned => DstColumnType::UnsignedLong,
+        SrcColumnType::Tiny | SrcColumnType::Int24 | SrcColumnType::Year => DstColumnType::Int,
+        SrcColumnType::Short => DstColumnType::Short,
+        SrcColumnType::Long | SrcColumnType::LongLong => DstColumnType::Long,
+        SrcColumnType::Float => DstColumnType::Float,
+        SrcColumnType::Double => DstColumnType::Double,
+        SrcColumnType::Timestamp | SrcColumnType::Timestamp2 => DstColumnType::Timestamp,
+        SrcColumnType::Date | SrcColumnType::NewDate => DstColumnType::Date,
+        SrcColumnType::Time | SrcColumnType::Time2 => DstColumnType::Time,
+        SrcColumnType::DateTime | SrcColumnType::DateTime2 => DstColumnType::DateTime,
+        SrcColumnType::VarChar | SrcColumnType::VarString | SrcColumnType::String | SrcColumnType::Array => DstColumnType::String,
+        SrcColumnType::Enum => DstColumnType::Enum,
+        SrcColumnType::Set => DstColumnType::Set,
+        SrcColumnType::Bit => DstColumnType::Bitmap,
+        SrcColumnType::Bool => DstColumnType::Boolean,
+        SrcColumnType::Json => DstColumnType::JSON,
+        SrcColumnType::TinyBlob | SrcColumnType::MediumBlob | SrcColumnType::LongBlob | SrcColumnType::Blob => DstColumnType::Blob,
+        SrcColumnType::Geometry => DstColumnType::Geometry,
+        SrcColumnType::Invalid => {
+            return Err(ReError::String(format!("unsupported source column type {:?}", src)));
+        }
+    };
+    Ok(dst)
+}
+
+/// 源列类型可以转换成的目标类型
+pub fn dst_column_types(src: SrcColumnType) -> &'static [DstColumnType] {
+    match src {
+        SrcColumnType::Null => NULL_TARGETS,
+        SrcColumnType::Tiny | SrcColumnType::Short | SrcColumnType::Int24 | SrcColumnType::Long |
+        SrcColumnType::LongLong | SrcColumnType::Year | SrcColumnType::Bool => INTEGER_TARGETS,
+        SrcColumnType::Bit => BIT_TARGETS,
+        SrcColumnType::Float | SrcColumnType::Double => REAL_TARGETS,
+        SrcColumnType::Decimal | SrcColumnType::NewDecimal => DECIMAL_TARGETS,
+        SrcColumnType::VarChar | SrcColumnType::VarString | SrcColumnType::String | SrcColumnType::Array => TEXT_TARGETS,
+        SrcColumnType::Enum => ENUM_TARGETS,
+        SrcColumnType::Set => SET_TARGETS,
+        SrcColumnType::TinyBlob | SrcColumnType::MediumBlob | SrcColumnType::LongBlob | SrcColumnType::Blob => BLOB_TARGETS,
+        SrcColumnType::Json => JSON_TARGETS,
+        SrcColumnType::Geometry => GEOMETRY_TARGETS,
+        SrcColumnType::Date | SrcColumnType::NewDate => DATE_TARGETS,
+        SrcColumnType::Time | SrcColumnType::Time2 => TIME_TARGETS,
+        SrcColumnType::DateTime | SrcColumnType::DateTime2 => DATETIME_TARGETS,
+        SrcColumnType::Timestamp | SrcColumnType::Timestamp2 => TIMESTAMP_TARGETS,
+        SrcColumnType::Invalid => &[],
+    }
+}
+
+/// 转换矩阵中是否有 src -> dst
+pub fn is_supported(src: SrcColumnType, dst: DstColumnType) -> bool {
+    dst_column_types(src).iter().any(|t| *t as i32 == dst as i32)
+}
+
+/// 一列从源类型到目标类型的转换, 值超出目标类型的范围或精度时返回错误, 不做截断
+#[derive(Debug, Clone)]
+pub struct ColumnConversion {
+    src: SrcColumnType,
+    unsigned: bool,
+    dst: DstColumnType,
+    // enum/set 的取值名称, enum 序号从 1 开始, set 第 i 位对应第 i 个名称
+    enum_values: Vec<String>,
+}
+
+impl ColumnConversion {
+    /// 按 [dst_column_type] 确定目标类型。
+    ///
+    /// enum_values 为空(binlog_row_metadata=MINIMAL)时 enum 转换为序号, set 转换为位图, 目标库均能按数字写入
+    pub fn new(src: SrcColumnType, unsigned: bool, enum_values: Vec<String>) -> CResult<Self> {
+        let dst = match src {
+            SrcColumnType::Enum if enum_values.is_empty() => DstColumnType::UnsignedShort,
+            SrcColumnType::Set if enum_values.is_empty() => DstColumnType::UnsignedLong,
+            _ => dst_column_type(src, unsigned)?,
+        };
+        Ok(Self {
+            src,
+            unsigned,
+            dst,
+            enum_values,
+        })
+    }
+
+    /// 指定目标类型, 不在转换矩阵中时返回错误
+    pub fn with_dst_type(mut self, dst: DstColumnType) -> CResult<Self> {
+        if !is_supported(self.src, dst) {
+            return Err(ReError::String(format!("unsupported column conversion {:?} -> {:?}", self.src, dst)));
+        }
+        self.dst = dst;
+        Ok(self)
+    }
+
+    #[inline]
+    pub fn src_type(&self) -> SrcColumnType {
+        self.src
+    }
+
+    #[inline]
+    pub fn dst_type(&self) -> DstColumnType {
+        self.dst
+    }
+
+    /// 转换一个值, NULL 转换为 Value::Null
+    pub fn convert(&self, value: Option<&SrcColumnValue>) -> CResult<Value> {
+        let value = match value {
+            Some(v) => v,
+            None => return Ok(Value::Null),
+        };
+        let to = self.dst;
+        match value {
+            SrcColumnValue::TinyInt(v) => integer_to(if self.unsigned { *v as i128 } else { *v as i8 as i128 }, to),
+            SrcColumnValue::SmallInt(v) => integer_to(if self.unsigned { *v as i128 } else { *v as i16 as i128 }, to),
+            // 24 位补码
+            SrcColumnValue::MediumInt(v) => integer_to(if self.unsigned { *v as i128 } else { (((*v << 8) as i32) >> 8) as i128 }, to),
+            SrcColumnValue::Int(v) => integer_to(if self.unsigned { *v as i128 } else { *v as i32 as i128 }, to),
+            SrcColumnValue::BigInt(v) => integer_to(if self.unsigned { *v as i128 } else { *v as i64 as i128 }, to),
+            SrcColumnValue::Year(v) => integer_to(*v as i128, to),
+            SrcColumnValue::Bit(bits) => bit_to(bits, to),
+            SrcColumnValue::Enum(index) => self.enum_to(*index, to),
+            SrcColumnValue::Set(bits) => self.set_to(*bits, to),
+            SrcColumnValue::Float(v) => real_to(*v as f64, v.to_string(), to, value),
+            SrcColumnValue::Double(v) => real_to(*v, v.to_string(), to, value),
+            SrcColumnValue::Decimal(v) => decimal_to(v, to),
+            SrcColumnValue::String(v) => string_to(v, to),
+            SrcColumnValue::Blob(v) => self.bytes_to(v, to),
+            SrcColumnValue::Date(d) => date_to(d, to),
+            SrcColumnValue::Time(t) => time_to(t, to),
+            SrcColumnValue::DateTime(d) => datetime_to(d, to),
+            SrcColumnValue::Timestamp(millis) => timestamp_to(*millis, to),
+        }
+    }
+
+    fn enum_to(&self, index: u32, to: DstColumnType) -> CResult<Value> {
+        match to {
+            DstColumnType::Enum | DstColumnType::String => {
+                // 0 为非严格模式下写入的非法值 ''
+                let name = match index {
+                    0 => String::new(),
+                    _ => self.enum_values.get(index as usize - 1).cloned().ok_or_else(|| {
+                        ReError::String(format!("enum index {} out of {} values", index, self.enum_values.len()))
+                    })?,
+                };
+                Ok(if matches!(to, DstColumnType::Enum) { Value::Enum(name) } else { Value::String(name) })
+            }
+            _ => integer_to(index as i128, to),
+        }
+    }
+
+    fn set_to(&self, bits: u64, to: DstColumnType) -> CResult<Value> {
+        match to {
+            DstColumnType::Set | DstColumnType::String => {
+                if self.enum_values.len() < 64 && bits >> self.enum_values.len() != 0 {
+                    return Err(ReError::String(format!("set bits {:#x} out of {} values", bits, self.enum_values.len())));
+                }
+                let names: Vec<&str> = self.enum_values.iter().enumerate()
+                    .filter(|(i, _)| bits & (1 << i) != 0)
+                    .map(|(_, name)| name.as_str())
+                    .collect();
+                let names = names.join(",");
+                Ok(if matches!(to, DstColumnType::Set) { Value::Set(names) } else { Value::String(names) })
+            }
+            _ => integer_to(bits as i128, to),
+        }
+    }
+
+    fn bytes_to(&self, v: &[u8], to: DstColumnType) -> CResult<Value> {
+        let value = match to {
+            DstColumnType::Blob => Value::Blob(v.to_vec()),
+            DstColumnType::Binary => Value::Binary(v.to_vec()),
+            DstColumnType::Bytes => Value::Bytes(v.to_vec()),
+            // WKB, 前 4 字节为 SRID
+            DstColumnType::Geometry => Value::Blob(v.to_vec()),
+            // todo binlog 中的 JSON 为 MySQL 二进制格式, 暂不解码, 保留原始字节
+            DstColumnType::JSON if matches!(self.src, SrcColumnType::Json) => Value::Blob(v.to_vec()),
+            DstColumnType::String => Value::String(String::from_utf8(v.to_vec()).map_err(|e| {
+                ReError::String(format!("lossy conversion of {:?} to String: {}", self.src, e))
+            })?),
+            _ => return Err(unsupported(&format!("{:?}", self.src), to)),
+        };
+        Ok(value)
+    }
+}
+
+fn unsupported(value: &str, to: DstColumnType) -> ReError {
+    ReError::String(format!("can not convert {} to {:?}", value, to))
+}
+
+fn lossy(value: &str, to: DstColumnType) -> ReError {
+    ReError::String(format!("lossy conversion of {} to {:?}", value, to))
+}
+
+/// 整数, 超出目标类型的范围或浮点数的精确范围时返回错误
+fn integer_to(n: i128, to: DstColumnType) -> CResult<Value> {
+    let lossy = || lossy(&n.to_string(), to);
+    let value = match to {
+        DstColumnType::Boolean => match n {
+            0 => Value::Boolean(false),
+            1 => Value::Boolean(true),
+            _ => return Err(lossy()),
+        },
+        DstColumnType::Byte => Value::Byte(i8::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::Short => Value::Short(i16::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::Int => Value::Int(i32::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::Long => Value::Long(i64::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::UnsignedByte => Value::UnsignedByte(u8::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::UnsignedShort => Value::UnsignedShort(u16::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::UnsignedInt => Value::UnsignedInt(u32::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::UnsignedLong => Value::UnsignedLong(u64::try_from(n).map_err(|_| lossy())?),
+        DstColumnType::Bitmap => Value::Bitmap(u64::try_from(n).map_err(|_| lossy())?),
+        // 尾数 24 位
+        DstColumnType::Float if n.unsigned_abs() <= 1 << 24 => Value::Float(n as f32),
+        // 尾数 53 位
+        DstColumnType::Double if n.unsigned_abs() <= 1 << 53 => Value::Double(n as f64),
+        DstColumnType::Float | DstColumnType::Double => return Err(lossy()),
+        DstColumnType::Decimal => Value::Decimal(n.to_string()),
+        DstColumnType::String => Value::String(n.to_string()),
+        _ => return Err(unsupported(&n.to_string(), to)),
+    };
+    Ok(value)
+}
+
+/// BIT(M), 高位在前
+fn bit_to(bits: &[bool], to: DstColumnType) -> CResult<Value> {
+    if bits.len() > 64 {
+        return Err(lossy(&format!("bit({})", bits.len()), to));
+    }
+    let n = bits.iter().fold(0u64, |n, b| (n << 1) | *b as u64);
+    integer_to(n as i128, to)
+}
+
+/// float/double, text 为源值的文本
+fn real_to(v: f64, text: String, to: DstColumnType, value: &SrcColumnValue) -> CResult<Value> {
+    let value = match to {
+        DstColumnType::Float => {
+            let f = v as f32;
+            if v.is_finite() && f as f64 != v {
+                return Err(lossy(&text, to));
+            }
+            Value::Float(f)
+        }
+        DstColumnType::Double => Value::Double(v),
+        DstColumnType::Decimal if !v.is_finite() => return Err(lossy(&text, to)),
+        DstColumnType::Decimal => Value::Decimal(text),
+        DstColumnType::String => Value::String(text),
+        _ => return Err(unsupported(&format!("{:?}", value), to)),
+    };
+    Ok(value)
+}
+
+fn decimal_to(v: &str, to: DstColumnType) -> CResult<Value> {
+    match to {
+        DstColumnType::Decimal => Ok(Value::Decimal(v.to_string())),
+        DstColumnType::String => Ok(Value::String(v.to_string())),
+        _ => Err(unsupported(v, to)),
+    }
+}
+
+fn string_to(v: &str, to: DstColumnType) -> CResult<Value> {
+    let value = match to {
+        DstColumnType::String => Value::String(v.to_string()),
+        DstColumnType::JSON => Value::JSON(v.to_string()),
+        DstColumnType::Enum => Value::Enum(v.to_string()),
+        DstColumnType::Set => Value::Set(v.to_string()),
+        DstColumnType::Blob => Value::Blob(v.as_bytes().to_vec()),
+        DstColumnType::Binary => Value::Binary(v.as_bytes().to_vec()),
+        DstColumnType::Bytes => Value::Bytes(v.as_bytes().to_vec()),
+        _ => return Err(unsupported(v, to)),
+    };
+    Ok(value)
+}
+
+fn date_text(d: &Date) -> String {
+    format!("{:04}-{:02}-{:02}", d.year, d.month, d.day)
+}
+
+/// 零值日期 0000-00-00 没有对应的时间戳, 返回错误
+fn date_to(d: &Date, to: DstColumnType) -> CResult<Value> {
+    let millis = || {
+        NaiveDate::from_ymd_opt(d.year as i32, d.month as u32, d.day as u32)
+            .and_then(|date| date.and_hms_milli_opt(0, 0, 0, 0))
+            .map(|datetime| datetime.timestamp_millis())
+            .ok_or_else(|| lossy(&date_text(d), to))
+    };
+    let value = match to {
+        DstColumnType::Date => Value::Date(millis()?),
+        DstColumnType::DateTime => Value::DateTime(millis()?),
+        DstColumnType::String => Value::String(date_text(d)),
+        _ => return Err(unsupported(&date_text(d), to)),
+    };
+    Ok(value)
+}
+
+fn time_text(t: &Time) -> String {
+    let sign = if t.hour < 0 { "-" } else { "" };
+    format!("{}{:02}:{:02}:{:02}.{:03}", sign, t.hour.unsigned_abs(), t.minute, t.second, t.millis)
+}
+
+/// Time 为当天该时刻的毫秒时间戳, 超出一天的时长(-838:59:59 ~ 838:59:59)返回错误
+fn time_to(t: &Time, to: DstColumnType) -> CResult<Value> {
+    let value = match to {
+        DstColumnType::Time => {
+            let hour = u32::try_from(t.hour).map_err(|_| lossy(&time_text(t), to))?;
+            let time = NaiveTime::from_hms_milli_opt(hour, t.minute as u32, t.second as u32, t.millis)
+                .ok_or_else(|| lossy(&time_text(t), to))?;
+            Value::Time(NaiveDateTime::new(Utc::now().date_naive(), time).timestamp_millis())
+        }
+        DstColumnType::String => Value::String(time_text(t)),
+        _ => return Err(unsupported(&time_text(t), to)),
+    };
+    Ok(value)
+}
+
+fn datetime_text(d: &DateTime) -> String {
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}", d.year, d.month, d.day, d.hour, d.minute, d.second, d.millis)
+}
+
+fn datetime_to(d: &DateTime, to: DstColumnType) -> CResult<Value> {
+    let value = match to {
+        DstColumnType::DateTime => {
+            let millis = NaiveDate::from_ymd_opt(d.year as i32, d.month as u32, d.day as u32)
+                .and_then(|date| date.and_hms_milli_opt(d.hour as u32, d.minute as u32, d.second as u32, d.millis))
+                .map(|datetime| datetime.timestamp_millis())
+                .ok_or_else(|| lossy(&datetime_text(d), to))?;
+            Value::DateTime(millis)
+        }
+        DstColumnType::String => Value::String(datetime_text(d)),
+        _ => return Err(unsupported(&datetime_text(d), to)),
+    };
+    Ok(value)
+}
+
+fn timestamp_to(millis: u64, to: DstColumnType) -> CResult<Value> {
+    let millis = i64::try_from(millis).map_err(|_| lossy(&millis.to_string(), to))?;
+    match to {
+        DstColumnType::Timestamp => Ok(Value::Timestamp(millis)),
+        DstColumnType::Long => Ok(Value::Long(millis)),
+        _ => Err(unsupported(&millis.to_string(), to)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binlog::column::column_type::SrcColumnType;
+    use crate::binlog::column::column_value::{Date, SrcColumnValue};
+    use crate::err::CResult;
+    use crate::schema::data_type::{DstColumnType, Value};
+    use crate::schema::type_conversion::{ColumnConversion, dst_column_type, is_supported};
+
+    fn convert(src: SrcColumnType, unsigned: bool, value: SrcColumnValue) -> CResult<Value> {
+        ColumnConversion::new(src, unsigned, vec![])?.convert(Some(&value))
+    }
+
+    #[test]
+    fn test_matrix() -> CResult<()> {
+        assert!(matches!(dst_column_type(SrcColumnType::Tiny, false)?, DstColumnType::Int));
+        assert!(matches!(dst_column_type(SrcColumnType::Tiny, true)?, DstColumnType::UnsignedByte));
+        assert!(matches!(dst_column_type(SrcColumnType::LongLong, true)?, DstColumnType::UnsignedLong));
+        assert!(matches!(dst_column_type(SrcColumnType::Set, false)?, DstColumnType::Set));
+        assert!(dst_column_type(SrcColumnType::Invalid, false).is_err());
+
+        assert!(is_supported(SrcColumnType::Long, DstColumnType::Short));
+        assert!(!is_supported(SrcColumnType::Date, DstColumnType::Long));
+        assert!(ColumnConversion::new(SrcColumnType::Json, false, vec![])?.with_dst_type(DstColumnType::Int).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer() -> CResult<()> {
+        assert_eq!("Int(-1)", format!("{:?}", convert(SrcColumnType::Tiny, false, SrcColumnValue::TinyInt(255))?));
+        assert_eq!("UnsignedByte(255)", format!("{:?}", convert(SrcColumnType::Tiny, true, SrcColumnValue::TinyInt(255))?));
+        assert_eq!("Int(-8388608)", format!("{:?}", convert(SrcColumnType::Int24, false, SrcColumnValue::MediumInt(0x800000))?));
+        assert_eq!("UnsignedLong(18446744073709551615)",
+                   format!("{:?}", convert(SrcColumnType::LongLong, true, SrcColumnValue::BigInt(u64::MAX))?));
+
+        // bigint unsigned 超出 Long 的范围
+        let to_long = ColumnConversion::new(SrcColumnType::LongLong, true, vec![])?.with_dst_type(DstColumnType::Long)?;
+        assert!(to_long.convert(Some(&SrcColumnValue::BigInt(u64::MAX))).is_err());
+        assert_eq!("Long(7)", format!("{:?}", to_long.convert(Some(&SrcColumnValue::BigInt(7)))?));
+
+        let to_float = ColumnConversion::new(SrcColumnType::Long, false, vec![])?.with_dst_type(DstColumnType::Float)?;
+        assert!(to_float.convert(Some(&SrcColumnValue::Int(16_777_217))).is_err());
+        assert!(matches!(to_float.convert(None)?, Value::Null));
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_set_bit() -> CResult<()> {
+        let values = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let enum_conversion = ColumnConversion::new(SrcColumnType::Enum, false, values.clone())?;
+        assert_eq!("Enum(\"b\")", format!("{:?}", enum_conversion.convert(Some(&SrcColumnValue::Enum(2)))?));
+        assert!(enum_conversion.convert(Some(&SrcColumnValue::Enum(4))).is_err());
+
+        let set_conversion = ColumnConversion::new(SrcColumnType::Set, false, values)?;
+        assert_eq!("Set(\"a,c\")", format!("{:?}", set_conversion.convert(Some(&SrcColumnValue::Set(0b101)))?));
+        assert!(set_conversion.convert(Some(&SrcColumnValue::Set(0b1000))).is_err());
+
+        // 没有取值名称时为序号与位图
+        assert_eq!("UnsignedShort(2)", format!("{:?}", convert(SrcColumnType::Enum, false, SrcColumnValue::Enum(2))?));
+        assert_eq!("UnsignedLong(5)", format!("{:?}", convert(SrcColumnType::Set, false, SrcColumnValue::Set(5))?));
+
+        assert_eq!("Bitmap(5)", format!("{:?}", convert(SrcColumnType::Bit, false, SrcColumnValue::Bit(vec![true, false, true]))?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_lossy() -> CResult<()> {
+        let to_float = ColumnConversion::new(SrcColumnType::Double, false, vec![])?.with_dst_type(DstColumnType::Float)?;
+        assert!(to_float.convert(Some(&SrcColumnValue::Double(0.1))).is_err());
+        assert_eq!("Float(0.5)", format!("{:?}", to_float.convert(Some(&SrcColumnValue::Double(0.5)))?));
+
+        let zero_date = SrcColumnValue::Date(Date { year: 0, month: 0, day: 0 });
+        assert!(convert(SrcColumnType::Date, false, zero_date.clone()).is_err());
+        let to_string = ColumnConversion::new(SrcColumnType::Date, false, vec![])?.with_dst_type(DstColumnType::String)?;
+        assert_eq!("String(\"0000-00-00\")", format!("{:?}", to_string.convert(Some(&zero_date))?));
+
+        let to_string = ColumnConversion::new(SrcColumnType::Blob, false, vec![])?.with_dst_type(DstColumnType::String)?;
+        assert!(to_string.convert(Some(&SrcColumnValue::Blob(vec![0xff, 0xfe]))).is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use futures_util::future::join_all;
+use serde::Serialize;
 use tracing::warn;
 use crate::err::CResult;
 use crate::err::decode_error::ReError;
@@ -13,6 +15,115 @@ pub trait Server: Send {
 
     async fn shutdown(&mut self, graceful: bool) -> CResult<()>;
 
+    /// 暂停消费, 保留连接与状态, 由 resume 继续。 默认不支持暂停, 直接返回
+    async fn pause(&mut self) -> CResult<()> {
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> CResult<()> {
+        Ok(())
+    }
+
+    /// 组件当前的健康状态
+    fn health(&self) -> ComponentHealth {
+        ComponentHealth::new(short_type_name::<Self>(), HealthStatus::Running)
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Starting,
+    Running,
+    /// 被 pause 暂停, 连接仍然保留
+    Paused,
+    /// 运行中但出现了可恢复的错误, 如正在重连
+    Degraded,
+    Stopped,
+}
+
+/// Server::health 的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ComponentHealth {
+    pub fn new(name: &str, status: HealthStatus) -> Self {
+        ComponentHealth {
+            name: name.to_string(),
+            status,
+            message: None,
+        }
+    }
+
+    pub fn with_message(mut self, message: Option<String>) -> Self {
+        self.message = message;
+        self
+    }
+
+    /// 暂停不视为异常
+    pub fn is_healthy(&self) -> bool {
+        matches!(self.status, HealthStatus::Starting | HealthStatus::Running | HealthStatus::Paused)
+    }
+}
+
+/// 不带模块路径的类型名, 如 `CliClient`
+fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// 暂停开关, 在读取事件的线程与 pause / resume 的调用方之间共享。
+///
+/// 读取线程在事件之间调用 wait_resumed, 暂停期间阻塞在此, 不再从连接中读取; 服务端的发送因 TCP 背压而挂起,
+/// 超过 net_write_timeout 断开时由重连从最后的位置继续
+#[derive(Debug, Clone, Default)]
+pub struct PauseSwitch {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl PauseSwitch {
+    pub fn new() -> Self {
+        PauseSwitch::default()
+    }
+
+    pub fn pause(&self) {
+        self.set(true);
+    }
+
+    pub fn resume(&self) {
+        self.set(false);
+    }
+
+    fn set(&self, paused: bool) {
+        let (lock, cond) = &*self.inner;
+        if let Ok(mut p) = lock.lock() {
+            *p = paused;
+            cond.notify_all();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.0.lock().map_or(false, |p| *p)
+    }
+
+    /// 暂停时阻塞至 resume 或超过 timeout, 返回是否仍处于暂停。
+    /// 调用方在循环中检查退出标记, 暂停期间也能及时退出
+    pub fn wait_resumed(&self, timeout: Duration) -> bool {
+        let (lock, cond) = &*self.inner;
+        match lock.lock() {
+            Ok(p) => match cond.wait_timeout_while(p, timeout, |p| *p) {
+                Ok((p, _)) => *p,
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
 }
 
 pub struct ShutdownHandle {
@@ -36,6 +147,9 @@ impl ShutdownHandle {
     }
 
     pub async fn shutdown_services(&mut self, graceful: bool) -> Result<(), ReError> {
+        // 已主动关闭, drop 时不再重复关闭
+        self.shutdown.store(true, Ordering::SeqCst);
+
         let mut futures = vec![];
         for s in &mut self.services {
             futures.push(s.shutdown(graceful));
@@ -61,4 +175,61 @@ impl Drop for ShutdownHandle {
             warn!("server shutdown {:?}", r);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use mysql_common::serde_json;
+
+    use crate::err::CResult;
+    use crate::server::{ComponentHealth, HealthStatus, PauseSwitch, Server};
+
+    struct Noop;
+
+    #[async_trait::async_trait]
+    impl Server for Noop {
+        async fn start(&mut self) -> CResult<()> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self, _graceful: bool) -> CResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_health() {
+        let health = Noop.health();
+        assert_eq!(health, ComponentHealth::new("Noop", HealthStatus::Running));
+        assert_eq!(serde_json::to_string(&health).unwrap(), r#"{"name":"Noop","status":"running"}"#);
+
+        let health = ComponentHealth::new("client", HealthStatus::Paused).with_message(Some(String::from("maintenance")));
+        assert!(health.is_healthy());
+        assert_eq!(serde_json::to_string(&health).unwrap(), r#"{"name":"client","status":"paused","message":"maintenance"}"#);
+        assert!(!ComponentHealth::new("client", HealthStatus::Degraded).is_healthy());
+    }
+
+    #[test]
+    fn test_pause_switch() {
+        let switch = PauseSwitch::new();
+        assert!(!switch.wait_resumed(Duration::from_secs(10)));
+
+        switch.pause();
+        assert!(switch.is_paused());
+        assert!(switch.wait_resumed(Duration::from_millis(10)));
+
+        let s = switch.clone();
+        let resume = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            s.resume();
+        });
+        let start = Instant::now();
+        assert!(!switch.wait_resumed(Duration::from_secs(10)));
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert!(!switch.is_paused());
+        resume.join().unwrap();
+    }
+}
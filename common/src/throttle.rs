@@ -0,0 +1,138 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 令牌桶, 容量为 1 秒的速率。 允许透支, 单个超过容量的事件不会被永久阻塞
+#[derive(Debug)]
+struct TokenBucket {
+    /// 每秒补充的令牌数
+    rate: f64,
+
+    tokens: f64,
+
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        TokenBucket {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last = now;
+    }
+
+    /// 取出 n 个令牌, 返回需要等待的时间
+    fn acquire(&mut self, n: u64, now: Instant) -> Duration {
+        self.refill(now);
+
+        self.tokens -= n as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// 限制读取速度, 避免回放历史 binlog 时压垮下游。 对应 `--max-events-per-sec` / `--max-bytes-per-sec` 与 [rate_limit]
+#[derive(Debug)]
+pub struct RateLimiter {
+    events: Option<TokenBucket>,
+
+    bytes: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    /// 两个限制都未指定(或为 0)时返回 None
+    pub fn new(max_events_per_sec: Option<u64>, max_bytes_per_sec: Option<u64>) -> Option<Self> {
+        let now = Instant::now();
+        let events = max_events_per_sec.filter(|r| *r > 0).map(|r| TokenBucket::new(r, now));
+        let bytes = max_bytes_per_sec.filter(|r| *r > 0).map(|r| TokenBucket::new(r, now));
+        if events.is_none() && bytes.is_none() {
+            return None;
+        }
+
+        Some(RateLimiter { events, bytes })
+    }
+
+    /// 读取一个大小为 bytes 的事件, 超过速率时阻塞当前线程
+    pub fn acquire(&mut self, bytes: u64) {
+        let wait = self.wait_time(bytes, Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    /// 不阻塞: 令牌足够时取出并返回 true, 否则不取出并返回 false
+    pub fn try_acquire(&mut self, bytes: u64) -> bool {
+        self.try_acquire_at(bytes, Instant::now())
+    }
+
+    fn try_acquire_at(&mut self, bytes: u64, now: Instant) -> bool {
+        let mut buckets = [(self.events.as_mut(), 1), (self.bytes.as_mut(), bytes)];
+        for (bucket, n) in buckets.iter_mut() {
+            if let Some(b) = bucket {
+                b.refill(now);
+                // 单个超过容量的事件在令牌满时放行
+                if b.tokens < *n as f64 && b.tokens < b.rate {
+                    return false;
+                }
+            }
+        }
+
+        for (bucket, n) in buckets.iter_mut() {
+            if let Some(b) = bucket {
+                b.tokens -= *n as f64;
+            }
+        }
+        true
+    }
+
+    fn wait_time(&mut self, bytes: u64, now: Instant) -> Duration {
+        let events_wait = self.events.as_mut().map(|b| b.acquire(1, now)).unwrap_or_default();
+        let bytes_wait = self.bytes.as_mut().map(|b| b.acquire(bytes, now)).unwrap_or_default();
+
+        events_wait.max(bytes_wait)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::throttle::RateLimiter;
+
+    #[test]
+    fn test_wait_time() {
+        assert!(RateLimiter::new(None, Some(0)).is_none());
+
+        let mut limiter = RateLimiter::new(Some(2), Some(1000)).unwrap();
+        let now = Instant::now();
+        // 初始有 1 秒的令牌
+        assert_eq!(limiter.wait_time(100, now), Duration::ZERO);
+        assert_eq!(limiter.wait_time(100, now), Duration::ZERO);
+        // 第 3 个事件需要等待半秒
+        assert_eq!(limiter.wait_time(100, now), Duration::from_millis(500));
+
+        // 1 秒后补满, 超出字节数限制的大事件按透支计算
+        let later = now + Duration::from_secs(2);
+        assert_eq!(limiter.wait_time(1500, later), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_try_acquire() {
+        let mut limiter = RateLimiter::new(Some(2), None).unwrap();
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(10, now));
+        assert!(limiter.try_acquire_at(10, now));
+        // 失败时不消耗令牌
+        assert!(!limiter.try_acquire_at(10, now));
+        assert!(limiter.try_acquire_at(10, now + Duration::from_millis(500)));
+    }
+}
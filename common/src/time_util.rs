@@ -1,5 +1,8 @@
 use std::time::{SystemTime, UNIX_EPOCH};
-use chrono::Local;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
 
 /// 获取当前时间的秒数
 pub fn now() -> u64 {
@@ -20,4 +23,227 @@ pub fn now_str() -> String {
     let chrono_time = Local::now();
 
     return chrono_time.format("%Y-%m-%d %H:%M:%S").to_string();
-}
\ No newline at end of file
+}
+
+/// 解析本地时间 `YYYY-MM-DD HH:MM:SS` 为秒级时间戳, 同 mysqlbinlog 的 --start-datetime
+pub fn parse_datetime(datetime: &str) -> CResult<u64> {
+    let naive = NaiveDateTime::parse_from_str(datetime.trim(), "%Y-%m-%d %H:%M:%S")
+        .map_err(|e| ReError::String(format!("Invalid datetime {}, expect YYYY-MM-DD HH:MM:SS: {}", datetime, e)))?;
+
+    match Local.from_local_datetime(&naive).earliest() {
+        Some(t) => Ok(t.timestamp() as u64),
+        None => Err(ReError::String(format!("Invalid local datetime {}", datetime))),
+    }
+}
+
+/// 时区, 取值同 MySQL 的 time_zone: `SYSTEM`(本机时区)、`UTC`、`+08:00` 形式的固定偏移,
+/// 或 `Asia/Shanghai` 等 IANA 命名时区
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeZoneSpec {
+    Utc,
+    Local,
+    Fixed(FixedOffset),
+
+    /// 命名时区, 偏移随夏令时变化
+    Named(Tz),
+}
+
+/// TIMESTAMP 列的输出方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampOutput {
+    /// 秒级时间戳, 如 `1704164645.123`
+    Epoch,
+
+    Utc,
+
+    /// 源库会话的时区
+    Source,
+
+    Zone(TimeZoneSpec),
+}
+
+/// 源库会话的 time_zone。 QueryEvent 带有 time_zone 时使用该值, 否则使用默认时区
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTimeZone {
+    default: TimeZoneSpec,
+
+    current: TimeZoneSpec,
+}
+
+impl TryFrom<&str> for TimeZoneSpec {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = value.trim();
+        match value.to_ascii_uppercase().as_str() {
+            "UTC" | "GMT" | "Z" => return Ok(TimeZoneSpec::Utc),
+            "SYSTEM" | "LOCAL" => return Ok(TimeZoneSpec::Local),
+            _ => {}
+        }
+
+        let invalid = || ReError::String(format!("Invalid time zone {}, expect SYSTEM | UTC | +HH:MM | Area/City", value));
+        let (sign, rest) = match value.as_bytes().first() {
+            Some(b'+') => (1, &value[1..]),
+            Some(b'-') => (-1, &value[1..]),
+            _ => return value.parse::<Tz>().map(TimeZoneSpec::Named).map_err(|_| invalid()),
+        };
+        let (hour, minute) = rest.split_once(':').ok_or_else(invalid)?;
+        let hour = hour.parse::<i32>().map_err(|_| invalid())?;
+        let minute = minute.parse::<i32>().map_err(|_| invalid())?;
+        // 同 MySQL, 范围为 -13:59 ~ +14:00
+        if minute >= 60 || (sign > 0 && hour * 60 + minute > 14 * 60) || (sign < 0 && hour >= 14) {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hour * 3600 + minute * 60))
+            .map(TimeZoneSpec::Fixed)
+            .ok_or_else(invalid)
+    }
+}
+
+impl TimeZoneSpec {
+    /// 时间戳所在时刻的偏移, 本机时区随夏令时变化
+    pub fn offset_at(&self, secs: i64) -> FixedOffset {
+        match self {
+            TimeZoneSpec::Utc => Utc.fix(),
+            TimeZoneSpec::Local => Local.timestamp_opt(secs, 0).single()
+                .map_or_else(|| Utc.fix(), |t| t.offset().fix()),
+            TimeZoneSpec::Fixed(offset) => *offset,
+            TimeZoneSpec::Named(tz) => tz.timestamp_opt(secs, 0).single()
+                .map_or_else(|| Utc.fix(), |t| t.offset().fix()),
+        }
+    }
+
+    /// 毫秒时间戳格式化为带偏移的时间, 如 `2024-01-02 11:04:05.123+08:00`, 毫秒为 0 时省略
+    pub fn format_timestamp(&self, millis: i64) -> String {
+        let secs = millis.div_euclid(1000);
+        let ms = millis.rem_euclid(1000);
+        let offset = self.offset_at(secs);
+        let time: DateTime<FixedOffset> = match offset.timestamp_opt(secs, 0).single() {
+            Some(time) => time,
+            None => return millis.to_string(),
+        };
+
+        let fraction = if ms == 0 { String::new() } else { format!(".{:03}", ms) };
+        format!("{}{}{}", time.format("%Y-%m-%d %H:%M:%S"), fraction, time.format("%:z"))
+    }
+}
+
+impl TryFrom<&str> for TimestampOutput {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "epoch" => Ok(TimestampOutput::Epoch),
+            "utc" => Ok(TimestampOutput::Utc),
+            "source" => Ok(TimestampOutput::Source),
+            _ => TimeZoneSpec::try_from(value).map(TimestampOutput::Zone)
+                .map_err(|_| ReError::String(format!("Invalid timestamp output {}, expect epoch | utc | source | SYSTEM | +HH:MM | Area/City", value))),
+        }
+    }
+}
+
+impl TimestampOutput {
+    /// 输出使用的时区, Epoch 时为 None
+    pub fn zone(&self, session: &SessionTimeZone) -> Option<TimeZoneSpec> {
+        match self {
+            TimestampOutput::Epoch => None,
+            TimestampOutput::Utc => Some(TimeZoneSpec::Utc),
+            TimestampOutput::Source => Some(session.current()),
+            TimestampOutput::Zone(zone) => Some(*zone),
+        }
+    }
+}
+
+impl SessionTimeZone {
+    /// default 为源库的全局 time_zone, 对应 [binlog] time_zone
+    pub fn new(default: TimeZoneSpec) -> Self {
+        SessionTimeZone {
+            default,
+            current: default,
+        }
+    }
+
+    /// 按 QueryEvent 中的 time_zone 更新, 无法识别的时区保持默认并返回错误
+    pub fn update(&mut self, time_zone: Option<&str>) -> CResult<()> {
+        self.current = self.default;
+        if let Some(time_zone) = time_zone {
+            self.current = TimeZoneSpec::try_from(time_zone)?;
+        }
+        Ok(())
+    }
+
+    pub fn current(&self) -> TimeZoneSpec {
+        self.current
+    }
+}
+
+impl Default for SessionTimeZone {
+    fn default() -> Self {
+        SessionTimeZone::new(TimeZoneSpec::Local)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Local, TimeZone};
+    use crate::time_util::{parse_datetime, SessionTimeZone, TimestampOutput, TimeZoneSpec};
+
+    #[test]
+    fn test_parse_datetime() {
+        let expect = Local.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap().timestamp() as u64;
+        assert_eq!(parse_datetime("2024-01-02 03:04:05").unwrap(), expect);
+        assert!(parse_datetime("2024-01-02").is_err());
+    }
+
+    #[test]
+    fn test_time_zone() {
+        assert_eq!(TimeZoneSpec::try_from("utc").unwrap(), TimeZoneSpec::Utc);
+        assert_eq!(TimeZoneSpec::try_from("SYSTEM").unwrap(), TimeZoneSpec::Local);
+        assert!(TimeZoneSpec::try_from("Asia/Unknown").is_err());
+        assert!(TimeZoneSpec::try_from("+14:01").is_err());
+        assert!(TimeZoneSpec::try_from("-14:00").is_err());
+        assert!(TimeZoneSpec::try_from("+08:60").is_err());
+
+        // 2024-01-02 03:04:05.123 UTC
+        let millis = 1704164645123;
+        assert_eq!("2024-01-02 03:04:05.123+00:00", TimeZoneSpec::Utc.format_timestamp(millis));
+        assert_eq!("2024-01-02 11:04:05.123+08:00", TimeZoneSpec::try_from("+08:00").unwrap().format_timestamp(millis));
+        assert_eq!("2024-01-01 21:34:05-05:30", TimeZoneSpec::try_from("-05:30").unwrap().format_timestamp(1704164645000));
+    }
+
+    #[test]
+    fn test_named_time_zone() {
+        let shanghai = TimeZoneSpec::try_from("Asia/Shanghai").unwrap();
+        assert_eq!("2024-01-02 11:04:05.123+08:00", shanghai.format_timestamp(1704164645123));
+
+        // 夏令时: 2024-07-01 00:00:00 UTC 为 CEST(+02:00), 2024-01-02 为 CET(+01:00)
+        let paris = TimeZoneSpec::try_from("Europe/Paris").unwrap();
+        assert_eq!("2024-07-01 02:00:00+02:00", paris.format_timestamp(1719792000000));
+        assert_eq!("2024-01-02 04:04:05+01:00", paris.format_timestamp(1704164645000));
+
+        let mut session = SessionTimeZone::new(TimeZoneSpec::Utc);
+        session.update(Some("Europe/Paris")).unwrap();
+        assert_eq!(session.current(), paris);
+    }
+
+    #[test]
+    fn test_session_time_zone() {
+        let mut session = SessionTimeZone::new(TimeZoneSpec::Utc);
+        let source = TimestampOutput::try_from("source").unwrap();
+        assert_eq!(source.zone(&session), Some(TimeZoneSpec::Utc));
+
+        session.update(Some("+08:00")).unwrap();
+        assert_eq!(source.zone(&session), Some(TimeZoneSpec::try_from("+08:00").unwrap()));
+        assert_eq!(TimestampOutput::Utc.zone(&session), Some(TimeZoneSpec::Utc));
+        assert_eq!(TimestampOutput::Epoch.zone(&session), None);
+
+        // 没有 time_zone 的 QueryEvent 恢复为默认时区
+        session.update(None).unwrap();
+        assert_eq!(session.current(), TimeZoneSpec::Utc);
+        assert!(session.update(Some("Europe/Nowhere")).is_err());
+        assert_eq!(session.current(), TimeZoneSpec::Utc);
+
+        assert_eq!(TimestampOutput::try_from("-03:00").unwrap(), TimestampOutput::Zone(TimeZoneSpec::try_from("-03:00").unwrap()));
+        assert!(TimestampOutput::try_from("tomorrow").is_err());
+    }
+}
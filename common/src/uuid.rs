@@ -1,8 +1,17 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::hash::{Hash, Hasher};
 use fnv::FnvHasher; // 使用FNV哈希算法，因为它通常很快
+use once_cell::sync::Lazy;
 use rand::Rng;
 
+use crate::err::CResult;
+use crate::err::decode_error::ReError;
+
+/// 上一个 UUIDv7 的 (毫秒时间戳, 序号), 保证进程内生成的 id 递增
+static LAST_V7: Lazy<Mutex<(u64, u16)>> = Lazy::new(|| Mutex::new((0, 0)));
+
 /// 生成 uuid 字符串，包含当前时间戳
 pub fn uuid_timestamp() -> String {
     let now = SystemTime::now()
@@ -26,9 +35,138 @@ pub fn uuid_timestamp() -> String {
     hash_str + &random_str
 }
 
+/// 解析 server_uuid, 如 `80549ecc-d2f2-11ea-b790-0242ac130002`, 也接受不带 `-` 的 32 位十六进制
+pub fn parse_server_uuid(uuid: &str) -> CResult<[u8; 16]> {
+    let hex = if uuid.len() == 36 {
+        let dashes = [8, 13, 18, 23];
+        if !uuid.char_indices().all(|(i, c)| (c == '-') == dashes.contains(&i)) {
+            return Err(ReError::String(format!("invalid uuid: {}", uuid)));
+        }
+        uuid.replace('-', "")
+    } else {
+        uuid.to_string()
+    };
+    if hex.len() != 32 {
+        return Err(ReError::String(format!("invalid uuid: {}", uuid)));
+    }
+
+    let mut data = [0u8; 16];
+    hex::decode_to_slice(&hex, &mut data).map_err(|e| ReError::String(format!("invalid uuid {}: {}", uuid, e)))?;
+    Ok(data)
+}
+
+/// server_uuid 的标准格式: 小写, 8-4-4-4-12
+pub fn format_server_uuid(data: &[u8; 16]) -> String {
+    let mut uuid = hex::encode(data);
+    uuid.insert(20, '-');
+    uuid.insert(16, '-');
+    uuid.insert(12, '-');
+    uuid.insert(8, '-');
+    uuid
+}
+
+/// 生成 UUIDv7, 按时间排序, 用作事件 id。 同一毫秒内递增序号, 进程内严格递增
+pub fn uuid_v7() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64;
+
+    let (ms, seq) = {
+        let mut last = LAST_V7.lock().unwrap_or_else(|e| e.into_inner());
+        // 时钟回拨或序号用完时沿用上一个时间戳
+        *last = if now > last.0 {
+            (now, rand::thread_rng().gen_range(0..0x400))
+        } else if last.1 < 0xFFF {
+            (last.0, last.1 + 1)
+        } else {
+            (last.0 + 1, 0)
+        };
+        *last
+    };
+
+    format_server_uuid(&uuid_v7_bytes(ms, seq, rand::thread_rng().gen()))
+}
+
+/// UUIDv7 中的毫秒时间戳
+pub fn uuid_v7_timestamp(uuid: &str) -> CResult<u64> {
+    let data = parse_server_uuid(uuid)?;
+    if data[6] >> 4 != 7 {
+        return Err(ReError::String(format!("{} is not a uuid v7", uuid)));
+    }
+    let mut ms = [0u8; 8];
+    ms[2..].copy_from_slice(&data[..6]);
+    Ok(u64::from_be_bytes(ms))
+}
+
+/// unix_ts_ms(48) | ver(4) | seq(12) | var(2) | rand(62)
+fn uuid_v7_bytes(ms: u64, seq: u16, rand: u64) -> [u8; 16] {
+    let mut data = [0u8; 16];
+    data[..6].copy_from_slice(&ms.to_be_bytes()[2..]);
+    data[6..8].copy_from_slice(&(0x7000 | (seq & 0x0FFF)).to_be_bytes());
+    data[8..].copy_from_slice(&((rand & 0x3FFF_FFFF_FFFF_FFFF) | 0x8000_0000_0000_0000).to_be_bytes());
+    data
+}
+
+/// server_uuid 与 sid 的映射, 用 (sid, gno) 代替 `uuid:gno` 紧凑地表示 GTID。
+///
+/// sid 从 1 开始按首次出现的顺序分配, 同一个 SidMap 内不变
+#[derive(Debug, Clone, Default)]
+pub struct SidMap {
+    uuids: Vec<[u8; 16]>,
+
+    sids: HashMap<[u8; 16], u32>,
+}
+
+impl SidMap {
+    pub fn new() -> Self {
+        SidMap::default()
+    }
+
+    /// 返回 uuid 的 sid, 不存在时分配
+    pub fn add(&mut self, uuid: &[u8; 16]) -> u32 {
+        if let Some(sid) = self.sids.get(uuid) {
+            return *sid;
+        }
+        self.uuids.push(*uuid);
+        let sid = self.uuids.len() as u32;
+        self.sids.insert(*uuid, sid);
+        sid
+    }
+
+    pub fn sid(&self, uuid: &[u8; 16]) -> Option<u32> {
+        self.sids.get(uuid).copied()
+    }
+
+    pub fn uuid(&self, sid: u32) -> Option<&[u8; 16]> {
+        self.uuids.get((sid as usize).checked_sub(1)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.uuids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uuids.is_empty()
+    }
+
+    /// `uuid:gno` 编码为 (sid, gno)
+    pub fn encode_gtid(&mut self, gtid: &str) -> CResult<(u32, u64)> {
+        let (uuid, gno) = gtid.rsplit_once(':')
+            .ok_or_else(|| ReError::String(format!("invalid gtid: {}", gtid)))?;
+        let gno = gno.parse::<u64>().map_err(|e| ReError::String(format!("invalid gtid {}: {}", gtid, e)))?;
+        Ok((self.add(&parse_server_uuid(uuid)?), gno))
+    }
+
+    /// (sid, gno) 还原为 `uuid:gno`, sid 不存在时返回 None
+    pub fn decode_gtid(&self, sid: u32, gno: u64) -> Option<String> {
+        self.uuid(sid).map(|uuid| format!("{}:{}", format_server_uuid(uuid), gno))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::uuid::uuid_timestamp;
+    use crate::uuid::{format_server_uuid, parse_server_uuid, SidMap, uuid_timestamp, uuid_v7, uuid_v7_bytes, uuid_v7_timestamp};
 
     #[test]
     fn test_uuid_timestamp() {
@@ -38,4 +176,42 @@ mod tests {
         // 输出可能类似于 "15ddccbeaf8d"
         println!("{}", result);
     }
+
+    #[test]
+    fn test_server_uuid() {
+        let uuid = "80549ecc-d2f2-11ea-b790-0242ac130002";
+        let data = parse_server_uuid(uuid).unwrap();
+        assert_eq!(0x80, data[0]);
+        assert_eq!(uuid, format_server_uuid(&data));
+        assert_eq!(data, parse_server_uuid("80549ECCD2F211EAB7900242AC130002").unwrap());
+
+        assert!(parse_server_uuid("80549ecc-d2f2-11ea-b790").is_err());
+        assert!(parse_server_uuid("80549ecc-d2f211ea-b790-0242ac1300020").is_err());
+        assert!(parse_server_uuid("x0549ecc-d2f2-11ea-b790-0242ac130002").is_err());
+    }
+
+    #[test]
+    fn test_uuid_v7() {
+        let data = uuid_v7_bytes(0x0123_4567_89AB, 0xFFF, u64::MAX);
+        assert_eq!("01234567-89ab-7fff-bfff-ffffffffffff", format_server_uuid(&data));
+        assert_eq!(0x0123_4567_89AB, uuid_v7_timestamp(&format_server_uuid(&data)).unwrap());
+
+        let ids: Vec<String> = (0..5000).map(|_| uuid_v7()).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(uuid_v7_timestamp("80549ecc-d2f2-11ea-b790-0242ac130002").is_err());
+    }
+
+    #[test]
+    fn test_sid_map() {
+        let mut sids = SidMap::new();
+        assert_eq!((1, 5), sids.encode_gtid("80549ecc-d2f2-11ea-b790-0242ac130002:5").unwrap());
+        assert_eq!((2, 1), sids.encode_gtid("00000000-0000-0000-0000-000000000001:1").unwrap());
+        assert_eq!((1, 6), sids.encode_gtid("80549ecc-d2f2-11ea-b790-0242ac130002:6").unwrap());
+        assert_eq!(2, sids.len());
+
+        assert_eq!(Some(String::from("80549ecc-d2f2-11ea-b790-0242ac130002:6")), sids.decode_gtid(1, 6));
+        assert_eq!(None, sids.decode_gtid(0, 1));
+        assert_eq!(None, sids.decode_gtid(3, 1));
+        assert!(sids.encode_gtid("80549ecc-d2f2-11ea-b790-0242ac130002").is_err());
+    }
 }
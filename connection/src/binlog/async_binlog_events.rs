@@ -0,0 +1,107 @@
+use binlog::decoder::event_decoder::LogEventDecoder;
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::events::checksum_type::ChecksumType;
+use binlog::events::log_context::{ILogContext, LogContextRef};
+use binlog::events::log_position::LogFilePosition;
+use binlog::factory::event_factory::EventReaderOption;
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use crate::binlog::binlog_events::decode_event_packet;
+use crate::binlog::replication_filter::ReplicationFilter;
+use crate::conn::async_packet_channel::AsyncPacketChannel;
+use crate::packet::end_of_file_packet::EndOfFilePacket;
+use crate::packet::error_packet::ErrorPacket;
+use crate::packet::response_type::ResponseType;
+
+/// BinlogEvents 的异步版本, 由 AsyncConnection::binlog 创建。
+///
+/// 事件解码与 BinlogEvents 共用 decode_event_packet。 LogContext 为 Rc<RefCell<_>>,
+/// 因此需要在 tokio::task::LocalSet / spawn_local 中驱动
+#[derive(Debug)]
+pub struct AsyncBinlogEvents {
+    channel: AsyncPacketChannel,
+    parser: LogEventDecoder,
+
+    options: EventReaderOption,
+    log_context: LogContextRef,
+
+    /// 复制过滤规则, 被过滤的事件不会进入解码
+    filter: Option<ReplicationFilter>,
+
+    /// 是否已读取到 EOF
+    finished: bool,
+}
+
+impl AsyncBinlogEvents {
+    pub fn new(channel: AsyncPacketChannel, log_context: LogContextRef, checksum: ChecksumType,
+               payload_buffer_size: usize) -> Self {
+        AsyncBinlogEvents {
+            channel,
+            parser: LogEventDecoder::new(),
+            options: EventReaderOption::debug_with_payload_buffer_size(payload_buffer_size),
+            log_context,
+            filter: None,
+            finished: false,
+        }
+    }
+
+    /// 设置复制过滤规则
+    pub fn with_filter(mut self, filter: Option<ReplicationFilter>) -> Self {
+        self.filter = filter.filter(|f| !f.is_empty());
+        self
+    }
+
+    /// 读取下一批事件。 返回 None 表示 binlog 流已结束(非 blocking 模式下读取到 EOF)
+    pub async fn next(&mut self) -> Option<CResult<Vec<BinlogEvent>>> {
+        if self.finished {
+            return None;
+        }
+
+        let (packet, _) = match self.channel.read_packet().await {
+            Ok(x) => x,
+            Err(e) => return Some(Err(e)),
+        };
+
+        match packet[0] {
+            ResponseType::OK => {
+                self.log_context.borrow_mut().add_log_stat(packet.len());
+
+                Some(decode_event_packet(&mut self.parser, &self.options, &self.log_context, self.filter.as_mut(), &packet))
+            },
+            ResponseType::ERROR => {
+                let error = match ErrorPacket::parse(&packet[1..]) {
+                    Ok(e) => e,
+                    Err(e) => return Some(Err(ReError::IoError(e))),
+                };
+                Some(Err(ReError::String(format!("Event stream error. {:?}", error))))
+            },
+            ResponseType::END_OF_FILE => {
+                let _ = EndOfFilePacket::parse(&packet[1..]);
+                self.finished = true;
+                None
+            },
+            _ => Some(Err(ReError::String(
+                "Unknown network stream status".to_string(),
+            ))),
+        }
+    }
+
+    /// 获取接受到的流量总大小
+    pub fn get_receives_bytes(&self) -> usize {
+        self.log_context.borrow().load_receives_bytes()
+    }
+
+    /// 获取当前的 LogFilePosition
+    pub fn get_log_position(&self) -> LogFilePosition {
+        self.log_context.borrow().get_log_position()
+    }
+
+    pub fn get_log_context(&self) -> LogContextRef {
+        self.log_context.clone()
+    }
+
+    pub async fn close(&mut self) -> CResult<()> {
+        self.finished = true;
+        self.channel.shutdown().await
+    }
+}
@@ -14,6 +14,8 @@ use binlog::factory::event_factory::{EventReaderOption, IEventFactory};
 use common::binlog::{EVENT_HEADER_SIZE, PAYLOAD_BUFFER_SIZE};
 use common::err::CResult;
 use common::err::decode_error::ReError;
+use crate::binlog::reconnect::{BinlogReconnector, ResumePosition, ER_MASTER_FATAL_ERROR_READING_BINLOG};
+use crate::binlog::replication_filter::ReplicationFilter;
 use crate::conn::packet_channel::PacketChannel;
 use crate::packet::end_of_file_packet::EndOfFilePacket;
 use crate::packet::error_packet::ErrorPacket;
@@ -29,6 +31,15 @@ pub struct BinlogEvents {
 
     /// 加载的缓冲区。 会被多次复用
     payload_buffer: Vec<u8>,
+
+    /// 复制过滤规则, 被过滤的事件不会进入解码
+    filter: Option<ReplicationFilter>,
+
+    /// 断线重连, None 时错误直接返回
+    reconnector: Option<Arc<BinlogReconnector>>,
+
+    /// 重连时的起始位置: 最近一个事务边界
+    resume: ResumePosition,
 }
 
 impl BinlogEvents {
@@ -44,30 +55,26 @@ impl BinlogEvents {
             options,
             log_context,
             payload_buffer: Vec::with_capacity(payload_buffer_size),
+            filter: None,
+            reconnector: None,
+            resume: ResumePosition::default(),
         }
     }
 
-    pub fn read_event(&mut self, packet: &[u8]) -> CResult<Vec<BinlogEvent>> {
-        let header = Header::parse_v4_header(&packet[1..], self.log_context.clone()).unwrap();
-        let payload_length = (&header.get_event_length() - LOG_EVENT_HEADER_LEN as u32) as usize;
-
-        let header_ref: HeaderRef = Rc::new(RefCell::new(header));
-
-        let event = if payload_length > self.options.get_payload_buffer_size() {
-            // 事件payload大小超过缓冲buffer，直接以事件payload大小分配新字节数组，用于读取事件的完整大小
-            // let mut event_slice: Vec<u8> = vec![0; payload_length];
-            let event_slice = &packet[1 + EVENT_HEADER_SIZE..];
-
-            self.parser.event_parse_mergr(event_slice, header_ref.clone(), self.log_context.clone())?
-        } else {
-            // 从缓冲区中取空字节数组，用于读取事件的完整大小。let event_slice = &mut self.packet[0..payload_length]。
-            // 此处采用了直接 slice 的切片形式。不存在内存分配。更节省内存。
-            let event_slice = &packet[1 + EVENT_HEADER_SIZE..];
+    pub(crate) fn with_reconnector(mut self, reconnector: Option<BinlogReconnector>) -> Self {
+        self.reconnector = reconnector.map(Arc::new);
+        self.resume = ResumePosition::new(&self.log_context);
+        self
+    }
 
-            self.parser.event_parse_mergr(event_slice, header_ref.clone(), self.log_context.clone())?
-        };
+    /// 设置复制过滤规则
+    pub fn with_filter(mut self, filter: Option<ReplicationFilter>) -> Self {
+        self.filter = filter.filter(|f| !f.is_empty());
+        self
+    }
 
-        Ok(vec![event])
+    pub fn read_event(&mut self, packet: &[u8]) -> CResult<Vec<BinlogEvent>> {
+        decode_event_packet(&mut self.parser, &self.options, &self.log_context, self.filter.as_mut(), packet)
     }
 
     pub fn read_error(&mut self, packet: &[u8]) -> CResult<Vec<BinlogEvent>> {
@@ -87,6 +94,44 @@ impl BinlogEvents {
     }
 }
 
+/// 解析一个 OK 数据包中的 binlog 事件, 同步与异步的 binlog 流共用
+pub(crate) fn decode_event_packet(parser: &mut LogEventDecoder, options: &EventReaderOption, log_context: &LogContextRef,
+                                  filter: Option<&mut ReplicationFilter>, packet: &[u8]) -> CResult<Vec<BinlogEvent>> {
+    let header = Header::parse_v4_header(&packet[1..], log_context.clone()).unwrap();
+    let payload_length = (&header.get_event_length() - LOG_EVENT_HEADER_LEN as u32) as usize;
+
+    // 心跳与伪造的 RotateEvent 时间戳为 0
+    if header.when > 0 {
+        log_context.borrow_mut().set_event_timestamp(header.when);
+    }
+
+    if let Some(filter) = filter {
+        if !filter.accept(header.get_event_type(), &packet[1 + EVENT_HEADER_SIZE..]) {
+            // 被过滤的事件不解码，仅推进 position
+            log_context.borrow_mut().update_position_offset(header.get_log_pos());
+            return Ok(vec![]);
+        }
+    }
+
+    let header_ref: HeaderRef = Rc::new(RefCell::new(header));
+
+    let event = if payload_length > options.get_payload_buffer_size() {
+        // 事件payload大小超过缓冲buffer，直接以事件payload大小分配新字节数组，用于读取事件的完整大小
+        // let mut event_slice: Vec<u8> = vec![0; payload_length];
+        let event_slice = &packet[1 + EVENT_HEADER_SIZE..];
+
+        parser.event_parse_mergr(event_slice, header_ref.clone(), log_context.clone())?
+    } else {
+        // 从缓冲区中取空字节数组，用于读取事件的完整大小。let event_slice = &mut self.packet[0..payload_length]。
+        // 此处采用了直接 slice 的切片形式。不存在内存分配。更节省内存。
+        let event_slice = &packet[1 + EVENT_HEADER_SIZE..];
+
+        parser.event_parse_mergr(event_slice, header_ref.clone(), log_context.clone())?
+    };
+
+    Ok(vec![event])
+}
+
 impl Clone for BinlogEvents {
     fn clone(&self) -> Self {
         BinlogEvents {
@@ -95,6 +140,9 @@ impl Clone for BinlogEvents {
             options: self.options.clone(),
             log_context: self.log_context.clone(),
             payload_buffer: self.payload_buffer.clone(),
+            filter: self.filter.clone(),
+            reconnector: self.reconnector.clone(),
+            resume: self.resume.clone(),
         }
     }
 }
@@ -107,6 +155,9 @@ impl Default for BinlogEvents {
             options: EventReaderOption::default(),
             log_context: Rc::new(RefCell::new(LogContext::default())),
             payload_buffer: Vec::new(),
+            filter: None,
+            reconnector: None,
+            resume: ResumePosition::default(),
         }
     }
 }
@@ -117,25 +168,64 @@ impl Iterator for BinlogEvents {
     /// Reads binlog event packets from network stream.
     /// <a href="https://mariadb.com/kb/en/3-binlog-network-stream/">See more</a>
     fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (next, recoverable) = self.read_next();
+
+            let reconnector = match self.reconnector.as_ref() {
+                Some(r) if recoverable && r.is_recoverable(next.is_none()) => r.clone(),
+                _ => return next,
+            };
+
+            let cause = match next {
+                Some(Err(e)) => e,
+                _ => ReError::ConnectionError(String::from("binlog stream reached EOF")),
+            };
+
+            match reconnector.reconnect(cause, &mut self.resume, &self.log_context) {
+                Ok(channel) => self.channel = channel,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl BinlogEvents {
+    /// 读取下一个数据包。 返回值中的 bool 表示该错误(或 EOF)是否可以通过重新注册 slave 恢复
+    fn read_next(&mut self) -> (Option<CResult<Vec<BinlogEvent>>>, bool) {
         let (packet, _) = match self.channel.borrow_mut().read_packet() {
             Ok(x) => x,
-            Err(e) => return Some(Err(e)),
+            Err(e) => {
+                let recoverable = !e.is_fatal();
+                return (Some(Err(e)), recoverable);
+            },
         };
 
         match packet[0] {
             ResponseType::OK => {
                 self.log_context.borrow_mut().add_log_stat(packet.len());
 
-                Some(self.read_event(&packet))
+                let events = self.read_event(&packet);
+                if events.is_ok() && self.reconnector.is_some() {
+                    // event header: timestamp(4) event_type(1) ...
+                    self.resume.observe(packet[1 + 4], &packet[1 + EVENT_HEADER_SIZE..], &self.log_context);
+                }
+
+                (Some(events), false)
+            },
+            ResponseType::ERROR => {
+                let recoverable = ErrorPacket::parse(&packet[1..])
+                    .map(|e| e.error_code == ER_MASTER_FATAL_ERROR_READING_BINLOG)
+                    .unwrap_or(false);
+
+                (Some(self.read_error(&packet)), recoverable)
             },
-            ResponseType::ERROR => Some(self.read_error(&packet)),
             ResponseType::END_OF_FILE => {
                 let _ = EndOfFilePacket::parse(&packet[1..]);
-                None
+                (None, true)
             },
-            _ => Some(Err(ReError::String(
+            _ => (Some(Err(ReError::String(
                 "Unknown network stream status".to_string(),
-            ))),
+            ))), false),
         }
     }
 }
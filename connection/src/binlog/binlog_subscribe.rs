@@ -1,4 +1,7 @@
-use std::fmt::Debug;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use serde::Serialize;
@@ -8,24 +11,36 @@ use binlog::events::binlog_event::BinlogEvent;
 use binlog::events::log_context::ILogContext;
 use binlog::events::log_position::LogFilePosition;
 use crate::binlog::lifecycle::lifecycle::BinlogLifecycle;
+use common::binlog::FIRST_EVENT_POSITION;
 use common::config::BinlogConfig;
 use common::config::load_style::Format;
 use common::err::CResult;
 use common::err::decode_error::ReError;
 use common::pretty_util::{to_bytes_len_pretty, to_duration_pretty, to_string_pretty};
-use common::server::Server;
+use common::server::{ComponentHealth, HealthStatus, PauseSwitch, Server};
 use crate::binlog::binlog_events_wrapper::{BinlogEventsWrapper};
+use crate::binlog::binlog_options::BinlogOptions;
+use crate::binlog::reconnect::ReconnectOptions;
+use crate::binlog::replication_filter::ReplicationFilter;
 use crate::conn::binlog_connection::{BinlogConnection, IBinlogConnection};
 use crate::conn::connection::IConnection;
 use crate::conn::connection_options::ConnectionOptions;
 use crate::env_options::EnvOptions;
 
+/// 暂停期间检查停止标记的间隔
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 暂停期间缓冲的事件批数上限
+const PAUSE_BUFFER_BATCHES: usize = 1024;
+
 /// Binlog 订阅器
 ///
 ///   setup ----> start  -----> binlogs   ---->  pause
 ///                                       ---->  shutdown
 ///
-#[derive(Debug)]
+/// 订阅到的每个事件的回调
+pub type EventListener = Box<dyn FnMut(&BinlogEvent) + Send>;
+
 pub struct BinlogSubscribe {
     debug: bool,
 
@@ -33,6 +48,27 @@ pub struct BinlogSubscribe {
 
     binlog_config: BinlogConfig,
     subscribe_options: SubscribeOptions,
+
+    /// 置为 true 后, 在读取到下一个事件时退出 start
+    shutdown: Arc<AtomicBool>,
+
+    /// 暂停时不再回调事件, 见 [PauseBuffer]
+    pause: PauseSwitch,
+
+    listener: Option<EventListener>,
+}
+
+impl Debug for BinlogSubscribe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinlogSubscribe")
+            .field("debug", &self.debug)
+            .field("conn", &self.conn)
+            .field("binlog_config", &self.binlog_config)
+            .field("subscribe_options", &self.subscribe_options)
+            .field("paused", &self.pause.is_paused())
+            .field("listener", &self.listener.is_some())
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -44,14 +80,17 @@ pub struct SubscribeOptions {
     print_logs: bool,
 
     format: Format,
+
+    /// 持续读取: blocking 模式并在连接异常时重连
+    follow: bool,
 }
 
 #[async_trait::async_trait]
 impl Server for BinlogSubscribe {
     async fn start(&mut self) -> CResult<()> {
         let c = self.get_binlog_config();
-        self.setup(&c).expect("BinlogSubscribe setup Error!");
-        self.start_in().expect("BinlogSubscribe start Error!");
+        self.setup(&c)?;
+        self.start_in()?;
 
         // 延缓启动，便于观察上述配置项信息
         debug!("wait for 500 millis to-viewing of the above configuration...");
@@ -60,11 +99,16 @@ impl Server for BinlogSubscribe {
 
         let mut binlogs_warpper = self.binlogs().await?;
         // 读取binlog 数据
-        for x in binlogs_warpper.get_iter() {
+        let binlogs = PauseBuffer::new(binlogs_warpper.get_iter(), self.pause.clone(), self.shutdown.clone(), PAUSE_BUFFER_BATCHES);
+        for x in binlogs {
             if x.is_ok() {
                 let list = x.unwrap();
 
                 for e in list {
+                    if let Some(listener) = self.listener.as_mut() {
+                        listener(&e);
+                    }
+
                     let event_type = BinlogEvent::get_type_name(&e);
 
                     // 输出事件的详细信息
@@ -97,22 +141,64 @@ impl Server for BinlogSubscribe {
 
     async fn shutdown(&mut self, graceful: bool) -> CResult<()> {
         println!("BinlogSubscribe shutdown");
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
 
+    async fn pause(&mut self) -> CResult<()> {
+        self.pause.pause();
         Ok(())
     }
+
+    async fn resume(&mut self) -> CResult<()> {
+        self.pause.resume();
+        Ok(())
+    }
+
+    fn health(&self) -> ComponentHealth {
+        let status = if self.shutdown.load(Ordering::SeqCst) {
+            HealthStatus::Stopped
+        } else if self.pause.is_paused() {
+            HealthStatus::Paused
+        } else if self.conn.is_none() {
+            HealthStatus::Starting
+        } else {
+            HealthStatus::Running
+        };
+        ComponentHealth::new("BinlogSubscribe", status)
+    }
 }
 
 #[async_trait::async_trait]
 impl BinlogLifecycle for BinlogSubscribe {
     #[instrument]
     fn setup(&mut self, binlog_config: &BinlogConfig) -> CResult<()> {
-        let mut opts = ConnectionOptions::new(
+        // 指定了 binlog file 时从该文件的 position 开始, 否则从第一个 binlog 开始
+        let binlog_options = match binlog_config.file.as_ref() {
+            Some(file) => {
+                let position = binlog_config.position.map(|p| p as u64).unwrap_or(FIRST_EVENT_POSITION as u64);
+                BinlogOptions::from_position(file.clone(), position)
+            },
+            None => BinlogOptions::from_start(),
+        };
+        let mut opts = ConnectionOptions::new_with_binlog(
             binlog_config.get_host().to_string(),
             binlog_config.get_port(),
             binlog_config.username.clone(),
             binlog_config.password.clone(),
+            binlog_options,
         );
         opts.set_env(EnvOptions::new(self.debug, false));
+        if let Some(replicate) = binlog_config.replicate.as_ref() {
+            opts.set_replication_filter(ReplicationFilter::new(replicate)?);
+        }
+        if self.subscribe_options.is_follow() {
+            // blocking 模式下以 slave 身份注册, 需要唯一的 server_id
+            opts.update_server_id(binlog_config.get_server_id());
+            opts.blocking = true;
+            opts.set_reconnect(ReconnectOptions::default());
+        }
 
         let binlog_conn = BinlogConnection::new(&opts);
         self.conn = Some(binlog_conn);
@@ -134,10 +220,6 @@ impl BinlogLifecycle for BinlogSubscribe {
             }
         }
     }
-
-    async fn pause(&mut self) -> CResult<()> {
-        todo!()
-    }
 }
 
 impl BinlogSubscribe {
@@ -147,9 +229,30 @@ impl BinlogSubscribe {
             conn: None,
             binlog_config,
             subscribe_options,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            pause: PauseSwitch::new(),
+            listener: None,
         }
     }
 
+    /// 每读取到一个事件时调用 listener
+    pub fn with_listener(mut self, listener: EventListener) -> Self {
+        self.listener = Some(listener);
+        self
+    }
+
+    /// 使用外部的停止标记, 在 start 运行的线程之外停止订阅
+    pub fn with_shutdown(mut self, shutdown: Arc<AtomicBool>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// 使用外部的暂停开关, 在 start 运行的线程之外暂停与恢复订阅
+    pub fn with_pause(mut self, pause: PauseSwitch) -> Self {
+        self.pause = pause;
+        self
+    }
+
     /// 当前已经处理的binlog数量
     pub fn load_read_ptr(&self) -> u64 {
         self.conn.as_ref().unwrap().get_log_context().borrow().load_read_ptr()
@@ -160,6 +263,16 @@ impl BinlogSubscribe {
         self.conn.as_ref().unwrap().get_log_context().borrow().get_log_position()
     }
 
+    /// 已执行的 GTID 集合, 以 GTID 方式订阅时存在
+    pub fn get_gtid_set(&self) -> Option<String> {
+        self.conn.as_ref().unwrap().get_log_context().borrow().get_gtid_set().map(|g| g.to_string())
+    }
+
+    /// 最近一个事件的时间戳(秒)
+    pub fn get_event_timestamp(&self) -> u32 {
+        self.conn.as_ref().unwrap().get_log_context().borrow().get_event_timestamp()
+    }
+
     pub fn get_binlog_config(&self) -> BinlogConfig {
         self.binlog_config.clone()
     }
@@ -181,6 +294,78 @@ impl BinlogSubscribe {
     }
 }
 
+/// 暂停期间继续从连接读取事件到有界的缓冲中, 恢复后先返回缓冲的事件。
+///
+/// 暂停时不读取连接, 源库的发送会超过 net_write_timeout 而断开连接。 缓冲满、读取出错或读取结束后不再读取,
+/// 之后连接仍可能断开, follow 模式下恢复时由重连从已读取的位置继续。 停止后不再返回事件, 包括缓冲中的事件
+pub struct PauseBuffer<I: Iterator<Item = CResult<T>>, T> {
+    inner: I,
+    buffered: VecDeque<CResult<T>>,
+    capacity: usize,
+    // inner 已读取结束
+    ended: bool,
+    pause: PauseSwitch,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl<I: Iterator<Item = CResult<T>>, T> PauseBuffer<I, T> {
+    pub fn new(inner: I, pause: PauseSwitch, shutdown: Arc<AtomicBool>, capacity: usize) -> Self {
+        PauseBuffer {
+            inner,
+            buffered: VecDeque::new(),
+            capacity: capacity.max(1),
+            ended: false,
+            pause,
+            shutdown,
+        }
+    }
+
+    /// 暂停期间缓冲的批数
+    pub fn buffered(&self) -> usize {
+        self.buffered.len()
+    }
+
+    fn read_inner(&mut self) -> Option<CResult<T>> {
+        if self.ended {
+            return None;
+        }
+        let next = self.inner.next();
+        self.ended = next.is_none();
+        next
+    }
+
+    /// 暂停期间是否继续读取
+    fn can_buffer(&self) -> bool {
+        !self.ended && self.buffered.len() < self.capacity && !self.buffered.back().map_or(false, |x| x.is_err())
+    }
+}
+
+impl<I: Iterator<Item = CResult<T>>, T> Iterator for PauseBuffer<I, T> {
+    type Item = CResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.shutdown.load(Ordering::Relaxed) {
+                return None;
+            }
+            if !self.pause.is_paused() {
+                return match self.buffered.pop_front() {
+                    Some(x) => Some(x),
+                    None => self.read_inner(),
+                };
+            }
+
+            if self.can_buffer() {
+                if let Some(x) = self.read_inner() {
+                    self.buffered.push_back(x);
+                }
+            } else {
+                self.pause.wait_resumed(PAUSE_POLL_INTERVAL);
+            }
+        }
+    }
+}
+
 impl Default for SubscribeOptions {
     fn default() -> Self {
         SubscribeOptions::new(false, false, Format::None)
@@ -193,9 +378,18 @@ impl SubscribeOptions {
             debug,
             print_logs,
             format,
+            follow: false,
         }
     }
 
+    pub fn set_follow(&mut self, follow: bool) {
+        self.follow = follow;
+    }
+
+    pub fn is_follow(&self) -> bool {
+        self.follow
+    }
+
     pub fn is_print_logs(&self) -> bool {
         self.print_logs
     }
@@ -208,3 +402,99 @@ impl SubscribeOptions {
         self.format.clone()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use common::err::CResult;
+    use common::err::decode_error::ReError;
+    use common::server::PauseSwitch;
+
+    use crate::binlog::binlog_subscribe::PauseBuffer;
+
+    /// 记录读取次数的事件源
+    fn source(items: Vec<CResult<u32>>, reads: Arc<AtomicUsize>) -> impl Iterator<Item = CResult<u32>> {
+        items.into_iter().inspect(move |_| {
+            reads.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    fn ids(items: Vec<CResult<u32>>) -> Vec<u32> {
+        items.into_iter().map(|x| x.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_pause_resume() {
+        let pause = PauseSwitch::new();
+        let reads = Arc::new(AtomicUsize::new(0));
+        let mut binlogs = PauseBuffer::new(source((1..=10).map(Ok).collect(), reads.clone()), pause.clone(), Arc::new(AtomicBool::new(false)), 4);
+        assert_eq!(binlogs.next().unwrap().unwrap(), 1);
+
+        // 暂停期间继续读取, 缓冲满后不再读取
+        pause.pause();
+        let resume = {
+            let pause = pause.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                pause.resume();
+            })
+        };
+        assert_eq!(binlogs.next().unwrap().unwrap(), 2);
+        resume.join().unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 5);
+        assert_eq!(binlogs.buffered(), 3);
+
+        // 恢复后按顺序返回缓冲与之后的事件
+        assert_eq!(ids(binlogs.collect()), (3..=10).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_pause_buffer_error() {
+        let pause = PauseSwitch::new();
+        let reads = Arc::new(AtomicUsize::new(0));
+        let items = vec![Ok(1), Err(ReError::String("connection reset".to_string())), Ok(2)];
+        let mut binlogs = PauseBuffer::new(source(items, reads.clone()), pause.clone(), Arc::new(AtomicBool::new(false)), 4);
+
+        // 读取出错后暂停期间不再读取
+        pause.pause();
+        let resume = {
+            let pause = pause.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                pause.resume();
+            })
+        };
+        assert_eq!(binlogs.next().unwrap().unwrap(), 1);
+        resume.join().unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 2);
+        assert!(binlogs.next().unwrap().is_err());
+        assert_eq!(binlogs.next().unwrap().unwrap(), 2);
+        assert!(binlogs.next().is_none());
+    }
+
+    #[test]
+    fn test_stop_while_paused() {
+        let pause = PauseSwitch::new();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let reads = Arc::new(AtomicUsize::new(0));
+        let mut binlogs = PauseBuffer::new(source((1..=10).map(Ok).collect(), reads.clone()), pause.clone(), shutdown.clone(), 4);
+
+        pause.pause();
+        let stop = {
+            let shutdown = shutdown.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                shutdown.store(true, Ordering::SeqCst);
+            })
+        };
+        // 停止后不再返回缓冲中的事件
+        assert!(binlogs.next().is_none());
+        stop.join().unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), 4);
+        assert!(pause.is_paused());
+    }
+}
@@ -11,8 +11,4 @@ pub trait BinlogLifecycle: Server {
 
     async fn binlogs(&mut self) -> Result<BinlogEventsWrapper, ReError>;
 
-    /// 暂停服务，服务挂起
-    async fn pause(&mut self) -> CResult<()>;
-
-
 }
\ No newline at end of file
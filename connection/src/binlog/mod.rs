@@ -1,7 +1,11 @@
 pub mod starting_strategy;
 pub mod binlog_options;
 pub mod binlog_events;
+pub mod async_binlog_events;
 pub mod binlog_events_wrapper;
 pub mod binlog_subscribe;
+pub mod replication_filter;
+pub mod parallel_applier;
+pub mod reconnect;
 pub mod lifecycle;
 mod reg;
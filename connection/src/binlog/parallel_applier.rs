@@ -0,0 +1,586 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::thread::JoinHandle;
+use tracing::{debug, error};
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::events::protocol::table_map_event::TableMapEvent;
+use binlog::row::row_data::{RowData, UpdateRowData};
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+/// 事务内行变更的分发粒度
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleStrategy {
+    /// 按表分发, 同一张表的变更由同一个 worker 顺序执行
+    Table,
+
+    /// 按主键分发, 同一行的变更由同一个 worker 顺序执行。
+    /// 表结构中没有主键信息时(binlog_row_metadata != FULL), 退化为按表分发
+    PrimaryKey,
+}
+
+/// 并行回放配置
+#[derive(Debug, Clone)]
+pub struct ApplierOptions {
+    /// worker 线程数。 Defaults to 4.
+    pub workers: usize,
+
+    /// 分发粒度。 Defaults to ScheduleStrategy::Table.
+    pub strategy: ScheduleStrategy,
+
+    /// 是否保持事务的提交顺序。 开启后, 前一个事务的全部变更执行完成后才会分发下一个事务。
+    /// Defaults to false.
+    pub preserve_commit_order: bool,
+}
+
+impl Default for ApplierOptions {
+    fn default() -> Self {
+        ApplierOptions {
+            workers: 4,
+            strategy: ScheduleStrategy::Table,
+            preserve_commit_order: false,
+        }
+    }
+}
+
+/// 单行变更
+#[derive(Debug, Clone)]
+pub enum RowChange {
+    Insert(RowData),
+    Update(UpdateRowData),
+    Delete(RowData),
+}
+
+/// 分发给 worker 的执行单元
+#[derive(Debug, Clone)]
+pub enum ApplyTask {
+    /// 行变更。 seq 为所属事务的序号
+    Rows {
+        seq: u64,
+        database: String,
+        table: String,
+        changes: Vec<RowChange>,
+    },
+
+    /// DDL 等语句, 执行时其他 worker 均处于空闲状态
+    Query {
+        seq: u64,
+        schema: String,
+        query: String,
+    },
+}
+
+/// 在 worker 线程中执行的回放逻辑, 如写入目标库
+pub trait EventApplier: Send + Sync {
+    fn apply(&self, task: &ApplyTask) -> CResult<()>;
+}
+
+/// 按 库/表/主键 的 hash 把变更分配到 lane, 同一 key 总是分配到同一个 lane。
+///
+/// [ParallelApplier] 与中继日志回放共用, 修改分配方式会使中继日志回放记录的各 lane 位点失效
+#[derive(Debug, Clone)]
+pub struct LaneScheduler {
+    lanes: usize,
+}
+
+impl LaneScheduler {
+    pub fn new(lanes: usize) -> Self {
+        LaneScheduler {
+            lanes: lanes.max(1),
+        }
+    }
+
+    pub fn lanes(&self) -> usize {
+        self.lanes
+    }
+
+    /// key 为主键值, 为 None 时按表分配
+    pub fn lane_of(&self, database: &str, table: &str, key: Option<&str>) -> usize {
+        let mut hasher = DefaultHasher::new();
+        database.hash(&mut hasher);
+        table.hash(&mut hasher);
+        key.hash(&mut hasher);
+
+        (hasher.finish() % self.lanes as u64) as usize
+    }
+}
+
+/// 按表/主键将解码后的事务分发到多个 worker 并行执行, 同一 key 的变更保持原有顺序。
+///
+///   push(BinlogEvent) --> Transaction --> hash(table | pk) % workers --> worker[i] --> EventApplier
+///
+/// 任一 worker 执行失败后, 所有 worker 跳过剩余的任务, 不再分发新的任务, 之后的 push / flush 均返回错误
+#[derive(Debug)]
+pub struct ParallelApplier {
+    options: ApplierOptions,
+    scheduler: LaneScheduler,
+
+    workers: Vec<Sender<(ApplyTask, Arc<Latch>)>>,
+    handles: Vec<JoinHandle<()>>,
+
+    /// worker 执行失败时记录的首个错误
+    error: Arc<Mutex<Option<ReError>>>,
+    /// 执行失败后置位, worker 不再执行任务
+    stopped: Arc<AtomicBool>,
+    /// 首个错误已返回给调用方后, 之后调用返回的错误信息
+    failed: Option<String>,
+
+    /// 尚未执行完成的任务
+    in_flight: Arc<Latch>,
+
+    /// table_id -> TableMapEvent
+    tables: HashMap<u64, TableMapEvent>,
+
+    /// 当前事务中的待分发任务: (worker, 任务, 是否为屏障)
+    pending: Vec<(usize, ApplyTask, bool)>,
+    seq: u64,
+}
+
+impl ParallelApplier {
+    pub fn new(options: ApplierOptions, applier: Arc<dyn EventApplier>) -> Self {
+        let worker_count = options.workers.max(1);
+        let error = Arc::new(Mutex::new(None));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let mut workers = Vec::with_capacity(worker_count);
+        let mut handles = Vec::with_capacity(worker_count);
+        for i in 0..worker_count {
+            let (tx, rx) = channel::<(ApplyTask, Arc<Latch>)>();
+            let applier = applier.clone();
+            let error = error.clone();
+            let stopped = stopped.clone();
+
+            let handle = std::thread::Builder::new()
+                .name(format!("binlog-applier-{}", i))
+                .spawn(move || {
+                    for (task, latch) in rx {
+                        if stopped.load(Ordering::SeqCst) {
+                            latch.count_down();
+                            continue;
+                        }
+                        if let Err(e) = applier.apply(&task) {
+                            error!("applier worker {} apply error: {:?}", i, &e);
+                            let mut guard = error.lock().unwrap();
+                            if !stopped.swap(true, Ordering::SeqCst) {
+                                *guard = Some(e);
+                            }
+                        }
+                        latch.count_down();
+                    }
+                    debug!("applier worker {} exit", i);
+                })
+                .expect("spawn applier worker");
+
+            workers.push(tx);
+            handles.push(handle);
+        }
+
+        ParallelApplier {
+            options,
+            scheduler: LaneScheduler::new(worker_count),
+            workers,
+            handles,
+            error,
+            stopped,
+            failed: None,
+            in_flight: Arc::new(Latch::new()),
+            tables: HashMap::new(),
+            pending: Vec::new(),
+            seq: 0,
+        }
+    }
+
+    /// 接收解码后的事件。 事务结束(XID / COMMIT)时分发整个事务
+    pub fn push(&mut self, event: BinlogEvent) -> CResult<()> {
+        self.check_error()?;
+
+        match event {
+            BinlogEvent::TableMap(e) => {
+                self.tables.insert(e.get_table_id(), e);
+            },
+            BinlogEvent::WriteRows(e) => {
+                let changes = e.rows.into_iter().map(RowChange::Insert).collect();
+                self.add_rows(e.table_id, changes)?;
+            },
+            BinlogEvent::UpdateRows(e) => {
+                let changes = e.rows.into_iter().map(RowChange::Update).collect();
+                self.add_rows(e.table_id, changes)?;
+            },
+            BinlogEvent::DeleteRows(e) => {
+                let changes = e.rows.into_iter().map(RowChange::Delete).collect();
+                self.add_rows(e.table_id, changes)?;
+            },
+            BinlogEvent::XID(_) => {
+                self.commit()?;
+            },
+            BinlogEvent::Query(e) => {
+                let query = e.query.trim();
+                if query.eq_ignore_ascii_case("BEGIN") {
+                    // 事务开始, 无需处理
+                } else if query.eq_ignore_ascii_case("COMMIT") {
+                    self.commit()?;
+                } else {
+                    // DDL 等语句作为屏障执行
+                    self.commit()?;
+                    let task = ApplyTask::Query {
+                        seq: self.seq,
+                        schema: e.schema.clone(),
+                        query: query.to_string(),
+                    };
+                    self.pending.push((0, task, true));
+                    self.commit()?;
+                }
+            },
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    /// 等待所有已分发的任务执行完成
+    pub fn flush(&mut self) -> CResult<()> {
+        self.commit()?;
+        self.in_flight.wait();
+
+        self.check_error()
+    }
+
+    /// 关闭所有 worker, 等待执行完成
+    pub fn shutdown(mut self) -> CResult<()> {
+        let rs = self.flush();
+
+        self.workers.clear();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+
+        rs
+    }
+
+    fn add_rows(&mut self, table_id: u64, changes: Vec<RowChange>) -> CResult<()> {
+        let table = match self.tables.get(&table_id) {
+            Some(t) => t,
+            None => {
+                return Err(ReError::String(format!("TableMapEvent not found for table_id {}", table_id)));
+            }
+        };
+        let database = table.get_database_name();
+        let table_name = table.get_table_name();
+
+        let pk_index: Vec<usize> = table.get_column_infos().iter().enumerate()
+            .filter(|(_, c)| c.is_pk())
+            .map(|(i, _)| i)
+            .collect();
+
+        if self.options.strategy == ScheduleStrategy::Table || pk_index.is_empty() {
+            let lane = self.scheduler.lane_of(&database, &table_name, None);
+            let task = ApplyTask::Rows { seq: self.seq, database, table: table_name, changes };
+            self.pending.push((lane, task, false));
+
+            return Ok(());
+        }
+
+        for change in changes {
+            let (lane, barrier) = match &change {
+                RowChange::Insert(row) | RowChange::Delete(row) => {
+                    (self.scheduler.lane_of(&database, &table_name, Some(&pk_key(row, &pk_index))), false)
+                },
+                RowChange::Update(row) => {
+                    let before = self.scheduler.lane_of(&database, &table_name, Some(&pk_key(&row.before_update, &pk_index)));
+                    let after = self.scheduler.lane_of(&database, &table_name, Some(&pk_key(&row.after_update, &pk_index)));
+                    // 主键被修改时, 新旧 key 可能落在不同 worker 上, 作为屏障执行以保证顺序
+                    (before, before != after)
+                },
+            };
+
+            let task = ApplyTask::Rows {
+                seq: self.seq,
+                database: database.clone(),
+                table: table_name.clone(),
+                changes: vec![change],
+            };
+            self.pending.push((lane, task, barrier));
+        }
+
+        Ok(())
+    }
+
+    /// 分发当前事务
+    fn commit(&mut self) -> CResult<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let pending = std::mem::take(&mut self.pending);
+        for (lane, task, barrier) in pending {
+            if barrier {
+                self.in_flight.wait();
+                self.check_error()?;
+                self.dispatch(lane, task)?;
+                self.in_flight.wait();
+            } else {
+                self.check_error()?;
+                self.dispatch(lane, task)?;
+            }
+        }
+        self.seq += 1;
+
+        if self.options.preserve_commit_order {
+            self.in_flight.wait();
+        }
+
+        self.check_error()
+    }
+
+    fn dispatch(&self, index: usize, task: ApplyTask) -> CResult<()> {
+        self.in_flight.count_up();
+        if let Err(e) = self.workers[index].send((task, self.in_flight.clone())) {
+            self.in_flight.count_down();
+            return Err(ReError::String(format!("applier worker {} is closed: {}", index, e)));
+        }
+
+        Ok(())
+    }
+
+    /// 首次返回 worker 的原始错误, 之后一直返回失败
+    fn check_error(&mut self) -> CResult<()> {
+        if let Some(msg) = &self.failed {
+            return Err(ReError::String(format!("parallel applier stopped after error: {}", msg)));
+        }
+        if !self.stopped.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let e = self.error.lock().unwrap().take()
+            .unwrap_or_else(|| ReError::String("parallel applier stopped.".to_string()));
+        self.failed = Some(e.to_string());
+        Err(e)
+    }
+}
+
+/// SrcColumnValue 未实现 Hash, 以 Debug 输出作为主键值
+fn pk_key(row: &RowData, pk_index: &[usize]) -> String {
+    pk_index.iter()
+        .map(|i| format!("{:?}", row.get_cells().get(*i)))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// 未完成任务计数, 归零时唤醒等待方
+#[derive(Debug)]
+struct Latch {
+    count: Mutex<usize>,
+    cond: Condvar,
+}
+
+impl Latch {
+    fn new() -> Self {
+        Latch {
+            count: Mutex::new(0),
+            cond: Condvar::new(),
+        }
+    }
+
+    fn count_up(&self) {
+        *self.count.lock().unwrap() += 1;
+    }
+
+    fn count_down(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.cond.notify_all();
+        }
+    }
+
+    fn wait(&self) {
+        let mut count = self.count.lock().unwrap();
+        while *count > 0 {
+            count = self.cond.wait(count).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use common::err::CResult;
+    use common::err::decode_error::ReError;
+    use crate::binlog::parallel_applier::{ApplierOptions, ApplyTask, EventApplier, LaneScheduler, Latch, ParallelApplier};
+
+    struct Recorder {
+        tasks: Mutex<Vec<String>>,
+        running: AtomicUsize,
+        // 屏障执行时仍有其他任务在执行的次数
+        overlapped: AtomicUsize,
+    }
+
+    impl Recorder {
+        fn new() -> Arc<Self> {
+            Arc::new(Recorder { tasks: Mutex::new(vec![]), running: AtomicUsize::new(0), overlapped: AtomicUsize::new(0) })
+        }
+    }
+
+    impl EventApplier for Recorder {
+        fn apply(&self, task: &ApplyTask) -> CResult<()> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst);
+            let rs = match task {
+                ApplyTask::Query { query, .. } => {
+                    if running > 0 {
+                        self.overlapped.fetch_add(1, Ordering::SeqCst);
+                    }
+                    self.tasks.lock().unwrap().push(query.clone());
+                    Ok(())
+                }
+                ApplyTask::Rows { seq, table, .. } => {
+                    std::thread::sleep(Duration::from_millis(1));
+                    self.tasks.lock().unwrap().push(format!("{}:{}", table, seq));
+                    if table == "bad" {
+                        Err(ReError::String(format!("apply {} failed", seq)))
+                    } else {
+                        Ok(())
+                    }
+                }
+            };
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            rs
+        }
+    }
+
+    fn add_rows(applier: &mut ParallelApplier, table: &str) {
+        let lane = applier.scheduler.lane_of("db", table, None);
+        let task = ApplyTask::Rows { seq: applier.seq, database: "db".to_string(), table: table.to_string(), changes: vec![] };
+        applier.pending.push((lane, task, false));
+    }
+
+    fn add_query(applier: &mut ParallelApplier, query: &str) {
+        let task = ApplyTask::Query { seq: applier.seq, schema: "db".to_string(), query: query.to_string() };
+        applier.pending.push((0, task, true));
+    }
+
+    #[test]
+    fn test_latch() {
+        let latch = Arc::new(Latch::new());
+        latch.count_up();
+        latch.count_up();
+
+        let l = latch.clone();
+        let h = std::thread::spawn(move || {
+            l.count_down();
+            l.count_down();
+        });
+        latch.wait();
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn test_lane_scheduler() {
+        let scheduler = LaneScheduler::new(4);
+        for i in 0..32 {
+            let key = i.to_string();
+            let lane = scheduler.lane_of("db", "t1", Some(&key));
+            assert!(lane < 4);
+            assert_eq!(lane, scheduler.lane_of("db", "t1", Some(&key)));
+        }
+        assert_eq!(LaneScheduler::new(0).lanes(), 1);
+        assert_eq!(LaneScheduler::new(1).lane_of("db", "t1", None), 0);
+    }
+
+    #[test]
+    fn test_empty_flush() {
+        let recorder = Recorder::new();
+        let mut applier = ParallelApplier::new(ApplierOptions::default(), recorder.clone());
+
+        assert!(applier.flush().is_ok());
+        assert!(applier.shutdown().is_ok());
+        assert!(recorder.tasks.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dispatch_order() {
+        let recorder = Recorder::new();
+        let mut applier = ParallelApplier::new(ApplierOptions::default(), recorder.clone());
+        for _ in 0..20 {
+            for table in ["t1", "t2", "t3"] {
+                add_rows(&mut applier, table);
+            }
+            applier.commit().unwrap();
+        }
+        applier.shutdown().unwrap();
+
+        // 同一张表的变更按事务顺序执行
+        let tasks = recorder.tasks.lock().unwrap();
+        assert_eq!(tasks.len(), 60);
+        for table in ["t1", "t2", "t3"] {
+            let seqs: Vec<u64> = tasks.iter()
+                .filter_map(|t| t.strip_prefix(&format!("{}:", table)))
+                .map(|s| s.parse().unwrap())
+                .collect();
+            assert_eq!(seqs, (0..20).collect::<Vec<u64>>());
+        }
+    }
+
+    #[test]
+    fn test_barrier() {
+        let recorder = Recorder::new();
+        let mut applier = ParallelApplier::new(ApplierOptions::default(), recorder.clone());
+        for i in 0..5 {
+            for table in ["t1", "t2", "t3", "t4"] {
+                add_rows(&mut applier, table);
+            }
+            applier.commit().unwrap();
+            add_query(&mut applier, &format!("ALTER TABLE t{} ADD c int", i));
+            applier.commit().unwrap();
+        }
+        applier.shutdown().unwrap();
+
+        // 屏障执行时没有其他任务在执行, 且在之前的事务全部执行完成之后
+        assert_eq!(recorder.overlapped.load(Ordering::SeqCst), 0);
+        let tasks = recorder.tasks.lock().unwrap();
+        assert_eq!(tasks.len(), 25);
+        for i in 0..5 {
+            let query = tasks.iter().position(|t| *t == format!("ALTER TABLE t{} ADD c int", i)).unwrap();
+            assert_eq!(query, i * 5 + 4);
+        }
+    }
+
+    #[test]
+    fn test_error_propagation() {
+        let recorder = Recorder::new();
+        let mut applier = ParallelApplier::new(ApplierOptions::default(), recorder.clone());
+        add_rows(&mut applier, "bad");
+        applier.commit().unwrap();
+
+        let e = applier.flush().unwrap_err();
+        assert!(e.to_string().contains("apply 0 failed"));
+        // 之后的提交均失败, 不再分发
+        for _ in 0..3 {
+            add_rows(&mut applier, "t1");
+            assert!(applier.commit().is_err());
+            assert!(applier.flush().is_err());
+        }
+        assert!(applier.shutdown().is_err());
+        assert_eq!(*recorder.tasks.lock().unwrap(), vec!["bad:0".to_string()]);
+    }
+
+    #[test]
+    fn test_error_stops_workers() {
+        let recorder = Recorder::new();
+        let mut applier = ParallelApplier::new(ApplierOptions { workers: 1, ..ApplierOptions::default() }, recorder.clone());
+        add_rows(&mut applier, "bad");
+        for _ in 0..10 {
+            add_rows(&mut applier, "t1");
+        }
+        // 已进入 worker 队列的任务在失败后被跳过
+        let _ = applier.commit();
+        assert!(applier.flush().is_err());
+        assert!(applier.shutdown().is_err());
+        assert_eq!(*recorder.tasks.lock().unwrap(), vec!["bad:0".to_string()]);
+    }
+}
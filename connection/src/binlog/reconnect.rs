@@ -0,0 +1,349 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+use binlog::alias::mysql::gtid::gtid_set::GtidSet;
+use binlog::b_type::LogEventType;
+use binlog::events::log_context::{ILogContext, LogContextRef};
+use binlog::events::log_position::LogFilePosition;
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use crate::binlog::binlog_options::BinlogOptions;
+use crate::binlog::replication_filter::parse_query_schema;
+use crate::binlog::starting_strategy::StartingStrategy;
+use crate::conn::binlog_connection::BinlogConnection;
+use crate::conn::connection::{Connection, IConnection};
+use crate::conn::connection_options::ConnectionOptions;
+use crate::conn::packet_channel::PacketChannel;
+
+/// Got fatal error from master when reading data from binary log
+pub const ER_MASTER_FATAL_ERROR_READING_BINLOG: u16 = 1236;
+
+/// 重新注册过程中的通知
+pub trait ReconnectListener: Send + Sync {
+    /// 开始第 attempt 次重连
+    fn on_reconnecting(&self, _attempt: u32, _cause: &ReError, _position: &LogFilePosition) {}
+
+    /// 重连成功, 已从 position 处重新请求 dump
+    fn on_reconnected(&self, _attempt: u32, _position: &LogFilePosition) {}
+
+    /// 超过最大重试次数, 放弃重连
+    fn on_give_up(&self, _attempts: u32, _cause: &ReError) {}
+}
+
+/// 断线重连配置。 在连接断开、EOF(blocking 模式)或 ER_MASTER_FATAL_ERROR_READING_BINLOG 时,
+/// 从最近一个事务边界(已完整消费的事务之后)处重新注册为 slave 并请求 dump, 而不是直接将错误返回给调用方。
+#[derive(Clone)]
+pub struct ReconnectOptions {
+    /// 单次故障的最大重试次数。 Defaults to 5.
+    pub max_retries: u32,
+
+    /// 首次重试的等待间隔, 之后每次翻倍。 Defaults to 1 second.
+    pub retry_interval: Duration,
+
+    /// 重试等待间隔的上限。 Defaults to 30 seconds.
+    pub max_retry_interval: Duration,
+
+    listeners: Vec<Arc<dyn ReconnectListener>>,
+}
+
+impl Default for ReconnectOptions {
+    fn default() -> Self {
+        ReconnectOptions {
+            max_retries: 5,
+            retry_interval: Duration::from_secs(1),
+            max_retry_interval: Duration::from_secs(30),
+            listeners: Vec::new(),
+        }
+    }
+}
+
+impl Debug for ReconnectOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectOptions")
+            .field("max_retries", &self.max_retries)
+            .field("retry_interval", &self.retry_interval)
+            .field("max_retry_interval", &self.max_retry_interval)
+            .field("listeners", &self.listeners.len())
+            .finish()
+    }
+}
+
+impl ReconnectOptions {
+    pub fn new(max_retries: u32, retry_interval: Duration) -> Self {
+        ReconnectOptions {
+            max_retries,
+            retry_interval,
+            ..ReconnectOptions::default()
+        }
+    }
+
+    pub fn add_listener(&mut self, listener: Arc<dyn ReconnectListener>) {
+        self.listeners.push(listener);
+    }
+
+    /// 第 attempt 次重试前的等待时间
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+
+        self.retry_interval.saturating_mul(factor).min(self.max_retry_interval)
+    }
+}
+
+/// 最近一个事务边界之后的 position 与 gtid_set, 重连时从这里重新请求 dump, 避免从事务中间恢复。
+///
+/// 事务边界: XID_EVENT、COMMIT / ROLLBACK, 以及事务之外的事件(DDL、ROTATE 等)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ResumePosition {
+    position: LogFilePosition,
+    gtid_set: Option<GtidSet>,
+
+    /// 是否处于 BEGIN 之后、提交之前
+    in_trx: bool,
+}
+
+impl ResumePosition {
+    /// 以 log_context 当前的位置作为起点
+    pub(crate) fn new(log_context: &LogContextRef) -> Self {
+        let mut resume = ResumePosition::default();
+        resume.commit(log_context);
+        resume
+    }
+
+    pub(crate) fn get_log_position(&self) -> &LogFilePosition {
+        &self.position
+    }
+
+    pub(crate) fn get_gtid_set(&self) -> Option<&GtidSet> {
+        self.gtid_set.as_ref()
+    }
+
+    /// 事件解码(或被过滤)之后调用, 此时 log_context 的 position 已推进到该事件之后
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type`: 事件类型
+    /// * `payload`: 不包含 event header 的事件内容
+    /// * `log_context`: 事件所在的上下文
+    pub(crate) fn observe(&mut self, event_type: u8, payload: &[u8], log_context: &LogContextRef) {
+        let boundary = match LogEventType::from(event_type) {
+            LogEventType::XID_EVENT => {
+                self.in_trx = false;
+                true
+            },
+            LogEventType::QUERY_EVENT => {
+                match parse_query_schema(payload).map(|(_, query)| query) {
+                    Some(query) if starts_with_ignore_case(query, "BEGIN") => {
+                        self.in_trx = true;
+                        false
+                    },
+                    Some(query) if starts_with_ignore_case(query, "COMMIT") || starts_with_ignore_case(query, "ROLLBACK") => {
+                        self.in_trx = false;
+                        true
+                    },
+                    _ => !self.in_trx,
+                }
+            },
+            // GTID 事件之后紧跟着事务内容; 心跳中的 position 不是已读取的位置
+            LogEventType::GTID_LOG_EVENT | LogEventType::ANONYMOUS_GTID_LOG_EVENT |
+            LogEventType::HEARTBEAT_LOG_EVENT => false,
+            _ => !self.in_trx,
+        };
+
+        if boundary {
+            self.commit(log_context);
+        }
+    }
+
+    /// 重新请求 dump 之后, 事件从边界处重新开始
+    pub(crate) fn reset(&mut self, log_context: &LogContextRef) {
+        self.in_trx = false;
+
+        let mut ctx = log_context.borrow_mut();
+        ctx.force_set_log_position(self.position.clone());
+        if let (Some(current), Some(gtid_set)) = (ctx.get_gtid_set_as_mut(), self.gtid_set.as_ref()) {
+            *current = gtid_set.clone();
+        }
+    }
+
+    fn commit(&mut self, log_context: &LogContextRef) {
+        let ctx = log_context.borrow();
+        self.position = ctx.get_log_position();
+        self.gtid_set = ctx.get_gtid_set().cloned();
+    }
+}
+
+fn starts_with_ignore_case(s: &[u8], prefix: &str) -> bool {
+    s.len() >= prefix.len() && s[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+/// 重新注册 slave 并请求 dump
+#[derive(Debug)]
+pub(crate) struct BinlogReconnector {
+    options: ConnectionOptions,
+    reconnect: ReconnectOptions,
+}
+
+impl BinlogReconnector {
+    pub(crate) fn new(options: ConnectionOptions, reconnect: ReconnectOptions) -> Self {
+        BinlogReconnector {
+            options,
+            reconnect,
+        }
+    }
+
+    /// 是否可以通过重新注册恢复。 非 blocking 模式下 EOF 表示读取结束
+    pub(crate) fn is_recoverable(&self, eof: bool) -> bool {
+        !eof || self.options.blocking
+    }
+
+    /// 按重试策略从 resume 记录的事务边界处重新建立 dump 连接, 返回新的 channel
+    pub(crate) fn reconnect(&self, cause: ReError, resume: &mut ResumePosition,
+                            log_context: &LogContextRef) -> CResult<Arc<RefCell<PacketChannel>>> {
+        let position = resume.get_log_position().clone();
+        let mut last_err = cause;
+        let mut attempts = 0;
+
+        for attempt in 1..=self.reconnect.max_retries {
+            attempts = attempt;
+            warn!("binlog stream interrupted: {}, reconnect attempt {}/{} from {}:{}",
+                last_err, attempt, self.reconnect.max_retries, position.get_file_name(), position.get_position());
+            for l in &self.reconnect.listeners {
+                l.on_reconnecting(attempt, &last_err, &position);
+            }
+
+            std::thread::sleep(self.reconnect.backoff(attempt));
+
+            match self.dump(resume) {
+                Ok(channel) => {
+                    resume.reset(log_context);
+                    for l in &self.reconnect.listeners {
+                        l.on_reconnected(attempt, &position);
+                    }
+                    return Ok(channel);
+                },
+                Err(e) if e.is_fatal() => {
+                    // 认证失败等重试无法恢复的错误, 不再重试
+                    last_err = e;
+                    break;
+                },
+                Err(e) => {
+                    last_err = e;
+                }
+            }
+        }
+
+        error!("binlog reconnect give up after {} attempts: {}", attempts, last_err);
+        for l in &self.reconnect.listeners {
+            l.on_give_up(attempts, &last_err);
+        }
+
+        Err(last_err)
+    }
+
+    fn dump(&self, resume: &ResumePosition) -> CResult<Arc<RefCell<PacketChannel>>> {
+        let mut options = self.options.clone();
+
+        let position = resume.get_log_position();
+        let gtid_set = resume.get_gtid_set().cloned();
+        let from_gtid = options.binlog.as_ref()
+            .map(|b| b.borrow().starting_strategy == StartingStrategy::FromGtid)
+            .unwrap_or(false);
+
+        // 从最近的事务边界继续, 未读取到任何位置信息时沿用原配置
+        if from_gtid && gtid_set.is_some() {
+            options.binlog = Some(Arc::new(RefCell::new(BinlogOptions::from_gtid(gtid_set.unwrap()))));
+        } else if !position.get_file_name().is_empty() {
+            options.binlog = Some(Arc::new(RefCell::new(
+                BinlogOptions::from_position(position.get_file_name(), position.get_position()))));
+        }
+
+        let mut conn = Connection::new(options.clone());
+        conn.try_connect()?;
+
+        let mut channel = match conn.channel.clone() {
+            Some(c) => c,
+            None => return Err(ReError::ConnectionError(String::from("channel not found"))),
+        };
+        conn.configure.set_master_heartbeat(&mut channel)?;
+        conn.configure.set_master_binlog_checksum(&mut channel)?;
+
+        let server_id = if options.blocking {
+            options.server_id
+        } else {
+            0
+        };
+        BinlogConnection::replicate_mysql(&mut channel, &options, server_id)?;
+
+        Ok(channel)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use binlog::b_type::LogEventType;
+    use binlog::events::log_context::{ILogContext, LogContext, LogContextRef};
+    use binlog::events::log_position::LogFilePosition;
+    use crate::binlog::reconnect::{ReconnectOptions, ResumePosition};
+
+    /// QUERY_EVENT payload: thread_id(4) exec_time(4) schema_len(1) error_code(2) status_vars_len(2) schema 0x00 query
+    fn query(sql: &str) -> Vec<u8> {
+        let mut payload = vec![0u8; 13];
+        payload[8] = 3;
+        payload.extend_from_slice(b"db1\0");
+        payload.extend_from_slice(sql.as_bytes());
+        payload
+    }
+
+    fn observe(resume: &mut ResumePosition, ctx: &LogContextRef, event_type: LogEventType, payload: &[u8], pos: u64) -> u64 {
+        ctx.borrow_mut().update_position_offset(pos);
+        resume.observe(event_type as u8, payload, ctx);
+        resume.get_log_position().get_position()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut opts = ReconnectOptions::new(10, Duration::from_secs(1));
+        opts.max_retry_interval = Duration::from_secs(5);
+
+        assert_eq!(opts.backoff(1), Duration::from_secs(1));
+        assert_eq!(opts.backoff(2), Duration::from_secs(2));
+        assert_eq!(opts.backoff(3), Duration::from_secs(4));
+        assert_eq!(opts.backoff(4), Duration::from_secs(5));
+        assert_eq!(opts.backoff(64), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_resume_position() {
+        let ctx: LogContextRef = Rc::new(RefCell::new(LogContext::new(LogFilePosition::new_with_position("mysql-bin.000001", 4))));
+        let mut resume = ResumePosition::new(&ctx);
+        assert_eq!(resume.get_log_position().get_position(), 4);
+
+        // 事务中间断开时回到事务开始之前
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::GTID_LOG_EVENT, &[], 100), 4);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::QUERY_EVENT, &query("BEGIN"), 180), 4);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::TABLE_MAP_EVENT, &[], 240), 4);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::WRITE_ROWS_EVENT, &[], 300), 4);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::XID_EVENT, &[], 331), 331);
+
+        // DDL 不在事务内, 本身就是边界
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::GTID_LOG_EVENT, &[], 400), 331);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::QUERY_EVENT, &query("CREATE TABLE t2 (id int)"), 500), 500);
+
+        // 非事务引擎以 COMMIT 结束
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::QUERY_EVENT, &query("BEGIN"), 560), 500);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::WRITE_ROWS_EVENT, &[], 600), 500);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::HEARTBEAT_LOG_EVENT, &[], 600), 500);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::QUERY_EVENT, &query("COMMIT"), 640), 640);
+
+        // 重连后 log_context 回到边界处
+        observe(&mut resume, &ctx, LogEventType::QUERY_EVENT, &query("BEGIN"), 700);
+        resume.reset(&ctx);
+        assert_eq!(ctx.borrow().get_log_position().get_position(), 640);
+        assert_eq!(observe(&mut resume, &ctx, LogEventType::ROTATE_EVENT, &[], 660), 660);
+    }
+}
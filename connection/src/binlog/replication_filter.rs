@@ -0,0 +1,588 @@
+use std::collections::HashSet;
+use regex::Regex;
+use binlog::alias::mysql::gtid::gtid::Gtid;
+use binlog::alias::mysql::gtid::gtid_set::GtidSet;
+use binlog::alias::mysql::gtid::uuid::Uuid;
+use binlog::b_type::LogEventType;
+use binlog::events::binlog_event::BinlogEvent;
+use common::config::ReplicateConfig;
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+/// 复制过滤器, 语义与 MySQL 的 replicate-do-db / replicate-ignore-db / replicate-*-table 保持一致。
+///
+/// 评估顺序参考 <a href="https://dev.mysql.com/doc/refman/8.0/en/replication-rules.html">Replication Rules</a>:
+///
+///   1. 库级别: 配置了 do_db 时, 不在 do_db 中的库直接忽略; 否则配置了 ignore_db 时, 命中 ignore_db 的库直接忽略。
+///   2. 命令行的排除规则(exclude_wild_table / exclude_regex_table): 命中即忽略, 优先于全部 do 规则。
+///   3. 表级别: do_table -> ignore_table -> wild_do_table -> wild_ignore_table -> regex_do_table -> regex_ignore_table
+///      依次匹配, 命中即返回。 均未命中时, 若配置了任一 do 规则则忽略, 否则执行。
+///
+/// 另外支持按 GTID 过滤事务: GTID_LOG_EVENT 不在 do_gtids 中或在 ignore_gtids 中时, 跳过直到下一个 GTID 事件之前的全部事件。
+#[derive(Debug, Clone, Default)]
+pub struct ReplicationFilter {
+    do_db: HashSet<String>,
+    ignore_db: HashSet<String>,
+
+    /// `db.table`
+    do_table: HashSet<String>,
+    ignore_table: HashSet<String>,
+
+    /// `db%.table_%`
+    wild_do_table: Vec<String>,
+    wild_ignore_table: Vec<String>,
+
+    /// 匹配 `db.table` 的正则
+    regex_do_table: Vec<Regex>,
+    regex_ignore_table: Vec<Regex>,
+
+    /// 命令行的排除规则, 先于其他表级别规则评估
+    exclude_wild_table: Vec<String>,
+    exclude_regex_table: Vec<Regex>,
+
+    do_gtids: Option<GtidSet>,
+    ignore_gtids: Option<GtidSet>,
+
+    /// 当前事务被 GTID 规则过滤
+    skip_trx: bool,
+
+    /// 被过滤的 TableMapEvent 的 table_id, 对应的行事件一并过滤
+    ignored_table_ids: HashSet<u64>,
+}
+
+impl ReplicationFilter {
+    pub fn new(config: &ReplicateConfig) -> CResult<Self> {
+        let do_table = ReplicationFilter::table_rules(&config.replicate_do_table, "replicate_do_table")?;
+        let ignore_table = ReplicationFilter::table_rules(&config.replicate_ignore_table, "replicate_ignore_table")?;
+        let wild_do_table = ReplicationFilter::table_rules(&config.replicate_wild_do_table, "replicate_wild_do_table")?;
+        let wild_ignore_table = ReplicationFilter::table_rules(&config.replicate_wild_ignore_table, "replicate_wild_ignore_table")?;
+        let regex_do_table = ReplicationFilter::regex_rules(&config.replicate_regex_do_table, "replicate_regex_do_table")?;
+        let regex_ignore_table = ReplicationFilter::regex_rules(&config.replicate_regex_ignore_table, "replicate_regex_ignore_table")?;
+        let exclude_wild_table = ReplicationFilter::table_rules(&config.exclude_wild_table, "exclude_wild_table")?;
+        let exclude_regex_table = ReplicationFilter::regex_rules(&config.exclude_regex_table, "exclude_regex_table")?;
+
+        Ok(ReplicationFilter {
+            do_db: config.replicate_do_db.clone().unwrap_or_default().into_iter().collect(),
+            ignore_db: config.replicate_ignore_db.clone().unwrap_or_default().into_iter().collect(),
+            do_table: do_table.into_iter().collect(),
+            ignore_table: ignore_table.into_iter().collect(),
+            wild_do_table,
+            wild_ignore_table,
+            regex_do_table,
+            regex_ignore_table,
+            exclude_wild_table,
+            exclude_regex_table,
+            do_gtids: ReplicationFilter::gtid_rules(&config.replicate_do_gtids, "replicate_do_gtids")?,
+            ignore_gtids: ReplicationFilter::gtid_rules(&config.replicate_ignore_gtids, "replicate_ignore_gtids")?,
+            skip_trx: false,
+            ignored_table_ids: HashSet::new(),
+        })
+    }
+
+    fn regex_rules(rules: &Option<Vec<String>>, name: &str) -> CResult<Vec<Regex>> {
+        let mut list = Vec::new();
+
+        for rule in rules.clone().unwrap_or_default() {
+            // 整体匹配 `db.table`
+            let regex = Regex::new(&format!("^(?:{})$", rule)).map_err(|e| {
+                ReError::ConfigFileParseErr(format!("{} 正则表达式错误: {}, {}", name, rule, e))
+            })?;
+            list.push(regex);
+        }
+
+        Ok(list)
+    }
+
+    fn gtid_rules(rules: &Option<String>, name: &str) -> CResult<Option<GtidSet>> {
+        match rules {
+            Some(gtids) if !gtids.trim().is_empty() => {
+                let set = GtidSet::parse(gtids.clone()).map_err(|e| {
+                    ReError::ConfigFileParseErr(format!("{} 格式错误: {}, {:?}", name, gtids, e))
+                })?;
+                Ok(Some(set))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn table_rules(rules: &Option<Vec<String>>, name: &str) -> CResult<Vec<String>> {
+        let rules = rules.clone().unwrap_or_default();
+
+        for rule in &rules {
+            match rule.split_once('.') {
+                Some((db, table)) if !db.is_empty() && !table.is_empty() => {},
+                _ => {
+                    return Err(ReError::ConfigFileParseErr(
+                        format!("{} 格式错误, 应为 db_name.tbl_name: {}", name, rule)));
+                }
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// 未配置任何规则
+    pub fn is_empty(&self) -> bool {
+        self.do_db.is_empty() && self.ignore_db.is_empty() && !self.has_table_rules() && !self.has_gtid_rules()
+    }
+
+    fn has_table_rules(&self) -> bool {
+        !self.do_table.is_empty() || !self.ignore_table.is_empty()
+            || !self.wild_do_table.is_empty() || !self.wild_ignore_table.is_empty()
+            || !self.regex_do_table.is_empty() || !self.regex_ignore_table.is_empty()
+            || !self.exclude_wild_table.is_empty() || !self.exclude_regex_table.is_empty()
+    }
+
+    fn has_gtid_rules(&self) -> bool {
+        self.do_gtids.is_some() || self.ignore_gtids.is_some()
+    }
+
+    /// GTID 级别规则
+    pub fn gtid_ok(&self, gtid: &Gtid) -> bool {
+        if let Some(do_gtids) = &self.do_gtids {
+            if !do_gtids.contains_gtid(gtid) {
+                return false;
+            }
+        }
+
+        match &self.ignore_gtids {
+            Some(ignore_gtids) => !ignore_gtids.contains_gtid(gtid),
+            None => true,
+        }
+    }
+
+    /// 库级别规则
+    pub fn db_ok(&self, db: &str) -> bool {
+        if !self.do_db.is_empty() {
+            return self.do_db.contains(db);
+        }
+
+        if !self.ignore_db.is_empty() {
+            return !self.ignore_db.contains(db);
+        }
+
+        true
+    }
+
+    /// 行事件的完整评估: 库级别规则通过后, 再评估表级别规则
+    pub fn table_ok(&self, db: &str, table: &str) -> bool {
+        if !self.db_ok(db) {
+            return false;
+        }
+
+        if !self.has_table_rules() {
+            return true;
+        }
+
+        let full_name = format!("{}.{}", db, table);
+        if self.exclude_wild_table.iter().any(|p| wild_match(p.as_bytes(), full_name.as_bytes()))
+            || self.exclude_regex_table.iter().any(|r| r.is_match(&full_name)) {
+            return false;
+        }
+        if self.do_table.contains(&full_name) {
+            return true;
+        }
+        if self.ignore_table.contains(&full_name) {
+            return false;
+        }
+        if self.wild_do_table.iter().any(|p| wild_match(p.as_bytes(), full_name.as_bytes())) {
+            return true;
+        }
+        if self.wild_ignore_table.iter().any(|p| wild_match(p.as_bytes(), full_name.as_bytes())) {
+            return false;
+        }
+        if self.regex_do_table.iter().any(|r| r.is_match(&full_name)) {
+            return true;
+        }
+        if self.regex_ignore_table.iter().any(|r| r.is_match(&full_name)) {
+            return false;
+        }
+
+        self.do_table.is_empty() && self.wild_do_table.is_empty() && self.regex_do_table.is_empty()
+    }
+
+    /// 在解码之前判断事件是否需要复制。 只读取 post-header 中的库名、表名和 table_id, 不做完整解析。
+    ///
+    /// * QUERY_EVENT: 按默认库评估库级别规则, BEGIN / COMMIT 等事务控制语句始终保留
+    /// * TABLE_MAP_EVENT: 按库名、表名完整评估, 被过滤的 table_id 会被记录
+    /// * ROWS_EVENT: table_id 在被过滤集合中时过滤
+    /// * GTID_LOG_EVENT: 按 GTID 规则决定是否跳过整个事务
+    ///
+    /// # Arguments
+    ///
+    /// * `event_type`: 事件类型
+    /// * `payload`: 不包含 event header 的事件内容
+    ///
+    /// returns: bool, false 表示该事件被过滤
+    pub fn accept(&mut self, event_type: u8, payload: &[u8]) -> bool {
+        let event_type = LogEventType::from(event_type);
+        match event_type {
+            LogEventType::GTID_LOG_EVENT => {
+                self.skip_trx = match read_gtid(payload) {
+                    Some(gtid) => !self.gtid_ok(&gtid),
+                    None => false,
+                };
+                return !self.skip_trx;
+            },
+            LogEventType::ANONYMOUS_GTID_LOG_EVENT => {
+                self.skip_trx = self.do_gtids.is_some();
+                return !self.skip_trx;
+            },
+            LogEventType::ROTATE_EVENT | LogEventType::FORMAT_DESCRIPTION_EVENT | LogEventType::STOP_EVENT |
+            LogEventType::PREVIOUS_GTIDS_LOG_EVENT | LogEventType::HEARTBEAT_LOG_EVENT => return true,
+            _ => {},
+        }
+        if self.skip_trx {
+            return false;
+        }
+
+        match event_type {
+            LogEventType::QUERY_EVENT => {
+                match parse_query_schema(payload) {
+                    Some((schema, query)) => {
+                        is_transaction_control(query) || schema.is_empty() || self.db_ok(&schema)
+                    },
+                    None => true,
+                }
+            },
+            LogEventType::TABLE_MAP_EVENT => {
+                match parse_table_map_name(payload) {
+                    Some((table_id, db, table)) => {
+                        let ok = self.table_ok(&db, &table);
+                        if ok {
+                            self.ignored_table_ids.remove(&table_id);
+                        } else {
+                            self.ignored_table_ids.insert(table_id);
+                        }
+                        ok
+                    },
+                    None => true,
+                }
+            },
+            LogEventType::WRITE_ROWS_EVENT_V1 | LogEventType::UPDATE_ROWS_EVENT_V1 | LogEventType::DELETE_ROWS_EVENT_V1 |
+            LogEventType::WRITE_ROWS_EVENT | LogEventType::UPDATE_ROWS_EVENT | LogEventType::DELETE_ROWS_EVENT => {
+                match read_table_id(payload) {
+                    Some(table_id) => !self.ignored_table_ids.contains(&table_id),
+                    None => true,
+                }
+            },
+            _ => true,
+        }
+    }
+
+    /// 对已解码的事件执行过滤, 用于离线解析 binlog 文件等不经过 decode_event_packet 的场景。
+    /// 规则与 accept 相同
+    pub fn accept_event(&mut self, event: &BinlogEvent) -> bool {
+        match event {
+            BinlogEvent::GtidLog(e) => {
+                self.skip_trx = !self.gtid_ok(&e.gtid);
+                return !self.skip_trx;
+            },
+            BinlogEvent::AnonymousGtidLog(_) => {
+                self.skip_trx = self.do_gtids.is_some();
+                return !self.skip_trx;
+            },
+            BinlogEvent::Rotate(_) | BinlogEvent::FormatDescription(_) | BinlogEvent::Stop(_) |
+            BinlogEvent::PreviousGtidsLog(_) | BinlogEvent::Heartbeat { .. } => return true,
+            _ => {},
+        }
+        if self.skip_trx {
+            return false;
+        }
+
+        match event {
+            BinlogEvent::Query(e) => {
+                is_transaction_control(e.query.as_bytes()) || e.schema.is_empty() || self.db_ok(&e.schema)
+            },
+            BinlogEvent::TableMap(e) => {
+                let ok = self.table_ok(&e.get_database_name(), &e.get_table_name());
+                if ok {
+                    self.ignored_table_ids.remove(&e.table_id);
+                } else {
+                    self.ignored_table_ids.insert(e.table_id);
+                }
+                ok
+            },
+            BinlogEvent::WriteRows(e) => !self.ignored_table_ids.contains(&e.table_id),
+            BinlogEvent::UpdateRows(e) => !self.ignored_table_ids.contains(&e.table_id),
+            BinlogEvent::DeleteRows(e) => !self.ignored_table_ids.contains(&e.table_id),
+            _ => true,
+        }
+    }
+}
+
+/// 6 bytes table_id (MySQL 5.1.4 之后)
+fn read_table_id(payload: &[u8]) -> Option<u64> {
+    if payload.len() < 6 {
+        return None;
+    }
+
+    let mut buf = [0u8; 8];
+    buf[..6].copy_from_slice(&payload[..6]);
+    Some(u64::from_le_bytes(buf))
+}
+
+/// GTID_LOG_EVENT: flags(1) sid(16) gno(8)
+fn read_gtid(payload: &[u8]) -> Option<Gtid> {
+    let sid: [u8; 16] = payload.get(1..17)?.try_into().ok()?;
+    let gno: [u8; 8] = payload.get(17..25)?.try_into().ok()?;
+
+    Some(Gtid::new(Uuid::new(sid), u64::from_le_bytes(gno)))
+}
+
+/// TABLE_MAP_EVENT: table_id(6) flags(2) db_len(1) db 0x00 table_len(1) table 0x00 ...
+fn parse_table_map_name(payload: &[u8]) -> Option<(u64, String, String)> {
+    let table_id = read_table_id(payload)?;
+
+    let db_len = *payload.get(8)? as usize;
+    let db = payload.get(9..9 + db_len)?;
+
+    let table_offset = 9 + db_len + 1;
+    let table_len = *payload.get(table_offset)? as usize;
+    let table = payload.get(table_offset + 1..table_offset + 1 + table_len)?;
+
+    Some((table_id, String::from_utf8_lossy(db).to_string(), String::from_utf8_lossy(table).to_string()))
+}
+
+/// QUERY_EVENT: thread_id(4) exec_time(4) schema_len(1) error_code(2) status_vars_len(2) status_vars schema 0x00 query
+pub(crate) fn parse_query_schema(payload: &[u8]) -> Option<(String, &[u8])> {
+    let schema_len = *payload.get(8)? as usize;
+    let status_vars_len = u16::from_le_bytes([*payload.get(11)?, *payload.get(12)?]) as usize;
+
+    let schema_offset = 13 + status_vars_len;
+    let schema = payload.get(schema_offset..schema_offset + schema_len)?;
+    let query = payload.get(schema_offset + schema_len + 1..).unwrap_or_default();
+
+    Some((String::from_utf8_lossy(schema).to_string(), query))
+}
+
+fn is_transaction_control(query: &[u8]) -> bool {
+    ["BEGIN", "COMMIT", "ROLLBACK", "XA "].iter().any(|k| {
+        query.len() >= k.len() && query[..k.len()].eq_ignore_ascii_case(k.as_bytes())
+    })
+}
+
+/// LIKE 风格的通配符匹配。 `%` 匹配任意多个字符, `_` 匹配单个字符, `\` 转义
+fn wild_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0usize, 0usize);
+    // 最近一次 `%` 的位置, 以及当时 s 的下标, 用于回溯
+    let mut star: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'%' => {
+                    star = Some((p, i));
+                    p += 1;
+                    continue;
+                },
+                b'\\' if p + 1 < pattern.len() && pattern[p + 1] == s[i] => {
+                    p += 2;
+                    i += 1;
+                    continue;
+                },
+                b'_' => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                },
+                c if c != b'\\' && c == s[i] => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                },
+                _ => {},
+            }
+        }
+
+        match star {
+            Some((sp, si)) => {
+                p = sp + 1;
+                i = si + 1;
+                star = Some((sp, si + 1));
+            },
+            None => return false,
+        }
+    }
+
+    while p < pattern.len() && pattern[p] == b'%' {
+        p += 1;
+    }
+
+    p == pattern.len()
+}
+
+#[cfg(test)]
+mod test {
+    use common::config::ReplicateConfig;
+    use binlog::b_type::LogEventType;
+    use crate::binlog::replication_filter::{ReplicationFilter, wild_match};
+
+    fn rules(v: &[&str]) -> Option<Vec<String>> {
+        Some(v.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_wild_match() {
+        assert!(wild_match(b"db1.%", b"db1.t1"));
+        assert!(wild_match(b"db_.t%", b"db2.test"));
+        assert!(wild_match(b"%", b""));
+        assert!(!wild_match(b"db1.t_", b"db1.t10"));
+        assert!(wild_match(b"db1.t\\_1", b"db1.t_1"));
+        assert!(!wild_match(b"db1.t\\_1", b"db1.tx1"));
+    }
+
+    #[test]
+    fn test_db_rules() {
+        let mut c = ReplicateConfig::default();
+        c.replicate_do_db = rules(&["db1"]);
+        c.replicate_ignore_db = rules(&["db1", "db2"]);
+        let f = ReplicationFilter::new(&c).unwrap();
+
+        // do_db 存在时 ignore_db 不生效
+        assert!(f.db_ok("db1"));
+        assert!(!f.db_ok("db2"));
+        assert!(!f.db_ok("db3"));
+
+        let mut c = ReplicateConfig::default();
+        c.replicate_ignore_db = rules(&["mysql"]);
+        let f = ReplicationFilter::new(&c).unwrap();
+        assert!(!f.table_ok("mysql", "user"));
+        assert!(f.table_ok("db1", "t1"));
+    }
+
+    #[test]
+    fn test_table_rules() {
+        let mut c = ReplicateConfig::default();
+        c.replicate_do_table = rules(&["db1.t1"]);
+        c.replicate_ignore_table = rules(&["db1.order_tmp"]);
+        c.replicate_wild_do_table = rules(&["db1.order_%"]);
+        let f = ReplicationFilter::new(&c).unwrap();
+
+        assert!(!f.is_empty());
+        assert!(f.table_ok("db1", "t1"));
+        assert!(!f.table_ok("db1", "order_tmp"));
+        assert!(f.table_ok("db1", "order_1"));
+        // 配置了 do 规则, 均未命中时忽略
+        assert!(!f.table_ok("db1", "t2"));
+
+        let mut c = ReplicateConfig::default();
+        c.replicate_wild_ignore_table = rules(&["db1.tmp%"]);
+        let f = ReplicationFilter::new(&c).unwrap();
+        assert!(!f.table_ok("db1", "tmp_1"));
+        assert!(f.table_ok("db1", "t2"));
+    }
+
+    #[test]
+    fn test_regex_rules() {
+        let mut c = ReplicateConfig::default();
+        c.replicate_regex_do_table = rules(&[r"db\d+\.orders?"]);
+        let f = ReplicationFilter::new(&c).unwrap();
+
+        assert!(f.table_ok("db1", "order"));
+        assert!(f.table_ok("db2", "orders"));
+        // 整体匹配
+        assert!(!f.table_ok("db2", "orders_1"));
+        assert!(!f.table_ok("dbx", "order"));
+    }
+
+    #[test]
+    fn test_exclude_rules() {
+        let mut c = ReplicateConfig::default();
+        c.replicate_do_table = rules(&["db1.tmp"]);
+        c.replicate_wild_do_table = rules(&["db1.%"]);
+        c.exclude_wild_table = rules(&["db1.tmp"]);
+        c.exclude_regex_table = rules(&[r"db1\.bak\d+"]);
+        let f = ReplicationFilter::new(&c).unwrap();
+
+        // 排除规则先于 do 规则
+        assert!(!f.table_ok("db1", "tmp"));
+        assert!(!f.table_ok("db1", "bak1"));
+        assert!(f.table_ok("db1", "t1"));
+        assert!(!f.table_ok("db2", "t1"));
+
+        // 仅有排除规则时, 其余表均同步
+        let mut c = ReplicateConfig::default();
+        c.exclude_wild_table = rules(&["db1.tmp%"]);
+        let f = ReplicationFilter::new(&c).unwrap();
+        assert!(!f.is_empty());
+        assert!(!f.table_ok("db1", "tmp_1"));
+        assert!(f.table_ok("db2", "tmp_1"));
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        let mut c = ReplicateConfig::default();
+        c.replicate_do_table = rules(&["t1"]);
+        assert!(ReplicationFilter::new(&c).is_err());
+
+        let mut c = ReplicateConfig::default();
+        c.replicate_regex_do_table = rules(&["db1.(t1"]);
+        assert!(ReplicationFilter::new(&c).is_err());
+    }
+
+    #[test]
+    fn test_gtid_rules() {
+        let mut c = ReplicateConfig::default();
+        c.replicate_ignore_gtids = Some(String::from("24bc7850-2c16-11e6-a073-0242ac110001:2-3"));
+        let mut f = ReplicationFilter::new(&c).unwrap();
+        assert!(!f.is_empty());
+
+        let gtid = |gno: u64| {
+            let mut payload = vec![1];
+            payload.extend_from_slice(&hex::decode("24bc78502c1611e6a0730242ac110001").unwrap());
+            payload.extend_from_slice(&gno.to_le_bytes());
+            payload
+        };
+        let rows = vec![100, 0, 0, 0, 0, 0, 1, 0, 1];
+
+        assert!(f.accept(LogEventType::GTID_LOG_EVENT as u8, &gtid(1)));
+        assert!(f.accept(LogEventType::WRITE_ROWS_EVENT as u8, &rows));
+        assert!(!f.accept(LogEventType::GTID_LOG_EVENT as u8, &gtid(2)));
+        assert!(!f.accept(LogEventType::WRITE_ROWS_EVENT as u8, &rows));
+        assert!(!f.accept(LogEventType::XID_EVENT as u8, &[0u8; 8]));
+        // 事务被跳过时控制事件依然保留
+        assert!(f.accept(LogEventType::ROTATE_EVENT as u8, &[0u8; 8]));
+        assert!(f.accept(LogEventType::GTID_LOG_EVENT as u8, &gtid(4)));
+        assert!(f.accept(LogEventType::WRITE_ROWS_EVENT as u8, &rows));
+    }
+
+    #[test]
+    fn test_accept() {
+        let mut c = ReplicateConfig::default();
+        c.replicate_wild_do_table = rules(&["db1.%"]);
+        let mut f = ReplicationFilter::new(&c).unwrap();
+
+        let table_map = |table_id: u8, db: &str, table: &str| {
+            let mut payload = vec![table_id, 0, 0, 0, 0, 0, 1, 0];
+            payload.push(db.len() as u8);
+            payload.extend_from_slice(db.as_bytes());
+            payload.push(0);
+            payload.push(table.len() as u8);
+            payload.extend_from_slice(table.as_bytes());
+            payload.push(0);
+            payload
+        };
+        let rows = |table_id: u8| vec![table_id, 0, 0, 0, 0, 0, 1, 0, 1];
+
+        assert!(f.accept(LogEventType::TABLE_MAP_EVENT as u8, &table_map(100, "db1", "t1")));
+        assert!(f.accept(LogEventType::WRITE_ROWS_EVENT as u8, &rows(100)));
+        assert!(!f.accept(LogEventType::TABLE_MAP_EVENT as u8, &table_map(101, "db2", "t1")));
+        assert!(!f.accept(LogEventType::UPDATE_ROWS_EVENT as u8, &rows(101)));
+
+        let query = |schema: &str, sql: &str| {
+            let mut payload = vec![1, 0, 0, 0, 0, 0, 0, 0, schema.len() as u8, 0, 0, 0, 0];
+            payload.extend_from_slice(schema.as_bytes());
+            payload.push(0);
+            payload.extend_from_slice(sql.as_bytes());
+            payload
+        };
+        assert!(f.accept(LogEventType::QUERY_EVENT as u8, &query("db2", "BEGIN")));
+
+        let mut c = ReplicateConfig::default();
+        c.replicate_do_db = rules(&["db1"]);
+        let mut f = ReplicationFilter::new(&c).unwrap();
+        assert!(f.accept(LogEventType::QUERY_EVENT as u8, &query("db1", "create table t1(id int)")));
+        assert!(!f.accept(LogEventType::QUERY_EVENT as u8, &query("db2", "create table t1(id int)")));
+    }
+}
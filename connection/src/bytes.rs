@@ -1,6 +1,6 @@
 use std::io;
 use std::io::{Cursor, Write};
-use byteorder::WriteBytesExt;
+use byteorder::{LittleEndian, WriteBytesExt};
 use sha1::Sha1;
 use sha2::{Digest, Sha256};
 use crate::declar::auth_plugin_names::AuthPlugin;
@@ -15,6 +15,36 @@ pub fn write_null_term_string(
     Ok(())
 }
 
+/// 写入 length-encoded integer
+pub fn write_len_enc_num(
+    cursor: &mut Cursor<&mut Vec<u8>>,
+    num: u64) -> Result<(), io::Error> {
+    if num < 0xFB {
+        cursor.write_u8(num as u8)?;
+    } else if num <= 0xFFFF {
+        cursor.write_u8(0xFC)?;
+        cursor.write_u16::<LittleEndian>(num as u16)?;
+    } else if num <= 0xFF_FFFF {
+        cursor.write_u8(0xFD)?;
+        cursor.write_u24::<LittleEndian>(num as u32)?;
+    } else {
+        cursor.write_u8(0xFE)?;
+        cursor.write_u64::<LittleEndian>(num)?;
+    }
+
+    Ok(())
+}
+
+/// 写入 length-encoded string
+pub fn write_len_enc_string(
+    cursor: &mut Cursor<&mut Vec<u8>>,
+    str: &String) -> Result<(), io::Error> {
+    write_len_enc_num(cursor, str.len() as u64)?;
+    cursor.write(str.as_bytes())?;
+
+    Ok(())
+}
+
 pub fn encrypt_password(password: &String, scramble: &String, auth_plugin: &AuthPlugin) -> Vec<u8> {
     match auth_plugin {
         AuthPlugin::MySqlNativePassword => {
@@ -1,7 +1,7 @@
 use std::io;
 use std::io::{Cursor, Write};
 use byteorder::{LittleEndian, WriteBytesExt};
-use crate::bytes::{encrypt_password, write_null_term_string};
+use crate::bytes::{encrypt_password, write_len_enc_num, write_len_enc_string, write_null_term_string};
 use crate::conn::connection_options::ConnectionOptions;
 use crate::declar::auth_plugin_names::AuthPlugin;
 use crate::declar::capability_flags;
@@ -17,6 +17,8 @@ pub struct AuthenticateCommand {
     pub scramble: String,
     pub auth_plugin: AuthPlugin,
     pub auth_plugin_name: String,
+    /// 服务端支持 CLIENT_CONNECT_ATTRS 时发送的连接属性
+    pub connect_attrs: Option<Vec<(String, String)>>,
 }
 
 impl AuthenticateCommand {
@@ -34,6 +36,16 @@ impl AuthenticateCommand {
         if let Some(_x) = &options.database {
             client_capabilities |= capability_flags::CLIENT_CONNECT_WITH_DB;
         }
+        if options.local_infile_handler.is_some() {
+            client_capabilities |= capability_flags::CLIENT_LOCAL_FILES;
+        }
+
+        let connect_attrs = if handshake.server_capabilities & capability_flags::CLIENT_CONNECT_ATTRS != 0 {
+            client_capabilities |= capability_flags::CLIENT_CONNECT_ATTRS;
+            Some(options.connect_attributes())
+        } else {
+            None
+        };
 
         let client_capabilities = client_capabilities as u32;
 
@@ -47,6 +59,7 @@ impl AuthenticateCommand {
             scramble: handshake.scramble.clone(),
             auth_plugin_name: handshake.auth_plugin_name.clone(),
             auth_plugin: auth_plugin,
+            connect_attrs,
         }
     }
 
@@ -75,6 +88,17 @@ impl AuthenticateCommand {
         }
 
         write_null_term_string(&mut cursor, &self.auth_plugin_name)?;
+
+        if let Some(connect_attrs) = &self.connect_attrs {
+            let mut attrs = Vec::new();
+            let mut attrs_cursor = Cursor::new(&mut attrs);
+            for (key, value) in connect_attrs {
+                write_len_enc_string(&mut attrs_cursor, key)?;
+                write_len_enc_string(&mut attrs_cursor, value)?;
+            }
+            write_len_enc_num(&mut cursor, attrs.len() as u64)?;
+            cursor.write(&attrs)?;
+        }
         Ok(vec)
     }
 }
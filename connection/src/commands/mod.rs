@@ -5,4 +5,5 @@ pub mod command;
 pub mod register_slave_command;
 pub mod query_command;
 pub mod dump_binlog_command;
-pub mod dump_binlog_gtid_command;
\ No newline at end of file
+pub mod dump_binlog_gtid_command;
+pub mod ping_command;
@@ -0,0 +1,21 @@
+use std::io;
+use std::io::Cursor;
+use byteorder::WriteBytesExt;
+use crate::commands::command::CommandType;
+
+pub struct PingCommand {}
+
+impl PingCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, io::Error> {
+        let mut vec = Vec::new();
+        let mut cursor = Cursor::new(&mut vec);
+
+        cursor.write_u8(CommandType::Ping as u8)?;
+
+        Ok(vec)
+    }
+}
@@ -0,0 +1,391 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use tracing::instrument;
+
+use binlog::events::checksum_type::ChecksumType;
+use binlog::events::log_context::LogContext;
+use common::binlog::row::row_string::RowString;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+use common::schema::mysql_metadata::MetadataQuery;
+
+use crate::binlog::async_binlog_events::AsyncBinlogEvents;
+use crate::binlog::starting_strategy::StartingStrategy;
+use crate::commands::auth_plugin_switch_command::AuthPluginSwitchCommand;
+use crate::commands::authenticate_command::AuthenticateCommand;
+use crate::commands::query_command::QueryCommand;
+use crate::commands::ssl_request_command::SslRequestCommand;
+use crate::conn::async_packet_channel::AsyncPacketChannel;
+use crate::conn::binlog_connection::BinlogConnection;
+use crate::conn::connection::Connection;
+use crate::conn::connection_options::ConnectionOptions;
+use crate::conn::local_infile::{send_local_infile_async, LocalInfileHandlerRef, LOCAL_INFILE_REQUEST};
+use crate::conn::ssl_mode::SslMode;
+use crate::declar::auth_plugin_names;
+use crate::declar::capability_flags;
+use crate::declar::capability_flags::CapabilityFlags;
+use crate::packet::auth_switch_packet::AuthPluginSwitchPacket;
+use crate::packet::check_error_packet;
+use crate::packet::error_packet::ErrorPacket;
+use crate::packet::ok_packet::OkPacket;
+use crate::packet::handshake_packet::HandshakePacket;
+use crate::packet::response_type::ResponseType;
+use crate::packet::result_set_row_packet::ResultSetRowPacket;
+use crate::{NULL_TERMINATOR, UTF8_MB4_GENERAL_CI};
+
+/// Connection 的异步版本。 握手、认证与命令的编解码逻辑与 Connection 共用,
+/// 便于 tokio 中的 CLI / web 驱动复制而无需为每个数据源占用一个阻塞线程。
+#[derive(Debug)]
+pub struct AsyncConnection {
+    pub options: ConnectionOptions,
+
+    channel: Option<AsyncPacketChannel>,
+
+    capability_flags: CapabilityFlags,
+    connection_id: u32,
+    server_version: String,
+}
+
+/// 与 Connection 一致, ConnectionOptions 中的 Arc<RefCell<_>> 仅在单个连接内部使用
+unsafe impl Send for AsyncConnection {}
+
+#[async_trait::async_trait]
+impl MetadataQuery for AsyncConnection {
+    async fn query(&mut self, sql: String) -> CResult<Vec<RowString>> {
+        AsyncConnection::query(self, sql).await
+    }
+}
+
+impl AsyncConnection {
+    pub fn new(options: ConnectionOptions) -> Self {
+        AsyncConnection {
+            options,
+            channel: None,
+            capability_flags: CapabilityFlags::empty(),
+            connection_id: 0,
+            server_version: String::default(),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.channel.is_some()
+    }
+
+    pub fn get_connection_id(&self) -> u32 {
+        self.connection_id
+    }
+
+    pub fn get_server_version(&self) -> &str {
+        &self.server_version
+    }
+
+    /// 建立连接并完成握手
+    #[instrument]
+    pub async fn connect(&mut self) -> CResult<()> {
+        if self.channel.is_none() {
+            let channel = AsyncPacketChannel::new(&self.options).await?;
+            let channel = self.do_handshake(channel).await?;
+            self.channel = Some(channel);
+        }
+
+        Ok(())
+    }
+
+    #[instrument]
+    pub async fn query(&mut self, sql: String) -> CResult<Vec<RowString>> {
+        let command = QueryCommand::new(sql);
+        let local_infile_handler = self.options.local_infile_handler.clone();
+        let channel = self.channel()?;
+        channel.write_packet(&command.serialize()?, 0).await?;
+
+        let result_set = AsyncConnection::read_result_set(channel, local_infile_handler).await?;
+
+        let mut result = Vec::<RowString>::with_capacity(result_set.len());
+        for packet in result_set {
+            result.push(RowString::new_row(packet.cells));
+        }
+
+        Ok(result)
+    }
+
+    /// 执行不返回结果集的语句, 如 SET
+    pub async fn execute(&mut self, sql: String) -> CResult<()> {
+        let command = QueryCommand::new(sql);
+        let channel = self.channel()?;
+        channel.write_packet(&command.serialize()?, 0).await?;
+
+        let (packet, _) = channel.read_packet().await?;
+        check_error_packet(&packet, "Execute error.")
+    }
+
+    /// 执行不返回结果集的语句, 返回 OK 包中的影响行数等信息;
+    /// 服务端返回的错误作为 Ok(Err(ErrorPacket)) 交给调用方按错误码处理, 连接错误仍为 Err
+    pub async fn execute_update(&mut self, sql: String) -> CResult<Result<OkPacket, ErrorPacket>> {
+        let command = QueryCommand::new(sql);
+        let channel = self.channel()?;
+        channel.write_packet(&command.serialize()?, 0).await?;
+
+        let (packet, _) = channel.read_packet().await?;
+        if packet[0] == ResponseType::ERROR {
+            return Ok(Err(ErrorPacket::parse(&packet[1..])?));
+        }
+        Ok(Ok(OkPacket::parse(&packet)?))
+    }
+
+    /// 注册为 slave 并请求 dump, 返回异步的 binlog 事件流
+    ///
+    /// # Arguments
+    ///
+    /// * `payload_buffer_size`:  读取binlog 的缓冲区大小
+    #[instrument]
+    pub async fn binlog(mut self, payload_buffer_size: usize) -> CResult<AsyncBinlogEvents> {
+        self.connect().await?;
+
+        self.adjust_starting_position().await?;
+
+        let nanoseconds = self.options.heartbeat_interval.as_millis() * 1000 * 1000;
+        self.execute(format!("set @master_heartbeat_period={}", nanoseconds)).await?;
+
+        self.execute("SET @master_binlog_checksum= @@global.binlog_checksum".to_string()).await?;
+        let rows = self.query("SELECT @master_binlog_checksum".to_string()).await?;
+        let checksum = match rows.get(0).and_then(|r| r.as_slice().get(0).cloned()) {
+            Some(name) => ChecksumType::from_name(&name.unwrap_or_default())?,
+            None => ChecksumType::None,
+        };
+
+        let server_id = if self.options.blocking {
+            self.options.server_id
+        } else {
+            0
+        };
+        let command = BinlogConnection::dump_command(&self.options, server_id)?;
+        self.channel()?.write_packet(&command, 0).await?;
+
+        let log_context = Rc::new(RefCell::new(LogContext::default()));
+        let filter = self.options.replication_filter.clone();
+        let channel = self.channel.take().unwrap();
+
+        Ok(AsyncBinlogEvents::new(channel, log_context, checksum, payload_buffer_size).with_filter(filter))
+    }
+
+    pub async fn close(&mut self) -> CResult<()> {
+        if let Some(mut channel) = self.channel.take() {
+            channel.shutdown().await?;
+        }
+
+        Ok(())
+    }
+
+    fn channel(&mut self) -> CResult<&mut AsyncPacketChannel> {
+        match self.channel.as_mut() {
+            Some(c) => Ok(c),
+            None => Err(ReError::ConnectionError(String::from("channel not found"))),
+        }
+    }
+
+    /// 同 Configure::adjust_starting_position, FromEnd 时读取 master 当前位置
+    async fn adjust_starting_position(&mut self) -> CResult<()> {
+        let from_end = match self.options.binlog.as_ref() {
+            Some(b) => {
+                // Ignore if position was read before in case of reconnect.
+                b.borrow().starting_strategy == StartingStrategy::FromEnd && b.borrow().filename.is_empty()
+            }
+            None => false,
+        };
+        if !from_end {
+            return Ok(());
+        }
+
+        let rows = self.query("show master status".to_string()).await?;
+        if rows.len() != 1 {
+            return Err(ReError::String(
+                "Could not read master binlog position.".to_string(),
+            ));
+        }
+
+        let cells = rows[0].as_slice();
+        let filename = cells.get(0).cloned().flatten().ok_or(ReError::MysqlQueryErr(String::from(
+            "Can not get binlog filename from 'show master status'",
+        )))?;
+        let pos = cells.get(1).cloned().flatten().ok_or(ReError::MysqlQueryErr(String::from(
+            "Can not get binlog position from 'show master status'",
+        )))?;
+        self.options.update_binlog_position(filename, pos.parse()?);
+
+        Ok(())
+    }
+
+    async fn read_result_set(channel: &mut AsyncPacketChannel, local_infile_handler: Option<LocalInfileHandlerRef>)
+        -> CResult<Vec<ResultSetRowPacket>> {
+        let (packet, seq_num) = channel.read_packet().await?;
+        check_error_packet(&packet, "Reading result set error.")?;
+        if packet[0] == LOCAL_INFILE_REQUEST {
+            send_local_infile_async(channel, local_infile_handler, &packet, seq_num).await?;
+            return Ok(Vec::new());
+        }
+
+        loop {
+            // Skip through metadata
+            let (packet, _) = channel.read_packet().await?;
+            if packet[0] == ResponseType::END_OF_FILE {
+                break;
+            }
+        }
+
+        let mut result_set = Vec::new();
+        loop {
+            let (packet, _) = channel.read_packet().await?;
+            check_error_packet(&packet, "Query result set error.")?;
+            if packet[0] == ResponseType::END_OF_FILE {
+                break;
+            }
+            result_set.push(ResultSetRowPacket::parse(&packet)?);
+        }
+        Ok(result_set)
+    }
+
+    /// 进行mysql握手, ssl的情况channel会发生变更
+    async fn do_handshake(&mut self, mut channel: AsyncPacketChannel) -> CResult<AsyncPacketChannel> {
+        // 获取server发送的第一个握手包
+        let (packet, seq_num) = channel.read_packet().await?;
+        check_error_packet(&packet, "Initial handshake error.")?;
+        let handshake = HandshakePacket::parse(&packet)?;
+
+        let mut seq_num = seq_num;
+        // 协议版本号0x0A/10
+        if handshake.protocol_version != 10u8 {
+            return Err(ReError::ConnectionError(format!(
+                "Unsupported protocol version. {}",
+                handshake.protocol_version
+            )));
+        }
+
+        let capability_flags = CapabilityFlags::new(handshake.server_capabilities);
+        // 必须是CLIENT_PROTOCOL_41
+        if !capability_flags.contains(capability_flags::CLIENT_PROTOCOL_41) {
+            return Err(ReError::ConnectionError(format!(
+                "Protocol41 not set. {}",
+                handshake.protocol_version
+            )));
+        }
+
+        // 记录握手包信息
+        self.capability_flags = CapabilityFlags::new(handshake.server_capabilities & Connection::client_flags());
+        self.connection_id = handshake.connection_id;
+        self.server_version = handshake.server_version.clone();
+
+        // 如果是ssl连接，发送ssl包
+        if self.options.ssl_mode != SslMode::Disabled {
+            let ssl_available = capability_flags.contains(capability_flags::CLIENT_SSL);
+            if !ssl_available && self.options.ssl_mode as u8 >= SslMode::Require as u8 {
+                return Err(ReError::String(
+                    "The server doesn't support SSL encryption".to_string(),
+                ));
+            }
+            if ssl_available {
+                let ssl_command = SslRequestCommand::new(UTF8_MB4_GENERAL_CI);
+                seq_num += 1;
+                channel.write_packet(&ssl_command.serialize()?, seq_num).await?;
+                channel = channel.upgrade_to_ssl(&self.options).await?;
+            }
+        }
+
+        // 发送握手结果并完成认证
+        let auth_plugin = Connection::get_auth_plugin(&handshake.auth_plugin_name)?;
+        let auth_command =
+            AuthenticateCommand::new(&self.options, &handshake, auth_plugin, UTF8_MB4_GENERAL_CI);
+        seq_num += 1;
+        channel.write_packet(&auth_command.serialize()?, seq_num).await?;
+
+        let (packet, seq_num) = channel.read_packet().await?;
+        check_error_packet(&packet, "Authentication error.")?;
+        match packet[0] {
+            ResponseType::OK => {},
+            ResponseType::AUTH_PLUGIN_SWITCH => {
+                let switch_packet = AuthPluginSwitchPacket::parse(&packet[1..])?;
+                let password = self.options.password.clone();
+                AsyncConnection::handle_auth_plugin_switch(&mut channel, switch_packet, &password, seq_num + 1).await?;
+            }
+            _ => {
+                let password = self.options.password.clone();
+                AsyncConnection::authenticate_sha_256(&mut channel, &packet, &handshake.scramble, &password, seq_num + 1).await?;
+            }
+        }
+
+        Ok(channel)
+    }
+
+    async fn handle_auth_plugin_switch(channel: &mut AsyncPacketChannel, switch_packet: AuthPluginSwitchPacket,
+                                       password: &String, seq_num: u8) -> CResult<()> {
+        let auth_plugin = Connection::get_auth_plugin(&switch_packet.auth_plugin_name)?;
+        let auth_switch_command = AuthPluginSwitchCommand::new(
+            password,
+            &switch_packet.auth_plugin_data,
+            &switch_packet.auth_plugin_name,
+            auth_plugin,
+        );
+        channel.write_packet(&auth_switch_command.serialize()?, seq_num).await?;
+        let (packet, seq_num) = channel.read_packet().await?;
+        check_error_packet(&packet, "Authentication switch error.")?;
+
+        if switch_packet.auth_plugin_name == auth_plugin_names::CACHING_SHA2_PASSWORD {
+            AsyncConnection::authenticate_sha_256(channel, &packet, &switch_packet.auth_plugin_data, password, seq_num + 1).await?;
+        }
+        Ok(())
+    }
+
+    async fn authenticate_sha_256(channel: &mut AsyncPacketChannel, packet: &[u8],
+                                  scramble: &String, password: &String, seq_num: u8) -> CResult<()> {
+        // See https://mariadb.com/kb/en/caching_sha2_password-authentication-plugin/
+        // Success authentication.
+        if packet[0] == 0x01 && packet[1] == 0x03 {
+            return Ok(());
+        }
+
+        let mut password = password.as_bytes().to_vec();
+        password.push(NULL_TERMINATOR);
+
+        // Send clear password if ssl is used.
+        if channel.is_ssl() {
+            channel.write_packet(&password, seq_num).await?;
+            let (packet, _seq_num) = channel.read_packet().await?;
+            check_error_packet(&packet, "Sending clear password error.")?;
+            return Ok(());
+        }
+
+        // Request public key.
+        channel.write_packet(&[0x02], seq_num).await?;
+        let (packet, seq_num) = channel.read_packet().await?;
+        check_error_packet(&packet, "Requesting caching_sha2_password public key.")?;
+
+        let encrypted_body = Connection::encrypt_password_with_public_key(&password, scramble, &packet[1..])?;
+        channel.write_packet(&encrypted_body, seq_num + 1).await?;
+
+        let (packet, _seq_num) = channel.read_packet().await?;
+        check_error_packet(&packet, "Authentication error.")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::conn::async_connection::AsyncConnection;
+    use crate::conn::connection_options::ConnectionOptions;
+
+    #[tokio::test]
+    async fn test_conn() {
+        let mut opts = ConnectionOptions::default();
+        opts.update_auth(String::from("root"), String::from("123456"));
+
+        let mut conn = AsyncConnection::new(opts);
+        assert!(conn.connect().await.is_ok());
+
+        let query = conn
+            .query(String::from("select 1+ 1"))
+            .await
+            .expect("test_conn error");
+        let values = &query[0].as_slice();
+        assert_eq!(values[0].clone().unwrap(), "2")
+    }
+}
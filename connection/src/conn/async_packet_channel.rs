@@ -0,0 +1,154 @@
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_native_tls::TlsStream;
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::conn::connection_options::ConnectionOptions;
+use crate::conn::tls;
+use crate::{PACKET_HEADER_SIZE, TIMEOUT_LATENCY_DELTA, TIMEOUT_MESSAGE};
+
+/// PacketChannel 的异步版本, 基于 tokio
+pub struct AsyncPacketChannel {
+    stream: AsyncChannelStream,
+
+    /// 超过该时长未收到数据(包括 master heartbeat)时返回超时错误
+    read_timeout: Duration,
+}
+
+enum AsyncChannelStream {
+    Tls(TlsStream<TcpStream>),
+    Tcp(TcpStream),
+}
+
+impl AsyncPacketChannel {
+    /// 返回的 Future 不持有 options 的引用, 可以在 tokio::spawn 中使用
+    pub fn new(options: &ConnectionOptions) -> impl Future<Output = CResult<Self>> {
+        let address: String = format!("{}:{}", options.hostname, options.port.to_string());
+        let read_timeout = options.heartbeat_interval + TIMEOUT_LATENCY_DELTA;
+
+        async move {
+            let stream = TcpStream::connect(address).await?;
+
+            Ok(Self {
+                stream: AsyncChannelStream::Tcp(stream),
+                read_timeout,
+            })
+        }
+    }
+
+    pub fn is_ssl(&self) -> bool {
+        match self.stream {
+            AsyncChannelStream::Tls(_) => true,
+            _ => false,
+        }
+    }
+
+    pub async fn read_packet(&mut self) -> CResult<(Vec<u8>, u8)> {
+        match tokio::time::timeout(self.read_timeout, self.read_packet_in()).await {
+            Ok(rs) => rs,
+            Err(_) => Err(ReError::ConnectionError(TIMEOUT_MESSAGE.to_string())),
+        }
+    }
+
+    async fn read_packet_in(&mut self) -> CResult<(Vec<u8>, u8)> {
+        let mut header_buffer = [0; PACKET_HEADER_SIZE];
+        self.read_exact(&mut header_buffer).await?;
+
+        let packet_size = u32::from_le_bytes([header_buffer[0], header_buffer[1], header_buffer[2], 0]);
+        let seq_num = header_buffer[3];
+
+        let mut packet: Vec<u8> = vec![0; packet_size as usize];
+        self.read_exact(&mut packet).await?;
+
+        Ok((packet, seq_num))
+    }
+
+    pub async fn write_packet(&mut self, packet: &[u8], seq_num: u8) -> CResult<()> {
+        let packet_len = (packet.len() as u32).to_le_bytes();
+
+        let mut buf = Vec::with_capacity(PACKET_HEADER_SIZE + packet.len());
+        buf.extend_from_slice(&packet_len[0..3]);
+        buf.push(seq_num);
+        buf.extend_from_slice(packet);
+
+        match &mut self.stream {
+            AsyncChannelStream::Tcp(stream) => stream.write_all(&buf).await?,
+            AsyncChannelStream::Tls(stream) => stream.write_all(&buf).await?,
+        }
+        Ok(())
+    }
+
+    pub fn upgrade_to_ssl(self, options: &ConnectionOptions) -> impl Future<Output = CResult<Self>> {
+        let ssl_opts = options.ssl_opts.clone();
+        let hostname = options.hostname.clone();
+
+        async move {
+            let ssl_opts = match ssl_opts {
+                Some(ssl_opts) => ssl_opts,
+                None => {
+                    return Err(ReError::ConnectionError(
+                        "The ssl options is empty.".to_string(),
+                    ))
+                }
+            };
+
+            let domain = ssl_opts.tls_hostname().unwrap_or(&hostname).to_string();
+            let tls_connector = tokio_native_tls::TlsConnector::from(tls::build_connector(&ssl_opts)?);
+
+            self.upgrade(tls_connector, domain, ssl_opts.spki_pins()).await
+        }
+    }
+
+    async fn upgrade(self, tls_connector: tokio_native_tls::TlsConnector, domain: String, spki_pins: &[String]) -> CResult<Self> {
+        match self.stream {
+            AsyncChannelStream::Tcp(tcp_stream) => {
+                let secure_stream = match tls_connector.connect(&domain, tcp_stream).await {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        return Err(ReError::ConnectionError(format!(
+                            "Can not connect tls. err:{{{err}}}"
+                        )))
+                    }
+                };
+                tls::verify_spki_pins(secure_stream.get_ref(), spki_pins)?;
+
+                Ok(Self {
+                    stream: AsyncChannelStream::Tls(secure_stream),
+                    read_timeout: self.read_timeout,
+                })
+            }
+            AsyncChannelStream::Tls(_) => Ok(self),
+        }
+    }
+
+    pub async fn shutdown(&mut self) -> CResult<()> {
+        match &mut self.stream {
+            AsyncChannelStream::Tcp(stream) => stream.shutdown().await?,
+            AsyncChannelStream::Tls(stream) => stream.shutdown().await?,
+        }
+        Ok(())
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8]) -> CResult<()> {
+        match &mut self.stream {
+            AsyncChannelStream::Tcp(stream) => stream.read_exact(buf).await?,
+            AsyncChannelStream::Tls(stream) => stream.read_exact(buf).await?,
+        };
+        Ok(())
+    }
+}
+
+impl fmt::Debug for AsyncPacketChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stream {
+            AsyncChannelStream::Tcp(ref s) => write!(f, "Async tcp stream {:?}", s),
+            AsyncChannelStream::Tls(_) => write!(f, "Async tls stream"),
+        }
+    }
+}
@@ -13,6 +13,7 @@ use common::server::Server;
 use crate::binlog::binlog_events::BinlogEvents;
 use crate::binlog::binlog_events_wrapper::{BinlogEventsWrapper};
 use crate::binlog::binlog_options::{BinlogOptions, BinlogOptionsRef};
+use crate::binlog::reconnect::BinlogReconnector;
 use crate::binlog::starting_strategy::StartingStrategy;
 use crate::commands::dump_binlog_command::DumpBinlogCommand;
 use crate::commands::dump_binlog_gtid_command::DumpBinlogGtidCommand;
@@ -77,10 +78,17 @@ impl BinlogConnection {
 }
 
 impl BinlogConnection {
-    fn replicate_mysql(channel: &mut Arc<RefCell<PacketChannel>>,
+    pub(crate) fn replicate_mysql(channel: &mut Arc<RefCell<PacketChannel>>,
                        options: &ConnectionOptions,
                        server_id: u32) -> CResult<()> {
+        let command = BinlogConnection::dump_command(options, server_id)?;
+        channel.borrow_mut().write_packet(&command, 0)?;
 
+        Ok(())
+    }
+
+    /// 构建 COM_BINLOG_DUMP / COM_BINLOG_DUMP_GTID 命令, 同步与异步连接共用
+    pub(crate) fn dump_command(options: &ConnectionOptions, server_id: u32) -> CResult<Vec<u8>> {
         if options.binlog.is_none() {
             return Err(ReError::ConnectionError(String::from("BinlogOptions is not found")))
         }
@@ -94,9 +102,9 @@ impl BinlogConnection {
                     binlog_.borrow().filename.clone(),
                     binlog_.borrow().position,
                 );
-                channel.borrow_mut().write_packet(&command.serialize(&gtid_set)?, 0)?
+                Ok(command.serialize(&gtid_set)?)
             } else {
-                return Err(ReError::String("GtidSet was not specified".to_string()));
+                Err(ReError::String("GtidSet was not specified".to_string()))
             }
         } else {
             let command = DumpBinlogCommand::new(
@@ -105,10 +113,8 @@ impl BinlogConnection {
                 binlog_.borrow().position,
             );
 
-            channel.borrow_mut().write_packet(&command.serialize()?, 0)?
+            Ok(command.serialize()?)
         }
-
-        Ok(())
     }
 
 }
@@ -166,7 +172,11 @@ impl IBinlogConnection for BinlogConnection {
 
         BinlogConnection::replicate_mysql(&mut channel.clone(), &self.conn.options, server_id)?;
 
-        let binlogs = BinlogEvents::new(channel.clone(), self.log_context.clone(), checksum, payload_buffer_size);
+        let reconnector = self.conn.options.reconnect.clone()
+            .map(|r| BinlogReconnector::new(self.conn.options.clone(), r));
+        let binlogs = BinlogEvents::new(channel.clone(), self.log_context.clone(), checksum, payload_buffer_size)
+            .with_filter(self.conn.options.replication_filter.clone())
+            .with_reconnector(reconnector);
         Ok(BinlogEventsWrapper::new(Arc::new(RefCell::new(binlogs))))
     }
 }
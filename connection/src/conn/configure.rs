@@ -8,6 +8,7 @@ use common::err::CResult;
 use crate::binlog::starting_strategy::StartingStrategy;
 use crate::commands::query_command::QueryCommand;
 use crate::conn::connection_options::{ConnectionOptions, ConnectionOptionsRef};
+use crate::conn::local_infile::{send_local_infile, LOCAL_INFILE_REQUEST};
 use crate::conn::packet_channel::PacketChannel;
 use crate::packet::check_error_packet;
 use crate::packet::response_type::ResponseType;
@@ -155,8 +156,13 @@ impl Configure {
         &self,
         channel: &mut Arc<RefCell<PacketChannel>>,
     ) -> CResult<Vec<ResultSetRowPacket>> {
-        let (packet, _) = channel.borrow_mut().read_packet()?;
+        let (packet, seq_num) = channel.borrow_mut().read_packet()?;
         check_error_packet(&packet, "Reading result set error.")?;
+        if packet[0] == LOCAL_INFILE_REQUEST {
+            let handler = self.options.borrow().local_infile_handler.clone();
+            send_local_infile(&mut channel.borrow_mut(), handler.as_ref(), &packet, seq_num)?;
+            return Ok(Vec::new());
+        }
 
         loop {
             // Skip through metadata
@@ -13,10 +13,12 @@ use common::server::Server;
 use crate::bytes::xor;
 use crate::commands::auth_plugin_switch_command::AuthPluginSwitchCommand;
 use crate::commands::authenticate_command::AuthenticateCommand;
+use crate::commands::ping_command::PingCommand;
 use crate::commands::query_command::QueryCommand;
 use crate::commands::ssl_request_command::SslRequestCommand;
 use crate::conn::configure::Configure;
 use crate::conn::connection_options::ConnectionOptions;
+use crate::conn::local_infile::send_local_infile;
 use crate::conn::packet_channel::PacketChannel;
 use crate::conn::query_result;
 use crate::conn::query_result::StreamQueryResult;
@@ -297,17 +299,7 @@ impl Connection {
         check_error_packet(&packet, "Requesting caching_sha2_password public key.")?;
 
         // Extract public key.
-        let public_key = &packet[1..];
-        let encrypted_password = xor(&password, &scramble.as_bytes());
-
-        let rsa = Rsa::public_key_from_pem(public_key).expect("load public_key error");
-        let mut encrypted_body = vec![0u8; rsa.size() as usize];
-        rsa.public_encrypt(
-            &encrypted_password,
-            &mut encrypted_body,
-            Padding::PKCS1_OAEP,
-        )
-        .expect("public_encrypt error");
+        let encrypted_body = Connection::encrypt_password_with_public_key(&password, scramble, &packet[1..])?;
 
         channel.write_packet(&encrypted_body, seq_num + 1)?;
 
@@ -316,7 +308,23 @@ impl Connection {
         Ok(())
     }
 
-    fn get_auth_plugin(auth_plugin_name: &String) -> CResult<AuthPlugin> {
+    /// caching_sha2_password 在非 ssl 连接下, 使用服务端公钥加密密码
+    pub(crate) fn encrypt_password_with_public_key(password: &[u8], scramble: &String, public_key: &[u8]) -> CResult<Vec<u8>> {
+        let encrypted_password = xor(password, &scramble.as_bytes());
+
+        let rsa = match Rsa::public_key_from_pem(public_key) {
+            Ok(rsa) => rsa,
+            Err(err) => return Err(ReError::ConnectionError(format!("load public_key error. err:{{{err}}}"))),
+        };
+        let mut encrypted_body = vec![0u8; rsa.size() as usize];
+        if let Err(err) = rsa.public_encrypt(&encrypted_password, &mut encrypted_body, Padding::PKCS1_OAEP) {
+            return Err(ReError::ConnectionError(format!("public_encrypt error. err:{{{err}}}")));
+        }
+
+        Ok(encrypted_body)
+    }
+
+    pub(crate) fn get_auth_plugin(auth_plugin_name: &String) -> CResult<AuthPlugin> {
         if auth_plugin_name == auth_plugin_names::MY_SQL_NATIVE_PASSWORD {
             return Ok(AuthPlugin::MySqlNativePassword);
         }
@@ -353,6 +361,31 @@ impl Connection {
         Ok((packet, seq_num))
     }
 
+    /// 发送 COM_PING, 检查连接是否存活
+    pub fn ping(&mut self) -> CResult<()> {
+        let command = PingCommand::new();
+        self.write_packet(&command.serialize()?, 0)?;
+        self.read_packet_with_check("Ping error.")?;
+
+        Ok(())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.is_closed
+    }
+
+    /// 响应服务端的 LOCAL INFILE 请求
+    pub(crate) fn handle_local_infile(&mut self, packet: &[u8], seq_num: u8) -> CResult<()> {
+        let channel_rs = self.channel.as_mut();
+
+        if channel_rs.is_none() {
+            return Err(ReError::ConnectionError(String::from("channel not found")));
+        }
+
+        let channel = channel_rs.unwrap();
+        send_local_infile(&mut channel.borrow_mut(), self.options.local_infile_handler.as_ref(), packet, seq_num)
+    }
+
     /// 是否有更多的result, 处理MultiResultSet时使用
     pub fn more_results_exists(&self) -> bool {
         self.session
@@ -382,7 +415,11 @@ impl Connection {
 
     /// 获得client能力flag
     fn get_client_flags(&self) -> u64 {
-        let mut client_flags = capability_flags::CLIENT_PROTOCOL_41
+        Connection::client_flags()
+    }
+
+    pub(crate) fn client_flags() -> u64 {
+        let client_flags = capability_flags::CLIENT_PROTOCOL_41
             | capability_flags::CLIENT_SECURE_CONNECTION
             | capability_flags::CLIENT_LONG_PASSWORD
             | capability_flags::CLIENT_TRANSACTIONS
@@ -9,11 +9,17 @@ use common::err::decode_error::ReError;
 use common::err::CResult;
 
 use crate::binlog::binlog_options::{BinlogOptions, BinlogOptionsRef};
+use crate::binlog::reconnect::ReconnectOptions;
+use crate::binlog::replication_filter::ReplicationFilter;
+use crate::conn::local_infile::LocalInfileHandlerRef;
 use crate::conn::ssl_mode::SslMode;
 use crate::env_options::{EnvOptions, EnvOptionsRef};
 
 pub type ConnectionOptionsRef = Arc<RefCell<ConnectionOptions>>;
 
+/// 连接属性 _client_name 的值
+const CLIENT_NAME: &str = "mysql-cdc-rs";
+
 /// Settings used to connect to MySQL/MariaDB.
 #[derive(Debug, Clone)]
 pub struct ConnectionOptions {
@@ -57,6 +63,24 @@ pub struct ConnectionOptions {
 
     /// Driver will require SSL connection if this option isn't `None` (default to `None`).
     pub ssl_opts: Option<SslOpts>,
+
+    /// 复制过滤规则(replicate-do-db / replicate-ignore-db / replicate-*-table), 默认 `None` 不过滤.
+    pub replication_filter: Option<ReplicationFilter>,
+
+    /// 连接断开、EOF 或 ER_MASTER_FATAL_ERROR_READING_BINLOG 时自动重新注册并从已消费位置继续 dump.
+    /// Defaults to `None`, 错误直接返回给调用方.
+    pub reconnect: Option<ReconnectOptions>,
+
+    /// LOAD DATA LOCAL INFILE 处理器. 设置后握手时声明 CLIENT_LOCAL_FILES;
+    /// Defaults to `None`, 服务端的 LOCAL INFILE 请求会被拒绝.
+    pub local_infile_handler: Option<LocalInfileHandlerRef>,
+
+    /// 连接属性中的 program_name, 可在 performance_schema.session_connect_attrs 中识别复制会话.
+    /// Defaults to `None`, 使用当前可执行文件名.
+    pub program_name: Option<String>,
+
+    /// 额外的连接属性, 同名时覆盖默认属性.
+    pub connect_attrs: Vec<(String, String)>,
 }
 
 impl Default for ConnectionOptions {
@@ -74,6 +98,11 @@ impl Default for ConnectionOptions {
             binlog: Some(Arc::new(RefCell::new(BinlogOptions::from_start()))),
             env: Some(Arc::new(RefCell::new(EnvOptions::default()))),
             ssl_opts: None,
+            replication_filter: None,
+            reconnect: None,
+            local_infile_handler: None,
+            program_name: None,
+            connect_attrs: Vec::new(),
         }
     }
 }
@@ -123,6 +152,11 @@ impl ConnectionOptions {
             binlog: Some(Arc::new(RefCell::new(binlog))),
             env: None,
             ssl_opts: None,
+            replication_filter: None,
+            reconnect: None,
+            local_infile_handler: None,
+            program_name: None,
+            connect_attrs: Vec::new(),
         }
     }
 
@@ -146,6 +180,60 @@ impl ConnectionOptions {
         }
     }
 
+    pub fn set_replication_filter(&mut self, filter: ReplicationFilter) {
+        self.replication_filter = Some(filter);
+    }
+
+    pub fn set_ssl(&mut self, ssl_mode: SslMode, ssl_opts: SslOpts) {
+        self.ssl_mode = ssl_mode;
+        self.ssl_opts = Some(ssl_opts);
+    }
+
+    pub fn set_reconnect(&mut self, reconnect: ReconnectOptions) {
+        self.reconnect = Some(reconnect);
+    }
+
+    pub fn set_local_infile_handler(&mut self, handler: LocalInfileHandlerRef) {
+        self.local_infile_handler = Some(handler);
+    }
+
+    pub fn with_program_name(mut self, program_name: String) -> Self {
+        self.program_name = Some(program_name);
+        self
+    }
+
+    pub fn with_connect_attr(mut self, key: String, value: String) -> Self {
+        self.connect_attrs.retain(|(k, _)| k != &key);
+        self.connect_attrs.push((key, value));
+        self
+    }
+
+    /// 握手时发送的连接属性: _client_name/_client_version/_os/_platform/_pid/program_name 以及额外属性
+    pub fn connect_attributes(&self) -> Vec<(String, String)> {
+        let program_name = self.program_name.clone().unwrap_or_else(|| {
+            std::env::current_exe().ok()
+                .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                .unwrap_or_default()
+        });
+
+        let mut attrs = vec![
+            (String::from("_client_name"), String::from(CLIENT_NAME)),
+            (String::from("_client_version"), String::from(env!("CARGO_PKG_VERSION"))),
+            (String::from("_os"), String::from(std::env::consts::OS)),
+            (String::from("_platform"), String::from(std::env::consts::ARCH)),
+            (String::from("_pid"), std::process::id().to_string()),
+            (String::from("program_name"), program_name),
+        ];
+        for (key, value) in &self.connect_attrs {
+            match attrs.iter_mut().find(|(k, _)| k == key) {
+                Some(attr) => attr.1 = value.clone(),
+                None => attrs.push((key.clone(), value.clone())),
+            }
+        }
+
+        attrs
+    }
+
     pub fn set_env(&mut self, env: EnvOptions) {
         self.env = Some(Arc::new(RefCell::new(env)));
     }
@@ -167,6 +255,10 @@ impl ConnectionOptions {
 pub struct SslOpts {
     client_identity: Option<ClientIdentity>,
     root_cert_path: Option<String>,
+    ca_bundle_paths: Vec<String>,
+    disable_built_in_roots: bool,
+    tls_hostname: Option<String>,
+    spki_pins: Vec<String>,
     skip_domain_validation: bool,
     accept_invalid_certs: bool,
 }
@@ -188,6 +280,34 @@ impl SslOpts {
         self
     }
 
+    /// 追加 CA 证书文件(.der / .pem, pem 中允许多个证书), 与 root_cert_path 一并生效
+    pub fn with_ca_bundle_path(mut self, ca_bundle_path: String) -> Self {
+        self.ca_bundle_paths.push(ca_bundle_path);
+        self
+    }
+
+    /// 仅信任配置的 CA, 不使用系统内置的根证书
+    /// (defaults to `false`).
+    pub fn with_disable_built_in_roots(mut self, value: bool) -> Self {
+        self.disable_built_in_roots = value;
+        self
+    }
+
+    /// 校验证书时使用的主机名(同时作为 SNI), 用于通过 IP 或代理连接的场景.
+    /// 默认使用 ConnectionOptions.hostname
+    pub fn with_tls_hostname(mut self, tls_hostname: Option<String>) -> Self {
+        self.tls_hostname = tls_hostname;
+        self
+    }
+
+    /// 追加服务端公钥(SubjectPublicKeyInfo)的 sha256 pin, 命中任一 pin 即通过.
+    ///
+    /// 支持 `sha256//<base64>`(同 curl --pinnedpubkey) 与 hex 两种格式
+    pub fn with_spki_pin(mut self, pin: String) -> Self {
+        self.spki_pins.push(pin);
+        self
+    }
+
     /// 不验证服务器域
     /// (defaults to `false`).
     pub fn with_danger_skip_domain_validation(mut self, value: bool) -> Self {
@@ -210,6 +330,22 @@ impl SslOpts {
         self.root_cert_path.as_ref().map(Path::new)
     }
 
+    pub fn ca_bundle_paths(&self) -> &[String] {
+        &self.ca_bundle_paths
+    }
+
+    pub fn disable_built_in_roots(&self) -> bool {
+        self.disable_built_in_roots
+    }
+
+    pub fn tls_hostname(&self) -> Option<&str> {
+        self.tls_hostname.as_deref()
+    }
+
+    pub fn spki_pins(&self) -> &[String] {
+        &self.spki_pins
+    }
+
     pub fn skip_domain_validation(&self) -> bool {
         self.skip_domain_validation
     }
@@ -223,6 +359,10 @@ impl SslOpts {
 pub struct ClientIdentity {
     pkcs12_path: String,
     password: Option<String>,
+
+    /// PEM 格式的客户端证书与 PKCS#8 私钥, 设置后优先于 pkcs12
+    cert_pem_path: Option<String>,
+    key_pem_path: Option<String>,
 }
 
 impl ClientIdentity {
@@ -231,6 +371,18 @@ impl ClientIdentity {
         Self {
             pkcs12_path,
             password: None,
+            cert_pem_path: None,
+            key_pem_path: None,
+        }
+    }
+
+    /// Creates new identity with the given PEM certificate chain and PKCS#8 PEM private key.
+    pub fn from_pem(cert_pem_path: String, key_pem_path: String) -> Self {
+        Self {
+            pkcs12_path: String::new(),
+            password: None,
+            cert_pem_path: Some(cert_pem_path),
+            key_pem_path: Some(key_pem_path),
         }
     }
 
@@ -251,8 +403,19 @@ impl ClientIdentity {
     }
 
     pub(crate) fn load(&self) -> CResult<Identity> {
-        let der = std::fs::read(&self.pkcs12_path)?;
-        match Identity::from_pkcs12(&der, self.password.as_deref().unwrap_or("")) {
+        let identity = match (&self.cert_pem_path, &self.key_pem_path) {
+            (Some(cert_path), Some(key_path)) => {
+                let cert = std::fs::read(cert_path)?;
+                let key = std::fs::read(key_path)?;
+                Identity::from_pkcs8(&cert, &key)
+            }
+            _ => {
+                let der = std::fs::read(&self.pkcs12_path)?;
+                Identity::from_pkcs12(&der, self.password.as_deref().unwrap_or(""))
+            }
+        };
+
+        match identity {
             Ok(identity) => Ok(identity),
             Err(err) => Err(ReError::ConnectionError(format!(
                 "Can not load identity. err:{{{err}}}"
@@ -263,7 +426,7 @@ impl ClientIdentity {
 
 #[cfg(test)]
 mod tests {
-    use crate::conn::connection_options::ConnectionOptions;
+    use crate::conn::connection_options::{ConnectionOptions, SslOpts};
     use crate::env_options::EnvOptions;
 
     #[test]
@@ -276,4 +439,34 @@ mod tests {
         assert!(opts.is_debug());
         assert!(opts.is_debug());
     }
+
+    #[test]
+    fn test_connect_attributes() {
+        let opts = ConnectionOptions::default()
+            .with_program_name(String::from("replayer"))
+            .with_connect_attr(String::from("_client_name"), String::from("cdc"))
+            .with_connect_attr(String::from("role"), String::from("replica"));
+
+        let attrs = opts.connect_attributes();
+        let get = |key: &str| attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(get("program_name"), Some("replayer"));
+        assert_eq!(get("_client_name"), Some("cdc"));
+        assert_eq!(get("role"), Some("replica"));
+        assert_eq!(get("_pid"), Some(std::process::id().to_string().as_str()));
+    }
+
+    #[test]
+    fn test_ssl_opts() {
+        let ssl_opts = SslOpts::default()
+            .with_ca_bundle_path(String::from("/etc/mysql/ca.pem"))
+            .with_disable_built_in_roots(true)
+            .with_tls_hostname(Some(String::from("db.internal")))
+            .with_spki_pin(String::from("sha256//AAAA"));
+
+        assert_eq!(ssl_opts.ca_bundle_paths().len(), 1);
+        assert!(ssl_opts.disable_built_in_roots());
+        assert_eq!(ssl_opts.tls_hostname(), Some("db.internal"));
+        assert_eq!(ssl_opts.spki_pins(), &[String::from("sha256//AAAA")]);
+        assert!(!ssl_opts.skip_domain_validation());
+    }
 }
@@ -0,0 +1,224 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::warn;
+
+use binlog::events::log_position::LogFilePosition;
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::conn::connection::{Connection, IConnection};
+use crate::conn::connection_options::ConnectionOptions;
+
+/// show master status 结果中 File / Position / Executed_Gtid_Set 的序号
+const MASTER_STATUS_FILE_INDEX: usize = 0;
+const MASTER_STATUS_POSITION_INDEX: usize = 1;
+const MASTER_STATUS_GTID_INDEX: usize = 4;
+
+/// show master status 的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MasterStatus {
+    pub file: String,
+    pub position: u64,
+    pub executed_gtid_set: Option<String>,
+}
+
+/// slave 端的消费进度, 由驱动 binlog 流的一方提供
+#[derive(Debug, Clone, Default)]
+pub struct ReplicaProgress {
+    /// 已消费到的位置
+    pub position: LogFilePosition,
+    /// 最近一个事件 header 中的 timestamp(秒), 0 表示尚未收到事件
+    pub last_event_timestamp: u32,
+}
+
+/// 一次健康检查的结果, 供 web /health 与 CLI status 使用
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// 连接是否存活
+    pub alive: bool,
+    /// COM_PING 耗时(毫秒)
+    pub ping_latency_ms: Option<u64>,
+    pub master_status: Option<MasterStatus>,
+    /// 估算的复制延迟(秒), 无法估算时为 None
+    pub seconds_behind_master: Option<u64>,
+    /// 检查时间, unix 秒
+    pub checked_at: u64,
+    pub error: Option<String>,
+}
+
+/// 使用独立的连接检查 master 的可用性与复制延迟, 不影响 binlog dump 连接
+#[derive(Debug)]
+pub struct HealthChecker {
+    options: ConnectionOptions,
+
+    conn: Option<Connection>,
+}
+
+impl HealthChecker {
+    pub fn new(options: ConnectionOptions) -> Self {
+        HealthChecker {
+            options,
+            conn: None,
+        }
+    }
+
+    /// 发送 COM_PING, 返回耗时。 失败时丢弃连接, 下次调用重新建立
+    pub fn ping(&mut self) -> CResult<Duration> {
+        let start = Instant::now();
+        let rs = self.connection().and_then(|conn| conn.ping());
+
+        self.release_on_err(rs).map(|_| start.elapsed())
+    }
+
+    /// 连接是否存活
+    pub fn is_alive(&mut self) -> bool {
+        self.ping().is_ok()
+    }
+
+    /// 轮询 master 当前的 binlog 位置
+    pub fn master_status(&mut self) -> CResult<MasterStatus> {
+        let rs = self.connection()
+            .and_then(|conn| conn.query(String::from("show master status")));
+        let rows = self.release_on_err(rs)?;
+
+        let row = match rows.get(0) {
+            Some(row) => row.as_slice(),
+            None => {
+                return Err(ReError::MysqlQueryErr(String::from(
+                    "'show master status' returns empty, binlog may be disabled",
+                )))
+            }
+        };
+        let file = row.get(MASTER_STATUS_FILE_INDEX).cloned().flatten()
+            .ok_or(ReError::MysqlQueryErr(String::from(
+                "Can not get binlog filename from 'show master status'",
+            )))?;
+        let position = row.get(MASTER_STATUS_POSITION_INDEX).cloned().flatten()
+            .ok_or(ReError::MysqlQueryErr(String::from(
+                "Can not get binlog position from 'show master status'",
+            )))?;
+        let executed_gtid_set = row.get(MASTER_STATUS_GTID_INDEX).cloned().flatten()
+            .filter(|gtid| !gtid.is_empty());
+
+        Ok(MasterStatus {
+            file,
+            position: position.parse()?,
+            executed_gtid_set,
+        })
+    }
+
+    /// 执行一次完整的健康检查
+    ///
+    /// # Arguments
+    ///
+    /// * `progress`: slave 的消费进度, 为 None 时不估算复制延迟
+    pub fn check(&mut self, progress: Option<&ReplicaProgress>) -> HealthReport {
+        let checked_at = now_secs();
+
+        let ping_latency = match self.ping() {
+            Ok(latency) => latency,
+            Err(e) => {
+                return HealthReport {
+                    alive: false,
+                    ping_latency_ms: None,
+                    master_status: None,
+                    seconds_behind_master: None,
+                    checked_at,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        let (master_status, error) = match self.master_status() {
+            Ok(status) => (Some(status), None),
+            Err(e) => {
+                warn!("health check poll master status error: {}", e);
+                (None, Some(e.to_string()))
+            }
+        };
+        let seconds_behind_master = match (&master_status, progress) {
+            (Some(master), Some(progress)) => seconds_behind_master(master, progress, checked_at),
+            _ => None,
+        };
+
+        HealthReport {
+            alive: true,
+            ping_latency_ms: Some(ping_latency.as_millis() as u64),
+            master_status,
+            seconds_behind_master,
+            checked_at,
+            error,
+        }
+    }
+
+    fn connection(&mut self) -> CResult<&mut Connection> {
+        if self.conn.is_none() {
+            let mut conn = Connection::new(self.options.clone());
+            conn.try_connect()?;
+            self.conn = Some(conn);
+        }
+
+        Ok(self.conn.as_mut().unwrap())
+    }
+
+    fn release_on_err<T>(&mut self, rs: CResult<T>) -> CResult<T> {
+        if rs.is_err() {
+            self.conn = None;
+        }
+        rs
+    }
+}
+
+/// 估算复制延迟: 已追上 master 位置时为 0, 否则为当前时间与最近一个事件时间之差
+pub fn seconds_behind_master(master: &MasterStatus, progress: &ReplicaProgress, now: u64) -> Option<u64> {
+    let position = &progress.position;
+    if position.get_file_name() == master.file && position.get_position() >= master.position {
+        return Some(0);
+    }
+    if progress.last_event_timestamp == 0 {
+        return None;
+    }
+
+    Some(now.saturating_sub(progress.last_event_timestamp as u64))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use binlog::events::log_position::LogFilePosition;
+    use crate::conn::health::{seconds_behind_master, MasterStatus, ReplicaProgress};
+
+    #[test]
+    fn test_seconds_behind_master() {
+        let master = MasterStatus {
+            file: String::from("binlog.000003"),
+            position: 1000,
+            executed_gtid_set: None,
+        };
+
+        let caught_up = ReplicaProgress {
+            position: LogFilePosition::new_with_position("binlog.000003", 1000),
+            last_event_timestamp: 100,
+        };
+        assert_eq!(seconds_behind_master(&master, &caught_up, 200), Some(0));
+
+        let behind = ReplicaProgress {
+            position: LogFilePosition::new_with_position("binlog.000002", 4),
+            last_event_timestamp: 150,
+        };
+        assert_eq!(seconds_behind_master(&master, &behind, 200), Some(50));
+
+        let unknown = ReplicaProgress {
+            position: LogFilePosition::new_with_position("binlog.000002", 4),
+            last_event_timestamp: 0,
+        };
+        assert_eq!(seconds_behind_master(&master, &unknown, 200), None);
+    }
+}
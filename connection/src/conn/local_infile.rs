@@ -0,0 +1,172 @@
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::warn;
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::conn::async_packet_channel::AsyncPacketChannel;
+use crate::conn::packet_channel::PacketChannel;
+use crate::packet::check_error_packet;
+
+/// LOAD DATA LOCAL INFILE 请求包的首字节
+pub const LOCAL_INFILE_REQUEST: u8 = 0xFB;
+
+/// 每个数据包发送的文件内容大小
+const LOCAL_INFILE_CHUNK_SIZE: usize = 64 * 1024;
+
+pub type LocalInfileHandlerRef = Arc<dyn LocalInfileHandler>;
+
+/// LOCAL INFILE 处理器。 服务端请求读取客户端文件时, 由该处理器决定是否允许以及提供文件内容.
+///
+/// 未配置处理器或处理器返回错误时, 会回复空包拒绝该请求, 保证连接上的协议状态不被阻塞.
+pub trait LocalInfileHandler: Send + Sync {
+    fn open(&self, file_name: &str) -> CResult<Box<dyn Read + Send>>;
+}
+
+/// 基于回调的处理器
+impl<F> LocalInfileHandler for F
+    where F: Fn(&str) -> CResult<Box<dyn Read + Send>> + Send + Sync {
+    fn open(&self, file_name: &str) -> CResult<Box<dyn Read + Send>> {
+        self(file_name)
+    }
+}
+
+impl fmt::Debug for dyn LocalInfileHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LocalInfileHandler")
+    }
+}
+
+/// 只允许读取白名单目录下文件的处理器
+#[derive(Debug, Clone)]
+pub struct WhitelistInfileHandler {
+    dirs: Vec<PathBuf>,
+}
+
+impl WhitelistInfileHandler {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        WhitelistInfileHandler {
+            dirs
+        }
+    }
+
+    fn is_allowed(&self, path: &Path) -> bool {
+        self.dirs.iter()
+            .filter_map(|d| d.canonicalize().ok())
+            .any(|d| path.starts_with(d))
+    }
+}
+
+impl LocalInfileHandler for WhitelistInfileHandler {
+    fn open(&self, file_name: &str) -> CResult<Box<dyn Read + Send>> {
+        // 解析符号链接与 .., 防止越过白名单目录
+        let path = Path::new(file_name).canonicalize()?;
+        if !self.is_allowed(&path) {
+            return Err(ReError::ConnectionError(format!(
+                "LOCAL INFILE file {} is not in the whitelist.", file_name
+            )));
+        }
+
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+}
+
+/// 处理 LOCAL INFILE 请求: 发送文件内容, 以空包结束, 并读取服务端的执行结果
+///
+/// # Arguments
+///
+/// * `packet`: 首字节为 0xFB 的请求包, 其余部分为文件名
+/// * `seq_num`: 请求包的序号
+pub(crate) fn send_local_infile(channel: &mut PacketChannel, handler: Option<&LocalInfileHandlerRef>,
+                                packet: &[u8], seq_num: u8) -> CResult<()> {
+    let mut seq_num = seq_num;
+    let mut reader = open_local_infile(handler, packet);
+
+    if let Ok(reader) = reader.as_mut() {
+        let mut buf = vec![0u8; LOCAL_INFILE_CHUNK_SIZE];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            seq_num = seq_num.wrapping_add(1);
+            channel.write_packet(&buf[..n], seq_num)?;
+        }
+    }
+
+    seq_num = seq_num.wrapping_add(1);
+    channel.write_packet(&[], seq_num)?;
+
+    let (packet, _) = channel.read_packet()?;
+    reader?;
+    check_error_packet(&packet, "LOCAL INFILE error.")
+}
+
+/// send_local_infile 的异步版本
+pub(crate) async fn send_local_infile_async(channel: &mut AsyncPacketChannel, handler: Option<LocalInfileHandlerRef>,
+                                            packet: &[u8], seq_num: u8) -> CResult<()> {
+    let mut seq_num = seq_num;
+    let mut reader = open_local_infile(handler.as_ref(), packet);
+
+    if let Ok(reader) = reader.as_mut() {
+        let mut buf = vec![0u8; LOCAL_INFILE_CHUNK_SIZE];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            seq_num = seq_num.wrapping_add(1);
+            channel.write_packet(&buf[..n], seq_num).await?;
+        }
+    }
+
+    seq_num = seq_num.wrapping_add(1);
+    channel.write_packet(&[], seq_num).await?;
+
+    let (packet, _) = channel.read_packet().await?;
+    reader?;
+    check_error_packet(&packet, "LOCAL INFILE error.")
+}
+
+fn open_local_infile(handler: Option<&LocalInfileHandlerRef>, packet: &[u8]) -> CResult<Box<dyn Read + Send>> {
+    let file_name = String::from_utf8_lossy(&packet[1..]).to_string();
+
+    let rs = match handler {
+        Some(h) => h.open(&file_name),
+        None => Err(ReError::ConnectionError(format!(
+            "LOCAL INFILE request for {} rejected, no LocalInfileHandler configured.", file_name
+        ))),
+    };
+    if let Err(e) = &rs {
+        warn!("{}", e);
+    }
+
+    rs
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+    use std::path::PathBuf;
+    use crate::conn::local_infile::{LocalInfileHandler, WhitelistInfileHandler};
+
+    #[test]
+    fn test_whitelist() {
+        let dir = std::env::temp_dir().join("local_infile_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("data.csv");
+        std::fs::write(&file, "1,a\n2,b\n").unwrap();
+
+        let handler = WhitelistInfileHandler::new(vec![dir.clone()]);
+        let mut content = String::new();
+        handler.open(file.to_str().unwrap()).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "1,a\n2,b\n");
+
+        let handler = WhitelistInfileHandler::new(vec![PathBuf::from("/not/exists")]);
+        assert!(handler.open(file.to_str().unwrap()).is_err());
+    }
+}
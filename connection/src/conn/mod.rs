@@ -1,7 +1,12 @@
 pub mod packet_channel;
+pub mod async_packet_channel;
 pub mod configure;
 pub mod connection_options;
 pub mod connection;
+pub mod async_connection;
 pub mod binlog_connection;
 pub mod ssl_mode;
+pub mod local_infile;
+pub mod health;
+mod tls;
 mod query_result;
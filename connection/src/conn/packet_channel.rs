@@ -1,15 +1,14 @@
-use std::fs::File;
 use std::io::{Read, Write};
 use std::net::{Shutdown, TcpStream};
 use std::{fmt, io, net};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use native_tls::{Certificate, TlsConnector};
 
 use common::err::decode_error::ReError;
 use common::err::CResult;
 
 use crate::conn::connection_options::ConnectionOptions;
+use crate::conn::tls;
 use crate::{PACKET_HEADER_SIZE, TIMEOUT_LATENCY_DELTA};
 
 #[derive(Debug)]
@@ -64,50 +63,8 @@ impl PacketChannel {
         }
         let ssl_opts = options.ssl_opts.clone().unwrap();
 
-        let domain = options.hostname.clone();
-
-        let mut builder = TlsConnector::builder();
-        if let Some(root_cert_path) = ssl_opts.root_cert_path() {
-            let mut root_cert_data = vec![];
-            let mut root_cert_file = File::open(root_cert_path)?;
-            root_cert_file.read_to_end(&mut root_cert_data)?;
-
-            let root_certs = match Certificate::from_der(&root_cert_data)
-                .map(|x| vec![x])
-                .or_else(|_| {
-                    pem::parse_many(&*root_cert_data)
-                        .unwrap_or_default()
-                        .iter()
-                        .map(pem::encode)
-                        .map(|s| Certificate::from_pem(s.as_bytes()))
-                        .collect()
-                }) {
-                Ok(cert) => cert,
-                Err(err) => {
-                    return Err(ReError::ConnectionError(format!(
-                        "The ssl cert can not load. err:{{{err}}}"
-                    )))
-                }
-            };
-
-            for root_cert in root_certs {
-                builder.add_root_certificate(root_cert);
-            }
-        }
-        if let Some(client_identity) = ssl_opts.client_identity() {
-            let identity = client_identity.load()?;
-            builder.identity(identity);
-        }
-        builder.danger_accept_invalid_hostnames(ssl_opts.skip_domain_validation());
-        builder.danger_accept_invalid_certs(ssl_opts.accept_invalid_certs());
-        let tls_connector = match builder.build() {
-            Ok(tls) => tls,
-            Err(err) => {
-                return Err(ReError::ConnectionError(format!(
-                    "Can not build tls. err:{{{err}}}"
-                )))
-            }
-        };
+        let domain = ssl_opts.tls_hostname().unwrap_or(&options.hostname).to_string();
+        let tls_connector = tls::build_connector(&ssl_opts)?;
 
         match self.stream {
             ChannelStream::Tcp(tcp_stream) => {
@@ -119,6 +76,8 @@ impl PacketChannel {
                         )))
                     }
                 };
+                tls::verify_spki_pins(&secure_stream, ssl_opts.spki_pins())?;
+
                 Ok(Self {
                     stream: ChannelStream::Tls(secure_stream),
                 })
@@ -14,6 +14,7 @@ use common::err::decode_error::ReError;
 use common::err::CResult;
 
 use crate::conn::connection::Connection;
+use crate::conn::local_infile::LOCAL_INFILE_REQUEST;
 use crate::declar::capability_flags;
 use crate::packet::end_of_file_packet::EndOfFilePacket;
 use crate::packet::result_set_column_packet::ResultSetColumnPacket;
@@ -93,7 +94,12 @@ impl Drop for StreamQueryResult<'_> {
 }
 
 pub fn read_column_set(conn: &mut Connection) -> CResult<Vec<SrcColumn>> {
-    let (packet, _) = conn.read_packet_with_check("Query result column load error.")?;
+    let (packet, seq_num) = conn.read_packet_with_check("Query result column load error.")?;
+    if packet[0] == LOCAL_INFILE_REQUEST {
+        // LOAD DATA LOCAL INFILE 没有结果集
+        conn.handle_local_infile(&packet, seq_num)?;
+        return Ok(Vec::new());
+    }
 
     let mut cursor = Cursor::new(packet.as_slice());
     let column_count = read_len_enc_num(&mut cursor)?.1;
@@ -0,0 +1,159 @@
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use native_tls::{Certificate, TlsConnector, TlsStream};
+use openssl::base64;
+use openssl::sha::sha256;
+use openssl::x509::X509;
+
+use common::err::decode_error::ReError;
+use common::err::CResult;
+
+use crate::conn::connection_options::SslOpts;
+
+/// SPKI pin 的前缀, 格式同 curl `--pinnedpubkey`: sha256//<base64>
+const SPKI_PIN_PREFIX: &str = "sha256//";
+
+/// 根据 SslOpts 构建 TlsConnector
+pub(crate) fn build_connector(ssl_opts: &SslOpts) -> CResult<TlsConnector> {
+    let mut builder = TlsConnector::builder();
+
+    if let Some(root_cert_path) = ssl_opts.root_cert_path() {
+        for root_cert in load_certificates(root_cert_path)? {
+            builder.add_root_certificate(root_cert);
+        }
+    }
+    for ca_bundle_path in ssl_opts.ca_bundle_paths() {
+        for ca_cert in load_certificates(Path::new(ca_bundle_path))? {
+            builder.add_root_certificate(ca_cert);
+        }
+    }
+    builder.disable_built_in_roots(ssl_opts.disable_built_in_roots());
+
+    if let Some(client_identity) = ssl_opts.client_identity() {
+        let identity = client_identity.load()?;
+        builder.identity(identity);
+    }
+    builder.danger_accept_invalid_hostnames(ssl_opts.skip_domain_validation());
+    builder.danger_accept_invalid_certs(ssl_opts.accept_invalid_certs());
+
+    match builder.build() {
+        Ok(tls) => Ok(tls),
+        Err(err) => Err(ReError::ConnectionError(format!(
+            "Can not build tls. err:{{{err}}}"
+        ))),
+    }
+}
+
+/// 加载证书文件。 支持 .der 与 .pem, pem 文件中允许包含多个证书
+fn load_certificates(path: &Path) -> CResult<Vec<Certificate>> {
+    let mut cert_data = vec![];
+    let mut cert_file = File::open(path)?;
+    cert_file.read_to_end(&mut cert_data)?;
+
+    match Certificate::from_der(&cert_data)
+        .map(|x| vec![x])
+        .or_else(|_| {
+            pem::parse_many(&*cert_data)
+                .unwrap_or_default()
+                .iter()
+                .map(pem::encode)
+                .map(|s| Certificate::from_pem(s.as_bytes()))
+                .collect()
+        }) {
+        Ok(certs) => Ok(certs),
+        Err(err) => Err(ReError::ConnectionError(format!(
+            "The ssl cert can not load. err:{{{err}}}"
+        ))),
+    }
+}
+
+/// 校验服务端证书公钥是否命中任一 pin。 pins 为空时不校验
+pub(crate) fn verify_spki_pins<S: io::Read + io::Write>(stream: &TlsStream<S>, pins: &[String]) -> CResult<()> {
+    if pins.is_empty() {
+        return Ok(());
+    }
+
+    let peer_cert = match stream.peer_certificate() {
+        Ok(Some(cert)) => cert,
+        Ok(None) => {
+            return Err(ReError::ConnectionError(
+                "The server did not present a certificate for pinning.".to_string(),
+            ))
+        }
+        Err(err) => {
+            return Err(ReError::ConnectionError(format!(
+                "Can not read peer certificate. err:{{{err}}}"
+            )))
+        }
+    };
+    let der = match peer_cert.to_der() {
+        Ok(der) => der,
+        Err(err) => {
+            return Err(ReError::ConnectionError(format!(
+                "Can not encode peer certificate. err:{{{err}}}"
+            )))
+        }
+    };
+
+    let actual = spki_sha256(&der)?;
+    if pins.iter().any(|pin| pin_matches(pin, &actual)) {
+        return Ok(());
+    }
+
+    Err(ReError::ConnectionError(format!(
+        "The server public key does not match any pinned key. actual:{}{}",
+        SPKI_PIN_PREFIX,
+        base64::encode_block(&actual)
+    )))
+}
+
+/// 计算证书 SubjectPublicKeyInfo 的 sha256
+pub fn spki_sha256(der_cert: &[u8]) -> CResult<[u8; 32]> {
+    let spki = X509::from_der(der_cert)
+        .and_then(|cert| cert.public_key())
+        .and_then(|key| key.public_key_to_der());
+
+    match spki {
+        Ok(spki) => Ok(sha256(&spki)),
+        Err(err) => Err(ReError::ConnectionError(format!(
+            "Can not extract public key from certificate. err:{{{err}}}"
+        ))),
+    }
+}
+
+/// pin 支持 `sha256//<base64>` 与 64 位 hex 两种格式
+fn pin_matches(pin: &str, actual: &[u8; 32]) -> bool {
+    let pin = pin.trim();
+
+    let expected = match pin.strip_prefix(SPKI_PIN_PREFIX) {
+        Some(b64) => base64::decode_block(b64).ok(),
+        None => hex::decode(pin).ok(),
+    };
+
+    match expected {
+        Some(expected) => expected.as_slice() == actual.as_slice(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use openssl::base64;
+    use crate::conn::tls::pin_matches;
+
+    #[test]
+    fn test_pin_matches() {
+        let digest = [7u8; 32];
+
+        let b64 = format!("sha256//{}", base64::encode_block(&digest));
+        assert!(pin_matches(&b64, &digest));
+        assert!(pin_matches(&hex::encode(digest), &digest));
+        assert!(pin_matches(&hex::encode_upper(digest), &digest));
+
+        assert!(!pin_matches(&hex::encode([8u8; 32]), &digest));
+        assert!(!pin_matches("sha256//not-base64", &digest));
+    }
+}
@@ -13,10 +13,18 @@ pub mod response_type;
 pub mod result_set_column_packet;
 pub mod result_set_row_packet;
 
+/// 认证失败与权限不足的错误码: ER_DBACCESS_DENIED_ERROR, ER_ACCESS_DENIED_ERROR, ER_SPECIFIC_ACCESS_DENIED_ERROR,
+/// ER_NOT_SUPPORTED_AUTH_MODE, ER_ACCESS_DENIED_NO_PASSWORD_ERROR, ER_MUST_CHANGE_PASSWORD_LOGIN
+const AUTH_ERROR_CODES: [u16; 6] = [1044, 1045, 1227, 1251, 1698, 1862];
+
 pub fn check_error_packet(packet: &[u8], message: &str) -> CResult<()> {
     if packet[0] == ResponseType::ERROR {
         let error = ErrorPacket::parse(&packet[1..])?;
+        let is_auth_error = AUTH_ERROR_CODES.contains(&error.error_code);
         let message = format!("{} {:?}", message, error).to_string();
+        if is_auth_error {
+            return Err(ReError::AuthError(message));
+        }
         return Err(ReError::String(message));
     }
 
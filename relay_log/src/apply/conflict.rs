@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// 目标库中记录冲突的表, 与位点表位于同一个库
+pub const DEFAULT_CONFLICT_TABLE: &str = "relay_log_apply_conflict";
+/// ER_DUP_ENTRY
+pub const ER_DUP_ENTRY: u16 = 1062;
+
+/// 回放到非空目标库时的冲突处理方式。
+///
+/// 冲突指 Insert 主键/唯一键重复, 或 Update/Delete 在目标库中找不到变更前的行
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 回滚当前事务并停止回放
+    #[default]
+    Error,
+    /// 跳过冲突的行并记录日志
+    Skip,
+    /// 覆盖: Insert 使用 REPLACE, Update 找不到行时写入变更后的行, Delete 找不到行时忽略
+    Overwrite,
+    /// 跳过冲突的行, 并在同一个事务中写入冲突表供之后核对
+    Record,
+}
+
+/// 冲突类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    // 主键/唯一键重复
+    Duplicate,
+    // 目标库中没有变更前的行
+    Missing,
+}
+
+impl ConflictKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictKind::Duplicate => "duplicate",
+            ConflictKind::Missing => "missing",
+        }
+    }
+}
+
+/// 按表配置的冲突处理方式, 未配置的表使用默认方式
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConflictPolicies {
+    #[serde(default)]
+    pub default: ConflictPolicy,
+    // db.table -> 冲突处理方式
+    #[serde(default)]
+    pub tables: HashMap<String, ConflictPolicy>,
+}
+
+impl ConflictPolicies {
+    pub fn new(default: ConflictPolicy) -> Self {
+        Self {
+            default,
+            tables: HashMap::new(),
+        }
+    }
+
+    /// 设置表的冲突处理方式
+    pub fn with_table(mut self, database: &str, table: &str, policy: ConflictPolicy) -> Self {
+        self.tables.insert(format!("{}.{}", database, table), policy);
+        self
+    }
+
+    pub fn policy(&self, database: &str, table: &str) -> ConflictPolicy {
+        self.tables.get(&format!("{}.{}", database, table)).copied().unwrap_or(self.default)
+    }
+
+    /// 是否需要冲突表
+    pub fn records(&self) -> bool {
+        self.default == ConflictPolicy::Record || self.tables.values().any(|p| *p == ConflictPolicy::Record)
+    }
+}
@@ -1,2 +1,6 @@
 pub mod relay_log_apply;
-pub mod relay_log_apply_snapshot;
\ No newline at end of file
+pub mod relay_log_apply_snapshot;
+pub mod sql_builder;
+pub mod conflict;
+pub mod transform;
+pub mod replay_progress;
@@ -1,5 +1,779 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-/// todo
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use common::binlog::row::row_string::RowString;
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use common::memory_governor::{DEFAULT_BACKPRESSURE_TIMEOUT, MemoryConsumer, MemoryGovernor};
+use connection::binlog::parallel_applier::LaneScheduler;
+use connection::conn::async_connection::AsyncConnection;
+
+use crate::apply::replay_progress::ReplayProgress;
+use crate::apply::conflict::{ConflictKind, ConflictPolicies, ConflictPolicy, DEFAULT_CONFLICT_TABLE, ER_DUP_ENTRY};
+use crate::apply::sql_builder::{ApplyStatement, SqlBuilder};
+use crate::apply::transform::TransformChain;
+use crate::relay_log::{RelayCommand, RelayLog, RelayRowData};
+use crate::storage::relay_log_tail::RelayLogTail;
+use crate::storage::retention::ConsumerOffsets;
+use crate::storage::storage_entry::StorageEntry;
+
+/// 目标库中保存回放位点的库
+pub const DEFAULT_POSITION_SCHEMA: &str = "mysql_cdc";
+/// 目标库中保存回放位点的表
+pub const DEFAULT_POSITION_TABLE: &str = "relay_log_apply_position";
+/// 每个 lane 排队的事务数
+const LANE_QUEUE_SIZE: usize = 64;
+/// 写入回放进度状态文件的最小间隔
+const STATUS_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 回放位点, 与回放的变更在目标库的同一个事务中提交。
+///
+/// 源库读取可从 binlog_file:event_log_pos 或 gtid 之后继续, 与中继日志一起丢失时也不会重复回放
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyPosition {
+    // 下一个待回放的 entry index
+    pub next_index: u64,
+    // 最后回放的事件在源库 binlog 中的位置
+    pub event_log_pos: u64,
+    pub event_timestamp: u32,
+    // 最后回放的事件所在的源库 binlog 文件, 中继日志中没有 Rotate 时为空
+    pub binlog_file: String,
+    // 最后回放的事务的 GTID, 源库未开启 GTID 时为空
+    pub gtid: String,
+}
+
+/// lane 回放完成的事务: (事务第一个 entry index, 结果)
+type LaneDone = (u64, CResult<ApplyPosition>);
+
+/// 事务的回放方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dispatch {
+    /// 并行度为 1, 在主连接上回放
+    Serial,
+    /// 只涉及一个 lane, 由该 lane 按顺序回放
+    Lane(usize),
+    /// 涉及多个 lane 或为DDL, 等待全部 lane 回放完成后在主连接上回放
+    Barrier,
+    /// 重启前已回放, 跳过
+    Applied,
+}
+
+/// 回放事务所需的目标库信息, 由主连接与各 lane 共享
+#[derive(Debug, Clone)]
+struct ApplyTarget {
+    // 回放任务名
+    name: String,
+    position_table: String,
+    conflict_table: String,
+    policies: Arc<ConflictPolicies>,
+    transforms: Arc<TransformChain>,
+}
+
+/// 把中继日志回放到目标 MySQL。
+///
+/// 以事务提交标记划分事务, 一个事务中的变更与位点表的更新在目标库的同一个事务中提交,
+/// 重启后从位点表记录的 next_index 继续, 不会重复或遗漏。
+/// 中继日志中的DDL不带语句, 回放时跳过并单独提交。
+///
+/// 并行回放时按 库/表/主键 的 hash 把事务分发到多个 lane, 每个 lane 使用独立的连接按顺序回放并记录自己的位点:
+///
+///   tail --> 事务 --> hash(db, table | pk) % parallelism --> lane[i] --> 目标库
+///
+/// 涉及多个 lane 的事务与DDL作为屏障: 等待全部 lane 回放完成后执行, 并把所有 lane 的位点推进到屏障之后。
+///
+/// 目标库非空时按 [ConflictPolicies] 处理主键重复或找不到行的冲突, 默认回滚并停止回放。
+/// 生成SQL前执行 [TransformChain] 中的变换, 冲突处理方式按变换后的库表匹配
 pub struct RelayLogApply {
+    // 回放任务名, 位点表的主键
+    name: String,
+    // 目标库连接, 串行回放与屏障使用
+    conn: AsyncConnection,
+    position_schema: String,
+    position_table: String,
+    // 并行度
+    parallelism: usize,
+    // 主键列名, 为空时按表分发
+    primary_keys: Vec<String>,
+    // 冲突处理方式
+    policies: Arc<ConflictPolicies>,
+    // 生成SQL前的变换
+    transforms: Arc<TransformChain>,
+    // 连续回放完成的位点
+    position: ApplyPosition,
+    // 已读取到的源库 binlog 文件与 GTID
+    binlog_file: String,
+    gtid: String,
+    // 当前事务中已读取、尚未回放的日志
+    pending: Vec<StorageEntry>,
+    // pending 占用的字节数
+    pending_bytes: u64,
+    // 当前事务已等待超时并透支, 之后的日志直接占用不再等待
+    overdrawn: bool,
+    // 已读取、尚未提交的事务占用的内存, 超出预算时等待其他组件释放; 事务在目标库提交后释放
+    memory: MemoryConsumer,
+    // 超出预算时等待的最长时间
+    backpressure_timeout: Duration,
+    // 提交回放位点, 避免未回放的segment被清理
+    consumer_offsets: Option<ConsumerOffsets>,
+    // 每个 lane 的事务队列
+    lanes: Vec<mpsc::Sender<(Vec<StorageEntry>, ApplyPosition)>>,
+    // 每个 lane 已回放到的 next_index, 重启后跳过已回放的事务
+    lane_next: Vec<u64>,
+    done_tx: mpsc::UnboundedSender<LaneDone>,
+    done_rx: mpsc::UnboundedReceiver<LaneDone>,
+    // 已分发的事务: 第一个 entry index -> (行数, 占用的内存, 完成后的位点)
+    in_flight: BTreeMap<u64, (u64, u64, Option<ApplyPosition>)>,
+    // 回放进度与吞吐量
+    progress: ReplayProgress,
+    // 回放进度状态文件所在的文件夹, 为空时不写入
+    status_dir: Option<String>,
+    last_status_save: Option<Instant>,
+}
+
+impl RelayLogApply {
+    pub fn new(name: &str, conn: AsyncConnection) -> Self {
+        let (done_tx, done_rx) = mpsc::unbounded_channel();
+        Self {
+            name: name.to_string(),
+            conn,
+            position_schema: DEFAULT_POSITION_SCHEMA.to_string(),
+            position_table: DEFAULT_POSITION_TABLE.to_string(),
+            parallelism: 1,
+            primary_keys: vec![],
+            policies: Arc::new(ConflictPolicies::default()),
+            transforms: Arc::new(TransformChain::default()),
+            position: ApplyPosition::default(),
+            binlog_file: String::new(),
+            gtid: String::new(),
+            pending: vec![],
+            pending_bytes: 0,
+            overdrawn: false,
+            memory: MemoryGovernor::global().register(&format!("relay_apply:{}", name)),
+            backpressure_timeout: DEFAULT_BACKPRESSURE_TIMEOUT,
+            consumer_offsets: None,
+            lanes: vec![],
+            lane_next: vec![],
+            done_tx,
+            done_rx,
+            in_flight: BTreeMap::new(),
+            progress: ReplayProgress::new(name),
+            status_dir: None,
+            last_status_save: None,
+        }
+    }
+
+    /// 指定位点表
+    pub fn with_position_table(mut self, schema: &str, table: &str) -> Self {
+        self.position_schema = schema.to_string();
+        self.position_table = table.to_string();
+        self
+    }
+
+    /// 每回放一个事务, 以任务名向中继日志存储提交消费者 offset
+    pub fn with_consumer_offsets(mut self, consumer_offsets: ConsumerOffsets) -> Self {
+        self.consumer_offsets = Some(consumer_offsets);
+        self
+    }
+
+    /// 并行度, 每个 lane 使用一个新的连接. Defaults to 1.
+    ///
+    /// 非正常退出后修改并行度会导致无法确定哪些事务已回放, 此时 [RelayLogApply::init] 返回错误
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// 按主键分发, 同一行的变更由同一个 lane 顺序回放; 不指定时按表分发
+    pub fn with_primary_keys(mut self, primary_keys: Vec<String>) -> Self {
+        self.primary_keys = primary_keys;
+        self
+    }
+
+    /// 冲突处理方式, 默认为 [ConflictPolicy::Error]
+    pub fn with_conflict_policies(mut self, policies: ConflictPolicies) -> Self {
+        self.policies = Arc::new(policies);
+        self
+    }
+
+    /// 生成SQL前对中继日志的变换, 如修改库表名、列名、替换值与过滤行
+    pub fn with_transforms(mut self, transforms: TransformChain) -> Self {
+        self.transforms = Arc::new(transforms);
+        self
+    }
+
+    /// 未提交的事务占用的内存预算, 默认为进程内共享的 [MemoryGovernor::global]
+    pub fn with_memory_governor(mut self, governor: Arc<MemoryGovernor>) -> Self {
+        self.memory = governor.register(&format!("relay_apply:{}", self.name));
+        self
+    }
+
+    /// 超出内存预算时等待其他组件释放的最长时间, 超时后透支。 Defaults to [DEFAULT_BACKPRESSURE_TIMEOUT].
+    ///
+    /// 一个事务中只等待一次, 超时后该事务剩余的日志直接占用
+    pub fn with_backpressure_timeout(mut self, timeout: Duration) -> Self {
+        self.backpressure_timeout = timeout;
+        self
+    }
+
+    /// 未提交的事务占用的内存
+    pub fn memory_used(&self) -> u64 {
+        self.memory.used()
+    }
+
+    /// 把回放进度写入 {status_dir}/{name}.json, 每秒至多一次, 供 CLI `status` 与 web 读取
+    pub fn with_status_dir(mut self, status_dir: &str) -> Self {
+        self.status_dir = Some(status_dir.to_string());
+        self
+    }
+
+    /// 回放进度, 可交给其它线程读取
+    pub fn progress(&self) -> ReplayProgress {
+        self.progress.clone()
+    }
+
+    /// 连续回放完成的位点
+    pub fn position(&self) -> &ApplyPosition {
+        &self.position
+    }
+
+    /// 连接目标库, 创建位点表, 读取上次回放的位点并启动 lane, 应从返回的 next_index 开始读取中继日志
+    pub async fn init(&mut self) -> CResult<ApplyPosition> {
+        if !self.conn.is_connected() {
+            self.conn.connect().await?;
+        }
+        self.conn.execute(format!("CREATE DATABASE IF NOT EXISTS {}", SqlBuilder::ident(&self.position_schema))).await?;
+        self.conn.execute(format!("CREATE TABLE IF NOT EXISTS {} (\
+            name VARCHAR(255) NOT NULL PRIMARY KEY, \
+            next_index BIGINT UNSIGNED NOT NULL, \
+            event_log_pos BIGINT UNSIGNED NOT NULL, \
+            event_timestamp INT UNSIGNED NOT NULL, \
+            binlog_file VARCHAR(512) NOT NULL DEFAULT '', \
+            gtid VARCHAR(255) NOT NULL DEFAULT '', \
+            updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP)",
+            self.position_table_name())).await?;
+        if self.policies.records() {
+            self.conn.execute(format!("CREATE TABLE IF NOT EXISTS {} (\
+                id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY, \
+                name VARCHAR(255) NOT NULL, \
+                entry_index BIGINT UNSIGNED NOT NULL, \
+                database_name VARCHAR(255) NOT NULL, \
+                table_name VARCHAR(255) NOT NULL, \
+                kind VARCHAR(32) NOT NULL, \
+                statement LONGTEXT NOT NULL, \
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP, \
+                KEY idx_name_index (name, entry_index))",
+                self.conflict_table_name())).await?;
+        }
+
+        let positions = self.load_positions().await?;
+        let names = self.lane_names();
+        let position_table = self.position_table_name();
+        let target = self.target();
+        if self.restore_positions(&positions)? {
+            // 按当前并行度重写位点
+            let sqls = vec![
+                format!("DELETE FROM {} WHERE name LIKE {}", position_table, SqlBuilder::quote(&format!("{}#%", self.name))),
+                Self::position_sql(&position_table, &names, &self.position),
+            ];
+            Self::execute_transaction(&mut self.conn, sqls).await?;
+        }
+
+        if self.parallelism > 1 {
+            for (lane, name) in names.into_iter().enumerate() {
+                let mut conn = AsyncConnection::new(self.conn.options.clone());
+                conn.connect().await?;
+                let (tx, rx) = mpsc::channel(LANE_QUEUE_SIZE);
+                tokio::spawn(Self::run_lane(lane, conn, target.clone(), name, rx, self.done_tx.clone()));
+                self.lanes.push(tx);
+            }
+        }
+        self.progress.start(self.position.next_index);
+        info!("relay log apply {} start from {:?}, parallelism {}", self.name, self.position, self.parallelism);
+
+        Ok(self.position.clone())
+    }
+
+    /// 按位点表中的位点恢复各 lane 已回放到的 next_index 与连续回放完成的位点。
+    ///
+    /// 上次正常退出时只有任务名的位点, 各 lane 从该位点开始, 因此可以修改并行度;
+    /// 上次以其他并行度非正常退出时, 只有各 lane 的位点相同时可以继续, 返回 true 表示需要按当前并行度重写位点表
+    pub fn restore_positions(&mut self, positions: &BTreeMap<String, ApplyPosition>) -> CResult<bool> {
+        let names = self.lane_names();
+        let stale = positions.keys().any(|n| *n != self.name && !names.contains(n));
+        let lane_rows: Vec<ApplyPosition> = positions.iter()
+            .filter(|(n, _)| **n != self.name)
+            .map(|(_, p)| p.clone())
+            .collect();
+        if stale && lane_rows.iter().any(|p| p.next_index != lane_rows[0].next_index) {
+            return Err(ReError::String(format!("relay log apply {} stopped uncleanly with another parallelism, \
+                restart it with the previous parallelism first.", self.name)));
+        }
+
+        let lane_positions: Vec<ApplyPosition> = if stale {
+            vec![lane_rows[0].clone(); names.len()]
+        } else {
+            let fallback = positions.get(&self.name).cloned().unwrap_or_default();
+            names.iter().map(|n| positions.get(n).cloned().unwrap_or(fallback.clone())).collect()
+        };
+        self.lane_next = lane_positions.iter().map(|p| p.next_index).collect();
+        self.position = lane_positions.iter().min_by_key(|p| p.next_index).cloned().unwrap_or_default();
+        self.binlog_file = self.position.binlog_file.clone();
+        self.gtid = self.position.gtid.clone();
+
+        Ok(stale)
+    }
+
+    /// 回放 tail 产出的日志, 存储关闭后等待全部 lane 完成并合并位点, 最后一个不完整的事务不回放
+    pub async fn run(&mut self, tail: &mut RelayLogTail) -> CResult<()> {
+        while let Some(entry) = tail.next().await {
+            self.progress.set_newest(tail.appended_index());
+            self.apply_entry(entry?).await?;
+        }
+        if !self.pending.is_empty() {
+            warn!("relay log apply {} stop with {} uncommitted entries.", self.name, self.pending.len());
+            self.pending.clear();
+            self.memory.release(std::mem::take(&mut self.pending_bytes));
+            self.overdrawn = false;
+        }
+
+        self.close().await
+    }
+
+    /// 处理一条日志, 遇到事务提交标记或DDL时分发当前事务
+    pub async fn apply_entry(&mut self, entry: StorageEntry) -> CResult<()> {
+        if *entry.index() < self.position.next_index {
+            return Ok(());
+        }
+        let boundary = match entry.relay_log().relay_command() {
+            RelayCommand::Commit(_) => true,
+            RelayCommand::Rotate(file) => {
+                self.binlog_file = file.clone();
+                false
+            }
+            RelayCommand::Gtid(gtid) => {
+                self.gtid = gtid.clone();
+                false
+            }
+            c if Self::is_ddl(c) => {
+                warn!("relay log apply {} skip ddl {} at index {}.", self.name, entry.relay_log().event_name(), entry.index());
+                true
+            }
+            _ => false,
+        };
+        let size = *entry.log_size();
+        if self.overdrawn {
+            self.memory.force_reserve(size);
+        } else if !self.memory.reserve(size, self.backpressure_timeout).await {
+            self.overdrawn = true;
+        }
+        self.pending.push(entry);
+        self.pending_bytes += size;
+
+        if boundary {
+            let entries = std::mem::take(&mut self.pending);
+            let bytes = std::mem::take(&mut self.pending_bytes);
+            self.overdrawn = false;
+            self.dispatch(entries, bytes).await?;
+        }
+        Ok(())
+    }
+
+    /// 等待全部 lane 回放完成, 合并为一个位点并删除各 lane 的位点, 之后可以修改并行度
+    pub async fn close(&mut self) -> CResult<()> {
+        self.drain().await?;
+        self.save_status(true);
+        if self.lanes.is_empty() {
+            return Ok(());
+        }
+        self.lanes.clear();
+
+        let position_table = self.position_table_name();
+        let sqls = vec![
+            format!("DELETE FROM {} WHERE name LIKE {}", position_table, SqlBuilder::quote(&format!("{}#%", self.name))),
+            Self::position_sql(&position_table, &[self.name.clone()], &self.position),
+        ];
+        Self::execute_transaction(&mut self.conn, sqls).await
+    }
+
+    /// 串行回放, 或按 lane 分发; 涉及多个 lane 的事务作为屏障执行。
+    /// 事务占用的 bytes 在目标库提交(或确认已回放)后释放
+    async fn dispatch(&mut self, entries: Vec<StorageEntry>, bytes: u64) -> CResult<()> {
+        let first_index = *entries[0].index();
+        let last = &entries[entries.len() - 1];
+        let last_index = *last.index();
+        let position = ApplyPosition {
+            next_index: last_index + 1,
+            event_log_pos: *last.relay_log().event_log_pos(),
+            event_timestamp: *last.relay_log().event_timestamp(),
+            binlog_file: self.binlog_file.clone(),
+            gtid: self.gtid.clone(),
+        };
+        let rows = Self::row_count(&entries);
+        let target = self.target();
+        let names = self.lane_names();
+
+        match self.dispatch_of(&entries) {
+            Dispatch::Serial => {
+                let rs = Self::apply_transaction(&mut self.conn, &target, &names, &entries, position).await;
+                self.memory.release(bytes);
+                self.advance(rs?, rows);
+            }
+            Dispatch::Applied => {
+                self.memory.release(bytes);
+            }
+            Dispatch::Lane(lane) => {
+                let sender = self.lanes.get(lane)
+                    .ok_or_else(|| ReError::String(format!("relay log apply lane {} is not started.", lane)))?;
+                self.in_flight.insert(first_index, (rows, bytes, None));
+                sender.send((entries, position)).await
+                    .map_err(|_| ReError::String(format!("relay log apply lane {} closed.", lane)))?;
+                self.poll_done()?;
+            }
+            Dispatch::Barrier => {
+                self.drain().await?;
+                let rs = Self::apply_transaction(&mut self.conn, &target, &names, &entries, position).await;
+                self.memory.release(bytes);
+                let position = rs?;
+                self.lane_next.iter_mut().for_each(|n| *n = position.next_index);
+                self.advance(position, rows);
+            }
+        }
+        Ok(())
+    }
+
+    /// 事务的回放方式, lane 的位点见 [RelayLogApply::restore_positions]
+    pub fn dispatch_of(&self, entries: &[StorageEntry]) -> Dispatch {
+        if self.parallelism == 1 {
+            return Dispatch::Serial;
+        }
+
+        let last_index = entries.last().map_or(0, |e| *e.index());
+        let lanes = self.lanes_of(entries);
+        if lanes.len() == 1 {
+            let lane = *lanes.iter().next().unwrap_or(&0);
+            if last_index < self.lane_next.get(lane).copied().unwrap_or_default() {
+                return Dispatch::Applied;
+            }
+            Dispatch::Lane(lane)
+        } else {
+            // 屏障会把全部 lane 的位点推进到屏障之后, 早于最小位点的屏障已回放
+            if last_index < self.lane_next.iter().copied().min().unwrap_or_default() {
+                return Dispatch::Applied;
+            }
+            Dispatch::Barrier
+        }
+    }
+
+    /// 事务涉及的 lane, DDL 涉及全部 lane。 按 [LaneScheduler] 分配, 与 binlog 的并行回放一致
+    pub fn lanes_of(&self, entries: &[StorageEntry]) -> BTreeSet<usize> {
+        let scheduler = LaneScheduler::new(self.parallelism);
+        let mut lanes = BTreeSet::new();
+        for entry in entries {
+            let log = entry.relay_log();
+            let mut keys: Vec<Option<String>> = vec![];
+            match log.relay_command() {
+                RelayCommand::Insert(rows) | RelayCommand::Delete(rows) => {
+                    rows.iter().for_each(|r| keys.push(self.row_key(log, r)));
+                }
+                RelayCommand::Update(rows) => {
+                    rows.iter().for_each(|(before, after)| {
+                        keys.push(self.row_key(log, before));
+                        keys.push(self.row_key(log, after));
+                    });
+                }
+                c if Self::is_ddl(c) => return (0..self.parallelism).collect(),
+                // 标记不属于任何表
+                RelayCommand::Commit(_) | RelayCommand::Rotate(_) | RelayCommand::Gtid(_) => {}
+                _ => keys.push(None),
+            }
+
+            for key in keys {
+                lanes.insert(scheduler.lane_of(log.database_name(), log.table_name(), key.as_deref()));
+            }
+        }
+        lanes
+    }
+
+    /// 行的主键值, 未指定主键或缺少主键列时为 None, 即按表分发
+    fn row_key(&self, log: &RelayLog, row: &RelayRowData) -> Option<String> {
+        if self.primary_keys.is_empty() {
+            return None;
+        }
+        let mut values = Vec::with_capacity(self.primary_keys.len());
+        for pk in &self.primary_keys {
+            let i = log.columns().iter().position(|c| c.column_name() == pk)?;
+            values.push(format!("{:?}", row.values().get(i)?));
+        }
+        Some(values.join(","))
+    }
+
+    /// 事务中变更的行数
+    fn row_count(entries: &[StorageEntry]) -> u64 {
+        entries.iter().map(|e| match e.relay_log().relay_command() {
+            RelayCommand::Insert(rows) | RelayCommand::Delete(rows) => rows.len() as u64,
+            RelayCommand::Update(rows) => rows.len() as u64,
+            _ => 0,
+        }).sum()
+    }
+
+    fn is_ddl(command: &RelayCommand) -> bool {
+        matches!(command, RelayCommand::CreateDatabase | RelayCommand::DropDatabase | RelayCommand::CreateTable |
+            RelayCommand::DropTable | RelayCommand::AlterTable)
+    }
+
+    /// 处理已完成的事务, 不等待
+    fn poll_done(&mut self) -> CResult<()> {
+        while let Ok((first_index, rs)) = self.done_rx.try_recv() {
+            self.complete(first_index, rs)?;
+        }
+        Ok(())
+    }
+
+    /// 等待所有已分发的事务回放完成
+    async fn drain(&mut self) -> CResult<()> {
+        while self.in_flight.values().any(|(_, _, p)| p.is_none()) {
+            match self.done_rx.recv().await {
+                Some((first_index, rs)) => self.complete(first_index, rs)?,
+                None => return Err(ReError::String("relay log apply lanes closed.".to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    /// 记录完成的事务, 按 index 顺序推进连续回放完成的位点
+    fn complete(&mut self, first_index: u64, rs: CResult<ApplyPosition>) -> CResult<()> {
+        if let Some((_, bytes, done)) = self.in_flight.get_mut(&first_index) {
+            // 事务已在目标库提交或已回滚
+            self.memory.release(std::mem::take(bytes));
+            if let Ok(position) = &rs {
+                *done = Some(position.clone());
+            }
+        }
+        rs?;
+        while let Some(entry) = self.in_flight.first_entry() {
+            if entry.get().2.is_none() {
+                break;
+            }
+            if let (rows, _, Some(position)) = entry.remove() {
+                self.advance(position, rows);
+            }
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, position: ApplyPosition, rows: u64) {
+        if position.next_index <= self.position.next_index {
+            return;
+        }
+        if let Some(offsets) = &self.consumer_offsets {
+            offsets.commit(&self.name, position.next_index);
+        }
+        self.progress.record_applied(position.next_index, rows);
+        self.position = position;
+        self.save_status(false);
+    }
+
+    /// 写入回放进度状态文件, 失败时只记录日志
+    fn save_status(&mut self, force: bool) {
+        let status_dir = match &self.status_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        if !force && self.last_status_save.map_or(false, |t| t.elapsed() < STATUS_SAVE_INTERVAL) {
+            return;
+        }
+        self.last_status_save = Some(Instant::now());
+        if let Err(e) = self.progress.status().save(status_dir) {
+            warn!("relay log apply {} save status err: {:?}", self.name, e);
+        }
+    }
+
+    /// lane 按顺序回放分发来的事务
+    async fn run_lane(lane: usize,
+                      mut conn: AsyncConnection,
+                      target: ApplyTarget,
+                      name: String,
+                      mut rx: mpsc::Receiver<(Vec<StorageEntry>, ApplyPosition)>,
+                      done: mpsc::UnboundedSender<LaneDone>) {
+        let names = [name];
+        while let Some((entries, position)) = rx.recv().await {
+            let first_index = *entries[0].index();
+            let rs = Self::apply_transaction(&mut conn, &target, &names, &entries, position).await;
+            let failed = rs.is_err();
+            if done.send((first_index, rs)).is_err() || failed {
+                break;
+            }
+        }
+        if let Err(e) = conn.close().await {
+            warn!("close relay log apply lane {} err: {:?}", lane, e);
+        }
+    }
+
+    /// 在一个事务中回放 entries 并把 names 对应的位点更新为 position
+    async fn apply_transaction(conn: &mut AsyncConnection,
+                               target: &ApplyTarget,
+                               names: &[String],
+                               entries: &[StorageEntry],
+                               position: ApplyPosition) -> CResult<ApplyPosition> {
+        conn.execute("BEGIN".to_string()).await?;
+        let mut rs = Self::apply_entries(conn, target, entries).await;
+        if rs.is_ok() {
+            rs = conn.execute(Self::position_sql(&target.position_table, names, &position)).await;
+        }
+        if let Err(e) = rs {
+            if let Err(rollback) = conn.execute("ROLLBACK".to_string()).await {
+                warn!("relay log apply rollback err: {:?}", rollback);
+            }
+            return Err(e);
+        }
+        conn.execute("COMMIT".to_string()).await?;
+
+        Ok(position)
+    }
+
+    /// 在当前事务中逐条执行回放语句, 检查冲突
+    async fn apply_entries(conn: &mut AsyncConnection, target: &ApplyTarget, entries: &[StorageEntry]) -> CResult<()> {
+        for entry in entries {
+            let log = match target.transforms.apply(entry.relay_log().clone())? {
+                Some(log) => log,
+                None => continue,
+            };
+            let policy = target.policies.policy(log.database_name(), log.table_name());
+            for statement in SqlBuilder::statements(&log, policy)? {
+                let kind = match conn.execute_update(statement.sql.clone()).await? {
+                    Ok(ok) if statement.expect_rows && ok.affected_rows == 0 => ConflictKind::Missing,
+                    Ok(_) => continue,
+                    Err(e) if e.error_code == ER_DUP_ENTRY && policy != ConflictPolicy::Error => ConflictKind::Duplicate,
+                    Err(e) => return Err(ReError::String(format!("Execute error. {} {}: {}",
+                                                                  e.error_code, e.error_message, statement.sql))),
+                };
+                Self::resolve_conflict(conn, target, *entry.index(), &log, policy, kind, &statement).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 按冲突处理方式处理一条冲突的语句
+    async fn resolve_conflict(conn: &mut AsyncConnection,
+                              target: &ApplyTarget,
+                              index: u64,
+                              log: &RelayLog,
+                              policy: ConflictPolicy,
+                              kind: ConflictKind,
+                              statement: &ApplyStatement) -> CResult<()> {
+        match policy {
+            ConflictPolicy::Error => Err(ReError::String(format!("relay log apply {} conflict ({}) at index {}: {}",
+                                                                 target.name, kind.name(), index, statement.sql))),
+            ConflictPolicy::Skip => {
+                warn!("relay log apply {} skip conflict ({}) at index {}: {}", target.name, kind.name(), index, statement.sql);
+                Ok(())
+            }
+            ConflictPolicy::Overwrite => match &statement.overwrite {
+                Some(sql) => conn.execute(sql.clone()).await,
+                // Delete 找不到行
+                None => Ok(()),
+            },
+            ConflictPolicy::Record => {
+                warn!("relay log apply {} record conflict ({}) at index {}.", target.name, kind.name(), index);
+                conn.execute(format!("INSERT INTO {} (name, entry_index, database_name, table_name, kind, statement) \
+                    VALUES ({}, {}, {}, {}, {}, {})", target.conflict_table, SqlBuilder::quote(&target.name), index,
+                                     SqlBuilder::quote(log.database_name()), SqlBuilder::quote(log.table_name()),
+                                     SqlBuilder::quote(kind.name()), SqlBuilder::quote(&statement.sql))).await
+            }
+        }
+    }
+
+    /// 执行一个事务, 失败时回滚
+    async fn execute_transaction(conn: &mut AsyncConnection, sqls: Vec<String>) -> CResult<()> {
+        conn.execute("BEGIN".to_string()).await?;
+        for sql in sqls {
+            if let Err(e) = conn.execute(sql).await {
+                if let Err(rollback) = conn.execute("ROLLBACK".to_string()).await {
+                    warn!("relay log apply rollback err: {:?}", rollback);
+                }
+                return Err(e);
+            }
+        }
+        conn.execute("COMMIT".to_string()).await
+    }
+
+    fn position_sql(position_table: &str, names: &[String], position: &ApplyPosition) -> String {
+        let values: Vec<String> = names.iter()
+            .map(|n| format!("({}, {}, {}, {}, {}, {})", SqlBuilder::quote(n), position.next_index, position.event_log_pos,
+                             position.event_timestamp, SqlBuilder::quote(&position.binlog_file), SqlBuilder::quote(&position.gtid)))
+            .collect();
+        format!("INSERT INTO {} (name, next_index, event_log_pos, event_timestamp, binlog_file, gtid) VALUES {} \
+            ON DUPLICATE KEY UPDATE next_index = VALUES(next_index), event_log_pos = VALUES(event_log_pos), \
+            event_timestamp = VALUES(event_timestamp), binlog_file = VALUES(binlog_file), gtid = VALUES(gtid)",
+                position_table, values.join(", "))
+    }
+
+    /// 读取本任务及其各 lane 的位点
+    async fn load_positions(&mut self) -> CResult<BTreeMap<String, ApplyPosition>> {
+        let rows = self.conn.query(format!("SELECT name, next_index, event_log_pos, event_timestamp, binlog_file, gtid FROM {} WHERE name = {} OR name LIKE {}",
+                                           self.position_table_name(), SqlBuilder::quote(&self.name),
+                                           SqlBuilder::quote(&format!("{}#%", self.name)))).await?;
+        self.parse_positions(rows)
+    }
+
+    /// 位点表中的行 (name, next_index, event_log_pos, event_timestamp, binlog_file, gtid), 只保留本任务及其各 lane 的位点
+    pub fn parse_positions(&self, rows: Vec<RowString>) -> CResult<BTreeMap<String, ApplyPosition>> {
+        let lane_prefix = format!("{}#", self.name);
+        let mut positions = BTreeMap::new();
+        for row in rows {
+            let name = row.as_slice().first().cloned().flatten().unwrap_or_default();
+            // LIKE 中的 _ 与 % 会匹配其它任务
+            if name != self.name && !name.strip_prefix(&lane_prefix).map_or(false, |lane| lane.parse::<usize>().is_ok()) {
+                continue;
+            }
+            let value = |i: usize| -> CResult<u64> {
+                row.as_slice().get(i)
+                    .and_then(|v| v.as_ref())
+                    .ok_or(ReError::String(format!("invalid apply position of {}.", name)))?
+                    .parse::<u64>()
+                    .map_err(|e| ReError::String(format!("invalid apply position of {}: {}", name, e)))
+            };
+            let text = |i: usize| row.as_slice().get(i).cloned().flatten().unwrap_or_default();
+            let position = ApplyPosition {
+                next_index: value(1)?,
+                event_log_pos: value(2)?,
+                event_timestamp: value(3)? as u32,
+                binlog_file: text(4),
+                gtid: text(5),
+            };
+            positions.insert(name, position);
+        }
+        Ok(positions)
+    }
+
+    /// 各 lane 位点的名称, 串行回放时为任务名
+    fn lane_names(&self) -> Vec<String> {
+        if self.parallelism == 1 {
+            vec![self.name.clone()]
+        } else {
+            (0..self.parallelism).map(|i| format!("{}#{}", self.name, i)).collect()
+        }
+    }
+
+    fn position_table_name(&self) -> String {
+        format!("{}.{}", SqlBuilder::ident(&self.position_schema), SqlBuilder::ident(&self.position_table))
+    }
+
+    fn conflict_table_name(&self) -> String {
+        format!("{}.{}", SqlBuilder::ident(&self.position_schema), SqlBuilder::ident(DEFAULT_CONFLICT_TABLE))
+    }
 
-}
\ No newline at end of file
+    fn target(&self) -> ApplyTarget {
+        ApplyTarget {
+            name: self.name.clone(),
+            position_table: self.position_table_name(),
+            conflict_table: self.conflict_table_name(),
+            policies: self.policies.clone(),
+            transforms: self.transforms.clone(),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+/// 计算吞吐量的时间窗口
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// 回放任务的进度: 已回放的位点与中继日志中最新的位点, 可交给其它线程读取.
+///
+/// 吞吐量为最近 10 秒的平均值, ETA 按 entries/sec 估算追平最新位点的时间
+#[derive(Debug, Clone)]
+pub struct ReplayProgress {
+    inner: Arc<ProgressInner>,
+}
+
+#[derive(Debug)]
+struct ProgressInner {
+    name: String,
+    // 本次启动时的 next_index, 作为进度百分比的起点
+    start_index: AtomicU64,
+    // 下一个待回放的 entry index
+    next_index: AtomicU64,
+    // 中继日志中最后一个 entry index
+    newest_index: AtomicU64,
+    applied_rows: AtomicU64,
+    applied_transactions: AtomicU64,
+    // (时刻, next_index, applied_rows)
+    samples: Mutex<VecDeque<(Instant, u64, u64)>>,
+}
+
+/// 某一时刻的回放进度
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayStatus {
+    pub name: String,
+    pub next_index: u64,
+    pub newest_index: u64,
+    // 未回放的entry数量
+    pub lag_entries: u64,
+    // 本次启动以来的进度 [0, 100]
+    pub progress_percent: f64,
+    pub applied_rows: u64,
+    pub applied_transactions: u64,
+    pub rows_per_sec: f64,
+    pub entries_per_sec: f64,
+    // 预计追平的秒数, 没有吞吐量时为 None
+    pub eta_seconds: Option<u64>,
+    // 生成时刻, unix 秒
+    pub updated_at: u64,
+}
+
+impl ReplayProgress {
+    pub fn new(name: &str) -> Self {
+        Self {
+            inner: Arc::new(ProgressInner {
+                name: name.to_string(),
+                start_index: AtomicU64::new(0),
+                next_index: AtomicU64::new(0),
+                newest_index: AtomicU64::new(0),
+                applied_rows: AtomicU64::new(0),
+                applied_transactions: AtomicU64::new(0),
+                samples: Mutex::new(VecDeque::new()),
+            }),
+        }
+    }
+
+    /// 回放开始, next_index 为上次回放到的位点
+    pub fn start(&self, next_index: u64) {
+        self.inner.start_index.store(next_index, Ordering::Relaxed);
+        self.inner.next_index.store(next_index, Ordering::Relaxed);
+        let mut samples = self.inner.samples.lock().unwrap();
+        samples.clear();
+        samples.push_back((Instant::now(), next_index, self.inner.applied_rows.load(Ordering::Relaxed)));
+    }
+
+    /// 中继日志中最后一个 entry index
+    pub fn set_newest(&self, newest_index: u64) {
+        self.inner.newest_index.fetch_max(newest_index, Ordering::Relaxed);
+    }
+
+    /// 一个事务回放完成, next_index 为连续回放完成的位点
+    pub fn record_applied(&self, next_index: u64, rows: u64) {
+        let inner = &self.inner;
+        inner.next_index.store(next_index, Ordering::Relaxed);
+        inner.newest_index.fetch_max(next_index.saturating_sub(1), Ordering::Relaxed);
+        let applied_rows = inner.applied_rows.fetch_add(rows, Ordering::Relaxed) + rows;
+        inner.applied_transactions.fetch_add(1, Ordering::Relaxed);
+
+        let now = Instant::now();
+        let mut samples = inner.samples.lock().unwrap();
+        samples.push_back((now, next_index, applied_rows));
+        // 保留窗口之前的最后一个样本作为起点
+        while samples.len() > 2 && now.duration_since(samples[1].0) >= RATE_WINDOW {
+            samples.pop_front();
+        }
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        let inner = &self.inner;
+        let start_index = inner.start_index.load(Ordering::Relaxed);
+        let next_index = inner.next_index.load(Ordering::Relaxed);
+        let newest_index = inner.newest_index.load(Ordering::Relaxed);
+        let lag_entries = (newest_index + 1).saturating_sub(next_index);
+
+        let total = (newest_index + 1).saturating_sub(start_index);
+        let progress_percent = if total == 0 || lag_entries == 0 {
+            100.0
+        } else {
+            (next_index.saturating_sub(start_index) as f64 / total as f64 * 100.0).clamp(0.0, 100.0)
+        };
+
+        let (rows_per_sec, entries_per_sec) = {
+            let samples = inner.samples.lock().unwrap();
+            match (samples.front(), samples.back()) {
+                (Some(first), Some(last)) if last.0 > first.0 => {
+                    let secs = last.0.duration_since(first.0).as_secs_f64();
+                    ((last.2 - first.2) as f64 / secs, (last.1.saturating_sub(first.1)) as f64 / secs)
+                }
+                _ => (0.0, 0.0),
+            }
+        };
+        let eta_seconds = if lag_entries == 0 {
+            Some(0)
+        } else if entries_per_sec > 0.0 {
+            Some((lag_entries as f64 / entries_per_sec).ceil() as u64)
+        } else {
+            None
+        };
+
+        ReplayStatus {
+            name: inner.name.clone(),
+            next_index,
+            newest_index,
+            lag_entries,
+            progress_percent,
+            applied_rows: inner.applied_rows.load(Ordering::Relaxed),
+            applied_transactions: inner.applied_transactions.load(Ordering::Relaxed),
+            rows_per_sec,
+            entries_per_sec,
+            eta_seconds,
+            updated_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+        }
+    }
+}
+
+impl ReplayStatus {
+    /// 写入状态文件夹中的 {name}.json, 供 CLI `status` 与 web 读取
+    pub fn save(&self, status_dir: &str) -> CResult<()> {
+        fs::create_dir_all(status_dir)?;
+        let path = PathBuf::from(status_dir).join(format!("{}.json", self.name));
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| ReError::String(e.to_string()))?;
+        let tmp = path.with_extension("tmp");
+        {
+            let mut f = File::create(&tmp)?;
+            f.write_all(&bytes)?;
+        }
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// 读取状态文件夹中全部回放任务的进度, 按任务名排序; 文件夹不存在时为空
+    pub fn load_dir(status_dir: &str) -> CResult<Vec<ReplayStatus>> {
+        let path = PathBuf::from(status_dir);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let mut statuses = vec![];
+        for f in path.read_dir()?.flatten() {
+            let file = f.path();
+            if file.extension().map_or(false, |e| e == "json") {
+                let status: ReplayStatus = serde_json::from_slice(&fs::read(&file)?)
+                    .map_err(|e| ReError::String(format!("read replay status {:?} err: {}", file, e)))?;
+                statuses.push(status);
+            }
+        }
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(statuses)
+    }
+}
+
+impl Display for ReplayStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let eta = match self.eta_seconds {
+            Some(s) => format!("{}s", s),
+            None => String::from("-"),
+        };
+        write!(f, "replay {}: {}/{} ({:.1}%), lag {} entries, {:.0} rows/s, eta {}",
+               self.name, self.next_index.saturating_sub(1), self.newest_index, self.progress_percent,
+               self.lag_entries, self.rows_per_sec, eta)
+    }
+}
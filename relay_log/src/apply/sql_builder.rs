@@ -0,0 +1,175 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use common::schema::data_type::Value;
+
+use crate::apply::conflict::ConflictPolicy;
+use crate::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+
+/// 一条回放语句
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyStatement {
+    pub sql: String,
+    // 是否必须影响至少一行, Update/Delete 影响 0 行即目标库中没有变更前的行
+    pub expect_rows: bool,
+    // 冲突时用于覆盖的语句
+    pub overwrite: Option<String>,
+}
+
+impl ApplyStatement {
+    fn new(sql: String, expect_rows: bool, overwrite: Option<String>) -> Self {
+        Self {
+            sql,
+            expect_rows,
+            overwrite,
+        }
+    }
+}
+
+/// 把中继日志转换为在目标库执行的SQL。
+///
+/// 中继日志中没有主键信息, Delete/Update 以变更前的全部列定位行(NULL 使用 `<=>` 比较), 每条语句只影响一行
+pub struct SqlBuilder;
+
+impl SqlBuilder {
+    /// 一条中继日志对应的SQL, 提交标记与不带语句的DDL没有SQL
+    pub fn build(log: &RelayLog) -> CResult<Vec<String>> {
+        Ok(Self::statements(log, ConflictPolicy::Error)?.into_iter().map(|s| s.sql).collect())
+    }
+
+    /// 按冲突处理方式生成回放语句: 跳过或记录冲突时 Insert 逐行执行, 覆盖时 Insert 使用 REPLACE
+    pub fn statements(log: &RelayLog, policy: ConflictPolicy) -> CResult<Vec<ApplyStatement>> {
+        let table = format!("{}.{}", Self::ident(log.database_name()), Self::ident(log.table_name()));
+        let columns = log.columns();
+        let names: Vec<String> = columns.iter().map(|c| Self::ident(c.column_name())).collect();
+        let values = |row: &RelayRowData| -> String {
+            let row_values: Vec<String> = row.values().iter().map(Self::literal).collect();
+            format!("({})", row_values.join(", "))
+        };
+
+        let statements = match log.relay_command() {
+            RelayCommand::Insert(rows) => {
+                for row in rows {
+                    Self::check_row(log, row)?;
+                }
+                match policy {
+                    _ if rows.is_empty() => vec![],
+                    ConflictPolicy::Error | ConflictPolicy::Overwrite => {
+                        let verb = if policy == ConflictPolicy::Overwrite { "REPLACE" } else { "INSERT" };
+                        let values: Vec<String> = rows.iter().map(values).collect();
+                        vec![ApplyStatement::new(format!("{} INTO {} ({}) VALUES {}", verb, table, names.join(", "), values.join(", ")), false, None)]
+                    }
+                    ConflictPolicy::Skip | ConflictPolicy::Record => {
+                        rows.iter()
+                            .map(|r| ApplyStatement::new(format!("INSERT INTO {} ({}) VALUES {}", table, names.join(", "), values(r)), false, None))
+                            .collect()
+                    }
+                }
+            }
+            RelayCommand::Delete(rows) => {
+                let mut statements = Vec::with_capacity(rows.len());
+                for row in rows {
+                    Self::check_row(log, row)?;
+                    let sql = format!("DELETE FROM {} WHERE {} LIMIT 1", table, Self::condition(columns, row));
+                    statements.push(ApplyStatement::new(sql, true, None));
+                }
+                statements
+            }
+            RelayCommand::Update(rows) => {
+                let mut statements = Vec::with_capacity(rows.len());
+                for (before, after) in rows {
+                    Self::check_row(log, before)?;
+                    Self::check_row(log, after)?;
+                    let set: Vec<String> = columns.iter()
+                        .zip(after.values())
+                        .map(|(c, v)| format!("{} = {}", Self::ident(c.column_name()), Self::literal(v)))
+                        .collect();
+                    let sql = format!("UPDATE {} SET {} WHERE {} LIMIT 1", table, set.join(", "), Self::condition(columns, before));
+                    // 未设置 CLIENT_FOUND_ROWS, 值没有变化的 Update 影响 0 行
+                    let changed = format!("{:?}", before.values()) != format!("{:?}", after.values());
+                    let overwrite = format!("REPLACE INTO {} ({}) VALUES {}", table, names.join(", "), values(after));
+                    statements.push(ApplyStatement::new(sql, changed, Some(overwrite)));
+                }
+                statements
+            }
+            _ => vec![],
+        };
+
+        Ok(statements)
+    }
+
+    /// 标识符, 反引号转义
+    pub fn ident(name: &str) -> String {
+        format!("`{}`", name.replace('`', "``"))
+    }
+
+    /// 字符串字面量, 按 MySQL 默认的 sql_mode 转义
+    pub fn quote(s: &str) -> String {
+        let mut quoted = String::with_capacity(s.len() + 2);
+        quoted.push('\'');
+        for c in s.chars() {
+            match c {
+                '\'' => quoted.push_str("\\'"),
+                '\\' => quoted.push_str("\\\\"),
+                '\0' => quoted.push_str("\\0"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\x1a' => quoted.push_str("\\Z"),
+                _ => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        quoted
+    }
+
+    /// 值的SQL字面量, 日期时间类型为毫秒时间戳
+    pub fn literal(value: &Value) -> String {
+        match value {
+            Value::Null => "NULL".to_string(),
+            Value::Boolean(v) => (*v as u8).to_string(),
+            Value::Byte(v) => v.to_string(),
+            Value::Short(v) => v.to_string(),
+            Value::Int(v) => v.to_string(),
+            Value::Long(v) => v.to_string(),
+            Value::UnsignedByte(v) => v.to_string(),
+            Value::UnsignedShort(v) => v.to_string(),
+            Value::UnsignedInt(v) => v.to_string(),
+            Value::UnsignedLong(v) | Value::Bitmap(v) => v.to_string(),
+            Value::Float(v) if v.is_finite() => v.to_string(),
+            Value::Double(v) if v.is_finite() => v.to_string(),
+            Value::Float(_) | Value::Double(_) => "NULL".to_string(),
+            Value::String(v) | Value::JSON(v) | Value::Decimal(v) | Value::Enum(v) | Value::Set(v) => Self::quote(v),
+            Value::Date(ms) => Self::quote(&Self::datetime(*ms).format("%Y-%m-%d").to_string()),
+            Value::Time(ms) => Self::quote(&Self::datetime(*ms).format("%H:%M:%S%.3f").to_string()),
+            Value::DateTime(ms) => Self::quote(&Self::datetime(*ms).format("%Y-%m-%d %H:%M:%S%.3f").to_string()),
+            // TIMESTAMP 与会话时区无关
+            Value::Timestamp(ms) => format!("FROM_UNIXTIME({}.{:03})", ms.div_euclid(1000), ms.rem_euclid(1000)),
+            Value::Binary(v) | Value::Bytes(v) | Value::Blob(v) => {
+                let hex: String = v.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("X'{}'", hex)
+            }
+        }
+    }
+
+    fn datetime(ms: i64) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default()
+            .and_hms_opt(0, 0, 0).unwrap_or_default() + Duration::milliseconds(ms)
+    }
+
+    /// 以全部列定位一行
+    fn condition(columns: &[RelayColumnInfo], row: &RelayRowData) -> String {
+        let conditions: Vec<String> = columns.iter()
+            .zip(row.values())
+            .map(|(c, v)| format!("{} <=> {}", Self::ident(c.column_name()), Self::literal(v)))
+            .collect();
+        conditions.join(" AND ")
+    }
+
+    fn check_row(log: &RelayLog, row: &RelayRowData) -> CResult<()> {
+        if row.values().len() != log.columns().len() || log.columns().iter().any(|c| c.column_name().is_empty()) {
+            return Err(ReError::String(format!("row of {}.{} does not match its columns.", log.database_name(), log.table_name())));
+        }
+        Ok(())
+    }
+}
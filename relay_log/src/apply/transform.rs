@@ -0,0 +1,297 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use common::schema::column_mapping::{ColumnMapping, MappingRule};
+use common::schema::data_type::{DstColumnType, Value};
+
+use crate::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+
+/// 回放前对中继日志的变换, 在读取中继日志之后、生成SQL之前执行。
+///
+/// 返回 None 时丢弃整条日志; 自定义的变换通过 [TransformChain::with_transform] 注册
+pub trait RelayLogTransform: Debug + Send + Sync {
+    fn name(&self) -> String;
+
+    fn transform(&self, log: RelayLog) -> CResult<Option<RelayLog>>;
+}
+
+/// 声明式的变换规则, 对应配置中的 [[transform]]:
+///
+/// ```toml
+/// [[transform]]
+/// type = "rename_table"
+/// table = "db1.t1"
+/// to = "db2.t2"
+///
+/// [[transform]]
+/// type = "filter_rows"
+/// table = "db1.*"
+/// column = "tenant_id"
+/// values = ["1", "2"]
+/// ```
+///
+/// table 的格式为 `[db.]table`, 不指定库时匹配任意库, `*` 匹配任意库或表
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformRule {
+    /// 修改库名
+    RenameDatabase { database: String, to: String },
+    /// 修改表名, to 为 `[db.]table`, 不指定库时保留原库名
+    RenameTable { table: String, to: String },
+    /// 修改列名
+    RenameColumn { table: String, column: String, to: String },
+    /// 把列的值替换为 value, 不指定时为 NULL。 Update/Delete 变更前的值同样替换, 以便在目标库中定位已替换的行
+    SetValue { table: String, column: String, value: Option<String> },
+    /// 只保留列值在 values 中的行, exclude 时丢弃这些行。 Update 按变更后的值判断
+    FilterRows {
+        table: String,
+        column: String,
+        values: Vec<String>,
+        #[serde(default)]
+        exclude: bool,
+    },
+}
+
+impl TransformRule {
+    fn table(&self) -> Option<&str> {
+        match self {
+            TransformRule::RenameDatabase { .. } => None,
+            TransformRule::RenameTable { table, .. } |
+            TransformRule::RenameColumn { table, .. } |
+            TransformRule::SetValue { table, .. } |
+            TransformRule::FilterRows { table, .. } => Some(table),
+        }
+    }
+
+    /// `[db.]table` 是否匹配
+    fn matches(pattern: &str, database: &str, table: &str) -> bool {
+        let (db, tbl) = match pattern.split_once('.') {
+            Some((db, tbl)) => (Some(db), tbl),
+            None => (None, pattern),
+        };
+        db.map_or(true, |db| db == "*" || db == database) && (tbl == "*" || tbl == table)
+    }
+
+    /// 列的位置, 没有该列时为 None
+    fn column_index(log: &RelayLog, column: &str) -> Option<usize> {
+        log.columns().iter().position(|c| c.column_name() == column)
+    }
+}
+
+impl RelayLogTransform for TransformRule {
+    fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn transform(&self, mut log: RelayLog) -> CResult<Option<RelayLog>> {
+        if let Some(table) = self.table() {
+            if !Self::matches(table, log.database_name(), log.table_name()) {
+                return Ok(Some(log));
+            }
+        }
+
+        match self {
+            TransformRule::RenameDatabase { database, to } => {
+                if log.database_name() == database {
+                    log.set_database_name(to.clone());
+                }
+            }
+            TransformRule::RenameTable { to, .. } => {
+                match to.split_once('.') {
+                    Some((db, tbl)) => {
+                        log.set_database_name(db.to_string());
+                        log.set_table_name(tbl.to_string());
+                    }
+                    None => {
+                        log.set_table_name(to.clone());
+                    }
+                }
+            }
+            TransformRule::RenameColumn { column, to, .. } => {
+                if let Some(i) = Self::column_index(&log, column) {
+                    let mut columns = log.columns().clone();
+                    columns[i].set_column_name(to.clone());
+                    log.set_columns(columns);
+                }
+            }
+            TransformRule::SetValue { column, value, .. } => {
+                if let Some(i) = Self::column_index(&log, column) {
+                    let value = value.as_ref().map_or(Value::Null, |v| Value::String(v.clone()));
+                    let set = |row: &mut RelayRowData| {
+                        let mut values = row.values().clone();
+                        if let Some(v) = values.get_mut(i) {
+                            *v = value.clone();
+                        }
+                        row.set_values(values);
+                    };
+                    let command = match log.relay_command().clone() {
+                        RelayCommand::Insert(mut rows) => {
+                            rows.iter_mut().for_each(set);
+                            RelayCommand::Insert(rows)
+                        }
+                        RelayCommand::Delete(mut rows) => {
+                            rows.iter_mut().for_each(set);
+                            RelayCommand::Delete(rows)
+                        }
+                        RelayCommand::Update(mut rows) => {
+                            rows.iter_mut().for_each(|(before, after)| {
+                                set(before);
+                                set(after);
+                            });
+                            RelayCommand::Update(rows)
+                        }
+                        c => c,
+                    };
+                    log.set_relay_command(command);
+                }
+            }
+            TransformRule::FilterRows { column, values, exclude, .. } => {
+                let i = match Self::column_index(&log, column) {
+                    Some(i) => i,
+                    None => return Ok(Some(log)),
+                };
+                let keep = |row: &RelayRowData| {
+                    let text = row.values().get(i).and_then(Value::text);
+                    text.map_or(false, |t| values.contains(&t)) != *exclude
+                };
+                let command = match log.relay_command().clone() {
+                    RelayCommand::Insert(rows) => RelayCommand::Insert(rows.into_iter().filter(keep).collect()),
+                    RelayCommand::Delete(rows) => RelayCommand::Delete(rows.into_iter().filter(keep).collect()),
+                    RelayCommand::Update(rows) => RelayCommand::Update(rows.into_iter().filter(|(_, after)| keep(after)).collect()),
+                    c => c,
+                };
+                let empty = match &command {
+                    RelayCommand::Insert(rows) | RelayCommand::Delete(rows) => rows.is_empty(),
+                    RelayCommand::Update(rows) => rows.is_empty(),
+                    _ => false,
+                };
+                if empty {
+                    return Ok(None);
+                }
+                log.set_relay_command(command);
+            }
+        }
+
+        Ok(Some(log))
+    }
+}
+
+/// 配置中 [[mapping]] 的表与列映射: 修改库表名、列名, 删除列, 转换类型与计算列。
+///
+/// 行变更按日志中的列计算映射, 其余日志只修改库表名
+impl RelayLogTransform for ColumnMapping {
+    fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn transform(&self, mut log: RelayLog) -> CResult<Option<RelayLog>> {
+        let is_rows = matches!(log.relay_command(), RelayCommand::Insert(_) | RelayCommand::Delete(_) | RelayCommand::Update(_));
+        if !is_rows {
+            let (database, table) = self.map_table_name(log.database_name(), log.table_name());
+            log.set_database_name(database);
+            log.set_table_name(table);
+            return Ok(Some(log));
+        }
+
+        let columns: Vec<(String, DstColumnType)> = log.columns().iter()
+            .map(|c| (c.column_name().clone(), *c.column_type()))
+            .collect();
+        let mapping = self.map_table(log.database_name(), log.table_name(), &columns)?;
+        let map = |row: RelayRowData| -> CResult<RelayRowData> {
+            let mut mapped = RelayRowData::default();
+            mapped.set_values(mapping.map_row(row.values())?);
+            Ok(mapped)
+        };
+        let command = match log.relay_command().clone() {
+            RelayCommand::Insert(rows) => RelayCommand::Insert(rows.into_iter().map(map).collect::<CResult<_>>()?),
+            RelayCommand::Delete(rows) => RelayCommand::Delete(rows.into_iter().map(map).collect::<CResult<_>>()?),
+            RelayCommand::Update(rows) => RelayCommand::Update(rows.into_iter()
+                .map(|(before, after)| Ok((map(before)?, map(after)?)))
+                .collect::<CResult<_>>()?),
+            c => c,
+        };
+
+        log.set_database_name(mapping.database.clone());
+        log.set_table_name(mapping.table.clone());
+        log.set_columns(mapping.columns.iter().map(|(name, column_type)| {
+            let mut column = RelayColumnInfo::default();
+            column.set_column_name(name.clone());
+            column.set_column_type(*column_type);
+            column
+        }).collect());
+        log.set_relay_command(command);
+        Ok(Some(log))
+    }
+}
+
+/// 配置文件中的变换规则
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransformConfig {
+    #[serde(default)]
+    pub transform: Vec<TransformRule>,
+    /// [[mapping]], 在 [[transform]] 之后执行
+    #[serde(default)]
+    pub mapping: Vec<MappingRule>,
+}
+
+/// 按注册顺序执行的变换, 任一变换丢弃日志后不再执行后续变换
+#[derive(Debug, Clone, Default)]
+pub struct TransformChain {
+    transforms: Vec<Arc<dyn RelayLogTransform>>,
+}
+
+impl TransformChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_rules(rules: Vec<TransformRule>) -> Self {
+        let mut chain = Self::new();
+        for rule in rules {
+            chain = chain.with_transform(Arc::new(rule));
+        }
+        chain
+    }
+
+    /// 读取 TOML 中的 [[transform]] 与 [[mapping]]
+    pub fn from_toml(toml: &str) -> CResult<Self> {
+        let config: TransformConfig = toml::from_str(toml)
+            .map_err(|e| ReError::ConfigFileParseErr(format!("[transform] {}", e)))?;
+        let mapping = ColumnMapping::new(config.mapping)
+            .map_err(|e| ReError::ConfigFileParseErr(format!("[mapping] {}", e)))?;
+        Ok(Self::from_rules(config.transform).with_mapping(mapping))
+    }
+
+    /// 注册 [[mapping]] 的映射, 在已注册的变换之后执行
+    pub fn with_mapping(self, mapping: ColumnMapping) -> Self {
+        if mapping.is_empty() {
+            return self;
+        }
+        self.with_transform(Arc::new(mapping))
+    }
+
+    /// 注册变换, 在已注册的变换之后执行
+    pub fn with_transform(mut self, transform: Arc<dyn RelayLogTransform>) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    pub fn apply(&self, log: RelayLog) -> CResult<Option<RelayLog>> {
+        let mut log = log;
+        for transform in &self.transforms {
+            log = match transform.transform(log)? {
+                Some(log) => log,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(log))
+    }
+}
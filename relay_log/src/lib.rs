@@ -1,6 +1,7 @@
 
 pub mod storage;
 pub mod apply;
+pub mod snapshot;
 pub mod codec;
 pub mod relay_log_server;
 pub mod relay_log_server_machine;
@@ -1,4 +1,3 @@
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use getset::{Getters, Setters};
 use serde::{Deserialize, Serialize};
 use tracing::warn;
@@ -8,10 +7,9 @@ use binlog::events::declare::log_event::LogEvent;
 use binlog::events::declare::rows_log_event::RowsLogEvent;
 use binlog::events::protocol::table_map_event::ColumnInfo;
 use binlog::row::row_data::RowData;
-use common::binlog::column::column_type::SrcColumnType;
-use common::binlog::column::column_value::SrcColumnValue;
 use common::binlog::src_meta::SrcType;
 use common::schema::data_type::{DstColumnType, Value};
+use common::schema::type_conversion::ColumnConversion;
 
 /// 中继日志信息
 #[derive(Serialize, Deserialize, Debug, Clone, Getters, Setters)]
@@ -24,6 +22,10 @@ pub struct RelayLog {
     #[getset(get = "pub", set = "pub")]
     event_log_pos: u64,
 
+    /// binlog event timestamp, 秒
+    #[getset(get = "pub", set = "pub")]
+    event_timestamp: u32,
+
     /// binlog event name
     #[getset(get = "pub", set = "pub")]
     event_name: String,
@@ -45,6 +47,34 @@ pub struct RelayLog {
     relay_command: RelayCommand,
 }
 
+/// 版本 1 的segment中的中继日志, 没有 event_timestamp。 bincode 按字段顺序编码, 不能直接解码为 [RelayLog]
+#[derive(Deserialize, Debug)]
+pub(crate) struct RelayLogV1 {
+    src_type: SrcType,
+    event_log_pos: u64,
+    event_name: String,
+    database_name: String,
+    table_name: String,
+    columns: Vec<RelayColumnInfo>,
+    relay_command: RelayCommand,
+}
+
+impl From<RelayLogV1> for RelayLog {
+    /// 旧日志没有事件时间戳, 记为 0
+    fn from(log: RelayLogV1) -> Self {
+        Self {
+            src_type: log.src_type,
+            event_log_pos: log.event_log_pos,
+            event_timestamp: 0,
+            event_name: log.event_name,
+            database_name: log.database_name,
+            table_name: log.table_name,
+            columns: log.columns,
+            relay_command: log.relay_command,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum RelayCommand {
     None,
@@ -59,6 +89,12 @@ pub enum RelayCommand {
     Delete(Vec<RelayRowData>),
     /// 更新数据: (deleteRows, insertRows)
     Update(Vec<(RelayRowData, RelayRowData)>),
+    /// 事务提交(xid), 之前的日志属于同一个事务
+    Commit(u64),
+    /// 源库切换到新的 binlog 文件(文件名), 之后日志的 event_log_pos 属于该文件
+    Rotate(String),
+    /// 之后的事务在源库中的 GTID, 如 `uuid:23`
+    Gtid(String),
 }
 
 /// 列信息
@@ -74,97 +110,27 @@ pub struct RelayColumnInfo {
 }
 
 impl RelayColumnInfo {
-    fn from_binlog_column_info(binlog_column_info: &ColumnInfo) -> Self {
-        let column_name = binlog_column_info.get_name();
-        if let Some(binlog_column_type) = binlog_column_info.get_c_type().take() {
-            let column_type = match binlog_column_type {
-                SrcColumnType::Null => {
-                    DstColumnType::Null
-                }
-                // Decimal
-                SrcColumnType::Decimal |
-                SrcColumnType::NewDecimal => {
-                    DstColumnType::Decimal
-                }
-                // Int
-                SrcColumnType::Tiny |
-                SrcColumnType::Int24 |
-                SrcColumnType::Year => {
-                    DstColumnType::Int
-                }
-                SrcColumnType::Short => {
-                    DstColumnType::Short
-                }
-                // Long
-                SrcColumnType::Long |
-                SrcColumnType::LongLong => {
-                    DstColumnType::Long
-                }
-                SrcColumnType::Float => {
-                    DstColumnType::Float
-                }
-                SrcColumnType::Double => {
-                    DstColumnType::Double
-                }
-                // Timestamp
-                SrcColumnType::Timestamp |
-                SrcColumnType::Timestamp2 => {
-                    DstColumnType::Timestamp
-                }
-                // Date
-                SrcColumnType::Date |
-                SrcColumnType::NewDate => {
-                    DstColumnType::Date
-                }
-                // Time
-                SrcColumnType::Time |
-                SrcColumnType::Time2 => {
-                    DstColumnType::Time
-                }
-                // DateTime
-                SrcColumnType::DateTime |
-                SrcColumnType::DateTime2 => {
-                    DstColumnType::DateTime
-                }
-                // String
-                SrcColumnType::VarChar |
-                SrcColumnType::Enum |
-                SrcColumnType::Set |
-                SrcColumnType::VarString |
-                SrcColumnType::String |
-                SrcColumnType::Array => {
-                    DstColumnType::String
-                }
-                SrcColumnType::Bit => {
-                    DstColumnType::Bitmap
-                }
-                SrcColumnType::Bool => {
-                    DstColumnType::Boolean
-                }
-                SrcColumnType::Json => {
-                    DstColumnType::JSON
-                }
-                // Blob
-                SrcColumnType::TinyBlob |
-                SrcColumnType::MediumBlob |
-                SrcColumnType::LongBlob |
-                SrcColumnType::Blob => {
-                    DstColumnType::Blob
-                }
-                SrcColumnType::Geometry => {
-                    DstColumnType::Geometry
-                }
-                // todo 未知？
-                SrcColumnType::Invalid => {
-                    DstColumnType::Other
-                }
-            };
-            Self {
-                column_type,
-                column_name,
-            }
-        } else {
-            Self::default()
+    fn from_binlog_column_info(binlog_column_info: &ColumnInfo, conversion: Option<&ColumnConversion>) -> Self {
+        if binlog_column_info.get_c_type().is_none() {
+            return Self::default();
+        }
+        Self {
+            // 不支持的类型
+            column_type: conversion.map_or(DstColumnType::Other, |c| c.dst_type()),
+            column_name: binlog_column_info.get_name(),
+        }
+    }
+}
+
+/// 列的类型转换, 见 [ColumnConversion]; 不支持的源类型返回 None, 该列的值为 NULL
+fn column_conversion(binlog_column_info: &ColumnInfo) -> Option<ColumnConversion> {
+    let src = binlog_column_info.get_real_c_type()?;
+    let enum_values = binlog_column_info.get_set_enum_values().to_vec();
+    match ColumnConversion::new(src, binlog_column_info.is_unsigned(), enum_values) {
+        Ok(conversion) => Some(conversion),
+        Err(e) => {
+            warn!("column {} conversion err: {:?}", binlog_column_info.get_name(), e);
+            None
         }
     }
 }
@@ -186,98 +152,15 @@ pub struct RelayRowData {
 }
 
 impl RelayRowData {
-    fn from_binlog_row(binlog_row: &RowData) -> Self {
-        let values: Vec<Value> = binlog_row.get_cells().iter().map(|c| {
-            if let Some(v) = c {
-                match v {
-                    // `TinyInt`,`SmallInt`,`MediumInt`,`Int` => `Int`
-                    SrcColumnValue::TinyInt(data) => {
-                        Value::Int(*data as i32)
-                    }
-                    SrcColumnValue::SmallInt(data) => {
-                        Value::Int(*data as i32)
-                    }
-                    SrcColumnValue::MediumInt(data) => {
-                        Value::Int(*data as i32)
-                    }
-                    SrcColumnValue::Int(data) => {
-                        Value::Int(*data as i32)
-                    }
-                    // `BigInt` => `Long`
-                    SrcColumnValue::BigInt(data) => {
-                        Value::Long(*data as i64)
-                    }
-                    SrcColumnValue::Float(data) => {
-                        Value::Float(*data)
-                    }
-                    SrcColumnValue::Double(data) => {
-                        Value::Double(*data)
-                    }
-                    SrcColumnValue::Decimal(data) => {
-                        Value::Decimal(data.to_string())
-                    }
-                    SrcColumnValue::String(data) => {
-                        Value::String(data.to_string())
-                    }
-                    SrcColumnValue::Blob(data) => {
-                        Value::Blob(data.to_vec())
-                    }
-                    // Year => Int
-                    SrcColumnValue::Year(data) => {
-                        Value::Int(*data as i32)
-                    }
-                    SrcColumnValue::Date(data) => {
-                        if let Some(naive_date) = NaiveDate::from_ymd_opt(data.year as i32, data.month as u32, data.day as u32) {
-                            if let Some(naive_datetime) = naive_date.and_hms_milli_opt(0, 0, 0, 0) {
-                                Value::Date(naive_datetime.timestamp_millis())
-                            } else {
-                                warn!("Date parse error.");
-                                Value::Null
-                            }
-                        } else {
-                            warn!("Date parse error.");
-                            Value::Null
-                        }
-                    }
-                    SrcColumnValue::Time(data) => {
-                        if let Some(naive_time) = NaiveTime::from_hms_milli_opt(data.hour as u32, data.minute as u32, data.second as u32, data.millis) {
-                            let now_date = Utc::now().date_naive();
-                            Value::Time(NaiveDateTime::new(now_date, naive_time).timestamp_millis())
-                        } else {
-                            warn!("Time parse error.");
-                            Value::Null
-                        }
-                    }
-                    SrcColumnValue::DateTime(data) => {
-                        if let Some(naive_date) = NaiveDate::from_ymd_opt(data.year as i32, data.month as u32, data.day as u32) {
-                            if let Some(naive_datetime) = naive_date.and_hms_milli_opt(data.hour as u32, data.minute as u32, data.second as u32, data.millis) {
-                                Value::DateTime(naive_datetime.timestamp_millis())
-                            } else {
-                                warn!("DateTime parse error.");
-                                Value::Null
-                            }
-                        } else {
-                            warn!("DateTime parse error.");
-                            Value::Null
-                        }
-                    }
-                    SrcColumnValue::Timestamp(data) => {
-                        Value::Timestamp(*data as i64)
-                    }
-
-                    // todo 暂不支持的类型：`Bit`,`Enum`,`Set`
-                    SrcColumnValue::Bit(data) => {
-                        Value::Null
-                    }
-                    SrcColumnValue::Enum(data) => {
-                        Value::Null
-                    }
-                    SrcColumnValue::Set(data) => {
-                        Value::Null
-                    }
-                }
-            } else {
-                Value::Null
+    /// 按列的类型转换, 无法转换的值为 NULL
+    fn from_binlog_row(binlog_row: &RowData, conversions: &[Option<ColumnConversion>]) -> Self {
+        let values: Vec<Value> = binlog_row.get_cells().iter().enumerate().map(|(i, c)| {
+            match conversions.get(i) {
+                Some(Some(conversion)) => conversion.convert(c.as_ref()).unwrap_or_else(|e| {
+                    warn!("column {} value convert err: {:?}", i, e);
+                    Value::Null
+                }),
+                _ => Value::Null,
             }
         }).collect();
 
@@ -303,23 +186,30 @@ impl RelayLog {
             BinlogEvent::WriteRows(e) => {
                 if let Some(table) = e.get_table_map_event() {
                     let event_log_pos = e.get_header().get_log_pos();
+                    let event_timestamp = e.get_header().when;
                     let event_name = e.get_type_name();
+                    let conversions: Vec<Option<ColumnConversion>> = table.get_column_infos()
+                        .iter()
+                        .map(column_conversion)
+                        .collect();
                     let insert_rows: Vec<RelayRowData> = e.get_rows()
                         .iter()
                         .map(|r| {
-                            RelayRowData::from_binlog_row(r)
+                            RelayRowData::from_binlog_row(r, &conversions)
                         }).collect();
                     let database_name = table.get_database_name();
                     let table_name = table.get_table_name();
                     let columns = table.get_column_infos()
                         .iter()
-                        .map(|c| {
-                            RelayColumnInfo::from_binlog_column_info(c)
+                        .zip(&conversions)
+                        .map(|(c, conversion)| {
+                            RelayColumnInfo::from_binlog_column_info(c, conversion.as_ref())
                         }).collect();
                     let relay_command = RelayCommand::Insert(insert_rows);
                     Self {
                         src_type,
                         event_log_pos,
+                        event_timestamp,
                         event_name,
                         database_name,
                         table_name,
@@ -333,23 +223,30 @@ impl RelayLog {
             BinlogEvent::UpdateRows(e) => {
                 if let Some(table) = e.get_table_map_event() {
                     let event_log_pos = e.get_header().get_log_pos();
+                    let event_timestamp = e.get_header().when;
                     let event_name = e.get_type_name();
+                    let conversions: Vec<Option<ColumnConversion>> = table.get_column_infos()
+                        .iter()
+                        .map(column_conversion)
+                        .collect();
                     let update_rows: Vec<(RelayRowData, RelayRowData)> = e.rows
                         .iter()
                         .map(|r| {
-                            (RelayRowData::from_binlog_row(&(r.get_before_update())), RelayRowData::from_binlog_row(&(r.get_after_update())))
+                            (RelayRowData::from_binlog_row(&(r.get_before_update()), &conversions), RelayRowData::from_binlog_row(&(r.get_after_update()), &conversions))
                         }).collect();
                     let database_name = table.get_database_name();
                     let table_name = table.get_table_name();
                     let columns = table.get_column_infos()
                         .iter()
-                        .map(|c| {
-                            RelayColumnInfo::from_binlog_column_info(c)
+                        .zip(&conversions)
+                        .map(|(c, conversion)| {
+                            RelayColumnInfo::from_binlog_column_info(c, conversion.as_ref())
                         }).collect();
                     let relay_command = RelayCommand::Update(update_rows);
                     Self {
                         src_type,
                         event_log_pos,
+                        event_timestamp,
                         event_name,
                         database_name,
                         table_name,
@@ -363,21 +260,28 @@ impl RelayLog {
             BinlogEvent::DeleteRows(e) => {
                 if let Some(table) = e.get_table_map_event() {
                     let event_log_pos = e.get_header().get_log_pos();
+                    let event_timestamp = e.get_header().when;
                     let event_name = e.get_type_name();
+                    let conversions: Vec<Option<ColumnConversion>> = table.get_column_infos()
+                        .iter()
+                        .map(column_conversion)
+                        .collect();
                     let delete_rows: Vec<RelayRowData> = e.get_rows().iter().map(|r| {
-                        RelayRowData::from_binlog_row(r)
+                        RelayRowData::from_binlog_row(r, &conversions)
                     }).collect();
                     let database_name = table.get_database_name();
                     let table_name = table.get_table_name();
                     let columns = table.get_column_infos()
                         .iter()
-                        .map(|c| {
-                            RelayColumnInfo::from_binlog_column_info(c)
+                        .zip(&conversions)
+                        .map(|(c, conversion)| {
+                            RelayColumnInfo::from_binlog_column_info(c, conversion.as_ref())
                         }).collect();
                     let relay_command = RelayCommand::Delete(delete_rows);
                     Self {
                         src_type,
                         event_log_pos,
+                        event_timestamp,
                         event_name,
                         database_name,
                         table_name,
@@ -388,6 +292,36 @@ impl RelayLog {
                     Self::default()
                 }
             }
+            BinlogEvent::XID(e) => {
+                Self {
+                    src_type,
+                    event_log_pos: e.get_header().get_log_pos(),
+                    event_timestamp: e.get_header().when,
+                    event_name: e.get_type_name(),
+                    relay_command: RelayCommand::Commit(e.get_xid()),
+                    ..Self::default()
+                }
+            }
+            BinlogEvent::Rotate(e) => {
+                Self {
+                    src_type,
+                    event_log_pos: e.get_binlog_position(),
+                    event_timestamp: e.get_header().when,
+                    event_name: e.get_type_name(),
+                    relay_command: RelayCommand::Rotate(e.get_file_name()),
+                    ..Self::default()
+                }
+            }
+            BinlogEvent::GtidLog(e) => {
+                Self {
+                    src_type,
+                    event_log_pos: e.get_header().get_log_pos(),
+                    event_timestamp: e.get_header().when,
+                    event_name: e.get_type_name(),
+                    relay_command: RelayCommand::Gtid(e.get_gtid_str()),
+                    ..Self::default()
+                }
+            }
             _ => {
                 // todo 其它event后续实现
                 Self::default()
@@ -409,6 +343,7 @@ impl Default for RelayLog {
         Self {
             src_type: SrcType::default(),
             event_log_pos: 0,
+            event_timestamp: 0,
             event_name: "".to_string(),
             database_name: "".to_string(),
             table_name: "".to_string(),
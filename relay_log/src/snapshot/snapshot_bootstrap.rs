@@ -0,0 +1,415 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use tracing::{info, warn};
+
+use binlog::alias::mysql::gtid::gtid_set::GtidSet;
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use common::schema::column_mapping::cast_value;
+use common::schema::data_type::{DstColumnType, Value};
+use common::schema::mysql_metadata::MetadataQuery;
+use connection::binlog::async_binlog_events::AsyncBinlogEvents;
+use connection::binlog::binlog_options::BinlogOptions;
+use connection::conn::async_connection::AsyncConnection;
+use connection::conn::connection_options::ConnectionOptions;
+
+use crate::apply::sql_builder::SqlBuilder;
+use crate::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+use crate::storage::segment_manager::SegmentManager;
+use crate::storage::storage_backend::open_backend;
+use crate::storage::storage_config::StorageConfig;
+
+/// 每次 SELECT 读取的行数
+pub const DEFAULT_CHUNK_SIZE: usize = 1024;
+/// 快照生成的中继日志的事件名
+pub const SNAPSHOT_EVENT_NAME: &str = "SnapshotInsert";
+/// 快照写入的暂存目录, 位于分区目录下; 名称包含 `.`, 不会被当作分区
+const STAGING_DIR: &str = ".snapshot";
+
+/// 快照读取源库的接口, 由 connection 中的连接实现, 测试中可以替换
+#[async_trait::async_trait]
+pub trait SnapshotQuery: MetadataQuery {
+
+    async fn execute(&mut self, sql: String) -> CResult<()>;
+
+}
+
+#[async_trait::async_trait]
+impl SnapshotQuery for AsyncConnection {
+    async fn execute(&mut self, sql: String) -> CResult<()> {
+        AsyncConnection::execute(self, sql).await
+    }
+}
+
+/// 快照对应的源库 binlog 位置
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotPosition {
+    pub binlog_file: String,
+    pub binlog_position: u64,
+    // Executed_Gtid_Set, 源库未开启 GTID 时为空
+    pub gtid_set: String,
+}
+
+impl SnapshotPosition {
+    /// 从快照位置继续读取 binlog 的参数, 源库开启 GTID 时按 GTID 定位
+    pub fn binlog_options(&self) -> CResult<BinlogOptions> {
+        if self.gtid_set.is_empty() {
+            Ok(BinlogOptions::from_position(self.binlog_file.clone(), self.binlog_position))
+        } else {
+            Ok(BinlogOptions::from_gtid(GtidSet::parse(self.gtid_set.clone())?))
+        }
+    }
+}
+
+/// 快照结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    pub tables: usize,
+    pub rows: u64,
+}
+
+/// 快照表的列
+#[derive(Debug, Clone)]
+struct SnapshotColumn {
+    name: String,
+    // information_schema.COLUMNS.DATA_TYPE
+    data_type: String,
+    // information_schema.COLUMNS.COLUMN_TYPE 是否包含 unsigned
+    unsigned: bool,
+}
+
+impl SnapshotColumn {
+    fn is_binary(&self) -> bool {
+        matches!(self.data_type.as_str(), "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob")
+    }
+
+    /// SELECT 中的表达式: 二进制列读取十六进制, timestamp 读取毫秒, 避免文本协议与时区的影响
+    fn select_expr(&self) -> String {
+        let ident = SqlBuilder::ident(&self.name);
+        match self.data_type.as_str() {
+            _ if self.is_binary() => format!("HEX({})", ident),
+            "timestamp" => format!("CAST(UNIX_TIMESTAMP({}) * 1000 AS SIGNED)", ident),
+            "bit" => format!("CAST({} AS UNSIGNED)", ident),
+            _ => ident,
+        }
+    }
+
+    /// 与 binlog 行事件转换得到的列类型保持一致
+    fn column_type(&self) -> DstColumnType {
+        DstColumnType::from_mysql_column(&self.data_type, self.unsigned)
+    }
+
+    /// 文本协议的值转换为与 binlog 行事件相同的 Value
+    fn value(&self, text: Option<&String>) -> CResult<Value> {
+        let text = match text {
+            Some(t) => t,
+            None => return Ok(Value::Null),
+        };
+        let invalid = |e: String| ReError::String(format!("invalid {} value of column {}: {}", self.data_type, self.name, e));
+
+        let value = match self.data_type.as_str() {
+            "tinyint" | "smallint" | "mediumint" | "int" | "integer" | "bigint" | "year" | "bit" | "enum" | "set" => {
+                cast_value(Value::String(text.clone()), self.column_type()).map_err(|e| invalid(e.to_string()))?
+            }
+            "float" => Value::Float(text.parse().map_err(|e: std::num::ParseFloatError| invalid(e.to_string()))?),
+            "double" | "real" => Value::Double(text.parse().map_err(|e: std::num::ParseFloatError| invalid(e.to_string()))?),
+            "decimal" | "numeric" => Value::Decimal(text.clone()),
+            "timestamp" => Value::Timestamp(text.parse().map_err(|e: std::num::ParseIntError| invalid(e.to_string()))?),
+            "date" => match NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|d| d.and_hms_milli_opt(0, 0, 0, 0)) {
+                Some(d) => Value::Date(d.timestamp_millis()),
+                None => {
+                    warn!("Date parse error.");
+                    Value::Null
+                }
+            },
+            "datetime" => match NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f") {
+                Ok(d) => Value::DateTime(d.timestamp_millis()),
+                Err(_) => {
+                    warn!("DateTime parse error.");
+                    Value::Null
+                }
+            },
+            "time" => match NaiveTime::parse_from_str(text, "%H:%M:%S%.f") {
+                Ok(t) => Value::Time(NaiveDateTime::new(Utc::now().date_naive(), t).timestamp_millis()),
+                Err(_) => {
+                    warn!("Time parse error.");
+                    Value::Null
+                }
+            },
+            "json" => Value::JSON(text.clone()),
+            _ if matches!(self.column_type(), DstColumnType::Geometry) => Value::Null,
+            _ if self.is_binary() => Value::Blob(hex::decode(text).map_err(|e| invalid(e.to_string()))?),
+            _ => Value::String(text.clone()),
+        };
+        Ok(value)
+    }
+}
+
+/// 初始快照: 在一致性快照中按块读取表的全部数据, 作为 Insert 写入中继日志, 之后从快照对应的 binlog 位置开始增量读取。
+///
+/// 读取前以 `FLUSH TABLES WITH READ LOCK` 短暂加锁, 开启一致性快照事务并记录 binlog 位置后立即解锁,
+/// 需要 RELOAD 与 REPLICATION CLIENT 权限。
+/// 每个块写入一条 Insert 与一个提交标记, 回放时每块为一个事务; 表的第一条日志为 Rotate 标记, 回放位点从快照的 binlog 文件开始。
+/// 快照只能写入空的中继日志: 先写入暂存目录, 全部表读取完成后再移动到中继日志目录, 中途失败时中继日志仍为空, 可以直接重试
+#[derive(Debug)]
+pub struct SnapshotBootstrap {
+    options: ConnectionOptions,
+    storage_config: StorageConfig,
+    // (db, table)
+    tables: Vec<(String, String)>,
+    chunk_size: usize,
+}
+
+impl SnapshotBootstrap {
+    pub fn new(options: ConnectionOptions, storage_config: StorageConfig) -> Self {
+        Self {
+            options,
+            storage_config,
+            tables: vec![],
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// 添加快照的表
+    pub fn with_table(mut self, database: &str, table: &str) -> Self {
+        self.tables.push((database.to_string(), table.to_string()));
+        self
+    }
+
+    /// 每次 SELECT 读取的行数, 同时是中继日志中每条 Insert 的行数. Defaults to 1024.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// 执行快照, 返回快照对应的 binlog 位置
+    pub async fn snapshot(&self) -> CResult<(SnapshotPosition, SnapshotStats)> {
+        let mut conn = AsyncConnection::new(self.options.clone());
+        conn.connect().await?;
+
+        let rs = self.snapshot_with(&mut conn).await;
+        if let Err(e) = conn.close().await {
+            warn!("close snapshot connection err: {:?}", e);
+        }
+        rs
+    }
+
+    /// 执行快照, 然后从快照位置开始读取 binlog
+    ///
+    /// # Arguments
+    ///
+    /// * `payload_buffer_size`:  读取binlog 的缓冲区大小
+    pub async fn run(self, payload_buffer_size: usize) -> CResult<AsyncBinlogEvents> {
+        let (position, stats) = self.snapshot().await?;
+        info!("snapshot {} tables, {} rows, switch to binlog {:?}", stats.tables, stats.rows, position);
+
+        let mut options = self.options;
+        options.binlog = Some(Arc::new(RefCell::new(position.binlog_options()?)));
+        AsyncConnection::new(options).binlog(payload_buffer_size).await
+    }
+
+    /// 使用已连接的 conn 执行快照, 返回快照对应的 binlog 位置
+    pub async fn snapshot_with<C: SnapshotQuery + ?Sized>(&self, conn: &mut C) -> CResult<(SnapshotPosition, SnapshotStats)> {
+        for (database, table) in &self.tables {
+            self.check_empty(database, table)?;
+            // 上次失败残留的暂存日志
+            let staging_dir = SegmentManager::get_segment_dir_path(&self.staging_config().partition_dir(), database, table)?;
+            if PathBuf::from(&staging_dir).exists() {
+                fs::remove_dir_all(&staging_dir)?;
+            }
+        }
+
+        conn.execute("SET SESSION TRANSACTION ISOLATION LEVEL REPEATABLE READ".to_string()).await?;
+        conn.execute("FLUSH TABLES WITH READ LOCK".to_string()).await?;
+        let position = match Self::begin_snapshot(conn).await {
+            Ok(position) => position,
+            Err(e) => {
+                if let Err(unlock) = conn.execute("UNLOCK TABLES".to_string()).await {
+                    warn!("snapshot unlock tables err: {:?}", unlock);
+                }
+                return Err(e);
+            }
+        };
+        conn.execute("UNLOCK TABLES".to_string()).await?;
+        info!("snapshot start at {:?}", position);
+
+        let mut stats = SnapshotStats::default();
+        for (database, table) in &self.tables {
+            stats.rows += self.snapshot_table(conn, &position, database, table).await?;
+            stats.tables += 1;
+        }
+        conn.execute("COMMIT".to_string()).await?;
+
+        for (database, table) in &self.tables {
+            self.publish(database, table)?;
+        }
+        Ok((position, stats))
+    }
+
+    /// 暂存目录的配置: 快照完成前不清理、不上传
+    fn staging_config(&self) -> StorageConfig {
+        let mut config = self.storage_config.for_partition("");
+        config.set_relay_log_dir(PathBuf::from(self.storage_config.partition_dir()).join(STAGING_DIR).to_string_lossy().to_string());
+        config.set_retention_millisecond(0);
+        config.set_retention_bytes(0);
+        config.set_tiering_millisecond(0);
+        config
+    }
+
+    fn check_empty(&self, database: &str, table: &str) -> CResult<()> {
+        let mut log_storage = open_backend(&self.storage_config, database, table)?;
+        if log_storage.index_range()?.is_some() {
+            return Err(ReError::String(format!("relay log of {}.{} is not empty, snapshot needs an empty relay log.", database, table)));
+        }
+        Ok(())
+    }
+
+    /// 把暂存的日志移动到中继日志目录
+    fn publish(&self, database: &str, table: &str) -> CResult<()> {
+        self.check_empty(database, table)?;
+        let staging_dir = SegmentManager::get_segment_dir_path(&self.staging_config().partition_dir(), database, table)?;
+        let log_dir = SegmentManager::get_segment_dir_path(&self.storage_config.partition_dir(), database, table)?;
+        if PathBuf::from(&log_dir).exists() {
+            fs::remove_dir_all(&log_dir)?;
+        }
+        fs::rename(&staging_dir, &log_dir)?;
+        Ok(())
+    }
+
+    /// 加锁期间开启一致性快照并读取 binlog 位置
+    async fn begin_snapshot<C: SnapshotQuery + ?Sized>(conn: &mut C) -> CResult<SnapshotPosition> {
+        conn.execute("START TRANSACTION WITH CONSISTENT SNAPSHOT".to_string()).await?;
+        let rows = match conn.query("SHOW MASTER STATUS".to_string()).await {
+            Ok(rows) => rows,
+            // MySQL 8.4
+            Err(_) => conn.query("SHOW BINARY LOG STATUS".to_string()).await?,
+        };
+        let row = rows.first()
+            .ok_or(ReError::String("binary log is not enabled on the source.".to_string()))?;
+        let text = |i: usize| row.as_slice().get(i).cloned().flatten().unwrap_or_default();
+
+        Ok(SnapshotPosition {
+            binlog_file: text(0),
+            binlog_position: text(1).parse::<u64>()
+                .map_err(|e| ReError::String(format!("invalid binlog position: {}", e)))?,
+            gtid_set: text(4).replace('\n', ""),
+        })
+    }
+
+    /// 按主键顺序分块读取一张表, 返回行数。
+    ///
+    /// 没有主键时按全部列排序后以 LIMIT offset 分块, 一致性快照中数据不变, 各块的顺序一致;
+    /// 只有在 max_sort_length 内完全相同的行之间顺序不确定, 这些行互相替换不影响结果
+    async fn snapshot_table<C: SnapshotQuery + ?Sized>(&self, conn: &mut C, position: &SnapshotPosition, database: &str, table: &str) -> CResult<u64> {
+        let columns = Self::columns(conn, database, table).await?;
+        if columns.is_empty() {
+            return Err(ReError::String(format!("snapshot table {}.{} not found.", database, table)));
+        }
+        let primary_keys: Vec<SnapshotColumn> = Self::primary_keys(conn, database, table).await?
+            .iter()
+            .filter_map(|pk| columns.iter().find(|c| c.name == *pk).cloned())
+            .collect();
+
+        let mut log_storage = open_backend(&self.staging_config(), database, table)?;
+        let timestamp = Utc::now().timestamp() as u32;
+        let marker = |command: RelayCommand| {
+            let mut log = RelayLog::default();
+            log.set_event_log_pos(position.binlog_position);
+            log.set_event_timestamp(timestamp);
+            log.set_event_name(SNAPSHOT_EVENT_NAME.to_string());
+            log.set_relay_command(command);
+            log
+        };
+        log_storage.append_relay_log(marker(RelayCommand::Rotate(position.binlog_file.clone())))?;
+
+        let relay_columns: Vec<RelayColumnInfo> = columns.iter().map(|c| {
+            let mut column = RelayColumnInfo::default();
+            column.set_column_name(c.name.clone());
+            column.set_column_type(c.column_type());
+            column
+        }).collect();
+        let table_name = format!("{}.{}", SqlBuilder::ident(database), SqlBuilder::ident(table));
+        let mut select: Vec<String> = columns.iter().map(|c| c.select_expr()).collect();
+        // 主键的原值放在最后, 作为下一块的起点
+        select.extend(primary_keys.iter().map(|c| if c.is_binary() { format!("HEX({})", SqlBuilder::ident(&c.name)) } else { SqlBuilder::ident(&c.name) }));
+        let order: Vec<String> = primary_keys.iter().map(|c| SqlBuilder::ident(&c.name)).collect();
+        let all_columns: Vec<String> = columns.iter().map(|c| SqlBuilder::ident(&c.name)).collect();
+
+        let mut cursor: Option<Vec<String>> = None;
+        let mut total = 0u64;
+        loop {
+            let sql = if primary_keys.is_empty() {
+                format!("SELECT {} FROM {} ORDER BY {} LIMIT {}, {}", select.join(", "), table_name, all_columns.join(", "), total, self.chunk_size)
+            } else {
+                let condition = match &cursor {
+                    Some(values) => format!(" WHERE ({}) > ({})", order.join(", "), values.join(", ")),
+                    None => String::new(),
+                };
+                format!("SELECT {} FROM {}{} ORDER BY {} LIMIT {}", select.join(", "), table_name, condition, order.join(", "), self.chunk_size)
+            };
+            let rows = conn.query(sql).await?;
+            if rows.is_empty() {
+                break;
+            }
+
+            let mut relay_rows = Vec::with_capacity(rows.len());
+            for row in &rows {
+                let values = row.as_slice();
+                let mut relay_row = RelayRowData::default();
+                relay_row.set_values(columns.iter().enumerate()
+                    .map(|(i, c)| c.value(values.get(i).and_then(|v| v.as_ref())))
+                    .collect::<CResult<Vec<Value>>>()?);
+                relay_rows.push(relay_row);
+            }
+            if let Some(last) = rows.last() {
+                let values = &last.as_slice()[columns.len()..];
+                cursor = Some(primary_keys.iter().zip(values).map(|(c, v)| match v {
+                    Some(v) if c.is_binary() => format!("X'{}'", v),
+                    Some(v) => SqlBuilder::quote(v),
+                    None => "NULL".to_string(),
+                }).collect());
+            }
+
+            total += rows.len() as u64;
+            let mut log = marker(RelayCommand::Insert(relay_rows));
+            log.set_database_name(database.to_string());
+            log.set_table_name(table.to_string());
+            log.set_columns(relay_columns.clone());
+            log_storage.append_relay_log(log)?;
+            log_storage.append_relay_log(marker(RelayCommand::Commit(0)))?;
+
+            if rows.len() < self.chunk_size {
+                break;
+            }
+        }
+        log_storage.flush()?;
+        info!("snapshot table {}.{}: {} rows", database, table, total);
+
+        Ok(total)
+    }
+
+    async fn columns<C: SnapshotQuery + ?Sized>(conn: &mut C, database: &str, table: &str) -> CResult<Vec<SnapshotColumn>> {
+        let rows = conn.query(format!("SELECT COLUMN_NAME, DATA_TYPE, COLUMN_TYPE FROM information_schema.COLUMNS \
+            WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} ORDER BY ORDINAL_POSITION",
+                                      SqlBuilder::quote(database), SqlBuilder::quote(table))).await?;
+        Ok(rows.iter().map(|r| {
+            let text = |i: usize| r.as_slice().get(i).cloned().flatten().unwrap_or_default();
+            SnapshotColumn {
+                name: text(0),
+                data_type: text(1).to_ascii_lowercase(),
+                unsigned: text(2).to_ascii_lowercase().contains("unsigned"),
+            }
+        }).collect())
+    }
+
+    async fn primary_keys<C: SnapshotQuery + ?Sized>(conn: &mut C, database: &str, table: &str) -> CResult<Vec<String>> {
+        let rows = conn.query(format!("SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+            WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} AND CONSTRAINT_NAME = 'PRIMARY' ORDER BY ORDINAL_POSITION",
+                                      SqlBuilder::quote(database), SqlBuilder::quote(table))).await?;
+        Ok(rows.iter().filter_map(|r| r.as_slice().first().cloned().flatten()).collect())
+    }
+}
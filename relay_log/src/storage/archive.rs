@@ -0,0 +1,220 @@
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use checksum::crc32::Crc32;
+use serde::{Deserialize, Serialize};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+use crate::relay_log::{RelayCommand, RelayLog};
+use crate::storage::relay_log_storage::RelayLogStorage;
+use crate::storage::segment::Segment;
+use crate::storage::segment_manager::SegmentManager;
+use crate::storage::storage_config::StorageConfig;
+use crate::storage::storage_entry::StorageEntry;
+
+/// 归档清单文件, 最后写入, 存在即表示归档完整
+pub const ARCHIVE_MANIFEST_FILE: &str = "archive.json";
+/// 归档中的表结构变更历史
+pub const SCHEMA_HISTORY_FILE: &str = "schema_history.json";
+/// 归档格式版本
+const ARCHIVE_VERSION: u32 = 1;
+
+/// 归档中的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    pub name: String,
+    pub size: u64,
+    // 文件内容的crc32
+    pub checksum: u32,
+}
+
+/// 归档清单.
+///
+/// 归档是一个文件夹, 包含 [first_index, last_index] 范围内的entry重新写成的segment、
+/// 表结构变更历史与本清单, 可以整体打包复制到其它节点后通过 [import] 导入
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    pub database: String,
+    pub table: String,
+    pub first_index: u64,
+    pub last_index: u64,
+    // segment与索引文件
+    pub segments: Vec<ArchiveFile>,
+    pub schema_history: ArchiveFile,
+}
+
+impl ArchiveManifest {
+    /// 读取归档文件夹中的清单
+    pub fn load(archive_dir: &str) -> CResult<Self> {
+        let path = PathBuf::from(archive_dir).join(ARCHIVE_MANIFEST_FILE);
+        let manifest: ArchiveManifest = serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| ReError::Error(format!("read archive manifest {:?} err: {}", path, e)))?;
+        if manifest.version != ARCHIVE_VERSION {
+            return Err(ReError::Error(format!("unsupported archive version {}.", manifest.version)));
+        }
+        Ok(manifest)
+    }
+
+    pub fn entries(&self) -> u64 {
+        self.last_index - self.first_index + 1
+    }
+}
+
+/// 表结构变更: 中继日志中的 DDL 及其 index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaChange {
+    pub index: u64,
+    pub log: RelayLog,
+}
+
+impl SchemaChange {
+    fn is_schema_change(log: &RelayLog) -> bool {
+        matches!(log.relay_command(),
+            RelayCommand::CreateDatabase | RelayCommand::DropDatabase |
+            RelayCommand::CreateTable | RelayCommand::DropTable | RelayCommand::AlterTable)
+    }
+
+    /// 读取归档中截止到 last_index 的表结构变更历史
+    pub fn load(archive_dir: &str) -> CResult<Vec<SchemaChange>> {
+        let path = PathBuf::from(archive_dir).join(SCHEMA_HISTORY_FILE);
+        serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| ReError::Error(format!("read schema history {:?} err: {}", path, e)))
+    }
+}
+
+/// 导出 [from_index, to_index] 到 archive_dir, 文件夹不存在时创建, 已有归档时报错.
+///
+/// entry 保留原 index, 按本表的segment配置重新写入; 表结构变更历史包含本地日志中 to_index 及之前的全部 DDL
+pub fn export(storage: &mut RelayLogStorage, from_index: u64, to_index: u64, archive_dir: &str) -> CResult<ArchiveManifest> {
+    let (first, last) = storage.index_range()?.ok_or(ReError::Error("relay log is empty.".to_string()))?;
+    if from_index > to_index || from_index < first || to_index > last {
+        return Err(ReError::Error(format!("export range [{}, {}] out of relay log [{}, {}].", from_index, to_index, first, last)));
+    }
+    let dir = PathBuf::from(archive_dir);
+    if dir.join(ARCHIVE_MANIFEST_FILE).exists() {
+        return Err(ReError::Error(format!("archive {} already exists.", archive_dir)));
+    }
+    fs::create_dir_all(&dir)?;
+
+    let mut schema_history = vec![];
+    for index in first..from_index {
+        let entry = storage.get_entry(index)?;
+        if SchemaChange::is_schema_change(entry.relay_log()) {
+            schema_history.push(SchemaChange { index, log: entry.relay_log().clone() });
+        }
+    }
+
+    let mut segment_names = vec![];
+    let mut segment: Option<Segment> = None;
+    for index in from_index..=to_index {
+        let log = storage.get_entry(index)?.relay_log().clone();
+        if SchemaChange::is_schema_change(&log) {
+            schema_history.push(SchemaChange { index, log: log.clone() });
+        }
+
+        if segment.as_ref().map_or(true, |s| s.is_full()) {
+            if let Some(s) = segment.take() {
+                segment_names.push(seal(&storage.segment_manager, s)?);
+            }
+            let id = segment_names.len() as u32 + 1;
+            segment = Some(storage.segment_manager.create_segment(archive_dir, id, index)?);
+        }
+        let mut entry = StorageEntry::new(index, 0, 0, log);
+        segment.as_mut().unwrap().append(&mut entry)?;
+    }
+    if let Some(s) = segment.take() {
+        segment_names.push(seal(&storage.segment_manager, s)?);
+    }
+
+    let bytes = serde_json::to_vec_pretty(&schema_history).map_err(|e| ReError::Error(e.to_string()))?;
+    write_file(&dir.join(SCHEMA_HISTORY_FILE), &bytes)?;
+
+    let mut segments = vec![];
+    for name in segment_names {
+        let index_name = format!("{}.idx", name.strip_suffix(".log").unwrap_or(&name));
+        segments.push(archive_file(&dir, &name)?);
+        if dir.join(&index_name).exists() {
+            segments.push(archive_file(&dir, &index_name)?);
+        }
+    }
+    let manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        database: storage.dst_db_name().to_string(),
+        table: storage.dst_table_name().to_string(),
+        first_index: from_index,
+        last_index: to_index,
+        segments,
+        schema_history: archive_file(&dir, SCHEMA_HISTORY_FILE)?,
+    };
+    let bytes = serde_json::to_vec_pretty(&manifest).map_err(|e| ReError::Error(e.to_string()))?;
+    write_file(&dir.join(ARCHIVE_MANIFEST_FILE), &bytes)?;
+    Ok(manifest)
+}
+
+/// 把归档导入到 storage_config 所在分区下同名表的中继日志, 校验全部文件后复制, 目标表已有segment时报错.
+///
+/// 导入后通过 [RelayLogStorage::new] 打开, 新的entry从 last_index + 1 开始追加
+pub fn import(storage_config: &StorageConfig, archive_dir: &str) -> CResult<ArchiveManifest> {
+    let manifest = ArchiveManifest::load(archive_dir)?;
+    let dir = PathBuf::from(archive_dir);
+    for file in manifest.segments.iter().chain([&manifest.schema_history]) {
+        let actual = archive_file(&dir, &file.name)?;
+        if actual != *file {
+            return Err(ReError::ChecksumError(format!("archive file {} is corrupted.", file.name)));
+        }
+    }
+
+    let segment_dir = SegmentManager::get_segment_dir_path(&storage_config.partition_dir(), &manifest.database, &manifest.table)?;
+    if Path::new(&segment_dir).exists() && !SegmentManager::segment_files(&segment_dir)?.is_empty() {
+        return Err(ReError::Error(format!("relay log {}#{} is not empty.", manifest.database, manifest.table)));
+    }
+    fs::create_dir_all(&segment_dir)?;
+
+    // 先复制索引与表结构历史, 最后复制segment文件, 中途失败时目标表仍为空
+    let target = PathBuf::from(&segment_dir);
+    let (logs, others): (Vec<&ArchiveFile>, Vec<&ArchiveFile>) = manifest.segments.iter()
+        .chain([&manifest.schema_history])
+        .partition(|f| f.name.ends_with(".log"));
+    for file in others.into_iter().chain(logs) {
+        let tmp = target.join(format!("{}.import", file.name));
+        fs::copy(dir.join(&file.name), &tmp)?;
+        File::open(&tmp)?.sync_all()?;
+        fs::rename(&tmp, target.join(&file.name))?;
+    }
+    Ok(manifest)
+}
+
+/// 关闭写入并按本表配置压缩, 返回segment文件名
+fn seal(segment_manager: &SegmentManager, mut segment: Segment) -> CResult<String> {
+    segment.write_close()?;
+    let (codec, level) = segment_manager.compression();
+    segment.compress(codec, level)?;
+    Ok(segment.segment_file().name().clone())
+}
+
+fn archive_file(dir: &Path, name: &str) -> CResult<ArchiveFile> {
+    let bytes = fs::read(dir.join(name))?;
+    let mut crc = Crc32::new();
+    Ok(ArchiveFile {
+        name: name.to_string(),
+        size: bytes.len() as u64,
+        checksum: crc.checksum(&bytes),
+    })
+}
+
+/// 写入临时文件后替换
+fn write_file(path: &Path, bytes: &[u8]) -> CResult<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut f = File::create(&tmp)?;
+        f.write_all(bytes)?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -1,5 +1,223 @@
+use std::collections::{BTreeMap, HashMap};
 
-/// todo 日志整理，定时删除已经Apply完的segment文件
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use common::schema::data_type::Value;
+
+use crate::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+
+/// 压缩后entry的 event_name
+pub const COMPACTED_EVENT_NAME: &str = "Compacted";
+
+/// 一次主键压缩的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactStats {
+    // 被重写的segment数量
+    pub segments: usize,
+    // 重写前的entry数量
+    pub entries_before: u64,
+    // 重写后的entry数量
+    pub entries_after: u64,
+}
+
+/// 日志整理: 按主键压缩已写满且已消费完的segment。
+///
+/// 每个主键只保留最新的行镜像(Insert), 被删除的行保留一条墓碑(Delete), 重放压缩后的日志得到与原日志相同的最终状态。
+/// 中继日志按目标表存储, 因此主键即 (table, PK)。
+/// DropTable/CreateTable 等会清空表的DDL保留最后一条, 放在压缩结果的最前面;
+/// AlterTable 作为屏障: 之前的行先压缩输出, 再输出 AlterTable, 之后的行在其后单独压缩, 重放时表结构变更仍在原来的位置;
+/// 源库位置标记(Rotate/Gtid)与事务提交标记各保留最后一条放在最后, 压缩后的segment回放时作为一个事务
+#[derive(Debug, Clone)]
 pub struct Compactor {
+    // 主键列名
+    primary_keys: Vec<String>,
+}
+
+/// 主键压缩后的一行
+#[derive(Debug, Clone)]
+struct CompactedRow {
+    // 是否为墓碑
+    tombstone: bool,
+    row: RelayRowData,
+    columns: Vec<RelayColumnInfo>,
+    event_log_pos: u64,
+    event_timestamp: u32,
+}
+
+impl Compactor {
+    pub fn new(primary_keys: Vec<String>) -> Self {
+        Self {
+            primary_keys,
+        }
+    }
+
+    /// 压缩 logs, 结果不超过 max_entries 条, 无法减少行数或entry数量时返回 None
+    pub fn compact_logs<I: Iterator<Item = RelayLog>>(&self, logs: I, max_entries: usize) -> CResult<Option<Vec<RelayLog>>> {
+        let mut reset: Option<RelayLog> = None;
+        // reset 之后已结束的阶段: 该阶段压缩后的行与结束它的 AlterTable
+        let mut phases: Vec<(Vec<CompactedRow>, RelayLog)> = Vec::new();
+        let mut commit: Option<RelayLog> = None;
+        let mut rotate: Option<RelayLog> = None;
+        let mut gtid: Option<RelayLog> = None;
+        // 主键 -> 最新状态
+        let mut rows: HashMap<String, CompactedRow> = HashMap::new();
+        let mut template: Option<RelayLog> = None;
+        // 压缩前的行数与DDL数量
+        let mut input_rows = 0;
+
+        for log in logs {
+            let mut image = |tombstone: bool, row: &RelayRowData| -> CResult<()> {
+                let key = self.key(&log, row)?;
+                rows.insert(key, CompactedRow {
+                    tombstone,
+                    row: row.clone(),
+                    columns: log.columns().clone(),
+                    event_log_pos: *log.event_log_pos(),
+                    event_timestamp: *log.event_timestamp(),
+                });
+                Ok(())
+            };
+
+            input_rows += match log.relay_command() {
+                RelayCommand::Insert(rows) | RelayCommand::Delete(rows) => rows.len(),
+                RelayCommand::Update(rows) => rows.len(),
+                RelayCommand::None | RelayCommand::Commit(_) | RelayCommand::Rotate(_) | RelayCommand::Gtid(_) => 0,
+                _ => 1,
+            };
+
+            match log.relay_command() {
+                RelayCommand::Insert(inserts) => {
+                    for row in inserts {
+                        image(false, row)?;
+                    }
+                }
+                RelayCommand::Delete(deletes) => {
+                    for row in deletes {
+                        image(true, row)?;
+                    }
+                }
+                RelayCommand::Update(updates) => {
+                    for (before, after) in updates {
+                        // 主键被修改时旧主键变为墓碑
+                        if self.key(&log, before)? != self.key(&log, after)? {
+                            image(true, before)?;
+                        }
+                        image(false, after)?;
+                    }
+                }
+                RelayCommand::CreateDatabase | RelayCommand::DropDatabase |
+                RelayCommand::CreateTable | RelayCommand::DropTable => {
+                    rows.clear();
+                    phases.clear();
+                    reset = Some(log.clone());
+                }
+                RelayCommand::AlterTable => {
+                    phases.push((rows.drain().map(|(_, row)| row).collect(), log.clone()));
+                }
+                RelayCommand::Commit(_) => {
+                    commit = Some(log.clone());
+                }
+                RelayCommand::Rotate(_) => {
+                    rotate = Some(log.clone());
+                }
+                RelayCommand::Gtid(_) => {
+                    gtid = Some(log.clone());
+                }
+                RelayCommand::None => {}
+            }
+
+            // 标记不带库表与列信息, 不能作为模板
+            let marker = matches!(log.relay_command(), RelayCommand::None | RelayCommand::Commit(_) |
+                RelayCommand::Rotate(_) | RelayCommand::Gtid(_));
+            if template.is_none() && !marker {
+                template = Some(log);
+            }
+        }
+
+        let template = match template {
+            None => return Ok(None),
+            Some(t) => t,
+        };
+        let output_rows = phases.iter().map(|(rows, _)| rows.len() + 1).sum::<usize>() + rows.len() + reset.is_some() as usize;
+        if output_rows >= input_rows {
+            return Ok(None);
+        }
+        let markers: Vec<RelayLog> = [rotate, gtid, commit].into_iter().flatten().collect();
+        Ok(Self::build_logs(&template, reset, phases, markers, rows.into_values().collect(), max_entries))
+    }
+
+    /// 行的主键, 列按名称在该条日志的列信息中查找
+    fn key(&self, log: &RelayLog, row: &RelayRowData) -> CResult<String> {
+        let mut values: Vec<&Value> = Vec::with_capacity(self.primary_keys.len());
+        for pk in &self.primary_keys {
+            let value = log.columns().iter()
+                .position(|c| c.column_name() == pk)
+                .and_then(|i| row.values().get(i))
+                .ok_or_else(|| ReError::String(format!("primary key column {} not found in {}.{}",
+                                                       pk, log.database_name(), log.table_name())))?;
+            values.push(value);
+        }
+
+        serde_json::to_string(&values).map_err(|e| ReError::Error(e.to_string()))
+    }
+
+    /// 每个阶段的墓碑与行镜像分别按列信息分组, 每组按需合并为多行的 Delete/Insert, 使entry数量不超过 max_entries。
+    ///
+    /// 同一阶段中每个主键只出现一次, 因此阶段内不同主键之间的顺序不影响最终状态
+    fn build_logs(template: &RelayLog, reset: Option<RelayLog>, phases: Vec<(Vec<CompactedRow>, RelayLog)>,
+                  markers: Vec<RelayLog>, rows: Vec<CompactedRow>, max_entries: usize) -> Option<Vec<RelayLog>> {
+        if rows.is_empty() && reset.is_none() && phases.is_empty() {
+            return None;
+        }
+
+        let mut phases: Vec<(BTreeMap<(bool, String), Vec<CompactedRow>>, Option<RelayLog>)> = phases.into_iter()
+            .map(|(rows, alter)| (Self::group(rows), Some(alter)))
+            .collect();
+        phases.push((Self::group(rows), None));
+
+        let fixed = phases.iter().map(|(groups, alter)| groups.len() + alter.is_some() as usize).sum::<usize>()
+            + reset.is_some() as usize + markers.len();
+        if fixed >= max_entries {
+            return None;
+        }
+        let total: usize = phases.iter().flat_map(|(groups, _)| groups.values()).map(|g| g.len()).sum();
+        let rows_per_entry = ((total + max_entries - fixed - 1) / (max_entries - fixed)).max(1);
+
+        let mut logs: Vec<RelayLog> = reset.into_iter().collect();
+        for (groups, alter) in phases {
+            for ((image, _), rows) in groups {
+                for chunk in rows.chunks(rows_per_entry) {
+                    let mut log = template.clone();
+                    log.set_event_name(COMPACTED_EVENT_NAME.to_string());
+                    log.set_columns(chunk[0].columns.clone());
+                    log.set_event_log_pos(chunk.iter().map(|r| r.event_log_pos).max().unwrap_or_default());
+                    log.set_event_timestamp(chunk.iter().map(|r| r.event_timestamp).max().unwrap_or_default());
+                    let chunk_rows: Vec<RelayRowData> = chunk.iter().map(|r| r.row.clone()).collect();
+                    log.set_relay_command(if image {
+                        RelayCommand::Insert(chunk_rows)
+                    } else {
+                        RelayCommand::Delete(chunk_rows)
+                    });
+                    logs.push(log);
+                }
+            }
+            logs.extend(alter);
+        }
+        logs.extend(markers);
+
+        if logs.len() >= max_entries {
+            return None;
+        }
+        Some(logs)
+    }
 
-}
\ No newline at end of file
+    /// 按 (是否为行镜像, 列信息) 分组, 墓碑在前
+    fn group(rows: Vec<CompactedRow>) -> BTreeMap<(bool, String), Vec<CompactedRow>> {
+        let mut groups: BTreeMap<(bool, String), Vec<CompactedRow>> = BTreeMap::new();
+        for row in rows {
+            let columns = serde_json::to_string(&row.columns).unwrap_or_default();
+            groups.entry((!row.tombstone, columns)).or_default().push(row);
+        }
+        groups
+    }
+}
@@ -0,0 +1,44 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+/// segment 的压缩方式, 记录在 segment_header 中.
+///
+/// 写入中的 segment 不压缩, 写满滚动到下一个 segment 时整体压缩 entry 区域
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum CompressionCodec {
+    #[default]
+    None = 0,
+    Zstd = 1,
+}
+
+impl TryFrom<&str> for CompressionCodec {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "none" => Ok(CompressionCodec::None),
+            "zstd" => Ok(CompressionCodec::Zstd),
+            _ => Err(ReError::String(format!("Unsupported relay log compression: {}", value))),
+        }
+    }
+}
+
+impl CompressionCodec {
+    pub fn compress(&self, level: i32, data: &[u8]) -> CResult<Vec<u8>> {
+        match self {
+            CompressionCodec::None => Ok(data.to_vec()),
+            CompressionCodec::Zstd => Ok(zstd::bulk::compress(data, level)?),
+        }
+    }
+
+    /// raw_size: 压缩前的大小
+    pub fn decompress(&self, data: &[u8], raw_size: usize) -> CResult<Vec<u8>> {
+        match self {
+            CompressionCodec::None => Ok(data.to_vec()),
+            CompressionCodec::Zstd => Ok(zstd::bulk::decompress(data, raw_size)?),
+        }
+    }
+}
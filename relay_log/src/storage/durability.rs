@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use common::err::decode_error::ReError;
+
+use crate::storage::storage_config::StorageConfig;
+
+/// segment 的落盘策略, 决定何时对 segment 文件调用 fsync.
+///
+/// 写入的entry以批次为单位提交(写入提交标记), 批次的大小由 group commit 配置决定;
+/// 只有提交且 fsync 之后的批次才能在宕机后保留
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncPolicy {
+    /// 只写入操作系统缓冲, 由操作系统决定落盘时机
+    #[default]
+    OsBuffered,
+    /// 每个entry单独作为一个批次提交并 fsync
+    PerEntry,
+    /// 每个批次提交后 fsync
+    PerBatch,
+    /// 批次提交时距上次 fsync 超过 fsync_interval_millisecond 才 fsync, 之后没有新的批次时由 [RelayLogStorage::tick] 补齐
+    ///
+    /// [RelayLogStorage::tick]: crate::storage::relay_log_storage::RelayLogStorage::tick
+    Interval,
+}
+
+impl TryFrom<&str> for FsyncPolicy {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "" | "os_buffered" | "none" => Ok(FsyncPolicy::OsBuffered),
+            "per_entry" | "entry" => Ok(FsyncPolicy::PerEntry),
+            "per_batch" | "batch" => Ok(FsyncPolicy::PerBatch),
+            "interval" => Ok(FsyncPolicy::Interval),
+            _ => Err(ReError::String(format!("Unsupported relay log fsync policy: {}", value))),
+        }
+    }
+}
+
+/// 提交批次时的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct CommitAction {
+    // 写入提交标记
+    pub commit: bool,
+    // 提交后 fsync
+    pub sync: bool,
+}
+
+/// 追加entry的 group commit 状态:
+/// 攒够 group_commit_entries 个entry, 或批次中最早的entry超过 group_commit_millisecond 时提交一个批次
+#[derive(Debug)]
+pub(crate) struct GroupCommit {
+    policy: FsyncPolicy,
+    // 每批最多的entry数量, 0 表示只在显式 flush 时提交
+    max_entries: u32,
+    // 批次最长等待时长, 0 表示不限制
+    max_wait: Duration,
+    // Interval 策略的 fsync 间隔
+    sync_interval: Duration,
+
+    // 当前批次未提交的entry数量
+    pending: u32,
+    // 当前批次第一个entry的追加时间
+    batch_started: Option<Instant>,
+    // 上次 fsync 时间
+    last_sync: Instant,
+    // 是否有已提交但未 fsync 的批次
+    unsynced: bool,
+}
+
+impl GroupCommit {
+    pub fn new(policy: FsyncPolicy, max_entries: u32, max_wait: Duration, sync_interval: Duration) -> Self {
+        Self {
+            policy,
+            max_entries,
+            max_wait,
+            sync_interval,
+            pending: 0,
+            batch_started: None,
+            last_sync: Instant::now(),
+            unsynced: false,
+        }
+    }
+
+    /// flush_on_commit 等价于每批一个entry
+    pub fn from_config(config: &StorageConfig) -> Self {
+        let max_entries = match *config.group_commit_entries() {
+            0 if *config.flush_on_commit() => 1,
+            n => n,
+        };
+        Self::new(*config.fsync_policy(),
+                  max_entries,
+                  Duration::from_millis(*config.group_commit_millisecond()),
+                  Duration::from_millis(*config.fsync_interval_millisecond()))
+    }
+
+    pub fn policy(&self) -> FsyncPolicy {
+        self.policy
+    }
+
+    /// 追加一个entry之后调用
+    pub fn on_append(&mut self) -> CommitAction {
+        self.pending += 1;
+        let started = *self.batch_started.get_or_insert_with(Instant::now);
+
+        let full = match self.policy {
+            FsyncPolicy::PerEntry => true,
+            _ => self.max_entries > 0 && self.pending >= self.max_entries,
+        };
+        let expired = !self.max_wait.is_zero() && started.elapsed() >= self.max_wait;
+        if full || expired {
+            self.on_commit()
+        } else {
+            CommitAction::default()
+        }
+    }
+
+    /// 显式提交当前批次(flush、segment写满滚动)时调用
+    pub fn on_commit(&mut self) -> CommitAction {
+        self.pending = 0;
+        self.batch_started = None;
+        self.unsynced = true;
+        let sync = match self.policy {
+            FsyncPolicy::OsBuffered => false,
+            FsyncPolicy::PerEntry | FsyncPolicy::PerBatch => true,
+            FsyncPolicy::Interval => self.last_sync.elapsed() >= self.sync_interval,
+        };
+        CommitAction { commit: true, sync }
+    }
+
+    /// 没有追加时定时调用: 批次等待超过 max_wait 时提交; Interval 策略下已提交的批次距上次 fsync 超过间隔时 fsync
+    pub fn on_tick(&mut self) -> CommitAction {
+        let expired = self.pending > 0 && !self.max_wait.is_zero()
+            && self.batch_started.map_or(false, |started| started.elapsed() >= self.max_wait);
+        if expired {
+            return self.on_commit();
+        }
+
+        let sync = self.policy == FsyncPolicy::Interval && self.unsynced && self.last_sync.elapsed() >= self.sync_interval;
+        CommitAction { commit: sync, sync }
+    }
+
+    /// 已完成 fsync
+    pub fn on_sync(&mut self) {
+        self.last_sync = Instant::now();
+        self.unsynced = false;
+    }
+
+    /// 是否有已提交但未 fsync 的批次, 用于关闭前或写满滚动时补齐 fsync
+    pub fn is_unsynced(&self) -> bool {
+        self.unsynced && self.policy != FsyncPolicy::OsBuffered
+    }
+}
@@ -9,5 +9,18 @@ pub mod segment_file;
 pub mod segment_header;
 pub mod segment_entry_position;
 pub mod file_system;
+pub mod compression;
 
 
+
+pub mod segment_index;
+pub mod retention;
+pub mod relay_log_tail;
+pub mod tiering;
+pub mod storage_metrics;
+pub mod durability;
+pub mod archive;
+pub mod partition;
+pub mod storage_backend;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb_storage;
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use common::err::CResult;
+
+use crate::storage::relay_log_storage::RelayLogStorage;
+use crate::storage::segment_manager::SegmentManager;
+use crate::storage::storage_config::StorageConfig;
+
+/// 同一存储根目录下的多个命名分区, 每个上游源或租户一个分区:
+///
+/// ```txt
+/// relay_log_dir/
+///   {db}#{table}/            -> 默认分区
+///   {partition}/{db}#{table}/
+/// ```
+///
+/// 每个分区的每张表是独立的 [RelayLogStorage], 消费者offset、保留策略与指标互不影响;
+/// 分区可以通过 [RelayLogPartitions::with_partition_config] 使用单独的配置, 例如不同的保留时长
+#[derive(Debug, Clone)]
+pub struct RelayLogPartitions {
+    // 存储根目录与分区的默认配置
+    config: StorageConfig,
+    // 分区单独的配置
+    partition_configs: BTreeMap<String, StorageConfig>,
+}
+
+impl RelayLogPartitions {
+    pub fn new(config: StorageConfig) -> Self {
+        Self {
+            config,
+            partition_configs: BTreeMap::new(),
+        }
+    }
+
+    /// 分区单独的配置, relay_log_dir 与 partition 以存储根目录和分区名为准
+    pub fn with_partition_config(mut self, partition: &str, config: StorageConfig) -> CResult<Self> {
+        SegmentManager::check_partition_name(partition)?;
+        let mut config = config.for_partition(partition);
+        config.set_relay_log_dir(self.config.relay_log_dir().clone());
+        self.partition_configs.insert(partition.to_string(), config);
+        Ok(self)
+    }
+
+    /// 已有日志或单独配置过的分区名, 不包含默认分区
+    pub fn partitions(&self) -> CResult<Vec<String>> {
+        let mut partitions = SegmentManager::partitions(self.config.relay_log_dir())?;
+        partitions.extend(self.partition_configs.keys().cloned());
+        partitions.sort();
+        partitions.dedup();
+        Ok(partitions)
+    }
+
+    /// 分区的配置, partition 为空时为默认分区
+    pub fn config(&self, partition: &str) -> CResult<StorageConfig> {
+        if partition.is_empty() {
+            return Ok(self.config.for_partition(""));
+        }
+        SegmentManager::check_partition_name(partition)?;
+        Ok(self.partition_configs.get(partition)
+            .cloned()
+            .unwrap_or_else(|| self.config.for_partition(partition)))
+    }
+
+    /// 打开分区中目标表的中继日志, 不存在时创建
+    pub fn open(&self, partition: &str, dst_db_name: &str, dst_table_name: &str) -> CResult<RelayLogStorage> {
+        RelayLogStorage::new(&self.config(partition)?, dst_db_name.to_string(), dst_table_name.to_string())
+    }
+
+    /// 分区中已有日志的目标表: (库, 表)
+    pub fn tables(&self, partition: &str) -> CResult<Vec<(String, String)>> {
+        SegmentManager::tables(&self.config(partition)?.partition_dir())
+    }
+
+    /// 删除分区的全部日志, 调用前需要关闭该分区打开的 [RelayLogStorage]
+    pub fn remove(&mut self, partition: &str) -> CResult<()> {
+        SegmentManager::check_partition_name(partition)?;
+        let dir = self.config(partition)?.partition_dir();
+        if Path::new(&dir).exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        self.partition_configs.remove(partition);
+        Ok(())
+    }
+}
@@ -1,13 +1,27 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use tracing::warn;
 
 use common::err::CResult;
+use common::memory_governor::{MemoryConsumer, MemoryGovernor, MemoryPressure};
 
 use crate::relay_log::RelayLog;
+use crate::storage::archive;
+use crate::storage::archive::ArchiveManifest;
+use crate::storage::compactor::{CompactStats, Compactor};
+use crate::storage::durability::{CommitAction, GroupCommit};
+use crate::storage::relay_log_tail::RelayLogTail;
+use crate::storage::retention::{ConsumerOffsets, PurgeStats, PurgeTrigger, RetentionPolicy};
 use crate::storage::segment::Segment;
 use crate::storage::segment_manager::SegmentManager;
 use crate::storage::storage_config::StorageConfig;
 use crate::storage::storage_entry::StorageEntry;
+use crate::storage::storage_metrics::StorageMetrics;
+use crate::storage::tiering::{TierStats, TieringPolicy};
 
 /// todo 目标表日志存储
 pub struct RelayLogStorage {
@@ -19,6 +33,22 @@ pub struct RelayLogStorage {
     pub segment_manager: SegmentManager,
     // 环形队列
     entry_buffer: EntryRingBuffer,
+    // 批次提交与落盘
+    group_commit: GroupCommit,
+    // 保留策略
+    retention: RetentionPolicy,
+    // 消费者offset, 清理时不删除未消费的segment
+    consumer_offsets: ConsumerOffsets,
+    // 冷segment分层策略
+    tiering: TieringPolicy,
+    // 定时清理与分层, 未配置保留策略与分层策略时为 None
+    purge_trigger: Option<PurgeTrigger>,
+    // 已写入文件、可被尾随读取的最后一个 index
+    appended: watch::Sender<u64>,
+    // 运行指标
+    metrics: StorageMetrics,
+    // 上次读取的segment的第一个 index, 读取移到其它segment时释放其解压数据
+    reading_segment: Option<u64>,
     // 日志整理
     // log_compactor: Compactor,
 }
@@ -27,21 +57,32 @@ struct EntryRingBuffer {
     entry_buffer_num: usize,
     // entry缓存
     entry_buffer: Vec<Option<Rc<StorageEntry>>>,
-
+    // 缓存占用的内存, 内存紧张时清空缓存, 读取时从segment加载
+    memory: MemoryConsumer,
 }
 
 impl EntryRingBuffer {
-    pub fn new(entry_buffer_num: usize) -> Self {
+    pub fn new(entry_buffer_num: usize, name: &str) -> Self {
         let entry_buffer: Vec<Option<Rc<StorageEntry>>> = vec![None; entry_buffer_num];
         Self {
             entry_buffer_num,
             entry_buffer,
+            memory: MemoryGovernor::global().register(name),
         }
     }
 
     pub fn add(&mut self, entry: StorageEntry) {
         let offset = self.offset(*entry.index());
-        self.entry_buffer[offset] = Some(Rc::new(entry));
+        if let Some(evicted) = self.entry_buffer[offset].take() {
+            self.memory.release(*evicted.log_size());
+        }
+        if self.memory.pressure() != MemoryPressure::Normal {
+            self.clear();
+            return;
+        }
+        if self.memory.try_reserve(*entry.log_size()) {
+            self.entry_buffer[offset] = Some(Rc::new(entry));
+        }
     }
 
     pub fn get(&self, index: u64) -> Option<Rc<StorageEntry>> {
@@ -60,6 +101,11 @@ impl EntryRingBuffer {
         }
     }
 
+    pub fn clear(&mut self) {
+        self.entry_buffer.iter_mut().for_each(|e| *e = None);
+        self.memory.release_all();
+    }
+
     fn offset(&self, index: u64) -> usize {
         let mut offset = index as usize % self.entry_buffer_num;
         if offset < 0 {
@@ -72,40 +118,318 @@ impl EntryRingBuffer {
 impl RelayLogStorage {
     pub fn new(storage_config: &StorageConfig, dst_db_name: String, dst_table_name: String) -> CResult<Self> {
         let segment_manager = SegmentManager::new(storage_config, &dst_db_name, &dst_table_name)?;
-        let entry_buffer = EntryRingBuffer::new(*storage_config.entry_buffer_num());
+        let recovery = segment_manager.recovery();
+        if recovery.entries > 0 || recovery.bytes > 0 {
+            warn!("relay log {}#{} recovered from torn write, discard {} entries, {} bytes.",
+                dst_db_name, dst_table_name, recovery.entries, recovery.bytes);
+        }
+        Self::with_segment_manager(storage_config, dst_db_name, dst_table_name, segment_manager)
+    }
+
+    /// 只读打开, 供 web 查询等与写入方并存的读取者使用, 见 [SegmentManager::open_read_only]。
+    /// 不截断、不追加、不写入提交标记, 也不定时清理
+    pub fn open_read_only(storage_config: &StorageConfig, dst_db_name: String, dst_table_name: String) -> CResult<Self> {
+        let segment_manager = SegmentManager::open_read_only(storage_config, &dst_db_name, &dst_table_name)?;
+        Self::with_segment_manager(storage_config, dst_db_name, dst_table_name, segment_manager)
+    }
+
+    fn with_segment_manager(storage_config: &StorageConfig, dst_db_name: String, dst_table_name: String,
+                            segment_manager: SegmentManager) -> CResult<Self> {
+        let entry_buffer = EntryRingBuffer::new(*storage_config.entry_buffer_num(),
+                                                &format!("relay_cache:{}#{}", dst_db_name, dst_table_name));
+        let retention = RetentionPolicy::from_config(storage_config);
+        let tiering = TieringPolicy::from_config(storage_config);
+        let purge_trigger = if !segment_manager.is_read_only() && (retention.is_enabled() || tiering.is_enabled()) {
+            Some(PurgeTrigger::start(Duration::from_millis((*storage_config.purge_interval_millisecond()).max(1))))
+        } else {
+            None
+        };
+        let (appended, _) = watch::channel(segment_manager.current_segment().borrow().last_index());
+        let metrics = StorageMetrics::new(storage_config.partition(), &dst_db_name, &dst_table_name);
+        metrics.set_segments(segment_manager.segment_count());
+        metrics.set_active_segment_bytes(segment_manager.current_segment().borrow().current_segment_size());
         Ok(Self {
             dst_db_name,
             dst_table_name,
             segment_manager,
             entry_buffer,
+            group_commit: GroupCommit::from_config(storage_config),
+            retention,
+            tiering,
+            consumer_offsets: ConsumerOffsets::default(),
+            purge_trigger,
+            appended,
+            metrics,
+            reading_segment: None,
         })
     }
 
     /// 追加中继日志
     pub fn append_relay_log(&mut self, log: RelayLog) -> CResult<()> {
+        self.append_relay_log_with_gtid(log, None)
+    }
+
+    /// 追加中继日志, gtid 为日志所属事务的GTID, 记录到segment索引中供 [RelayLogStorage::seek_gtid] 使用
+    pub fn append_relay_log_with_gtid(&mut self, log: RelayLog, gtid: Option<&str>) -> CResult<()> {
         let mut entry = self.create_entry(log)?;
         let current_segment = self.current_usable_segment()?;
         // append to disk
-        current_segment.borrow_mut().append(&mut entry)?;
+        let size = current_segment.borrow().current_segment_size();
+        current_segment.borrow_mut().append_with_gtid(&mut entry, gtid)?;
+        let appended_size = current_segment.borrow().current_segment_size();
+        self.metrics.record_write(appended_size.saturating_sub(size));
+        self.metrics.set_active_segment_bytes(appended_size);
+        let action = self.group_commit.on_append();
+        self.commit(&current_segment, action)?;
+        // 有尾随读取器时flush写缓冲并通知
+        if self.appended.receiver_count() > 0 {
+            current_segment.borrow_mut().flush_buffer()?;
+            self.appended.send_replace(*entry.index());
+        }
         // add buf
         self.entry_buffer.add(entry);
 
+        if self.purge_trigger.as_ref().map_or(false, |t| t.take()) {
+            if let Err(e) = self.purge() {
+                warn!("purge relay log {}#{} err: {:?}", self.dst_db_name, self.dst_table_name, e);
+            }
+            if let Err(e) = self.offload() {
+                warn!("offload relay log {}#{} err: {:?}", self.dst_db_name, self.dst_table_name, e);
+            }
+        }
+
         // todo send storage_event
 
         Ok(())
     }
 
+    /// flush 当前segment, 已追加的日志作为一个批次写入提交标记
+    pub fn flush(&mut self) -> CResult<()> {
+        let current = self.segment_manager.current_segment();
+        let action = self.group_commit.on_commit();
+        self.commit(&current, action)?;
+        self.appended.send_replace(current.borrow().last_index());
+        Ok(())
+    }
+
+    /// 提交当前批次并 fsync, 与落盘策略无关
+    pub fn sync(&mut self) -> CResult<()> {
+        let current = self.segment_manager.current_segment();
+        self.group_commit.on_commit();
+        self.commit(&current, CommitAction { commit: true, sync: true })?;
+        self.appended.send_replace(current.borrow().last_index());
+        Ok(())
+    }
+
+    /// 按 group_commit_millisecond 提交等待过久的批次, 按 Interval 策略 fsync 已提交的批次。
+    ///
+    /// 追加时会检查, 不再追加时由持有者定时调用; 读取、尾随读取与查询 index 范围时也会检查
+    pub fn tick(&mut self) -> CResult<()> {
+        let action = self.group_commit.on_tick();
+        if !action.commit {
+            return Ok(());
+        }
+        let current = self.segment_manager.current_segment();
+        self.commit(&current, action)?;
+        self.appended.send_replace(current.borrow().last_index());
+        Ok(())
+    }
+
+    /// 从 from_offset 开始尾随读取, 读到末尾后等待新追加的entry, 支持阻塞读取与异步流。
+    ///
+    /// 读取器可以在其它线程中使用, 解码、web会话与binlog server等多个消费者各自持有一个读取器
+    pub fn tail(&mut self, from_offset: u64) -> CResult<RelayLogTail> {
+        self.tick()?;
+        let current = self.segment_manager.current_segment();
+        current.borrow_mut().flush_buffer()?;
+        self.appended.send_replace(current.borrow().last_index());
+
+        Ok(RelayLogTail::new(self.segment_manager.segment_dir().to_string(), from_offset, self.appended.subscribe()))
+    }
+
+    /// 目标库
+    pub fn dst_db_name(&self) -> &str {
+        &self.dst_db_name
+    }
+
+    /// 目标表
+    pub fn dst_table_name(&self) -> &str {
+        &self.dst_table_name
+    }
+
+    /// 导出 [from_index, to_index] 到归档文件夹, 见 [archive::export]
+    pub fn export(&mut self, from_index: u64, to_index: u64, archive_dir: &str) -> CResult<ArchiveManifest> {
+        self.flush()?;
+        archive::export(self, from_index, to_index, archive_dir)
+    }
+
+    /// 导入归档并打开导入后的中继日志, 见 [archive::import]
+    pub fn import(storage_config: &StorageConfig, archive_dir: &str) -> CResult<(Self, ArchiveManifest)> {
+        let manifest = archive::import(storage_config, archive_dir)?;
+        let storage = Self::new(storage_config, manifest.database.clone(), manifest.table.clone())?;
+        Ok((storage, manifest))
+    }
+
+    /// 运行指标, 可交给其它线程读取
+    pub fn metrics(&self) -> StorageMetrics {
+        self.metrics.clone()
+    }
+
+    /// 消费者offset, 可交给其它线程中的消费者提交
+    pub fn consumer_offsets(&self) -> ConsumerOffsets {
+        self.consumer_offsets.clone()
+    }
+
+    /// 按保留策略立即清理, 不删除活跃消费者尚未读取的segment
+    pub fn purge(&mut self) -> CResult<PurgeStats> {
+        let protected_from = self.consumer_offsets.min_offset();
+        let stats = self.segment_manager.purge(&self.retention, protected_from)?;
+        self.metrics.record_purge(&stats);
+        self.metrics.set_segments(self.segment_manager.segment_count());
+        Ok(stats)
+    }
+
+    /// 按分层策略立即把冷segment上传到对象存储, 之后读取这些segment时自动取回
+    pub fn offload(&mut self) -> CResult<TierStats> {
+        let protected_from = self.consumer_offsets.min_offset();
+        let stats = self.segment_manager.offload(&self.tiering, protected_from)?;
+        self.metrics.record_offload(&stats);
+        self.metrics.set_segments(self.segment_manager.segment_count());
+        Ok(stats)
+    }
+
+    /// 按主键压缩已写满且所有消费者都已读取完的segment, 压缩后的segment保留原 index 区间的起点,
+    /// 区间中多出的 index 不再存在
+    pub fn compact(&mut self, compactor: &Compactor) -> CResult<CompactStats> {
+        let protected_from = self.consumer_offsets.min_offset();
+        let current = self.segment_manager.current_segment();
+        let segments: Vec<Rc<RefCell<Segment>>> = self.segment_manager.segments()
+            .into_iter()
+            .take_while(|s| !Rc::ptr_eq(s, &current)
+                && !s.borrow().is_empty()
+                && protected_from.map_or(true, |offset| s.borrow().last_index() < offset))
+            .collect();
+        if segments.is_empty() {
+            return Ok(CompactStats::default());
+        }
+
+        self.metrics.compaction_started(segments.len());
+        let rs = self.compact_segments(compactor, &segments);
+        self.metrics.record_compact(rs.as_ref().unwrap_or(&CompactStats::default()));
+        self.metrics.set_segments(self.segment_manager.segment_count());
+        rs
+    }
+
+    fn compact_segments(&mut self, compactor: &Compactor, segments: &[Rc<RefCell<Segment>>]) -> CResult<CompactStats> {
+        let first = segments[0].borrow().first_index();
+        let last = segments[segments.len() - 1].borrow().last_index();
+        let mut logs = Vec::with_capacity((last - first + 1) as usize);
+        for (i, s) in segments.iter().enumerate() {
+            let (segment_first, segment_last) = (s.borrow().first_index(), s.borrow().last_index());
+            for index in segment_first..=segment_last {
+                logs.push(self.get_entry(index)?.relay_log().clone());
+            }
+            self.metrics.compaction_progress(i + 1);
+        }
+        let entries_before = logs.len() as u64;
+
+        let compacted = match compactor.compact_logs(logs.into_iter(), entries_before as usize)? {
+            None => return Ok(CompactStats::default()),
+            Some(compacted) => compacted,
+        };
+        let entries_after = compacted.len() as u64;
+        self.segment_manager.rewrite(segments, compacted)?;
+        // 缓存中的entry可能已被重写
+        self.entry_buffer.clear();
+
+        Ok(CompactStats {
+            segments: segments.len(),
+            entries_before,
+            entries_after,
+        })
+    }
+
     /// get an entry by index
     pub fn get_entry(&mut self, index: u64) -> CResult<Rc<StorageEntry>> {
+        self.tick()?;
         if let Some(entry) = &self.entry_buffer.get(index) {
             Ok(Rc::clone(entry))
         } else {
             let segment = self.segment_manager.segment(index)?;
+            let first_index = segment.borrow().first_index();
+            if let Some(previous) = self.reading_segment.replace(first_index).filter(|i| *i != first_index) {
+                // 只在已加载的segment中查找, 不重新下载已分层的segment
+                if let Some(previous) = self.segment_manager.segments().iter().find(|s| s.borrow().first_index() == previous) {
+                    previous.borrow_mut().release_data();
+                }
+            }
             let entry = segment.borrow_mut().get_entry(index)?;
+            // index + size + checksum
+            self.metrics.record_read(*entry.log_size() + 20);
             Ok(Rc::new(entry))
         }
     }
 
+    /// 已存储的 index 范围 [first, last], 包括已上传到对象存储的segment, 没有日志时返回 None
+    pub fn index_range(&mut self) -> CResult<Option<(u64, u64)>> {
+        self.tick()?;
+        let first = self.segment_manager.first_index()?;
+        let last = self.segment_manager.last_segment()?.borrow().last_index();
+        if first == 0 || last < first {
+            return Ok(None);
+        }
+
+        Ok(Some((first, last)))
+    }
+
+    /// binlog 中位于 pos 及之后的第一个 entry index, 用于按 position 断点续传。
+    ///
+    /// RelayLog 中没有 binlog 文件名, 多个 binlog 文件中存在相同的 pos 时取最后一个文件,
+    /// 从最新的segment开始查找; pos 之后没有日志时返回 None
+    pub fn seek_position(&mut self, pos: u64) -> CResult<Option<u64>> {
+        let last = match self.index_range()? {
+            None => return Ok(None),
+            Some((_, last)) => last,
+        };
+
+        for segment in self.segment_manager.segments().iter().rev() {
+            if let Some(index) = segment.borrow_mut().seek_position(pos)? {
+                return Ok(Some(index).filter(|i| *i <= last));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// gtid 所在事务的第一个 entry index
+    pub fn seek_gtid(&mut self, gtid: &str) -> CResult<Option<u64>> {
+        for segment in self.segment_manager.segments().iter().rev() {
+            if let Some(index) = segment.borrow().seek_gtid(gtid) {
+                return Ok(Some(index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// 按事件时间戳扫描, 返回的迭代器按 index 升序产出时间戳在 [from_ts, to_ts] 内的entry。
+    ///
+    /// 时间范围不相交的segment直接跳过, segment内通过时间戳索引跳过之前的entry; 相交的已分层segment先取回本地
+    pub fn scan_range(&mut self, from_ts: u32, to_ts: u32) -> CResult<RangeScan<'_>> {
+        self.segment_manager.fetch_range(from_ts, to_ts)?;
+        let ranges = self.segment_manager.segments()
+            .iter()
+            .filter_map(|s| s.borrow().scan_bounds(from_ts, to_ts))
+            .collect();
+
+        Ok(RangeScan {
+            storage: self,
+            from_ts,
+            to_ts,
+            ranges,
+        })
+    }
+
     /// 创建中继日志存储实体
     fn create_entry(&mut self, log: RelayLog) -> CResult<StorageEntry> {
         let current_segment = self.current_usable_segment()?;
@@ -117,9 +441,65 @@ impl RelayLogStorage {
     fn current_usable_segment(&mut self) -> CResult<Rc<RefCell<Segment>>> {
         let mut current_segment = self.segment_manager.current_segment();
         if current_segment.borrow().is_full() {
-            current_segment.borrow_mut().write_flush()?;
+            // 写满的segment不再写入, 落盘策略不是 OsBuffered 时补齐 fsync
+            let mut action = self.group_commit.on_commit();
+            action.sync |= self.group_commit.is_unsynced();
+            self.commit(&current_segment, action)?;
             current_segment = self.segment_manager.create_next_segment()?;
+            self.metrics.set_segments(self.segment_manager.segment_count());
         }
         Ok(current_segment)
     }
-}
\ No newline at end of file
+
+    /// 按 action 写入提交标记、fsync, 并记录 fsync 耗时
+    fn commit(&mut self, segment: &Rc<RefCell<Segment>>, action: CommitAction) -> CResult<()> {
+        if !action.commit {
+            return Ok(());
+        }
+        let start = Instant::now();
+        segment.borrow_mut().write_flush()?;
+        if action.sync {
+            segment.borrow_mut().sync_data()?;
+            self.group_commit.on_sync();
+            self.metrics.record_fsync(start.elapsed());
+        }
+        Ok(())
+    }
+}
+
+/// [RelayLogStorage::scan_range] 返回的迭代器, 读取失败时产出错误并结束
+pub struct RangeScan<'a> {
+    storage: &'a mut RelayLogStorage,
+    from_ts: u32,
+    to_ts: u32,
+    // 待扫描的 index 区间 [start, end]
+    ranges: VecDeque<(u64, u64)>,
+}
+
+impl Iterator for RangeScan<'_> {
+    type Item = CResult<Rc<StorageEntry>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((start, end)) = self.ranges.pop_front() {
+            if start > end {
+                continue;
+            }
+            self.ranges.push_front((start + 1, end));
+
+            match self.storage.get_entry(start) {
+                Ok(entry) => {
+                    let timestamp = *entry.relay_log().event_timestamp();
+                    if timestamp >= self.from_ts && timestamp <= self.to_ts {
+                        return Some(Ok(entry));
+                    }
+                }
+                Err(e) => {
+                    self.ranges.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        None
+    }
+}
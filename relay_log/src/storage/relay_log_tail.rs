@@ -0,0 +1,154 @@
+use std::fmt::{Debug, Formatter};
+
+use futures_util::Stream;
+use futures_util::stream;
+use tokio::sync::watch;
+
+use common::err::CResult;
+
+use crate::storage::segment::Segment;
+use crate::storage::segment_manager::SegmentManager;
+use crate::storage::storage_entry::StorageEntry;
+
+/// [crate::storage::relay_log_storage::RelayLogStorage::tail] 返回的尾随读取器,
+/// 按 index 顺序产出已追加的entry, 读到末尾后等待写入方通知新的entry。
+///
+/// 读取器独立只读打开segment文件, 可以移动到其它线程或异步任务中, 多个读取器互不影响。
+/// 起始 offset 已被清理时从最早的entry开始, 主键压缩后不存在的 index 被跳过;
+/// 存储关闭后读完剩余的entry即结束
+pub struct RelayLogTail {
+    // 目标表的日志文件夹
+    segment_dir: String,
+    // 下一个要读取的 index
+    next_index: u64,
+    // 当前读取的segment
+    segment: Option<Segment>,
+    // 写入方已写入文件的最后一个 index
+    appended: watch::Receiver<u64>,
+}
+
+impl RelayLogTail {
+    pub(crate) fn new(segment_dir: String, from_offset: u64, appended: watch::Receiver<u64>) -> Self {
+        Self {
+            segment_dir,
+            next_index: from_offset,
+            segment: None,
+            appended,
+        }
+    }
+
+    /// 下一个要读取的 index, 可作为消费者 offset 提交
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+
+    /// 写入方已写入文件的最后一个 index, 与 next_index 的差即为未读取的entry数量
+    pub fn appended_index(&self) -> u64 {
+        *self.appended.borrow()
+    }
+
+    /// 读取下一个已追加的entry, 没有新的entry时立即返回 None
+    pub fn try_next(&mut self) -> CResult<Option<StorageEntry>> {
+        let appended = *self.appended.borrow_and_update();
+        while self.next_index <= appended {
+            let index = self.next_index;
+            if let Some(entry) = self.read_next()? {
+                return Ok(Some(entry));
+            }
+            // 没有跳过不存在的 index 时等待下一次通知
+            if self.next_index == index {
+                break;
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// 等待并读取下一个entry, 存储关闭且已读完时返回 None
+    pub async fn next(&mut self) -> Option<CResult<StorageEntry>> {
+        loop {
+            match self.try_next() {
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            if self.appended.changed().await.is_err() {
+                // 写入方已关闭, 读取关闭前写入的剩余entry
+                return self.try_next().transpose();
+            }
+        }
+    }
+
+    /// [RelayLogTail::next] 的阻塞版本, 阻塞当前线程, 不能在异步任务中调用
+    pub fn next_blocking(&mut self) -> Option<CResult<StorageEntry>> {
+        futures_executor::block_on(self.next())
+    }
+
+    /// 阻塞迭代器, 见 [RelayLogTail::next_blocking]
+    pub fn blocking_iter(&mut self) -> impl Iterator<Item = CResult<StorageEntry>> + '_ {
+        std::iter::from_fn(move || self.next_blocking())
+    }
+
+    /// 转换为异步流
+    pub fn into_stream(self) -> impl Stream<Item = CResult<StorageEntry>> {
+        stream::unfold(self, |mut tail| async move {
+            tail.next().await.map(|entry| (entry, tail))
+        })
+    }
+
+    /// 读取 next_index 所在的entry。
+    ///
+    /// index 早于最早的segment或落在主键压缩后的空缺中时, 把 next_index 移到下一个segment的起点并返回 None
+    fn read_next(&mut self) -> CResult<Option<StorageEntry>> {
+        let index = self.next_index;
+        let contained = match &mut self.segment {
+            Some(segment) => {
+                segment.refresh()?;
+                segment.contain_index(index)
+            }
+            None => false,
+        };
+
+        if !contained {
+            let files = SegmentManager::segment_files(&self.segment_dir)?;
+            let path = match files.range(..=index).next_back() {
+                Some((_, path)) => path,
+                None => {
+                    // 已被清理
+                    if let Some(first) = files.keys().next() {
+                        self.next_index = *first;
+                    }
+                    return Ok(None);
+                }
+            };
+
+            let segment = Segment::open_read_only(path)?;
+            if !segment.contain_index(index) {
+                if let Some((first, _)) = files.range(index + 1..).next() {
+                    self.next_index = *first;
+                }
+                return Ok(None);
+            }
+            self.segment = Some(segment);
+        }
+
+        match &mut self.segment {
+            Some(segment) => {
+                let entry = segment.get_entry(index)?;
+                self.next_index += 1;
+                Ok(Some(entry))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Debug for RelayLogTail {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelayLogTail")
+            .field("segment_dir", &self.segment_dir)
+            .field("next_index", &self.next_index)
+            .field("segment", &self.segment)
+            .finish()
+    }
+}
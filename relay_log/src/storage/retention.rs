@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use std::thread;
+use std::time::Duration;
+
+use tracing::error;
+
+use crate::storage::storage_config::StorageConfig;
+
+/// segment 保留策略, 任一条件满足即可删除最旧的已写满segment, 0 表示不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    // 写满后保留的时长, 毫秒
+    pub max_age_millisecond: u64,
+    // 全部segment的最大字节数
+    pub max_bytes: u64,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &StorageConfig) -> Self {
+        Self {
+            max_age_millisecond: *config.retention_millisecond(),
+            max_bytes: *config.retention_bytes(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_age_millisecond > 0 || self.max_bytes > 0
+    }
+}
+
+/// 一次清理的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeStats {
+    pub segments: usize,
+    pub bytes: u64,
+}
+
+/// 消费者已提交的 offset(下一个待读取的 entry index), 可在消费线程间共享。
+///
+/// 包含最小 offset 的 segment 及之后的 segment 不会被清理
+#[derive(Debug, Clone, Default)]
+pub struct ConsumerOffsets {
+    offsets: Arc<RwLock<BTreeMap<String, u64>>>,
+}
+
+impl ConsumerOffsets {
+    /// 提交消费者的 offset
+    pub fn commit(&self, consumer: &str, offset: u64) {
+        self.offsets.write().unwrap().insert(consumer.to_string(), offset);
+    }
+
+    /// 消费者退出后移除, 不再阻止清理
+    pub fn remove(&self, consumer: &str) -> Option<u64> {
+        self.offsets.write().unwrap().remove(consumer)
+    }
+
+    pub fn get(&self, consumer: &str) -> Option<u64> {
+        self.offsets.read().unwrap().get(consumer).copied()
+    }
+
+    /// 所有活跃消费者中最小的 offset, 没有消费者时返回 None
+    pub fn min_offset(&self) -> Option<u64> {
+        self.offsets.read().unwrap().values().min().copied()
+    }
+}
+
+/// 后台定时器, 每个周期标记一次需要清理, 由写入线程在追加日志时执行清理。
+///
+/// segment 不能跨线程访问, 因此清理本身不在后台线程中执行; 持有者释放后后台线程随之退出
+#[derive(Debug)]
+pub struct PurgeTrigger {
+    due: Arc<AtomicBool>,
+}
+
+impl PurgeTrigger {
+    pub fn start(interval: Duration) -> Self {
+        let due = Arc::new(AtomicBool::new(true));
+        let weak: Weak<AtomicBool> = Arc::downgrade(&due);
+        let rs = thread::Builder::new().name(String::from("relay-log-purge")).spawn(move || {
+            loop {
+                thread::sleep(interval);
+                match weak.upgrade() {
+                    Some(due) => due.store(true, Ordering::Release),
+                    None => break,
+                }
+            }
+        });
+        if let Err(e) = rs {
+            error!("start relay log purge timer err: {:?}", e);
+        }
+
+        Self { due }
+    }
+
+    /// 是否到了清理时间, 返回 true 后重新计时
+    pub fn take(&self) -> bool {
+        self.due.swap(false, Ordering::AcqRel)
+    }
+}
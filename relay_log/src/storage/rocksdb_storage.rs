@@ -0,0 +1,257 @@
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use checksum::crc32::Crc32;
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{ColumnFamilyDescriptor, DB, IteratorMode, Options, WriteBatch, WriteOptions};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+use crate::codec::binary_codec::{BinaryCodec, CodecStyle};
+use crate::relay_log::RelayLog;
+use crate::storage::durability::{CommitAction, FsyncPolicy, GroupCommit};
+use crate::storage::retention::{ConsumerOffsets, PurgeStats, RetentionPolicy};
+use crate::storage::segment_manager::SegmentManager;
+use crate::storage::storage_backend::StorageBackend;
+use crate::storage::storage_config::StorageConfig;
+use crate::storage::storage_entry::StorageEntry;
+use crate::storage::storage_metrics::StorageMetrics;
+
+/// entry: index(大端) -> checksum(4) + 日志内容
+const CF_ENTRIES: &str = "entries";
+/// GTID -> 所在事务的第一个 entry index(大端)
+const CF_GTIDS: &str = "gtids";
+
+/// 基于 RocksDB 的目标表日志存储, 数据位于目标表的日志文件夹中.
+///
+/// 每次追加是一次原子写入, 落盘策略通过 WAL 实现: 提交批次时 flush WAL, 需要 fsync 时同步 WAL;
+/// 压缩由 RocksDB 完成, [RocksDbStorage::checkpoint] 生成一致的快照
+pub struct RocksDbStorage {
+    db: DB,
+    codec: BinaryCodec,
+    // 最后一个 index, 没有日志时为 first - 1
+    last_index: u64,
+    group_commit: GroupCommit,
+    retention: RetentionPolicy,
+    consumer_offsets: ConsumerOffsets,
+    metrics: StorageMetrics,
+}
+
+fn db_err(e: rocksdb::Error) -> ReError {
+    ReError::Error(format!("rocksdb err: {}", e))
+}
+
+impl RocksDbStorage {
+    pub fn new(storage_config: &StorageConfig, dst_db_name: &str, dst_table_name: &str) -> CResult<Self> {
+        let dir = SegmentManager::get_segment_dir_path(&storage_config.partition_dir(), dst_db_name, dst_table_name)?;
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let cfs = vec![
+            ColumnFamilyDescriptor::new(CF_ENTRIES, Options::default()),
+            ColumnFamilyDescriptor::new(CF_GTIDS, Options::default()),
+        ];
+        let db = DB::open_cf_descriptors(&options, &dir, cfs).map_err(db_err)?;
+
+        let mut storage = Self {
+            db,
+            codec: BinaryCodec::new(),
+            last_index: 0,
+            group_commit: GroupCommit::from_config(storage_config),
+            retention: RetentionPolicy::from_config(storage_config),
+            consumer_offsets: ConsumerOffsets::default(),
+            metrics: StorageMetrics::new(storage_config.partition(), dst_db_name, dst_table_name),
+        };
+        storage.last_index = storage.edge_index(IteratorMode::End)?.unwrap_or(0);
+        Ok(storage)
+    }
+
+    /// 生成一致的快照到 dir, dir 不能已存在; 快照可以直接作为目标表的日志文件夹打开
+    pub fn checkpoint(&self, dir: &str) -> CResult<()> {
+        let checkpoint = Checkpoint::new(&self.db).map_err(db_err)?;
+        checkpoint.create_checkpoint(Path::new(dir)).map_err(db_err)
+    }
+
+    /// 第一个或最后一个 entry 的 index
+    fn edge_index(&self, mode: IteratorMode) -> CResult<Option<u64>> {
+        let cf = self.cf(CF_ENTRIES)?;
+        match self.db.iterator_cf(cf, mode).next() {
+            None => Ok(None),
+            Some(item) => {
+                let (key, _) = item.map_err(db_err)?;
+                Ok(Some(Self::decode_index(&key)?))
+            }
+        }
+    }
+
+    fn cf(&self, name: &str) -> CResult<&rocksdb::ColumnFamily> {
+        self.db.cf_handle(name).ok_or(ReError::Error(format!("rocksdb column family {} not found.", name)))
+    }
+
+    fn decode_index(key: &[u8]) -> CResult<u64> {
+        let bytes: [u8; 8] = key.try_into().map_err(|_| ReError::Error(format!("invalid rocksdb entry key {:?}.", key)))?;
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn checksum(buf: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.checksum(buf)
+    }
+
+    fn commit(&mut self, action: CommitAction) -> CResult<()> {
+        if !action.commit {
+            return Ok(());
+        }
+        let start = Instant::now();
+        self.db.flush_wal(action.sync).map_err(db_err)?;
+        if action.sync {
+            self.group_commit.on_sync();
+        }
+        self.metrics.record_fsync(start.elapsed());
+        Ok(())
+    }
+
+    /// 删除 index 之前的entry
+    fn delete_before(&mut self, index: u64) -> CResult<()> {
+        let first = match self.edge_index(IteratorMode::Start)? {
+            None => return Ok(()),
+            Some(first) => first,
+        };
+        let cf = self.cf(CF_ENTRIES)?;
+        self.db.delete_range_cf(cf, first.to_be_bytes(), index.to_be_bytes()).map_err(db_err)?;
+        let gtids = self.cf(CF_GTIDS)?;
+        let mut batch = WriteBatch::default();
+        for item in self.db.iterator_cf(gtids, IteratorMode::Start) {
+            let (gtid, value) = item.map_err(db_err)?;
+            if Self::decode_index(&value)? < index {
+                batch.delete_cf(gtids, gtid);
+            }
+        }
+        self.db.write(batch).map_err(db_err)?;
+        self.db.compact_range_cf(cf, None::<&[u8]>, Some(index.to_be_bytes()));
+        Ok(())
+    }
+}
+
+impl StorageBackend for RocksDbStorage {
+    fn name(&self) -> String {
+        String::from("rocksdb")
+    }
+
+    fn append_relay_log_with_gtid(&mut self, log: RelayLog, gtid: Option<&str>) -> CResult<()> {
+        let index = self.last_index + 1;
+        let log_bytes = self.codec.binary_serialize(&CodecStyle::LittleVar, &log)?;
+        let mut value = Vec::with_capacity(4 + log_bytes.len());
+        value.extend_from_slice(&Self::checksum(&log_bytes).to_le_bytes());
+        value.extend_from_slice(&log_bytes);
+
+        let mut batch = WriteBatch::default();
+        batch.put_cf(self.cf(CF_ENTRIES)?, index.to_be_bytes(), &value);
+        if let Some(gtid) = gtid {
+            let gtids = self.cf(CF_GTIDS)?;
+            if self.db.get_cf(gtids, gtid).map_err(db_err)?.is_none() {
+                batch.put_cf(gtids, gtid, index.to_be_bytes());
+            }
+        }
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(self.group_commit.policy() == FsyncPolicy::PerEntry);
+        self.db.write_opt(batch, &write_options).map_err(db_err)?;
+        self.last_index = index;
+        // index + checksum + 日志内容
+        self.metrics.record_write(12 + log_bytes.len() as u64);
+
+        let action = self.group_commit.on_append();
+        self.commit(action)
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        let action = self.group_commit.on_commit();
+        self.commit(action)
+    }
+
+    fn sync(&mut self) -> CResult<()> {
+        self.group_commit.on_commit();
+        self.commit(CommitAction { commit: true, sync: true })
+    }
+
+    fn get_entry(&mut self, index: u64) -> CResult<Rc<StorageEntry>> {
+        let value = self.db.get_cf(self.cf(CF_ENTRIES)?, index.to_be_bytes()).map_err(db_err)?
+            .ok_or(ReError::Error(format!("relay log entry {} not found.", index)))?;
+        if value.len() < 4 {
+            return Err(ReError::Error(format!("relay log entry {} is truncated.", index)));
+        }
+        let checksum = u32::from_le_bytes(value[..4].try_into().unwrap());
+        let log_bytes = &value[4..];
+        if checksum != Self::checksum(log_bytes) {
+            return Err(ReError::Error("log checksum err.".to_string()));
+        }
+        let relay_log = self.codec.binary_deserialize::<RelayLog>(&CodecStyle::LittleVar, log_bytes)?;
+        self.metrics.record_read(12 + log_bytes.len() as u64);
+        Ok(Rc::new(StorageEntry::new(index, log_bytes.len() as u64, checksum, relay_log)))
+    }
+
+    fn index_range(&mut self) -> CResult<Option<(u64, u64)>> {
+        Ok(self.edge_index(IteratorMode::Start)?.map(|first| (first, self.last_index)))
+    }
+
+    fn seek_gtid(&mut self, gtid: &str) -> CResult<Option<u64>> {
+        match self.db.get_cf(self.cf(CF_GTIDS)?, gtid).map_err(db_err)? {
+            None => Ok(None),
+            Some(value) => Ok(Some(Self::decode_index(&value)?)),
+        }
+    }
+
+    /// 从最旧的entry开始删除超过保留时长(按事件时间戳)或超出总大小的entry, 返回的 segments 为删除的entry数量
+    fn purge(&mut self) -> CResult<PurgeStats> {
+        let mut stats = PurgeStats::default();
+        if !self.retention.is_enabled() {
+            return Ok(stats);
+        }
+        let protected_from = self.consumer_offsets.min_offset().unwrap_or(u64::MAX).min(self.last_index + 1);
+        // SST 文件与 memtable 中的数据大小
+        let mut total_bytes = 0;
+        for property in ["rocksdb.estimate-live-data-size", "rocksdb.cur-size-all-mem-tables"] {
+            total_bytes += self.db.property_int_value_cf(self.cf(CF_ENTRIES)?, property).map_err(db_err)?.unwrap_or(0);
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs();
+        let max_age = self.retention.max_age_millisecond / 1000;
+
+        let mut purge_to = None;
+        let cf = self.cf(CF_ENTRIES)?;
+        for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+            let (key, value) = item.map_err(db_err)?;
+            let index = Self::decode_index(&key)?;
+            if index >= protected_from {
+                break;
+            }
+            let oversize = self.retention.max_bytes > 0 && total_bytes > self.retention.max_bytes;
+            let expired = self.retention.max_age_millisecond > 0 && {
+                let log = self.codec.binary_deserialize::<RelayLog>(&CodecStyle::LittleVar, &value[4.min(value.len())..])?;
+                now.saturating_sub(*log.event_timestamp() as u64) > max_age
+            };
+            if !oversize && !expired {
+                break;
+            }
+            total_bytes = total_bytes.saturating_sub(value.len() as u64);
+            stats.segments += 1;
+            stats.bytes += value.len() as u64;
+            purge_to = Some(index + 1);
+        }
+
+        if let Some(index) = purge_to {
+            self.delete_before(index)?;
+            self.metrics.record_purge(&stats);
+        }
+        Ok(stats)
+    }
+
+    fn consumer_offsets(&self) -> ConsumerOffsets {
+        self.consumer_offsets.clone()
+    }
+
+    fn metrics(&self) -> StorageMetrics {
+        self.metrics.clone()
+    }
+}
@@ -1,31 +1,54 @@
 use std::fmt::{Debug, Formatter};
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use checksum::crc32::Crc32;
+use lazy_static::lazy_static;
+use memmap2::Mmap;
 use tracing::{error, warn};
 
 use common::err::CResult;
 use common::err::decode_error::ReError;
+use common::memory_governor::{MemoryConsumer, MemoryGovernor, MemoryPressure};
 
 use crate::codec::binary_codec::{BinaryCodec, CodecStyle};
 use crate::codec::binary_codec::CodecStyle::LittleVar;
 use crate::codec::codec::Codec;
-use crate::relay_log::RelayLog;
+use crate::relay_log::{RelayLog, RelayLogV1};
+use crate::storage::compression::CompressionCodec;
 use crate::storage::file_system::FileSystem;
 use crate::storage::segment::SegmentStatus::{ReadOnly, WriteRead};
 use crate::storage::segment_entry_position::SegmentEntryPosition;
 use crate::storage::segment_file::SegmentFile;
 use crate::storage::segment_header::SegmentHeader;
-use crate::storage::storage_config::{SEGMENT_FILE_PRE, SEGMENT_HEADER_SIZE_BYTES, VERSION};
+use crate::storage::segment_index::SegmentIndex;
+use crate::storage::storage_config::{DEFAULT_INDEX_INTERVAL, SEGMENT_FILE_PRE, SEGMENT_HEADER_SIZE_BYTES, VERSION};
 use crate::storage::storage_entry::StorageEntry;
 
+lazy_static! {
+    /// 已压缩segment解压后的entry区域占用的内存, 所有segment共用
+    static ref DECOMPRESSED_DATA: MemoryConsumer = MemoryGovernor::global().register("relay_log_decompressed_segment");
+}
+
 const FILE_WRITE_BUFFER_SIZE: usize = 4 * 1024;
 const FILE_READ_BUFFER_SIZE: usize = 16 * 1024;
+/// 提交标记, 与entry的index位于同一位置
+const COMMIT_MARKER: u64 = u64::MAX;
+/// 提交标记大小: marker(8) + last index(8) + checksum(4)
+const COMMIT_MARKER_SIZE: u64 = 20;
+
+/// 启动时截断最后一个segment损坏尾部的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryStats {
+    // 丢弃的entry数量
+    pub entries: u32,
+    // 丢弃的字节数
+    pub bytes: u64,
+}
 
 /// 日志存储文件.
 ///
@@ -39,8 +62,19 @@ const FILE_READ_BUFFER_SIZE: usize = 16 * 1024;
 /// |   StorageEntry  |
 /// |   StorageEntry  |
 /// |      ...        |
+/// |  commit marker  |
 /// |=================|
 /// ```
+///
+/// 每次flush时写入一个提交标记, 标记之前的entry为一个完整的批次。
+/// 启动时通过 [Segment::recover_torn_write] 截断最后一个提交标记之后未完成的写入。
+///
+/// 开启压缩时, 写满的 segment 在滚动后将全部 StorageEntry 压缩为一块, entry position 仍为压缩前的偏移量。
+///
+/// 写满且关闭写入的 segment 通过 mmap 读取, entry 内容直接从映射中切片交给解码器, 不经过读缓冲;
+/// 写入中的 segment 仍使用 BufReader 读取。
+///
+/// position/GTID 的稀疏索引保存在同名的 `.idx` 文件中, 见 [SegmentIndex]
 pub(crate) struct Segment {
     // 文件信息（文件名、文件路径、文件大小）
     segment_file: SegmentFile,
@@ -48,6 +82,8 @@ pub(crate) struct Segment {
     header: SegmentHeader,
     // entry偏移量
     entry_position: SegmentEntryPosition,
+    // position/GTID 稀疏索引
+    index: SegmentIndex,
     // 编解码
     codec: BinaryCodec,
     // 编解码风格
@@ -56,6 +92,12 @@ pub(crate) struct Segment {
     reader: Arc<Mutex<BufReader<File>>>,
     // segment状态
     status: SegmentStatus,
+    // 已压缩的segment解压后的entry区域, 首次读取时解压; 占用计入内存预算, 内存紧张时释放, 之后读取时重新解压
+    data: Option<Vec<u8>>,
+    // 写满的segment的只读映射, 首次读取时建立
+    mmap: Option<Mmap>,
+    // 上次提交标记之后是否有新的entry
+    uncommitted: bool,
 }
 
 /// Segment Status
@@ -87,7 +129,8 @@ impl Segment {
                id: u32,
                first_index: u64,
                max_segment_size: u64,
-               max_entries: u32) -> CResult<Self> {
+               max_entries: u32,
+               index_interval: u32) -> CResult<Self> {
         // rlog-{version}-{id}-{index}.log
         let segment_file_name = format!("{}-{}-{}-{}.log", SEGMENT_FILE_PRE, VERSION, id, first_index);
         // /x/x/x/x/rlog-{version}-{id}-{index}.log
@@ -98,6 +141,7 @@ impl Segment {
         let segment_file_path_str = segment_file_path.to_str().ok_or(ReError::String("segment file not exists.".to_string()))?;
         let header = SegmentHeader::new(segment_file_path_str, id, first_index, max_segment_size, max_entries)?;
         let entry_position = SegmentEntryPosition::new(segment_file_path_str, max_entries)?;
+        let index = SegmentIndex::new(&Self::index_file_path(segment_file_path_str), first_index, index_interval)?;
         let init_segment_size = SEGMENT_HEADER_SIZE_BYTES as u64 + (4 + max_entries as u64 * 8);
         let segment_file = SegmentFile::new(segment_file_path_str.to_string(), segment_file_name, init_segment_size);
 
@@ -106,34 +150,112 @@ impl Segment {
             segment_file,
             header,
             entry_position,
+            index,
             codec: BinaryCodec::new(),
             codec_style: LittleVar,
             reader: Arc::new(Mutex::new(reader)),
             status: ReadOnly,
+            data: None,
+            mmap: None,
+            uncommitted: false,
         })
     }
 
-    /// 从文件初始化segment
+    /// 从文件初始化segment, 索引文件缺失或落后于entry时根据entry补齐position与时间戳索引
     pub fn from_file(file_path: &str) -> CResult<Self> {
+        let mut segment = Self::open(file_path, true)?;
+        segment.recover_index()?;
+        Ok(segment)
+    }
+
+    /// 只读打开其它实例正在写入的segment, 不加载也不修改索引文件, 通过 [Segment::refresh] 读取新追加的entry
+    pub fn open_read_only(file_path: &str) -> CResult<Self> {
+        Self::open(file_path, false)
+    }
+
+    /// 只读打开segment并加载已有的索引文件, 不截断、不重建索引, 也不写入提交标记。
+    ///
+    /// 写入方尚未为全部entry建立索引时不使用索引, 避免按过期的时间范围跳过新追加的entry
+    pub fn open_for_read(file_path: &str) -> CResult<Self> {
+        let mut segment = Self::open(file_path, false)?;
+        if let Some(index) = SegmentIndex::load_read_only(&segment.index_file(), *segment.header.first_index()) {
+            if index.indexed_count() >= segment.entry_position.get_entry_count() {
+                segment.index = index;
+            }
+        }
+        Ok(segment)
+    }
+
+    fn open(file_path: &str, load_index: bool) -> CResult<Self> {
         let segment_file = SegmentFile::from_path(file_path)?;
         let header = SegmentHeader::from_file(file_path, 0, SEGMENT_HEADER_SIZE_BYTES)?;
 
         let start_offset = SEGMENT_HEADER_SIZE_BYTES as u64;
         let bytes_size = 4 + (*header.max_entries()) * 8;
         let entry_position = SegmentEntryPosition::from_file(file_path, start_offset, bytes_size as usize)?;
+        let index_file_path = Self::index_file_path(file_path);
+        let index = if load_index {
+            SegmentIndex::load(&index_file_path, *header.first_index(), DEFAULT_INDEX_INTERVAL)?
+        } else {
+            SegmentIndex::detached(&index_file_path, *header.first_index())
+        };
 
         let reader = BufReader::with_capacity(FILE_READ_BUFFER_SIZE, File::open(file_path)?);
         Ok(Self {
             segment_file,
             header,
             entry_position,
+            index,
             codec: BinaryCodec::new(),
             codec_style: LittleVar,
             reader: Arc::new(Mutex::new(reader)),
             status: ReadOnly,
+            data: None,
+            mmap: None,
+            uncommitted: false,
         })
     }
 
+    /// 索引文件路径: rlog-{version}-{id}-{index}.idx
+    fn index_file_path(segment_file_path: &str) -> String {
+        match segment_file_path.strip_suffix(".log") {
+            Some(p) => format!("{}.idx", p),
+            None => format!("{}.idx", segment_file_path),
+        }
+    }
+
+    /// 本segment的索引文件路径
+    pub fn index_file(&self) -> String {
+        Self::index_file_path(self.segment_file.path())
+    }
+
+    /// 为尚未建立索引的entry补齐position与时间戳索引
+    fn recover_index(&mut self) -> CResult<()> {
+        let indexed = self.index.indexed_count();
+        let count = self.entry_position.get_entry_count();
+        if indexed >= count {
+            return Ok(());
+        }
+
+        warn!("rebuild segment index {} from entry {}", self.segment_file.name(), indexed);
+        let first_index = *self.header.first_index();
+        for i in indexed..count {
+            let entry_index = first_index + i as u64;
+            match self.get_entry(entry_index) {
+                Ok(entry) => {
+                    let log = entry.relay_log();
+                    self.index.add(entry_index, *log.event_log_pos(), *log.event_timestamp(), None)?
+                }
+                Err(e) => {
+                    // 索引只影响查找速度, 读取失败时保留已建立的部分
+                    warn!("rebuild segment index {} stop at entry {}: {:?}", self.segment_file.name(), entry_index, e);
+                    break;
+                }
+            }
+        }
+        self.index.flush()
+    }
+
     /// 计算切片crc32值
     fn checksum(buf: &[u8]) -> u32 {
         let mut crc = Crc32::new();
@@ -142,25 +264,28 @@ impl Segment {
 
     /// 开启可写模式
     pub fn write_open(&mut self) -> CResult<()> {
+        if self.header.is_compressed() {
+            return Err(ReError::String("compressed segment is read only.".to_string()));
+        }
         let f1 = OpenOptions::new()
             .write(true)
             .append(true)
             .open(self.segment_file.path())?;
         let writer = BufWriter::with_capacity(FILE_WRITE_BUFFER_SIZE, f1);
+        // 续写时以最后一个entry的log_pos判断binlog是否切换了文件
+        if !self.is_empty() {
+            if let Ok(last) = self.get_entry(self.last_index()) {
+                self.index.set_last_log_pos(*last.relay_log().event_log_pos());
+            }
+        }
         self.status = WriteRead(writer);
         Ok(())
     }
 
     /// 关闭可写模式
     pub fn write_close(&mut self) -> CResult<()> {
-        match &mut self.status {
-            WriteRead(w) => {
-                self.entry_position.flush()?;
-                w.flush()?;
-                self.status = ReadOnly;
-            }
-            ReadOnly => {}
-        }
+        self.write_flush()?;
+        self.status = ReadOnly;
         Ok(())
     }
 
@@ -173,6 +298,16 @@ impl Segment {
         }
     }
 
+    /// 文件头中的第一个index值, 空segment也返回文件头中的值
+    pub fn header_first_index(&self) -> u64 {
+        *self.header.first_index()
+    }
+
+    /// 写入时的存储版本, 决定entry的解码方式
+    pub fn version(&self) -> u32 {
+        *self.header.version()
+    }
+
     /// segment id
     pub fn id(&self) -> u32 {
         *self.header.id()
@@ -215,6 +350,24 @@ impl Segment {
         self.segment_file.size()
     }
 
+    /// 重新读取磁盘上的entry数量, 只读打开的segment可以读取到写入方新追加的entry
+    pub fn refresh(&mut self) -> CResult<()> {
+        self.entry_position.refresh()
+    }
+
+    /// 只flush写缓冲, 不写入提交标记, 使其它实例可以读取已追加的entry
+    pub fn flush_buffer(&mut self) -> CResult<()> {
+        if let WriteRead(w) = &mut self.status {
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    /// segment文件信息
+    pub fn segment_file(&self) -> &SegmentFile {
+        &self.segment_file
+    }
+
     /// append entry(非线程安全，只能单线程写)
     /// </p>
     /// 每个Entry块包含如下内容（字节大小 = 8 + 8 + 8 + 4 + {RelayLogSize}）:
@@ -227,6 +380,11 @@ impl Segment {
     /// relay_log: 日志内容, 动态大小
     /// ```
     pub fn append(&mut self, entry: &mut StorageEntry) -> CResult<()> {
+        self.append_with_gtid(entry, None)
+    }
+
+    /// append entry, gtid 为entry所属事务的GTID, 用于 [Segment::seek_gtid]
+    pub fn append_with_gtid(&mut self, entry: &mut StorageEntry, gtid: Option<&str>) -> CResult<()> {
         match &mut self.status {
             WriteRead(w) => {
                 // log serialize
@@ -264,6 +422,8 @@ impl Segment {
                 }
 
                 self.segment_file.add_entry_size(entry_size);
+                self.uncommitted = true;
+                self.index.add(index, *entry.relay_log().event_log_pos(), *entry.relay_log().event_timestamp(), gtid)?;
                 Ok(())
             }
             ReadOnly => {
@@ -287,6 +447,35 @@ impl Segment {
         let offset = index - self.first_index();
         let entry_position = self.entry_position.get_position(offset as usize);
 
+        if self.header.is_compressed() {
+            let start = entry_position.checked_sub(self.data_start())
+                .ok_or(ReError::Error(format!("entry position {} out of compressed segment.", entry_position)))?;
+            if let Some(data) = &self.data {
+                let entry = self.decode_entry(Self::entry_slice(data, start)?);
+                if DECOMPRESSED_DATA.pressure() != MemoryPressure::Normal {
+                    self.release_data();
+                }
+                return entry;
+            }
+
+            let data = self.decompress()?;
+            let entry = self.decode_entry(Self::entry_slice(&data, start)?);
+            // 预算内时缓存, 否则只用于本次读取
+            if DECOMPRESSED_DATA.pressure() == MemoryPressure::Normal && DECOMPRESSED_DATA.try_reserve(data.len() as u64) {
+                self.data = Some(data);
+            }
+            return entry;
+        }
+
+        self.map_sealed()?;
+        if let Some(mmap) = &self.mmap {
+            // 映射建立之后文件不会再追加, 越界时说明映射时写入方尚未flush, 重新映射
+            if let Ok(slice) = Self::entry_slice(mmap, entry_position) {
+                return self.decode_entry(slice);
+            }
+            self.mmap = None;
+        }
+
         let r = Arc::clone(&self.reader);
         let mut reader = r.lock().or_else(|e| {
             error!("segment read lock err: {:?}", &e);
@@ -294,21 +483,161 @@ impl Segment {
         })?;
 
         reader.seek(SeekFrom::Start(entry_position))?;
+        let (idx, log_size, checksum, log_bytes) = Self::read_entry(&mut *reader)?;
+        self.decode_entry((idx, log_size, checksum, &log_bytes))
+    }
+
+    /// 校验crc32值并解码日志内容
+    fn decode_entry(&self, (idx, log_size, checksum, log_bytes): (u64, u64, u32, &[u8])) -> CResult<StorageEntry> {
+        if checksum != Self::checksum(log_bytes) {
+            return Err(ReError::Error("log checksum err.".to_string()));
+        }
 
+        let relay_log = match *self.header.version() {
+            1 => self.codec.binary_deserialize::<RelayLogV1>(&self.codec_style, log_bytes)?.into(),
+            _ => self.codec.binary_deserialize::<RelayLog>(&self.codec_style, log_bytes)?,
+        };
+        Ok(StorageEntry::new(idx, log_size, checksum, relay_log))
+    }
+
+    /// 写满且关闭写入的segment是否已封存, 封存后文件内容不再变化
+    fn is_sealed(&self) -> bool {
+        !self.is_writable() && !self.header.is_compressed() && self.is_full()
+    }
+
+    /// 封存的segment建立只读映射
+    fn map_sealed(&mut self) -> CResult<()> {
+        if self.mmap.is_none() && self.is_sealed() {
+            let file = File::open(self.segment_file.path())?;
+            // 封存的segment不再写入; 截断、压缩前会释放映射
+            self.mmap = Some(unsafe { Mmap::map(&file)? });
+        }
+        Ok(())
+    }
+
+    /// binlog 中位于 pos 及之后的第一个 entry index: log_pos 不小于 pos, 或 binlog 切换文件后的第一个entry。
+    ///
+    /// 通过稀疏索引定位扫描起点, 通常只需顺序读取不超过 interval 个entry。
+    /// 本segment中没有时返回 last_index + 1, 即下一个segment的第一个entry;
+    /// 所有position段的起点都大于 pos 时返回 None
+    pub fn seek_position(&mut self, pos: u64) -> CResult<Option<u64>> {
+        let start = match self.index.seek_position(pos) {
+            None => return Ok(None),
+            Some(start) => start,
+        };
+
+        let mut last_log_pos = 0;
+        for entry_index in start..=self.last_index() {
+            let log_pos = *self.get_entry(entry_index)?.relay_log().event_log_pos();
+            if log_pos >= pos || log_pos < last_log_pos {
+                return Ok(Some(entry_index));
+            }
+            last_log_pos = log_pos;
+        }
+
+        Ok(Some(self.last_index() + 1))
+    }
+
+    /// gtid 所在事务的第一个 entry index
+    pub fn seek_gtid(&self, gtid: &str) -> Option<u64> {
+        self.index.seek_gtid(gtid)
+    }
+
+    /// 全部entry的 (最小时间戳, 最大时间戳)
+    pub fn time_range(&self) -> Option<(u32, u32)> {
+        self.index.time_range()
+    }
+
+    /// 时间范围 [from_ts, to_ts] 内的entry可能出现的 index 区间, 与本segment不相交时返回 None
+    pub fn scan_bounds(&self, from_ts: u32, to_ts: u32) -> Option<(u64, u64)> {
+        if self.is_empty() {
+            return None;
+        }
+        match self.index.time_range() {
+            Some((min_ts, max_ts)) if max_ts < from_ts || min_ts > to_ts => None,
+            Some(_) => Some((self.index.seek_timestamp(from_ts), self.last_index())),
+            // 没有时间戳索引的旧索引文件, 扫描整个segment
+            None => Some((self.first_index(), self.last_index())),
+        }
+    }
+
+    /// 从 buf 的 position 处切出一个entry, 不复制日志内容: (index, log_size, checksum, log_bytes)
+    fn entry_slice(buf: &[u8], position: u64) -> CResult<(u64, u64, u32, &[u8])> {
+        let out_of_range = || ReError::Error(format!("entry position {} out of segment.", position));
+        let mut header = buf.get(position as usize..).filter(|b| b.len() >= 20).ok_or_else(out_of_range)?;
+        let idx = header.read_u64::<LittleEndian>()?;
+        let log_size = header.read_u64::<LittleEndian>()?;
+        let checksum = header.read_u32::<LittleEndian>()?;
+
+        let log_bytes = header.get(..log_size as usize).ok_or_else(out_of_range)?;
+        Ok((idx, log_size, checksum, log_bytes))
+    }
+
+    /// 读取一个entry: (index, log_size, checksum, log_bytes)
+    fn read_entry<R: Read>(reader: &mut R) -> CResult<(u64, u64, u32, Vec<u8>)> {
         let idx = reader.read_u64::<LittleEndian>()?;
         let log_size = reader.read_u64::<LittleEndian>()?;
         let checksum = reader.read_u32::<LittleEndian>()?;
 
         let mut log_bytes: Vec<u8> = vec![0; log_size as usize];
         reader.read_exact(&mut log_bytes)?;
+        Ok((idx, log_size, checksum, log_bytes))
+    }
 
-        // 校验crc32值
-        if checksum != Self::checksum(&log_bytes) {
-            return Err(ReError::Error("log checksum err.".to_string()));
+    /// entry区域在文件中的起始位置
+    fn data_start(&self) -> u64 {
+        SEGMENT_HEADER_SIZE_BYTES as u64 + 4 + *self.header.max_entries() as u64 * 8
+    }
+
+    /// 读取并解压entry区域
+    fn decompress(&self) -> CResult<Vec<u8>> {
+        let compressed = fs::read(self.segment_file.path())?;
+        let data_start = (self.data_start() as usize).min(compressed.len());
+        self.header.compression()
+            .decompress(&compressed[data_start..], *self.header.raw_data_size() as usize)
+    }
+
+    /// 释放缓存的解压数据
+    pub fn release_data(&mut self) {
+        if let Some(data) = self.data.take() {
+            DECOMPRESSED_DATA.release(data.len() as u64);
         }
+    }
 
-        let relay_log = self.codec.binary_deserialize::<RelayLog>(&self.codec_style, &log_bytes)?;
-        Ok(StorageEntry::new(idx, log_size, checksum, relay_log))
+    /// 压缩全部entry, 只能在segment关闭可写模式后调用.
+    ///
+    /// 先写入临时文件再替换原文件, 压缩过程中崩溃不会损坏原segment
+    pub fn compress(&mut self, codec: CompressionCodec, level: i32) -> CResult<()> {
+        if codec == CompressionCodec::None || self.header.is_compressed() {
+            return Ok(());
+        }
+        if self.is_writable() {
+            return Err(ReError::String("segment is writable, close it before compress.".to_string()));
+        }
+
+        let path = self.segment_file.path().clone();
+        let file_bytes = fs::read(&path)?;
+        let data_start = self.data_start() as usize;
+        if file_bytes.len() < data_start {
+            return Err(ReError::String(format!("segment file {} is truncated.", path)));
+        }
+        let raw = &file_bytes[data_start..];
+        let compressed = codec.compress(level, raw)?;
+
+        self.header.set_compression(codec);
+        self.header.set_raw_data_size(raw.len() as u64);
+        let tmp_path = format!("{}.tmp", path);
+        {
+            let mut f = File::create(&tmp_path)?;
+            f.write_all(&self.header.to_bytes()?)?;
+            f.write_all(&file_bytes[SEGMENT_HEADER_SIZE_BYTES..data_start])?;
+            f.write_all(&compressed)?;
+            f.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)?;
+
+        *self = Segment::from_file(&path)?;
+        Ok(())
     }
 
     /// 是否可写
@@ -323,8 +652,11 @@ impl Segment {
         }
     }
 
-    /// segment is full
+    /// segment is full, 已压缩的segment不再写入
     pub fn is_full(&self) -> bool {
+        if self.header.is_compressed() {
+            return true;
+        }
         let max_size = *self.header.max_segment_size();
         let max_entries = *self.header.max_entries();
         let current_size = self.segment_file.size();
@@ -336,22 +668,133 @@ impl Segment {
         }
     }
 
-    /// flush the segment writer buf to disk
+    /// flush the segment writer buf to disk, 有新的entry时先写入提交标记
     pub fn write_flush(&mut self) -> CResult<()> {
+        let last_index = self.last_index();
         match &mut self.status {
             WriteRead(w) => {
-                self.entry_position.flush()?;
+                if self.uncommitted {
+                    let index_bytes = last_index.to_le_bytes();
+                    w.write_u64::<LittleEndian>(COMMIT_MARKER)?;
+                    w.write_all(&index_bytes)?;
+                    w.write_u32::<LittleEndian>(Self::checksum(&index_bytes))?;
+                    self.segment_file.add_entry_size(COMMIT_MARKER_SIZE);
+                    self.uncommitted = false;
+                }
                 w.flush()?;
+                self.entry_position.flush()?;
+                self.index.flush()?;
             }
             ReadOnly => {}
         }
         Ok(())
     }
 
+    /// fsync segment 文件, 提交标记之前的entry在宕机后仍然保留, 只对可写segment生效
+    pub fn sync_data(&mut self) -> CResult<()> {
+        if let WriteRead(w) = &mut self.status {
+            w.flush()?;
+            w.get_ref().sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// 检查尾部未完成的写入: 从entry区域起点顺序扫描, 校验index连续与crc32,
+    /// 截断到最后一个有效的提交标记; 旧版本写入的segment没有提交标记时截断到最后一个有效entry。
+    ///
+    /// 只能在开启可写模式之前调用, 截断后重建position表与索引
+    pub fn recover_torn_write(&mut self) -> CResult<RecoveryStats> {
+        if self.header.is_compressed() || self.is_writable() {
+            return Ok(RecoveryStats::default());
+        }
+        let bytes = fs::read(self.segment_file.path())?;
+        let data_start = self.data_start() as usize;
+        if bytes.len() < data_start {
+            return Err(ReError::Error(format!("segment file {} is truncated.", self.segment_file.name())));
+        }
+
+        let first_index = *self.header.first_index();
+        let max_entries = *self.header.max_entries() as usize;
+        // 有效entry的位置
+        let mut positions: Vec<u64> = vec![];
+        let mut entries_end = data_start;
+        // 最后一个提交标记: (entry数量, 标记结束位置)
+        let mut committed: Option<(usize, usize)> = None;
+        let mut cursor = Cursor::new(&bytes[..]);
+        cursor.set_position(data_start as u64);
+        loop {
+            let start = cursor.position();
+            let idx = match cursor.read_u64::<LittleEndian>() {
+                Ok(idx) => idx,
+                Err(_) => break,
+            };
+            if idx == COMMIT_MARKER {
+                let mut index_bytes = [0u8; 8];
+                if cursor.read_exact(&mut index_bytes).is_err() {
+                    break;
+                }
+                let checksum = match cursor.read_u32::<LittleEndian>() {
+                    Ok(c) => c,
+                    Err(_) => break,
+                };
+                if positions.is_empty() || checksum != Self::checksum(&index_bytes)
+                    || u64::from_le_bytes(index_bytes) != first_index + positions.len() as u64 - 1 {
+                    break;
+                }
+                committed = Some((positions.len(), cursor.position() as usize));
+                continue;
+            }
+
+            if idx != first_index + positions.len() as u64 || positions.len() >= max_entries {
+                break;
+            }
+            // 不完整的entry头或过大的日志大小
+            match cursor.read_u64::<LittleEndian>() {
+                Ok(log_size) if log_size.checked_add(4)
+                    .map_or(false, |n| n <= bytes.len() as u64 - cursor.position()) => {}
+                _ => break,
+            }
+            cursor.set_position(start);
+            match Self::read_entry(&mut cursor) {
+                Ok((_, _, checksum, log_bytes)) if checksum == Self::checksum(&log_bytes) => {
+                    positions.push(start);
+                    entries_end = cursor.position() as usize;
+                }
+                _ => break,
+            }
+        }
+
+        let (keep, valid_end) = committed.unwrap_or((positions.len(), entries_end));
+        let count = self.entry_position.get_entry_count() as usize;
+        let unchanged = keep == count && valid_end == bytes.len()
+            && (0..keep).all(|i| self.entry_position.get_position(i) == positions[i]);
+        if unchanged {
+            return Ok(RecoveryStats::default());
+        }
+
+        let stats = RecoveryStats {
+            entries: (positions.len().max(count) - keep) as u32,
+            bytes: (bytes.len() - valid_end) as u64,
+        };
+        warn!("segment {} torn write, discard {} entries, {} bytes.", self.segment_file.name(), stats.entries, stats.bytes);
+
+        // 截断前释放映射, 访问已截断的映射会触发 SIGBUS
+        self.mmap = None;
+        OpenOptions::new().write(true).open(self.segment_file.path())?.set_len(valid_end as u64)?;
+        self.entry_position.reset(&positions[..keep])?;
+        self.segment_file = SegmentFile::from_path(self.segment_file.path())?;
+        // 重建索引, 被截断的segment的GTID索引会丢失
+        let interval = self.index.interval();
+        self.index = SegmentIndex::new(&Self::index_file_path(self.segment_file.path()), first_index, interval)?;
+        self.recover_index()?;
+        Ok(stats)
+    }
+
     /// 删除segment文件
     pub fn delete(&self) -> CResult<()> {
         let file_path = self.segment_file.path();
         warn!("===删除segment文件: {:?}", file_path);
+        self.index.delete()?;
         Ok(fs::remove_file(file_path)?)
     }
 }
@@ -363,7 +806,21 @@ impl Debug for Segment {
             .field("segment_id", &self.header.id())
             .field("first_index", &self.header.first_index())
             .field("entry_count", &self.entry_position.get_entry_count())
+            .field("compression", &self.header.compression())
             .field("status", &self.status.name())
             .finish()
     }
 }
+
+impl Drop for Segment {
+    /// 关闭时释放解压数据并写入提交标记, 正常退出前写入的entry在下次启动时不会被当作未完成的写入截断。 只读的segment不修改文件
+    fn drop(&mut self) {
+        self.release_data();
+        if !self.is_writable() {
+            return;
+        }
+        if let Err(e) = self.write_close() {
+            error!("close segment {} err: {:?}", self.segment_file.name(), e);
+        }
+    }
+}
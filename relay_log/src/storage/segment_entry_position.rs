@@ -98,6 +98,29 @@ impl SegmentEntryPosition {
         Ok(())
     }
 
+    /// 用 positions 重置全部entry位置信息, 用于截断损坏的尾部
+    pub fn reset(&mut self, positions: &[u64]) -> CResult<()> {
+        self.position_info.iter_mut().for_each(|p| *p = 0);
+        self.position_info[..positions.len()].copy_from_slice(positions);
+        for offset in 0..self.position_info.len() {
+            let position = self.position_info[offset];
+            self.update_file_position_info(offset, position)?;
+        }
+        self.entry_count = positions.len() as u32;
+        self.update_file_entry_count()?;
+        self.flush()
+    }
+
+    /// 从磁盘重新读取entry数量与新增的位置信息, 用于读取其它实例正在追加的segment
+    pub fn refresh(&mut self) -> CResult<()> {
+        let count = self.get_entry_count_disk()?.min(self.position_info.len() as u32);
+        for offset in self.entry_count as usize..count as usize {
+            self.position_info[offset] = self.get_position_disk(offset)?;
+        }
+        self.entry_count = count;
+        Ok(())
+    }
+
     /// 返回entry位置信息(内存)
     pub fn get_position(&self, offset: usize) -> u64 {
         self.position_info[offset]
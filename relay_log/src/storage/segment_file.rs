@@ -1,4 +1,5 @@
 use std::path::Path;
+use std::time::SystemTime;
 
 use getset::{Getters, Setters};
 
@@ -94,6 +95,12 @@ impl SegmentFile {
         Ok(p.metadata()?.len())
     }
 
+    /// segment文件最后修改时间
+    pub fn modified(&self) -> CResult<SystemTime> {
+        let p = Path::new(self.path());
+        Ok(p.metadata()?.modified()?)
+    }
+
     /// 增加entry大小
     pub fn add_entry_size(&mut self, entry_size: u64) {
         self.size += entry_size;
@@ -4,8 +4,10 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use getset::{Getters, Setters};
 
 use common::err::CResult;
+use common::err::decode_error::ReError;
 use common::file_util;
 
+use crate::storage::compression::CompressionCodec;
 use crate::storage::file_system::FileSystem;
 use crate::storage::storage_config::{SEGMENT_HEADER_SIZE_BYTES, VERSION};
 
@@ -16,7 +18,10 @@ use crate::storage::storage_config::{SEGMENT_HEADER_SIZE_BYTES, VERSION};
 /// 8字节：第一个entry的index值,
 /// 8字节：segment最大容量,
 /// 4字节：segment最多存Entry数量
-/// 36字节预留空间(用于后续扩展...)
+/// 1字节：压缩方式(CompressionCodec), 0 为不压缩
+/// 3字节：预留
+/// 8字节：压缩前entry区域的大小, 不压缩时为 0
+/// 24字节预留空间(用于后续扩展...)
 /// ```
 #[derive(Debug, Getters, Setters)]
 pub(crate) struct SegmentHeader {
@@ -39,6 +44,14 @@ pub(crate) struct SegmentHeader {
     // segment最大存Entry数量
     #[getset(get = "pub")]
     max_entries: u32,
+
+    // entry区域的压缩方式
+    #[getset(get = "pub", set = "pub")]
+    compression: CompressionCodec,
+
+    // 压缩前entry区域的大小
+    #[getset(get = "pub", set = "pub")]
+    raw_data_size: u64,
 }
 
 impl SegmentHeader {
@@ -48,22 +61,39 @@ impl SegmentHeader {
                first_index: u64,
                max_segment_size: u64,
                max_entries: u32) -> CResult<Self> {
-        let mut bytes_buffer: [u8; SEGMENT_HEADER_SIZE_BYTES] = [0; SEGMENT_HEADER_SIZE_BYTES];
-        let mut c = Cursor::new(&mut bytes_buffer[0..]);
-        c.write_u32::<LittleEndian>(id)?;
-        c.write_u32::<LittleEndian>(VERSION)?;
-        c.write_u64::<LittleEndian>(first_index)?;
-        c.write_u64::<LittleEndian>(max_segment_size)?;
-        c.write_u32::<LittleEndian>(max_entries)?;
-        // 初始化
-        file_util::update_file_bytes(file_path, 0, &bytes_buffer)?;
-        Ok(Self {
+        let header = Self {
             id,
             version: VERSION,
             first_index,
             max_segment_size,
             max_entries,
-        })
+            compression: CompressionCodec::None,
+            raw_data_size: 0,
+        };
+        // 初始化
+        file_util::update_file_bytes(file_path, 0, &header.to_bytes()?)?;
+        Ok(header)
+    }
+
+    /// 文件头的字节表示
+    pub fn to_bytes(&self) -> CResult<[u8; SEGMENT_HEADER_SIZE_BYTES]> {
+        let mut bytes_buffer: [u8; SEGMENT_HEADER_SIZE_BYTES] = [0; SEGMENT_HEADER_SIZE_BYTES];
+        let mut c = Cursor::new(&mut bytes_buffer[0..]);
+        c.write_u32::<LittleEndian>(self.id)?;
+        c.write_u32::<LittleEndian>(self.version)?;
+        c.write_u64::<LittleEndian>(self.first_index)?;
+        c.write_u64::<LittleEndian>(self.max_segment_size)?;
+        c.write_u32::<LittleEndian>(self.max_entries)?;
+        c.write_u8(self.compression.into())?;
+        c.set_position(32);
+        c.write_u64::<LittleEndian>(self.raw_data_size)?;
+
+        Ok(bytes_buffer)
+    }
+
+    /// entry区域是否已压缩
+    pub fn is_compressed(&self) -> bool {
+        self.compression != CompressionCodec::None
     }
 }
 
@@ -87,12 +117,23 @@ impl FileSystem for SegmentHeader {
         cursor.set_position(24);
         let max_entries = cursor.read_u32::<LittleEndian>()?;
 
+        // 旧文件中为预留的 0, 即不压缩
+        cursor.set_position(28);
+        let codec = cursor.read_u8()?;
+        let compression = CompressionCodec::try_from(codec)
+            .map_err(|_| ReError::String(format!("unknown segment compression {} in {}", codec, file_path)))?;
+
+        cursor.set_position(32);
+        let raw_data_size = cursor.read_u64::<LittleEndian>()?;
+
         Ok(Self {
             id,
             version,
             first_index,
             max_segment_size,
             max_entries,
+            compression,
+            raw_data_size,
         })
     }
 
@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+/// 索引文件头大小: interval(4) + indexed_count(4)
+const INDEX_HEADER_SIZE_BYTES: u64 = 8;
+/// position 索引记录
+const RECORD_POSITION: u8 = 1;
+/// GTID 索引记录
+const RECORD_GTID: u8 = 2;
+/// 时间戳索引记录
+const RECORD_TIMESTAMP: u8 = 3;
+/// segment 时间范围记录
+const RECORD_TIME_RANGE: u8 = 4;
+
+/// 索引文件中的一条记录
+enum IndexRecord {
+    Position { index: u64, log_pos: u64 },
+    Gtid { index: u64, gtid: String },
+    /// max_ts 为 segment 起点到该entry的最大时间戳
+    Timestamp { index: u64, max_ts: u32 },
+    /// segment 起点到该entry的最小、最大时间戳
+    TimeRange { index: u64, min_ts: u32, max_ts: u32 },
+}
+
+impl IndexRecord {
+    fn index(&self) -> u64 {
+        match self {
+            IndexRecord::Position { index, .. } => *index,
+            IndexRecord::Gtid { index, .. } => *index,
+            IndexRecord::Timestamp { index, .. } => *index,
+            IndexRecord::TimeRange { index, .. } => *index,
+        }
+    }
+}
+
+/// segment 的稀疏索引, 与 segment 同名的 `.idx` 文件: rlog-{version}-{id}-{index}.idx
+///
+/// 文件结构:
+/// ```txt
+/// interval: 每隔多少个 entry 记录一个 position, 4字节
+/// indexed_count: 已建立索引的 entry 数量, 4字节
+/// record: type(1) + entry index(8) + [log_pos(8) | gtid_len(2) + gtid | max_ts(4) | min_ts(4) + max_ts(4)]
+/// ...
+/// ```
+///
+/// binlog 切换文件后 log_pos 会从头开始, 因此 position 索引按 log_pos 回退切分为多段, 每段内单调递增。
+/// 事件时间戳不保证单调, 时间戳索引记录的是截至采样点的最大时间戳, 可以二分查找。
+/// GTID 不在 RelayLog 中, 由写入方提供, 索引文件丢失时只能根据 entry 重建 position 索引
+pub(crate) struct SegmentIndex {
+    // 索引文件路径
+    path: String,
+    // segment第一个index
+    first_index: u64,
+    // position 采样间隔
+    interval: u32,
+    // 已建立索引的entry数量
+    indexed_count: u32,
+    // 上一个entry的log_pos
+    last_log_pos: Option<u64>,
+    // (log_pos, entry index), 按 entry index 升序
+    positions: Vec<(u64, u64)>,
+    // gtid -> 事务的第一个entry index
+    gtids: BTreeMap<String, u64>,
+    // (截至该entry的最大时间戳, entry index), 按 entry index 升序
+    timestamps: Vec<(u32, u64)>,
+    // 全部entry的 (最小时间戳, 最大时间戳)
+    time_range: Option<(u32, u32)>,
+    // time_range 是否有未写入文件的变化
+    time_range_changed: bool,
+    // 未写入文件的记录
+    pending: Vec<u8>,
+}
+
+impl SegmentIndex {
+    /// 新建索引文件, 已存在时覆盖
+    pub fn new(path: &str, first_index: u64, interval: u32) -> CResult<Self> {
+        let interval = interval.max(1);
+        let mut f = File::create(path)?;
+        f.write_u32::<LittleEndian>(interval)?;
+        f.write_u32::<LittleEndian>(0)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            first_index,
+            interval,
+            indexed_count: 0,
+            last_log_pos: None,
+            positions: vec![],
+            gtids: BTreeMap::new(),
+            timestamps: vec![],
+            time_range: None,
+            time_range_changed: false,
+            pending: vec![],
+        })
+    }
+
+    /// 不关联索引文件的空索引, 供只读打开的segment使用, 不读写索引文件
+    pub fn detached(path: &str, first_index: u64) -> Self {
+        Self {
+            path: path.to_string(),
+            first_index,
+            interval: 1,
+            indexed_count: 0,
+            last_log_pos: None,
+            positions: vec![],
+            gtids: BTreeMap::new(),
+            timestamps: vec![],
+            time_range: None,
+            time_range_changed: false,
+            pending: vec![],
+        }
+    }
+
+    /// 加载索引文件, 文件不存在或文件头损坏时新建。
+    ///
+    /// 末尾不完整的记录(写入过程中崩溃)以及超出 indexed_count 的记录被忽略
+    pub fn load(path: &str, first_index: u64, default_interval: u32) -> CResult<Self> {
+        if !Path::new(path).exists() {
+            return Self::new(path, first_index, default_interval);
+        }
+        let bytes = fs::read(path)?;
+        if (bytes.len() as u64) < INDEX_HEADER_SIZE_BYTES {
+            return Self::new(path, first_index, default_interval);
+        }
+        Self::parse(path, first_index, &bytes)
+    }
+
+    /// 只读加载索引文件, 不新建也不修改; 文件不存在或损坏时返回 None
+    pub fn load_read_only(path: &str, first_index: u64) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        if (bytes.len() as u64) < INDEX_HEADER_SIZE_BYTES {
+            return None;
+        }
+        Self::parse(path, first_index, &bytes).ok()
+    }
+
+    fn parse(path: &str, first_index: u64, bytes: &[u8]) -> CResult<Self> {
+        let mut cursor = Cursor::new(bytes);
+        let interval = cursor.read_u32::<LittleEndian>()?.max(1);
+        let indexed_count = cursor.read_u32::<LittleEndian>()?;
+        let mut index = Self {
+            path: path.to_string(),
+            first_index,
+            interval,
+            indexed_count,
+            last_log_pos: None,
+            positions: vec![],
+            gtids: BTreeMap::new(),
+            timestamps: vec![],
+            time_range: None,
+            time_range_changed: false,
+            pending: vec![],
+        };
+
+        let end_index = first_index + indexed_count as u64;
+        while let Ok(record) = Self::read_record(&mut cursor) {
+            if record.index() < first_index || record.index() >= end_index {
+                continue;
+            }
+            match record {
+                IndexRecord::Position { index: entry_index, log_pos } => {
+                    index.positions.push((log_pos, entry_index));
+                    index.last_log_pos = Some(log_pos);
+                }
+                IndexRecord::Gtid { index: entry_index, gtid } => {
+                    index.gtids.entry(gtid).or_insert(entry_index);
+                }
+                IndexRecord::Timestamp { index: entry_index, max_ts } => {
+                    index.timestamps.push((max_ts, entry_index));
+                }
+                IndexRecord::TimeRange { min_ts, max_ts, .. } => {
+                    index.time_range = Some((min_ts, max_ts));
+                }
+            }
+        }
+        index.positions.sort_by_key(|(_, i)| *i);
+        index.timestamps.sort_by_key(|(_, i)| *i);
+
+        Ok(index)
+    }
+
+    /// 读取一条记录
+    fn read_record<R: Read>(reader: &mut R) -> CResult<IndexRecord> {
+        let record_type = reader.read_u8()?;
+        let index = reader.read_u64::<LittleEndian>()?;
+        match record_type {
+            RECORD_POSITION => {
+                let log_pos = reader.read_u64::<LittleEndian>()?;
+                Ok(IndexRecord::Position { index, log_pos })
+            }
+            RECORD_GTID => {
+                let len = reader.read_u16::<LittleEndian>()?;
+                let mut gtid = vec![0; len as usize];
+                reader.read_exact(&mut gtid)?;
+                let gtid = String::from_utf8(gtid).map_err(|e| ReError::Error(e.to_string()))?;
+                Ok(IndexRecord::Gtid { index, gtid })
+            }
+            RECORD_TIMESTAMP => {
+                let max_ts = reader.read_u32::<LittleEndian>()?;
+                Ok(IndexRecord::Timestamp { index, max_ts })
+            }
+            RECORD_TIME_RANGE => {
+                let min_ts = reader.read_u32::<LittleEndian>()?;
+                let max_ts = reader.read_u32::<LittleEndian>()?;
+                Ok(IndexRecord::TimeRange { index, min_ts, max_ts })
+            }
+            t => Err(ReError::Error(format!("unknown segment index record: {}", t))),
+        }
+    }
+
+    /// position 采样间隔
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// 已建立索引的entry数量
+    pub fn indexed_count(&self) -> u32 {
+        self.indexed_count
+    }
+
+    /// 续写时设置最后一个entry的log_pos
+    pub fn set_last_log_pos(&mut self, log_pos: u64) {
+        self.last_log_pos = Some(log_pos);
+    }
+
+    /// 为新追加的entry建立索引, entry 需按 index 顺序添加。
+    ///
+    /// 每 interval 个 entry 以及 log_pos 回退时记录一个 position, 每 interval 个 entry 记录一个时间戳,
+    /// 同一个 gtid 只记录第一个 entry
+    pub fn add(&mut self, entry_index: u64, log_pos: u64, timestamp: u32, gtid: Option<&str>) -> CResult<()> {
+        let offset = entry_index - self.first_index;
+        let sampled = offset % self.interval as u64 == 0;
+
+        let (min_ts, max_ts) = match self.time_range {
+            None => (timestamp, timestamp),
+            Some((min_ts, max_ts)) => (min_ts.min(timestamp), max_ts.max(timestamp)),
+        };
+        if self.time_range != Some((min_ts, max_ts)) {
+            self.time_range = Some((min_ts, max_ts));
+            self.time_range_changed = true;
+        }
+        if sampled {
+            self.pending.write_u8(RECORD_TIMESTAMP)?;
+            self.pending.write_u64::<LittleEndian>(entry_index)?;
+            self.pending.write_u32::<LittleEndian>(max_ts)?;
+            self.timestamps.push((max_ts, entry_index));
+        }
+
+        let rotated = self.last_log_pos.map_or(false, |last| log_pos < last);
+        if sampled || rotated {
+            self.pending.write_u8(RECORD_POSITION)?;
+            self.pending.write_u64::<LittleEndian>(entry_index)?;
+            self.pending.write_u64::<LittleEndian>(log_pos)?;
+            self.positions.push((log_pos, entry_index));
+        }
+        self.last_log_pos = Some(log_pos);
+
+        if let Some(gtid) = gtid.filter(|g| !g.is_empty() && !self.gtids.contains_key(*g)) {
+            self.pending.write_u8(RECORD_GTID)?;
+            self.pending.write_u64::<LittleEndian>(entry_index)?;
+            self.pending.write_u16::<LittleEndian>(gtid.len() as u16)?;
+            self.pending.write_all(gtid.as_bytes())?;
+            self.gtids.insert(gtid.to_string(), entry_index);
+        }
+
+        self.indexed_count += 1;
+        Ok(())
+    }
+
+    /// 写入未落盘的记录, 然后更新文件头中的 indexed_count
+    pub fn flush(&mut self) -> CResult<()> {
+        if let Some((min_ts, max_ts)) = self.time_range.filter(|_| self.time_range_changed) {
+            self.pending.write_u8(RECORD_TIME_RANGE)?;
+            self.pending.write_u64::<LittleEndian>(self.first_index + self.indexed_count as u64 - 1)?;
+            self.pending.write_u32::<LittleEndian>(min_ts)?;
+            self.pending.write_u32::<LittleEndian>(max_ts)?;
+            self.time_range_changed = false;
+        }
+
+        let mut f = OpenOptions::new().write(true).open(&self.path)?;
+        if !self.pending.is_empty() {
+            f.seek(SeekFrom::End(0))?;
+            f.write_all(&self.pending)?;
+            self.pending.clear();
+        }
+        f.seek(SeekFrom::Start(4))?;
+        f.write_u32::<LittleEndian>(self.indexed_count)?;
+        f.flush()?;
+        Ok(())
+    }
+
+    /// 查找 log_pos 的扫描起点: 从最后一段开始, 找到起始 log_pos 不大于 pos 的一段,
+    /// 在段内二分查找最后一个不大于 pos 的采样点, 返回其 entry index
+    pub fn seek_position(&self, pos: u64) -> Option<u64> {
+        let mut end = self.positions.len();
+        while end > 0 {
+            // 当前段的起点
+            let mut start = end - 1;
+            while start > 0 && self.positions[start - 1].0 <= self.positions[start].0 {
+                start -= 1;
+            }
+
+            let run = &self.positions[start..end];
+            if run[0].0 <= pos {
+                let p = run.partition_point(|(log_pos, _)| *log_pos <= pos);
+                return Some(run[p - 1].1);
+            }
+            end = start;
+        }
+
+        None
+    }
+
+    /// 时间戳不小于 from_ts 的entry可能出现的第一个 entry index:
+    /// 最大时间戳仍小于 from_ts 的采样点及其之前的entry都可以跳过
+    pub fn seek_timestamp(&self, from_ts: u32) -> u64 {
+        let p = self.timestamps.partition_point(|(max_ts, _)| *max_ts < from_ts);
+        if p == 0 {
+            self.first_index
+        } else {
+            self.timestamps[p - 1].1 + 1
+        }
+    }
+
+    /// 全部entry的 (最小时间戳, 最大时间戳), 没有entry时返回 None
+    pub fn time_range(&self) -> Option<(u32, u32)> {
+        self.time_range
+    }
+
+    /// gtid 所在事务的第一个 entry index
+    pub fn seek_gtid(&self, gtid: &str) -> Option<u64> {
+        self.gtids.get(gtid).copied()
+    }
+
+    /// 删除索引文件
+    pub fn delete(&self) -> CResult<()> {
+        if Path::new(&self.path).exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for SegmentIndex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SegmentIndex")
+            .field("path", &self.path)
+            .field("indexed_count", &self.indexed_count)
+            .field("positions", &self.positions.len())
+            .field("gtids", &self.gtids.len())
+            .field("time_range", &self.time_range)
+            .finish()
+    }
+}
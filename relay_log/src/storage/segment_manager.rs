@@ -3,18 +3,32 @@ use std::collections::BTreeMap;
 use std::ffi::OsString;
 use std::fmt::{Debug, Display, Formatter};
 use std::fs;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::fmt::format;
 
 use common::err::CResult;
 use common::err::decode_error::ReError;
 
-use crate::storage::segment::Segment;
+use crate::relay_log::RelayLog;
+use crate::storage::compression::CompressionCodec;
+use crate::storage::retention::{PurgeStats, RetentionPolicy};
+use crate::storage::segment::{RecoveryStats, Segment};
 use crate::storage::segment_file::SegmentFile;
-use crate::storage::storage_config::StorageConfig;
+use crate::storage::storage_config::{StorageConfig, VERSION};
+use crate::storage::storage_entry::StorageEntry;
+use crate::storage::tiering::{ObjectStore, TieredSegment, TierManifest, TierStats, TieringPolicy};
+
+/// 主键压缩时新segment的临时目录
+const COMPACT_DIR: &str = ".compact";
+/// 新segment写完后的提交标记, 第一行为新segment文件名, 其余为待删除的旧segment文件名
+const COMPACT_COMMIT_FILE: &str = "COMMIT";
 
 /// 目标表segment文件管理.
 ///
@@ -29,54 +43,139 @@ pub struct SegmentManager {
     max_segment_size: u64,
     // 单个segment最多entry数
     max_segment_entries: u32,
+    // 写满滚动时的压缩方式与级别
+    compression: CompressionCodec,
+    compression_level: i32,
+    // segment索引的position采样间隔
+    index_interval: u32,
+    // 启动时截断最后一个segment损坏尾部的结果
+    recovery: RecoveryStats,
+    // 冷segment的对象存储
+    object_store: Option<Arc<dyn ObjectStore>>,
+    // 已上传到对象存储的segment清单
+    manifest: TierManifest,
+    // 只读打开, 不修改任何文件
+    read_only: bool,
 }
 
 impl SegmentManager {
     /// 初始化一个segment管理器
     pub fn new(storage_config: &StorageConfig, dst_db_name: &str, dst_table_name: &str) -> CResult<Self> {
-        let segment_dir = Self::get_segment_dir_path(storage_config.relay_log_dir(), dst_db_name, dst_table_name)?;
+        let segment_dir = Self::get_segment_dir_path(&storage_config.partition_dir(), dst_db_name, dst_table_name)?;
         let max_segment_size = *storage_config.max_segment_size();
         let max_segment_entries = *storage_config.max_segment_entries();
         // 加载已有的segment文件
-        let mut segments = Self::load_segment(segment_dir.as_str())?;
+        let (mut segments, recovery) = Self::load_segment(segment_dir.as_str())?;
         info!("load segments: {:?}", &segments);
-        if segments.is_empty() {
-            // 实例化第一个segment
-            let mut segment = Segment::new(&segment_dir,
-                                           1,
-                                           1,
-                                           max_segment_size,
-                                           max_segment_entries)?;
-            segment.write_open()?;
-            let index = segment.first_index();
-            let current_segment = Rc::new(RefCell::new(segment));
-            segments.insert(index, Rc::clone(&current_segment));
-            Ok(Self {
-                current_segment,
-                segments,
-                segment_dir,
-                max_segment_size,
-                max_segment_entries,
-            })
-        } else {
-            let current_segment = Rc::clone(segments.last_entry().ok_or(ReError::Error("get last segment err.".to_string()))?.get());
-            Ok(Self {
-                current_segment,
-                segments,
-                segment_dir,
-                max_segment_size,
-                max_segment_entries,
-            })
+        let manifest = TierManifest::load(&segment_dir)?;
+        // 第一个segment的 (id, first index)
+        let mut first = (1, 1);
+        // 旧版本的空segment直接删除, 在原位置新建
+        let stale = segments.values().last()
+            .filter(|s| s.borrow().version() != VERSION && s.borrow().is_empty())
+            .map(Rc::clone);
+        if let Some(stale) = stale {
+            let stale = stale.borrow();
+            stale.delete()?;
+            segments.remove(&stale.segment_file().index()?);
+            first = (stale.id() + 1, *stale.header_first_index());
+        }
+
+        let current_segment = match segments.values().last() {
+            Some(last) => Rc::clone(last),
+            None => {
+                let mut segment = Segment::new(&segment_dir,
+                                               first.0,
+                                               first.1,
+                                               max_segment_size,
+                                               max_segment_entries,
+                                               *storage_config.index_interval())?;
+                segment.write_open()?;
+                let current_segment = Rc::new(RefCell::new(segment));
+                segments.insert(first.1, Rc::clone(&current_segment));
+                current_segment
+            }
+        };
+        // 旧版本的segment不再追加, 滚动到新的segment
+        let upgrade = current_segment.borrow().version() != VERSION;
+        let mut manager = Self {
+            current_segment,
+            segments,
+            segment_dir,
+            max_segment_size,
+            max_segment_entries,
+            compression: *storage_config.compression(),
+            compression_level: *storage_config.compression_level(),
+            index_interval: *storage_config.index_interval(),
+            recovery,
+            object_store: storage_config.object_store().clone(),
+            manifest,
+            read_only: false,
+        };
+        if upgrade {
+            manager.create_next_segment()?;
+        }
+        Ok(manager)
+    }
+
+    /// 只读打开其它实例可能正在写入的目标表日志: 不恢复中断的压缩与未完成的写入, 不开启可写模式,
+    /// 不从对象存储取回segment。 只能读取, 写入、清理、压缩与分层均返回错误
+    pub fn open_read_only(storage_config: &StorageConfig, dst_db_name: &str, dst_table_name: &str) -> CResult<Self> {
+        let segment_dir = Self::get_segment_dir_path(&storage_config.partition_dir(), dst_db_name, dst_table_name)?;
+        let mut segments = BTreeMap::new();
+        for (first_index, path) in Self::segment_files(&segment_dir)? {
+            if let Ok(segment) = Segment::open_for_read(&path) {
+                segments.insert(first_index, Rc::new(RefCell::new(segment)));
+            }
+        }
+        let current_segment = Rc::clone(segments.values().last()
+            .ok_or(ReError::String(format!("no segment in {}.", segment_dir)))?);
+        let manifest = TierManifest::load(&segment_dir)?;
+
+        Ok(Self {
+            current_segment,
+            segments,
+            segment_dir,
+            max_segment_size: *storage_config.max_segment_size(),
+            max_segment_entries: *storage_config.max_segment_entries(),
+            compression: *storage_config.compression(),
+            compression_level: *storage_config.compression_level(),
+            index_interval: *storage_config.index_interval(),
+            recovery: RecoveryStats::default(),
+            object_store: None,
+            manifest,
+            read_only: true,
+        })
+    }
+
+    /// 目标表的日志文件夹中是否有segment文件
+    pub fn has_segments(segment_dir: &str) -> CResult<bool> {
+        if !Path::new(segment_dir).exists() {
+            return Ok(false);
+        }
+        Ok(!Self::segment_files(segment_dir)?.is_empty())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn check_writable(&self) -> CResult<()> {
+        if self.read_only {
+            return Err(ReError::String(format!("relay log {} is opened read only.", self.segment_dir)));
         }
+        Ok(())
     }
 
-    /// 加载目标表所有segment文件
-    fn load_segment(segment_dir: &str) -> CResult<BTreeMap<u64, Rc<RefCell<Segment>>>> {
+    /// 加载目标表所有segment文件, 并截断最后一个segment中未完成的写入
+    fn load_segment(segment_dir: &str) -> CResult<(BTreeMap<u64, Rc<RefCell<Segment>>>, RecoveryStats)> {
         info!("++++start load segments: {:?}", segment_dir);
         let path = PathBuf::from(segment_dir);
         if !path.exists() {
-            fs::create_dir(path.as_path())?;
+            fs::create_dir_all(path.as_path())?;
         }
+        // 完成上次中断的主键压缩
+        Self::recover_compaction(segment_dir)?;
         let mut segments: BTreeMap<u64, Rc<RefCell<Segment>>> = BTreeMap::new();
         let files = path.read_dir()?;
         for file in files {
@@ -87,19 +186,35 @@ impl SegmentManager {
                         if SegmentFile::is_segment_file(segment_file_name)? {
                             // segment文件全路径
                             let segment_file_path = file_path.to_str().ok_or(ReError::String("".to_string()))?;
-                            if let Ok(mut segment) = Segment::from_file(segment_file_path) {
-                                if !segment.is_full() {
-                                    segment.write_open()?;
-                                }
+                            if let Ok(segment) = Segment::from_file(segment_file_path) {
                                 // todo 校验segment合法性?
-                                segments.insert(segment.first_index(), Rc::new(RefCell::new(segment)));
+                                // 以文件名中的 first index 排序, 空segment的 first_index() 为 0
+                                let first_index = segment.segment_file().index()?;
+                                segments.insert(first_index, Rc::new(RefCell::new(segment)));
                             }
                         }
                     }
                 }
             }
         }
-        Ok(segments)
+
+        // 只有最后一个segment可能存在未完成的写入
+        let recovery = match segments.values().last() {
+            Some(last) => last.borrow_mut().recover_torn_write()?,
+            None => RecoveryStats::default(),
+        };
+        // 旧版本的segment不再追加, 由 new 滚动到新的segment
+        for segment in segments.values() {
+            if !segment.borrow().is_full() && segment.borrow().version() == VERSION {
+                segment.borrow_mut().write_open()?;
+            }
+        }
+        Ok((segments, recovery))
+    }
+
+    /// 启动时截断最后一个segment损坏尾部的结果
+    pub fn recovery(&self) -> RecoveryStats {
+        self.recovery
     }
 
     /// 获取目标表的日志文件夹路径
@@ -109,6 +224,73 @@ impl SegmentManager {
         Ok(path.to_str().ok_or(ReError::String("".to_string()))?.to_string())
     }
 
+    /// 存储根目录下的分区名, 不包含默认分区; 目标表的日志文件夹名包含 `#`, 不会被当作分区
+    pub fn partitions(relay_log_dir: &str) -> CResult<Vec<String>> {
+        let path = PathBuf::from(relay_log_dir);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let mut partitions = vec![];
+        for f in path.read_dir()?.flatten() {
+            if let Some(name) = f.file_name().to_str() {
+                if f.path().is_dir() && Self::check_partition_name(name).is_ok() {
+                    partitions.push(name.to_string());
+                }
+            }
+        }
+        partitions.sort();
+        Ok(partitions)
+    }
+
+    /// 分区目录下已有日志文件夹的目标表: (库, 表)
+    pub fn tables(partition_dir: &str) -> CResult<Vec<(String, String)>> {
+        let path = PathBuf::from(partition_dir);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let mut tables = vec![];
+        for f in path.read_dir()?.flatten() {
+            if let Some((db, table)) = f.file_name().to_str().and_then(|n| n.split_once('#')) {
+                if f.path().is_dir() {
+                    tables.push((db.to_string(), table.to_string()));
+                }
+            }
+        }
+        tables.sort();
+        Ok(tables)
+    }
+
+    /// 分区名只能包含字母、数字、`_` 与 `-`
+    pub fn check_partition_name(partition: &str) -> CResult<()> {
+        if partition.is_empty() || !partition.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(ReError::String(format!("invalid relay log partition name: {:?}", partition)));
+        }
+        Ok(())
+    }
+
+    /// 目标表的日志文件夹
+    pub fn segment_dir(&self) -> &str {
+        &self.segment_dir
+    }
+
+    /// 文件夹中的segment文件: 文件名中的 first index -> 文件路径
+    pub(crate) fn segment_files(segment_dir: &str) -> CResult<BTreeMap<u64, String>> {
+        let mut files = BTreeMap::new();
+        for f in PathBuf::from(segment_dir).read_dir()?.flatten() {
+            let file_path = f.path();
+            let name = match file_path.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            if file_path.is_file() && SegmentFile::is_segment_file(name)? {
+                if let Some(path) = file_path.to_str() {
+                    files.insert(SegmentFile::new(path.to_string(), name.to_string(), 0).index()?, path.to_string());
+                }
+            }
+        }
+        Ok(files)
+    }
+
     /// 当前segment
     pub fn current_segment(&self) -> Rc<RefCell<Segment>> {
         Rc::clone(&self.current_segment)
@@ -124,7 +306,190 @@ impl SegmentManager {
         Ok(Rc::clone(self.segments.first_entry().ok_or(ReError::Error("get last segment err.".to_string()))?.get()))
     }
 
-    /// 返回index所在的segment
+    /// 最早的 index, 包括已上传到对象存储的segment
+    pub fn first_index(&mut self) -> CResult<u64> {
+        let local = self.first_segment()?.borrow().first_index();
+        Ok(match self.manifest.first_index() {
+            Some(tiered) if local == 0 || tiered < local => tiered,
+            _ => local,
+        })
+    }
+
+    /// 全部本地segment, 按 first index 升序
+    pub(crate) fn segments(&self) -> Vec<Rc<RefCell<Segment>>> {
+        self.segments.values().map(Rc::clone).collect()
+    }
+
+    /// 在 dir 下新建一个与本表配置相同的可写segment, 用于导出归档
+    pub(crate) fn create_segment(&self, dir: &str, id: u32, first_index: u64) -> CResult<Segment> {
+        let mut segment = Segment::new(dir,
+                                       id,
+                                       first_index,
+                                       self.max_segment_size,
+                                       self.max_segment_entries,
+                                       self.index_interval)?;
+        segment.write_open()?;
+        Ok(segment)
+    }
+
+    /// 写满滚动时的压缩方式与级别
+    pub(crate) fn compression(&self) -> (CompressionCodec, i32) {
+        (self.compression, self.compression_level)
+    }
+
+    /// 本地segment数量
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// 按保留策略从最旧的segment开始删除, 当前写入的segment以及包含 protected_from 的segment及之后的segment不删除
+    pub fn purge(&mut self, policy: &RetentionPolicy, protected_from: Option<u64>) -> CResult<PurgeStats> {
+        let mut stats = PurgeStats::default();
+        if !policy.is_enabled() {
+            return Ok(stats);
+        }
+        self.check_writable()?;
+
+        let mut total_bytes: u64 = 0;
+        for s in self.segments.values() {
+            total_bytes += s.borrow().segment_file().size_file()?;
+        }
+        let now = SystemTime::now();
+        let max_age = Duration::from_millis(policy.max_age_millisecond);
+
+        let first_indexes: Vec<u64> = self.segments.keys().copied().collect();
+        for first_index in first_indexes {
+            let segment = Rc::clone(&self.segments[&first_index]);
+            if Rc::ptr_eq(&segment, &self.current_segment) {
+                break;
+            }
+            let (last_index, size, modified) = {
+                let s = segment.borrow();
+                (s.last_index(), s.segment_file().size_file()?, s.segment_file().modified()?)
+            };
+            // 仍有消费者未读取完
+            if protected_from.map_or(false, |offset| last_index >= offset) {
+                break;
+            }
+
+            let expired = policy.max_age_millisecond > 0
+                && now.duration_since(modified).map_or(false, |age| age > max_age);
+            let oversize = policy.max_bytes > 0 && total_bytes > policy.max_bytes;
+            if !expired && !oversize {
+                break;
+            }
+
+            segment.borrow().delete()?;
+            self.segments.remove(&first_index);
+            total_bytes -= size;
+            stats.segments += 1;
+            stats.bytes += size;
+        }
+
+        if stats.segments > 0 {
+            info!("purge {} segments, {} bytes from {}", stats.segments, stats.bytes, self.segment_dir);
+        }
+        Ok(stats)
+    }
+
+    /// 用 logs 重写 first_indexes 对应的连续segment, 新segment沿用第一个segment的id与first index。
+    ///
+    /// 先在 .compact 目录中写好新segment并写入 COMMIT 标记, 再删除旧segment、移入新segment,
+    /// 中途崩溃时由 [SegmentManager::recover_compaction] 在加载时继续完成
+    pub(crate) fn rewrite(&mut self, olds: &[Rc<RefCell<Segment>>], logs: Vec<RelayLog>) -> CResult<()> {
+        self.check_writable()?;
+        if olds.is_empty() || logs.is_empty() || olds.iter().any(|s| Rc::ptr_eq(s, &self.current_segment)) {
+            return Err(ReError::String("only sealed segments can be rewritten.".to_string()));
+        }
+        let first_index = olds[0].borrow().first_index();
+        let id = olds[0].borrow().id();
+
+        let compact_dir = PathBuf::from(&self.segment_dir).join(COMPACT_DIR);
+        if compact_dir.exists() {
+            fs::remove_dir_all(&compact_dir)?;
+        }
+        fs::create_dir(&compact_dir)?;
+        let compact_dir_str = compact_dir.to_str().ok_or(ReError::String("".to_string()))?;
+
+        let segment_file_name = {
+            let mut segment = Segment::new(compact_dir_str,
+                                           id,
+                                           first_index,
+                                           self.max_segment_size,
+                                           logs.len() as u32,
+                                           self.index_interval)?;
+            segment.write_open()?;
+            for (i, log) in logs.into_iter().enumerate() {
+                let mut entry = StorageEntry::new(first_index + i as u64, 0, 0, log);
+                segment.append(&mut entry)?;
+            }
+            segment.write_close()?;
+            segment.compress(self.compression, self.compression_level)?;
+            segment.segment_file().name().clone()
+        };
+
+        let mut commit = File::create(compact_dir.join(COMPACT_COMMIT_FILE))?;
+        writeln!(commit, "{}", segment_file_name)?;
+        for s in olds {
+            writeln!(commit, "{}", s.borrow().segment_file().name())?;
+        }
+        commit.sync_all()?;
+        drop(commit);
+
+        Self::apply_compaction(&self.segment_dir)?;
+        self.segments.retain(|_, s| !olds.iter().any(|o| Rc::ptr_eq(o, s)));
+        let path = PathBuf::from(&self.segment_dir).join(&segment_file_name);
+        let segment = Segment::from_file(path.to_str().ok_or(ReError::String("".to_string()))?)?;
+        self.segments.insert(first_index, Rc::new(RefCell::new(segment)));
+        Ok(())
+    }
+
+    /// 加载segment前处理上次中断的主键压缩: 已提交则继续完成, 未提交则丢弃
+    fn recover_compaction(segment_dir: &str) -> CResult<()> {
+        let compact_dir = PathBuf::from(segment_dir).join(COMPACT_DIR);
+        if !compact_dir.exists() {
+            return Ok(());
+        }
+        if compact_dir.join(COMPACT_COMMIT_FILE).exists() {
+            warn!("resume segment compaction in {}", segment_dir);
+            Self::apply_compaction(segment_dir)
+        } else {
+            warn!("discard uncommitted segment compaction in {}", segment_dir);
+            Ok(fs::remove_dir_all(compact_dir)?)
+        }
+    }
+
+    /// 将新segment移入segment目录, 再删除 COMMIT 中的旧segment及其索引, 可重复执行。
+    ///
+    /// 新segment与第一个旧segment同名, 移入时直接替换, 删除时跳过
+    fn apply_compaction(segment_dir: &str) -> CResult<()> {
+        let dir = Path::new(segment_dir);
+        let compact_dir = dir.join(COMPACT_DIR);
+        let commit_file = compact_dir.join(COMPACT_COMMIT_FILE);
+        let commit = fs::read_to_string(&commit_file)?;
+        let mut names = commit.lines().filter(|l| !l.is_empty());
+        let new_name = names.next().ok_or(ReError::Error(format!("empty compaction commit in {}.", segment_dir)))?;
+
+        for file in compact_dir.read_dir()? {
+            let file = file?;
+            if file.file_name() != COMPACT_COMMIT_FILE {
+                fs::rename(file.path(), dir.join(file.file_name()))?;
+            }
+        }
+        for name in names.filter(|n| *n != new_name) {
+            let old = dir.join(name);
+            for path in [old.with_extension("log"), old.with_extension("idx")] {
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        fs::remove_file(&commit_file)?;
+        fs::remove_dir(&compact_dir)?;
+        Ok(())
+    }
+
+    /// 返回index所在的segment, 已上传到对象存储的segment先取回本地
     pub fn segment(&mut self, index: u64) -> CResult<Rc<RefCell<Segment>>> {
         if self.current_segment.borrow().contain_index(index) {
             Ok(Rc::clone(&self.current_segment))
@@ -134,24 +499,141 @@ impl SegmentManager {
                     return Ok(Rc::clone(s));
                 }
             }
-            Err(ReError::Error(format!("unknown index: {}.", index)))
+            match self.manifest.find(index).map(|s| s.first_index) {
+                Some(first_index) => self.fetch(first_index),
+                None => Err(ReError::Error(format!("unknown index: {}.", index))),
+            }
         }
     }
 
+    /// 把写满超过 cold_after 的segment上传到对象存储并删除本地文件, 从最旧的segment开始。
+    ///
+    /// 当前写入的segment以及包含 protected_from 的segment及之后的segment保留在本地, 供尾随读取
+    pub fn offload(&mut self, policy: &TieringPolicy, protected_from: Option<u64>) -> CResult<TierStats> {
+        let mut stats = TierStats::default();
+        self.check_writable()?;
+        let store = match &self.object_store {
+            Some(store) if policy.is_enabled() => Arc::clone(store),
+            _ => return Ok(stats),
+        };
+        let now = SystemTime::now();
+        let cold_after = Duration::from_millis(policy.cold_after_millisecond);
+        let prefix = Path::new(&self.segment_dir).file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let first_indexes: Vec<u64> = self.segments.keys().copied().collect();
+        for first_index in first_indexes {
+            let segment = Rc::clone(&self.segments[&first_index]);
+            if Rc::ptr_eq(&segment, &self.current_segment) {
+                break;
+            }
+            let s = segment.borrow();
+            if protected_from.map_or(false, |offset| s.last_index() >= offset) {
+                break;
+            }
+            if s.is_empty() || now.duration_since(s.segment_file().modified()?).map_or(true, |age| age <= cold_after) {
+                break;
+            }
+
+            let size = s.segment_file().size_file()?;
+            // 取回后再次变冷的segment已在对象存储中, 不再上传
+            if self.manifest.get(first_index).map_or(true, |t| t.size != size) {
+                let key = format!("{}/{}", prefix, s.segment_file().name());
+                store.put(&key, Path::new(s.segment_file().path()))?;
+                let index_file = s.index_file();
+                let index_key = if Path::new(&index_file).exists() {
+                    let index_key = format!("{}.idx", key.strip_suffix(".log").unwrap_or(&key));
+                    store.put(&index_key, Path::new(&index_file))?;
+                    Some(index_key)
+                } else {
+                    None
+                };
+                self.manifest.insert(TieredSegment {
+                    name: s.segment_file().name().clone(),
+                    key,
+                    index_key,
+                    first_index,
+                    last_index: s.last_index(),
+                    time_range: s.time_range(),
+                    size,
+                })?;
+            }
+
+            s.delete()?;
+            drop(s);
+            self.segments.remove(&first_index);
+            stats.segments += 1;
+            stats.bytes += size;
+        }
+
+        if stats.segments > 0 {
+            info!("offload {} segments, {} bytes from {} to {}", stats.segments, stats.bytes, self.segment_dir, store.name());
+        }
+        Ok(stats)
+    }
+
+    /// 取回时间范围与 [from_ts, to_ts] 相交、且不在本地的segment
+    pub(crate) fn fetch_range(&mut self, from_ts: u32, to_ts: u32) -> CResult<()> {
+        // 只读时只扫描本地segment
+        if self.read_only {
+            return Ok(());
+        }
+        let first_indexes: Vec<u64> = self.manifest.overlapping(from_ts, to_ts)
+            .iter()
+            .map(|s| s.first_index)
+            .filter(|i| !self.segments.contains_key(i))
+            .collect();
+        for first_index in first_indexes {
+            self.fetch(first_index)?;
+        }
+        Ok(())
+    }
+
+    /// 从对象存储取回segment, 先下载到临时文件再替换
+    fn fetch(&mut self, first_index: u64) -> CResult<Rc<RefCell<Segment>>> {
+        self.check_writable()?;
+        let store = self.object_store.as_ref()
+            .ok_or(ReError::Error(format!("segment {} is tiered but no object store is configured.", first_index)))?;
+        let tiered = self.manifest.get(first_index)
+            .ok_or(ReError::Error(format!("unknown tiered segment: {}.", first_index)))?;
+        info!("fetch segment {} from {}", tiered.name, store.name());
+
+        let path = PathBuf::from(&self.segment_dir).join(&tiered.name);
+        if let Some(index_key) = &tiered.index_key {
+            let index_path = path.with_extension("idx");
+            let tmp = path.with_extension("idx.fetch");
+            store.get(index_key, &tmp)?;
+            fs::rename(&tmp, &index_path)?;
+        }
+        let tmp = path.with_extension("log.fetch");
+        store.get(&tiered.key, &tmp)?;
+        fs::rename(&tmp, &path)?;
+
+        let segment_file_path = path.to_str().ok_or(ReError::String("".to_string()))?;
+        let segment = Rc::new(RefCell::new(Segment::from_file(segment_file_path)?));
+        self.segments.insert(first_index, Rc::clone(&segment));
+        Ok(segment)
+    }
+
     /// 创建下一个segment
     pub fn create_next_segment(&mut self) -> CResult<Rc<RefCell<Segment>>> {
+        self.check_writable()?;
         let last_segment = self.last_segment()?;
         if last_segment.borrow().is_writable() {
             // 关闭最后一个segment可写模式
             last_segment.borrow_mut().write_close()?;
         }
+        last_segment.borrow_mut().compress(self.compression, self.compression_level)?;
         let next_segment_id = last_segment.borrow().id() + 1;
         let next_segment_first_index = last_segment.borrow().last_index() + 1;
         let mut next_segment = Segment::new(&self.segment_dir,
                                             next_segment_id,
                                             next_segment_first_index,
                                             self.max_segment_size,
-                                            self.max_segment_entries)?;
+                                            self.max_segment_entries,
+                                            self.index_interval)?;
         next_segment.write_open()?;
         self.current_segment = Rc::new(RefCell::new(next_segment));
         let r = self.segments.insert(next_segment_first_index, Rc::clone(&self.current_segment));
@@ -0,0 +1,142 @@
+use std::rc::Rc;
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+use crate::relay_log::RelayLog;
+use crate::storage::relay_log_storage::RelayLogStorage;
+use crate::storage::retention::{ConsumerOffsets, PurgeStats};
+use crate::storage::storage_config::StorageConfig;
+use crate::storage::storage_entry::StorageEntry;
+use crate::storage::storage_metrics::StorageMetrics;
+
+/// 目标表中继日志的存储后端: 按 index 顺序追加, 按 index 或 GTID 读取。
+///
+/// 默认为 segment 文件([RelayLogStorage]); 开启 `rocksdb` feature 后可选 RocksDB,
+/// 由 RocksDB 负责压缩与快照。 通过 [open_backend] 按 [StorageConfig::backend] 打开
+pub trait StorageBackend {
+    fn name(&self) -> String;
+
+    /// 追加中继日志, gtid 为其所属事务的GTID
+    fn append_relay_log_with_gtid(&mut self, log: RelayLog, gtid: Option<&str>) -> CResult<()>;
+
+    fn append_relay_log(&mut self, log: RelayLog) -> CResult<()> {
+        self.append_relay_log_with_gtid(log, None)
+    }
+
+    /// 提交已追加的日志, 是否 fsync 由落盘策略决定
+    fn flush(&mut self) -> CResult<()>;
+
+    /// 提交已追加的日志并 fsync, 与落盘策略无关
+    fn sync(&mut self) -> CResult<()>;
+
+    /// 不再追加时定时调用, 按落盘策略提交或 fsync 等待过久的日志
+    fn tick(&mut self) -> CResult<()> {
+        Ok(())
+    }
+
+    fn get_entry(&mut self, index: u64) -> CResult<Rc<StorageEntry>>;
+
+    /// 已存储的 index 范围 [first, last], 没有日志时返回 None
+    fn index_range(&mut self) -> CResult<Option<(u64, u64)>>;
+
+    /// gtid 所在事务的第一个 entry index
+    fn seek_gtid(&mut self, gtid: &str) -> CResult<Option<u64>>;
+
+    /// 按保留策略清理, 不清理消费者未读取的日志
+    fn purge(&mut self) -> CResult<PurgeStats>;
+
+    fn consumer_offsets(&self) -> ConsumerOffsets;
+
+    fn metrics(&self) -> StorageMetrics;
+}
+
+/// 存储后端类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageBackendKind {
+    /// segment 文件
+    #[default]
+    Segment,
+    /// RocksDB, 需要开启 `rocksdb` feature
+    RocksDb,
+}
+
+impl TryFrom<&str> for StorageBackendKind {
+    type Error = ReError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "segment" => Ok(StorageBackendKind::Segment),
+            "rocksdb" => Ok(StorageBackendKind::RocksDb),
+            _ => Err(ReError::String(format!("Unsupported relay log storage backend: {}", value))),
+        }
+    }
+}
+
+/// 按配置的后端打开目标表的中继日志
+pub fn open_backend(storage_config: &StorageConfig, dst_db_name: &str, dst_table_name: &str) -> CResult<Box<dyn StorageBackend>> {
+    match storage_config.backend() {
+        StorageBackendKind::Segment => {
+            Ok(Box::new(RelayLogStorage::new(storage_config, dst_db_name.to_string(), dst_table_name.to_string())?))
+        }
+        StorageBackendKind::RocksDb => open_rocksdb(storage_config, dst_db_name, dst_table_name),
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+fn open_rocksdb(storage_config: &StorageConfig, dst_db_name: &str, dst_table_name: &str) -> CResult<Box<dyn StorageBackend>> {
+    use crate::storage::rocksdb_storage::RocksDbStorage;
+
+    Ok(Box::new(RocksDbStorage::new(storage_config, dst_db_name, dst_table_name)?))
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn open_rocksdb(_storage_config: &StorageConfig, _dst_db_name: &str, _dst_table_name: &str) -> CResult<Box<dyn StorageBackend>> {
+    Err(ReError::String(String::from("rocksdb storage backend is not enabled, rebuild relay_log with `--features rocksdb`")))
+}
+
+impl StorageBackend for RelayLogStorage {
+    fn name(&self) -> String {
+        String::from("segment")
+    }
+
+    fn append_relay_log_with_gtid(&mut self, log: RelayLog, gtid: Option<&str>) -> CResult<()> {
+        RelayLogStorage::append_relay_log_with_gtid(self, log, gtid)
+    }
+
+    fn flush(&mut self) -> CResult<()> {
+        RelayLogStorage::flush(self)
+    }
+
+    fn sync(&mut self) -> CResult<()> {
+        RelayLogStorage::sync(self)
+    }
+
+    fn tick(&mut self) -> CResult<()> {
+        RelayLogStorage::tick(self)
+    }
+
+    fn get_entry(&mut self, index: u64) -> CResult<Rc<StorageEntry>> {
+        RelayLogStorage::get_entry(self, index)
+    }
+
+    fn index_range(&mut self) -> CResult<Option<(u64, u64)>> {
+        RelayLogStorage::index_range(self)
+    }
+
+    fn seek_gtid(&mut self, gtid: &str) -> CResult<Option<u64>> {
+        RelayLogStorage::seek_gtid(self, gtid)
+    }
+
+    fn purge(&mut self) -> CResult<PurgeStats> {
+        RelayLogStorage::purge(self)
+    }
+
+    fn consumer_offsets(&self) -> ConsumerOffsets {
+        RelayLogStorage::consumer_offsets(self)
+    }
+
+    fn metrics(&self) -> StorageMetrics {
+        RelayLogStorage::metrics(self)
+    }
+}
@@ -1,11 +1,24 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use getset::{Getters, Setters};
 
-/// 版本号
-pub(crate) const VERSION: u32 = 1;
+use crate::storage::compression::CompressionCodec;
+use crate::storage::durability::FsyncPolicy;
+use crate::storage::storage_backend::StorageBackendKind;
+use crate::storage::tiering::ObjectStore;
+
+/// 版本号, 写入segment文件头与文件名。
+///
+/// - 1: 初始版本
+/// - 2: RelayLog 增加 event_timestamp, 版本 1 的entry按 [crate::relay_log::RelayLogV1] 解码
+pub(crate) const VERSION: u32 = 2;
 /// segment文件前缀
 pub(crate) const SEGMENT_FILE_PRE: &str = "rlog";
 /// segment文件头大小
 pub(crate) const SEGMENT_HEADER_SIZE_BYTES: usize = 64;
+/// segment索引默认的position采样间隔
+pub(crate) const DEFAULT_INDEX_INTERVAL: u32 = 16;
 
 /// 存储可配项
 #[derive(Debug, Clone, Getters, Setters)]
@@ -14,6 +27,14 @@ pub struct StorageConfig {
     #[getset(get = "pub", set = "pub")]
     relay_log_dir: String,
 
+    // 存储后端
+    #[getset(get = "pub", set = "pub")]
+    backend: StorageBackendKind,
+
+    // 分区名, 每个上游源或租户一个分区, 位于 relay_log_dir/{partition}; 为空时为默认分区, 即 relay_log_dir 本身
+    #[getset(get = "pub", set = "pub")]
+    partition: String,
+
     // 每个segment最大值
     #[getset(get = "pub", set = "pub")]
     max_segment_size: u64,
@@ -26,19 +47,69 @@ pub struct StorageConfig {
     #[getset(get = "pub", set = "pub")]
     entry_buffer_num: usize,
 
-    // 是否flush
+    // 是否flush, 开启时每次追加都提交一个批次, 等价于 group_commit_entries = 1
     #[getset(get = "pub", set = "pub")]
     flush_on_commit: bool,
 
+    // 落盘策略
+    #[getset(get = "pub", set = "pub")]
+    fsync_policy: FsyncPolicy,
+
+    // Interval 落盘策略的 fsync 间隔
+    #[getset(get = "pub", set = "pub")]
+    fsync_interval_millisecond: u64,
+
+    // group commit: 每批最多的entry数量, 0 表示只在 flush 或 segment 写满时提交
+    #[getset(get = "pub", set = "pub")]
+    group_commit_entries: u32,
+
+    // group commit: 批次最长等待时长, 0 表示不限制
+    #[getset(get = "pub", set = "pub")]
+    group_commit_millisecond: u64,
+
     // 日志整理周期
     #[getset(get = "pub", set = "pub")]
     compact_interval_millisecond: u64,
+
+    // segment写满滚动时的压缩方式
+    #[getset(get = "pub", set = "pub")]
+    compression: CompressionCodec,
+
+    // 压缩级别, zstd 为 1~22
+    #[getset(get = "pub", set = "pub")]
+    compression_level: i32,
+
+    // segment索引每隔多少个entry记录一个position
+    #[getset(get = "pub", set = "pub")]
+    index_interval: u32,
+
+    // 写满的segment保留时长, 0 表示不限制
+    #[getset(get = "pub", set = "pub")]
+    retention_millisecond: u64,
+
+    // 全部segment最多占用的字节数, 0 表示不限制
+    #[getset(get = "pub", set = "pub")]
+    retention_bytes: u64,
+
+    // 检查保留策略的周期
+    #[getset(get = "pub", set = "pub")]
+    purge_interval_millisecond: u64,
+
+    // 写满超过该时长的segment上传到对象存储并删除本地文件, 0 表示不分层
+    #[getset(get = "pub", set = "pub")]
+    tiering_millisecond: u64,
+
+    // 冷segment的对象存储
+    #[getset(get = "pub", set = "pub")]
+    object_store: Option<Arc<dyn ObjectStore>>,
 }
 
 impl Default for StorageConfig {
     fn default() -> Self {
         Self {
             relay_log_dir: "".to_string(),
+            backend: StorageBackendKind::Segment,
+            partition: "".to_string(),
             // 10M
             max_segment_size: 10 * 1024 * 1024,
             //
@@ -46,8 +117,39 @@ impl Default for StorageConfig {
             // 1k个
             entry_buffer_num: 1024,
             flush_on_commit: false,
+            fsync_policy: FsyncPolicy::OsBuffered,
+            // 1s
+            fsync_interval_millisecond: 1000,
+            group_commit_entries: 0,
+            group_commit_millisecond: 0,
             // 5min
             compact_interval_millisecond: 5 * 60 * 1000,
+            compression: CompressionCodec::None,
+            compression_level: 3,
+            index_interval: DEFAULT_INDEX_INTERVAL,
+            retention_millisecond: 0,
+            retention_bytes: 0,
+            // 1min
+            purge_interval_millisecond: 60 * 1000,
+            tiering_millisecond: 0,
+            object_store: None,
         }
     }
-}
\ No newline at end of file
+}
+impl StorageConfig {
+    /// 同一存储根目录下 partition 分区的配置, 其它配置项不变
+    pub fn for_partition(&self, partition: &str) -> Self {
+        let mut config = self.clone();
+        config.partition = partition.to_string();
+        config
+    }
+
+    /// 分区的日志目录, 目标表的日志文件夹位于其下
+    pub fn partition_dir(&self) -> String {
+        if self.partition.is_empty() {
+            self.relay_log_dir.clone()
+        } else {
+            PathBuf::from(&self.relay_log_dir).join(&self.partition).to_string_lossy().to_string()
+        }
+    }
+}
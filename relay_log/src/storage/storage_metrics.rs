@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::storage::compactor::CompactStats;
+use crate::storage::retention::PurgeStats;
+use crate::storage::tiering::TierStats;
+
+/// 计算 flush 延迟分位数保留的最近样本数
+const LATENCY_SAMPLES: usize = 1024;
+
+/// 目标表日志存储的运行指标, 可在其它线程中读取, 由 CLI/web 的指标接口导出。
+///
+/// 计数器只增不减, 存储重新打开后从 0 开始
+#[derive(Debug, Clone)]
+pub struct StorageMetrics {
+    inner: Arc<MetricsInner>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    partition: String,
+    database: String,
+    table: String,
+
+    segments: AtomicU64,
+    active_segment_bytes: AtomicU64,
+
+    entries_written: AtomicU64,
+    bytes_written: AtomicU64,
+    entries_read: AtomicU64,
+    bytes_read: AtomicU64,
+
+    fsyncs: AtomicU64,
+    // 最近的 flush 耗时, 微秒
+    fsync_micros: Mutex<VecDeque<u64>>,
+
+    compactions: AtomicU64,
+    compacted_segments: AtomicU64,
+    // 当前压缩已读取的segment数与总数, 没有进行中的压缩时为 0
+    compaction_done: AtomicU64,
+    compaction_total: AtomicU64,
+
+    purges: AtomicU64,
+    purged_segments: AtomicU64,
+    purged_bytes: AtomicU64,
+    offloaded_segments: AtomicU64,
+}
+
+/// 某一时刻的指标
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StorageMetricsSnapshot {
+    // 分区名, 默认分区为空
+    pub partition: String,
+    pub database: String,
+    pub table: String,
+    pub segments: u64,
+    pub active_segment_bytes: u64,
+    pub entries_written: u64,
+    pub bytes_written: u64,
+    pub entries_read: u64,
+    pub bytes_read: u64,
+    pub fsyncs: u64,
+    pub fsync_p50_micros: u64,
+    pub fsync_p99_micros: u64,
+    pub fsync_max_micros: u64,
+    pub compactions: u64,
+    pub compacted_segments: u64,
+    // 进行中的压缩的进度 [0, 1], 没有进行中的压缩时为 1
+    pub compaction_progress: f64,
+    pub purges: u64,
+    pub purged_segments: u64,
+    pub purged_bytes: u64,
+    pub offloaded_segments: u64,
+}
+
+impl StorageMetrics {
+    pub fn new(partition: &str, database: &str, table: &str) -> Self {
+        Self {
+            inner: Arc::new(MetricsInner {
+                partition: partition.to_string(),
+                database: database.to_string(),
+                table: table.to_string(),
+                ..MetricsInner::default()
+            }),
+        }
+    }
+
+    pub(crate) fn set_segments(&self, segments: usize) {
+        self.inner.segments.store(segments as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_active_segment_bytes(&self, bytes: u64) {
+        self.inner.active_segment_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_write(&self, bytes: u64) {
+        self.inner.entries_written.fetch_add(1, Ordering::Relaxed);
+        self.inner.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_read(&self, bytes: u64) {
+        self.inner.entries_read.fetch_add(1, Ordering::Relaxed);
+        self.inner.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_fsync(&self, elapsed: Duration) {
+        self.inner.fsyncs.fetch_add(1, Ordering::Relaxed);
+        let mut samples = self.inner.fsync_micros.lock().unwrap();
+        if samples.len() >= LATENCY_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(elapsed.as_micros() as u64);
+    }
+
+    pub(crate) fn compaction_started(&self, segments: usize) {
+        self.inner.compaction_done.store(0, Ordering::Relaxed);
+        self.inner.compaction_total.store(segments as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn compaction_progress(&self, done: usize) {
+        self.inner.compaction_done.store(done as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_compact(&self, stats: &CompactStats) {
+        self.inner.compaction_total.store(0, Ordering::Relaxed);
+        self.inner.compaction_done.store(0, Ordering::Relaxed);
+        if stats.segments > 0 {
+            self.inner.compactions.fetch_add(1, Ordering::Relaxed);
+            self.inner.compacted_segments.fetch_add(stats.segments as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_purge(&self, stats: &PurgeStats) {
+        self.inner.purges.fetch_add(1, Ordering::Relaxed);
+        self.inner.purged_segments.fetch_add(stats.segments as u64, Ordering::Relaxed);
+        self.inner.purged_bytes.fetch_add(stats.bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_offload(&self, stats: &TierStats) {
+        self.inner.offloaded_segments.fetch_add(stats.segments as u64, Ordering::Relaxed);
+    }
+
+    /// 最近 flush 耗时的分位数, q 取 [0, 1], 没有样本时为 0
+    pub fn fsync_percentile(&self, q: f64) -> Duration {
+        let mut samples: Vec<u64> = self.inner.fsync_micros.lock().unwrap().iter().copied().collect();
+        if samples.is_empty() {
+            return Duration::ZERO;
+        }
+        samples.sort_unstable();
+        let rank = ((samples.len() - 1) as f64 * q.clamp(0.0, 1.0)).round() as usize;
+        Duration::from_micros(samples[rank])
+    }
+
+    pub fn snapshot(&self) -> StorageMetricsSnapshot {
+        let inner = &self.inner;
+        let load = |v: &AtomicU64| v.load(Ordering::Relaxed);
+        let total = load(&inner.compaction_total);
+
+        StorageMetricsSnapshot {
+            partition: inner.partition.clone(),
+            database: inner.database.clone(),
+            table: inner.table.clone(),
+            segments: load(&inner.segments),
+            active_segment_bytes: load(&inner.active_segment_bytes),
+            entries_written: load(&inner.entries_written),
+            bytes_written: load(&inner.bytes_written),
+            entries_read: load(&inner.entries_read),
+            bytes_read: load(&inner.bytes_read),
+            fsyncs: load(&inner.fsyncs),
+            fsync_p50_micros: self.fsync_percentile(0.5).as_micros() as u64,
+            fsync_p99_micros: self.fsync_percentile(0.99).as_micros() as u64,
+            fsync_max_micros: self.fsync_percentile(1.0).as_micros() as u64,
+            compactions: load(&inner.compactions),
+            compacted_segments: load(&inner.compacted_segments),
+            compaction_progress: if total == 0 { 1.0 } else { load(&inner.compaction_done) as f64 / total as f64 },
+            purges: load(&inner.purges),
+            purged_segments: load(&inner.purged_segments),
+            purged_bytes: load(&inner.purged_bytes),
+            offloaded_segments: load(&inner.offloaded_segments),
+        }
+    }
+
+    /// 多个存储的指标, Prometheus 文本格式, 以 database/table 标签区分, 非默认分区另有 partition 标签
+    pub fn render(metrics: &[StorageMetrics]) -> String {
+        let snapshots: Vec<StorageMetricsSnapshot> = metrics.iter().map(|m| m.snapshot()).collect();
+
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: fn(&StorageMetricsSnapshot) -> String| {
+            let _ = write!(out, "# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind);
+            for s in &snapshots {
+                let partition = if s.partition.is_empty() { String::new() } else { format!("partition=\"{}\",", s.partition) };
+                let _ = writeln!(out, "{}{{{}database=\"{}\",table=\"{}\"}} {}", name, partition, s.database, s.table, value(s));
+            }
+        };
+        metric("relay_log_segments", "gauge", "Local segments of the relay log.", |s| s.segments.to_string());
+        metric("relay_log_active_segment_bytes", "gauge", "Size of the segment being written.", |s| s.active_segment_bytes.to_string());
+        metric("relay_log_written_entries_total", "counter", "Entries appended.", |s| s.entries_written.to_string());
+        metric("relay_log_written_bytes_total", "counter", "Bytes appended.", |s| s.bytes_written.to_string());
+        metric("relay_log_read_entries_total", "counter", "Entries read from segment files.", |s| s.entries_read.to_string());
+        metric("relay_log_read_bytes_total", "counter", "Bytes read from segment files.", |s| s.bytes_read.to_string());
+        metric("relay_log_fsyncs_total", "counter", "Segment flushes.", |s| s.fsyncs.to_string());
+        metric("relay_log_fsync_p50_microseconds", "gauge", "Median of recent segment flush latencies.", |s| s.fsync_p50_micros.to_string());
+        metric("relay_log_fsync_p99_microseconds", "gauge", "99th percentile of recent segment flush latencies.", |s| s.fsync_p99_micros.to_string());
+        metric("relay_log_compactions_total", "counter", "Compactions that rewrote segments.", |s| s.compactions.to_string());
+        metric("relay_log_compacted_segments_total", "counter", "Segments rewritten by compaction.", |s| s.compacted_segments.to_string());
+        metric("relay_log_compaction_progress", "gauge", "Progress of the running compaction, 1 when idle.", |s| format!("{:.2}", s.compaction_progress));
+        metric("relay_log_purges_total", "counter", "Purge runs.", |s| s.purges.to_string());
+        metric("relay_log_purged_segments_total", "counter", "Segments deleted by retention.", |s| s.purged_segments.to_string());
+        metric("relay_log_purged_bytes_total", "counter", "Bytes deleted by retention.", |s| s.purged_bytes.to_string());
+        metric("relay_log_offloaded_segments_total", "counter", "Segments offloaded to the object store.", |s| s.offloaded_segments.to_string());
+
+        out
+    }
+}
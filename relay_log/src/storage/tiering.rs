@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+
+use crate::storage::storage_config::StorageConfig;
+
+/// 本地清单文件, 记录已上传到对象存储的segment
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// 冷segment的对象存储, S3/GCS 等通过实现该 trait 接入。
+///
+/// 上传与下载都以整个文件为单位, 实现方需要保证 put 完成后对象完整可读
+pub trait ObjectStore: Debug + Send + Sync {
+    fn name(&self) -> String;
+
+    /// 上传本地文件到 key
+    fn put(&self, key: &str, local_path: &Path) -> CResult<()>;
+
+    /// 下载 key 到本地文件
+    fn get(&self, key: &str, local_path: &Path) -> CResult<()>;
+
+    /// 删除 key, 不存在时忽略
+    fn delete(&self, key: &str) -> CResult<()>;
+}
+
+/// 以文件夹作为对象存储, 可用于挂载的网络存储或测试
+#[derive(Debug, Clone)]
+pub struct FileObjectStore {
+    root: PathBuf,
+}
+
+impl FileObjectStore {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+        }
+    }
+
+    /// 先写临时文件再替换, 中途失败不会留下不完整的文件
+    fn copy(from: &Path, to: &Path) -> CResult<()> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = to.with_extension("tmp");
+        fs::copy(from, &tmp)?;
+        File::open(&tmp)?.sync_all()?;
+        fs::rename(&tmp, to)?;
+        Ok(())
+    }
+}
+
+impl ObjectStore for FileObjectStore {
+    fn name(&self) -> String {
+        String::from("FileObjectStore")
+    }
+
+    fn put(&self, key: &str, local_path: &Path) -> CResult<()> {
+        Self::copy(local_path, &self.root.join(key))
+    }
+
+    fn get(&self, key: &str, local_path: &Path) -> CResult<()> {
+        Self::copy(&self.root.join(key), local_path)
+    }
+
+    fn delete(&self, key: &str) -> CResult<()> {
+        match fs::remove_file(self.root.join(key)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(ReError::IoError(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 分层策略: 写满超过 cold_after_millisecond 的segment上传到对象存储并删除本地文件, 0 表示不分层
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TieringPolicy {
+    pub cold_after_millisecond: u64,
+}
+
+impl TieringPolicy {
+    pub fn from_config(config: &StorageConfig) -> Self {
+        Self {
+            cold_after_millisecond: if config.object_store().is_some() { *config.tiering_millisecond() } else { 0 },
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.cold_after_millisecond > 0
+    }
+}
+
+/// 一次分层的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TierStats {
+    pub segments: usize,
+    pub bytes: u64,
+}
+
+/// 已上传到对象存储的segment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TieredSegment {
+    // segment文件名: rlog-{version}-{id}-{index}.log
+    pub name: String,
+    // segment文件的对象 key
+    pub key: String,
+    // 索引文件的对象 key
+    pub index_key: Option<String>,
+    pub first_index: u64,
+    pub last_index: u64,
+    // 事件时间范围, 用于按时间扫描时判断是否需要取回
+    pub time_range: Option<(u32, u32)>,
+    pub size: u64,
+}
+
+/// 本地清单: 目标表日志文件夹下的 manifest.json, first index -> 已上传的segment。
+///
+/// 清单先于删除本地文件写入, 同一个segment可能同时存在于本地与对象存储, 此时优先读取本地文件
+#[derive(Debug)]
+pub struct TierManifest {
+    path: PathBuf,
+    segments: BTreeMap<u64, TieredSegment>,
+}
+
+impl TierManifest {
+    /// 加载清单, 文件不存在时为空
+    pub fn load(segment_dir: &str) -> CResult<Self> {
+        let path = PathBuf::from(segment_dir).join(MANIFEST_FILE);
+        let segments = if path.exists() {
+            let list: Vec<TieredSegment> = serde_json::from_slice(&fs::read(&path)?)
+                .map_err(|e| ReError::Error(format!("read tier manifest {:?} err: {}", path, e)))?;
+            list.into_iter().map(|s| (s.first_index, s)).collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path,
+            segments,
+        })
+    }
+
+    /// 写入临时文件后替换
+    fn save(&self) -> CResult<()> {
+        let list: Vec<&TieredSegment> = self.segments.values().collect();
+        let bytes = serde_json::to_vec_pretty(&list).map_err(|e| ReError::Error(e.to_string()))?;
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut f = File::create(&tmp)?;
+            f.write_all(&bytes)?;
+            f.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, segment: TieredSegment) -> CResult<()> {
+        self.segments.insert(segment.first_index, segment);
+        self.save()
+    }
+
+    pub fn remove(&mut self, first_index: u64) -> CResult<Option<TieredSegment>> {
+        let removed = self.segments.remove(&first_index);
+        if removed.is_some() {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    pub fn get(&self, first_index: u64) -> Option<&TieredSegment> {
+        self.segments.get(&first_index)
+    }
+
+    /// index 所在的segment
+    pub fn find(&self, index: u64) -> Option<&TieredSegment> {
+        self.segments.range(..=index)
+            .next_back()
+            .map(|(_, s)| s)
+            .filter(|s| index <= s.last_index)
+    }
+
+    /// 时间范围与 [from_ts, to_ts] 相交的segment, 没有时间范围的segment都可能相交
+    pub fn overlapping(&self, from_ts: u32, to_ts: u32) -> Vec<&TieredSegment> {
+        self.segments.values()
+            .filter(|s| s.time_range.map_or(true, |(min_ts, max_ts)| max_ts >= from_ts && min_ts <= to_ts))
+            .collect()
+    }
+
+    /// 最早的已上传segment的 first index
+    pub fn first_index(&self) -> Option<u64> {
+        self.segments.keys().next().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
@@ -17,4 +17,20 @@ fn test_read_events() {
         idx += 1;
     }
     assert_eq!(idx, 3);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_read_chunk() {
+    let input = include_bytes!("../../../events/5.7/15_format_desc/log.bin");
+
+    let (mut reader, _) = BytesBinlogReader::new_without_context(false).unwrap();
+
+    // 按 7 字节拆分, magic 与事件都会被截断
+    let mut events = Vec::new();
+    for chunk in input.chunks(7) {
+        events.extend(reader.read_chunk(chunk).unwrap());
+    }
+    assert_eq!(events.len(), 3);
+    assert!(reader.get_source_bytes().is_empty());
+    assert_eq!(events.last().unwrap().0.get_log_pos() as usize, input.len());
+}
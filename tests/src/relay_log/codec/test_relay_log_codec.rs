@@ -1,9 +1,10 @@
 use tracing::info;
 use common::log::tracing_factory::TracingFactory;
+use common::schema::data_type::Value;
 use relay_log::codec::binary_codec::BinaryCodec;
 use relay_log::codec::binary_codec::CodecStyle::LittleVar;
 use relay_log::codec::codec::Codec;
-use relay_log::relay_log::RelayLog;
+use relay_log::relay_log::{RelayColumnInfo, RelayLog, RelayRowData};
 
 #[test]
 fn test_binary_codec() {
@@ -22,4 +23,31 @@ fn test_binary_codec() {
 
     let s2 = codec.binary_deserialize::<RelayLog>(&LittleVar, &bytes).unwrap();
     info!("反序列化：{:?}", s2);
-}
\ No newline at end of file
+}
+
+/// 新增 Value / DstColumnType 变体之前写入的中继日志仍按原来的类型解码
+#[test]
+fn test_decode_old_layout() {
+    let codec = BinaryCodec::new();
+
+    // [Long(7), String("a"), Float(1.5), Date(3)], 变体序号 5 / 6 / 8 / 11
+    let bytes = [4, 5, 14, 6, 1, b'a', 8, 0, 0, 0xC0, 0x3F, 11, 6];
+    let row = codec.binary_deserialize::<RelayRowData>(&LittleVar, &bytes).unwrap();
+    let values = row.values();
+    assert_eq!(values.len(), 4);
+    assert!(matches!(values[0], Value::Long(7)));
+    assert!(matches!(&values[1], Value::String(v) if v == "a"));
+    assert!(matches!(values[2], Value::Float(v) if v == 1.5));
+    assert!(matches!(values[3], Value::Date(3)));
+
+    // DstColumnType::Map 的变体序号为 32
+    let bytes = [32, 1, b'm'];
+    let column = codec.binary_deserialize::<RelayColumnInfo>(&LittleVar, &bytes).unwrap();
+    assert_eq!(i32::from(*column.column_type()), 50);
+    assert_eq!(column.column_name(), "m");
+
+    // 新增的变体追加在末尾
+    let bytes = codec.binary_serialize(&LittleVar, &Value::UnsignedLong(u64::MAX)).unwrap();
+    assert_eq!(bytes[0], 18);
+    assert!(matches!(codec.binary_deserialize::<Value>(&LittleVar, &bytes).unwrap(), Value::UnsignedLong(u64::MAX)));
+}
@@ -1,9 +1,19 @@
 #[cfg(test)]
 mod test_relay_log;
 #[cfg(test)]
+mod test_relay_log_apply;
+#[cfg(test)]
 mod test_relay_log_server;
 #[cfg(test)]
 mod test_relay_log_server_machine;
+#[cfg(test)]
+mod test_replay_progress;
+#[cfg(test)]
+mod test_snapshot;
+#[cfg(test)]
+mod test_sql_builder;
+#[cfg(test)]
+mod test_transform;
 
 mod storage;
 mod codec;
\ No newline at end of file
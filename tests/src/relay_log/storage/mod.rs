@@ -1,2 +1,59 @@
 #[cfg(test)]
-mod test_relay_log_storage;
\ No newline at end of file
+use std::fs;
+#[cfg(test)]
+use std::path::PathBuf;
+#[cfg(test)]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(test)]
+use relay_log::relay_log::RelayLog;
+
+#[cfg(test)]
+mod test_relay_log_storage;
+#[cfg(test)]
+mod test_segment_compression;
+#[cfg(test)]
+mod test_segment_index;
+#[cfg(test)]
+mod test_retention;
+#[cfg(test)]
+mod test_compactor;
+#[cfg(test)]
+mod test_torn_write;
+#[cfg(test)]
+mod test_tail;
+#[cfg(test)]
+mod test_tiering;
+#[cfg(test)]
+mod test_storage_metrics;
+#[cfg(test)]
+mod test_durability;
+#[cfg(test)]
+mod test_mmap_read;
+#[cfg(test)]
+mod test_archive;
+#[cfg(test)]
+mod test_partition;
+#[cfg(test)]
+mod test_storage_backend;
+#[cfg(test)]
+mod test_segment_version;
+
+/// 临时目录下新建的测试目录, 名称中包含进程 id 与时间, 并行运行的测试互不影响
+#[cfg(test)]
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let dir = std::env::temp_dir().join(format!("{}_{}_{}", name, std::process::id(), nanos));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// db1.t1 的日志, event_log_pos 为 log_pos
+#[cfg(test)]
+pub(crate) fn relay_log(log_pos: u64) -> RelayLog {
+    let mut relay_log = RelayLog::default();
+    relay_log.set_database_name("db1".to_string());
+    relay_log.set_table_name("t1".to_string());
+    relay_log.set_event_log_pos(log_pos);
+    relay_log
+}
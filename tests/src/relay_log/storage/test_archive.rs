@@ -0,0 +1,79 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use relay_log::relay_log::{RelayCommand, RelayLog};
+use relay_log::storage::archive::{ArchiveManifest, SchemaChange};
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{self, temp_dir};
+
+fn relay_log(i: u64, command: RelayCommand) -> RelayLog {
+    let mut relay_log = storage::relay_log(i);
+    relay_log.set_relay_command(command);
+    relay_log
+}
+
+fn storage_config(dir: &PathBuf) -> StorageConfig {
+    fs::create_dir_all(dir).unwrap();
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config
+}
+
+#[test]
+pub fn test_export_import() {
+    let dir = temp_dir("relay_log_archive");
+    let source_config = storage_config(&dir.join("source"));
+    let replica_config = storage_config(&dir.join("replica"));
+    let archive_dir = dir.join("archive");
+    let archive_dir = archive_dir.to_str().unwrap();
+
+    let mut source = RelayLogStorage::new(&source_config, "db1".to_string(), "t1".to_string()).unwrap();
+    source.append_relay_log(relay_log(1, RelayCommand::CreateTable)).unwrap();
+    for i in 2..=30 {
+        let command = if i == 12 { RelayCommand::AlterTable } else { RelayCommand::Insert(vec![]) };
+        source.append_relay_log(relay_log(i, command)).unwrap();
+    }
+
+    assert!(source.export(5, 0, archive_dir).is_err());
+    assert!(source.export(5, 31, archive_dir).is_err());
+    let manifest = source.export(5, 25, archive_dir).unwrap();
+    assert_eq!((manifest.database.as_str(), manifest.table.as_str()), ("db1", "t1"));
+    assert_eq!((manifest.first_index, manifest.last_index, manifest.entries()), (5, 25, 21));
+    assert_eq!(ArchiveManifest::load(archive_dir).unwrap(), manifest);
+    // 同一个文件夹不能重复导出
+    assert!(source.export(5, 25, archive_dir).is_err());
+
+    // 范围之前与范围内的 DDL
+    let schema_history = SchemaChange::load(archive_dir).unwrap();
+    assert_eq!(schema_history.iter().map(|c| c.index).collect::<Vec<u64>>(), vec![1, 12]);
+
+    let (mut replica, imported) = RelayLogStorage::import(&replica_config, archive_dir).unwrap();
+    assert_eq!(imported, manifest);
+    assert_eq!(replica.index_range().unwrap(), Some((5, 25)));
+    for i in 5..=25 {
+        assert_eq!(*replica.get_entry(i).unwrap().relay_log().event_log_pos(), i);
+    }
+    // 导入后继续追加
+    replica.append_relay_log(relay_log(26, RelayCommand::Insert(vec![]))).unwrap();
+    assert_eq!(*replica.get_entry(26).unwrap().relay_log().event_log_pos(), 26);
+    drop(replica);
+
+    // 目标表已有日志时不能导入
+    assert!(RelayLogStorage::import(&replica_config, archive_dir).is_err());
+
+    // 损坏的归档不能导入
+    let other_config = storage_config(&dir.join("other"));
+    let segment = PathBuf::from(archive_dir).join(&manifest.segments[0].name);
+    let mut f = OpenOptions::new().append(true).open(&segment).unwrap();
+    f.write_all(&[0]).unwrap();
+    drop(f);
+    assert!(RelayLogStorage::import(&other_config, archive_dir).is_err());
+
+    drop(source);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use common::schema::data_type::{DstColumnType, Value};
+use relay_log::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+use relay_log::storage::compactor::Compactor;
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::temp_dir;
+
+fn row(id: i32, name: &str) -> RelayRowData {
+    let mut row = RelayRowData::default();
+    row.set_values(vec![Value::Int(id), Value::String(name.to_string())]);
+    row
+}
+
+fn relay_log(command: RelayCommand) -> RelayLog {
+    let mut id = RelayColumnInfo::default();
+    id.set_column_name("id".to_string());
+    id.set_column_type(DstColumnType::Int);
+    let mut name = RelayColumnInfo::default();
+    name.set_column_name("name".to_string());
+    name.set_column_type(DstColumnType::String);
+
+    let mut relay_log = RelayLog::default();
+    relay_log.set_database_name("db1".to_string());
+    relay_log.set_table_name("t1".to_string());
+    relay_log.set_columns(vec![id, name]);
+    relay_log.set_relay_command(command);
+    relay_log
+}
+
+/// 按顺序重放 [first, last] 中存在的entry, 返回 id -> name
+fn replay(log_storage: &mut RelayLogStorage, first: u64, last: u64) -> BTreeMap<String, String> {
+    let mut table = BTreeMap::new();
+    let key = |r: &RelayRowData| format!("{:?}", r.values()[0]);
+    let name = |r: &RelayRowData| format!("{:?}", r.values()[1]);
+    for index in first..=last {
+        let entry = match log_storage.get_entry(index) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        match entry.relay_log().relay_command() {
+            RelayCommand::Insert(rows) => rows.iter().for_each(|r| { table.insert(key(r), name(r)); }),
+            RelayCommand::Delete(rows) => rows.iter().for_each(|r| { table.remove(&key(r)); }),
+            RelayCommand::Update(rows) => rows.iter().for_each(|(b, a)| {
+                table.remove(&key(b));
+                table.insert(key(a), name(a));
+            }),
+            _ => {}
+        }
+    }
+    table
+}
+
+#[test]
+pub fn test_compact_by_primary_key() {
+    let dir = temp_dir("relay_log_compact");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    // index 1~5
+    for id in 1..=5 {
+        log_storage.append_relay_log(relay_log(RelayCommand::Insert(vec![row(id, "v0")]))).unwrap();
+    }
+    // index 6~15
+    for k in 1..=10 {
+        let update = (row(1, &format!("v{}", k - 1)), row(1, &format!("v{}", k)));
+        log_storage.append_relay_log(relay_log(RelayCommand::Update(vec![update]))).unwrap();
+    }
+    // index 16~17: 删除 id 2, 主键 3 修改为 30
+    log_storage.append_relay_log(relay_log(RelayCommand::Delete(vec![row(2, "v0")]))).unwrap();
+    log_storage.append_relay_log(relay_log(RelayCommand::Update(vec![(row(3, "v0"), row(30, "v0"))]))).unwrap();
+    // index 18~25
+    for id in 101..=108 {
+        log_storage.append_relay_log(relay_log(RelayCommand::Insert(vec![row(id, "v0")]))).unwrap();
+    }
+    let expected = replay(&mut log_storage, 1, 25);
+
+    let compactor = Compactor::new(vec!["id".to_string()]);
+    let stats = log_storage.compact(&compactor).unwrap();
+    assert_eq!(stats.segments, 2);
+    assert_eq!(stats.entries_before, 20);
+    // 7 个行镜像 + 2 个墓碑
+    assert_eq!(stats.entries_after, 9);
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 25)));
+    assert!(log_storage.get_entry(10).is_err());
+    assert_eq!(replay(&mut log_storage, 1, 25), expected);
+
+    // 重新加载后读取压缩后的segment
+    drop(log_storage);
+    let segment_dir = SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap();
+    assert!(!PathBuf::from(&segment_dir).join(".compact").exists());
+    assert!(!PathBuf::from(&segment_dir).join("rlog-2-2-11.log").exists());
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(replay(&mut log_storage, 1, 25), expected);
+
+    // 没有可压缩的内容时不再重写
+    assert_eq!(log_storage.compact(&compactor).unwrap().segments, 0);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_compact_protects_consumers() {
+    let dir = temp_dir("relay_log_compact_consumer");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for k in 1..=25 {
+        let update = (row(1, &format!("v{}", k - 1)), row(1, &format!("v{}", k)));
+        log_storage.append_relay_log(relay_log(RelayCommand::Update(vec![update]))).unwrap();
+    }
+
+    log_storage.consumer_offsets().commit("replayer", 5);
+    assert_eq!(log_storage.compact(&Compactor::new(vec!["id".to_string()])).unwrap().segments, 0);
+
+    log_storage.consumer_offsets().commit("replayer", 15);
+    let stats = log_storage.compact(&Compactor::new(vec!["id".to_string()])).unwrap();
+    assert_eq!((stats.segments, stats.entries_after), (1, 1));
+    assert!(log_storage.get_entry(11).is_ok());
+
+    // 主键列不存在
+    log_storage.consumer_offsets().remove("replayer");
+    assert!(log_storage.compact(&Compactor::new(vec!["uid".to_string()])).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_compact_keeps_markers() {
+    let marker = |command: RelayCommand| {
+        let mut log = RelayLog::default();
+        log.set_relay_command(command);
+        log
+    };
+    let logs = vec![
+        marker(RelayCommand::Rotate("mysql-bin.000001".to_string())),
+        marker(RelayCommand::Gtid("uuid:1".to_string())),
+        relay_log(RelayCommand::Insert(vec![row(1, "v0")])),
+        marker(RelayCommand::Commit(1)),
+        marker(RelayCommand::Gtid("uuid:2".to_string())),
+        relay_log(RelayCommand::Update(vec![(row(1, "v0"), row(1, "v1"))])),
+        marker(RelayCommand::Commit(2)),
+    ];
+
+    let compacted = Compactor::new(vec!["id".to_string()]).compact_logs(logs.into_iter(), 10).unwrap().unwrap();
+    let commands: Vec<String> = compacted.iter().map(|l| format!("{:?}", l.relay_command())).collect();
+    assert_eq!(commands.len(), 4);
+    // 行镜像以第一条行日志为模板
+    assert_eq!(compacted[0].table_name(), "t1");
+    assert_eq!(commands[1], format!("{:?}", RelayCommand::Rotate("mysql-bin.000001".to_string())));
+    assert_eq!(commands[2], format!("{:?}", RelayCommand::Gtid("uuid:2".to_string())));
+    assert_eq!(commands[3], format!("{:?}", RelayCommand::Commit(2)));
+}
+
+#[test]
+pub fn test_compact_keeps_alter_table() {
+    let logs = vec![
+        relay_log(RelayCommand::CreateTable),
+        relay_log(RelayCommand::Insert(vec![row(1, "v0")])),
+        relay_log(RelayCommand::Update(vec![(row(1, "v0"), row(1, "v1"))])),
+        relay_log(RelayCommand::AlterTable),
+        relay_log(RelayCommand::Insert(vec![row(2, "v0")])),
+        relay_log(RelayCommand::Update(vec![(row(2, "v0"), row(2, "v1"))])),
+    ];
+
+    let compacted = Compactor::new(vec!["id".to_string()]).compact_logs(logs.into_iter(), 10).unwrap().unwrap();
+    let commands: Vec<String> = compacted.iter().map(|l| format!("{:?}", l.relay_command())).collect();
+    // ALTER 之前与之后的行分别压缩, ALTER 保留在原来的位置
+    assert_eq!(commands, vec![
+        format!("{:?}", RelayCommand::CreateTable),
+        format!("{:?}", RelayCommand::Insert(vec![row(1, "v1")])),
+        format!("{:?}", RelayCommand::AlterTable),
+        format!("{:?}", RelayCommand::Insert(vec![row(2, "v1")])),
+    ]);
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use relay_log::storage::durability::FsyncPolicy;
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment::RecoveryStats;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+#[test]
+pub fn test_fsync_policy_parse() {
+    assert_eq!(FsyncPolicy::try_from("").unwrap(), FsyncPolicy::OsBuffered);
+    assert_eq!(FsyncPolicy::try_from("os_buffered").unwrap(), FsyncPolicy::OsBuffered);
+    assert_eq!(FsyncPolicy::try_from("per-entry").unwrap(), FsyncPolicy::PerEntry);
+    assert_eq!(FsyncPolicy::try_from("PER_BATCH").unwrap(), FsyncPolicy::PerBatch);
+    assert_eq!(FsyncPolicy::try_from("interval").unwrap(), FsyncPolicy::Interval);
+    assert!(FsyncPolicy::try_from("always").is_err());
+}
+
+#[test]
+pub fn test_group_commit() {
+    let dir = temp_dir("relay_log_group_commit");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_fsync_policy(FsyncPolicy::PerBatch);
+    storage_config.set_group_commit_entries(3);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    let metrics = log_storage.metrics();
+    for i in 1..=7 {
+        log_storage.append_relay_log(relay_log(i)).unwrap();
+    }
+    // 每3个entry提交一个批次
+    assert_eq!(metrics.snapshot().fsyncs, 2);
+    // 第三个批次未提交时进程崩溃
+    std::mem::forget(log_storage);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats { entries: 1, bytes: 0 });
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 6)));
+
+    // 显式 sync 提交未满的批次
+    log_storage.append_relay_log(relay_log(7)).unwrap();
+    log_storage.sync().unwrap();
+    std::mem::forget(log_storage);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats::default());
+    assert_eq!(*log_storage.get_entry(7).unwrap().relay_log().event_log_pos(), 7);
+    drop(log_storage);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_fsync_per_entry() {
+    let dir = temp_dir("relay_log_fsync_entry");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_fsync_policy(FsyncPolicy::PerEntry);
+    storage_config.set_group_commit_entries(100);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in 1..=5 {
+        log_storage.append_relay_log(relay_log(i)).unwrap();
+    }
+    assert_eq!(log_storage.metrics().snapshot().fsyncs, 5);
+    std::mem::forget(log_storage);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats::default());
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 5)));
+    drop(log_storage);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_group_commit_idle() {
+    let dir = temp_dir("relay_log_group_commit_idle");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_fsync_policy(FsyncPolicy::PerBatch);
+    storage_config.set_group_commit_entries(100);
+    storage_config.set_group_commit_millisecond(50);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    let metrics = log_storage.metrics();
+    log_storage.append_relay_log(relay_log(1)).unwrap();
+    log_storage.append_relay_log(relay_log(2)).unwrap();
+    log_storage.tick().unwrap();
+    assert_eq!(metrics.snapshot().fsyncs, 0);
+
+    // 之后不再追加, 批次等待超时后由 tick 提交
+    thread::sleep(Duration::from_millis(80));
+    log_storage.tick().unwrap();
+    assert_eq!(metrics.snapshot().fsyncs, 1);
+    std::mem::forget(log_storage);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats::default());
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 2)));
+    drop(log_storage);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_fsync_interval_idle() {
+    let dir = temp_dir("relay_log_fsync_interval");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_fsync_policy(FsyncPolicy::Interval);
+    storage_config.set_fsync_interval_millisecond(50);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    let metrics = log_storage.metrics();
+    log_storage.append_relay_log(relay_log(1)).unwrap();
+    log_storage.flush().unwrap();
+    // 距上次 fsync 未超过间隔, 只提交
+    assert_eq!(metrics.snapshot().fsyncs, 0);
+
+    // 之后没有新的批次, 超过间隔后由读取时的检查 fsync
+    thread::sleep(Duration::from_millis(80));
+    assert_eq!(*log_storage.get_entry(1).unwrap().relay_log().event_log_pos(), 1);
+    assert_eq!(metrics.snapshot().fsyncs, 1);
+    log_storage.tick().unwrap();
+    assert_eq!(metrics.snapshot().fsyncs, 1);
+    drop(log_storage);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_os_buffered_no_fsync() {
+    let dir = temp_dir("relay_log_os_buffered");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_group_commit_entries(1);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in 1..=3 {
+        log_storage.append_relay_log(relay_log(i)).unwrap();
+    }
+    log_storage.flush().unwrap();
+    // 只提交, 不 fsync
+    assert_eq!(log_storage.metrics().snapshot().fsyncs, 0);
+    drop(log_storage);
+
+    fs::remove_dir_all(dir).unwrap();
+}
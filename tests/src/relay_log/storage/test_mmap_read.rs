@@ -0,0 +1,57 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+#[test]
+pub fn test_mmap_sealed_segments() {
+    let dir = temp_dir("relay_log_mmap");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config.set_entry_buffer_num(2);
+
+    {
+        let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+        for i in 1..=25 {
+            log_storage.append_relay_log(relay_log(i)).unwrap();
+        }
+        // 前两个segment已封存, 通过映射读取; 第三个segment仍在写入
+        for i in (1..=25).rev() {
+            assert_eq!(*log_storage.get_entry(i).unwrap().relay_log().event_log_pos(), i);
+        }
+        log_storage.append_relay_log(relay_log(26)).unwrap();
+        assert_eq!(*log_storage.get_entry(26).unwrap().relay_log().event_log_pos(), 26);
+    }
+
+    // 重新打开后写满的segment同样通过映射读取
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    let scanned: Vec<u64> = (1..=26)
+        .map(|i| *log_storage.get_entry(i).unwrap().relay_log().event_log_pos())
+        .collect();
+    assert_eq!(scanned, (1..=26).collect::<Vec<u64>>());
+    drop(log_storage);
+
+    // 映射中的日志内容同样校验crc32
+    let segment_dir = SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap();
+    let sealed = PathBuf::from(&segment_dir).join("rlog-2-1-1.log");
+    let len = fs::metadata(&sealed).unwrap().len();
+    let mut f = OpenOptions::new().write(true).open(&sealed).unwrap();
+    // 最后一个提交标记之前的entry内容
+    f.seek(SeekFrom::Start(len - 21)).unwrap();
+    f.write_all(&[0xff]).unwrap();
+    drop(f);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert!(log_storage.get_entry(10).is_err());
+    assert_eq!(*log_storage.get_entry(9).unwrap().relay_log().event_log_pos(), 9);
+    drop(log_storage);
+
+    fs::remove_dir_all(dir).unwrap();
+}
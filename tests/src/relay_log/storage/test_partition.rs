@@ -0,0 +1,66 @@
+use std::fs;
+
+use relay_log::storage::partition::RelayLogPartitions;
+use relay_log::storage::storage_config::StorageConfig;
+use relay_log::storage::storage_metrics::StorageMetrics;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+#[test]
+pub fn test_partitions() {
+    let dir = temp_dir("relay_log_partition");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+
+    let mut source_b = storage_config.clone();
+    source_b.set_retention_bytes(1);
+    let mut partitions = RelayLogPartitions::new(storage_config)
+        .with_partition_config("source_b", source_b).unwrap();
+    assert!(partitions.open("a/b", "db1", "t1").is_err());
+    assert!(partitions.open("a#b", "db1", "t1").is_err());
+
+    // 同名表在不同分区中是独立的日志
+    let mut default = partitions.open("", "db1", "t1").unwrap();
+    let mut a = partitions.open("source_a", "db1", "t1").unwrap();
+    let mut b = partitions.open("source_b", "db1", "t1").unwrap();
+    for i in 1..=5 {
+        default.append_relay_log(relay_log(i)).unwrap();
+    }
+    for i in 1..=25 {
+        a.append_relay_log(relay_log(i)).unwrap();
+        b.append_relay_log(relay_log(i)).unwrap();
+    }
+    assert_eq!(default.index_range().unwrap(), Some((1, 5)));
+    assert_eq!(a.index_range().unwrap(), Some((1, 25)));
+    assert!(dir.join("db1#t1").is_dir());
+    assert!(dir.join("source_a").join("db1#t1").is_dir());
+
+    // 消费者offset与保留策略按分区生效
+    a.consumer_offsets().commit("apply", 1);
+    b.consumer_offsets().commit("apply", 21);
+    assert_eq!(a.purge().unwrap().segments, 0);
+    assert_eq!(b.purge().unwrap().segments, 2);
+    assert_eq!(a.index_range().unwrap(), Some((1, 25)));
+    assert_eq!(b.index_range().unwrap(), Some((21, 25)));
+
+    // 指标按分区区分
+    assert_eq!(a.metrics().snapshot().entries_written, 25);
+    assert_eq!(default.metrics().snapshot().entries_written, 5);
+    let text = StorageMetrics::render(&[default.metrics(), a.metrics()]);
+    assert!(text.contains("relay_log_written_entries_total{database=\"db1\",table=\"t1\"} 5"));
+    assert!(text.contains("relay_log_written_entries_total{partition=\"source_a\",database=\"db1\",table=\"t1\"} 25"));
+
+    assert_eq!(partitions.partitions().unwrap(), vec!["source_a".to_string(), "source_b".to_string()]);
+    assert_eq!(partitions.tables("source_a").unwrap(), vec![("db1".to_string(), "t1".to_string())]);
+    assert_eq!(partitions.tables("").unwrap(), vec![("db1".to_string(), "t1".to_string())]);
+
+    drop(a);
+    partitions.remove("source_a").unwrap();
+    assert!(!dir.join("source_a").exists());
+    assert_eq!(partitions.partitions().unwrap(), vec!["source_b".to_string()]);
+
+    drop(default);
+    drop(b);
+    fs::remove_dir_all(dir).unwrap();
+}
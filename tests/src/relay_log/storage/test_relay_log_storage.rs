@@ -1,3 +1,5 @@
+use std::fs;
+
 use tracing::info;
 use common::log::tracing_factory::TracingFactory;
 use relay_log::relay_log::RelayLog;
@@ -6,6 +8,8 @@ use relay_log::storage::segment_file::SegmentFile;
 use relay_log::storage::segment_manager::SegmentManager;
 use relay_log::storage::storage_config::StorageConfig;
 
+use crate::relay_log::storage::temp_dir;
+
 #[test]
 pub fn test_segment_file() {
     TracingFactory::init_log(true);
@@ -16,6 +20,21 @@ pub fn test_segment_file() {
     info!("index: {:?}", segment_file.index());
 }
 
+#[test]
+pub fn test_segment_file_v2() {
+    let dir = temp_dir("relay_log_segment_file");
+    let path = dir.join("rlog-2-3-21.log");
+    fs::write(&path, b"").unwrap();
+
+    let segment_file = SegmentFile::from_path(path.to_str().unwrap()).unwrap();
+    assert!(SegmentFile::is_segment_file("rlog-2-3-21.log").unwrap());
+    assert_eq!(segment_file.version().unwrap(), 2);
+    assert_eq!(segment_file.segment_id().unwrap(), 3);
+    assert_eq!(segment_file.index().unwrap(), 21);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
 #[test]
 pub fn test_segment_manager() {
     TracingFactory::init_log(true);
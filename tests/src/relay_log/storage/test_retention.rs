@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+fn storage_config(dir: &PathBuf) -> StorageConfig {
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    // 测试中手动清理
+    storage_config.set_purge_interval_millisecond(60 * 60 * 1000);
+    storage_config
+}
+
+#[test]
+pub fn test_purge_by_size() {
+    let dir = temp_dir("relay_log_retention_size");
+    let mut storage_config = storage_config(&dir);
+    storage_config.set_retention_bytes(1);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in 1..=35 {
+        log_storage.append_relay_log(relay_log(i)).unwrap();
+    }
+    // 首次追加时已清理一次, 此时只有一个segment
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 35)));
+
+    // 消费者停在 index 15, 包含它的segment及之后的segment都保留
+    let offsets = log_storage.consumer_offsets();
+    offsets.commit("replayer", 15);
+    let stats = log_storage.purge().unwrap();
+    assert_eq!(stats.segments, 1);
+    assert_eq!(log_storage.index_range().unwrap(), Some((11, 35)));
+
+    // 消费者退出后清理到当前写入的segment
+    offsets.remove("replayer");
+    assert_eq!(log_storage.purge().unwrap().segments, 2);
+    assert_eq!(log_storage.index_range().unwrap(), Some((31, 35)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_purge_by_age() {
+    let dir = temp_dir("relay_log_retention_age");
+    let mut storage_config = storage_config(&dir);
+    storage_config.set_retention_millisecond(200);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in 1..=15 {
+        log_storage.append_relay_log(relay_log(i)).unwrap();
+    }
+    assert_eq!(log_storage.purge().unwrap().segments, 0);
+
+    thread::sleep(Duration::from_millis(300));
+    let stats = log_storage.purge().unwrap();
+    assert_eq!(stats.segments, 1);
+    assert!(stats.bytes > 0);
+    assert_eq!(log_storage.index_range().unwrap(), Some((11, 15)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
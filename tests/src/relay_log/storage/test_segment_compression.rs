@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::PathBuf;
+
+use relay_log::relay_log::RelayLog;
+use relay_log::storage::compression::CompressionCodec;
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{self, temp_dir};
+
+fn relay_log(i: u64) -> RelayLog {
+    let mut relay_log = storage::relay_log(i);
+    relay_log.set_event_name(format!("binlog_{}", i));
+    relay_log
+}
+
+#[test]
+pub fn test_codec() {
+    let data = "relay log ".repeat(100).into_bytes();
+    let compressed = CompressionCodec::Zstd.compress(3, &data).unwrap();
+    assert!(compressed.len() < data.len());
+    assert_eq!(CompressionCodec::Zstd.decompress(&compressed, data.len()).unwrap(), data);
+
+    assert_eq!(CompressionCodec::try_from("ZSTD").unwrap(), CompressionCodec::Zstd);
+    assert!(CompressionCodec::try_from("lz4").is_err());
+}
+
+#[test]
+pub fn test_compress_on_roll() {
+    let dir = temp_dir("relay_log_zstd");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config.set_compression(CompressionCodec::Zstd);
+
+    {
+        let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+        for i in 1..=25 {
+            log_storage.append_relay_log(relay_log(i)).unwrap();
+        }
+        // 已压缩的 segment 与写入中的 segment 都可以读取
+        assert_eq!(*log_storage.get_entry(3).unwrap().relay_log().event_log_pos(), 3);
+    }
+
+    let segment_dir = SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap();
+    let first = PathBuf::from(&segment_dir).join("rlog-2-1-1.log");
+    let third = PathBuf::from(&segment_dir).join("rlog-2-3-21.log");
+    // 写满的 segment 被压缩, 小于写入中的 segment
+    assert!(fs::metadata(&first).unwrap().len() < fs::metadata(&third).unwrap().len());
+
+    // 重新加载后读取已压缩的 segment, 不使用 entry 缓存
+    storage_config.set_entry_buffer_num(1);
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in [1, 10, 11, 20, 25] {
+        let entry = log_storage.get_entry(i).unwrap();
+        assert_eq!(*entry.relay_log().event_log_pos(), i);
+        assert_eq!(entry.relay_log().event_name(), &format!("binlog_{}", i));
+    }
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 25)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::PathBuf;
+
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+#[test]
+pub fn test_seek_position_and_gtid() {
+    let dir = temp_dir("relay_log_index");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config.set_index_interval(4);
+
+    {
+        let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+        // index 1~15: mysql-bin.000001 的 pos 100~1500, 每个事务 3 个entry
+        for i in 1..=15u64 {
+            let gtid = format!("3e11fa47-71ca-11e1-9e33-c80aa9429562:{}", (i + 2) / 3);
+            log_storage.append_relay_log_with_gtid(relay_log(i * 100), Some(&gtid)).unwrap();
+        }
+        // index 16~25: 切换到 mysql-bin.000002, pos 从头开始
+        for i in 1..=10u64 {
+            log_storage.append_relay_log(relay_log(i * 100 + 50)).unwrap();
+        }
+
+        assert_eq!(log_storage.seek_gtid("3e11fa47-71ca-11e1-9e33-c80aa9429562:2").unwrap(), Some(4));
+        assert_eq!(log_storage.seek_gtid("3e11fa47-71ca-11e1-9e33-c80aa9429562:9").unwrap(), None);
+    }
+
+    let segment_dir = SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap();
+    assert!(PathBuf::from(&segment_dir).join("rlog-2-1-1.idx").exists());
+    // 索引文件丢失后根据entry重建position索引
+    fs::remove_file(PathBuf::from(&segment_dir).join("rlog-2-3-21.idx")).unwrap();
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    // 相同的 pos 取最后一个 binlog 文件
+    assert_eq!(log_storage.seek_position(300).unwrap(), Some(18));
+    assert_eq!(log_storage.seek_position(400).unwrap(), Some(19));
+    // 跨越 segment 边界
+    assert_eq!(log_storage.seek_position(600).unwrap(), Some(21));
+    assert_eq!(log_storage.seek_position(1000).unwrap(), Some(25));
+    assert_eq!(log_storage.seek_position(1100).unwrap(), None);
+    assert_eq!(log_storage.seek_gtid("3e11fa47-71ca-11e1-9e33-c80aa9429562:1").unwrap(), Some(1));
+    assert_eq!(log_storage.seek_gtid("3e11fa47-71ca-11e1-9e33-c80aa9429562:5").unwrap(), Some(13));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_scan_range() {
+    let dir = temp_dir("relay_log_scan");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config.set_index_interval(4);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in 1..=30u64 {
+        let mut log = relay_log(i * 100);
+        // 时间戳不严格单调: index 15 早于前一个事件
+        log.set_event_timestamp(if i == 15 { 1000 + 12 } else { 1000 + i as u32 });
+        log_storage.append_relay_log(log).unwrap();
+    }
+
+    let indexes: Vec<u64> = log_storage.scan_range(1012, 1016).unwrap()
+        .map(|e| *e.unwrap().index())
+        .collect();
+    assert_eq!(indexes, vec![12, 13, 14, 15, 16]);
+    assert_eq!(log_storage.scan_range(1031, u32::MAX).unwrap().count(), 0);
+    assert_eq!(log_storage.scan_range(0, u32::MAX).unwrap().count(), 30);
+
+    fs::remove_dir_all(dir).unwrap();
+}
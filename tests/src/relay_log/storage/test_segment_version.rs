@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::PathBuf;
+
+use checksum::crc32::Crc32;
+
+use common::binlog::src_meta::SrcType;
+use common::schema::data_type::Value;
+use relay_log::codec::binary_codec::BinaryCodec;
+use relay_log::codec::binary_codec::CodecStyle::LittleVar;
+use relay_log::codec::codec::Codec;
+use relay_log::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::temp_dir;
+
+const MAX_ENTRIES: u32 = 10;
+
+fn crc32(buf: &[u8]) -> u32 {
+    Crc32::new().checksum(buf)
+}
+
+/// 按版本 1 的格式写入一个segment: RelayLog 中没有 event_timestamp
+fn write_v1_segment(segment_dir: &PathBuf, log_positions: &[u64]) -> PathBuf {
+    let codec = BinaryCodec::new();
+    // Insert([Long(7)])
+    let row = codec.binary_deserialize::<RelayRowData>(&LittleVar, &[1, 5, 14]).unwrap();
+
+    let mut header = vec![0u8; 64];
+    header[0..4].copy_from_slice(&1u32.to_le_bytes());
+    header[4..8].copy_from_slice(&1u32.to_le_bytes());
+    header[8..16].copy_from_slice(&1u64.to_le_bytes());
+    header[16..24].copy_from_slice(&(1024 * 1024u64).to_le_bytes());
+    header[24..28].copy_from_slice(&MAX_ENTRIES.to_le_bytes());
+
+    let data_start = 64 + 4 + MAX_ENTRIES as usize * 8;
+    let mut positions = vec![0u8; 4 + MAX_ENTRIES as usize * 8];
+    let mut entries = vec![];
+    for (i, pos) in log_positions.iter().enumerate() {
+        let log = (SrcType::Mysql, *pos, String::from("WriteRowsEvent"), String::from("db1"), String::from("t1"),
+                   Vec::<RelayColumnInfo>::new(), RelayCommand::Insert(vec![row.clone()]));
+        let bytes = codec.binary_serialize(&LittleVar, &log).unwrap();
+
+        let position = (data_start + entries.len()) as u64;
+        positions[4 + i * 8..12 + i * 8].copy_from_slice(&position.to_le_bytes());
+        entries.extend_from_slice(&(i as u64 + 1).to_le_bytes());
+        entries.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        entries.extend_from_slice(&crc32(&bytes).to_le_bytes());
+        entries.extend_from_slice(&bytes);
+    }
+    positions[0..4].copy_from_slice(&(log_positions.len() as u32).to_le_bytes());
+    // 提交标记
+    let last_index = (log_positions.len() as u64).to_le_bytes();
+    entries.extend_from_slice(&u64::MAX.to_le_bytes());
+    entries.extend_from_slice(&last_index);
+    entries.extend_from_slice(&crc32(&last_index).to_le_bytes());
+
+    fs::create_dir_all(segment_dir).unwrap();
+    let path = segment_dir.join("rlog-1-1-1.log");
+    fs::write(&path, [header, positions, entries].concat()).unwrap();
+    path
+}
+
+fn assert_v1_entry(log: &RelayLog, pos: u64) {
+    assert_eq!(*log.event_log_pos(), pos);
+    assert_eq!(*log.event_timestamp(), 0);
+    assert_eq!(log.event_name(), "WriteRowsEvent");
+    assert_eq!(log.table_name(), "t1");
+    match log.relay_command() {
+        RelayCommand::Insert(rows) => assert!(matches!(rows[0].values()[0], Value::Long(7))),
+        c => panic!("unexpected command {:?}", c),
+    }
+}
+
+#[test]
+pub fn test_read_v1_segment() {
+    let dir = temp_dir("relay_log_v1");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(MAX_ENTRIES);
+    let segment_dir = PathBuf::from(SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap());
+    let v1 = write_v1_segment(&segment_dir, &[120, 240]);
+    let v1_bytes = fs::read(&v1).unwrap();
+
+    // 只读打开不修改文件, 也不建立索引文件
+    {
+        let mut log_storage = RelayLogStorage::open_read_only(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+        assert_eq!(log_storage.index_range().unwrap(), Some((1, 2)));
+        assert_v1_entry(log_storage.get_entry(2).unwrap().relay_log(), 240);
+        assert!(log_storage.append_relay_log(RelayLog::default()).is_err());
+    }
+    assert_eq!(fs::read(&v1).unwrap(), v1_bytes);
+    assert!(!segment_dir.join("rlog-1-1-1.idx").exists());
+
+    // 写入方不再追加旧版本的segment, 滚动到新版本的segment
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_v1_entry(log_storage.get_entry(1).unwrap().relay_log(), 120);
+    let mut log = RelayLog::default();
+    log.set_event_log_pos(360);
+    log.set_event_timestamp(1_700_000_000);
+    log_storage.append_relay_log(log).unwrap();
+    drop(log_storage);
+    assert!(segment_dir.join("rlog-2-2-3.log").exists());
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 3)));
+    assert_v1_entry(log_storage.get_entry(2).unwrap().relay_log(), 240);
+    assert_eq!(*log_storage.get_entry(3).unwrap().relay_log().event_timestamp(), 1_700_000_000);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
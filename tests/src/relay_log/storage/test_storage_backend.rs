@@ -0,0 +1,72 @@
+use std::fs;
+
+use relay_log::storage::storage_backend::{open_backend, StorageBackend, StorageBackendKind};
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+/// 各后端相同的行为: 追加、读取、按GTID查找、重新打开后继续追加、按消费者offset保留
+fn check_backend(backend: StorageBackendKind, name: &str) {
+    let dir = temp_dir("relay_log_backend");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config.set_backend(backend);
+
+    {
+        let mut storage = open_backend(&storage_config, "db1", "t1").unwrap();
+        assert_eq!(storage.name(), name);
+        assert_eq!(storage.index_range().unwrap(), None);
+        for i in 1..=25 {
+            let gtid = format!("uuid:{}", (i + 1) / 2);
+            storage.append_relay_log_with_gtid(relay_log(i), Some(&gtid)).unwrap();
+        }
+        storage.flush().unwrap();
+        assert_eq!(storage.index_range().unwrap(), Some((1, 25)));
+        assert_eq!(*storage.get_entry(17).unwrap().relay_log().event_log_pos(), 17);
+        assert_eq!(storage.seek_gtid("uuid:5").unwrap(), Some(9));
+        assert_eq!(storage.seek_gtid("uuid:99").unwrap(), None);
+        assert!(storage.get_entry(26).is_err());
+        assert_eq!(storage.metrics().snapshot().entries_written, 25);
+    }
+
+    storage_config.set_retention_bytes(1);
+    let mut storage = open_backend(&storage_config, "db1", "t1").unwrap();
+    storage.append_relay_log(relay_log(26)).unwrap();
+    storage.sync().unwrap();
+    assert_eq!(storage.index_range().unwrap(), Some((1, 26)));
+
+    // 消费者未读取的日志不会被清理
+    storage.consumer_offsets().commit("apply", 21);
+    assert!(storage.purge().unwrap().segments > 0);
+    let (first, last) = storage.index_range().unwrap().unwrap();
+    assert!(first > 1 && first <= 21);
+    assert_eq!(last, 26);
+    assert_eq!(*storage.get_entry(21).unwrap().relay_log().event_log_pos(), 21);
+    drop(storage);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_segment_backend() {
+    check_backend(StorageBackendKind::Segment, "segment");
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+pub fn test_rocksdb_backend() {
+    check_backend(StorageBackendKind::RocksDb, "rocksdb");
+}
+
+#[cfg(not(feature = "rocksdb"))]
+#[test]
+pub fn test_rocksdb_backend_disabled() {
+    let dir = temp_dir("relay_log_backend");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_backend(StorageBackendKind::try_from("RocksDB").unwrap());
+    assert!(open_backend(&storage_config, "db1", "t1").is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
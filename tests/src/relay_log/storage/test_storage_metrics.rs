@@ -0,0 +1,54 @@
+use std::fs;
+
+use relay_log::storage::durability::FsyncPolicy;
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::storage_config::StorageConfig;
+use relay_log::storage::storage_metrics::StorageMetrics;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+#[test]
+pub fn test_storage_metrics() {
+    let dir = temp_dir("relay_log_metrics");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config.set_entry_buffer_num(5);
+    storage_config.set_fsync_policy(FsyncPolicy::PerBatch);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    let metrics = log_storage.metrics();
+    assert_eq!(metrics.snapshot().segments, 1);
+    assert_eq!(metrics.snapshot().entries_written, 0);
+
+    for i in 1..=25u64 {
+        log_storage.append_relay_log(relay_log(i * 100)).unwrap();
+    }
+    log_storage.flush().unwrap();
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.entries_written, 25);
+    assert!(snapshot.bytes_written > 0);
+    assert_eq!(snapshot.segments, 3);
+    assert!(snapshot.active_segment_bytes > 0);
+    // 两次写满 segment 以及一次手动 flush
+    assert!(snapshot.fsyncs >= 3);
+    assert!(snapshot.fsync_max_micros >= snapshot.fsync_p50_micros);
+    assert_eq!(snapshot.compaction_progress, 1.0);
+
+    // 缓存之外的entry从segment文件读取
+    for i in 1..=25u64 {
+        assert_eq!(*log_storage.get_entry(i).unwrap().relay_log().event_log_pos(), i * 100);
+    }
+    let snapshot = metrics.snapshot();
+    assert!(snapshot.entries_read >= 20);
+    assert!(snapshot.bytes_read > 0);
+
+    let text = StorageMetrics::render(&[metrics]);
+    assert!(text.contains("# TYPE relay_log_written_entries_total counter"));
+    assert!(text.contains("relay_log_written_entries_total{database=\"db1\",table=\"t1\"} 25"));
+    assert!(text.contains("relay_log_segments{database=\"db1\",table=\"t1\"} 3"));
+
+    drop(log_storage);
+    fs::remove_dir_all(&dir).unwrap();
+}
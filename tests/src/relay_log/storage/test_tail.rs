@@ -0,0 +1,75 @@
+use std::fs;
+use std::thread;
+
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+#[test]
+pub fn test_tail_blocking() {
+    let dir = temp_dir("relay_log_tail");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in 1..=5u64 {
+        log_storage.append_relay_log(relay_log(i * 100)).unwrap();
+    }
+
+    // 已追加的entry立即可读, 读到末尾后不等待
+    let mut tail = log_storage.tail(3).unwrap();
+    assert_eq!(*tail.try_next().unwrap().unwrap().index(), 3);
+    assert_eq!(*tail.try_next().unwrap().unwrap().index(), 4);
+    assert_eq!(*tail.try_next().unwrap().unwrap().index(), 5);
+    assert!(tail.try_next().unwrap().is_none());
+
+    // 在其它线程中阻塞读取, 跨越 segment 边界直到存储关闭
+    let mut tail = log_storage.tail(0).unwrap();
+    let reader = thread::spawn(move || {
+        tail.blocking_iter()
+            .map(|e| {
+                let entry = e.unwrap();
+                (*entry.index(), *entry.relay_log().event_log_pos())
+            })
+            .collect::<Vec<(u64, u64)>>()
+    });
+    for i in 6..=25u64 {
+        log_storage.append_relay_log(relay_log(i * 100)).unwrap();
+    }
+    drop(log_storage);
+
+    let entries = reader.join().unwrap();
+    assert_eq!(entries.len(), 25);
+    assert!(entries.iter().enumerate().all(|(i, (index, pos))| *index == i as u64 + 1 && *pos == (i as u64 + 1) * 100));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+pub async fn test_tail_async() {
+    let dir = temp_dir("relay_log_tail_async");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    let mut tail = log_storage.tail(1).unwrap();
+    let reader = tokio::spawn(async move {
+        let mut indexes = vec![];
+        while let Some(entry) = tail.next().await {
+            indexes.push(*entry.unwrap().index());
+        }
+        indexes
+    });
+
+    for i in 1..=15u64 {
+        log_storage.append_relay_log(relay_log(i * 100)).unwrap();
+    }
+    drop(log_storage);
+
+    assert_eq!(reader.await.unwrap(), (1..=15u64).collect::<Vec<u64>>());
+
+    fs::remove_dir_all(dir).unwrap();
+}
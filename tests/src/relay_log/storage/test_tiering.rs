@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use relay_log::relay_log::RelayLog;
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+use relay_log::storage::tiering::FileObjectStore;
+
+use crate::relay_log::storage::{self, temp_dir};
+
+fn relay_log(log_pos: u64) -> RelayLog {
+    let mut relay_log = storage::relay_log(log_pos);
+    relay_log.set_event_timestamp(1000 + log_pos as u32 / 100);
+    relay_log
+}
+
+#[test]
+pub fn test_offload_and_fetch() {
+    let dir = temp_dir("relay_log_tiering");
+    let bucket = temp_dir("relay_log_bucket");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+    storage_config.set_entry_buffer_num(4);
+    storage_config.set_tiering_millisecond(10);
+    storage_config.set_purge_interval_millisecond(60 * 60 * 1000);
+    storage_config.set_object_store(Some(Arc::new(FileObjectStore::new(bucket.to_str().unwrap()))));
+    let segment_dir = PathBuf::from(SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap());
+
+    {
+        let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+        for i in 1..=25u64 {
+            log_storage.append_relay_log(relay_log(i * 100)).unwrap();
+        }
+        thread::sleep(Duration::from_millis(20));
+
+        // 当前写入的segment保留在本地
+        let stats = log_storage.offload().unwrap();
+        assert_eq!(stats.segments, 2);
+        assert!(!segment_dir.join("rlog-2-1-1.log").exists());
+        assert!(!segment_dir.join("rlog-2-2-11.log").exists());
+        assert!(bucket.join("db1#t1").join("rlog-2-1-1.log").exists());
+        assert!(bucket.join("db1#t1").join("rlog-2-1-1.idx").exists());
+        assert!(segment_dir.join("manifest.json").exists());
+
+        assert_eq!(log_storage.index_range().unwrap(), Some((1, 25)));
+        // 读取时自动取回
+        assert_eq!(*log_storage.get_entry(5).unwrap().relay_log().event_log_pos(), 500);
+        assert!(segment_dir.join("rlog-2-1-1.log").exists());
+
+        // 已在对象存储中的segment不再上传
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(log_storage.offload().unwrap().segments, 1);
+    }
+
+    // 重启后根据清单取回
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 25)));
+    let indexes: Vec<u64> = log_storage.scan_range(1012, 1014).unwrap()
+        .map(|e| *e.unwrap().index())
+        .collect();
+    assert_eq!(indexes, vec![12, 13, 14]);
+    assert!(segment_dir.join("rlog-2-2-11.log").exists());
+    assert!(!segment_dir.join("rlog-2-1-1.log").exists());
+    assert_eq!(log_storage.scan_range(0, u32::MAX).unwrap().count(), 25);
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(bucket).unwrap();
+}
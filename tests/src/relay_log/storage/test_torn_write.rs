@@ -0,0 +1,106 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment::RecoveryStats;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::{relay_log, temp_dir};
+
+#[test]
+pub fn test_truncate_partial_entry() {
+    let dir = temp_dir("relay_log_torn");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    storage_config.set_max_segment_entries(10);
+
+    {
+        let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+        for i in 1..=15 {
+            log_storage.append_relay_log(relay_log(i)).unwrap();
+        }
+    }
+
+    // 模拟写入 index 16 时崩溃: entry 头完整, 日志内容只写入了一部分
+    let segment_dir = SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap();
+    let last = PathBuf::from(&segment_dir).join("rlog-2-2-11.log");
+    let mut f = OpenOptions::new().append(true).open(&last).unwrap();
+    f.write_all(&16u64.to_le_bytes()).unwrap();
+    f.write_all(&1000u64.to_le_bytes()).unwrap();
+    f.write_all(&0u32.to_le_bytes()).unwrap();
+    f.write_all(&[0u8; 10]).unwrap();
+    drop(f);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats { entries: 0, bytes: 30 });
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 15)));
+
+    log_storage.append_relay_log(relay_log(16)).unwrap();
+    assert_eq!(*log_storage.get_entry(16).unwrap().relay_log().event_log_pos(), 16);
+    drop(log_storage);
+
+    // 正常关闭后不需要截断
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats::default());
+    assert_eq!(*log_storage.get_entry(16).unwrap().relay_log().event_log_pos(), 16);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_discard_uncommitted_batch() {
+    let dir = temp_dir("relay_log_uncommitted");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    for i in 1..=3 {
+        log_storage.append_relay_log(relay_log(i)).unwrap();
+    }
+    log_storage.flush().unwrap();
+    // 第二个批次未flush时进程崩溃: entry数量已更新, 内容仍在写缓冲中
+    for i in 4..=5 {
+        log_storage.append_relay_log(relay_log(i)).unwrap();
+    }
+    std::mem::forget(log_storage);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats { entries: 2, bytes: 0 });
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 3)));
+    log_storage.append_relay_log(relay_log(4)).unwrap();
+    assert_eq!(*log_storage.get_entry(4).unwrap().relay_log().event_log_pos(), 4);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn test_truncate_corrupt_log_size() {
+    let dir = temp_dir("relay_log_corrupt_size");
+    let mut storage_config = StorageConfig::default();
+    storage_config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+
+    {
+        let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+        for i in 1..=3 {
+            log_storage.append_relay_log(relay_log(i)).unwrap();
+        }
+    }
+
+    // 损坏的日志大小: log_size + 4 溢出时同样按未完成的写入截断
+    let segment_dir = SegmentManager::get_segment_dir_path(storage_config.relay_log_dir(), "db1", "t1").unwrap();
+    let last = PathBuf::from(&segment_dir).join("rlog-2-1-1.log");
+    let mut f = OpenOptions::new().append(true).open(&last).unwrap();
+    f.write_all(&4u64.to_le_bytes()).unwrap();
+    f.write_all(&u64::MAX.to_le_bytes()).unwrap();
+    f.write_all(&[0u8; 10]).unwrap();
+    drop(f);
+
+    let mut log_storage = RelayLogStorage::new(&storage_config, "db1".to_string(), "t1".to_string()).unwrap();
+    assert_eq!(log_storage.segment_manager.recovery(), RecoveryStats { entries: 0, bytes: 26 });
+    assert_eq!(log_storage.index_range().unwrap(), Some((1, 3)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
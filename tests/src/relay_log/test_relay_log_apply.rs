@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+use common::binlog::row::row_string::RowString;
+use common::memory_governor::MemoryGovernor;
+use common::schema::data_type::Value;
+use connection::binlog::parallel_applier::LaneScheduler;
+use connection::conn::async_connection::AsyncConnection;
+use connection::conn::connection_options::ConnectionOptions;
+use relay_log::apply::relay_log_apply::{ApplyPosition, Dispatch, RelayLogApply};
+use relay_log::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+use relay_log::storage::storage_entry::StorageEntry;
+
+fn insert_entry(index: u64, size: u64) -> StorageEntry {
+    let mut log = RelayLog::default();
+    log.set_database_name("db1".to_string());
+    log.set_table_name("t1".to_string());
+    log.set_relay_command(RelayCommand::Insert(vec![RelayRowData::default()]));
+    StorageEntry::new(index, size, 0, log)
+}
+
+fn apply(name: &str, parallelism: usize) -> RelayLogApply {
+    RelayLogApply::new(name, AsyncConnection::new(ConnectionOptions::default()))
+        .with_parallelism(parallelism)
+        .with_primary_keys(vec!["id".to_string()])
+}
+
+/// db1.table 中主键 id 为 id 的行的日志
+fn row_entry(index: u64, table: &str, command: fn(Vec<RelayRowData>) -> RelayCommand, id: i64) -> StorageEntry {
+    let mut column = RelayColumnInfo::default();
+    column.set_column_name("id".to_string());
+    let mut row = RelayRowData::default();
+    row.set_values(vec![Value::Long(id)]);
+
+    let mut log = RelayLog::default();
+    log.set_database_name("db1".to_string());
+    log.set_table_name(table.to_string());
+    log.set_columns(vec![column]);
+    log.set_relay_command(command(vec![row]));
+    StorageEntry::new(index, 10, 0, log)
+}
+
+fn command_entry(index: u64, command: RelayCommand) -> StorageEntry {
+    let mut log = RelayLog::default();
+    log.set_database_name("db1".to_string());
+    log.set_table_name("t1".to_string());
+    log.set_relay_command(command);
+    StorageEntry::new(index, 10, 0, log)
+}
+
+fn position(next_index: u64) -> ApplyPosition {
+    ApplyPosition { next_index, ..Default::default() }
+}
+
+fn positions(rows: &[(&str, u64)]) -> BTreeMap<String, ApplyPosition> {
+    rows.iter().map(|(n, i)| (n.to_string(), position(*i))).collect()
+}
+
+/// id 列的值对应的主键
+fn key(id: i64) -> String {
+    format!("{:?}", Value::Long(id))
+}
+
+/// 与 id 为 1 的行不在同一个 lane 的 id
+fn other_lane_id(scheduler: &LaneScheduler) -> i64 {
+    let lane = scheduler.lane_of("db1", "t1", Some(key(1).as_str()));
+    (2..100).find(|id| scheduler.lane_of("db1", "t1", Some(key(*id).as_str())) != lane).unwrap()
+}
+
+#[test]
+fn test_lane_assignment() {
+    let scheduler = LaneScheduler::new(4);
+    let apply = apply("apply_lanes", 4);
+
+    // 与 binlog 的并行回放使用相同的分配
+    let lane = scheduler.lane_of("db1", "t1", Some(key(1).as_str()));
+    let insert = vec![row_entry(1, "t1", RelayCommand::Insert, 1), command_entry(2, RelayCommand::Commit(1))];
+    assert_eq!(apply.lanes_of(&insert), BTreeSet::from([lane]));
+    assert_eq!(apply.dispatch_of(&insert), Dispatch::Lane(lane));
+
+    // 同一行的插入与删除在同一个 lane
+    let same = vec![row_entry(1, "t1", RelayCommand::Insert, 1), row_entry(2, "t1", RelayCommand::Delete, 1)];
+    assert_eq!(apply.dispatch_of(&same), Dispatch::Lane(lane));
+
+    // 涉及多个 lane 的事务为屏障
+    let other = other_lane_id(&scheduler);
+    let cross = vec![row_entry(1, "t1", RelayCommand::Insert, 1), row_entry(2, "t1", RelayCommand::Insert, other)];
+    assert_eq!(apply.lanes_of(&cross).len(), 2);
+    assert_eq!(apply.dispatch_of(&cross), Dispatch::Barrier);
+
+    // 未指定主键时按表分配
+    let by_table = RelayLogApply::new("apply_tables", AsyncConnection::new(ConnectionOptions::default()))
+        .with_parallelism(4);
+    let lane = scheduler.lane_of("db1", "t1", None);
+    assert_eq!(by_table.dispatch_of(&cross), Dispatch::Lane(lane));
+
+    // 并行度为 1 时串行回放
+    assert_eq!(self::apply("apply_serial", 1).dispatch_of(&cross), Dispatch::Serial);
+}
+
+#[test]
+fn test_ddl_barrier() {
+    let apply = apply("apply_ddl", 4);
+    let ddl = vec![command_entry(1, RelayCommand::AlterTable)];
+    assert_eq!(apply.lanes_of(&ddl), (0..4).collect::<BTreeSet<usize>>());
+    assert_eq!(apply.dispatch_of(&ddl), Dispatch::Barrier);
+
+    // 事务中任一日志为 DDL 时整个事务为屏障
+    let mixed = vec![row_entry(1, "t1", RelayCommand::Insert, 1), command_entry(2, RelayCommand::CreateTable)];
+    assert_eq!(apply.dispatch_of(&mixed), Dispatch::Barrier);
+}
+
+#[test]
+fn test_lane_restart_positions() {
+    let scheduler = LaneScheduler::new(4);
+    let mut apply = apply("a", 4);
+    let rewrite = apply.restore_positions(&positions(&[("a", 3), ("a#0", 10), ("a#1", 20), ("a#2", 5), ("a#3", 30)])).unwrap();
+    assert!(!rewrite);
+    // 从最慢的 lane 继续
+    assert_eq!(apply.position().next_index, 5);
+
+    let id_lane = |id: i64| scheduler.lane_of("db1", "t1", Some(key(id).as_str()));
+    let lane_next = [10, 20, 5, 30];
+    for id in 1..20 {
+        let lane = id_lane(id);
+        let before = vec![row_entry(lane_next[lane] - 1, "t1", RelayCommand::Insert, id)];
+        let after = vec![row_entry(lane_next[lane], "t1", RelayCommand::Insert, id)];
+        // 该 lane 重启前已回放的事务跳过
+        assert_eq!(apply.dispatch_of(&before), Dispatch::Applied);
+        assert_eq!(apply.dispatch_of(&after), Dispatch::Lane(lane));
+    }
+
+    // 屏障早于最慢的 lane 时已回放
+    assert_eq!(apply.dispatch_of(&[command_entry(4, RelayCommand::AlterTable)]), Dispatch::Applied);
+    assert_eq!(apply.dispatch_of(&[command_entry(5, RelayCommand::AlterTable)]), Dispatch::Barrier);
+
+    // 缺少的 lane 从任务位点开始
+    let mut apply = self::apply("a", 4);
+    apply.restore_positions(&positions(&[("a", 3), ("a#0", 10)])).unwrap();
+    assert_eq!(apply.position().next_index, 3);
+}
+
+#[test]
+fn test_change_parallelism() {
+    // 正常退出后只有任务位点, 可以修改并行度
+    let mut apply = apply("a", 2);
+    assert!(!apply.restore_positions(&positions(&[("a", 100)])).unwrap());
+    assert_eq!(apply.position().next_index, 100);
+    assert_eq!(apply.dispatch_of(&[command_entry(99, RelayCommand::AlterTable)]), Dispatch::Applied);
+    assert_eq!(apply.dispatch_of(&[command_entry(100, RelayCommand::AlterTable)]), Dispatch::Barrier);
+
+    // 以 4 个 lane 非正常退出, 各 lane 位点相同时按 2 个 lane 重写
+    let mut apply = self::apply("a", 2);
+    let rewrite = apply.restore_positions(&positions(&[("a", 50), ("a#0", 80), ("a#1", 80), ("a#2", 80), ("a#3", 80)])).unwrap();
+    assert!(rewrite);
+    assert_eq!(apply.position().next_index, 80);
+
+    // 各 lane 位点不同时不能修改并行度
+    let mut apply = self::apply("a", 2);
+    assert!(apply.restore_positions(&positions(&[("a", 50), ("a#0", 80), ("a#1", 90), ("a#2", 80)])).is_err());
+
+    // 以多个 lane 非正常退出后改为串行
+    let mut apply = self::apply("a", 1);
+    assert!(apply.restore_positions(&positions(&[("a", 50), ("a#0", 80), ("a#1", 80)])).unwrap());
+    assert_eq!(apply.position().next_index, 80);
+}
+
+#[test]
+fn test_parse_positions() {
+    let apply = apply("a", 2);
+    let row = |name: &str, next: &str| RowString::new_row(vec![Some(name.to_string()), Some(next.to_string()),
+        Some("120".to_string()), Some("1700000000".to_string()), Some("mysql-bin.000001".to_string()), None]);
+    let positions = apply.parse_positions(vec![row("a", "3"), row("a#0", "10"), row("a#1", "20"),
+                                               // LIKE 'a#%' 匹配到的其它任务
+                                               row("a#x", "7"), row("ab", "8")]).unwrap();
+    assert_eq!(positions.keys().cloned().collect::<Vec<String>>(), vec!["a", "a#0", "a#1"]);
+    assert_eq!(positions["a#1"].next_index, 20);
+    assert_eq!(positions["a#1"].event_log_pos, 120);
+    assert_eq!(positions["a#1"].binlog_file, "mysql-bin.000001");
+    assert_eq!(positions["a#1"].gtid, "");
+
+    assert!(apply.parse_positions(vec![row("a#0", "x")]).is_err());
+}
+
+#[tokio::test]
+async fn test_transaction_exceeds_memory_budget() {
+    let governor = MemoryGovernor::new(Some(100));
+    let other = governor.register("other");
+    assert!(other.try_reserve(100));
+
+    let mut apply = RelayLogApply::new("apply_budget", AsyncConnection::new(ConnectionOptions::default()))
+        .with_memory_governor(governor.clone())
+        .with_backpressure_timeout(Duration::from_millis(200));
+
+    // 事务中只有第一条日志等待超时, 之后直接透支
+    let start = Instant::now();
+    for i in 1..=20 {
+        apply.apply_entry(insert_entry(i, 10)).await.unwrap();
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(200));
+    assert!(elapsed < Duration::from_millis(1000), "waited {:?}", elapsed);
+
+    // 事务提交前一直占用
+    assert_eq!(apply.memory_used(), 200);
+    assert_eq!(governor.used(), 300);
+
+    drop(apply);
+    assert_eq!(governor.used(), 100);
+}
@@ -0,0 +1,89 @@
+use std::thread;
+use std::time::Duration;
+
+use relay_log::apply::replay_progress::{ReplayProgress, ReplayStatus};
+
+use crate::relay_log::storage::temp_dir;
+
+#[test]
+fn test_progress_and_eta() {
+    let progress = ReplayProgress::new("apply1");
+    progress.start(100);
+    progress.set_newest(199);
+
+    let status = progress.status();
+    assert_eq!(status.lag_entries, 100);
+    assert_eq!(status.progress_percent, 0.0);
+    // 还没有吞吐量
+    assert_eq!(status.eta_seconds, None);
+
+    thread::sleep(Duration::from_millis(200));
+    progress.record_applied(150, 500);
+
+    let status = progress.status();
+    assert_eq!(status.next_index, 150);
+    assert_eq!(status.lag_entries, 50);
+    assert_eq!(status.progress_percent, 50.0);
+    assert_eq!(status.applied_rows, 500);
+    assert_eq!(status.applied_transactions, 1);
+    assert!(status.entries_per_sec > 0.0);
+    assert!(status.rows_per_sec > status.entries_per_sec);
+    // 50 个entry用时约 0.2 秒
+    let eta = status.eta_seconds.unwrap();
+    assert!((1..=2).contains(&eta), "eta {}", eta);
+
+    progress.record_applied(200, 10);
+    let status = progress.status();
+    assert_eq!(status.lag_entries, 0);
+    assert_eq!(status.progress_percent, 100.0);
+    assert_eq!(status.eta_seconds, Some(0));
+}
+
+#[test]
+fn test_newest_never_goes_back() {
+    let progress = ReplayProgress::new("apply1");
+    progress.start(1);
+    progress.set_newest(10);
+    progress.set_newest(5);
+    assert_eq!(progress.status().newest_index, 10);
+
+    // 回放超过已知的最新位点时, 最新位点随之推进
+    progress.record_applied(21, 1);
+    let status = progress.status();
+    assert_eq!(status.newest_index, 20);
+    assert_eq!(status.lag_entries, 0);
+}
+
+#[test]
+fn test_save_and_load_dir() {
+    let dir = temp_dir("replay_status");
+    let dir = dir.to_str().unwrap();
+    assert!(ReplayStatus::load_dir(dir).unwrap().is_empty());
+
+    for name in ["apply2", "apply1"] {
+        let progress = ReplayProgress::new(name);
+        progress.start(1);
+        progress.set_newest(10);
+        progress.record_applied(4, 3);
+        progress.status().save(dir).unwrap();
+    }
+    // 覆盖旧的状态
+    let progress = ReplayProgress::new("apply1");
+    progress.start(1);
+    progress.set_newest(10);
+    progress.record_applied(11, 3);
+    let saved = progress.status();
+    saved.save(dir).unwrap();
+
+    let statuses = ReplayStatus::load_dir(dir).unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses[0].name, saved.name);
+    assert_eq!(statuses[0].next_index, 11);
+    assert_eq!(statuses[0].applied_rows, 3);
+    assert_eq!(statuses[0].updated_at, saved.updated_at);
+    assert_eq!(statuses[1].name, "apply2");
+    assert_eq!(statuses[1].next_index, 4);
+    assert!(statuses[0].to_string().starts_with("replay apply1: 10/10 (100.0%), lag 0 entries"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
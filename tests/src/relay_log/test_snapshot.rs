@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use common::binlog::row::row_string::RowString;
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use common::schema::data_type::Value;
+use common::schema::mysql_metadata::MetadataQuery;
+use connection::binlog::starting_strategy::StartingStrategy;
+use connection::conn::connection_options::ConnectionOptions;
+use relay_log::relay_log::RelayCommand;
+use relay_log::snapshot::snapshot_bootstrap::{SnapshotBootstrap, SnapshotPosition, SnapshotQuery, SnapshotStats};
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::relay_log::storage::temp_dir;
+
+#[test]
+fn test_snapshot_binlog_options() {
+    let mut position = SnapshotPosition {
+        binlog_file: "mysql-bin.000003".to_string(),
+        binlog_position: 1024,
+        gtid_set: String::new(),
+    };
+    let options = position.binlog_options().unwrap();
+    assert_eq!(options.starting_strategy, StartingStrategy::FromPosition);
+    assert_eq!((options.filename.as_str(), options.position), ("mysql-bin.000003", 1024));
+
+    // 开启 GTID 时按 GTID 定位
+    position.gtid_set = "3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5".to_string();
+    let options = position.binlog_options().unwrap();
+    assert_eq!(options.starting_strategy, StartingStrategy::FromGtid);
+    assert!(options.gtid_set.is_some());
+}
+
+/// 按 SQL 返回预设结果的源库
+struct MockSource {
+    // information_schema.COLUMNS: (列名, DATA_TYPE, COLUMN_TYPE)
+    columns: Vec<(&'static str, &'static str, &'static str)>,
+    primary_keys: Vec<&'static str>,
+    // 每次分块 SELECT 的结果, None 表示查询失败
+    chunks: VecDeque<Option<Vec<Vec<Option<&'static str>>>>>,
+    binlog_enabled: bool,
+    sqls: Vec<String>,
+}
+
+impl MockSource {
+    fn new(columns: Vec<(&'static str, &'static str, &'static str)>, primary_keys: Vec<&'static str>) -> Self {
+        MockSource { columns, primary_keys, chunks: VecDeque::new(), binlog_enabled: true, sqls: vec![] }
+    }
+
+    fn with_chunk(mut self, rows: Vec<Vec<Option<&'static str>>>) -> Self {
+        self.chunks.push_back(Some(rows));
+        self
+    }
+
+    fn chunk_sqls(&self) -> Vec<&String> {
+        self.sqls.iter().filter(|s| s.starts_with("SELECT") && s.contains("FROM `db1`.`t1`")).collect()
+    }
+}
+
+fn rows(values: Vec<Vec<Option<&'static str>>>) -> Vec<RowString> {
+    values.into_iter().map(|r| RowString::new_row(r.into_iter().map(|v| v.map(String::from)).collect())).collect()
+}
+
+#[async_trait::async_trait]
+impl MetadataQuery for MockSource {
+    async fn query(&mut self, sql: String) -> CResult<Vec<RowString>> {
+        self.sqls.push(sql.clone());
+        if sql.contains("information_schema.COLUMNS") {
+            Ok(rows(self.columns.iter().map(|(n, t, ct)| vec![Some(*n), Some(*t), Some(*ct)]).collect()))
+        } else if sql.contains("information_schema.KEY_COLUMN_USAGE") {
+            Ok(rows(self.primary_keys.iter().map(|pk| vec![Some(*pk)]).collect()))
+        } else if sql.starts_with("SHOW") {
+            if !self.binlog_enabled {
+                return Err(ReError::String("binary log is disabled".to_string()));
+            }
+            Ok(rows(vec![vec![Some("mysql-bin.000003"), Some("1024"), None, None, Some("")]]))
+        } else {
+            match self.chunks.pop_front() {
+                Some(Some(chunk)) => Ok(rows(chunk)),
+                Some(None) => Err(ReError::String("lost connection".to_string())),
+                None => Ok(vec![]),
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SnapshotQuery for MockSource {
+    async fn execute(&mut self, sql: String) -> CResult<()> {
+        self.sqls.push(sql);
+        Ok(())
+    }
+}
+
+fn storage_config(dir: &PathBuf) -> StorageConfig {
+    let mut config = StorageConfig::default();
+    config.set_relay_log_dir(dir.to_str().unwrap().to_string());
+    config
+}
+
+fn bootstrap(config: &StorageConfig, chunk_size: usize) -> SnapshotBootstrap {
+    SnapshotBootstrap::new(ConnectionOptions::default(), config.clone())
+        .with_table("db1", "t1")
+        .with_chunk_size(chunk_size)
+}
+
+/// 中继日志中的全部命令
+fn commands(config: &StorageConfig) -> Vec<RelayCommand> {
+    let mut storage = RelayLogStorage::new(config, "db1".to_string(), "t1".to_string()).unwrap();
+    match storage.index_range().unwrap() {
+        Some((first, last)) => (first..=last)
+            .map(|i| storage.get_entry(i).unwrap().relay_log().relay_command().clone())
+            .collect(),
+        None => vec![],
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_chunks() {
+    let dir = temp_dir("snapshot_chunks");
+    let config = storage_config(&dir);
+    let mut source = MockSource::new(vec![("id", "int", "int"), ("name", "varchar", "varchar(32)")], vec!["id"])
+        .with_chunk(vec![vec![Some("1"), Some("a"), Some("1")], vec![Some("2"), Some("b"), Some("2")]])
+        .with_chunk(vec![vec![Some("3"), Some("c"), Some("3")]]);
+
+    let (position, stats) = bootstrap(&config, 2).snapshot_with(&mut source).await.unwrap();
+    assert_eq!((position.binlog_file.as_str(), position.binlog_position), ("mysql-bin.000003", 1024));
+    assert_eq!(stats, SnapshotStats { tables: 1, rows: 3 });
+
+    // 第二块从第一块最后一行的主键之后开始, 不足一块时结束
+    let sqls = source.chunk_sqls();
+    assert_eq!(sqls.len(), 2);
+    assert_eq!(sqls[0].as_str(), "SELECT `id`, `name`, `id` FROM `db1`.`t1` ORDER BY `id` LIMIT 2");
+    assert_eq!(sqls[1].as_str(), "SELECT `id`, `name`, `id` FROM `db1`.`t1` WHERE (`id`) > ('2') ORDER BY `id` LIMIT 2");
+
+    // Rotate, 每块一条 Insert 与提交标记
+    let commands = commands(&config);
+    assert_eq!(commands.len(), 5);
+    assert!(matches!(&commands[0], RelayCommand::Rotate(f) if f == "mysql-bin.000003"));
+    assert!(matches!(&commands[1], RelayCommand::Insert(rows) if rows.len() == 2));
+    assert!(matches!(&commands[2], RelayCommand::Commit(_)));
+    assert!(matches!(&commands[3], RelayCommand::Insert(rows) if rows.len() == 1));
+    assert!(matches!(&commands[4], RelayCommand::Commit(_)));
+
+    // 中继日志不为空时不能再次快照
+    let mut source = MockSource::new(vec![("id", "int", "int")], vec!["id"]);
+    assert!(bootstrap(&config, 2).snapshot_with(&mut source).await.is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_snapshot_cursor_quote() {
+    let dir = temp_dir("snapshot_cursor");
+    let config = storage_config(&dir);
+    // 二进制与字符串组成的复合主键
+    let mut source = MockSource::new(vec![("k", "varbinary", "varbinary(16)"), ("n", "varchar", "varchar(16)"), ("v", "int", "int")], vec!["k", "n"])
+        .with_chunk(vec![vec![Some("0A0B"), Some("it's"), Some("1"), Some("0A0B"), Some("it's")]])
+        .with_chunk(vec![]);
+
+    let (_, stats) = bootstrap(&config, 1).snapshot_with(&mut source).await.unwrap();
+    assert_eq!(stats.rows, 1);
+    let sqls = source.chunk_sqls();
+    assert_eq!(sqls[0].as_str(), "SELECT HEX(`k`), `n`, `v`, HEX(`k`), `n` FROM `db1`.`t1` ORDER BY `k`, `n` LIMIT 1");
+    assert_eq!(sqls[1].as_str(), "SELECT HEX(`k`), `n`, `v`, HEX(`k`), `n` FROM `db1`.`t1` WHERE (`k`, `n`) > (X'0A0B', 'it\\'s') ORDER BY `k`, `n` LIMIT 1");
+
+    let commands = commands(&config);
+    match &commands[1] {
+        RelayCommand::Insert(rows) => assert_eq!(format!("{:?}", rows[0].values()),
+                                                 format!("{:?}", vec![Value::Blob(vec![10, 11]), Value::String("it's".to_string()), Value::Long(1)])),
+        c => panic!("unexpected {:?}", c),
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_snapshot_without_primary_key() {
+    let dir = temp_dir("snapshot_no_pk");
+    let config = storage_config(&dir);
+    let mut source = MockSource::new(vec![("id", "int", "int"), ("name", "varchar", "varchar(32)")], vec![])
+        .with_chunk(vec![vec![Some("1"), Some("a")], vec![Some("1"), Some("b")]])
+        .with_chunk(vec![vec![Some("2"), Some("a")]]);
+
+    let (_, stats) = bootstrap(&config, 2).snapshot_with(&mut source).await.unwrap();
+    assert_eq!(stats.rows, 3);
+    // 按全部列排序, 各块的顺序一致
+    let sqls = source.chunk_sqls();
+    assert_eq!(sqls[0].as_str(), "SELECT `id`, `name` FROM `db1`.`t1` ORDER BY `id`, `name` LIMIT 0, 2");
+    assert_eq!(sqls[1].as_str(), "SELECT `id`, `name` FROM `db1`.`t1` ORDER BY `id`, `name` LIMIT 2, 2");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_snapshot_unlock_on_error() {
+    let dir = temp_dir("snapshot_unlock");
+    let config = storage_config(&dir);
+    let mut source = MockSource::new(vec![("id", "int", "int")], vec!["id"]);
+    source.binlog_enabled = false;
+
+    assert!(bootstrap(&config, 2).snapshot_with(&mut source).await.is_err());
+    let lock = source.sqls.iter().position(|s| s == "FLUSH TABLES WITH READ LOCK").unwrap();
+    let unlock = source.sqls.iter().position(|s| s == "UNLOCK TABLES").unwrap();
+    assert!(lock < unlock);
+    // 没有读取表
+    assert!(source.chunk_sqls().is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_snapshot_retry_after_failure() {
+    let dir = temp_dir("snapshot_retry");
+    let config = storage_config(&dir);
+    // 第二块读取失败
+    let mut source = MockSource::new(vec![("id", "int", "int")], vec!["id"])
+        .with_chunk(vec![vec![Some("1"), Some("1")], vec![Some("2"), Some("2")]]);
+    source.chunks.push_back(None);
+    assert!(bootstrap(&config, 2).snapshot_with(&mut source).await.is_err());
+    assert!(!source.sqls.contains(&"COMMIT".to_string()));
+    // 已读取的块只在暂存目录中
+    assert!(commands(&config).is_empty());
+
+    // 重试时清理暂存的日志
+    let mut source = MockSource::new(vec![("id", "int", "int")], vec!["id"])
+        .with_chunk(vec![vec![Some("1"), Some("1")], vec![Some("2"), Some("2")]])
+        .with_chunk(vec![vec![Some("3"), Some("3")]]);
+    let (_, stats) = bootstrap(&config, 2).snapshot_with(&mut source).await.unwrap();
+    assert_eq!(stats.rows, 3);
+    assert_eq!(commands(&config).len(), 5);
+    fs::remove_dir_all(dir).unwrap();
+}
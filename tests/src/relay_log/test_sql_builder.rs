@@ -0,0 +1,84 @@
+use common::schema::data_type::{DstColumnType, Value};
+use relay_log::apply::conflict::{ConflictPolicies, ConflictPolicy};
+use relay_log::apply::sql_builder::SqlBuilder;
+use relay_log::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+
+fn relay_log(relay_command: RelayCommand) -> RelayLog {
+    let mut relay_log = RelayLog::default();
+    relay_log.set_database_name("db1".to_string());
+    relay_log.set_table_name("t`1".to_string());
+    relay_log.set_columns(["id", "name"].iter().map(|n| {
+        let mut c = RelayColumnInfo::default();
+        c.set_column_type(DstColumnType::String);
+        c.set_column_name(n.to_string());
+        c
+    }).collect());
+    relay_log.set_relay_command(relay_command);
+    relay_log
+}
+
+fn row(id: i32, name: Option<&str>) -> RelayRowData {
+    let mut row = RelayRowData::default();
+    row.set_values(vec![Value::Int(id), name.map_or(Value::Null, |n| Value::String(n.to_string()))]);
+    row
+}
+
+#[test]
+fn test_build_dml() {
+    let insert = relay_log(RelayCommand::Insert(vec![row(1, Some("a'b")), row(2, None)]));
+    assert_eq!(SqlBuilder::build(&insert).unwrap(),
+               vec!["INSERT INTO `db1`.`t``1` (`id`, `name`) VALUES (1, 'a\\'b'), (2, NULL)"]);
+
+    let update = relay_log(RelayCommand::Update(vec![(row(2, None), row(2, Some("c")))]));
+    assert_eq!(SqlBuilder::build(&update).unwrap(),
+               vec!["UPDATE `db1`.`t``1` SET `id` = 2, `name` = 'c' WHERE `id` <=> 2 AND `name` <=> NULL LIMIT 1"]);
+
+    let delete = relay_log(RelayCommand::Delete(vec![row(1, Some("a"))]));
+    assert_eq!(SqlBuilder::build(&delete).unwrap(),
+               vec!["DELETE FROM `db1`.`t``1` WHERE `id` <=> 1 AND `name` <=> 'a' LIMIT 1"]);
+
+    assert!(SqlBuilder::build(&relay_log(RelayCommand::Commit(7))).unwrap().is_empty());
+    let mut mismatch = row(3, None);
+    mismatch.set_values(vec![Value::Int(3)]);
+    assert!(SqlBuilder::build(&relay_log(RelayCommand::Insert(vec![mismatch]))).is_err());
+}
+
+#[test]
+fn test_conflict_statements() {
+    let insert = relay_log(RelayCommand::Insert(vec![row(1, Some("a")), row(2, None)]));
+    let statements = SqlBuilder::statements(&insert, ConflictPolicy::Overwrite).unwrap();
+    assert_eq!(statements.len(), 1);
+    assert_eq!(statements[0].sql, "REPLACE INTO `db1`.`t``1` (`id`, `name`) VALUES (1, 'a'), (2, NULL)");
+
+    // 跳过冲突时逐行执行
+    let statements = SqlBuilder::statements(&insert, ConflictPolicy::Skip).unwrap();
+    assert_eq!(statements.len(), 2);
+    assert!(statements.iter().all(|s| !s.expect_rows && s.overwrite.is_none()));
+
+    let update = relay_log(RelayCommand::Update(vec![(row(2, None), row(2, Some("c"))), (row(3, None), row(3, None))]));
+    let statements = SqlBuilder::statements(&update, ConflictPolicy::Overwrite).unwrap();
+    assert_eq!(statements[0].overwrite.as_deref(), Some("REPLACE INTO `db1`.`t``1` (`id`, `name`) VALUES (2, 'c')"));
+    assert!(statements[0].expect_rows);
+    // 值没有变化的 Update 影响 0 行, 不视为冲突
+    assert!(!statements[1].expect_rows);
+
+    let delete = relay_log(RelayCommand::Delete(vec![row(1, Some("a"))]));
+    let statements = SqlBuilder::statements(&delete, ConflictPolicy::Overwrite).unwrap();
+    assert!(statements[0].expect_rows && statements[0].overwrite.is_none());
+
+    let policies = ConflictPolicies::new(ConflictPolicy::Skip).with_table("db1", "t1", ConflictPolicy::Record);
+    assert_eq!(policies.policy("db1", "t1"), ConflictPolicy::Record);
+    assert_eq!(policies.policy("db1", "t2"), ConflictPolicy::Skip);
+    assert!(policies.records());
+    assert!(!ConflictPolicies::default().records());
+}
+
+#[test]
+fn test_literal() {
+    assert_eq!(SqlBuilder::literal(&Value::Blob(vec![0x01, 0xab])), "X'01ab'");
+    assert_eq!(SqlBuilder::literal(&Value::DateTime(86_400_000 + 1_500)), "'1970-01-02 00:00:01.500'");
+    assert_eq!(SqlBuilder::literal(&Value::Date(86_400_000)), "'1970-01-02'");
+    assert_eq!(SqlBuilder::literal(&Value::Timestamp(1_700_000_000_123)), "FROM_UNIXTIME(1700000000.123)");
+    assert_eq!(SqlBuilder::literal(&Value::Double(f64::NAN)), "NULL");
+    assert_eq!(SqlBuilder::literal(&Value::String("a\\\n".to_string())), "'a\\\\\\n'");
+}
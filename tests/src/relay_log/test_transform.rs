@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use common::err::CResult;
+use common::schema::data_type::{DstColumnType, Value};
+use relay_log::apply::transform::{RelayLogTransform, TransformChain};
+use relay_log::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+
+fn relay_log(relay_command: RelayCommand) -> RelayLog {
+    let mut relay_log = RelayLog::default();
+    relay_log.set_database_name("db1".to_string());
+    relay_log.set_table_name("t1".to_string());
+    relay_log.set_columns(["id", "tenant", "phone"].iter().map(|n| {
+        let mut c = RelayColumnInfo::default();
+        c.set_column_type(DstColumnType::String);
+        c.set_column_name(n.to_string());
+        c
+    }).collect());
+    relay_log.set_relay_command(relay_command);
+    relay_log
+}
+
+fn row(id: i32, tenant: i32) -> RelayRowData {
+    let mut row = RelayRowData::default();
+    row.set_values(vec![Value::Int(id), Value::Int(tenant), Value::String("13800000000".to_string())]);
+    row
+}
+
+/// 丢弃 t2 的全部日志
+#[derive(Debug)]
+struct DropTable;
+
+impl RelayLogTransform for DropTable {
+    fn name(&self) -> String {
+        String::from("DropTable")
+    }
+
+    fn transform(&self, log: RelayLog) -> CResult<Option<RelayLog>> {
+        Ok(if log.table_name() == "t2" { None } else { Some(log) })
+    }
+}
+
+const TRANSFORMS: &str = r#"
+[[transform]]
+type = "filter_rows"
+table = "t1"
+column = "tenant"
+values = ["1"]
+
+[[transform]]
+type = "set_value"
+table = "db1.t1"
+column = "phone"
+
+[[transform]]
+type = "rename_column"
+table = "db1.*"
+column = "tenant"
+to = "tenant_id"
+
+[[transform]]
+type = "rename_table"
+table = "db1.t1"
+to = "db2.t2"
+"#;
+
+#[test]
+fn test_transform_chain() {
+    let chain = TransformChain::from_toml(TRANSFORMS).unwrap();
+    let log = chain.apply(relay_log(RelayCommand::Insert(vec![row(1, 1), row(2, 2)]))).unwrap().unwrap();
+    assert_eq!(log.database_name(), "db2");
+    assert_eq!(log.table_name(), "t2");
+    assert_eq!(log.columns()[1].column_name(), "tenant_id");
+    match log.relay_command() {
+        RelayCommand::Insert(rows) => {
+            assert_eq!(rows.len(), 1);
+            assert_eq!(format!("{:?}", rows[0].values()), format!("{:?}", vec![Value::Int(1), Value::Int(1), Value::Null]));
+        }
+        c => panic!("unexpected command {:?}", c),
+    }
+
+    // 过滤后没有行时丢弃整条日志, 提交标记不受影响
+    assert!(chain.apply(relay_log(RelayCommand::Update(vec![(row(2, 1), row(2, 2))]))).unwrap().is_none());
+    assert!(chain.apply(relay_log(RelayCommand::Commit(3))).unwrap().is_some());
+
+    // 注册的变换在声明的变换之后执行
+    let chain = chain.with_transform(Arc::new(DropTable));
+    assert!(chain.apply(relay_log(RelayCommand::Delete(vec![row(1, 1)]))).unwrap().is_none());
+
+    assert!(TransformChain::from_toml("[[transform]]\ntype = \"unknown\"").is_err());
+}
+
+const MAPPINGS: &str = r#"
+[[mapping]]
+type = "drop_column"
+table = "db1.t1"
+column = "phone"
+
+[[mapping]]
+type = "cast_column"
+table = "db1.t1"
+column = "id"
+to = "long"
+
+[[mapping]]
+type = "computed_column"
+table = "db1.t1"
+column = "tenant_key"
+expr = "{tenant}-{id}"
+
+[[mapping]]
+type = "rename_table"
+table = "db1.t1"
+to = "users"
+"#;
+
+#[test]
+fn test_column_mapping() {
+    let chain = TransformChain::from_toml(MAPPINGS).unwrap();
+    let log = chain.apply(relay_log(RelayCommand::Update(vec![(row(1, 2), row(1, 3))]))).unwrap().unwrap();
+    assert_eq!(log.database_name(), "db1");
+    assert_eq!(log.table_name(), "users");
+    let columns: Vec<&str> = log.columns().iter().map(|c| c.column_name().as_str()).collect();
+    assert_eq!(columns, vec!["id", "tenant", "tenant_key"]);
+    match log.relay_command() {
+        RelayCommand::Update(rows) => {
+            assert_eq!(format!("{:?}", rows[0].0.values()),
+                       format!("{:?}", vec![Value::Long(1), Value::Int(2), Value::String("2-1".to_string())]));
+            assert_eq!(format!("{:?}", rows[0].1.values()),
+                       format!("{:?}", vec![Value::Long(1), Value::Int(3), Value::String("3-1".to_string())]));
+        }
+        c => panic!("unexpected command {:?}", c),
+    }
+
+    // 提交标记等没有列的日志只修改库表名
+    let log = chain.apply(relay_log(RelayCommand::Commit(3))).unwrap().unwrap();
+    assert_eq!(log.table_name(), "users");
+
+    assert!(TransformChain::from_toml("[[mapping]]\ntype = \"cast_column\"\ntable = \"t1\"\ncolumn = \"id\"\nto = \"uuid\"").is_err());
+}
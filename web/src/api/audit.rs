@@ -0,0 +1,24 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+
+use crate::api::result::R;
+use crate::audit::{self, AuditQuery};
+use crate::namespace::request_namespace;
+
+/// GET http://127.0.0.1:8080/api/audit?action=task&from_ts=&to_ts=&limit=100
+///
+/// 请求所属命名空间的管理操作记录, 按时间倒序。 action 可以是完整的操作名或前缀, 如 `task`
+#[get("/api/audit")]
+async fn audit_log(req: HttpRequest, query: web::Query<AuditQuery>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+    };
+    let query = query.into_inner();
+    let rs = web::block(move || audit::query(&namespace, &query)).await;
+
+    match rs {
+        Ok(Ok(entries)) => HttpResponse::Ok().json(entries),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(R::error(500, &e.to_string())),
+        Err(e) => HttpResponse::InternalServerError().json(R::error(500, &e.to_string())),
+    }
+}
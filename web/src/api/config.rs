@@ -0,0 +1,39 @@
+use actix_web::{get, post, HttpRequest, HttpResponse, Responder};
+use serde_json::json;
+
+use crate::api::result::R;
+use crate::audit;
+use crate::config::runtime;
+use crate::namespace::require_admin;
+
+/// GET http://127.0.0.1:8080/api/config
+///
+/// 运行时配置对所有命名空间生效, 开启鉴权时只有管理命名空间可以访问
+#[get("/api/config")]
+async fn get_config(req: HttpRequest) -> impl Responder {
+    if let Err(e) = require_admin(&req) {
+        return HttpResponse::Forbidden().json(R::error(403, &e.to_string()));
+    }
+    HttpResponse::Ok().json(json!({ "config": runtime::current_value() }))
+}
+
+/// POST http://127.0.0.1:8080/api/config
+///
+/// 请求体为与配置文件相同格式的 TOML。 校验通过后立即生效, 过滤规则、脱敏与限速对运行中的任务
+/// 无需重启, 返回配置的变化。 开启鉴权时只有管理命名空间可以修改
+#[post("/api/config")]
+async fn reload_config(req: HttpRequest, body: String) -> impl Responder {
+    if let Err(e) = require_admin(&req) {
+        return HttpResponse::Forbidden().json(R::error(403, &e.to_string()));
+    }
+    let rs = runtime::reload(&body);
+    // 只记录变化的配置项, 新旧值中可能含有密码
+    let paths: Vec<&str> = rs.as_ref().map(|r| r.changes.iter().map(|c| c.path.as_str()).collect()).unwrap_or_default();
+    let target = rs.as_ref().map(|r| r.version.to_string()).unwrap_or_default();
+    audit::record(&req, "config.reload", &target, json!({ "changes": paths }), &rs);
+
+    match rs {
+        Ok(rs) => HttpResponse::Ok().json(rs),
+        Err(e) => HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use serde::Serialize;
+
+use common::pretty_util::parse_bytes_len;
+
+use crate::config::constant::CFG;
+use crate::config::runtime::current_sink;
+use crate::task::task_manager::TaskManager;
+
+/// 依赖检查的连接超时, 探针的超时一般为 1~3 秒
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(800);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Up,
+    Down,
+    /// 未启用, 不影响就绪
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Component {
+    pub name: &'static str,
+    pub status: ComponentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Component {
+    fn new(name: &'static str, status: ComponentStatus, message: Option<String>) -> Self {
+        Component { name, status, message }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    /// 任一组件为 down 时为 down
+    pub status: ComponentStatus,
+    pub components: Vec<Component>,
+}
+
+impl HealthReport {
+    fn new(components: Vec<Component>) -> Self {
+        let down = components.iter().any(|c| c.status == ComponentStatus::Down);
+        HealthReport {
+            status: if down { ComponentStatus::Down } else { ComponentStatus::Up },
+            components,
+        }
+    }
+}
+
+/// GET http://127.0.0.1:8080/healthz
+///
+/// 存活探针, 只表示进程能响应请求。 依赖不可用时重启进程无济于事, 因此不做依赖检查
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(HealthReport::new(vec![]))
+}
+
+/// GET http://127.0.0.1:8080/readyz
+///
+/// 就绪探针: 检查运行中任务的 MySQL、中继日志磁盘空间、raft 与 sink, 任一不可用时返回 503
+#[get("/readyz")]
+async fn readyz() -> impl Responder {
+    let rs = web::block(|| HealthReport::new(vec![check_mysql(), check_relay_log(), check_raft(), check_sink()])).await;
+
+    match rs {
+        Ok(report) if report.status == ComponentStatus::Down => HttpResponse::ServiceUnavailable().json(report),
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => HttpResponse::ServiceUnavailable()
+            .json(HealthReport::new(vec![Component::new("web", ComponentStatus::Down, Some(e.to_string()))])),
+    }
+}
+
+/// 运行中任务连接的 MySQL 是否可以建立 TCP 连接
+fn check_mysql() -> Component {
+    let addrs: BTreeSet<String> = TaskManager::all().iter()
+        .filter(|t| t.is_active())
+        .map(|t| format!("{}:{}", t.get_binlog_config().get_host(), t.get_binlog_config().get_port()))
+        .collect();
+    if addrs.is_empty() {
+        return Component::new("mysql", ComponentStatus::Disabled, Some(String::from("no running task")));
+    }
+
+    let failed: Vec<String> = addrs.iter().filter_map(|addr| tcp_check(addr).err()).collect();
+    if failed.is_empty() {
+        Component::new("mysql", ComponentStatus::Up, Some(addrs.into_iter().collect::<Vec<_>>().join(",")))
+    } else {
+        Component::new("mysql", ComponentStatus::Down, Some(failed.join("; ")))
+    }
+}
+
+/// 中继日志所在磁盘的可用空间不低于 HEALTH_MIN_FREE_DISK
+fn check_relay_log() -> Component {
+    let dir = CFG.get("RELAY_LOG_DIR").cloned().unwrap_or_default();
+    let min_free = CFG.get("HEALTH_MIN_FREE_DISK").map_or(Ok(0), |v| parse_bytes_len(v));
+    let min_free = match min_free {
+        Ok(v) => v,
+        Err(e) => return Component::new("relay_log", ComponentStatus::Down, Some(e.to_string())),
+    };
+
+    match available_space(Path::new(&dir)) {
+        Ok(free) => disk_status(free, min_free),
+        Err(e) => Component::new("relay_log", ComponentStatus::Down, Some(format!("{}: {}", dir, e))),
+    }
+}
+
+fn disk_status(free: u64, min_free: u64) -> Component {
+    let message = Some(format!("{} bytes available, require {}", free, min_free));
+    if free < min_free {
+        Component::new("relay_log", ComponentStatus::Down, message)
+    } else {
+        Component::new("relay_log", ComponentStatus::Up, message)
+    }
+}
+
+/// 目录还未创建时检查最近的已存在的上级目录
+#[cfg(unix)]
+fn available_space(dir: &Path) -> std::io::Result<u64> {
+    let dir = dir.ancestors().find(|p| p.exists()).unwrap_or(Path::new("/"));
+    let stat = nix::sys::statvfs::statvfs(dir).map_err(std::io::Error::from)?;
+
+    Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> std::io::Result<u64> {
+    Ok(u64::MAX)
+}
+
+/// web 服务目前以单机方式运行, 未启用 raft
+fn check_raft() -> Component {
+    Component::new("raft", ComponentStatus::Disabled, Some(String::from("raft is not enabled")))
+}
+
+/// [sink] 为 kafka 时检查 broker 能否连接, 为 file 时检查输出目录是否存在
+fn check_sink() -> Component {
+    let sink = match current_sink() {
+        Some(s) if s.sink_type.is_some() => s,
+        _ => return Component::new("sink", ComponentStatus::Disabled, None),
+    };
+
+    match sink.sink_type.as_deref().unwrap_or_default() {
+        "kafka" => {
+            let brokers: Vec<String> = sink.kafka_brokers.unwrap_or_default().split(',')
+                .map(|b| b.trim().to_string())
+                .filter(|b| !b.is_empty())
+                .collect();
+            // 任一 broker 可用即可获取集群元数据
+            let errors: Vec<String> = brokers.iter().map_while(|b| tcp_check(b).err()).collect();
+            if brokers.is_empty() || errors.len() == brokers.len() {
+                Component::new("sink", ComponentStatus::Down, Some(format!("kafka brokers unavailable: {}", errors.join("; "))))
+            } else {
+                Component::new("sink", ComponentStatus::Up, Some(String::from("kafka")))
+            }
+        },
+        "file" => {
+            let path = sink.path.unwrap_or_default();
+            let parent = Path::new(&path).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            if parent.is_dir() {
+                Component::new("sink", ComponentStatus::Up, Some(path))
+            } else {
+                Component::new("sink", ComponentStatus::Down, Some(format!("directory {} not found", parent.display())))
+            }
+        },
+        other => Component::new("sink", ComponentStatus::Up, Some(other.to_string())),
+    }
+}
+
+fn tcp_check(addr: &str) -> Result<(), String> {
+    let mut addrs = addr.to_socket_addrs().map_err(|e| format!("{}: {}", addr, e))?;
+    let socket = addrs.next().ok_or_else(|| format!("{}: no address", addr))?;
+
+    TcpStream::connect_timeout(&socket, CONNECT_TIMEOUT)
+        .map(|_| ())
+        .map_err(|e| format!("{}: {}", addr, e))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::api::health::{check_raft, disk_status, Component, ComponentStatus, HealthReport};
+
+    #[test]
+    fn test_report() {
+        assert_eq!(disk_status(10, 5).status, ComponentStatus::Up);
+        assert_eq!(disk_status(1, 5).status, ComponentStatus::Down);
+
+        let report = HealthReport::new(vec![check_raft(), Component::new("mysql", ComponentStatus::Up, None)]);
+        assert_eq!(report.status, ComponentStatus::Up);
+        assert_eq!(serde_json::to_string(&report).unwrap(),
+                   r#"{"status":"up","components":[{"name":"raft","status":"disabled","message":"raft is not enabled"},{"name":"mysql","status":"up"}]}"#);
+
+        let report = HealthReport::new(vec![disk_status(0, 1)]);
+        assert_eq!(report.status, ComponentStatus::Down);
+    }
+}
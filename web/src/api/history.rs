@@ -0,0 +1,203 @@
+
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use common::pretty_util::{RowTable, TableStyle};
+use common::schema::data_type::Value;
+use relay_log::relay_log::{RelayCommand, RelayLog, RelayRowData};
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::api::result::R;
+use crate::config::constant::CFG;
+use crate::namespace::request_namespace;
+use crate::web_error::{WebError, WResult};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+/// GET /api/events 的查询参数, table 为 `db.table`, 时间为秒级时间戳, page 从 1 开始。
+/// preview 为 ascii | markdown 时为行变更附带表格预览
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventQuery {
+    pub table: String,
+    pub from_ts: Option<u32>,
+    pub to_ts: Option<u32>,
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+    pub preview: Option<String>,
+}
+
+impl EventQuery {
+    fn contains(&self, timestamp: u32) -> bool {
+        self.from_ts.map_or(true, |from| timestamp >= from) && self.to_ts.map_or(true, |to| timestamp <= to)
+    }
+
+    fn preview_style(&self) -> WResult<Option<TableStyle>> {
+        match self.preview.as_deref() {
+            None => Ok(None),
+            Some(style) => TableStyle::try_from(style).map(Some).map_err(|e| WebError::Value(e.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryEvent {
+    /// 中继日志中的 index
+    pub index: u64,
+    pub log: RelayLog,
+    /// 行变更的表格预览
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventPage {
+    pub page: usize,
+    pub page_size: usize,
+    /// 满足条件的事件总数
+    pub total: usize,
+    pub events: Vec<HistoryEvent>,
+}
+
+/// GET http://127.0.0.1:8080/api/events?table=db1.t1&from_ts=&to_ts=&page=1
+///
+/// 从请求所属命名空间的中继日志中分页读取指定表的历史变更事件, 按 index 升序
+#[get("/api/events")]
+async fn history_events(req: HttpRequest, query: web::Query<EventQuery>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+    };
+    let query = query.into_inner();
+    let rs = web::block(move || read_page(&namespace, &query)).await;
+
+    match rs {
+        Ok(Ok(page)) => HttpResponse::Ok().json(page),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+        Err(e) => HttpResponse::InternalServerError().json(R::error(500, &e.to_string())),
+    }
+}
+
+fn read_page(namespace: &str, query: &EventQuery) -> WResult<EventPage> {
+    let (db, table) = query.table.split_once('.')
+        .ok_or_else(|| WebError::Value(format!("invalid table {}, expect db.table", query.table)))?;
+    query.preview_style()?;
+
+    let mut config = StorageConfig::default();
+    // 每个命名空间是 RELAY_LOG_DIR 下的一个分区
+    config.set_relay_log_dir(CFG.get("RELAY_LOG_DIR").cloned().unwrap_or_default());
+    config.set_partition(namespace.to_string());
+
+    // 没有该表的中继日志时返回空页, 不创建目录
+    let dir = SegmentManager::get_segment_dir_path(&config.partition_dir(), db, table)?;
+    if !SegmentManager::has_segments(&dir)? {
+        return Ok(page_of(std::iter::empty(), query));
+    }
+
+    // 写入方可能正在写入, 只读打开; 通过时间戳索引跳过时间范围之外的 segment 与 entry
+    let mut storage = RelayLogStorage::open_read_only(&config, db.to_string(), table.to_string())?;
+    let mut entries = vec![];
+    for entry in storage.scan_range(query.from_ts.unwrap_or(0), query.to_ts.unwrap_or(u32::MAX))? {
+        let entry = entry?;
+        entries.push((*entry.index(), entry.relay_log().clone()));
+    }
+
+    Ok(page_of(entries.into_iter(), query))
+}
+
+fn page_of<I: Iterator<Item = (u64, RelayLog)>>(entries: I, query: &EventQuery) -> EventPage {
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let skip = (page - 1) * page_size;
+    let style = query.preview_style().ok().flatten();
+
+    let mut total = 0;
+    let mut events = vec![];
+    for (index, log) in entries.filter(|(_, log)| query.contains(*log.event_timestamp())) {
+        if total >= skip && events.len() < page_size {
+            let preview = style.and_then(|style| preview(&log, style));
+            events.push(HistoryEvent { index, log, preview });
+        }
+        total += 1;
+    }
+
+    EventPage { page, page_size, total, events }
+}
+
+/// 行变更的表格, 其他事件为 None
+fn preview(log: &RelayLog, style: TableStyle) -> Option<String> {
+    let columns = log.columns().iter().map(|c| c.column_name().clone()).collect();
+    let texts = |row: &RelayRowData| row.values().iter().map(Value::text).collect::<Vec<_>>();
+
+    let mut table = RowTable::new(style, columns);
+    match log.relay_command() {
+        RelayCommand::Insert(rows) => rows.iter().for_each(|r| table.add_row("I", &texts(r))),
+        RelayCommand::Delete(rows) => rows.iter().for_each(|r| table.add_row("D", &texts(r))),
+        RelayCommand::Update(rows) => rows.iter().for_each(|(before, after)| table.add_update(&texts(before), &texts(after))),
+        _ => return None,
+    }
+    Some(table.render())
+}
+
+#[cfg(test)]
+mod test {
+    use common::pretty_util::TableStyle;
+    use common::schema::data_type::Value;
+    use relay_log::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+
+    use crate::api::history::{page_of, preview, EventQuery};
+
+    #[test]
+    fn test_page_of() {
+        let entries = (1..=10u64).map(|i| {
+            let mut log = RelayLog::default();
+            log.set_event_timestamp(100 + i as u32);
+            (i, log)
+        });
+        let query = EventQuery {
+            table: String::from("db1.t1"),
+            from_ts: Some(103),
+            to_ts: Some(109),
+            page: Some(2),
+            page_size: Some(3),
+            preview: None,
+        };
+
+        let page = page_of(entries, &query);
+        assert_eq!(page.total, 7);
+        let indexes: Vec<u64> = page.events.iter().map(|e| e.index).collect();
+        assert_eq!(indexes, vec![6, 7, 8]);
+    }
+
+    #[test]
+    fn test_preview() {
+        let column = |name: &str| {
+            let mut c = RelayColumnInfo::default();
+            c.set_column_name(name.to_string());
+            c
+        };
+        let row = |values: Vec<Value>| {
+            let mut r = RelayRowData::default();
+            r.set_values(values);
+            r
+        };
+
+        let mut log = RelayLog::default();
+        log.set_columns(vec![column("id"), column("name")]);
+        log.set_relay_command(RelayCommand::Update(vec![(
+            row(vec![Value::Int(1), Value::String(String::from("a"))]),
+            row(vec![Value::Int(1), Value::Null]),
+        )]));
+
+        let expect = "\
+| op | id | name     |
+|----|----|----------|
+| U- | 1  | a        |
+| U+ | 1  | **NULL** |
+";
+        assert_eq!(Some(String::from(expect)), preview(&log, TableStyle::Markdown));
+        assert_eq!(None, preview(&RelayLog::default(), TableStyle::Ascii));
+    }
+}
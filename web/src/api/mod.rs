@@ -1,3 +1,19 @@
+pub mod audit;
+
+pub mod config;
+
 pub mod default;
 
-pub mod result;
\ No newline at end of file
+pub mod health;
+
+pub mod history;
+
+pub mod result;
+
+pub mod schema;
+
+pub mod sse;
+
+pub mod task;
+
+pub mod webhook;
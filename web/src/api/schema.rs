@@ -0,0 +1,23 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+
+use crate::api::result::R;
+use crate::namespace::request_namespace;
+use crate::stream::schema_tracker::SCHEMA_TRACKER;
+
+/// GET http://127.0.0.1:8080/api/schema/{db}/{table}
+///
+/// 任务读取到的表结构: 列、类型、主键与历史版本, 订阅前可据此了解事件中的字段。
+/// 表结构来自 TableMapEvent, 列名与主键需要 binlog_row_metadata=FULL
+#[get("/api/schema/{db}/{table}")]
+async fn table_schema(req: HttpRequest, path: web::Path<(String, String)>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+    };
+    let (db, table) = path.into_inner();
+
+    match SCHEMA_TRACKER.get(&namespace, &db, &table) {
+        Some(history) => HttpResponse::Ok().json(history),
+        None => HttpResponse::NotFound().json(R::error(404, &format!("table {}.{} is not tracked yet", db, table))),
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use actix_web::{get, web, Error, HttpRequest, HttpResponse, Responder};
+use futures_util::{stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::api::result::R;
+use crate::namespace::request_namespace;
+use crate::stream::event_hub::{StreamEvent, EVENT_HUB};
+
+/// 没有事件时发送注释行的间隔, 避免代理断开空闲连接
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// GET http://127.0.0.1:8080/events
+///
+/// 以 Server-Sent Events 推送与 WebSocket 相同的事件流。 断线重连时按 `Last-Event-ID` 头
+/// (或 `?last_event_id=`) 从保留的历史事件中续传。 只推送请求所属命名空间的事件
+#[get("/events")]
+async fn events(req: HttpRequest) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+    };
+    let last_id = last_event_id(&req);
+    let (history, rx) = EVENT_HUB.subscribe(&namespace, last_id);
+    let last = history.last().map(|e| e.id).or(last_id).unwrap_or(0);
+
+    let history = stream::iter(history.into_iter().map(|e| Ok::<_, Error>(sse_frame(&e))));
+    let live = stream::unfold((rx, last, namespace), |(mut rx, last, namespace)| async move {
+        match tokio::time::timeout(KEEP_ALIVE_INTERVAL, rx.recv()).await {
+            Err(_) => Some((Ok::<_, Error>(Bytes::from_static(b": keep-alive\n\n")), (rx, last, namespace))),
+            // 追赶时已从 history 发送, 或属于其他命名空间
+            Ok(Ok(e)) if e.id <= last || e.namespace != namespace => Some((Ok(Bytes::new()), (rx, last, namespace))),
+            Ok(Ok(e)) => Some((Ok(sse_frame(&e)), (rx, e.id, namespace))),
+            // 消费过慢, 从 history 补齐
+            Ok(Err(RecvError::Lagged(_))) => {
+                let missed = EVENT_HUB.since(&namespace, last);
+                let id = missed.last().map_or(last, |e| e.id);
+                let frames: Vec<u8> = missed.iter().flat_map(|e| sse_frame(e).to_vec()).collect();
+                Some((Ok(Bytes::from(frames)), (rx, id, namespace)))
+            },
+            Ok(Err(RecvError::Closed)) => None,
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // 关闭 nginx 的响应缓冲
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(history.chain(live))
+}
+
+fn last_event_id(req: &HttpRequest) -> Option<u64> {
+    let header = req.headers().get("Last-Event-ID")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().parse().ok());
+    if header.is_some() {
+        return header;
+    }
+
+    web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()
+        .and_then(|q| q.get("last_event_id").and_then(|id| id.parse().ok()))
+}
+
+fn sse_frame(event: &StreamEvent) -> Bytes {
+    Bytes::from(format!("id: {}\nevent: binlog\ndata: {}\n\n", event.id, event.to_json()))
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    use crate::api::sse::{last_event_id, sse_frame};
+    use crate::stream::event_hub::StreamEvent;
+
+    #[test]
+    fn test_sse_frame() {
+        let event = StreamEvent { id: 7, namespace: String::from("default"), task: String::from("t1"), event_type: String::from("Query"), event: json!("BEGIN") };
+        assert_eq!(sse_frame(&event).as_ref(),
+                   b"id: 7\nevent: binlog\ndata: {\"id\":7,\"task\":\"t1\",\"type\":\"Query\",\"event\":\"BEGIN\"}\n\n");
+
+        let req = TestRequest::default().insert_header(("Last-Event-ID", "12")).to_http_request();
+        assert_eq!(last_event_id(&req), Some(12));
+        let req = TestRequest::with_uri("/events?last_event_id=3").to_http_request();
+        assert_eq!(last_event_id(&req), Some(3));
+        assert_eq!(last_event_id(&TestRequest::default().to_http_request()), None);
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::Path;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use serde_json::{json, Value};
+
+use relay_log::apply::replay_progress::ReplayStatus;
+
+use crate::api::result::R;
+use crate::audit;
+use crate::config::constant::CFG;
+use crate::namespace::request_namespace;
+use crate::task::binlog_task::{CreateTaskRequest, TaskView};
+use crate::task::task_manager::TaskManager;
+use crate::web_error::{WebError, WResult};
+
+/// POST http://127.0.0.1:8080/api/tasks
+#[post("/api/tasks")]
+async fn create_task(req: HttpRequest, request: web::Json<CreateTaskRequest>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    let rs = TaskManager::create(&namespace, &request);
+    let target = rs.as_ref().map(|t| t.get_id().to_string()).unwrap_or_default();
+    let config = request.binlog_config();
+    audit::record(&req, "task.create", &target,
+                  json!({ "name": request.name, "host": config.get_host(), "port": config.get_port() }), &rs);
+
+    match rs {
+        Ok(task) => HttpResponse::Created().json(task.view()),
+        Err(e) => to_response(Err(e), 429),
+    }
+}
+
+/// GET http://127.0.0.1:8080/api/tasks
+#[get("/api/tasks")]
+async fn list_tasks(req: HttpRequest) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return to_response(Err(e), 400),
+    };
+    let tasks: Vec<TaskView> = TaskManager::list(&namespace).iter().map(|t| t.view()).collect();
+
+    HttpResponse::Ok().json(tasks)
+}
+
+/// GET http://127.0.0.1:8080/api/tasks/{id}
+#[get("/api/tasks/{id}")]
+async fn task_status(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    to_response(TaskManager::get(&namespace, &id).map(|t| t.view()), 404)
+}
+
+#[post("/api/tasks/{id}/start")]
+async fn start_task(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    let task = match request_namespace(&req) {
+        Ok(ns) => match TaskManager::get(&ns, &id) {
+            Ok(t) => t,
+            Err(e) => return to_response(Err(e), 404),
+        },
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    let rs = task.start().map(|_| task.view());
+    audit::record(&req, "task.start", &id, Value::Null, &rs);
+    to_response(rs, 409)
+}
+
+#[post("/api/tasks/{id}/stop")]
+async fn stop_task(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    let task = match request_namespace(&req) {
+        Ok(ns) => match TaskManager::get(&ns, &id) {
+            Ok(t) => t,
+            Err(e) => return to_response(Err(e), 404),
+        },
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    let rs = task.stop().map(|_| task.view());
+    audit::record(&req, "task.stop", &id, Value::Null, &rs);
+    to_response(rs, 409)
+}
+
+/// 暂停读取, 保留与 MySQL 的连接, 用于下游维护等场景
+#[post("/api/tasks/{id}/pause")]
+async fn pause_task(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    let task = match request_namespace(&req) {
+        Ok(ns) => match TaskManager::get(&ns, &id) {
+            Ok(t) => t,
+            Err(e) => return to_response(Err(e), 404),
+        },
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    let rs = task.pause().map(|_| task.view());
+    audit::record(&req, "task.pause", &id, Value::Null, &rs);
+    to_response(rs, 409)
+}
+
+#[post("/api/tasks/{id}/resume")]
+async fn resume_task(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    let task = match request_namespace(&req) {
+        Ok(ns) => match TaskManager::get(&ns, &id) {
+            Ok(t) => t,
+            Err(e) => return to_response(Err(e), 404),
+        },
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    let rs = task.resume().map(|_| task.view());
+    audit::record(&req, "task.resume", &id, Value::Null, &rs);
+    to_response(rs, 409)
+}
+
+/// GET http://127.0.0.1:8080/api/tasks/{id}/health
+#[get("/api/tasks/{id}/health")]
+async fn task_health(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    let task = match request_namespace(&req) {
+        Ok(ns) => match TaskManager::get(&ns, &id) {
+            Ok(t) => t,
+            Err(e) => return to_response(Err(e), 404),
+        },
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    HttpResponse::Ok().json(task.health())
+}
+
+#[post("/api/tasks/{id}/delete")]
+async fn delete_task(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    let rs = TaskManager::remove(&namespace, &id).map(|t| t.view());
+    audit::record(&req, "task.delete", &id, Value::Null, &rs);
+    to_response(rs, 404)
+}
+
+/// GET http://127.0.0.1:8080/api/replays
+///
+/// 请求所属命名空间下各回放任务的进度、吞吐量与预计追平时间
+#[get("/api/replays")]
+async fn list_replays(req: HttpRequest) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+    };
+
+    match load_replays(&namespace) {
+        Ok(statuses) => HttpResponse::Ok().json(statuses),
+        Err(e) => HttpResponse::InternalServerError().json(R::error(500, &e.to_string())),
+    }
+}
+
+/// GET http://127.0.0.1:8080/api/replays/{name}
+#[get("/api/replays/{name}")]
+async fn replay_status(req: HttpRequest, name: web::Path<String>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return HttpResponse::BadRequest().json(R::error(400, &e.to_string())),
+    };
+
+    match load_replays(&namespace) {
+        Ok(statuses) => match statuses.into_iter().find(|s| s.name == *name) {
+            Some(status) => HttpResponse::Ok().json(status),
+            None => HttpResponse::NotFound().json(R::error(404, &format!("replay {} not found", name))),
+        },
+        Err(e) => HttpResponse::InternalServerError().json(R::error(500, &e.to_string())),
+    }
+}
+
+/// 回放任务写入 REPLAY_STATUS_DIR/{namespace} 的进度
+fn load_replays(namespace: &str) -> WResult<Vec<ReplayStatus>> {
+    let root = CFG.get("REPLAY_STATUS_DIR").cloned().unwrap_or_default();
+    let dir = Path::new(&root).join(namespace);
+    let dir = dir.to_str().ok_or_else(|| WebError::Value(format!("invalid replay status dir {:?}", dir)))?;
+
+    Ok(ReplayStatus::load_dir(dir)?)
+}
+
+/// 成功时返回任务状态, 失败时以 status 返回 R
+fn to_response(rs: WResult<TaskView>, status: u16) -> HttpResponse {
+    match rs {
+        Ok(view) => HttpResponse::Ok().json(view),
+        Err(e) => {
+            let code = actix_web::http::StatusCode::from_u16(status).unwrap();
+            HttpResponse::build(code).json(R::error(status, &e.to_string()))
+        },
+    }
+}
+
+/// 注册 /api/tasks 与 /api/replays 下的全部接口
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_task)
+        .service(list_tasks)
+        .service(task_status)
+        .service(start_task)
+        .service(stop_task)
+        .service(pause_task)
+        .service(resume_task)
+        .service(task_health)
+        .service(delete_task)
+        .service(list_replays)
+        .service(replay_status);
+}
@@ -0,0 +1,93 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use serde_json::{json, Value};
+
+use crate::api::result::R;
+use crate::audit;
+use crate::namespace::request_namespace;
+use crate::webhook::webhook::{WebhookRequest, WebhookView};
+use crate::webhook::webhook_manager::WebhookManager;
+use crate::web_error::WResult;
+
+/// POST http://127.0.0.1:8080/api/webhooks
+#[post("/api/webhooks")]
+async fn create_webhook(req: HttpRequest, request: web::Json<WebhookRequest>) -> impl Responder {
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return to_response(Err(e), 400),
+    };
+
+    let rs = WebhookManager::create(&namespace, &request);
+    let target = rs.as_ref().map(|v| v.id.clone()).unwrap_or_default();
+    audit::record(&req, "webhook.create", &target, audit_detail(&request), &rs);
+
+    match rs {
+        Ok(view) => HttpResponse::Created().json(view),
+        Err(e) => to_response(Err(e), 400),
+    }
+}
+
+/// GET http://127.0.0.1:8080/api/webhooks
+#[get("/api/webhooks")]
+async fn list_webhooks(req: HttpRequest) -> impl Responder {
+    match request_namespace(&req) {
+        Ok(ns) => HttpResponse::Ok().json(WebhookManager::list(&ns)),
+        Err(e) => to_response(Err(e), 400),
+    }
+}
+
+/// GET http://127.0.0.1:8080/api/webhooks/{id}
+#[get("/api/webhooks/{id}")]
+async fn get_webhook(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    match request_namespace(&req) {
+        Ok(ns) => to_response(WebhookManager::get(&ns, &id), 404),
+        Err(e) => to_response(Err(e), 400),
+    }
+}
+
+#[post("/api/webhooks/{id}/update")]
+async fn update_webhook(req: HttpRequest, id: web::Path<String>, request: web::Json<WebhookRequest>) -> impl Responder {
+    match request_namespace(&req) {
+        Ok(ns) => {
+            let rs = WebhookManager::update(&ns, &id, &request);
+            audit::record(&req, "webhook.update", &id, audit_detail(&request), &rs);
+            to_response(rs, 400)
+        },
+        Err(e) => to_response(Err(e), 400),
+    }
+}
+
+#[post("/api/webhooks/{id}/delete")]
+async fn delete_webhook(req: HttpRequest, id: web::Path<String>) -> impl Responder {
+    match request_namespace(&req) {
+        Ok(ns) => {
+            let rs = WebhookManager::remove(&ns, &id);
+            audit::record(&req, "webhook.delete", &id, Value::Null, &rs);
+            to_response(rs, 404)
+        },
+        Err(e) => to_response(Err(e), 400),
+    }
+}
+
+/// 审计记录中的 webhook 配置, 不包含 secret
+fn audit_detail(request: &WebhookRequest) -> Value {
+    json!({ "url": request.url, "tables": request.tables, "event_types": request.event_types })
+}
+
+fn to_response(rs: WResult<WebhookView>, status: u16) -> HttpResponse {
+    match rs {
+        Ok(view) => HttpResponse::Ok().json(view),
+        Err(e) => {
+            let code = actix_web::http::StatusCode::from_u16(status).unwrap();
+            HttpResponse::build(code).json(R::error(status, &e.to_string()))
+        },
+    }
+}
+
+/// 注册 /api/webhooks 下的全部接口
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_webhook)
+        .service(list_webhooks)
+        .service(get_webhook)
+        .service(update_webhook)
+        .service(delete_webhook);
+}
@@ -0,0 +1,192 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{HttpMessage, HttpRequest};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use common::time_util::now_str;
+
+use crate::auth::validator::Principal;
+use crate::config::constant::CFG;
+use crate::namespace::request_namespace;
+use crate::web_error::WResult;
+
+lazy_static! {
+    /// 追加写入的审计文件, 首次记录时打开
+    static ref AUDIT_FILE: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// 一条管理操作记录, 以 JSON Lines 追加到 AUDIT_LOG
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub time: String,
+    /// 秒级时间戳, 用于按时间查询
+    pub timestamp: u64,
+    /// 调用方, 未开启鉴权时为 anonymous
+    pub subject: String,
+    pub namespace: String,
+    /// 如 task.create、config.reload、webhook.delete
+    pub action: String,
+    /// 操作对象的 id
+    pub target: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub detail: Value,
+}
+
+/// 记录一次管理操作, 写入失败只打印日志, 不影响操作本身
+pub fn record<T>(req: &HttpRequest, action: &str, target: &str, detail: Value, rs: &WResult<T>) {
+    let subject = req.extensions().get::<Principal>()
+        .map_or_else(|| String::from("anonymous"), |p| p.subject.clone());
+    let entry = AuditEntry {
+        time: now_str(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+        subject,
+        namespace: request_namespace(req).unwrap_or_default(),
+        action: action.to_string(),
+        target: target.to_string(),
+        success: rs.is_ok(),
+        error: rs.as_ref().err().map(|e| e.to_string()),
+        detail,
+    };
+
+    if let Err(e) = append(&entry) {
+        log::error!("write audit log error: {}, entry: {:?}", e, entry);
+    }
+}
+
+fn append(entry: &AuditEntry) -> WResult<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    let mut file = AUDIT_FILE.lock()?;
+    if file.is_none() {
+        let path = audit_path();
+        if let Some(dir) = Path::new(&path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        *file = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+    }
+
+    let file = file.as_mut().unwrap();
+    file.write_all(line.as_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+fn audit_path() -> String {
+    CFG.get("AUDIT_LOG").cloned().unwrap_or_default()
+}
+
+/// GET /api/audit 的查询参数, 时间为秒级时间戳
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    pub action: Option<String>,
+    pub from_ts: Option<u64>,
+    pub to_ts: Option<u64>,
+    /// 默认 100, 最大 1000
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, namespace: &str, entry: &AuditEntry) -> bool {
+        entry.namespace == namespace
+            && self.action.as_ref().map_or(true, |a| entry.action == *a || entry.action.starts_with(&format!("{}.", a)))
+            && self.from_ts.map_or(true, |from| entry.timestamp >= from)
+            && self.to_ts.map_or(true, |to| entry.timestamp <= to)
+    }
+}
+
+/// 命名空间中满足条件的记录, 按时间倒序
+pub fn query(namespace: &str, query: &AuditQuery) -> WResult<Vec<AuditEntry>> {
+    let path = audit_path();
+    if !Path::new(&path).exists() {
+        return Ok(vec![]);
+    }
+
+    let reader = BufReader::new(File::open(&path)?);
+    Ok(filter(reader.lines().map_while(Result::ok), namespace, query))
+}
+
+/// 无法解析的行(如写入中断的最后一行)打印日志后跳过
+fn filter(lines: impl Iterator<Item = String>, namespace: &str, query: &AuditQuery) -> Vec<AuditEntry> {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+
+    let mut entries: Vec<AuditEntry> = lines
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| match serde_json::from_str::<AuditEntry>(&l) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("skip invalid audit log line: {}, line: {}", e, l);
+                None
+            }
+        })
+        .filter(|e| query.matches(namespace, e))
+        .collect();
+    entries.reverse();
+    entries.truncate(limit);
+
+    entries
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use crate::audit::{filter, AuditEntry, AuditQuery};
+
+    fn entry(namespace: &str, action: &str, timestamp: u64) -> String {
+        let entry = AuditEntry {
+            time: String::new(),
+            timestamp,
+            subject: String::from("token"),
+            namespace: namespace.to_string(),
+            action: action.to_string(),
+            target: String::from("t1"),
+            success: true,
+            error: None,
+            detail: Value::Null,
+        };
+        serde_json::to_string(&entry).unwrap()
+    }
+
+    #[test]
+    fn test_filter() {
+        let lines = vec![
+            entry("default", "task.create", 10),
+            entry("default", "task.start", 20),
+            entry("team-a", "task.create", 30),
+            entry("default", "config.reload", 40),
+        ];
+
+        let rs = filter(lines.clone().into_iter(), "default", &AuditQuery::default());
+        let actions: Vec<&str> = rs.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, vec!["config.reload", "task.start", "task.create"]);
+
+        let query = AuditQuery { action: Some(String::from("task")), from_ts: Some(15), ..AuditQuery::default() };
+        let rs = filter(lines.into_iter(), "default", &query);
+        assert_eq!(rs.len(), 1);
+        assert_eq!(rs[0].action, "task.start");
+    }
+
+    #[test]
+    fn test_filter_skip_invalid() {
+        let lines = vec![
+            entry("default", "task.create", 10),
+            String::from("{\"time\": \"2024"),
+            String::from("not json"),
+            entry("default", "task.start", 20),
+        ];
+
+        let rs = filter(lines.into_iter(), "default", &AuditQuery::default());
+        let actions: Vec<&str> = rs.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, vec!["task.start", "task.create"]);
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use futures_util::future::LocalBoxFuture;
+
+use crate::api::result::R;
+use crate::auth::validator::TokenValidator;
+
+/// 未携带 Authorization 头时(如浏览器的 WebSocket), 从该查询参数中读取 token
+const TOKEN_QUERY_PARAM: &str = "access_token";
+
+/// 访问日志的格式, 同 Logger::default, 请求行见 [masked_request_line]
+pub const ACCESS_LOG_FORMAT: &str = r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#;
+
+/// 访问日志中的请求行, 查询参数中的 token 替换为 `***`
+pub fn masked_request_line(req: &ServiceRequest) -> String {
+    let query = req.query_string();
+    if query.is_empty() {
+        format!("{} {} {:?}", req.method(), req.path(), req.version())
+    } else {
+        format!("{} {}?{} {:?}", req.method(), req.path(), mask_token(query), req.version())
+    }
+}
+
+fn mask_token(query: &str) -> String {
+    query.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((TOKEN_QUERY_PARAM, _)) => format!("{}=***", TOKEN_QUERY_PARAM),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// 不需要鉴权的页面、静态资源与 Kubernetes 探针
+fn is_public(path: &str) -> bool {
+    path == "/" || path == "/favicon" || path == "/healthz" || path == "/readyz" || path.starts_with("/static/")
+}
+
+/// `Authorization: Bearer <token>` 或 `?access_token=<token>`
+pub fn request_token(req: &HttpRequest) -> Option<String> {
+    let header = req.headers().get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| t.trim().to_string());
+    if header.is_some() {
+        return header;
+    }
+
+    web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()
+        .and_then(|q| q.get(TOKEN_QUERY_PARAM).cloned())
+}
+
+/// 对 REST 接口与 /ws 握手做 token 鉴权, validator 为 None 时放行全部请求。
+/// 通过后将 Principal 放入请求的 extensions
+#[derive(Clone)]
+pub struct Authentication {
+    validator: Option<Arc<dyn TokenValidator>>,
+}
+
+impl Authentication {
+    pub fn new(validator: Option<Arc<dyn TokenValidator>>) -> Self {
+        Authentication { validator }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware {
+            service,
+            validator: self.validator.clone(),
+        }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: S,
+    validator: Option<Arc<dyn TokenValidator>>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if let Some(validator) = self.validator.as_ref().filter(|_| !is_public(req.path())) {
+            let principal = match request_token(req.request()) {
+                Some(token) => validator.validate(&token).map_err(|e| e.to_string()),
+                None => Err(String::from("missing token")),
+            };
+
+            match principal {
+                Ok(p) => {
+                    req.extensions_mut().insert(p);
+                },
+                Err(msg) => {
+                    let resp = HttpResponse::Unauthorized().json(R::error(401, &msg));
+                    return Box::pin(async move { Ok(req.into_response(resp).map_into_right_body()) });
+                },
+            }
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::test::TestRequest;
+
+    use crate::auth::middleware::{is_public, masked_request_line, request_token};
+
+    #[test]
+    fn test_request_token() {
+        let req = TestRequest::default().insert_header(("Authorization", "Bearer abc")).to_http_request();
+        assert_eq!(request_token(&req).as_deref(), Some("abc"));
+
+        let req = TestRequest::with_uri("/ws?access_token=xyz&x=1").to_http_request();
+        assert_eq!(request_token(&req).as_deref(), Some("xyz"));
+
+        let req = TestRequest::with_uri("/api/tasks").to_http_request();
+        assert_eq!(request_token(&req), None);
+
+        assert!(is_public("/static/app.js"));
+        assert!(is_public("/readyz"));
+        assert!(!is_public("/ws"));
+    }
+
+    #[test]
+    fn test_masked_request_line() {
+        let req = TestRequest::with_uri("/ws?x=1&access_token=xyz").to_srv_request();
+        assert_eq!(masked_request_line(&req), "GET /ws?x=1&access_token=*** HTTP/1.1");
+
+        let req = TestRequest::with_uri("/api/tasks").to_srv_request();
+        assert_eq!(masked_request_line(&req), "GET /api/tasks HTTP/1.1");
+    }
+}
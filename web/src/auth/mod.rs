@@ -0,0 +1,24 @@
+pub mod validator;
+pub mod middleware;
+
+use std::sync::Arc;
+
+use crate::auth::validator::{JwtValidator, StaticTokenValidator, TokenValidator};
+use crate::config::constant::CFG;
+
+/// 按 CFG 中的 JWT_SECRET / AUTH_TOKENS 创建校验器, 都未配置时返回 None(不鉴权)
+pub fn validator_from_config() -> Option<Arc<dyn TokenValidator>> {
+    let secret = CFG.get("JWT_SECRET").cloned().unwrap_or_default();
+    if !secret.is_empty() {
+        return Some(Arc::new(JwtValidator::new(secret.as_bytes())));
+    }
+
+    let tokens = CFG.get("AUTH_TOKENS").cloned().unwrap_or_default();
+    let tokens: Vec<String> = tokens.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+    if !tokens.is_empty() {
+        return Some(Arc::new(StaticTokenValidator::new(tokens)));
+    }
+
+    log::warn!("WEB_AUTH_TOKENS and WEB_JWT_SECRET are empty, authentication is disabled");
+    None
+}
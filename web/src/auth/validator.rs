@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::namespace::{is_valid, DEFAULT_NAMESPACE};
+use crate::web_error::{WebError, WResult};
+
+/// 通过鉴权的调用方, 保存在请求的 extensions 中
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub subject: String,
+
+    /// 调用方所属的命名空间, 只能访问该命名空间下的任务、会话与中继日志
+    pub namespace: String,
+}
+
+/// token 校验器, 可按需替换为其他实现(如对接外部认证服务)
+pub trait TokenValidator: Send + Sync {
+    fn validate(&self, token: &str) -> WResult<Principal>;
+}
+
+/// 固定的 token 列表, `namespace:token` 指定命名空间, 否则属于 default。
+/// 调用方为 token 的指纹 `token:<sha256 前 12 位>`, 审计日志中可以区分 token 而不泄露 token
+#[derive(Debug)]
+pub struct StaticTokenValidator {
+    /// token -> Principal
+    tokens: HashMap<String, Principal>,
+}
+
+impl StaticTokenValidator {
+    pub fn new(tokens: Vec<String>) -> Self {
+        let tokens = tokens.into_iter()
+            .map(|t| match t.split_once(':') {
+                Some((namespace, token)) if is_valid(namespace) => (token.to_string(), namespace.to_string()),
+                _ => (t, String::from(DEFAULT_NAMESPACE)),
+            })
+            .map(|(token, namespace)| {
+                let subject = token_fingerprint(&token);
+                (token, Principal { subject, namespace })
+            })
+            .collect();
+
+        StaticTokenValidator { tokens }
+    }
+}
+
+impl TokenValidator for StaticTokenValidator {
+    fn validate(&self, token: &str) -> WResult<Principal> {
+        match self.tokens.get(token) {
+            Some(principal) => Ok(principal.clone()),
+            None => Err(WebError::Value(String::from("invalid token"))),
+        }
+    }
+}
+
+fn token_fingerprint(token: &str) -> String {
+    let digest = hex::encode(Sha256::digest(token.as_bytes()));
+    format!("token:{}", &digest[..12])
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: u64,
+    /// 命名空间, 缺省为 default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ns: Option<String>,
+}
+
+/// HS256 签名的 JWT, 校验签名与 exp
+pub struct JwtValidator {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtValidator {
+    pub fn new(secret: &[u8]) -> Self {
+        JwtValidator {
+            key: DecodingKey::from_secret(secret),
+            validation: Validation::new(Algorithm::HS256),
+        }
+    }
+}
+
+impl TokenValidator for JwtValidator {
+    fn validate(&self, token: &str) -> WResult<Principal> {
+        let data = decode::<Claims>(token, &self.key, &self.validation)
+            .map_err(|e| WebError::Value(format!("invalid token: {}", e)))?;
+
+        let namespace = data.claims.ns.unwrap_or_else(|| String::from(DEFAULT_NAMESPACE));
+        if !is_valid(&namespace) {
+            return Err(WebError::Value(format!("invalid token: bad namespace {}", namespace)));
+        }
+
+        Ok(Principal { subject: data.claims.sub, namespace })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    use crate::auth::validator::{Claims, JwtValidator, StaticTokenValidator, TokenValidator};
+
+    #[test]
+    fn test_static_token() {
+        let validator = StaticTokenValidator::new(vec![String::from("t1"), String::from("t2"), String::from("team-a:t3")]);
+        assert!(validator.validate("t2").is_ok());
+        assert!(validator.validate("t4").is_err());
+
+        assert_eq!(validator.validate("t1").unwrap().namespace, "default");
+        assert_eq!(validator.validate("t3").unwrap().namespace, "team-a");
+        assert!(validator.validate("team-a:t3").is_err());
+
+        // 审计中按指纹区分 token
+        let t1 = validator.validate("t1").unwrap().subject;
+        assert!(t1.starts_with("token:"));
+        assert!(!t1.contains("t1"));
+        assert_eq!(t1.len(), "token:".len() + 12);
+        assert_ne!(t1, validator.validate("t2").unwrap().subject);
+    }
+
+    #[test]
+    fn test_jwt() {
+        let exp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + 60;
+        let claims = Claims { sub: String::from("reader"), exp, ns: None };
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+
+        assert_eq!(JwtValidator::new(b"secret").validate(&token).unwrap().subject, "reader");
+        assert!(JwtValidator::new(b"other").validate(&token).is_err());
+
+        let claims = Claims { sub: String::from("reader"), exp, ns: Some(String::from("team-a")) };
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+        assert_eq!(JwtValidator::new(b"secret").validate(&token).unwrap().namespace, "team-a");
+
+        let expired = Claims { sub: String::from("reader"), exp: 1, ns: None };
+        let token = encode(&Header::default(), &expired, &EncodingKey::from_secret(b"secret")).unwrap();
+        assert!(JwtValidator::new(b"secret").validate(&token).is_err());
+    }
+}
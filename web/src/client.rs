@@ -29,8 +29,12 @@ async fn main() {
         cmd_tx.send(cmd).unwrap();
     });
 
-    let (res, mut ws) = awc::Client::new()
-        .ws("ws://127.0.0.1:8080/ws")
+    let mut request = awc::Client::new().ws("ws://127.0.0.1:8080/ws");
+    // 服务端开启鉴权时携带 token
+    if let Ok(token) = std::env::var("WEB_AUTH_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+    let (res, mut ws) = request
         .connect()
         .await
         .unwrap();
@@ -1,5 +1,6 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::env;
 
 lazy_static! {
 
@@ -14,6 +15,77 @@ lazy_static! {
             "PORT",
             String::from("8080"),
         );
+        // PEM 格式的证书与私钥, 都配置后以 HTTPS/WSS 提供服务
+        map.insert(
+            "TLS_CERT",
+            env::var("WEB_TLS_CERT").unwrap_or_default(),
+        );
+        map.insert(
+            "TLS_KEY",
+            env::var("WEB_TLS_KEY").unwrap_or_default(),
+        );
+        // 校验客户端证书的 CA(mTLS), 为空时不要求客户端证书
+        map.insert(
+            "TLS_CLIENT_CA",
+            env::var("WEB_TLS_CLIENT_CA").unwrap_or_default(),
+        );
+        // 逗号分隔的静态 token, `namespace:token` 指定 token 所属的命名空间。 与 JWT_SECRET 均为空时不做鉴权
+        map.insert(
+            "AUTH_TOKENS",
+            env::var("WEB_AUTH_TOKENS").unwrap_or_default(),
+        );
+        // HS256 的密钥, 配置后按 JWT 校验 token
+        map.insert(
+            "JWT_SECRET",
+            env::var("WEB_JWT_SECRET").unwrap_or_default(),
+        );
+        // 管理命名空间, 开启鉴权时只有该命名空间的 token 可以读取与修改全局的运行时配置
+        map.insert(
+            "ADMIN_NAMESPACE",
+            env::var("WEB_ADMIN_NAMESPACE").unwrap_or_else(|_| String::from("default")),
+        );
+        // 每个命名空间最多的任务数与 WebSocket 会话数, 0 不限制
+        map.insert(
+            "NAMESPACE_MAX_TASKS",
+            env::var("WEB_NAMESPACE_MAX_TASKS").unwrap_or_else(|_| String::from("0")),
+        );
+        map.insert(
+            "NAMESPACE_MAX_SESSIONS",
+            env::var("WEB_NAMESPACE_MAX_SESSIONS").unwrap_or_else(|_| String::from("0")),
+        );
+        // 管理操作的审计文件, JSON Lines 格式, 只追加
+        map.insert(
+            "AUDIT_LOG",
+            env::var("WEB_AUDIT_LOG").unwrap_or_else(|_| String::from("/tmp/replayer/audit.log")),
+        );
+        // 中继日志所在磁盘的最小可用空间, 低于该值时 /readyz 返回 503
+        map.insert(
+            "HEALTH_MIN_FREE_DISK",
+            env::var("WEB_HEALTH_MIN_FREE_DISK").unwrap_or_else(|_| String::from("1GB")),
+        );
+        // 中继日志目录, 按命名空间分目录, /api/events 从中读取历史事件
+        map.insert(
+            "RELAY_LOG_DIR",
+            env::var("WEB_RELAY_LOG_DIR").unwrap_or_else(|_| String::from("/tmp/replayer/relay_log")),
+        );
+        // 回放任务的进度状态文件夹, 按命名空间分目录, /api/replays 从中读取
+        map.insert(
+            "REPLAY_STATUS_DIR",
+            env::var("WEB_REPLAY_STATUS_DIR").unwrap_or_else(|_| String::from("/tmp/replayer/replay_status")),
+        );
+        // WebSocket 会话的发送队列上限、每秒推送事件数(0 不限制)与慢消费者策略: drop | coalesce | disconnect
+        map.insert(
+            "WS_MAX_QUEUE",
+            env::var("WEB_WS_MAX_QUEUE").unwrap_or_else(|_| String::from("1024")),
+        );
+        map.insert(
+            "WS_MAX_EVENTS_PER_SEC",
+            env::var("WEB_WS_MAX_EVENTS_PER_SEC").unwrap_or_else(|_| String::from("0")),
+        );
+        map.insert(
+            "WS_SLOW_CONSUMER_POLICY",
+            env::var("WEB_WS_SLOW_CONSUMER_POLICY").unwrap_or_else(|_| String::from("drop")),
+        );
 
         map
     };
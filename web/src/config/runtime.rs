@@ -0,0 +1,258 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::Value;
+
+use binlog::events::binlog_event::BinlogEvent;
+use binlog::row::masking::MaskingEngine;
+use common::config::{RateLimitConfig, RepConfig, ReplicateConfig, SinkConfig};
+use common::err::CResult;
+use common::pretty_util::parse_bytes_len;
+use common::throttle::RateLimiter;
+use connection::binlog::replication_filter::ReplicationFilter;
+
+use crate::web_error::{WebError, WResult};
+
+/// 运行中生效的配置项, 对应 RepConfig 中的路径前缀
+const LIVE_PATHS: [&str; 3] = ["binlog.replicate", "masking", "rate_limit"];
+
+lazy_static! {
+    static ref STATE: RwLock<RuntimeState> = RwLock::new(RuntimeState::default());
+}
+
+/// 当前配置的版本号, 任务据此判断是否需要重建过滤器等
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// 可在运行时修改的配置, 修改后对运行中的任务立即生效
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LiveConfig {
+    pub replicate: Option<ReplicateConfig>,
+    pub masking: Option<BTreeMap<String, String>>,
+    pub rate_limit: Option<RateLimitConfig>,
+    /// web 服务暂无 sink, 只做校验
+    pub sink: Option<SinkConfig>,
+}
+
+impl LiveConfig {
+    fn new(config: &RepConfig) -> Self {
+        LiveConfig {
+            replicate: config.binlog.replicate.clone(),
+            masking: config.masking.clone(),
+            rate_limit: config.rate_limit.clone(),
+            sink: config.sink.clone(),
+        }
+    }
+
+    fn filter(&self) -> CResult<Option<ReplicationFilter>> {
+        self.replicate.as_ref().map(ReplicationFilter::new).transpose()
+    }
+
+    fn masking(&self) -> CResult<Option<MaskingEngine>> {
+        self.masking.as_ref().map(MaskingEngine::new).transpose()
+    }
+
+    fn rate_limiter(&self) -> CResult<Option<RateLimiter>> {
+        let rate_limit = match self.rate_limit.as_ref() {
+            None => return Ok(None),
+            Some(r) => r,
+        };
+        let max_bytes = rate_limit.max_bytes_per_sec.as_deref().map(parse_bytes_len).transpose()?;
+
+        Ok(RateLimiter::new(rate_limit.max_events_per_sec, max_bytes))
+    }
+
+    fn validate(&self) -> WResult<()> {
+        self.filter().map_err(|e| WebError::Value(format!("[binlog.replicate] {}", e)))?;
+        self.masking().map_err(|e| WebError::Value(format!("[masking] {}", e)))?;
+        self.rate_limiter().map_err(|e| WebError::Value(format!("[rate_limit] {}", e)))?;
+
+        if let Some(sink_type) = self.sink.as_ref().and_then(|s| s.sink_type.as_deref()) {
+            if !["stdout", "file", "kafka"].contains(&sink_type) {
+                return Err(WebError::Value(format!("[sink] unsupported type {}", sink_type)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct RuntimeState {
+    /// 当前的 RepConfig, 用于计算 diff
+    value: Value,
+    live: Arc<LiveConfig>,
+}
+
+impl Default for RuntimeState {
+    fn default() -> Self {
+        let config = RepConfig::default();
+
+        RuntimeState {
+            value: serde_json::to_value(&config).unwrap_or(Value::Null),
+            live: Arc::new(LiveConfig::new(&config)),
+        }
+    }
+}
+
+/// 一项配置的变化, applied 为 false 的项需要重启任务后生效
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigChange {
+    pub path: String,
+    pub old: Value,
+    pub new: Value,
+    pub applied: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReloadResult {
+    pub version: u64,
+    pub changes: Vec<ConfigChange>,
+}
+
+/// 校验并应用新的配置(与配置文件相同的 TOML 格式), 返回变化的配置项
+pub fn reload(text: &str) -> WResult<ReloadResult> {
+    let config: RepConfig = toml::from_str(text).map_err(|e| WebError::Parse(e.to_string()))?;
+    config.validate().map_err(|e| WebError::Value(e.to_string()))?;
+    let live = LiveConfig::new(&config);
+    live.validate()?;
+    let value = serde_json::to_value(&config)?;
+
+    let mut state = STATE.write()?;
+    let mut changes = vec![];
+    diff("", &state.value, &value, &mut changes);
+    state.value = value;
+    state.live = Arc::new(live);
+    let version = VERSION.fetch_add(1, Ordering::SeqCst) + 1;
+
+    Ok(ReloadResult { version, changes })
+}
+
+/// 当前配置, 密码已隐藏
+pub fn current_value() -> Value {
+    let state = STATE.read().unwrap();
+    let mut value = state.value.clone();
+    hide_passwords(&mut value);
+
+    value
+}
+
+/// 当前的 [sink] 配置, 供 /readyz 检查
+pub fn current_sink() -> Option<SinkConfig> {
+    STATE.read().unwrap().live.sink.clone()
+}
+
+fn current() -> (u64, Arc<LiveConfig>) {
+    let state = STATE.read().unwrap();
+    (VERSION.load(Ordering::SeqCst), state.live.clone())
+}
+
+fn diff(path: &str, old: &Value, new: &Value, changes: &mut Vec<ConfigChange>) {
+    if let (Value::Object(o), Value::Object(n)) = (old, new) {
+        let keys: std::collections::BTreeSet<&String> = o.keys().chain(n.keys()).collect();
+        for key in keys {
+            let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+            diff(&child, o.get(key).unwrap_or(&Value::Null), n.get(key).unwrap_or(&Value::Null), changes);
+        }
+        return;
+    }
+
+    if old != new {
+        let (mut old, mut new) = (old.clone(), new.clone());
+        if path.ends_with("password") {
+            old = Value::String(String::from("******"));
+            new = Value::String(String::from("******"));
+        }
+        let applied = LIVE_PATHS.iter().any(|p| path == *p || path.starts_with(&format!("{}.", p)));
+        changes.push(ConfigChange { path: path.to_string(), old, new, applied });
+    }
+}
+
+fn hide_passwords(value: &mut Value) {
+    if let Value::Object(map) = value {
+        for (key, v) in map.iter_mut() {
+            if key.ends_with("password") && v.is_string() {
+                *v = Value::String(String::from("******"));
+            } else {
+                hide_passwords(v);
+            }
+        }
+    }
+}
+
+/// 任务中按版本号缓存的过滤器、脱敏与限速, 配置变化后在下一个事件时重建
+#[derive(Debug)]
+pub struct LiveSettings {
+    version: Option<u64>,
+    filter: Option<ReplicationFilter>,
+    masking: Option<MaskingEngine>,
+    limiter: Option<RateLimiter>,
+}
+
+impl LiveSettings {
+    pub fn new() -> Self {
+        LiveSettings { version: None, filter: None, masking: None, limiter: None }
+    }
+
+    fn refresh(&mut self) {
+        let (version, live) = current();
+        if self.version == Some(version) {
+            return;
+        }
+
+        // reload 时已校验
+        self.filter = live.filter().unwrap_or_else(|e| { log::error!("replicate filter error: {}", e); None });
+        self.masking = live.masking().unwrap_or_else(|e| { log::error!("masking error: {}", e); None });
+        self.limiter = live.rate_limiter().unwrap_or_else(|e| { log::error!("rate limit error: {}", e); None });
+        self.version = Some(version);
+    }
+
+    /// 过滤与脱敏, 返回 None 表示事件被过滤
+    pub fn apply<'a>(&mut self, event: &'a BinlogEvent) -> Option<Cow<'a, BinlogEvent>> {
+        self.refresh();
+
+        if let Some(filter) = self.filter.as_mut() {
+            if !filter.accept_event(event) {
+                return None;
+            }
+        }
+
+        match self.masking.as_mut() {
+            Some(masking) => {
+                let mut event = event.clone();
+                masking.apply(&mut event);
+                Some(Cow::Owned(event))
+            },
+            None => Some(Cow::Borrowed(event)),
+        }
+    }
+
+    /// 推送大小为 bytes 的事件后限速
+    pub fn throttle(&mut self, bytes: u64) {
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.acquire(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::config::runtime::diff;
+
+    #[test]
+    fn test_diff() {
+        let old = json!({"binlog": {"host": "a", "password": "x", "replicate": null}, "masking": null});
+        let new = json!({"binlog": {"host": "b", "password": "y", "replicate": {"replicate_do_db": ["db1"]}}, "masking": null});
+
+        let mut changes = vec![];
+        diff("", &old, &new, &mut changes);
+        let paths: Vec<(&str, bool)> = changes.iter().map(|c| (c.path.as_str(), c.applied)).collect();
+        assert_eq!(paths, vec![("binlog.host", false), ("binlog.password", false), ("binlog.replicate", true)]);
+        assert_eq!(changes[1].new, json!("******"));
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+
+use crate::config::constant::CFG;
+use crate::web_error::{WebError, WResult};
+
+/// 按 CFG 中的 TLS_CERT / TLS_KEY / TLS_CLIENT_CA 创建 rustls 配置, 未配置证书时返回 None(HTTP)
+pub fn server_config() -> WResult<Option<ServerConfig>> {
+    let get = |key: &str| CFG.get(key).cloned().filter(|v| !v.is_empty());
+
+    match (get("TLS_CERT"), get("TLS_KEY")) {
+        (Some(cert), Some(key)) => load(&cert, &key, get("TLS_CLIENT_CA").as_deref()).map(Some),
+        (None, None) => Ok(None),
+        _ => Err(WebError::Value(String::from("WEB_TLS_CERT and WEB_TLS_KEY must be set together"))),
+    }
+}
+
+/// client_ca 不为空时要求客户端提供由该 CA 签发的证书(mTLS)
+pub fn load(cert: &str, key: &str, client_ca: Option<&str>) -> WResult<ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(tls_error)?;
+
+    let builder = match client_ca {
+        Some(ca) => {
+            let mut roots = RootCertStore::empty();
+            for c in read_certs(ca)? {
+                roots.add(c).map_err(tls_error)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(tls_error)?;
+            builder.with_client_cert_verifier(verifier)
+        },
+        None => builder.with_no_client_auth(),
+    };
+
+    builder.with_single_cert(read_certs(cert)?, read_key(key)?).map_err(tls_error)
+}
+
+fn read_certs(path: &str) -> WResult<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| WebError::Value(format!("{}: {}", path, e)))?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(WebError::Value(format!("no certificate found in {}", path)));
+    }
+
+    Ok(certs)
+}
+
+fn read_key(path: &str) -> WResult<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| WebError::Value(format!("{}: {}", path, e)))?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| WebError::Value(format!("no private key found in {}", path)))
+}
+
+fn tls_error<E: std::fmt::Display>(e: E) -> WebError {
+    WebError::Value(format!("tls config error: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use crate::config::tls::load;
+
+    #[test]
+    fn test_load_error() {
+        let err = load("/not/exists/cert.pem", "/not/exists/key.pem", None).unwrap_err();
+        assert!(err.to_string().contains("/not/exists/cert.pem"));
+
+        let path = std::env::temp_dir().join("web_tls_test_empty.pem");
+        std::fs::File::create(&path).unwrap().write_all(b"").unwrap();
+        let path = path.to_str().unwrap();
+        let err = load(path, path, None).unwrap_err();
+        assert!(err.to_string().contains("no certificate found"));
+    }
+}
@@ -1,9 +1,18 @@
 mod api;
+mod audit;
+mod auth;
 mod config;
 mod client;
+mod namespace;
+mod stream;
+mod task;
+mod webhook;
 mod wss;
 mod web_error;
 
+use std::collections::HashMap;
+use std::io;
+
 use actix_web::{web, App, HttpServer, Error, Responder, HttpResponse, middleware, HttpRequest};
 use actix::{Actor, Addr, StreamHandler};
 use actix_files::Files;
@@ -12,24 +21,51 @@ use actix_web_actors::ws::WsResponseBuilder;
 use common::time_util::now_str;
 use common::uuid::uuid_timestamp;
 
+use crate::auth::middleware::{masked_request_line, Authentication, ACCESS_LOG_FORMAT};
 use crate::api::default::{data, index, favicon, get_static_dir};
+use crate::api::result::R;
 use crate::config::constant::CFG;
+use crate::config::tls;
+use crate::namespace::{request_namespace, scoped_key, Quota};
+use crate::web_error::WResult;
+use crate::wss::backpressure::SessionLimits;
+use crate::wss::encoding::{FrameEncoding, SUB_PROTOCOLS};
 use crate::wss::server::{MyWebSocket, SendMessage, WsContext};
 use crate::wss::session_manager::SessionManager;
 
 /// WebSocket handshake and start `MyWebSocket` actor.
 async fn index_echo_ws(req: HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
-    let session_id = get_session_id(&req, uuid_timestamp());
+    let namespace = match request_namespace(&req) {
+        Ok(ns) => ns,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(R::error(400, &e.to_string()))),
+    };
+    if let Err(e) = Quota::check(Quota::from_config().max_sessions, SessionManager::ws_count(&namespace), "session", &namespace) {
+        return Ok(HttpResponse::TooManyRequests().json(R::error(429, &e.to_string())));
+    }
+    // 不同命名空间可以使用相同的 X-Session-Id
+    let session_id = scoped_key(&namespace, &get_session_id(&req, uuid_timestamp()));
 
-    let build = WsResponseBuilder::new(MyWebSocket::new(Some(session_id.clone())), &req, stream);
+    let encoding = match get_encoding(&req) {
+        Ok(e) => e,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(R::error(400, &e.to_string()))),
+    };
+
+    let build = WsResponseBuilder::new(MyWebSocket::new(Some(session_id.clone())), &req, stream)
+        .protocols(&SUB_PROTOCOLS);
     let resp = build.start_with_addr();
 
     match resp {
         Ok((addr, resp)) => {
-            let context = WsContext::new(addr, session_id.clone(), now_str());
+            // 客户端可通过 ?max_events_per_sec= 请求更低的推送速率
+            let limits = SessionLimits::from_config(get_query_u64(&req, "max_events_per_sec"));
+            let context = WsContext::new(addr, session_id.clone(), now_str())
+                .with_namespace(&namespace)
+                .with_encoding(encoding)
+                .with_limits(limits);
             context.do_send("Binlog Server 连接成功");
 
-            SessionManager::ws_insertupdate(session_id, context);
+            // 断线重连时补发错过的事件
+            SessionManager::ws_resume(session_id, context, get_last_offset(&req));
 
             Ok(resp)
         }
@@ -47,9 +83,13 @@ async fn main() -> std::io::Result<()> {
     let host = CFG.get("ADDRESS").unwrap();
     let port = CFG.get("PORT").unwrap();
 
-    log::info!("{}", format!("starting HTTP server at http://{}:{}", &host, &port));
+    let tls = tls::server_config().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    log::info!("{}", format!("starting HTTP server at {}://{}:{}", scheme, &host, &port));
+
+    let validator = auth::validator_from_config();
 
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         App::new()
             // 将"/static"前缀映射到"./static"目录
             // 作为服务（service）被添加到应用中，而不是通过 .wrap() 方法。这是因为 Files 是一个完整的服务，它处理以 /static 开头的所有请求，并将它们映射到文件系统的 ./static 目录中
@@ -57,16 +97,65 @@ async fn main() -> std::io::Result<()> {
             // .route("/", HttpMethod::Get, |_| HttpResponse::Ok().body("Hello, Rust Web!"))
             .service(index)
             .service(data)
+            .service(api::health::healthz)
+            .service(api::health::readyz)
+            .configure(api::task::config)
+            .configure(api::webhook::config)
+            .service(api::sse::events)
+            .service(api::history::history_events)
+            .service(api::schema::table_schema)
+            .service(api::audit::audit_log)
+            .service(api::config::get_config)
+            .service(api::config::reload_config)
             .service(web::resource("/favicon").to(favicon))
             // websocket route
             .service(web::resource("/ws").route(web::get().to(index_echo_ws)))
+            // token 鉴权, 需在 Logger 之前注册, 使 Logger 包裹在最外层
+            .wrap(Authentication::new(validator.clone()))
             // enable logger
-            .wrap(middleware::Logger::default())
+            .wrap(middleware::Logger::new(ACCESS_LOG_FORMAT).custom_request_replace("request_line", masked_request_line))
     })
-        .workers(2)
-        .bind(format!("{}:{}", host, port))?
-        .run()
-        .await
+        .workers(2);
+
+    let addr = format!("{}:{}", host, port);
+    let server = match tls {
+        Some(config) => server.bind_rustls_0_23(addr, config)?,
+        None => server.bind(addr)?,
+    };
+    server.run().await
+}
+
+/// X-Last-Offset 头或 ?last_offset=, 为客户端最后收到的事件 id
+fn get_last_offset(req: &HttpRequest) -> Option<u64> {
+    let header = req.headers().get("X-Last-Offset")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().parse().ok());
+    if header.is_some() {
+        return header;
+    }
+
+    get_query_u64(req, "last_offset")
+}
+
+/// ?encoding= 或 Sec-WebSocket-Protocol 中的 binlog.msgpack / binlog.json, 默认 JSON
+fn get_encoding(req: &HttpRequest) -> WResult<FrameEncoding> {
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()
+        .and_then(|q| q.get("encoding").cloned());
+    if let Some(encoding) = query {
+        return FrameEncoding::try_from(encoding.as_str());
+    }
+
+    // 客户端按优先级列出子协议, 取第一个支持的
+    let protocol = req.headers().get("Sec-WebSocket-Protocol")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(',').map(str::trim).find(|p| SUB_PROTOCOLS.contains(p)));
+
+    Ok(protocol.and_then(|p| FrameEncoding::try_from(p).ok()).unwrap_or_default())
+}
+
+fn get_query_u64(req: &HttpRequest, name: &str) -> Option<u64> {
+    web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()
+        .and_then(|q| q.get(name).and_then(|v| v.parse().ok()))
 }
 
 fn get_session_id(req: &HttpRequest, default:String) -> String {
@@ -82,9 +171,38 @@ fn get_session_id(req: &HttpRequest, default:String) -> String {
 
 #[cfg(test)]
 mod test {
+    use actix_web::test::TestRequest;
+
+    use crate::wss::encoding::FrameEncoding;
+    use crate::{get_encoding, get_last_offset};
+
     #[test]
     fn test() {
         assert_eq!(1, 1);
         println!("binlog lib test:{}", 0x21);
     }
+
+    #[test]
+    fn test_get_last_offset() {
+        let req = TestRequest::default().insert_header(("X-Last-Offset", "42")).to_http_request();
+        assert_eq!(get_last_offset(&req), Some(42));
+
+        let req = TestRequest::with_uri("/ws?last_offset=7").to_http_request();
+        assert_eq!(get_last_offset(&req), Some(7));
+        assert_eq!(get_last_offset(&TestRequest::default().to_http_request()), None);
+    }
+
+    #[test]
+    fn test_get_encoding() {
+        assert_eq!(get_encoding(&TestRequest::default().to_http_request()).unwrap(), FrameEncoding::Json);
+
+        let req = TestRequest::with_uri("/ws?encoding=msgpack").to_http_request();
+        assert_eq!(get_encoding(&req).unwrap(), FrameEncoding::MsgPack);
+
+        let req = TestRequest::default().insert_header(("Sec-WebSocket-Protocol", "graphql-ws, binlog.msgpack")).to_http_request();
+        assert_eq!(get_encoding(&req).unwrap(), FrameEncoding::MsgPack);
+
+        let req = TestRequest::with_uri("/ws?encoding=protobuf").to_http_request();
+        assert!(get_encoding(&req).is_err());
+    }
 }
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpMessage, HttpRequest};
+
+use crate::auth::validator::Principal;
+use crate::config::constant::CFG;
+use crate::web_error::{WebError, WResult};
+
+/// 未指定命名空间时使用
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// 命名空间用于目录名等, 只允许字母、数字、`-` 与 `_`
+pub fn is_valid(namespace: &str) -> bool {
+    !namespace.is_empty() && namespace.len() <= 64
+        && namespace.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// 请求所属的命名空间。 开启鉴权时由 token 决定; 否则取 X-Namespace 头或 ?namespace=, 默认 default
+pub fn request_namespace(req: &HttpRequest) -> WResult<String> {
+    if let Some(principal) = req.extensions().get::<Principal>() {
+        return Ok(principal.namespace.clone());
+    }
+
+    let header = req.headers().get("X-Namespace").and_then(|h| h.to_str().ok()).map(String::from);
+    let namespace = header
+        .or_else(|| web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()
+            .and_then(|q| q.get("namespace").cloned()))
+        .unwrap_or_else(|| String::from(DEFAULT_NAMESPACE));
+
+    if !is_valid(&namespace) {
+        return Err(WebError::Value(format!("invalid namespace {}", namespace)));
+    }
+    Ok(namespace)
+}
+
+/// 运行时配置对所有命名空间生效, 开启鉴权时只允许管理命名空间(ADMIN_NAMESPACE)的调用方访问
+pub fn require_admin(req: &HttpRequest) -> WResult<()> {
+    let admin = CFG.get("ADMIN_NAMESPACE").map(String::as_str).unwrap_or(DEFAULT_NAMESPACE);
+    match req.extensions().get::<Principal>() {
+        Some(principal) if principal.namespace != admin => {
+            Err(WebError::Value(format!("namespace {} is not allowed to access the global config", principal.namespace)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 同一命名空间下的会话 key, 不同命名空间可以使用相同的 session id
+pub fn scoped_key(namespace: &str, key: &str) -> String {
+    format!("{}/{}", namespace, key)
+}
+
+/// 每个命名空间的配额, 0 表示不限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub max_tasks: usize,
+    pub max_sessions: usize,
+}
+
+impl Quota {
+    pub fn from_config() -> Self {
+        let get = |key: &str| CFG.get(key).and_then(|v| v.parse().ok()).unwrap_or(0);
+
+        Quota {
+            max_tasks: get("NAMESPACE_MAX_TASKS"),
+            max_sessions: get("NAMESPACE_MAX_SESSIONS"),
+        }
+    }
+
+    pub fn check(limit: usize, used: usize, what: &str, namespace: &str) -> WResult<()> {
+        if limit > 0 && used >= limit {
+            return Err(WebError::Value(format!("namespace {} exceeds the {} quota {}", namespace, what, limit)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::test::TestRequest;
+    use actix_web::HttpMessage;
+
+    use crate::auth::validator::Principal;
+    use crate::namespace::{is_valid, request_namespace, require_admin, Quota};
+
+    #[test]
+    fn test_request_namespace() {
+        assert!(is_valid("team-a_1"));
+        assert!(!is_valid("../etc"));
+
+        assert_eq!(request_namespace(&TestRequest::default().to_http_request()).unwrap(), "default");
+        let req = TestRequest::default().insert_header(("X-Namespace", "team-a")).to_http_request();
+        assert_eq!(request_namespace(&req).unwrap(), "team-a");
+        let req = TestRequest::with_uri("/events?namespace=a/b").to_http_request();
+        assert!(request_namespace(&req).is_err());
+
+        // token 中的命名空间优先
+        let req = TestRequest::default().insert_header(("X-Namespace", "team-a")).to_http_request();
+        req.extensions_mut().insert(Principal { subject: String::from("t"), namespace: String::from("team-b") });
+        assert_eq!(request_namespace(&req).unwrap(), "team-b");
+
+        // 未开启鉴权时不限制, 否则只有管理命名空间可以访问全局配置
+        assert!(require_admin(&TestRequest::default().to_http_request()).is_ok());
+        let req = TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Principal { subject: String::from("t"), namespace: String::from("default") });
+        assert!(require_admin(&req).is_ok());
+        req.extensions_mut().insert(Principal { subject: String::from("t"), namespace: String::from("team-b") });
+        assert!(require_admin(&req).is_err());
+
+        assert!(Quota::check(0, 10, "task", "a").is_ok());
+        assert!(Quota::check(2, 2, "task", "a").is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::config::constant::CFG;
+use crate::stream::event_journal::EventJournal;
+
+/// 保留最近的事件数, 断线重连时可从中续传
+const HISTORY_CAPACITY: usize = 4096;
+
+/// 订阅者的缓冲区大小, 超出时订阅者从 history 中补齐
+const CHANNEL_CAPACITY: usize = 1024;
+
+lazy_static! {
+    pub static ref EVENT_HUB: EventHub = {
+        let hub = EventHub::new(HISTORY_CAPACITY);
+        let dir = CFG.get("RELAY_LOG_DIR").cloned().unwrap_or_default();
+        match EventJournal::open(&dir) {
+            Ok((journal, last_id)) => hub.with_journal(journal, last_id),
+            Err(e) => {
+                log::warn!("open event journal in {} error, only the recent events can be resumed: {}", dir, e);
+                hub
+            }
+        }
+    };
+}
+
+/// 推送给客户端的事件, id 从 1 开始递增, 在全部命名空间中唯一
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamEvent {
+    pub id: u64,
+    /// 只推送给同一命名空间的订阅者
+    #[serde(skip)]
+    pub namespace: String,
+    pub task: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub event: serde_json::Value,
+}
+
+impl StreamEvent {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+struct HubState {
+    next_id: u64,
+    history: VecDeque<StreamEvent>,
+}
+
+/// 事件流: 为事件编号并保留最近的 capacity 个, 同时广播给实时订阅者。
+/// 配置了 [EventJournal] 时事件同时写入中继日志, 续传时从中补发 history 中已淘汰的事件
+#[derive(Debug)]
+pub struct EventHub {
+    capacity: usize,
+    state: Mutex<HubState>,
+    sender: broadcast::Sender<StreamEvent>,
+    journal: Option<EventJournal>,
+}
+
+impl EventHub {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        EventHub {
+            capacity,
+            state: Mutex::new(HubState { next_id: 1, history: VecDeque::with_capacity(capacity) }),
+            sender,
+            journal: None,
+        }
+    }
+
+    /// 事件同时写入 journal, id 接着 journal 中最后一个事件编号
+    pub fn with_journal(mut self, journal: EventJournal, last_id: u64) -> Self {
+        self.state.get_mut().unwrap().next_id = last_id + 1;
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn publish(&self, namespace: &str, task: &str, event_type: &str, event: serde_json::Value) -> StreamEvent {
+        self.publish_with(namespace, task, event_type, event, |_| {})
+    }
+
+    /// 发布事件, 并在持有锁时调用 deliver 推送, 保证各推送方按 id 顺序收到事件。
+    /// deliver 中不能调用 EventHub 的其他方法
+    pub fn publish_with<F>(&self, namespace: &str, task: &str, event_type: &str, event: serde_json::Value, deliver: F) -> StreamEvent
+        where F: FnOnce(&StreamEvent) {
+        let mut state = self.state.lock().unwrap();
+        let event = StreamEvent {
+            id: state.next_id,
+            namespace: namespace.to_string(),
+            task: task.to_string(),
+            event_type: event_type.to_string(),
+            event,
+        };
+        state.next_id += 1;
+
+        if state.history.len() >= self.capacity {
+            state.history.pop_front();
+        }
+        state.history.push_back(event.clone());
+        if let Some(journal) = &self.journal {
+            journal.append(&event);
+        }
+        // 没有订阅者时发送失败, 忽略
+        let _ = self.sender.send(event.clone());
+        deliver(&event);
+
+        event
+    }
+
+    /// 最后一个事件的 id, 没有事件时为 0
+    pub fn last_id(&self) -> u64 {
+        self.state.lock().unwrap().next_id - 1
+    }
+
+    /// history 中最早的事件 id, 没有事件时为 None
+    pub fn first_id(&self) -> Option<u64> {
+        self.state.lock().unwrap().history.front().map(|e| e.id)
+    }
+
+    /// history 中属于 namespace 且 id 大于 last_id 的事件
+    pub fn since(&self, namespace: &str, last_id: u64) -> Vec<StreamEvent> {
+        let state = self.state.lock().unwrap();
+        state.history.iter().filter(|e| e.id > last_id && e.namespace == namespace).cloned().collect()
+    }
+
+    /// 断点续传。 持有锁时以 (最后一个事件的 id, namespace 中 last_id 之后的事件, 已无法补发的 id 范围) 调用 f,
+    /// 期间没有新的事件发布, 补发的事件与之后 [EventHub::publish_with] 推送的事件之间没有遗漏与乱序。
+    ///
+    /// history 中已淘汰的事件从 journal 读取, 读取时不持有锁。 f 中不能调用 EventHub 的其他方法
+    pub fn resume<F, R>(&self, namespace: &str, last_id: Option<u64>, f: F) -> R
+        where F: FnOnce(u64, Vec<StreamEvent>, Option<(u64, u64)>) -> R {
+        let mut last_id = match last_id {
+            Some(id) => id,
+            None => {
+                let state = self.state.lock().unwrap();
+                return f(state.next_id - 1, vec![], None);
+            }
+        };
+
+        let mut missed = vec![];
+        let mut lost = None;
+        loop {
+            let state = self.state.lock().unwrap();
+            let first = state.history.front().map_or(state.next_id, |e| e.id);
+            if first <= last_id + 1 {
+                missed.extend(state.history.iter().filter(|e| e.id > last_id && e.namespace == namespace).cloned());
+                return f(state.next_id - 1, missed, lost);
+            }
+            drop(state);
+
+            // (last_id, first) 之间的事件已从 history 淘汰, 从 journal 补发
+            let (retained, events) = match &self.journal {
+                Some(journal) => journal.read(namespace, last_id, first - 1).unwrap_or_else(|e| {
+                    log::warn!("read event journal error: {}", e);
+                    (first, vec![])
+                }),
+                None => (first, vec![]),
+            };
+            if retained > last_id + 1 && lost.is_none() {
+                lost = Some((last_id + 1, retained - 1));
+            }
+            missed.extend(events);
+            last_id = first - 1;
+        }
+    }
+
+    /// 订阅实时事件。 指定 last_id 时同时返回 namespace 中其后的历史事件, 与实时事件之间没有遗漏与重复。
+    /// 实时事件包含全部命名空间, 由订阅者过滤
+    pub fn subscribe(&self, namespace: &str, last_id: Option<u64>) -> (Vec<StreamEvent>, broadcast::Receiver<StreamEvent>) {
+        let state = self.state.lock().unwrap();
+        let history = match last_id {
+            Some(last_id) => state.history.iter().filter(|e| e.id > last_id && e.namespace == namespace).cloned().collect(),
+            None => vec![],
+        };
+
+        (history, self.sender.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use crate::stream::event_hub::EventHub;
+
+    #[test]
+    fn test_subscribe() {
+        let hub = EventHub::new(3);
+        for i in 0..5 {
+            hub.publish("default", "t1", "WriteRows", json!(i));
+        }
+        // 只保留最近 3 个
+        let ids: Vec<u64> = hub.since("default", 0).iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![3, 4, 5]);
+        assert!(hub.since("team-a", 0).is_empty());
+        assert_eq!(hub.first_id(), Some(3));
+
+        let (history, mut rx) = hub.subscribe("default", Some(4));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].id, 5);
+
+        let event = hub.publish("default", "t1", "Query", json!("BEGIN"));
+        assert_eq!(rx.try_recv().unwrap().id, event.id);
+        assert_eq!(event.to_json(), r#"{"id":6,"task":"t1","type":"Query","event":"BEGIN"}"#);
+
+        let (history, _) = hub.subscribe("default", None);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_resume() {
+        let hub = EventHub::new(3);
+        for i in 0..5 {
+            hub.publish(if i == 1 { "team-a" } else { "default" }, "t1", "WriteRows", json!(i));
+        }
+
+        // 没有 journal 时, history 中已淘汰的事件无法补发
+        let (last_id, ids, lost) = hub.resume("default", Some(0), |last_id, missed, lost| {
+            (last_id, missed.iter().map(|e| e.id).collect::<Vec<u64>>(), lost)
+        });
+        assert_eq!(last_id, 5);
+        assert_eq!(ids, vec![3, 4, 5]);
+        assert_eq!(lost, Some((1, 2)));
+
+        let (ids, lost) = hub.resume("default", Some(3), |_, missed, lost| (missed.iter().map(|e| e.id).collect::<Vec<u64>>(), lost));
+        assert_eq!(ids, vec![4, 5]);
+        assert_eq!(lost, None);
+        assert_eq!(hub.resume("default", None, |last_id, missed, _| (last_id, missed.len())), (5, 0));
+
+        // 在锁内推送
+        let mut delivered = 0;
+        let event = hub.publish_with("default", "t1", "Query", json!("COMMIT"), |e| delivered = e.id);
+        assert_eq!(delivered, event.id);
+    }
+}
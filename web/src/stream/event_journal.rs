@@ -0,0 +1,227 @@
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use common::err::CResult;
+use common::err::decode_error::ReError;
+use common::schema::data_type::{DstColumnType, Value};
+use relay_log::relay_log::{RelayColumnInfo, RelayCommand, RelayLog, RelayRowData};
+use relay_log::storage::relay_log_storage::RelayLogStorage;
+use relay_log::storage::segment_manager::SegmentManager;
+use relay_log::storage::storage_config::StorageConfig;
+
+use crate::stream::event_hub::StreamEvent;
+
+/// 事件流所在的分区, 命名空间中不能包含 `.`, 不会与命名空间的分区冲突
+const JOURNAL_PARTITION: &str = ".stream";
+const JOURNAL_DB: &str = "stream";
+const JOURNAL_TABLE: &str = "events";
+/// 事件流保留 24 小时
+const JOURNAL_RETENTION_MILLISECOND: u64 = 24 * 60 * 60 * 1000;
+const JOURNAL_SEGMENT_ENTRIES: u32 = 4096;
+/// 读取时等待写入线程提交的次数与间隔
+const READ_RETRIES: usize = 50;
+const READ_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 把事件流按 id 顺序写入中继日志, entry index 即事件 id。
+///
+/// 断线重连时, 已从内存 history 中淘汰的事件从这里补发。 写入在单独的线程中进行, 不阻塞推送;
+/// 写入失败后不再写入, 之后的事件只能从内存 history 中补发
+#[derive(Debug)]
+pub struct EventJournal {
+    config: StorageConfig,
+    sender: Mutex<Sender<StreamEvent>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EventJournal {
+    /// 打开 relay_log_dir 下的事件流, 返回事件流及其中最后一个事件的 id
+    pub fn open(relay_log_dir: &str) -> CResult<(Self, u64)> {
+        let mut config = StorageConfig::default();
+        config.set_relay_log_dir(relay_log_dir.to_string());
+        config.set_partition(JOURNAL_PARTITION.to_string());
+        config.set_max_segment_entries(JOURNAL_SEGMENT_ENTRIES);
+        config.set_retention_millisecond(JOURNAL_RETENTION_MILLISECOND);
+
+        // RelayLogStorage 不能跨线程, 在写入线程中打开
+        let (sender, receiver) = channel::<StreamEvent>();
+        let (opened_tx, opened_rx) = channel::<CResult<u64>>();
+        let storage_config = config.clone();
+        let handle = std::thread::Builder::new()
+            .name(String::from("event-journal"))
+            .spawn(move || {
+                let mut storage = match RelayLogStorage::new(&storage_config, JOURNAL_DB.to_string(), JOURNAL_TABLE.to_string()) {
+                    Ok(storage) => storage,
+                    Err(e) => {
+                        let _ = opened_tx.send(Err(e));
+                        return;
+                    }
+                };
+                let last_id = storage.index_range().map(|r| r.map_or(0, |(_, last)| last));
+                let _ = opened_tx.send(last_id);
+
+                while let Ok(event) = receiver.recv() {
+                    let mut rs = storage.append_relay_log(to_relay_log(&event));
+                    // 队列为空时提交, 使只读打开的读取方可见
+                    while rs.is_ok() {
+                        match receiver.try_recv() {
+                            Ok(event) => rs = storage.append_relay_log(to_relay_log(&event)),
+                            Err(_) => break,
+                        }
+                    }
+                    if let Err(e) = rs.and_then(|_| storage.flush()) {
+                        log::warn!("event journal append error, stop journaling: {}", e);
+                        break;
+                    }
+                }
+            })
+            .map_err(ReError::IoError)?;
+
+        let last_id = opened_rx.recv()
+            .map_err(|_| ReError::String(String::from("event journal thread exited.")))??;
+        Ok((EventJournal { config, sender: Mutex::new(sender), handle: Some(handle) }, last_id))
+    }
+
+    /// 追加事件, 须按 id 顺序调用
+    pub fn append(&self, event: &StreamEvent) {
+        // 写入线程已退出时忽略
+        let _ = self.sender.lock().unwrap().send(event.clone());
+    }
+
+    /// 读取 namespace 中 id 在 (after, until] 之间的事件, 返回其中仍保留的第一个 id 与事件; 都已被清理时 id 为 until + 1
+    pub fn read(&self, namespace: &str, after: u64, until: u64) -> CResult<(u64, Vec<StreamEvent>)> {
+        let dir = SegmentManager::get_segment_dir_path(&self.config.partition_dir(), JOURNAL_DB, JOURNAL_TABLE)?;
+        if after >= until || !SegmentManager::has_segments(&dir)? {
+            return Ok((until + 1, vec![]));
+        }
+
+        // 写入线程落后时等待其提交
+        let mut retry = 0;
+        let (mut storage, first, last) = loop {
+            let mut storage = RelayLogStorage::open_read_only(&self.config, JOURNAL_DB.to_string(), JOURNAL_TABLE.to_string())?;
+            match storage.index_range()? {
+                Some((first, last)) if last >= until || retry >= READ_RETRIES => break (storage, first, last),
+                None if retry >= READ_RETRIES => return Ok((until + 1, vec![])),
+                _ => {
+                    retry += 1;
+                    std::thread::sleep(READ_RETRY_INTERVAL);
+                }
+            }
+        };
+
+        let mut events = vec![];
+        let from = (after + 1).max(first);
+        let to = until.min(last);
+        for id in from..=to {
+            let entry = storage.get_entry(id)?;
+            if let Some(event) = from_relay_log(entry.relay_log()).filter(|e| e.namespace == namespace) {
+                events.push(event);
+            }
+        }
+        Ok((if from <= to { from } else { until + 1 }, events))
+    }
+}
+
+impl Drop for EventJournal {
+    /// 关闭发送端, 等待写入线程写完队列中的事件
+    fn drop(&mut self) {
+        *self.sender.lock().unwrap() = channel().0;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// 事件保存为 {namespace}.{task} 中的一行, event 列为事件的 JSON, event_log_pos 为事件 id
+fn to_relay_log(event: &StreamEvent) -> RelayLog {
+    let mut column = RelayColumnInfo::default();
+    column.set_column_name(String::from("event"));
+    column.set_column_type(DstColumnType::JSON);
+    let mut row = RelayRowData::default();
+    row.set_values(vec![Value::JSON(event.event.to_string())]);
+
+    let mut log = RelayLog::default();
+    log.set_event_log_pos(event.id);
+    log.set_event_name(event.event_type.clone());
+    log.set_database_name(event.namespace.clone());
+    log.set_table_name(event.task.clone());
+    log.set_columns(vec![column]);
+    log.set_relay_command(RelayCommand::Insert(vec![row]));
+    log
+}
+
+fn from_relay_log(log: &RelayLog) -> Option<StreamEvent> {
+    let json = match log.relay_command() {
+        RelayCommand::Insert(rows) => match rows.first()?.values().first()? {
+            Value::JSON(json) => json,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(StreamEvent {
+        id: *log.event_log_pos(),
+        namespace: log.database_name().clone(),
+        task: log.table_name().clone(),
+        event_type: log.event_name().clone(),
+        event: serde_json::from_str(json).ok()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    use crate::stream::event_hub::StreamEvent;
+    use crate::stream::event_journal::{from_relay_log, to_relay_log, EventJournal};
+
+    fn event(id: u64, namespace: &str) -> StreamEvent {
+        StreamEvent {
+            id,
+            namespace: namespace.to_string(),
+            task: String::from("t1"),
+            event_type: String::from("WriteRows"),
+            event: json!({ "rows": [id] }),
+        }
+    }
+
+    #[test]
+    fn test_journal() {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("event_journal_{}_{}", std::process::id(), nanos));
+        let dir = dir.to_str().unwrap();
+
+        let e = from_relay_log(&to_relay_log(&event(7, "team-a"))).unwrap();
+        assert_eq!(e.to_json(), event(7, "team-a").to_json());
+        assert_eq!(e.namespace, "team-a");
+
+        let (journal, last_id) = EventJournal::open(dir).unwrap();
+        assert_eq!(last_id, 0);
+        for id in 1..=10 {
+            journal.append(&event(id, if id % 2 == 0 { "default" } else { "team-a" }));
+        }
+        // 等待写入线程提交
+        let mut events = vec![];
+        for _ in 0..100 {
+            events = journal.read("default", 3, 10).unwrap().1;
+            if events.len() == 4 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        let ids: Vec<u64> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![4, 6, 8, 10]);
+        assert!(journal.read("default", 10, 10).unwrap().1.is_empty());
+        assert_eq!(journal.read("team-a", 0, 10).unwrap().0, 1);
+        drop(journal);
+
+        // 重启后 id 接着最后一个事件编号
+        let (_, last_id) = EventJournal::open(dir).unwrap();
+        assert_eq!(last_id, 10);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+pub mod event_hub;
+pub mod event_journal;
+pub mod schema_tracker;
+
+use binlog::events::binlog_event::BinlogEvent;
+
+use crate::stream::event_hub::EVENT_HUB;
+use crate::stream::schema_tracker::SCHEMA_TRACKER;
+use crate::webhook::webhook_manager::WebhookManager;
+use crate::wss::session_manager::SessionManager;
+
+/// 任务读取到的事件: 编号后写入 EVENT_HUB(供 /events 订阅与断点续传, 同时写入中继日志), 并推送给同一命名空间的 WebSocket 会话。
+/// 同时投递给匹配的 webhook, TableMapEvent 记录到 SCHEMA_TRACKER。 返回推送的字节数
+pub fn publish(namespace: &str, task: &str, event: &BinlogEvent) -> usize {
+    if let BinlogEvent::TableMap(e) = event {
+        SCHEMA_TRACKER.track(namespace, e);
+    }
+
+    let data = match serde_json::to_value(event) {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("serialize event error: {}", e);
+            return 0;
+        }
+    };
+
+    // 在 EVENT_HUB 的锁内推送, 各会话按 id 顺序收到事件
+    let stream_event = EVENT_HUB.publish_with(namespace, task, &BinlogEvent::get_type_name(event), data,
+                                              SessionManager::ws_broadcast_event);
+    WebhookManager::dispatch(namespace, task, event, &stream_event);
+
+    stream_event.to_json().len()
+}
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use binlog::events::protocol::table_map_event::TableMapEvent;
+use common::binlog::column::column_type::SrcColumnType;
+use common::time_util::now_str;
+
+/// 每张表保留的历史版本数
+const MAX_VERSIONS: usize = 32;
+
+lazy_static! {
+    pub static ref SCHEMA_TRACKER: SchemaTracker = SchemaTracker::default();
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnDefinition {
+    /// binlog_row_metadata 不为 FULL 时没有列名, 使用 `@1`、`@2`
+    pub name: String,
+    pub column_type: Option<SrcColumnType>,
+    pub unsigned: bool,
+    pub nullable: bool,
+}
+
+/// 表结构的一个版本, 列定义变化时(如 ALTER TABLE 后的第一个 TableMapEvent)生成新版本
+#[derive(Debug, Clone, Serialize)]
+pub struct TableVersion {
+    /// 从 1 开始
+    pub version: u32,
+    pub columns: Vec<ColumnDefinition>,
+    pub primary_key: Vec<String>,
+    /// 首次读取到该版本的时间
+    pub since: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableHistory {
+    pub database: String,
+    pub table: String,
+    /// 按版本号升序, 最后一个为当前版本
+    pub versions: Vec<TableVersion>,
+}
+
+impl TableHistory {
+    pub fn current(&self) -> Option<&TableVersion> {
+        self.versions.last()
+    }
+}
+
+/// 按命名空间记录任务读取到的表结构, 来源为 TableMapEvent
+#[derive(Debug, Default)]
+pub struct SchemaTracker {
+    /// (namespace, db, table) -> history
+    tables: RwLock<BTreeMap<(String, String, String), TableHistory>>,
+}
+
+impl SchemaTracker {
+    pub fn track(&self, namespace: &str, event: &TableMapEvent) {
+        let infos = event.get_column_infos();
+        let columns: Vec<ColumnDefinition> = infos.iter().enumerate()
+            .map(|(idx, info)| ColumnDefinition {
+                name: Some(info.get_name()).filter(|n| !n.is_empty()).unwrap_or_else(|| format!("@{}", idx + 1)),
+                column_type: info.get_c_type(),
+                unsigned: info.is_unsigned(),
+                nullable: info.is_nullable(),
+            })
+            .collect();
+        let primary_key = infos.iter().zip(columns.iter())
+            .filter(|(info, _)| info.is_pk())
+            .map(|(_, c)| c.name.clone())
+            .collect();
+
+        self.update(namespace, &event.get_database_name(), &event.get_table_name(), columns, primary_key);
+    }
+
+    fn update(&self, namespace: &str, db: &str, table: &str, columns: Vec<ColumnDefinition>, primary_key: Vec<String>) {
+        let key = (namespace.to_string(), db.to_string(), table.to_string());
+        let mut tables = self.tables.write().unwrap();
+        let history = tables.entry(key).or_insert_with(|| TableHistory {
+            database: db.to_string(),
+            table: table.to_string(),
+            versions: vec![],
+        });
+
+        if let Some(current) = history.current() {
+            if current.columns == columns && current.primary_key == primary_key {
+                return;
+            }
+        }
+
+        let version = history.current().map_or(1, |v| v.version + 1);
+        if history.versions.len() >= MAX_VERSIONS {
+            history.versions.remove(0);
+        }
+        history.versions.push(TableVersion { version, columns, primary_key, since: now_str() });
+    }
+
+    pub fn get(&self, namespace: &str, db: &str, table: &str) -> Option<TableHistory> {
+        let key = (namespace.to_string(), db.to_string(), table.to_string());
+        self.tables.read().unwrap().get(&key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::binlog::column::column_type::SrcColumnType;
+
+    use crate::stream::schema_tracker::{ColumnDefinition, SchemaTracker};
+
+    fn column(name: &str, column_type: SrcColumnType) -> ColumnDefinition {
+        ColumnDefinition { name: name.to_string(), column_type: Some(column_type), unsigned: false, nullable: true }
+    }
+
+    #[test]
+    fn test_versions() {
+        let tracker = SchemaTracker::default();
+        let v1 = vec![column("id", SrcColumnType::Long), column("name", SrcColumnType::VarChar)];
+        tracker.update("default", "db1", "t1", v1.clone(), vec![String::from("id")]);
+        tracker.update("default", "db1", "t1", v1.clone(), vec![String::from("id")]);
+        assert_eq!(tracker.get("default", "db1", "t1").unwrap().versions.len(), 1);
+
+        let mut v2 = v1.clone();
+        v2.push(column("age", SrcColumnType::Long));
+        tracker.update("default", "db1", "t1", v2, vec![String::from("id")]);
+
+        let history = tracker.get("default", "db1", "t1").unwrap();
+        assert_eq!(history.versions.len(), 2);
+        assert_eq!(history.current().unwrap().version, 2);
+        assert_eq!(history.current().unwrap().columns.len(), 3);
+
+        assert!(tracker.get("team-a", "db1", "t1").is_none());
+    }
+}
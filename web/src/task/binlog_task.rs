@@ -0,0 +1,400 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use common::config::BinlogConfig;
+use common::err::decode_error::ReError;
+use common::server::{ComponentHealth, HealthStatus, PauseSwitch, Server};
+use common::time_util::now_str;
+use connection::binlog::binlog_subscribe::{BinlogSubscribe, SubscribeOptions};
+
+use crate::config::runtime::LiveSettings;
+use crate::namespace::DEFAULT_NAMESPACE;
+use crate::stream;
+use crate::web_error::{WebError, WResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    Created,
+    Running,
+    /// 被 pause 暂停, 连接仍然保留
+    Paused,
+    /// 已调用 stop, pipeline 线程尚未退出, 此时不能重新启动
+    Stopping,
+    /// 被 stop 停止
+    Stopped,
+    /// 非 follow 模式下读取到最后一个事件
+    Finished,
+    Failed,
+}
+
+/// POST /api/tasks 的请求体, 未指定的项使用 BinlogConfig 的默认值
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreateTaskRequest {
+    pub name: Option<String>,
+
+    pub host: Option<String>,
+    pub port: Option<i16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    pub file: Option<String>,
+    pub position: Option<i32>,
+    pub server_id: Option<u32>,
+
+    /// 持续读取, 默认 true
+    pub follow: Option<bool>,
+}
+
+impl CreateTaskRequest {
+    pub fn binlog_config(&self) -> BinlogConfig {
+        let mut config = BinlogConfig::default();
+        if self.host.is_some() {
+            config.set_host(self.host.clone());
+        }
+        if self.port.is_some() {
+            config.set_port(self.port);
+        }
+        if let Some(username) = &self.username {
+            config.username = username.clone();
+        }
+        if let Some(password) = &self.password {
+            config.password = password.clone();
+        }
+        // 空的 file 表示从第一个 binlog 开始
+        config.file = self.file.clone().filter(|f| !f.is_empty());
+        config.position = self.position;
+        config.server_id = self.server_id;
+
+        config
+    }
+}
+
+/// 任务的对外视图, 不包含密码
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskView {
+    pub id: String,
+    pub namespace: String,
+    pub name: String,
+    pub host: String,
+    pub port: i16,
+    pub file: Option<String>,
+    pub position: Option<i32>,
+    pub follow: bool,
+    pub status: TaskStatus,
+    pub error: Option<String>,
+    pub create_time: String,
+    pub start_time: Option<String>,
+}
+
+#[derive(Debug)]
+struct TaskState {
+    status: TaskStatus,
+    error: Option<String>,
+    start_time: Option<String>,
+    /// 运行中的 pipeline 的停止标记
+    shutdown: Option<Arc<AtomicBool>>,
+    /// 运行中的 pipeline 的暂停开关
+    pause: Option<PauseSwitch>,
+    /// 每次 start 加一, 只有当前一次运行的结束才更新状态
+    run: u64,
+}
+
+/// 一条 binlog pipeline: 在独立线程中以 Server 的方式运行 BinlogSubscribe
+#[derive(Debug)]
+pub struct BinlogTask {
+    id: String,
+    namespace: String,
+    name: String,
+    binlog_config: BinlogConfig,
+    follow: bool,
+    create_time: String,
+
+    state: Mutex<TaskState>,
+}
+
+impl BinlogTask {
+    pub fn new(id: String, request: &CreateTaskRequest) -> Self {
+        BinlogTask {
+            name: request.name.clone().unwrap_or_else(|| id.clone()),
+            id,
+            namespace: String::from(DEFAULT_NAMESPACE),
+            binlog_config: request.binlog_config(),
+            follow: request.follow.unwrap_or(true),
+            create_time: now_str(),
+            state: Mutex::new(TaskState {
+                status: TaskStatus::Created,
+                error: None,
+                start_time: None,
+                shutdown: None,
+                pause: None,
+                run: 0,
+            }),
+        }
+    }
+
+    /// 任务所属的命名空间, 事件只推送给同一命名空间的订阅者
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn get_binlog_config(&self) -> &BinlogConfig {
+        &self.binlog_config
+    }
+
+    pub fn status(&self) -> TaskStatus {
+        self.state.lock().unwrap().status
+    }
+
+    pub fn view(&self) -> TaskView {
+        let state = self.state.lock().unwrap();
+
+        TaskView {
+            id: self.id.clone(),
+            namespace: self.namespace.clone(),
+            name: self.name.clone(),
+            host: self.binlog_config.get_host().to_string(),
+            port: self.binlog_config.get_port(),
+            file: self.binlog_config.file.clone(),
+            position: self.binlog_config.position,
+            follow: self.follow,
+            status: state.status,
+            error: state.error.clone(),
+            create_time: self.create_time.clone(),
+            start_time: state.start_time.clone(),
+        }
+    }
+
+    /// 在后台线程中启动 pipeline, 运行中与停止中的任务不能重复启动
+    pub fn start(self: &Arc<Self>) -> WResult<()> {
+        let mut state = self.state.lock()?;
+        match state.status {
+            TaskStatus::Running | TaskStatus::Paused => return Err(WebError::Value(format!("task {} is running", self.id))),
+            TaskStatus::Stopping => return Err(WebError::Value(format!("task {} is stopping", self.id))),
+            _ => {},
+        }
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let pause = PauseSwitch::new();
+        state.status = TaskStatus::Running;
+        state.error = None;
+        state.start_time = Some(now_str());
+        state.shutdown = Some(shutdown.clone());
+        state.pause = Some(pause.clone());
+        state.run += 1;
+        let run = state.run;
+
+        let task = self.clone();
+        thread::Builder::new().name(format!("task-{}", self.id)).spawn(move || {
+            let rs = task.run(shutdown, pause);
+            task.finish(run, rs);
+        })?;
+
+        Ok(())
+    }
+
+    fn run(&self, shutdown: Arc<AtomicBool>, pause: PauseSwitch) -> Result<(), ReError> {
+        let mut options = SubscribeOptions::default();
+        options.set_follow(self.follow);
+        let task_id = self.id.clone();
+        let namespace = self.namespace.clone();
+        // 过滤、脱敏与限速随 POST /api/config 实时更新
+        let mut live = LiveSettings::new();
+        let mut subscribe = BinlogSubscribe::new(false, self.binlog_config.clone(), options)
+            .with_shutdown(shutdown)
+            .with_pause(pause)
+            .with_listener(Box::new(move |e| {
+                if let Some(event) = live.apply(e) {
+                    let bytes = stream::publish(&namespace, &task_id, &event);
+                    live.throttle(bytes as u64);
+                }
+            }));
+
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        rt.block_on(subscribe.start())
+    }
+
+    /// 第 run 次运行结束, 之后已重新启动时忽略
+    fn finish(&self, run: u64, rs: Result<(), ReError>) {
+        let mut state = self.state.lock().unwrap();
+        if state.run != run {
+            return;
+        }
+        let stopped = state.shutdown.take().map_or(false, |s| s.load(Ordering::SeqCst));
+        state.pause = None;
+
+        state.status = match rs {
+            _ if stopped => TaskStatus::Stopped,
+            Ok(_) => TaskStatus::Finished,
+            Err(e) => {
+                log::error!("task {} failed: {}", self.id, e);
+                state.error = Some(e.to_string());
+                TaskStatus::Failed
+            },
+        };
+    }
+
+    /// 通知 pipeline 停止, pipeline 在读取到下一个事件时退出, 退出前状态为 Stopping
+    pub fn stop(&self) -> WResult<()> {
+        let mut state = self.state.lock()?;
+        match state.shutdown.as_ref() {
+            Some(shutdown) => {
+                shutdown.store(true, Ordering::SeqCst);
+                state.status = TaskStatus::Stopping;
+                Ok(())
+            },
+            None => Err(WebError::Value(format!("task {} is not running", self.id))),
+        }
+    }
+
+    /// 暂停 pipeline, 在读取到下一个事件前挂起, 不断开与 MySQL 的连接
+    pub fn pause(&self) -> WResult<()> {
+        let mut state = self.state.lock()?;
+        match (state.status, state.pause.as_ref()) {
+            (TaskStatus::Running, Some(pause)) => {
+                pause.pause();
+                state.status = TaskStatus::Paused;
+                Ok(())
+            },
+            _ => Err(WebError::Value(format!("task {} is not running", self.id))),
+        }
+    }
+
+    pub fn resume(&self) -> WResult<()> {
+        let mut state = self.state.lock()?;
+        match (state.status, state.pause.as_ref()) {
+            (TaskStatus::Paused, Some(pause)) => {
+                pause.resume();
+                state.status = TaskStatus::Running;
+                Ok(())
+            },
+            _ => Err(WebError::Value(format!("task {} is not paused", self.id))),
+        }
+    }
+
+    /// 运行、暂停或停止中, 即 pipeline 线程仍在运行
+    pub fn is_active(&self) -> bool {
+        matches!(self.status(), TaskStatus::Running | TaskStatus::Paused | TaskStatus::Stopping)
+    }
+
+    pub fn health(&self) -> ComponentHealth {
+        let state = self.state.lock().unwrap();
+        let status = match state.status {
+            TaskStatus::Created => HealthStatus::Starting,
+            TaskStatus::Running => HealthStatus::Running,
+            TaskStatus::Paused => HealthStatus::Paused,
+            TaskStatus::Stopping | TaskStatus::Stopped | TaskStatus::Finished => HealthStatus::Stopped,
+            TaskStatus::Failed => HealthStatus::Degraded,
+        };
+
+        ComponentHealth::new(&format!("task:{}", self.id), status).with_message(state.error.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use common::err::decode_error::ReError;
+    use common::server::{HealthStatus, PauseSwitch};
+
+    use crate::task::binlog_task::{BinlogTask, CreateTaskRequest, TaskStatus};
+
+    #[test]
+    fn test_lifecycle() {
+        let request = CreateTaskRequest {
+            host: Some(String::from("10.0.0.1")),
+            file: Some(String::new()),
+            ..CreateTaskRequest::default()
+        };
+        let task = BinlogTask::new(String::from("t1"), &request);
+        let view = task.view();
+        assert_eq!(view.name, "t1");
+        assert_eq!(view.namespace, "default");
+        assert_eq!(view.host, "10.0.0.1");
+        assert_eq!(view.file, None);
+        assert_eq!(view.status, TaskStatus::Created);
+        assert!(task.stop().is_err());
+
+        task.state.lock().unwrap().shutdown = Some(Arc::new(AtomicBool::new(false)));
+        task.finish(0, Err(ReError::String(String::from("refused"))));
+        assert_eq!(task.status(), TaskStatus::Failed);
+        assert_eq!(task.view().error.as_deref(), Some("refused"));
+        assert_eq!(task.health().status, HealthStatus::Degraded);
+    }
+
+    #[test]
+    fn test_pause() {
+        let task = BinlogTask::new(String::from("t2"), &CreateTaskRequest::default());
+        assert!(task.pause().is_err());
+
+        let pause = PauseSwitch::new();
+        {
+            let mut state = task.state.lock().unwrap();
+            state.status = TaskStatus::Running;
+            state.shutdown = Some(Arc::new(AtomicBool::new(false)));
+            state.pause = Some(pause.clone());
+        }
+        assert!(task.resume().is_err());
+        task.pause().unwrap();
+        assert!(pause.is_paused());
+        assert!(task.is_active());
+        assert_eq!(task.health().status, HealthStatus::Paused);
+
+        task.resume().unwrap();
+        assert!(!pause.is_paused());
+        assert_eq!(task.status(), TaskStatus::Running);
+
+        task.finish(0, Ok(()));
+        assert_eq!(task.status(), TaskStatus::Finished);
+        assert!(task.pause().is_err());
+    }
+
+    #[test]
+    fn test_stop_then_start() {
+        let task = BinlogTask::new(String::from("t3"), &CreateTaskRequest::default());
+        let old = Arc::new(AtomicBool::new(false));
+        {
+            let mut state = task.state.lock().unwrap();
+            state.status = TaskStatus::Running;
+            state.shutdown = Some(old.clone());
+            state.run = 1;
+        }
+
+        // 线程退出前为 Stopping, 不能重新启动
+        task.stop().unwrap();
+        assert!(old.load(Ordering::SeqCst));
+        assert_eq!(task.status(), TaskStatus::Stopping);
+        assert!(task.is_active());
+        assert!(Arc::new(task).start().is_err());
+
+        // 旧的运行结束时不影响新一次运行的状态与停止标记
+        let task = BinlogTask::new(String::from("t4"), &CreateTaskRequest::default());
+        let current = Arc::new(AtomicBool::new(false));
+        {
+            let mut state = task.state.lock().unwrap();
+            state.status = TaskStatus::Running;
+            state.shutdown = Some(current.clone());
+            state.run = 2;
+        }
+        task.finish(1, Err(ReError::String(String::from("closed"))));
+        assert_eq!(task.status(), TaskStatus::Running);
+        assert!(task.state.lock().unwrap().shutdown.is_some());
+        assert!(task.stop().is_ok());
+        task.finish(2, Ok(()));
+        assert_eq!(task.status(), TaskStatus::Stopped);
+    }
+}
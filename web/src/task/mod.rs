@@ -0,0 +1,2 @@
+pub mod binlog_task;
+pub mod task_manager;
@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+use common::uuid::uuid_timestamp;
+
+use crate::namespace::{scoped_key, Quota};
+use crate::task::binlog_task::{BinlogTask, CreateTaskRequest};
+use crate::web_error::{WebError, WResult};
+
+lazy_static! {
+    /// namespace/id -> task
+    static ref TASKS: RwLock<BTreeMap<String, Arc<BinlogTask>>> = RwLock::new(BTreeMap::new());
+}
+
+/// 通过 /api/tasks 管理的全部任务, 按命名空间隔离
+pub struct TaskManager {
+
+}
+
+impl TaskManager {
+
+    /// 超出命名空间的任务配额时返回错误
+    pub fn create(namespace: &str, request: &CreateTaskRequest) -> WResult<Arc<BinlogTask>> {
+        let mut tasks = TASKS.write()?;
+        let used = tasks.values().filter(|t| t.get_namespace() == namespace).count();
+        Quota::check(Quota::from_config().max_tasks, used, "task", namespace)?;
+
+        let task = Arc::new(BinlogTask::new(uuid_timestamp(), request).with_namespace(namespace));
+        tasks.insert(scoped_key(namespace, task.get_id()), task.clone());
+
+        Ok(task)
+    }
+
+    pub fn get(namespace: &str, id: &str) -> WResult<Arc<BinlogTask>> {
+        TASKS.read()?.get(&scoped_key(namespace, id)).cloned()
+            .ok_or_else(|| WebError::Value(format!("task {} not found", id)))
+    }
+
+    pub fn list(namespace: &str) -> Vec<Arc<BinlogTask>> {
+        TASKS.read().unwrap().values()
+            .filter(|t| t.get_namespace() == namespace)
+            .cloned()
+            .collect()
+    }
+
+    /// 全部命名空间中的任务
+    pub fn all() -> Vec<Arc<BinlogTask>> {
+        TASKS.read().unwrap().values().cloned().collect()
+    }
+
+    /// 删除任务, 运行中的任务先停止
+    pub fn remove(namespace: &str, id: &str) -> WResult<Arc<BinlogTask>> {
+        let task = Self::get(namespace, id)?;
+        if task.is_active() {
+            task.stop()?;
+        }
+
+        TASKS.write()?.remove(&scoped_key(namespace, id));
+        Ok(task)
+    }
+}
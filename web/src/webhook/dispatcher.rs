@@ -0,0 +1,120 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use actix_web::rt::System;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+
+use crate::stream::event_hub::StreamEvent;
+use crate::webhook::webhook::Webhook;
+use crate::web_error::{WebError, WResult};
+
+/// 每个 webhook 待投递的事件数, 超出时丢弃并计入 failed
+const QUEUE_CAPACITY: usize = 1024;
+
+/// 投递统计
+#[derive(Debug, Default)]
+pub struct DeliveryStats {
+    pub delivered: AtomicU64,
+    pub failed: AtomicU64,
+    pub last_error: Mutex<Option<String>>,
+}
+
+impl DeliveryStats {
+    fn fail(&self, error: String) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        *self.last_error.lock().unwrap() = Some(error);
+    }
+}
+
+/// 在独立线程中按顺序投递一个 webhook 的事件, Dispatcher 被 drop 后线程退出
+#[derive(Debug)]
+pub struct Dispatcher {
+    sender: mpsc::Sender<StreamEvent>,
+    stats: Arc<DeliveryStats>,
+}
+
+impl Dispatcher {
+    pub fn start(webhook: Webhook, stats: Arc<DeliveryStats>) -> WResult<Self> {
+        let (sender, mut receiver) = mpsc::channel::<StreamEvent>(QUEUE_CAPACITY);
+
+        let thread_stats = stats.clone();
+        thread::Builder::new().name(format!("webhook-{}", webhook.id)).spawn(move || {
+            System::new().block_on(async move {
+                let client = awc::Client::builder()
+                    .timeout(Duration::from_millis(webhook.policy.timeout_ms))
+                    .finish();
+                while let Some(event) = receiver.recv().await {
+                    match deliver(&client, &webhook, &event).await {
+                        Ok(_) => { thread_stats.delivered.fetch_add(1, Ordering::Relaxed); },
+                        Err(e) => {
+                            log::warn!("webhook {} drop event {}: {}", webhook.id, event.id, e);
+                            thread_stats.fail(e.to_string());
+                        },
+                    }
+                }
+            });
+        }).map_err(|e| WebError::Value(e.to_string()))?;
+
+        Ok(Dispatcher { sender, stats })
+    }
+
+    /// 加入投递队列, 不阻塞读取事件的任务
+    pub fn offer(&self, event: &StreamEvent) {
+        if self.sender.try_send(event.clone()).is_err() {
+            self.stats.fail(String::from("delivery queue is full"));
+        }
+    }
+}
+
+/// 投递一个事件, 失败时按 policy 重试
+async fn deliver(client: &awc::Client, webhook: &Webhook, event: &StreamEvent) -> WResult<()> {
+    let body = event.to_json();
+    let signature = webhook.secret.as_ref().map(|s| sign(s, body.as_bytes()));
+
+    let mut attempt = 0;
+    loop {
+        let mut request = client.post(&webhook.url)
+            .insert_header(("Content-Type", "application/json"))
+            .insert_header(("X-Webhook-Id", webhook.id.as_str()))
+            .insert_header(("X-Event-Id", event.id.to_string()));
+        if let Some(signature) = signature.as_ref() {
+            request = request.insert_header(("X-Webhook-Signature", format!("sha256={}", signature)));
+        }
+
+        let error = match request.send_body(body.clone()).await {
+            Ok(resp) if resp.status().is_success() => return Ok(()),
+            Ok(resp) => format!("{} responded {}", webhook.url, resp.status()),
+            Err(e) => format!("{}: {}", webhook.url, e),
+        };
+
+        attempt += 1;
+        if attempt > webhook.policy.max_retries {
+            return Err(WebError::Value(error));
+        }
+        tokio::time::sleep(webhook.policy.backoff(attempt)).await;
+    }
+}
+
+/// 请求体的 HMAC-SHA256, 十六进制
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::webhook::dispatcher::sign;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(sign("Jefe", b"what do ya want for nothing?"),
+                   "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod webhook;
+pub mod webhook_manager;
+pub mod dispatcher;
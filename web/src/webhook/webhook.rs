@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use common::time_util::now_str;
+
+use crate::web_error::{WebError, WResult};
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 500;
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+/// POST /api/webhooks 的请求体
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WebhookRequest {
+    /// 接收事件的地址, http 或 https
+    pub url: String,
+
+    /// `db.table`, 支持 `db.*` 与 `*`, 为空时推送全部表
+    pub tables: Option<Vec<String>>,
+    /// 事件类型, 如 `WriteRows`、`QueryEvent`, 为空时推送全部类型
+    pub event_types: Option<Vec<String>>,
+
+    /// 用于签名请求体, 签名放在 `X-Webhook-Signature: sha256=<hex>` 头中
+    pub secret: Option<String>,
+
+    /// 投递失败后的重试次数, 默认 3
+    pub max_retries: Option<u32>,
+    /// 首次重试的等待时间, 之后每次翻倍, 默认 500ms
+    pub retry_backoff_ms: Option<u64>,
+    /// 单次请求的超时时间, 默认 5000ms
+    pub timeout_ms: Option<u64>,
+}
+
+/// 投递策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DeliveryPolicy {
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+    pub timeout_ms: u64,
+}
+
+impl DeliveryPolicy {
+    /// 第 attempt 次重试(从 1 开始)前的等待时间
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.retry_backoff_ms.saturating_mul(1 << (attempt - 1).min(16)))
+    }
+}
+
+/// 一个 webhook 订阅, 只接收所属命名空间的事件
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub namespace: String,
+    pub url: String,
+    pub tables: Vec<String>,
+    pub event_types: Vec<String>,
+    pub secret: Option<String>,
+    pub policy: DeliveryPolicy,
+    pub create_time: String,
+}
+
+/// webhook 的对外视图, 不包含 secret
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookView {
+    pub id: String,
+    pub namespace: String,
+    pub url: String,
+    pub tables: Vec<String>,
+    pub event_types: Vec<String>,
+    pub signed: bool,
+    pub policy: DeliveryPolicy,
+    pub create_time: String,
+    pub delivered: u64,
+    pub failed: u64,
+    pub last_error: Option<String>,
+}
+
+impl Webhook {
+    pub fn new(id: String, namespace: &str, request: &WebhookRequest) -> WResult<Self> {
+        let url = request.url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(WebError::Value(format!("invalid webhook url {}, expect http(s)://", url)));
+        }
+        let tables = request.tables.clone().unwrap_or_default();
+        if let Some(table) = tables.iter().find(|t| *t != "*" && !t.contains('.')) {
+            return Err(WebError::Value(format!("invalid table {}, expect db.table, db.* or *", table)));
+        }
+
+        Ok(Webhook {
+            id,
+            namespace: namespace.to_string(),
+            url: url.to_string(),
+            tables,
+            event_types: request.event_types.clone().unwrap_or_default(),
+            secret: request.secret.clone().filter(|s| !s.is_empty()),
+            policy: DeliveryPolicy {
+                max_retries: request.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+                retry_backoff_ms: request.retry_backoff_ms.unwrap_or(DEFAULT_RETRY_BACKOFF_MS),
+                timeout_ms: request.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS),
+            },
+            create_time: now_str(),
+        })
+    }
+
+    /// table 为 `(db, table)`, 非行事件为 None。 指定了 tables 时只推送匹配的行事件
+    pub fn matches(&self, event_type: &str, table: Option<(&str, &str)>) -> bool {
+        let type_matched = self.event_types.is_empty() || self.event_types.iter().any(|t| {
+            t.eq_ignore_ascii_case(event_type)
+                || event_type.strip_suffix("Event").map_or(false, |e| t.eq_ignore_ascii_case(e))
+        });
+        if !type_matched {
+            return false;
+        }
+        if self.tables.is_empty() {
+            return true;
+        }
+
+        match table {
+            None => false,
+            Some((db, table)) => self.tables.iter().any(|pattern| match pattern.split_once('.') {
+                None => pattern == "*",
+                Some((d, t)) => (d == "*" || d == db) && (t == "*" || t == table),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::webhook::webhook::{Webhook, WebhookRequest};
+
+    #[test]
+    fn test_matches() {
+        let request = WebhookRequest {
+            url: String::from("https://example.com/hook"),
+            tables: Some(vec![String::from("db1.*"), String::from("db2.orders")]),
+            event_types: Some(vec![String::from("WriteRows")]),
+            ..WebhookRequest::default()
+        };
+        let hook = Webhook::new(String::from("h1"), "default", &request).unwrap();
+        assert!(hook.matches("WriteRowsEvent", Some(("db1", "users"))));
+        assert!(hook.matches("WriteRowsEvent", Some(("db2", "orders"))));
+        assert!(!hook.matches("WriteRowsEvent", Some(("db2", "users"))));
+        assert!(!hook.matches("DeleteRowsEvent", Some(("db1", "users"))));
+        assert!(!hook.matches("WriteRowsEvent", None));
+
+        assert_eq!(hook.policy.backoff(1).as_millis(), 500);
+        assert_eq!(hook.policy.backoff(3).as_millis(), 2000);
+
+        let request = WebhookRequest { url: String::from("ftp://example.com"), ..WebhookRequest::default() };
+        assert!(Webhook::new(String::from("h2"), "default", &request).is_err());
+    }
+}
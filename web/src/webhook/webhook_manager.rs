@@ -0,0 +1,137 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+use binlog::events::binlog_event::BinlogEvent;
+use common::uuid::uuid_timestamp;
+
+use crate::namespace::scoped_key;
+use crate::stream::event_hub::StreamEvent;
+use crate::webhook::dispatcher::{DeliveryStats, Dispatcher};
+use crate::webhook::webhook::{Webhook, WebhookRequest, WebhookView};
+use crate::web_error::{WebError, WResult};
+
+struct Registration {
+    webhook: Webhook,
+    stats: Arc<DeliveryStats>,
+    dispatcher: Dispatcher,
+}
+
+impl Registration {
+    fn view(&self) -> WebhookView {
+        let hook = &self.webhook;
+        WebhookView {
+            id: hook.id.clone(),
+            namespace: hook.namespace.clone(),
+            url: hook.url.clone(),
+            tables: hook.tables.clone(),
+            event_types: hook.event_types.clone(),
+            signed: hook.secret.is_some(),
+            policy: hook.policy,
+            create_time: hook.create_time.clone(),
+            delivered: self.stats.delivered.load(Ordering::Relaxed),
+            failed: self.stats.failed.load(Ordering::Relaxed),
+            last_error: self.stats.last_error.lock().unwrap().clone(),
+        }
+    }
+}
+
+lazy_static! {
+    /// namespace/id -> webhook
+    static ref WEBHOOKS: RwLock<BTreeMap<String, Registration>> = RwLock::new(BTreeMap::new());
+
+    /// (namespace/task, table_id) -> (db, table), 用于按表过滤行事件
+    static ref TABLES: RwLock<HashMap<(String, u64), (String, String)>> = RwLock::new(HashMap::new());
+}
+
+/// 通过 /api/webhooks 管理的 webhook 订阅
+pub struct WebhookManager {
+
+}
+
+impl WebhookManager {
+
+    pub fn create(namespace: &str, request: &WebhookRequest) -> WResult<WebhookView> {
+        let webhook = Webhook::new(uuid_timestamp(), namespace, request)?;
+        let key = scoped_key(namespace, &webhook.id);
+        let registration = Self::register(webhook)?;
+        let view = registration.view();
+        WEBHOOKS.write().unwrap().insert(key, registration);
+
+        Ok(view)
+    }
+
+    /// 替换 webhook 的配置, 保留 id 与统计, 未投递的事件丢弃
+    pub fn update(namespace: &str, id: &str, request: &WebhookRequest) -> WResult<WebhookView> {
+        let key = scoped_key(namespace, id);
+        let mut webhooks = WEBHOOKS.write().unwrap();
+        let old = webhooks.get(&key).ok_or_else(|| WebError::Value(format!("webhook {} not found", id)))?;
+
+        let mut webhook = Webhook::new(id.to_string(), namespace, request)?;
+        webhook.create_time = old.webhook.create_time.clone();
+        let stats = old.stats.clone();
+        let dispatcher = Dispatcher::start(webhook.clone(), stats.clone())?;
+
+        let registration = Registration { webhook, stats, dispatcher };
+        let view = registration.view();
+        webhooks.insert(key, registration);
+
+        Ok(view)
+    }
+
+    pub fn get(namespace: &str, id: &str) -> WResult<WebhookView> {
+        WEBHOOKS.read().unwrap().get(&scoped_key(namespace, id)).map(|r| r.view())
+            .ok_or_else(|| WebError::Value(format!("webhook {} not found", id)))
+    }
+
+    pub fn list(namespace: &str) -> Vec<WebhookView> {
+        WEBHOOKS.read().unwrap().values()
+            .filter(|r| r.webhook.namespace == namespace)
+            .map(|r| r.view())
+            .collect()
+    }
+
+    pub fn remove(namespace: &str, id: &str) -> WResult<WebhookView> {
+        WEBHOOKS.write().unwrap().remove(&scoped_key(namespace, id)).map(|r| r.view())
+            .ok_or_else(|| WebError::Value(format!("webhook {} not found", id)))
+    }
+
+    /// 将事件加入同一命名空间中匹配的 webhook 的投递队列
+    pub fn dispatch(namespace: &str, task: &str, event: &BinlogEvent, stream_event: &StreamEvent) {
+        let webhooks = WEBHOOKS.read().unwrap();
+        if webhooks.is_empty() {
+            return;
+        }
+
+        let task_key = scoped_key(namespace, task);
+        let table_id = match event {
+            BinlogEvent::TableMap(e) => {
+                TABLES.write().unwrap().insert((task_key.clone(), e.table_id),
+                                               (e.get_database_name(), e.get_table_name()));
+                Some(e.table_id)
+            },
+            BinlogEvent::WriteRows(e) => Some(e.table_id),
+            BinlogEvent::UpdateRows(e) => Some(e.table_id),
+            BinlogEvent::DeleteRows(e) => Some(e.table_id),
+            _ => None,
+        };
+        let tables = TABLES.read().unwrap();
+        let table = table_id.and_then(|id| tables.get(&(task_key, id)))
+            .map(|(db, table)| (db.as_str(), table.as_str()));
+
+        for registration in webhooks.values().filter(|r| r.webhook.namespace == namespace) {
+            if registration.webhook.matches(&stream_event.event_type, table) {
+                registration.dispatcher.offer(stream_event);
+            }
+        }
+    }
+
+    fn register(webhook: Webhook) -> WResult<Registration> {
+        let stats = Arc::new(DeliveryStats::default());
+        let dispatcher = Dispatcher::start(webhook.clone(), stats.clone())?;
+
+        Ok(Registration { webhook, stats, dispatcher })
+    }
+}
@@ -0,0 +1,178 @@
+use common::throttle::RateLimiter;
+
+use crate::config::constant::CFG;
+use crate::wss::encoding::{Frame, FrameEncoding};
+use crate::web_error::WebError;
+
+/// 会话发送队列已满或超过速率时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowConsumerPolicy {
+    /// 丢弃事件, 恢复后推送一条丢弃数量的通知
+    Drop,
+    /// 暂存事件(最多 max_queue 个), 恢复后合并为一个数组推送
+    Coalesce,
+    /// 断开会话
+    Disconnect,
+}
+
+impl TryFrom<&str> for SlowConsumerPolicy {
+    type Error = WebError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "drop" => Ok(SlowConsumerPolicy::Drop),
+            "coalesce" => Ok(SlowConsumerPolicy::Coalesce),
+            "disconnect" => Ok(SlowConsumerPolicy::Disconnect),
+            _ => Err(WebError::Value(format!("unsupported slow consumer policy {}", value))),
+        }
+    }
+}
+
+/// 单个会话的推送限制
+#[derive(Debug, Clone)]
+pub struct SessionLimits {
+    /// 已发送给会话 actor 但尚未写出的最大消息数
+    pub max_queue: usize,
+    /// 每秒最多推送的事件数, None 不限制
+    pub max_events_per_sec: Option<u64>,
+    pub policy: SlowConsumerPolicy,
+}
+
+impl SessionLimits {
+    /// 按 CFG 中的 WS_* 配置创建。 requested_rate 为客户端请求的速率, 不能超过服务端的限制
+    pub fn from_config(requested_rate: Option<u64>) -> Self {
+        let max_queue = CFG.get("WS_MAX_QUEUE").and_then(|v| v.parse().ok()).unwrap_or(1024);
+        let server_rate = CFG.get("WS_MAX_EVENTS_PER_SEC").and_then(|v| v.parse::<u64>().ok()).filter(|r| *r > 0);
+        let policy = CFG.get("WS_SLOW_CONSUMER_POLICY")
+            .and_then(|v| SlowConsumerPolicy::try_from(v.as_str()).ok())
+            .unwrap_or(SlowConsumerPolicy::Drop);
+
+        let max_events_per_sec = match (server_rate, requested_rate.filter(|r| *r > 0)) {
+            (Some(s), Some(r)) => Some(s.min(r)),
+            (s, r) => s.or(r),
+        };
+
+        SessionLimits { max_queue, max_events_per_sec, policy }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// 依次推送这些消息
+    Send(Vec<Frame>),
+    /// 本次不推送
+    Skip,
+    Disconnect,
+}
+
+/// 会话的推送控制: 发送队列深度限制、速率限制与慢消费者处理
+#[derive(Debug)]
+pub struct DeliveryControl {
+    limits: SessionLimits,
+    limiter: Option<RateLimiter>,
+    /// 丢弃通知与合并事件的编码
+    encoding: FrameEncoding,
+    /// 未通知客户端的丢弃数
+    dropped: u64,
+    /// Coalesce 模式下暂存的事件
+    coalesced: Vec<Frame>,
+}
+
+impl DeliveryControl {
+    pub fn new(limits: SessionLimits) -> Self {
+        DeliveryControl {
+            limiter: RateLimiter::new(limits.max_events_per_sec, None),
+            limits,
+            encoding: FrameEncoding::Json,
+            dropped: 0,
+            coalesced: vec![],
+        }
+    }
+
+    pub fn with_encoding(self, encoding: FrameEncoding) -> Self {
+        DeliveryControl { encoding, ..self }
+    }
+
+    /// pending: 会话当前的队列深度
+    pub fn offer(&mut self, frame: Frame, pending: usize) -> Delivery {
+        let over_rate = self.limiter.as_mut().map_or(false, |l| !l.try_acquire(frame.len() as u64));
+        if pending >= self.limits.max_queue || over_rate {
+            match self.limits.policy {
+                SlowConsumerPolicy::Disconnect => return Delivery::Disconnect,
+                SlowConsumerPolicy::Drop => self.dropped += 1,
+                SlowConsumerPolicy::Coalesce => {
+                    if self.coalesced.len() >= self.limits.max_queue {
+                        self.coalesced.remove(0);
+                        self.dropped += 1;
+                    }
+                    self.coalesced.push(frame);
+                },
+            }
+            return Delivery::Skip;
+        }
+
+        let mut frames = self.take_pending();
+        frames.push(frame);
+        Delivery::Send(frames)
+    }
+
+    /// 队列恢复时推送暂存的事件与丢弃通知, 由心跳定时调用
+    pub fn flush(&mut self, pending: usize) -> Vec<Frame> {
+        if pending >= self.limits.max_queue {
+            return vec![];
+        }
+
+        self.take_pending()
+    }
+
+    fn take_pending(&mut self) -> Vec<Frame> {
+        let mut frames = vec![];
+        if self.dropped > 0 {
+            frames.push(self.encoding.encode_dropped(self.dropped));
+            self.dropped = 0;
+        }
+        if !self.coalesced.is_empty() {
+            let coalesced = std::mem::take(&mut self.coalesced);
+            frames.push(self.encoding.encode_batch(coalesced));
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::wss::backpressure::{Delivery, DeliveryControl, SessionLimits, SlowConsumerPolicy};
+    use crate::wss::encoding::Frame;
+
+    fn text(s: &str) -> Frame {
+        Frame::Text(String::from(s))
+    }
+
+    fn control(policy: SlowConsumerPolicy) -> DeliveryControl {
+        DeliveryControl::new(SessionLimits { max_queue: 2, max_events_per_sec: None, policy })
+    }
+
+    #[test]
+    fn test_drop() {
+        let mut c = control(SlowConsumerPolicy::Drop);
+        assert_eq!(c.offer(text("1"), 0), Delivery::Send(vec![text("1")]));
+        assert_eq!(c.offer(text("2"), 2), Delivery::Skip);
+        assert_eq!(c.offer(text("3"), 2), Delivery::Skip);
+        assert_eq!(c.offer(text("4"), 1),
+                   Delivery::Send(vec![text(r#"{"notice":"dropped","count":2}"#), text("4")]));
+    }
+
+    #[test]
+    fn test_coalesce_and_disconnect() {
+        let mut c = control(SlowConsumerPolicy::Coalesce);
+        for i in 1..=3 {
+            assert_eq!(c.offer(Frame::Text(i.to_string()), 5), Delivery::Skip);
+        }
+        assert!(c.flush(2).is_empty());
+        assert_eq!(c.flush(0), vec![text(r#"{"notice":"dropped","count":1}"#), text("[2,3]")]);
+
+        let mut c = control(SlowConsumerPolicy::Disconnect);
+        assert_eq!(c.offer(text("1"), 2), Delivery::Disconnect);
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+
+use crate::stream::event_hub::StreamEvent;
+use crate::web_error::WebError;
+
+/// `Sec-WebSocket-Protocol` 中可协商的子协议
+pub const SUB_PROTOCOLS: [&str; 2] = ["binlog.msgpack", "binlog.json"];
+
+/// 推送事件的帧编码, 默认 JSON 文本帧
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameEncoding {
+    #[default]
+    Json,
+    /// MessagePack 二进制帧, 字段与 JSON 相同
+    MsgPack,
+}
+
+impl TryFrom<&str> for FrameEncoding {
+    type Error = WebError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().trim_start_matches("binlog.") {
+            "json" => Ok(FrameEncoding::Json),
+            "msgpack" => Ok(FrameEncoding::MsgPack),
+            _ => Err(WebError::Value(format!("unsupported encoding {}, expect json or msgpack", value))),
+        }
+    }
+}
+
+/// 发送给会话的一帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Frame {
+    pub fn len(&self) -> usize {
+        match self {
+            Frame::Text(t) => t.len(),
+            Frame::Binary(b) => b.len(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DroppedNotice {
+    notice: &'static str,
+    count: u64,
+}
+
+impl FrameEncoding {
+    pub fn encode_event(&self, event: &StreamEvent) -> Frame {
+        match self {
+            FrameEncoding::Json => Frame::Text(event.to_json()),
+            FrameEncoding::MsgPack => Frame::Binary(rmp_serde::to_vec_named(event).unwrap_or_default()),
+        }
+    }
+
+    /// 慢消费者被丢弃的事件数
+    pub fn encode_dropped(&self, count: u64) -> Frame {
+        let notice = DroppedNotice { notice: "dropped", count };
+        match self {
+            FrameEncoding::Json => Frame::Text(serde_json::to_string(&notice).unwrap_or_default()),
+            FrameEncoding::MsgPack => Frame::Binary(rmp_serde::to_vec_named(&notice).unwrap_or_default()),
+        }
+    }
+
+    /// 将已编码的多个事件合并为一个数组
+    pub fn encode_batch(&self, frames: Vec<Frame>) -> Frame {
+        match self {
+            FrameEncoding::Json => {
+                let items: Vec<String> = frames.into_iter()
+                    .filter_map(|f| match f { Frame::Text(t) => Some(t), Frame::Binary(_) => None })
+                    .collect();
+                Frame::Text(format!("[{}]", items.join(",")))
+            },
+            // msgpack 的数组为数组头加依次排列的元素, 可以直接拼接
+            FrameEncoding::MsgPack => {
+                let mut buf = vec![];
+                let _ = rmp::encode::write_array_len(&mut buf, frames.len() as u32);
+                for frame in frames {
+                    if let Frame::Binary(b) = frame {
+                        buf.extend_from_slice(&b);
+                    }
+                }
+                Frame::Binary(buf)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{json, Value};
+
+    use crate::stream::event_hub::StreamEvent;
+    use crate::wss::encoding::{Frame, FrameEncoding};
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(FrameEncoding::try_from("binlog.msgpack").unwrap(), FrameEncoding::MsgPack);
+        assert!(FrameEncoding::try_from("protobuf").is_err());
+
+        let event = StreamEvent { id: 1, namespace: String::from("default"), task: String::from("t1"),
+            event_type: String::from("QueryEvent"), event: json!("BEGIN") };
+        let frame = FrameEncoding::MsgPack.encode_event(&event);
+        let Frame::Binary(bytes) = frame.clone() else { panic!("expect binary frame") };
+        let value: Value = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(value, json!({"id": 1, "task": "t1", "type": "QueryEvent", "event": "BEGIN"}));
+
+        let Frame::Binary(batch) = FrameEncoding::MsgPack.encode_batch(vec![frame.clone(), frame]) else { panic!() };
+        let value: Value = rmp_serde::from_slice(&batch).unwrap();
+        assert_eq!(value.as_array().unwrap().len(), 2);
+
+        assert_eq!(FrameEncoding::Json.encode_dropped(3), Frame::Text(String::from(r#"{"notice":"dropped","count":3}"#)));
+    }
+}
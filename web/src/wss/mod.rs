@@ -4,3 +4,5 @@ pub mod strategy;
 pub mod session;
 pub mod wss_action_type;
 pub mod session_manager;
+pub mod backpressure;
+pub mod encoding;
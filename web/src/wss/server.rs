@@ -1,4 +1,5 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use actix::Message;
 
@@ -6,6 +7,10 @@ use actix::prelude::*;
 use actix_http::ws::{CloseCode, CloseReason};
 use actix_web_actors::ws;
 use tokio::runtime::Runtime;
+use crate::namespace::DEFAULT_NAMESPACE;
+use crate::stream::event_hub::StreamEvent;
+use crate::wss::backpressure::{Delivery, DeliveryControl, SessionLimits};
+use crate::wss::encoding::{Frame, FrameEncoding};
 use crate::web_error::{WebError, WResult};
 use crate::wss::event::WSEvent;
 use crate::wss::session_manager::SessionManager;
@@ -189,6 +194,11 @@ impl MyWebSocket {
             }
 
             ctx.ping(b"");
+
+            // 推送慢消费者暂存的事件
+            if let Some(context) = act.session_id.as_ref().and_then(|id| SessionManager::ws_get(id)) {
+                context.flush();
+            }
         });
     }
 
@@ -241,13 +251,60 @@ impl Handler<SendMessage> for MyWebSocket {
     }
 }
 
+/// 推送事件, 写出后减少会话的队列深度
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SendEvent {
+    frame: Frame,
+    pending: Arc<AtomicUsize>,
+}
+
+impl Handler<SendEvent> for MyWebSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: SendEvent, ctx: &mut Self::Context) -> Self::Result {
+        match msg.frame {
+            Frame::Text(text) => ctx.text(text),
+            Frame::Binary(bytes) => ctx.binary(bytes),
+        }
+        msg.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 服务端主动断开会话
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseSession(pub String);
+
+impl Handler<CloseSession> for MyWebSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseSession, ctx: &mut Self::Context) -> Self::Result {
+        self.ctx_close(ctx, Some(CloseReason::from((CloseCode::Policy, msg.0))));
+    }
+}
+
 #[derive(Debug)]
 pub struct WsContext {
     addr: Addr<MyWebSocket>,
 
     cid: String,
 
+    /// 会话所属的命名空间, 只接收该命名空间的事件
+    namespace: String,
+
     create_time: String,
+
+    /// 事件的帧编码, 握手时协商
+    encoding: FrameEncoding,
+
+    /// 最后推送给该会话的事件 id
+    last_offset: AtomicU64,
+
+    /// 已发送给 actor 但尚未写出的事件数
+    pending: Arc<AtomicUsize>,
+
+    delivery: Mutex<DeliveryControl>,
 }
 
 impl WsContext {
@@ -255,11 +312,82 @@ impl WsContext {
         WsContext {
             addr,
             cid,
+            namespace: String::from(DEFAULT_NAMESPACE),
             create_time: now,
+            encoding: FrameEncoding::Json,
+            last_offset: AtomicU64::new(0),
+            pending: Arc::new(AtomicUsize::new(0)),
+            delivery: Mutex::new(DeliveryControl::new(SessionLimits::from_config(None))),
+        }
+    }
+
+    pub fn with_limits(self, limits: SessionLimits) -> Self {
+        WsContext {
+            delivery: Mutex::new(DeliveryControl::new(limits).with_encoding(self.encoding)),
+            ..self
+        }
+    }
+
+    pub fn with_encoding(self, encoding: FrameEncoding) -> Self {
+        let delivery = self.delivery.into_inner().unwrap().with_encoding(encoding);
+        WsContext {
+            encoding,
+            delivery: Mutex::new(delivery),
+            ..self
+        }
+    }
+
+    pub fn with_namespace(self, namespace: &str) -> Self {
+        WsContext {
+            namespace: namespace.to_string(),
+            ..self
         }
     }
 
+    pub fn get_namespace(&self) -> &str {
+        &self.namespace
+    }
+
     pub fn do_send(&self, msg: &str) {
         self.addr.do_send(SendMessage(String::from(msg)));
     }
+
+    /// 推送事件并记录 offset, 已推送过的事件(id 不大于 last_offset)不再重复推送
+    pub fn send_event(&self, event: &StreamEvent) {
+        if self.last_offset.fetch_max(event.id, Ordering::SeqCst) >= event.id {
+            return;
+        }
+
+        let pending = self.pending.load(Ordering::SeqCst);
+        let delivery = self.delivery.lock().unwrap().offer(self.encoding.encode_event(event), pending);
+        match delivery {
+            Delivery::Send(frames) => self.send_frames(frames),
+            Delivery::Skip => {},
+            Delivery::Disconnect => {
+                log::warn!("session {} is too slow, disconnect", self.cid);
+                self.addr.do_send(CloseSession(String::from("slow consumer")));
+            },
+        }
+    }
+
+    /// 队列恢复后推送暂存的事件
+    pub fn flush(&self) {
+        let frames = self.delivery.lock().unwrap().flush(self.pending.load(Ordering::SeqCst));
+        self.send_frames(frames);
+    }
+
+    fn send_frames(&self, frames: Vec<Frame>) {
+        for frame in frames {
+            self.pending.fetch_add(1, Ordering::SeqCst);
+            self.addr.do_send(SendEvent { frame, pending: self.pending.clone() });
+        }
+    }
+
+    pub fn last_offset(&self) -> u64 {
+        self.last_offset.load(Ordering::SeqCst)
+    }
+
+    pub fn set_last_offset(&self, offset: u64) {
+        self.last_offset.store(offset, Ordering::SeqCst);
+    }
 }
\ No newline at end of file
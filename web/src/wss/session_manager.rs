@@ -1,11 +1,21 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use lazy_static::lazy_static;
+use crate::stream::event_hub::{StreamEvent, EVENT_HUB};
 use crate::wss::server::WsContext;
 
+/// 已断开会话的 offset 保留时长
+const OFFSET_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// 最多保留的已断开会话数, 超出时淘汰最早断开的
+const MAX_OFFSETS: usize = 10000;
+
 // 使用 Mutex/RwLock 来确保 HashMap 的线程安全
 lazy_static! {
     static ref WS: RwLock<HashMap<String, Arc<WsContext>>> = RwLock::new(HashMap::new());
+
+    /// 已断开的会话最后推送的事件 id 与断开时间, 重连时从其后续传
+    static ref OFFSETS: RwLock<HashMap<String, (u64, Instant)>> = RwLock::new(HashMap::new());
 }
 
 pub struct SessionManager {
@@ -21,6 +31,33 @@ impl SessionManager {
         map.insert(key, Arc::new(value));
     }
 
+    /// 添加会话, 并补发同一命名空间中 last_offset 之后的事件, 内存中已淘汰的事件从中继日志中补发。
+    /// last_offset 为 None 时使用该会话上次断开时的 offset, 都没有时只推送实时事件
+    pub fn ws_resume(key: String, value: WsContext, last_offset: Option<u64>) {
+        let saved = OFFSETS.write().unwrap().remove(&key).map(|(offset, _)| offset);
+        let last_offset = last_offset.or(saved);
+        let namespace = value.get_namespace().to_string();
+
+        // 在 EVENT_HUB 的锁内补发并加入会话, 期间没有新事件广播, 补发与实时推送之间不会遗漏或乱序
+        EVENT_HUB.resume(&namespace, last_offset, |last_id, missed, lost| {
+            let mut map = WS.write().unwrap();
+            match last_offset {
+                Some(offset) => {
+                    if let Some((from, to)) = lost {
+                        value.do_send(&format!("events {}..{} are no longer retained", from, to));
+                    }
+                    value.set_last_offset(offset);
+                    for event in &missed {
+                        value.send_event(event);
+                    }
+                }
+                None => value.set_last_offset(last_id),
+            }
+
+            map.insert(key, Arc::new(value));
+        });
+    }
+
     /// 读取元素
     pub fn ws_get(key: &str) -> Option<Arc<WsContext>> {
         let guard = WS.read().unwrap();
@@ -38,6 +75,79 @@ impl SessionManager {
     pub fn ws_remove(key: &str) -> Option<Arc<WsContext>> {
         let mut map = WS.write().unwrap();
 
-        map.remove(key)
+        let context = map.remove(key);
+        if let Some(ctx) = context.as_ref() {
+            Self::save_offset(&mut OFFSETS.write().unwrap(), key, ctx.last_offset(), Instant::now());
+        }
+
+        context
+    }
+
+    /// 命名空间中在线的会话数
+    pub fn ws_count(namespace: &str) -> usize {
+        WS.read().unwrap().values().filter(|ctx| ctx.get_namespace() == namespace).count()
+    }
+
+    /// 会话最后推送的事件 id
+    pub fn ws_offset(key: &str) -> Option<u64> {
+        match Self::ws_get(key) {
+            Some(ctx) => Some(ctx.last_offset()),
+            None => OFFSETS.read().unwrap().get(key).map(|(offset, _)| *offset),
+        }
+    }
+
+    /// 记录断开会话的 offset, 淘汰过期的与超出数量上限的
+    fn save_offset(offsets: &mut HashMap<String, (u64, Instant)>, key: &str, offset: u64, now: Instant) {
+        offsets.retain(|_, (_, at)| now.duration_since(*at) < OFFSET_TTL);
+        while offsets.len() >= MAX_OFFSETS {
+            let oldest = offsets.iter().min_by_key(|(_, (_, at))| *at).map(|(k, _)| k.clone());
+            match oldest {
+                Some(k) => offsets.remove(&k),
+                None => break,
+            };
+        }
+        offsets.insert(key.to_string(), (offset, now));
     }
-}
\ No newline at end of file
+
+    /// 向全部会话推送消息
+    pub fn ws_broadcast(msg: &str) {
+        let guard = WS.read().unwrap();
+        for ctx in guard.values() {
+            ctx.do_send(msg);
+        }
+    }
+
+    /// 向事件所属命名空间的会话推送事件, 并记录各会话的 offset
+    pub fn ws_broadcast_event(event: &StreamEvent) {
+        let guard = WS.read().unwrap();
+        for ctx in guard.values().filter(|ctx| ctx.get_namespace() == event.namespace) {
+            ctx.send_event(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use crate::wss::session_manager::{SessionManager, MAX_OFFSETS, OFFSET_TTL};
+
+    #[test]
+    fn test_save_offset() {
+        let mut offsets = HashMap::new();
+        let start = Instant::now();
+        for i in 0..MAX_OFFSETS + 10 {
+            SessionManager::save_offset(&mut offsets, &format!("s{}", i), i as u64, start + Duration::from_millis(i as u64));
+        }
+        // 超出上限时淘汰最早断开的
+        assert_eq!(offsets.len(), MAX_OFFSETS);
+        assert!(!offsets.contains_key("s0"));
+        assert_eq!(offsets.get(&format!("s{}", MAX_OFFSETS + 9)).unwrap().0, (MAX_OFFSETS + 9) as u64);
+
+        // 过期的被清理
+        SessionManager::save_offset(&mut offsets, "late", 1, start + OFFSET_TTL + Duration::from_secs(60));
+        assert_eq!(offsets.len(), 1);
+        assert!(offsets.contains_key("late"));
+    }
+}